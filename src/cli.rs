@@ -1,5 +1,14 @@
 use clap::{Parser, arg, command};
 
+use crate::fs_service::{
+    config::{
+        DEFAULT_DISK_SPACE_CHECK_THRESHOLD, DEFAULT_MAX_DELETE_ENTRIES, DEFAULT_MAX_IMAGE_PIXELS,
+        DEFAULT_MAX_OPEN_FILES, DEFAULT_MAX_PATTERN_LENGTH, DEFAULT_REGEX_SIZE_LIMIT,
+        DEFAULT_SEARCH_TIMEOUT, DEFAULT_STREAM_BUFFER_SIZE,
+    },
+    utils::SizeUnits,
+};
+
 #[derive(Parser, Debug)]
 #[command(name =  env!("CARGO_PKG_NAME"))]
 #[command(version = env!("CARGO_PKG_VERSION"))]
@@ -26,6 +35,220 @@ pub struct CommandArguments {
     )]
     pub enable_roots: bool,
 
+    #[arg(
+        short = 's',
+        long,
+        help = "Starts the server with the valid subset of allowed directories when some of them are invalid, instead of failing. Defaults to disabled.\nRejected entries and the reason they were rejected are reported by the `list_allowed_directories` tool.",
+        action = clap::ArgAction::SetTrue,
+        value_parser = clap::value_parser!(bool),
+        env = "SKIP_INVALID_DIRECTORIES"
+    )]
+    pub skip_invalid_directories: bool,
+
+    #[arg(
+        long,
+        help = "Buffer size (in bytes) used for streaming file reads. Larger values may improve performance on high-latency storage. Must be at least 512.",
+        default_value_t = DEFAULT_STREAM_BUFFER_SIZE,
+        env = "STREAM_BUFFER_SIZE"
+    )]
+    pub stream_buffer_size: usize,
+
+    #[arg(
+        long,
+        help = "Opens files for writing with O_NOFOLLOW, so writes to a path that is itself a symlink are refused by the OS. Defaults to disabled. No effect on non-Unix platforms.",
+        action = clap::ArgAction::SetTrue,
+        value_parser = clap::value_parser!(bool),
+        env = "NO_FOLLOW_WRITES"
+    )]
+    pub no_follow_writes: bool,
+
+    #[arg(
+        long,
+        help = "Opens files for reading with O_NOATIME, so reads do not update access times. Defaults to disabled.\nOnly supported on Linux, and only for files owned by the server's user; other reads fall back to a regular open.",
+        action = clap::ArgAction::SetTrue,
+        value_parser = clap::value_parser!(bool),
+        env = "NO_ATIME_READS"
+    )]
+    pub no_atime_reads: bool,
+
+    #[arg(
+        long,
+        value_name = "EXT",
+        help = "File extensions (e.g. 'md,txt,rs') that write operations may create or modify. Can be repeated or comma separated.\nWhen set, writes to any other extension, or to files without an extension, are rejected. Defaults to allowing every extension.",
+        value_delimiter = ',',
+        env = "WRITABLE_EXTENSIONS"
+    )]
+    pub writable_extensions: Vec<String>,
+
+    #[arg(
+        long,
+        value_name = "EXT",
+        help = "File extensions (e.g. 'sh,exe') that write operations may never create or modify. Can be repeated or comma separated. Takes precedence over --writable-extensions.",
+        value_delimiter = ',',
+        env = "BLOCKED_EXTENSIONS"
+    )]
+    pub blocked_extensions: Vec<String>,
+
+    #[arg(
+        long,
+        help = "Maximum number of pixels (width x height) of an image decoded for resizing. Larger images are rejected before decoding.",
+        default_value_t = DEFAULT_MAX_IMAGE_PIXELS,
+        env = "MAX_IMAGE_PIXELS"
+    )]
+    pub max_image_pixels: u64,
+
+    #[arg(
+        long,
+        help = "Maximum length (in characters) of search patterns. Longer patterns are rejected.",
+        default_value_t = DEFAULT_MAX_PATTERN_LENGTH,
+        env = "MAX_PATTERN_LENGTH"
+    )]
+    pub max_pattern_length: usize,
+
+    #[arg(
+        long,
+        help = "Maximum compiled size (in bytes) of a search regex and of its DFA cache. Patterns exceeding it are rejected.",
+        default_value_t = DEFAULT_REGEX_SIZE_LIMIT,
+        env = "REGEX_SIZE_LIMIT"
+    )]
+    pub regex_size_limit: usize,
+
+    #[arg(
+        long,
+        help = "Time budget (in seconds) of a content search. When it runs out, the matches found so far are returned. 0 disables the limit.",
+        default_value_t = DEFAULT_SEARCH_TIMEOUT.as_secs(),
+        env = "SEARCH_TIMEOUT"
+    )]
+    pub search_timeout: u64,
+
+    #[arg(
+        long,
+        help = "Maximum number of bytes all write tools may write during the session. Writes that would exceed it fail before touching the disk. 0 means unlimited.",
+        default_value_t = 0,
+        env = "MCP_MAX_SESSION_WRITE_BYTES"
+    )]
+    pub max_session_write_bytes: u64,
+
+    #[arg(
+        long,
+        help = "Maximum number of bytes that may be removed by overwriting or replacing existing files during the session. 0 means unlimited.",
+        default_value_t = 0,
+        env = "MCP_MAX_SESSION_DELETE_BYTES"
+    )]
+    pub max_session_delete_bytes: u64,
+
+    #[arg(
+        long,
+        help = "Maximum number of files and directories a recursive delete may remove. Deleting a larger tree is refused unless the call passes `force: true`. 0 means unlimited.",
+        default_value_t = DEFAULT_MAX_DELETE_ENTRIES,
+        env = "MCP_MAX_DELETE_ENTRIES"
+    )]
+    pub max_delete_entries: u64,
+
+    #[arg(
+        long,
+        help = "Size in bytes from which writes, archive creation and extraction first check the free space of the destination's filesystem, and are refused when it is insufficient. 0 checks every write.",
+        default_value_t = DEFAULT_DISK_SPACE_CHECK_THRESHOLD,
+        env = "MCP_DISK_SPACE_CHECK_THRESHOLD"
+    )]
+    pub disk_space_check_threshold: u64,
+
+    #[arg(
+        long,
+        help = "Maximum size in bytes of the files read, searched or analyzed as text. Larger files are reported as too large by every text tool. 0 means unlimited.",
+        default_value_t = 0,
+        env = "MCP_MAX_TEXT_FILE_BYTES"
+    )]
+    pub max_text_file_bytes: u64,
+
+    #[arg(
+        long,
+        help = "Maximum number of files opened or queried at once by the parallel parts of recursive walks, such as directory sizes and duplicate detection. Lower it when large trees fail with \"too many open files\". 0 means unlimited.",
+        default_value_t = DEFAULT_MAX_OPEN_FILES,
+        env = "MCP_MAX_OPEN_FILES"
+    )]
+    pub max_open_files: usize,
+
+    #[arg(
+        long,
+        help = "Appends a footer with elapsed time, bytes read/written, entries visited and fast paths used to every tool result. Defaults to disabled.\nClients can also request the footer for a single call by passing `include_timings: true`.",
+        action = clap::ArgAction::SetTrue,
+        value_parser = clap::value_parser!(bool),
+        env = "MCP_RESULT_TIMINGS"
+    )]
+    pub result_timings: bool,
+
+    #[arg(
+        long,
+        value_name = "FILE",
+        help = "File the performance counters reported by `get_performance_counters` are written to, as a JSON object, when the server shuts down on SIGTERM or Ctrl+C.",
+        env = "MCP_METRICS_FILE"
+    )]
+    pub metrics_file: Option<String>,
+
+    #[arg(
+        long,
+        help = "Redacts well-known secrets (AWS keys, GitHub tokens, private keys, random values assigned to token/password keys) in file reads and content search results. Defaults to disabled.\nFiles on disk are never modified. Clients can override the setting for a single call by passing `redact_secrets`.",
+        action = clap::ArgAction::SetTrue,
+        value_parser = clap::value_parser!(bool),
+        env = "MCP_REDACT_SECRETS"
+    )]
+    pub redact_secrets: bool,
+
+    #[arg(
+        long,
+        value_name = "GLOB",
+        help = "Glob patterns, matched against the full path, of files that are never redacted (e.g. '**/fixtures/**'). Can be repeated or comma separated.",
+        value_delimiter = ',',
+        env = "MCP_REDACT_EXCLUDE"
+    )]
+    pub redact_exclude: Vec<String>,
+
+    #[arg(
+        long,
+        value_enum,
+        help = "Default units of human-readable sizes: 'binary' (1024-based KiB, MiB), 'decimal' (1000-based KB, MB) or 'compact' (1024-based, labelled KB, MB as in older versions).\nClients can override the units for a single call by passing `size_units`.",
+        default_value_t = SizeUnits::Binary,
+        env = "MCP_SIZE_UNITS"
+    )]
+    pub size_units: SizeUnits,
+
+    #[arg(
+        long,
+        help = "Groups the digits of sizes and counts in human-readable output by thousands (e.g. '12,345 bytes'). Defaults to disabled.",
+        action = clap::ArgAction::SetTrue,
+        value_parser = clap::value_parser!(bool),
+        env = "MCP_THOUSANDS_SEPARATORS"
+    )]
+    pub thousands_separators: bool,
+
+    #[arg(
+        long,
+        help = "Disables the 'did you mean' suggestions of similarly named sibling entries that are added to errors for paths that do not exist.",
+        action = clap::ArgAction::SetTrue,
+        value_parser = clap::value_parser!(bool),
+        env = "MCP_NO_PATH_SUGGESTIONS"
+    )]
+    pub no_path_suggestions: bool,
+
+    #[arg(
+        long,
+        help = "Rejects tool calls with arguments the tool does not know, such as misspelled ones, listing the accepted arguments. Set to false to run such calls anyway, with a warning naming the ignored arguments at the end of the result.",
+        default_value_t = true,
+        action = clap::ArgAction::Set,
+        value_parser = clap::value_parser!(bool),
+        env = "MCP_STRICT_PARAMS"
+    )]
+    pub strict_params: bool,
+
+    #[arg(
+        long,
+        value_name = "FILE",
+        help = "TOML configuration file. Its [tool_defaults.<tool>] tables set server-wide defaults of optional tool arguments (e.g. 'max_depth = 3' under [tool_defaults.directory_tree]).\nValues passed by clients always win. Defaults for unknown tools or arguments are ignored with a warning.",
+        env = "MCP_CONFIG"
+    )]
+    pub config: Option<String>,
+
     #[arg(
         help = "List of directories that are permitted for the operation. It is required when 'enable-roots' is not provided OR client does not support Roots.",
         long_help = concat!("Provide a space-separated list of directories that are permitted for the operation.\nThis list allows multiple directories to be provided.\n\nExample:  ", env!("CARGO_PKG_NAME"), " /path/to/dir1 /path/to/dir2 /path/to/dir3"),
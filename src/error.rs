@@ -2,6 +2,8 @@ use async_zip::error::ZipError;
 use rust_mcp_sdk::schema::{RpcError, schema_utils::SdkError};
 use rust_mcp_sdk::{TransportError, error::McpSdkError};
 
+use crate::fs_service::{text_probe::FileClass, utils::edit_distance, write_budget::BudgetCounter};
+use std::path::PathBuf;
 use thiserror::Error;
 use tokio::io;
 
@@ -15,6 +17,8 @@ pub enum ServiceError {
     NoWriteAccess,
     #[error("{0}")]
     FromString(String),
+    #[error("One or more allowed directories are invalid:\n{0}")]
+    InvalidAllowedDirectories(String),
     #[error("{0}")]
     TransportError(#[from] TransportError),
     #[error("{0}")]
@@ -28,9 +32,15 @@ pub enum ServiceError {
     #[error("{0}")]
     ContentSearchError(#[from] grep::regex::Error),
     #[error("{0}")]
+    RegexError(#[from] regex::Error),
+    #[error("{0}")]
     McpSdkError(#[from] McpSdkError),
     #[error("{0}")]
     ZipError(#[from] ZipError),
+    #[error("{0}")]
+    ImageError(#[from] image::ImageError),
+    #[error("{0}")]
+    CsvError(#[from] csv::Error),
     // #[error("{0}")]
     // GlobPatternError(#[from] PatternError),
     #[error("File size exceeds the maximum allowed limit of {0} bytes")]
@@ -39,4 +49,68 @@ pub enum ServiceError {
     FileTooSmall(usize),
     #[error("The file is either not an image/audio type or is unsupported (mime:{0}).")]
     InvalidMediaFile(String),
+    #[error("Cannot read '{}' as text: the file is {class}.", .path.display())]
+    NotText { path: PathBuf, class: FileClass },
+    #[error("{0} is not a file")]
+    NotAFile(String),
+    #[error("{0} is not a directory")]
+    NotADirectory(String),
+    #[error("Server shutting down")]
+    ShuttingDown,
+    #[error(
+        "Session {counter} budget exhausted: {used} bytes used, the operation needs {requested} more, and the limit is {limit} bytes."
+    )]
+    WriteBudgetExhausted {
+        counter: BudgetCounter,
+        used: u64,
+        requested: u64,
+        limit: u64,
+    },
+    #[error(
+        "Insufficient disk space on the filesystem of '{}': need {needed} bytes, have {available} bytes.",
+        .path.display()
+    )]
+    InsufficientDiskSpace {
+        path: PathBuf,
+        needed: u64,
+        available: u64,
+    },
+    #[error("No space left on the device while writing '{}'. {cleanup}", .path.display())]
+    DiskFull { path: PathBuf, cleanup: String },
+    #[error("Invalid value {value} for field \"{field}\", expected one of [{}]", .expected.join(", "))]
+    InvalidEnumValue {
+        field: String,
+        value: String,
+        expected: Vec<String>,
+    },
+    #[error("Unknown {} for tool '{tool}': {}. Accepted arguments: {}.", if .unknown.len() == 1 { "argument" } else { "arguments" }, describe_unknown_arguments(.unknown, .expected), .expected.join(", "))]
+    UnknownArguments {
+        tool: String,
+        unknown: Vec<String>,
+        expected: Vec<String>,
+    },
+}
+
+/// Lists the `unknown` arguments of a call, each with the `expected` argument it is likely a
+/// misspelling of, e.g. `recurse (did you mean 'recursive'?)`.
+pub fn describe_unknown_arguments(unknown: &[String], expected: &[String]) -> String {
+    unknown
+        .iter()
+        .map(|argument| {
+            let closest = expected
+                .iter()
+                .map(|name| (edit_distance(argument, name), name))
+                .filter(|(distance, name)| {
+                    *distance <= 2
+                        || name.starts_with(argument.as_str())
+                        || argument.starts_with(name.as_str())
+                })
+                .min();
+            match closest {
+                Some((_, name)) => format!("'{argument}' (did you mean '{name}'?)"),
+                None => format!("'{argument}'"),
+            }
+        })
+        .collect::<Vec<_>>()
+        .join(", ")
 }
@@ -1,24 +1,102 @@
+pub mod archive_verification;
+pub mod bulk_insert;
+pub mod code_definitions;
+pub mod comment_stripping;
+pub mod config;
+pub mod context_pack;
+pub mod deletion;
+pub mod descriptor_limit;
+pub mod directory_hash;
+pub mod disk_space;
+pub mod file_conversion;
 pub mod file_info;
+pub mod file_locks;
+pub mod flatten;
+pub mod generated_files;
+pub mod git_status;
+pub mod image_scaling;
+pub mod instrumentation;
+pub mod line_sorting;
+pub mod name_collisions;
+pub mod open_files;
+pub mod pattern_test;
+pub mod prune_directories;
+pub mod read_cache;
+pub mod redaction;
+pub mod rust_references;
+pub mod rust_signatures;
+pub mod shutdown;
+pub mod structured_edit;
+pub mod tabular;
+pub mod telemetry;
+pub mod text_probe;
 pub mod utils;
+pub mod write_budget;
 use crate::{
     error::{ServiceError, ServiceResult},
     fs_service::utils::is_system_metadata_file,
-    tools::EditOperation,
+    tools::{
+        CommentStyle, EditOperation, Indentation, LineEditKind, LineEditOperation, LineEnding,
+        PatternMode, PreconditionKind, StructuredEditOperation, StructuredFormat, SymlinkPolicy,
+        TargetEncoding, WritePrecondition,
+    },
 };
+use archive_verification::{ArchiveVerification, EntryFailure};
 use async_zip::tokio::{read::seek::ZipFileReader, write::ZipFileWriter};
 use base64::{engine::general_purpose, write::EncoderWriter};
+use bulk_insert::{
+    BulkInsertReport, FileInsertion, GlobPreview, InsertAnchor, InsertStatus, insert_at_anchor,
+    matches_file_glob, write_atomically,
+};
+use code_definitions::{
+    BlockStyle, CodeDefinition, DefinitionSpan, extract_definitions, find_enclosing_block,
+    list_definitions, list_definitions_with_imports,
+};
+use comment_stripping::{CommentSyntax, lines_inside_strings};
+use config::PerformanceConfig;
+use context_pack::{ContextPack, ManifestEntry, PackSection, parse_manifest, slice_lines};
+use deletion::{DeleteOptions, DeleteReport, execute_delete, plan_delete};
+use descriptor_limit::DescriptorLimit;
+use directory_hash::{DirectoryHash, hash_directory};
+use disk_space::{SpaceProvider, SystemSpace, is_storage_full};
+use file_conversion::{
+    ConversionReport, ConversionStep, MAX_INDENT_WIDTH, convert_indentation, convert_line_endings,
+    strip_trailing_whitespace,
+};
 use file_info::FileInfo;
+use file_locks::{FileLockReport, probe_file_lock};
+use flatten::{FlattenOptions, FlattenReport, execute_flatten, plan_flatten};
 use futures::{StreamExt, stream};
+use generated_files::{GENERATED_SAMPLE_SIZE, GeneratedFile, classify_sample};
+use git_status::{GitStatusMap, load_git_status};
 use glob_match::glob_match;
 use grep::{
     matcher::{Match, Matcher},
-    regex::RegexMatcherBuilder,
+    regex::{RegexMatcher, RegexMatcherBuilder},
     searcher::{BinaryDetection, Searcher, sinks::UTF8},
 };
+use image_scaling::{ScaledImage, downscale_image, scalable_format};
+use instrumentation::{
+    FAST_PATH_CONCURRENT, FAST_PATH_PARALLEL, OperationStats, record_bytes_read,
+    record_bytes_written, record_entries_visited, record_fast_path,
+};
+use line_sorting::{SortOptions, sort_lines};
+use name_collisions::{NameCollision, find_name_collision};
+use open_files::{ClosedFile, OpenFiles, OpenedFile};
+use pattern_test::{PatternTest, glob_syntax_error};
+use prune_directories::{PruneOptions, PrunePlan, execute_prune, plan_prune};
 use rayon::iter::{IntoParallelIterator, ParallelBridge, ParallelIterator};
+use read_cache::{DedupedRead, ReadCache};
+use redaction::{RedactionConfig, redact_secrets};
+use regex::{RegexSet, RegexSetBuilder};
 use rust_mcp_sdk::schema::RpcError;
+use rust_references::{
+    ReferenceUpdate, ReferenceUpdatePlan, add_mod_declaration, crate_src_dir, module_path,
+    parent_module_file, remove_mod_declaration, rewrite_crate_paths,
+};
 use serde_json::{Value, json};
 use sha2::{Digest, Sha256};
+use shutdown::{OperationGuard, OperationTracker, ShutdownResult};
 use similar::TextDiff;
 use std::{
     collections::{HashMap, HashSet},
@@ -27,22 +105,41 @@ use std::{
     io::{SeekFrom, Write},
     path::{Path, PathBuf},
     sync::Arc,
+    time::{Duration, Instant, SystemTime},
+};
+use structured_edit::apply_structured_edits;
+use tabular::{
+    DELIMITER_SAMPLE_SIZE, TabularOptions, TabularPreview, detect_delimiter, preview_tabular,
 };
+use telemetry::{Counter, Telemetry};
+use text_probe::{FileClass, classify, classify_bytes, decode_text};
 use tokio::{
     fs::{File, metadata},
-    io::{AsyncBufReadExt, AsyncReadExt, AsyncSeekExt, AsyncWriteExt, BufReader},
-    sync::RwLock,
+    io::{AsyncBufReadExt, AsyncRead, AsyncReadExt, AsyncSeekExt, AsyncWriteExt, BufReader},
+    sync::{RwLock, watch},
 };
-use tokio_util::compat::{FuturesAsyncReadCompatExt, TokioAsyncReadCompatExt};
+use tokio_util::{
+    compat::{FuturesAsyncReadCompatExt, TokioAsyncReadCompatExt},
+    sync::CancellationToken,
+};
+use unicode_normalization::UnicodeNormalization;
 use utils::{
-    contains_symlink, expand_home, format_bytes, normalize_line_endings, normalize_path,
-    write_zip_entry,
+    ByteFormat, DeadlineReader, allocated_size, archive_entry_path, contains_symlink,
+    edit_distance, exclude_glob, expand_home, format_bytes, is_zip_symlink, name_glob,
+    normalize_line_endings, normalize_path, parse_file_uri, resolve_archive_link, write_zip_entry,
 };
 use walkdir::WalkDir;
+use write_budget::{SessionWriteUsage, WriteBudget};
 
 const SNIPPET_MAX_LENGTH: usize = 200;
 const SNIPPET_BACKWARD_CHARS: usize = 30;
 const MAX_CONCURRENT_FILE_READ: usize = 5;
+/// Number of entry metadata lookups in flight while listing a directory.
+const MAX_CONCURRENT_METADATA: usize = 32;
+/// Longest time a file is followed for appended lines.
+pub const MAX_FOLLOW_DURATION: Duration = Duration::from_secs(60);
+/// How often a followed file is checked for appended content.
+const FOLLOW_POLL_INTERVAL: Duration = Duration::from_millis(100);
 
 #[cfg(windows)]
 pub const OS_LINE_ENDING: &str = "\r\n";
@@ -53,6 +150,111 @@ type PathResultList = Vec<Result<PathBuf, ServiceError>>;
 
 pub struct FileSystemService {
     allowed_path: RwLock<Arc<Vec<PathBuf>>>,
+    /// Canonical paths of the configured allowed directories that were given as a symlink.
+    symlinked_directories: HashSet<PathBuf>,
+    rejected_directories: Vec<DirectoryIssue>,
+    directory_warnings: Vec<DirectoryIssue>,
+    performance_config: PerformanceConfig,
+    redaction_config: RedactionConfig,
+    byte_format: ByteFormat,
+    path_suggestions: bool,
+    operations: OperationTracker,
+    read_cache: ReadCache,
+    open_files: OpenFiles,
+    write_budget: WriteBudget,
+    space_provider: Arc<dyn SpaceProvider>,
+    telemetry: Telemetry,
+    /// File the performance counters are written to on shutdown.
+    metrics_file: Option<PathBuf>,
+    descriptor_limit: DescriptorLimit,
+}
+
+/// Parent directories with more entries than this are not scanned for path suggestions.
+pub const MAX_SUGGESTION_SCAN_ENTRIES: usize = 1000;
+/// Maximum number of sibling names suggested for a path that was not found.
+pub const MAX_PATH_SUGGESTIONS: usize = 10;
+
+/// Determines how `FileSystemService` handles invalid entries in the configured allowed directories.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum InvalidDirectoryPolicy {
+    /// Fail with an error listing every invalid entry.
+    #[default]
+    Fail,
+    /// Start with the valid subset and keep track of the rejected entries.
+    Skip,
+}
+
+/// Describes a problem (or a notable adjustment) found for a configured allowed directory entry.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DirectoryIssue {
+    /// The entry as it was provided in the configuration.
+    pub entry: String,
+    /// Human readable explanation of the issue.
+    pub reason: String,
+}
+
+impl std::fmt::Display for DirectoryIssue {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}: {}", self.entry, self.reason)
+    }
+}
+
+/// A path that passed allowed-directory validation.
+///
+/// Dereferences to [`Path`], so it can be passed wherever a path is expected. Validators that
+/// had to stat the path (e.g. [`FileSystemService::validate_existing_file`]) keep the metadata
+/// they fetched, so callers don't need to query it again.
+#[derive(Debug, Clone)]
+pub struct ValidatedPath {
+    path: PathBuf,
+    metadata: Option<fs::Metadata>,
+}
+
+impl ValidatedPath {
+    pub fn as_path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Metadata fetched during validation, if the validator needed it.
+    pub fn metadata(&self) -> Option<&fs::Metadata> {
+        self.metadata.as_ref()
+    }
+
+    pub fn into_path_buf(self) -> PathBuf {
+        self.path
+    }
+}
+
+impl std::ops::Deref for ValidatedPath {
+    type Target = Path;
+
+    fn deref(&self) -> &Path {
+        &self.path
+    }
+}
+
+impl AsRef<Path> for ValidatedPath {
+    fn as_ref(&self) -> &Path {
+        &self.path
+    }
+}
+
+impl From<ValidatedPath> for PathBuf {
+    fn from(value: ValidatedPath) -> Self {
+        value.path
+    }
+}
+
+impl PartialEq<PathBuf> for ValidatedPath {
+    fn eq(&self, other: &PathBuf) -> bool {
+        &self.path == other
+    }
+}
+
+impl PartialEq<Path> for ValidatedPath {
+    fn eq(&self, other: &Path) -> bool {
+        self.path == other
+    }
 }
 
 /// Represents a single match found in a file's content.
@@ -63,7 +265,86 @@ pub struct ContentMatchResult {
     pub start_pos: usize,
     /// The line of text containing the match.
     /// If the line exceeds 255 characters (excluding the search term), only a truncated portion will be shown.
+    /// For a group of merged adjacent matches, the snippets of all lines separated by newlines.
     pub line_text: String,
+    /// The last line covered by this match (1-based). Equals `line_number` unless adjacent
+    /// matches were merged into one group.
+    pub end_line_number: u64,
+    /// Starting lines of later groups in the same file whose snippets were identical to this one
+    /// and were removed by deduplication.
+    pub duplicate_lines: Vec<u64>,
+}
+
+impl ContentMatchResult {
+    /// Merges matches on consecutive lines into a single group and removes groups whose
+    /// snippet text repeats an earlier group, recording their lines in `duplicate_lines`.
+    /// Expects `matches` in line order.
+    pub fn dedupe(matches: Vec<ContentMatchResult>) -> Vec<ContentMatchResult> {
+        let mut groups: Vec<ContentMatchResult> = Vec::with_capacity(matches.len());
+        for m in matches {
+            match groups.last_mut() {
+                Some(group) if m.line_number <= group.end_line_number + 1 => {
+                    if m.line_number > group.end_line_number {
+                        group.line_text.push('\n');
+                        group.line_text.push_str(&m.line_text);
+                        group.end_line_number = m.end_line_number;
+                    }
+                }
+                _ => groups.push(m),
+            }
+        }
+
+        let mut deduped: Vec<ContentMatchResult> = Vec::with_capacity(groups.len());
+        for group in groups {
+            match deduped
+                .iter_mut()
+                .find(|kept| kept.line_text == group.line_text)
+            {
+                Some(kept) => kept.duplicate_lines.push(group.line_number),
+                None => deduped.push(group),
+            }
+        }
+        deduped
+    }
+}
+
+/// The outcome of a content search over several files.
+#[derive(Debug, Clone, Default)]
+pub struct ContentSearchResults {
+    pub files: Vec<FileSearchResult>,
+    /// The search timeout ran out before every file was scanned; `files` holds the matches
+    /// found until then.
+    pub timed_out: bool,
+    /// Number of matching lines for each pattern of the [`ContentQuery`], in order. A line
+    /// matching several patterns counts for each of them.
+    pub pattern_matches: Vec<u64>,
+}
+
+/// What a content search looks for: lines matching any of `patterns`, except those matching
+/// one of `exclude_in_line`. Both are literal text or regexes, per
+/// [`ContentSearchOptions::is_regex`].
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ContentQuery {
+    pub patterns: Vec<String>,
+    pub exclude_in_line: Vec<String>,
+}
+
+impl From<&str> for ContentQuery {
+    fn from(query: &str) -> Self {
+        Self {
+            patterns: vec![query.to_string()],
+            exclude_in_line: vec![],
+        }
+    }
+}
+
+/// A compiled content query. `matcher` finds the lines matching any pattern in one pass; the
+/// sets attribute those lines to the individual patterns and drop excluded lines.
+struct ContentMatcher {
+    matcher: RegexMatcher,
+    /// Present when there are several patterns.
+    patterns: Option<RegexSet>,
+    exclusions: Option<RegexSet>,
 }
 
 /// Represents all matches found in a specific file.
@@ -75,550 +356,3398 @@ pub struct FileSearchResult {
     pub matches: Vec<ContentMatchResult>,
 }
 
-/// This addresses the issue with the DockerHub mcp-registry & mcp-gateway where tool discovery fails to resolve
-/// references to 'example' or 'default' values when running the run->command from the server.yaml file
-/// should be removed once mcp-gateway is more mature
-/// reference: https://github.com/docker/mcp-registry/blob/7d815fac2f3b7a9717eebc3f3db215de3ce3c3c7/internal/mcp/client.go#L170-L173
-#[allow(clippy::ptr_arg)]
-fn fix_dockerhub_mcp_registry_gateway(input: &String) -> &str {
-    if input.contains("{{rust-mcp-filesystem.allowed_directories|volume-target|into}}") {
-        "."
-    } else {
-        input
+/// Options controlling how file contents are matched by content search.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ContentSearchOptions {
+    /// Treat the query as a regular expression instead of literal text.
+    pub is_regex: bool,
+    /// Match case-insensitively, using Unicode case folding.
+    pub ignore_case: bool,
+    /// Normalize the query and file content to Unicode NFC before matching.
+    pub normalize_unicode: bool,
+    /// Merge matches on adjacent lines and drop repeated snippets, see [`ContentMatchResult::dedupe`].
+    pub dedupe: bool,
+}
+
+impl Default for ContentSearchOptions {
+    fn default() -> Self {
+        Self {
+            is_regex: false,
+            ignore_case: true,
+            normalize_unicode: false,
+            dedupe: false,
+        }
     }
 }
 
-impl FileSystemService {
-    pub fn try_new(allowed_directories: &[String]) -> ServiceResult<Self> {
-        let normalized_dirs: Vec<PathBuf> = allowed_directories
+/// The type of entry found at a probed path.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ::serde::Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum PathKind {
+    File,
+    Dir,
+    Symlink,
+    Other,
+    Missing,
+}
+
+/// The state of an allowed directory, checked when requested rather than at startup.
+#[derive(Debug, Clone, PartialEq, Eq, ::serde::Serialize)]
+pub struct AllowedDirectoryStatus {
+    pub path: PathBuf,
+    pub exists: bool,
+    /// Whether the directory can be listed.
+    pub readable: bool,
+    /// Whether the server process has write permission on the directory.
+    pub writable: bool,
+    pub is_symlink: bool,
+}
+
+impl std::fmt::Display for AllowedDirectoryStatus {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if !self.exists {
+            return write!(f, "{} [missing]", self.path.display());
+        }
+        let flags = [
+            (self.readable, "readable"),
+            (!self.readable, "not readable"),
+            (self.writable, "writable"),
+            (!self.writable, "not writable"),
+            (self.is_symlink, "symlink"),
+        ];
+        let flags: Vec<_> = flags
             .iter()
-            .map(fix_dockerhub_mcp_registry_gateway)
-            .map_while(|dir| {
-                let expand_result = expand_home(dir.into());
-                if !expand_result.is_dir() {
-                    panic!("{}", format!("Error: {dir} is not a directory"));
-                }
-                Some(expand_result)
-            })
+            .filter_map(|(set, flag)| set.then_some(*flag))
             .collect();
-
-        Ok(Self {
-            allowed_path: RwLock::new(Arc::new(normalized_dirs)),
-        })
+        write!(f, "{} [{}]", self.path.display(), flags.join(", "))
     }
+}
 
-    pub async fn allowed_directories(&self) -> Arc<Vec<PathBuf>> {
-        let guard = self.allowed_path.read().await;
-        guard.clone()
+/// The result of probing a single path with [`FileSystemService::probe_paths`].
+#[derive(Debug, Clone, PartialEq, Eq, ::serde::Serialize)]
+pub struct ProbedPath {
+    pub path: String,
+    /// Whether the path lies within the allowed directories. Paths outside are not inspected,
+    /// so `exists` and `kind` are `None` for them.
+    pub allowed: bool,
+    pub exists: Option<bool>,
+    #[serde(rename = "type")]
+    pub kind: Option<PathKind>,
+    /// Size in bytes, for files only.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub size: Option<u64>,
+}
+
+impl PathKind {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            PathKind::File => "file",
+            PathKind::Dir => "dir",
+            PathKind::Symlink => "symlink",
+            PathKind::Other => "other",
+            PathKind::Missing => "missing",
+        }
     }
 }
 
-impl FileSystemService {
-    pub fn valid_roots(&self, roots: Vec<&str>) -> ServiceResult<(Vec<PathBuf>, Option<String>)> {
-        let paths: Vec<Result<PathBuf, ServiceError>> = roots
-            .iter()
-            .map(|p| self.parse_file_path(p))
-            .collect::<Vec<_>>();
+impl std::fmt::Display for ProbedPath {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match (self.kind, self.size) {
+            (None, _) => write!(f, "{}: outside allowed directories", self.path),
+            (Some(kind), Some(size)) => write!(f, "{}: {} {size} bytes", self.path, kind.as_str()),
+            (Some(kind), None) => write!(f, "{}: {}", self.path, kind.as_str()),
+        }
+    }
+}
 
-        // Partition into Ok and Err results
-        let (ok_paths, err_paths): (PathResultList, PathResultList) =
-            paths.into_iter().partition(|p| p.is_ok());
+/// The outcome of a conditional write.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ConditionalWriteOutcome {
+    Written,
+    /// The precondition did not hold, for the given reason; nothing was written.
+    Skipped(String),
+}
 
-        // using HashSet to remove duplicates
-        let (valid_roots, no_dir_roots): (HashSet<PathBuf>, HashSet<PathBuf>) = ok_paths
-            .into_iter()
-            .collect::<Result<Vec<_>, _>>()?
-            .into_iter()
-            .map(expand_home)
-            .partition(|path| path.is_dir());
+/// The outcome of a move operation, as planned by a dry run.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MovePlan {
+    pub source: PathBuf,
+    pub destination: PathBuf,
+    /// Whether the source is a directory.
+    pub is_dir: bool,
+    /// Size of the source file, or combined size of all files under the source directory.
+    pub bytes: u64,
+    /// Reasons the move is expected to fail or overwrite existing data.
+    pub conflicts: Vec<String>,
+}
 
-        let skipped_roots = if !err_paths.is_empty() || !no_dir_roots.is_empty() {
-            Some(format!(
-                "Warning: skipped {} invalid roots.",
-                err_paths.len() + no_dir_roots.len()
-            ))
+impl std::fmt::Display for MovePlan {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "Dry run: would move {} '{}' -> '{}' ({})",
+            if self.is_dir { "directory" } else { "file" },
+            self.source.display(),
+            self.destination.display(),
+            format_bytes(self.bytes)
+        )?;
+        if self.conflicts.is_empty() {
+            write!(f, "\nConflicts: none")
         } else {
-            None
-        };
+            write!(f, "\nConflicts:")?;
+            for conflict in &self.conflicts {
+                write!(f, "\n  - {conflict}")?;
+            }
+            Ok(())
+        }
+    }
+}
 
-        let valid_roots = valid_roots.into_iter().collect();
+/// Limits applied when reading every file in a directory.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ReadDirectoryLimits {
+    /// The read is refused if the directory holds more regular files than this.
+    pub max_files: u64,
+    /// Files larger than this are skipped.
+    pub max_file_bytes: u64,
+    /// Combined size of the returned contents.
+    pub max_total_bytes: u64,
+}
 
-        Ok((valid_roots, skipped_roots))
+impl Default for ReadDirectoryLimits {
+    fn default() -> Self {
+        Self {
+            max_files: 50,
+            max_file_bytes: 64 * 1024,
+            max_total_bytes: 256 * 1024,
+        }
     }
+}
 
-    pub async fn update_allowed_paths(&self, valid_roots: Vec<PathBuf>) {
-        let mut guard = self.allowed_path.write().await;
-        *guard = Arc::new(valid_roots)
-    }
+/// An entry of a directory listed by [`FileSystemService::list_directory_entries`]. Symbolic
+/// links are described by their target.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ListedEntry {
+    pub name: String,
+    pub path: PathBuf,
+    pub is_dir: bool,
+    pub is_file: bool,
+    /// Size of a file in bytes, when sizes were requested.
+    pub size: Option<u64>,
+    /// Disk space allocated to a file, see [`allocated_size`], when sizes were requested.
+    pub allocated: Option<u64>,
+    /// Last modification time of a file, when sizes were requested and the platform reports it.
+    pub modified: Option<SystemTime>,
+}
 
-    /// Converts a string to a `PathBuf`, supporting both raw paths and `file://` URIs.
-    fn parse_file_path(&self, input: &str) -> ServiceResult<PathBuf> {
-        Ok(PathBuf::from(
-            input.strip_prefix("file://").unwrap_or(input).trim(),
-        ))
-    }
+/// Combined size of files, as computed by [`FileSystemService::calculate_directory_usage`].
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct DiskUsage {
+    /// Sum of the file sizes.
+    pub bytes: u64,
+    /// Sum of the disk space allocated to the files, see [`allocated_size`].
+    pub allocated_bytes: u64,
+}
 
-    pub fn validate_path(
-        &self,
-        requested_path: &Path,
-        allowed_directories: Arc<Vec<PathBuf>>,
-    ) -> ServiceResult<PathBuf> {
-        if allowed_directories.is_empty() {
-            return Err(ServiceError::FromString(
-                "Allowed directories list is empty. Client did not provide any valid root directories.".to_string()
-            ));
+/// A file visited by [`FileSystemService::read_directory`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DirectoryFile {
+    /// The file content, keyed by the path relative to the directory.
+    Content { path: PathBuf, content: String },
+    /// A file that was not included, with the reason why.
+    Skipped { path: PathBuf, reason: String },
+}
+
+/// Contents of the text files in a directory, in alphabetical order of their relative paths.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct DirectoryContents {
+    pub files: Vec<DirectoryFile>,
+}
+
+impl std::fmt::Display for DirectoryContents {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let mut skipped = vec![];
+        let mut first = true;
+        for file in &self.files {
+            match file {
+                DirectoryFile::Content { path, content } => {
+                    if !first {
+                        writeln!(f)?;
+                    }
+                    first = false;
+                    writeln!(f, "==> {} <==", path.display())?;
+                    write!(f, "{content}")?;
+                    if !content.ends_with('\n') {
+                        writeln!(f)?;
+                    }
+                }
+                DirectoryFile::Skipped { path, reason } => skipped.push((path, reason)),
+            }
         }
+        if first {
+            writeln!(f, "No text files read.")?;
+        }
+        if !skipped.is_empty() {
+            write!(f, "\nSkipped:")?;
+            for (path, reason) in skipped {
+                write!(f, "\n  - {}: {}", path.display(), reason)?;
+            }
+        }
+        Ok(())
+    }
+}
 
-        // Expand ~ to home directory
-        let expanded_path = expand_home(requested_path.to_path_buf());
+/// Totals collected while building a directory tree.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct DirectoryTreeSummary {
+    /// Number of files included in the tree.
+    pub file_count: u64,
+    /// Number of directories included in the tree, excluding the root.
+    pub dir_count: u64,
+    /// Combined size of all included files, in bytes.
+    pub total_size_bytes: u64,
+    /// Deepest level reached below the root (direct children are level 1).
+    pub max_depth: usize,
+    /// Entries that could not be walked; their subtrees are missing from the tree.
+    pub walk_errors: WalkErrors,
+}
 
-        // Resolve the absolute path
-        let absolute_path = if expanded_path.as_path().is_absolute() {
-            expanded_path.clone()
+/// Maximum number of walk errors listed individually in tool output.
+pub const MAX_REPORTED_WALK_ERRORS: usize = 20;
+
+/// An entry a directory walk could not read.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct WalkError {
+    pub path: PathBuf,
+    /// Short description of the failure, e.g. `permission denied` or `filesystem loop`.
+    pub kind: String,
+}
+
+/// Errors collected during a directory walk instead of being silently dropped.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct WalkErrors {
+    pub errors: Vec<WalkError>,
+}
+
+impl WalkErrors {
+    pub fn record(&mut self, error: walkdir::Error) {
+        let path = error.path().map(Path::to_path_buf).unwrap_or_default();
+        let kind = if error.loop_ancestor().is_some() {
+            "filesystem loop".to_string()
         } else {
-            env::current_dir().unwrap().join(&expanded_path)
+            error
+                .io_error()
+                .map(|io_error| io_error.kind().to_string())
+                .unwrap_or_else(|| error.to_string())
         };
+        self.errors.push(WalkError { path, kind });
+    }
 
-        // Normalize the path
-        let normalized_requested = normalize_path(&absolute_path);
+    /// Records an entry listed by the walk whose metadata could then not be read, such as a
+    /// file deleted in the meantime.
+    pub fn record_io(&mut self, path: &Path, error: &std::io::Error) {
+        self.errors.push(WalkError {
+            path: path.to_path_buf(),
+            kind: error.kind().to_string(),
+        });
+    }
 
-        // Check if path is within allowed directories
-        if !allowed_directories.iter().any(|dir| {
-            // Must account for both scenarios — the requested path may not exist yet, making canonicalization impossible.
-            normalized_requested.starts_with(dir)
-                || normalized_requested.starts_with(normalize_path(dir))
-        }) {
-            let symlink_target = if contains_symlink(&absolute_path)? {
-                "a symlink target path"
-            } else {
-                "path"
-            };
-            return Err(ServiceError::FromString(format!(
-                "Access denied - {} is outside allowed directories: {} not in {}",
-                symlink_target,
-                absolute_path.display(),
-                allowed_directories
-                    .iter()
-                    .map(|p| p.display().to_string())
-                    .collect::<Vec<_>>()
-                    .join(",\n"),
-            )));
+    /// Number of errors of each kind, most frequent first, e.g. `[("permission denied", 2)]`.
+    pub fn counts_by_kind(&self) -> Vec<(String, usize)> {
+        let mut counts: HashMap<&str, usize> = HashMap::new();
+        for error in &self.errors {
+            *counts.entry(error.kind.as_str()).or_default() += 1;
         }
-
-        Ok(absolute_path)
+        let mut counts: Vec<_> = counts
+            .into_iter()
+            .map(|(kind, count)| (kind.to_string(), count))
+            .collect();
+        counts.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+        counts
     }
 
-    // Get file stats
-    pub async fn get_file_stats(&self, file_path: &Path) -> ServiceResult<FileInfo> {
-        let allowed_directories = self.allowed_directories().await;
-        let valid_path = self.validate_path(file_path, allowed_directories)?;
+    /// Describes [`counts_by_kind`](Self::counts_by_kind), e.g. `2 permission denied, 1 entity
+    /// not found`.
+    pub fn describe_counts(&self) -> String {
+        self.counts_by_kind()
+            .into_iter()
+            .map(|(kind, count)| format!("{count} {kind}"))
+            .collect::<Vec<_>>()
+            .join(", ")
+    }
 
-        let metadata = fs::metadata(valid_path)?;
+    pub fn is_empty(&self) -> bool {
+        self.errors.is_empty()
+    }
 
-        let size = metadata.len();
-        let created = metadata.created().ok();
-        let modified = metadata.modified().ok();
-        let accessed = metadata.accessed().ok();
-        let is_directory = metadata.is_dir();
-        let is_file = metadata.is_file();
+    pub fn len(&self) -> usize {
+        self.errors.len()
+    }
 
-        Ok(FileInfo {
-            size,
-            created,
-            modified,
-            accessed,
-            is_directory,
-            is_file,
-            metadata,
-        })
+    /// Fails with the first error, for callers that must not return partial results.
+    pub fn check_strict(&self) -> ServiceResult<()> {
+        match self.errors.first() {
+            Some(first) => Err(ServiceError::FromString(format!(
+                "Directory walk failed at '{}': {} ({} error(s) in total)",
+                first.path.display(),
+                first.kind,
+                self.len()
+            ))),
+            None => Ok(()),
+        }
     }
 
-    fn detect_line_ending(&self, text: &str) -> &str {
-        if text.contains("\r\n") {
-            "\r\n"
-        } else if text.contains('\r') {
-            "\r"
-        } else {
-            "\n"
+    /// Renders an `Errors:` section listing up to [`MAX_REPORTED_WALK_ERRORS`] errors.
+    pub fn render(&self) -> String {
+        let mut output = String::from("Errors:");
+        for error in self.errors.iter().take(MAX_REPORTED_WALK_ERRORS) {
+            output.push_str(&format!("\n  - {}: {}", error.path.display(), error.kind));
+        }
+        if self.len() > MAX_REPORTED_WALK_ERRORS {
+            output.push_str(&format!(
+                "\n  ... and {} more",
+                self.len() - MAX_REPORTED_WALK_ERRORS
+            ));
         }
+        output
     }
+}
 
-    pub async fn zip_directory(
-        &self,
-        input_dir: String,
-        pattern: String,
-        target_zip_file: String,
-    ) -> ServiceResult<String> {
-        let allowed_directories = self.allowed_directories().await;
-        let valid_dir_path =
-            self.validate_path(Path::new(&input_dir), allowed_directories.clone())?;
+/// Returns up to [`MAX_PATH_SUGGESTIONS`] of `candidates` closest to `name` by case-insensitive
+/// edit distance, ignoring candidates too different to be a plausible typo.
+fn closest_names(name: &str, candidates: Vec<String>) -> Vec<String> {
+    let name = name.to_lowercase();
+    let max_distance = (name.chars().count() / 3).max(2);
+    let mut scored: Vec<(usize, String)> = candidates
+        .into_iter()
+        .map(|candidate| (edit_distance(&name, &candidate.to_lowercase()), candidate))
+        .filter(|(distance, _)| *distance <= max_distance)
+        .collect();
+    scored.sort();
+    scored
+        .into_iter()
+        .take(MAX_PATH_SUGGESTIONS)
+        .map(|(_, candidate)| candidate)
+        .collect()
+}
 
-        let input_dir_str = &valid_dir_path
-            .as_os_str()
-            .to_str()
-            .ok_or(std::io::Error::new(
-                std::io::ErrorKind::InvalidInput,
+/// Comments out or uncomments the line range of `line_edit` in `content` (with `\n` line endings).
+///
+/// Line comments are inserted at the smallest indentation (leading spaces and tabs) of the range
+/// so relative indentation is preserved, and lines that are already commented are left untouched. Block comments wrap the
+/// whole range, opening after the indentation of the first line and closing at the end of the last.
+/// When uncommenting, lines (or ranges) that are not commented are skipped and reported in `notes`.
+fn apply_line_edit(
+    content: &str,
+    line_edit: &LineEditOperation,
+    style: CommentStyle,
+    notes: &mut Vec<String>,
+) -> ServiceResult<String> {
+    let has_trailing_newline = content.ends_with('\n');
+    let mut lines: Vec<String> = content
+        .strip_suffix('\n')
+        .unwrap_or(content)
+        .split('\n')
+        .map(str::to_string)
+        .collect();
+
+    let (start, end) = (line_edit.start_line as usize, line_edit.end_line as usize);
+    if start == 0 || start > end || end > lines.len() {
+        return Err(ServiceError::FromString(format!(
+            "Invalid line range {start}-{end}: the file has {} lines.",
+            lines.len()
+        )));
+    }
+    let range = start - 1..end;
+    // Only spaces and tabs count, so the indentation of one line is a char boundary in any other
+    // line indented at least as deep
+    let indent_of = |line: &str| line.len() - line.trim_start_matches([' ', '\t']).len();
+    let (open, close) = style.tokens();
+
+    match (line_edit.operation, close) {
+        (LineEditKind::CommentLines, None) => {
+            let indent = lines[range.clone()]
+                .iter()
+                .filter(|line| !line.trim().is_empty())
+                .map(|line| indent_of(line))
+                .min()
+                .unwrap_or(0);
+            for line in lines[range].iter_mut() {
+                if line.trim().is_empty() || line.trim_start().starts_with(open) {
+                    continue;
+                }
+                line.insert_str(indent, &format!("{open} "));
+            }
+        }
+        (LineEditKind::UncommentLines, None) => {
+            for (index, line) in lines[range].iter_mut().enumerate() {
+                if line.trim().is_empty() {
+                    continue;
+                }
+                let indent = indent_of(line);
+                let Some(rest) = line[indent..].strip_prefix(open) else {
+                    notes.push(format!(
+                        "line {} is not commented with '{open}'; skipped.",
+                        start + index
+                    ));
+                    continue;
+                };
+                let rest = rest.strip_prefix(' ').unwrap_or(rest);
+                *line = format!("{}{}", &line[..indent], rest);
+            }
+        }
+        (LineEditKind::CommentLines, Some(close)) => {
+            let already_wrapped = lines[start - 1].trim_start().starts_with(open)
+                && lines[end - 1].trim_end().ends_with(close);
+            if !already_wrapped {
+                let indent = indent_of(&lines[start - 1]);
+                lines[start - 1].insert_str(indent, &format!("{open} "));
+                lines[end - 1].push_str(&format!(" {close}"));
+            }
+        }
+        (LineEditKind::UncommentLines, Some(close)) => {
+            let first = &lines[start - 1];
+            let last = &lines[end - 1];
+            let indent = indent_of(first);
+            if !(first[indent..].starts_with(open) && last.trim_end().ends_with(close)) {
+                notes.push(format!(
+                    "lines {start}-{end} are not wrapped in '{open} {close}'; skipped."
+                ));
+            } else {
+                let rest = &first[indent + open.len()..];
+                let rest = rest.strip_prefix(' ').unwrap_or(rest);
+                lines[start - 1] = format!("{}{}", &first[..indent], rest);
+
+                let last = lines[end - 1].trim_end();
+                let kept = &last[..last.len() - close.len()];
+                lines[end - 1] = kept.strip_suffix(' ').unwrap_or(kept).to_string();
+            }
+        }
+    }
+
+    let mut result = lines.join("\n");
+    if has_trailing_newline {
+        result.push('\n');
+    }
+    Ok(result)
+}
+
+fn check_file_size(
+    file_size: usize,
+    min_bytes: Option<usize>,
+    max_bytes: Option<usize>,
+) -> ServiceResult<()> {
+    match (min_bytes, max_bytes) {
+        (_, Some(max)) if file_size > max => Err(ServiceError::FileTooLarge(max)),
+        (Some(min), _) if file_size < min => Err(ServiceError::FileTooSmall(min)),
+        _ => Ok(()),
+    }
+}
+
+/// Wraps a diff in a code fence with more backticks than any run inside it.
+fn fenced_diff(diff: &str) -> String {
+    let mut num_backticks = 3;
+    while diff.contains(&"`".repeat(num_backticks)) {
+        num_backticks += 1;
+    }
+    format!(
+        "{}diff\n{}{}\n\n",
+        "`".repeat(num_backticks),
+        diff,
+        "`".repeat(num_backticks)
+    )
+}
+
+/// Size of the regular file at `path`, or `0` when there is none.
+async fn existing_file_size(path: &Path) -> u64 {
+    tokio::fs::metadata(path)
+        .await
+        .ok()
+        .filter(|metadata| metadata.is_file())
+        .map_or(0, |metadata| metadata.len())
+}
+
+/// Combined size of the regular files among `paths`.
+fn total_file_size<P: AsRef<Path>>(paths: &[P]) -> u64 {
+    paths
+        .iter()
+        .filter_map(|path| fs::metadata(path).ok())
+        .filter(|metadata| metadata.is_file())
+        .map(|metadata| metadata.len())
+        .sum()
+}
+
+/// Whether `err` means the file is in use by another process (`ERROR_SHARING_VIOLATION` or
+/// `ERROR_LOCK_VIOLATION`).
+#[cfg(windows)]
+fn is_sharing_violation(err: &std::io::Error) -> bool {
+    matches!(err.raw_os_error(), Some(32 | 33))
+}
+
+#[cfg(not(windows))]
+fn is_sharing_violation(_err: &std::io::Error) -> bool {
+    false
+}
+
+/// Whether the current process may create entries in `dir`.
+#[cfg(unix)]
+fn is_writable_dir(dir: &Path) -> bool {
+    use std::os::unix::ffi::OsStrExt;
+
+    std::ffi::CString::new(dir.as_os_str().as_bytes())
+        // SAFETY: `path` is a valid NUL-terminated string for the duration of the call.
+        .is_ok_and(|path| unsafe { libc::access(path.as_ptr(), libc::W_OK) } == 0)
+}
+
+/// Whether the current process may create entries in `dir`.
+#[cfg(not(unix))]
+fn is_writable_dir(dir: &Path) -> bool {
+    fs::metadata(dir).is_ok_and(|metadata| !metadata.permissions().readonly())
+}
+
+/// This addresses the issue with the DockerHub mcp-registry & mcp-gateway where tool discovery fails to resolve
+/// references to 'example' or 'default' values when running the run->command from the server.yaml file
+/// should be removed once mcp-gateway is more mature
+/// reference: https://github.com/docker/mcp-registry/blob/7d815fac2f3b7a9717eebc3f3db215de3ce3c3c7/internal/mcp/client.go#L170-L173
+#[allow(clippy::ptr_arg)]
+fn fix_dockerhub_mcp_registry_gateway(input: &String) -> &str {
+    if input.contains("{{rust-mcp-filesystem.allowed_directories|volume-target|into}}") {
+        "."
+    } else {
+        input
+    }
+}
+
+impl FileSystemService {
+    pub fn try_new(allowed_directories: &[String]) -> ServiceResult<Self> {
+        Self::try_new_with_policy(allowed_directories, InvalidDirectoryPolicy::Fail)
+    }
+
+    /// Creates a new `FileSystemService`, canonicalizing each configured allowed directory.
+    ///
+    /// Entries may be raw paths, `~` prefixed paths or `file://` URIs. Relative paths are resolved
+    /// against the current working directory and reported as a warning.
+    /// Entries that do not exist or are not directories are collected, and depending on the `policy`
+    /// either cause an error listing every invalid entry, or are skipped and reported via `rejected_directories()`.
+    pub fn try_new_with_policy(
+        allowed_directories: &[String],
+        policy: InvalidDirectoryPolicy,
+    ) -> ServiceResult<Self> {
+        let mut normalized_dirs: Vec<PathBuf> = vec![];
+        let mut symlinked_directories = HashSet::new();
+        let mut rejected_directories = vec![];
+        let mut directory_warnings = vec![];
+
+        for dir in allowed_directories
+            .iter()
+            .map(fix_dockerhub_mcp_registry_gateway)
+        {
+            let issue = |reason: String| DirectoryIssue {
+                entry: dir.to_string(),
+                reason,
+            };
+
+            let path = expand_home(parse_file_uri(dir));
+            let absolute_path = if path.is_absolute() {
+                path
+            } else {
+                let absolute_path = env::current_dir()?.join(&path);
+                directory_warnings.push(issue(format!(
+                    "relative path resolved against the current directory as '{}'",
+                    absolute_path.display()
+                )));
+                absolute_path
+            };
+
+            match absolute_path.canonicalize() {
+                Ok(canonical_path) if canonical_path.is_dir() => {
+                    // Only the canonical path is kept, so whether it was a link is recorded now
+                    if absolute_path
+                        .symlink_metadata()
+                        .is_ok_and(|metadata| metadata.is_symlink())
+                    {
+                        symlinked_directories.insert(canonical_path.clone());
+                    }
+                    if !normalized_dirs.contains(&canonical_path) {
+                        normalized_dirs.push(canonical_path);
+                    }
+                }
+                Ok(_) => rejected_directories.push(issue("is not a directory".to_string())),
+                Err(err) if err.kind() == std::io::ErrorKind::NotFound => {
+                    rejected_directories.push(issue("does not exist".to_string()))
+                }
+                Err(err) => rejected_directories.push(issue(err.to_string())),
+            }
+        }
+
+        if policy == InvalidDirectoryPolicy::Fail && !rejected_directories.is_empty() {
+            return Err(ServiceError::InvalidAllowedDirectories(
+                rejected_directories
+                    .iter()
+                    .map(|issue| format!("  {issue}"))
+                    .collect::<Vec<_>>()
+                    .join("\n"),
+            ));
+        }
+
+        Ok(Self {
+            allowed_path: RwLock::new(Arc::new(normalized_dirs)),
+            symlinked_directories,
+            rejected_directories,
+            directory_warnings,
+            performance_config: PerformanceConfig::default(),
+            redaction_config: RedactionConfig::default(),
+            byte_format: ByteFormat::default(),
+            path_suggestions: true,
+            operations: OperationTracker::default(),
+            read_cache: ReadCache::default(),
+            open_files: OpenFiles::default(),
+            write_budget: WriteBudget::default(),
+            space_provider: Arc::new(SystemSpace),
+            telemetry: Telemetry::default(),
+            metrics_file: None,
+            descriptor_limit: DescriptorLimit::default(),
+        })
+    }
+
+    /// Replaces the default `PerformanceConfig`, after validating it.
+    pub fn with_performance_config(mut self, config: PerformanceConfig) -> ServiceResult<Self> {
+        config.validate()?;
+        self.write_budget.set_limits(
+            config.max_session_write_bytes,
+            config.max_session_delete_bytes,
+        );
+        self.descriptor_limit.set_limit(config.max_open_files);
+        self.performance_config = config;
+        Ok(self)
+    }
+
+    pub fn performance_config(&self) -> &PerformanceConfig {
+        &self.performance_config
+    }
+
+    /// The bound on files opened or queried at once by the parallel parts of recursive walks.
+    pub fn descriptor_limit(&self) -> &DescriptorLimit {
+        &self.descriptor_limit
+    }
+
+    /// Replaces how the free space of destinations is queried before large writes (the
+    /// operating system by default).
+    pub fn with_space_provider(mut self, provider: impl SpaceProvider + 'static) -> Self {
+        self.space_provider = Arc::new(provider);
+        self
+    }
+
+    /// Refuses a write of `needed` bytes to `path` when it reaches the configured threshold and
+    /// the destination's filesystem has less space available. Unknown space is not checked.
+    fn check_disk_space(&self, path: &Path, needed: u64) -> ServiceResult<()> {
+        if needed < self.performance_config.disk_space_check_threshold {
+            self.telemetry.increment(Counter::DiskSpaceCheckSkipped);
+            return Ok(());
+        }
+        self.telemetry.increment(Counter::DiskSpaceChecked);
+        match self.space_provider.available_space(path) {
+            Some(available) if available < needed => Err(ServiceError::InsufficientDiskSpace {
+                path: path.to_path_buf(),
+                needed,
+                available,
+            }),
+            _ => Ok(()),
+        }
+    }
+
+    /// Removes the partial file left at `path` by a write that ran out of space, and returns the
+    /// error reporting it.
+    async fn discard_partial_file(&self, path: &Path) -> ServiceError {
+        self.read_cache.invalidate(path);
+        let cleanup = match tokio::fs::remove_file(path).await {
+            Ok(()) => "The partial file was removed.".to_string(),
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => {
+                "No partial file was left.".to_string()
+            }
+            Err(err) => format!("The partial file could not be removed: {err}"),
+        };
+        ServiceError::DiskFull {
+            path: path.to_path_buf(),
+            cleanup,
+        }
+    }
+
+    /// Removes the directory an extraction that ran out of space created at `target_dir`, and
+    /// returns the error reporting what was removed.
+    async fn discard_partial_extraction(&self, target_dir: &Path) -> ServiceError {
+        let allowed_directories = self.allowed_directories().await;
+        let options = DeleteOptions {
+            recursive: true,
+            allow_vcs: true,
+            force: true,
+            protected: allowed_directories.to_vec(),
+            ..Default::default()
+        };
+        let target = target_dir.to_path_buf();
+        let removed = tokio::task::spawn_blocking(move || {
+            plan_delete(&target, &options).map(|plan| execute_delete(&plan))
+        })
+        .await
+        .map_err(|err| ServiceError::FromString(err.to_string()));
+        let cleanup = match removed {
+            Ok(Ok(report)) => {
+                let mut cleanup = format!(
+                    "Removed the partial extraction: {}.",
+                    report.deleted.describe(&self.byte_format)
+                );
+                if !report.is_complete() {
+                    cleanup.push_str(&format!(
+                        " {} entries could not be removed: {}",
+                        report.failures.len(),
+                        report
+                            .failures
+                            .iter()
+                            .map(|failure| failure.path.display().to_string())
+                            .collect::<Vec<_>>()
+                            .join(", ")
+                    ));
+                }
+                cleanup
+            }
+            Ok(Err(err)) | Err(err) => {
+                format!("The partial extraction could not be removed: {err}")
+            }
+        };
+        ServiceError::DiskFull {
+            path: target_dir.to_path_buf(),
+            cleanup,
+        }
+    }
+
+    /// The performance counters of the service, cumulative since it started or was last reset.
+    /// With `reset`, the counters are set back to zero and their values before are returned.
+    pub fn performance_counters(&self, reset: bool) -> Vec<(Counter, u64)> {
+        if reset {
+            self.telemetry.reset()
+        } else {
+            self.telemetry.snapshot()
+        }
+    }
+
+    /// Records a fast path in the timings footer of the current call and in the performance
+    /// counters.
+    fn take_fast_path(&self, name: &'static str) {
+        record_fast_path(name);
+        if let Some(counter) = Counter::for_fast_path(name) {
+            self.telemetry.increment(counter);
+        }
+    }
+
+    /// Bytes written and removed by the write operations of this session so far.
+    pub fn session_write_usage(&self) -> SessionWriteUsage {
+        self.write_budget.usage()
+    }
+
+    /// Whether a ceiling on the bytes written or removed during the session is configured.
+    pub fn has_session_write_limits(&self) -> bool {
+        self.write_budget.is_limited()
+    }
+
+    /// Replaces the default `RedactionConfig`, which leaves output unredacted.
+    pub fn with_redaction_config(mut self, config: RedactionConfig) -> Self {
+        self.redaction_config = config;
+        self
+    }
+
+    pub fn redaction_config(&self) -> &RedactionConfig {
+        &self.redaction_config
+    }
+
+    /// Replaces the default format of human-readable sizes and counts (binary units, no
+    /// thousands separators). Tools may still override units and precision per call.
+    pub fn with_byte_format(mut self, format: ByteFormat) -> Self {
+        self.byte_format = format;
+        self
+    }
+
+    pub fn byte_format(&self) -> ByteFormat {
+        self.byte_format
+    }
+
+    /// Enables or disables "did you mean" suggestions for paths that were not found (enabled by
+    /// default).
+    pub fn with_path_suggestions(mut self, enabled: bool) -> Self {
+        self.path_suggestions = enabled;
+        self
+    }
+
+    /// Writes the performance counters to `path`, as a JSON object, when the service shuts down.
+    pub fn with_metrics_file(mut self, path: Option<PathBuf>) -> Self {
+        self.metrics_file = path;
+        self
+    }
+
+    /// Runs `operation` on `path` and, if it fails because the path does not exist, appends the
+    /// names of up to [`MAX_PATH_SUGGESTIONS`] siblings closest to the requested name to the error.
+    /// Suggestions are skipped when disabled, when the parent directory is missing or outside the
+    /// allowed directories, or when it has more than [`MAX_SUGGESTION_SCAN_ENTRIES`] entries.
+    pub async fn suggest_paths_on_not_found<T>(
+        &self,
+        path: &Path,
+        operation: impl Future<Output = ServiceResult<T>>,
+    ) -> ServiceResult<T> {
+        let error = match operation.await {
+            Err(ServiceError::IoError(error)) if error.kind() == std::io::ErrorKind::NotFound => {
+                error
+            }
+            result => return result,
+        };
+        if !self.path_suggestions {
+            return Err(error.into());
+        }
+
+        let allowed_directories = self.allowed_directories().await;
+        let suggestions = path
+            .parent()
+            .zip(path.file_name())
+            .and_then(|(parent, name)| {
+                let parent = self.validate_path(parent, allowed_directories).ok()?;
+                let mut siblings = Vec::new();
+                for entry in fs::read_dir(&parent).ok()? {
+                    if siblings.len() == MAX_SUGGESTION_SCAN_ENTRIES {
+                        return None;
+                    }
+                    siblings.push(entry.ok()?.file_name().to_string_lossy().into_owned());
+                }
+                record_entries_visited(siblings.len() as u64);
+                Some(closest_names(&name.to_string_lossy(), siblings))
+            })
+            .unwrap_or_default();
+
+        match suggestions.as_slice() {
+            [] => Err(error.into()),
+            [suggestion] => Err(ServiceError::FromString(format!(
+                "{error}\nDid you mean '{suggestion}'?"
+            ))),
+            _ => Err(ServiceError::FromString(format!(
+                "{error}\nDid you mean one of: {}?",
+                suggestions
+                    .iter()
+                    .map(|suggestion| format!("'{suggestion}'"))
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            ))),
+        }
+    }
+
+    /// Redacts secrets in `text` read from `path` if redaction applies to it, given the
+    /// per-call override. Returns the number of redactions, or `None` if redaction does not apply.
+    pub fn redact_output(
+        &self,
+        path: &Path,
+        requested: Option<bool>,
+        text: String,
+    ) -> (String, Option<usize>) {
+        if !self.redaction_config.applies_to(path, requested) {
+            return (text, None);
+        }
+        let (text, count) = redact_secrets(&text);
+        (text, Some(count))
+    }
+
+    /// Wraps a reader in a `BufReader` sized according to `PerformanceConfig::stream_buffer_size`.
+    pub fn stream_reader<R: AsyncRead>(&self, inner: R) -> BufReader<R> {
+        BufReader::with_capacity(self.performance_config.stream_buffer_size, inner)
+    }
+
+    /// Opens a file for reading, with `O_NOATIME` when `PerformanceConfig::no_atime_reads` is set.
+    pub async fn open_for_read(&self, path: &Path) -> std::io::Result<File> {
+        #[cfg(target_os = "linux")]
+        if self.performance_config.no_atime_reads {
+            let mut options = tokio::fs::OpenOptions::new();
+            options.read(true).custom_flags(libc::O_NOATIME);
+            match options.open(path).await {
+                // O_NOATIME is only permitted for the owner of the file
+                Err(err) if err.kind() == std::io::ErrorKind::PermissionDenied => {}
+                result => return result,
+            }
+        }
+        File::open(path).await
+    }
+
+    /// Creates or truncates a file for writing, with `O_NOFOLLOW` when
+    /// `PerformanceConfig::no_follow_writes` is set.
+    pub async fn open_for_write(&self, path: &Path) -> std::io::Result<File> {
+        let mut options = tokio::fs::OpenOptions::new();
+        options.write(true).create(true).truncate(true);
+        #[cfg(unix)]
+        if self.performance_config.no_follow_writes {
+            options.custom_flags(libc::O_NOFOLLOW);
+        }
+        options.open(path).await
+    }
+
+    async fn read_to_string_with_flags(&self, path: &Path) -> std::io::Result<String> {
+        let mut content = String::new();
+        self.open_for_read(path)
+            .await?
+            .read_to_string(&mut content)
+            .await?;
+        Ok(content)
+    }
+
+    /// Rejects writes to files whose extension is not allowed by `PerformanceConfig`.
+    pub fn check_write_extension(&self, path: &Path) -> ServiceResult<()> {
+        if self.performance_config.is_writable_extension(path) {
+            return Ok(());
+        }
+        let extension = path
+            .extension()
+            .map(|extension| format!("'.{}' files", extension.to_string_lossy()))
+            .unwrap_or_else(|| "files without an extension".to_string());
+        Err(ServiceError::FromString(format!(
+            "Writing {extension} is not allowed: '{}'",
+            path.display()
+        )))
+    }
+
+    /// Writes `content` to `path`, charging it and the size of the replaced file to the session
+    /// write budget first.
+    async fn write_with_flags(&self, path: &Path, content: &[u8]) -> ServiceResult<()> {
+        let replaced = existing_file_size(path).await;
+        self.check_disk_space(path, (content.len() as u64).saturating_sub(replaced))?;
+        self.write_budget.charge(content.len() as u64, replaced)?;
+        let result: ServiceResult<()> = async {
+            let mut file = self.open_for_write(path).await?;
+            file.write_all(content).await?;
+            file.flush().await?;
+            Ok(())
+        }
+        .await;
+        if result.is_err() {
+            self.write_budget.refund(content.len() as u64, replaced);
+        }
+        match result {
+            Err(err) if is_storage_full(&err) => Err(self.discard_partial_file(path).await),
+            result => result,
+        }
+    }
+
+    /// Writes `content` to a new file at `path`, opened with `create_new` (`O_EXCL`) so that an
+    /// existing file or link is never replaced. Returns `false` without writing when `path`
+    /// already exists.
+    async fn create_with_flags(&self, path: &Path, content: &[u8]) -> ServiceResult<bool> {
+        self.check_disk_space(path, content.len() as u64)?;
+        self.write_budget.check(content.len() as u64, 0)?;
+        let mut file = match tokio::fs::OpenOptions::new()
+            .write(true)
+            .create_new(true)
+            .open(path)
+            .await
+        {
+            Ok(file) => file,
+            Err(err) if err.kind() == std::io::ErrorKind::AlreadyExists => return Ok(false),
+            Err(err) => return Err(err.into()),
+        };
+        let result: ServiceResult<()> = async {
+            file.write_all(content).await?;
+            file.flush().await?;
+            Ok(())
+        }
+        .await;
+        match result {
+            Err(err) if is_storage_full(&err) => return Err(self.discard_partial_file(path).await),
+            result => result?,
+        }
+        self.write_budget.record(content.len() as u64, 0);
+        Ok(true)
+    }
+
+    /// Registers a new operation, which is tracked as in flight until the returned guard is dropped.
+    /// Fails with `ServiceError::ShuttingDown` once `shutdown` has been called.
+    pub fn begin_operation(&self) -> ServiceResult<OperationGuard<'_>> {
+        self.operations.begin()
+    }
+
+    pub fn is_shutting_down(&self) -> bool {
+        self.operations.is_shutting_down()
+    }
+
+    /// Fails with `ServiceError::ShuttingDown` once `shutdown` has been called. Tools call it
+    /// between the I/O steps of a call, before starting work that has not modified anything yet,
+    /// so a call in flight when shutdown starts finishes early instead of holding up the drain.
+    pub fn ensure_running(&self) -> ServiceResult<()> {
+        if self.is_shutting_down() {
+            return Err(ServiceError::ShuttingDown);
+        }
+        Ok(())
+    }
+
+    /// Returns a receiver that observes `true` once shutdown has started.
+    pub fn shutdown_signal(&self) -> watch::Receiver<bool> {
+        self.operations.subscribe()
+    }
+
+    /// Token cancelled when shutdown gives up waiting for in-flight operations.
+    pub fn cancellation_token(&self) -> CancellationToken {
+        self.operations.cancellation_token()
+    }
+
+    /// Stops accepting new operations and waits up to `timeout` for the in-flight ones to
+    /// complete. Operations still running after the timeout are cancelled through the
+    /// cancellation token. The performance counters are then exported to the metrics file, if
+    /// one is configured; a failed export is logged and does not fail the shutdown.
+    pub async fn shutdown(&self, timeout: Duration) -> ShutdownResult {
+        let result = self.operations.shutdown(timeout).await;
+        if let Some(path) = &self.metrics_file {
+            match self.export_metrics(path, &result).await {
+                Ok(()) => tracing::info!("Exported performance counters to {}", path.display()),
+                Err(err) => tracing::warn!(
+                    "Could not export performance counters to {}: {err}",
+                    path.display()
+                ),
+            }
+        }
+        result
+    }
+
+    async fn export_metrics(&self, path: &Path, result: &ShutdownResult) -> ServiceResult<()> {
+        let counters: serde_json::Map<String, Value> = self
+            .telemetry
+            .snapshot()
+            .into_iter()
+            .map(|(counter, value)| (counter.name().to_string(), Value::from(value)))
+            .collect();
+        let metrics = json!({
+            "drained_operations": result.drained_operations,
+            "timed_out_operations": result.timed_out_operations,
+            "counters": counters,
+        });
+        tokio::fs::write(path, serde_json::to_string_pretty(&metrics)? + "\n").await?;
+        Ok(())
+    }
+
+    pub async fn allowed_directories(&self) -> Arc<Vec<PathBuf>> {
+        let guard = self.allowed_path.read().await;
+        guard.clone()
+    }
+
+    /// Configured allowed directories that were rejected at startup, along with the reason.
+    pub fn rejected_directories(&self) -> &[DirectoryIssue] {
+        &self.rejected_directories
+    }
+
+    /// Warnings raised while resolving the configured allowed directories (e.g. relative paths).
+    pub fn directory_warnings(&self) -> &[DirectoryIssue] {
+        &self.directory_warnings
+    }
+}
+
+impl FileSystemService {
+    pub fn valid_roots(&self, roots: Vec<&str>) -> ServiceResult<(Vec<PathBuf>, Option<String>)> {
+        let paths: Vec<Result<PathBuf, ServiceError>> = roots
+            .iter()
+            .map(|p| self.parse_file_path(p))
+            .collect::<Vec<_>>();
+
+        // Partition into Ok and Err results
+        let (ok_paths, err_paths): (PathResultList, PathResultList) =
+            paths.into_iter().partition(|p| p.is_ok());
+
+        // using HashSet to remove duplicates
+        let (valid_roots, no_dir_roots): (HashSet<PathBuf>, HashSet<PathBuf>) = ok_paths
+            .into_iter()
+            .collect::<Result<Vec<_>, _>>()?
+            .into_iter()
+            .map(expand_home)
+            .partition(|path| path.is_dir());
+
+        let skipped_roots = if !err_paths.is_empty() || !no_dir_roots.is_empty() {
+            Some(format!(
+                "Warning: skipped {} invalid roots.",
+                err_paths.len() + no_dir_roots.len()
+            ))
+        } else {
+            None
+        };
+
+        let valid_roots = valid_roots.into_iter().collect();
+
+        Ok((valid_roots, skipped_roots))
+    }
+
+    pub async fn update_allowed_paths(&self, valid_roots: Vec<PathBuf>) {
+        let mut guard = self.allowed_path.write().await;
+        *guard = Arc::new(valid_roots)
+    }
+
+    /// Converts a string to a `PathBuf`, supporting both raw paths and `file://` URIs.
+    fn parse_file_path(&self, input: &str) -> ServiceResult<PathBuf> {
+        Ok(parse_file_uri(input))
+    }
+
+    pub fn validate_path(
+        &self,
+        requested_path: &Path,
+        allowed_directories: Arc<Vec<PathBuf>>,
+    ) -> ServiceResult<ValidatedPath> {
+        if allowed_directories.is_empty() {
+            return Err(ServiceError::FromString(
+                "Allowed directories list is empty. Client did not provide any valid root directories.".to_string()
+            ));
+        }
+
+        // Expand ~ to home directory
+        let expanded_path = expand_home(requested_path.to_path_buf());
+
+        // Resolve the absolute path
+        let absolute_path = if expanded_path.as_path().is_absolute() {
+            expanded_path.clone()
+        } else {
+            env::current_dir().unwrap().join(&expanded_path)
+        };
+
+        // Normalize the path
+        let normalized_requested = normalize_path(&absolute_path);
+
+        // Check if path is within allowed directories
+        if !allowed_directories.iter().any(|dir| {
+            // Must account for both scenarios — the requested path may not exist yet, making canonicalization impossible.
+            normalized_requested.starts_with(dir)
+                || normalized_requested.starts_with(normalize_path(dir))
+        }) {
+            let symlink_target = if contains_symlink(&absolute_path)? {
+                "a symlink target path"
+            } else {
+                "path"
+            };
+            return Err(ServiceError::FromString(format!(
+                "Access denied - {} is outside allowed directories: {} not in {}",
+                symlink_target,
+                absolute_path.display(),
+                allowed_directories
+                    .iter()
+                    .map(|p| p.display().to_string())
+                    .collect::<Vec<_>>()
+                    .join(",\n"),
+            )));
+        }
+
+        Ok(ValidatedPath {
+            path: absolute_path,
+            metadata: None,
+        })
+    }
+
+    /// Validates the path and fetches its metadata in the same pass, failing if it does not exist.
+    pub fn validate_existing_path(
+        &self,
+        requested_path: &Path,
+        allowed_directories: Arc<Vec<PathBuf>>,
+    ) -> ServiceResult<ValidatedPath> {
+        let mut valid_path = self.validate_path(requested_path, allowed_directories)?;
+        valid_path.metadata = Some(fs::metadata(&valid_path.path)?);
+        Ok(valid_path)
+    }
+
+    /// Validates that the path is allowed and refers to an existing file.
+    /// The returned path carries the file metadata.
+    pub fn validate_existing_file(
+        &self,
+        requested_path: &Path,
+        allowed_directories: Arc<Vec<PathBuf>>,
+    ) -> ServiceResult<ValidatedPath> {
+        let valid_path = self.validate_existing_path(requested_path, allowed_directories)?;
+        if !valid_path
+            .metadata()
+            .is_some_and(|metadata| metadata.is_file())
+        {
+            return Err(ServiceError::NotAFile(valid_path.display().to_string()));
+        }
+        Ok(valid_path)
+    }
+
+    /// Validates that the path is allowed and refers to an existing directory.
+    /// The returned path carries the directory metadata.
+    pub fn validate_dir(
+        &self,
+        requested_path: &Path,
+        allowed_directories: Arc<Vec<PathBuf>>,
+    ) -> ServiceResult<ValidatedPath> {
+        let valid_path = self.validate_existing_path(requested_path, allowed_directories)?;
+        if !valid_path
+            .metadata()
+            .is_some_and(|metadata| metadata.is_dir())
+        {
+            return Err(ServiceError::NotADirectory(
+                valid_path.display().to_string(),
+            ));
+        }
+        Ok(valid_path)
+    }
+
+    // Get file stats
+    pub async fn get_file_stats(&self, file_path: &Path) -> ServiceResult<FileInfo> {
+        self.get_file_stats_with(file_path, true).await
+    }
+
+    /// Returns file stats, either of the symlink target (`follow_symlinks`) or of the
+    /// symlink itself. For paths that are not symlinks both modes are identical.
+    pub async fn get_file_stats_with(
+        &self,
+        file_path: &Path,
+        follow_symlinks: bool,
+    ) -> ServiceResult<FileInfo> {
+        let allowed_directories = self.allowed_directories().await;
+        let metadata = if follow_symlinks {
+            let valid_path = self.validate_existing_path(file_path, allowed_directories)?;
+            match valid_path.metadata {
+                Some(metadata) => metadata,
+                None => fs::metadata(&valid_path.path)?,
+            }
+        } else {
+            let valid_path = self.validate_path(file_path, allowed_directories)?;
+            fs::symlink_metadata(&valid_path)?
+        };
+
+        let size = metadata.len();
+        let created = metadata.created().ok();
+        let modified = metadata.modified().ok();
+        let accessed = metadata.accessed().ok();
+        let is_directory = metadata.is_dir();
+        let is_file = metadata.is_file();
+        let is_symlink = metadata.is_symlink();
+
+        Ok(FileInfo {
+            size,
+            created,
+            modified,
+            accessed,
+            is_directory,
+            is_file,
+            is_symlink,
+            metadata,
+        })
+    }
+
+    /// Checks whether each allowed directory still exists and can be read and written, e.g.
+    /// after mounts changed since startup.
+    pub async fn allowed_directory_statuses(&self) -> Vec<AllowedDirectoryStatus> {
+        let allowed_directories = self.allowed_directories().await;
+        let statuses = allowed_directories.iter().map(|dir| async move {
+            let is_symlink = self.symlinked_directories.contains(dir)
+                || tokio::fs::symlink_metadata(dir)
+                    .await
+                    .is_ok_and(|metadata| metadata.is_symlink());
+            let exists = tokio::fs::metadata(dir)
+                .await
+                .is_ok_and(|metadata| metadata.is_dir());
+            let readable = exists && tokio::fs::read_dir(dir).await.is_ok();
+            let writable = exists && is_writable_dir(dir);
+            AllowedDirectoryStatus {
+                path: dir.clone(),
+                exists,
+                readable,
+                writable,
+                is_symlink,
+            }
+        });
+        futures::future::join_all(statuses).await
+    }
+
+    /// Classifies each path with a single `lstat`, concurrently. Missing paths are reported as
+    /// such rather than as errors, and paths outside the allowed directories are not inspected.
+    pub async fn probe_paths(&self, paths: &[String]) -> Vec<ProbedPath> {
+        let allowed_directories = self.allowed_directories().await;
+        let probes = paths.iter().map(|path| {
+            let validated = self.validate_path(Path::new(path), allowed_directories.clone());
+            async move {
+                let Ok(valid_path) = validated else {
+                    return ProbedPath {
+                        path: path.clone(),
+                        allowed: false,
+                        exists: None,
+                        kind: None,
+                        size: None,
+                    };
+                };
+                record_entries_visited(1);
+                let (kind, size) = match tokio::fs::symlink_metadata(&valid_path).await {
+                    Ok(metadata) if metadata.is_file() => (PathKind::File, Some(metadata.len())),
+                    Ok(metadata) if metadata.is_dir() => (PathKind::Dir, None),
+                    Ok(metadata) if metadata.is_symlink() => (PathKind::Symlink, None),
+                    Ok(_) => (PathKind::Other, None),
+                    Err(_) => (PathKind::Missing, None),
+                };
+                ProbedPath {
+                    path: path.clone(),
+                    allowed: true,
+                    exists: Some(kind != PathKind::Missing),
+                    kind: Some(kind),
+                    size,
+                }
+            }
+        });
+        futures::future::join_all(probes).await
+    }
+
+    /// Returns file stats for every entry under `root_path` whose name matches the glob
+    /// `pattern`, sorted by path. Patterns are resolved like in `search_files`.
+    pub async fn get_file_stats_matching(
+        &self,
+        root_path: &Path,
+        pattern: String,
+        follow_symlinks: bool,
+    ) -> ServiceResult<Vec<(PathBuf, FileInfo)>> {
+        let mut paths: Vec<PathBuf> = self
+            .search_files(root_path, pattern, vec![], None, None)
+            .await?
+            .into_iter()
+            .map(|entry| entry.into_path())
+            .collect();
+        paths.sort();
+
+        let mut result = Vec::with_capacity(paths.len());
+        for path in paths {
+            let stats = self.get_file_stats_with(&path, follow_symlinks).await?;
+            result.push((path, stats));
+        }
+        Ok(result)
+    }
+
+    fn detect_line_ending(&self, text: &str) -> &str {
+        if text.contains("\r\n") {
+            "\r\n"
+        } else if text.contains('\r') {
+            "\r"
+        } else {
+            "\n"
+        }
+    }
+
+    pub async fn zip_directory(
+        &self,
+        input_dir: String,
+        pattern: String,
+        target_zip_file: String,
+    ) -> ServiceResult<String> {
+        self.zip_directory_with(input_dir, pattern, target_zip_file, false)
+            .await
+    }
+
+    /// Same as [`zip_directory`](Self::zip_directory). Entries that cannot be walked are listed
+    /// in an `Errors:` section of the result, or fail the call before the archive is created
+    /// when `strict` is set.
+    pub async fn zip_directory_with(
+        &self,
+        input_dir: String,
+        pattern: String,
+        target_zip_file: String,
+        strict: bool,
+    ) -> ServiceResult<String> {
+        let allowed_directories = self.allowed_directories().await;
+        let valid_dir_path =
+            self.validate_path(Path::new(&input_dir), allowed_directories.clone())?;
+
+        let input_dir_str = &valid_dir_path
+            .as_os_str()
+            .to_str()
+            .ok_or(std::io::Error::new(
+                std::io::ErrorKind::InvalidInput,
                 "Invalid UTF-8 in file name",
             ))?;
 
-        let target_path =
-            self.validate_path(Path::new(&target_zip_file), allowed_directories.clone())?;
+        let target_path =
+            self.validate_path(Path::new(&target_zip_file), allowed_directories.clone())?;
+
+        if target_path.exists() {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::AlreadyExists,
+                format!("'{target_zip_file}' already exists!"),
+            )
+            .into());
+        }
+
+        let updated_pattern = if pattern.contains('*') {
+            pattern.to_lowercase()
+        } else {
+            format!("*{}*", &pattern.to_lowercase())
+        };
+
+        let glob_pattern = &updated_pattern;
+
+        let mut walk_errors = WalkErrors::default();
+        let entries: Vec<_> = WalkDir::new(&valid_dir_path)
+            .follow_links(true)
+            .into_iter()
+            .filter_map(|entry| entry.map_err(|err| walk_errors.record(err)).ok())
+            .inspect(|_| record_entries_visited(1))
+            .filter_map(|entry| {
+                let full_path = entry.path();
+
+                self.validate_path(full_path, allowed_directories.clone())
+                    .ok()
+                    .and_then(|path| {
+                        if *path != *valid_dir_path
+                            && glob_match(glob_pattern, path.display().to_string().as_ref())
+                        {
+                            Some(path)
+                        } else {
+                            None
+                        }
+                    })
+            })
+            .collect();
+
+        if strict {
+            walk_errors.check_strict()?;
+        }
+
+        self.check_write_extension(&target_path)?;
+        // The archive is at most about as large as its uncompressed input
+        let input_size = total_file_size(&entries);
+        self.check_disk_space(&target_path, input_size)?;
+        self.write_budget.check(input_size, 0)?;
+        self.read_cache.invalidate(&target_path);
+        let zip_file = File::create(&target_path).await?;
+        let mut zip_writer = ZipFileWriter::new(zip_file.compat());
+
+        let written: ServiceResult<_> = async {
+            for entry_path_buf in &entries {
+                if entry_path_buf.is_dir() {
+                    continue;
+                }
+                let entry_path = entry_path_buf.as_path();
+                let entry_str = entry_path.as_os_str().to_str().ok_or(std::io::Error::new(
+                    std::io::ErrorKind::InvalidInput,
+                    "Invalid UTF-8 in file name",
+                ))?;
+
+                if !entry_str.starts_with(input_dir_str) {
+                    return Err(std::io::Error::new(
+                        std::io::ErrorKind::InvalidInput,
+                        "Entry file path does not start with base input directory path.",
+                    )
+                    .into());
+                }
+
+                let entry_str = &entry_str[input_dir_str.len() + 1..];
+                write_zip_entry(entry_str, entry_path, &mut zip_writer).await?;
+            }
+            Ok(zip_writer.close().await?)
+        }
+        .await;
+        let z_file = match written {
+            Err(err) if is_storage_full(&err) => {
+                return Err(self.discard_partial_file(&target_path).await);
+            }
+            written => written?,
+        };
+        let zip_file_size = if let Ok(meta_data) = z_file.into_inner().metadata().await {
+            record_bytes_written(meta_data.len());
+            self.write_budget.record(meta_data.len(), 0);
+            self.byte_format.format(meta_data.len())
+        } else {
+            "unknown".to_string()
+        };
+        let mut result_message = format!(
+            "Successfully compressed '{}' directory into '{}' ({}).",
+            input_dir,
+            target_path.display(),
+            zip_file_size
+        );
+        if !walk_errors.is_empty() {
+            result_message.push_str(&format!(
+                "\n{} entries could not be read and were skipped ({}).\n{}",
+                walk_errors.len(),
+                walk_errors.describe_counts(),
+                walk_errors.render()
+            ));
+        }
+        Ok(result_message)
+    }
+
+    pub async fn zip_files(
+        &self,
+        input_files: Vec<String>,
+        target_zip_file: String,
+    ) -> ServiceResult<String> {
+        let file_count = input_files.len();
+
+        if file_count == 0 {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::InvalidInput,
+                "No file(s) to zip. The input files array is empty.",
+            )
+            .into());
+        }
+        let allowed_directories = self.allowed_directories().await;
+        let target_path =
+            self.validate_path(Path::new(&target_zip_file), allowed_directories.clone())?;
+
+        if target_path.exists() {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::AlreadyExists,
+                format!("'{target_zip_file}' already exists!"),
+            )
+            .into());
+        }
+
+        let source_paths = input_files
+            .iter()
+            .map(|p| self.validate_path(Path::new(p), allowed_directories.clone()))
+            .collect::<Result<Vec<_>, _>>()?;
+
+        self.check_write_extension(&target_path)?;
+        let input_size = total_file_size(&source_paths);
+        self.check_disk_space(&target_path, input_size)?;
+        self.write_budget.check(input_size, 0)?;
+        self.read_cache.invalidate(&target_path);
+        let zip_file = File::create(&target_path).await?;
+        let mut zip_writer = ZipFileWriter::new(zip_file.compat());
+        let written: ServiceResult<_> = async {
+            for path in source_paths {
+                let filename = path.file_name().ok_or(std::io::Error::new(
+                    std::io::ErrorKind::InvalidInput,
+                    "Invalid path!",
+                ))?;
+
+                let filename = filename.to_str().ok_or(std::io::Error::new(
+                    std::io::ErrorKind::InvalidInput,
+                    "Invalid UTF-8 in file name",
+                ))?;
+
+                write_zip_entry(filename, &path, &mut zip_writer).await?;
+            }
+            Ok(zip_writer.close().await?)
+        }
+        .await;
+        let z_file = match written {
+            Err(err) if is_storage_full(&err) => {
+                return Err(self.discard_partial_file(&target_path).await);
+            }
+            written => written?,
+        };
+
+        let zip_file_size = if let Ok(meta_data) = z_file.into_inner().metadata().await {
+            record_bytes_written(meta_data.len());
+            self.write_budget.record(meta_data.len(), 0);
+            self.byte_format.format(meta_data.len())
+        } else {
+            "unknown".to_string()
+        };
+
+        let result_message = format!(
+            "Successfully compressed {} {} into '{}' ({}).",
+            file_count,
+            if file_count == 1 { "file" } else { "files" },
+            target_path.display(),
+            zip_file_size
+        );
+        Ok(result_message)
+    }
+
+    pub async fn unzip_file(&self, zip_file: &str, target_dir: &str) -> ServiceResult<String> {
+        self.unzip_file_with(zip_file, target_dir, SymlinkPolicy::default())
+            .await
+    }
+
+    /// Extracts `zip_file` into `target_dir`, handling symbolic link entries according to
+    /// `on_symlink`. The disposition of each link is listed in the returned message.
+    pub async fn unzip_file_with(
+        &self,
+        zip_file: &str,
+        target_dir: &str,
+        on_symlink: SymlinkPolicy,
+    ) -> ServiceResult<String> {
+        let allowed_directories = self.allowed_directories().await;
+
+        let zip_file = self.validate_path(Path::new(&zip_file), allowed_directories.clone())?;
+        let target_dir_path = self.validate_path(Path::new(target_dir), allowed_directories)?;
+        if !zip_file.exists() {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::NotFound,
+                "Zip file does not exists.",
+            )
+            .into());
+        }
+
+        if target_dir_path.exists() {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::AlreadyExists,
+                format!("'{target_dir}' directory already exists!"),
+            )
+            .into());
+        }
+
+        self.read_cache.invalidate(&target_dir_path);
+        let file = BufReader::new(File::open(zip_file).await?);
+        let mut zip = ZipFileReader::with_tokio(file).await?;
+
+        let entries: Vec<(String, bool)> = zip
+            .file()
+            .entries()
+            .iter()
+            .map(|entry| {
+                Ok((
+                    entry.filename().as_str()?.to_string(),
+                    is_zip_symlink(entry),
+                ))
+            })
+            .collect::<ServiceResult<_>>()?;
+
+        // Reject the whole archive before extracting anything
+        for (entry_name, _) in &entries {
+            if archive_entry_path(entry_name).is_none() {
+                return Err(ServiceError::FromString(format!(
+                    "Refusing to extract '{entry_name}': the entry path is absolute or escapes the extraction root."
+                )));
+            }
+        }
+        for (entry_name, _) in entries.iter().filter(|(name, _)| !name.ends_with('/')) {
+            self.check_write_extension(&target_dir_path.join(entry_name))?;
+        }
+        let uncompressed_size = zip
+            .file()
+            .entries()
+            .iter()
+            .map(|entry| entry.uncompressed_size())
+            .sum();
+        self.check_disk_space(&target_dir_path, uncompressed_size)?;
+        // The sizes in the headers are only an estimate; the entries record what they extract
+        self.write_budget.check(uncompressed_size, 0)?;
+
+        let extracted: ServiceResult<_> = async {
+            let mut file_count = 0;
+            let mut symlink_dispositions = vec![];
+            // Symlinks created so far, by path relative to the root, with their resolved targets,
+            // and the paths their resolutions go through
+            #[cfg_attr(not(unix), allow(unused_mut))]
+            let mut links: HashMap<PathBuf, PathBuf> = HashMap::new();
+            #[cfg_attr(not(unix), allow(unused_mut))]
+            let mut traversed: HashSet<PathBuf> = HashSet::new();
+
+            for (index, (entry_name, is_symlink)) in entries.iter().enumerate() {
+                // Checked for every entry before extracting
+                let relative_path = archive_entry_path(entry_name).unwrap_or_default();
+                // Writing below a created symlink would write wherever it points
+                if let Some(link) = relative_path
+                    .ancestors()
+                    .skip(1)
+                    .find(|ancestor| links.contains_key(*ancestor))
+                {
+                    return Err(ServiceError::FromString(format!(
+                        "Refusing to extract '{entry_name}': '{}' is a symbolic link extracted from the archive.",
+                        link.display()
+                    )));
+                }
+                let entry_path = target_dir_path.join(entry_name);
+                // Ensure the parent directory exists
+                if let Some(parent) = entry_path.parent() {
+                    tokio::fs::create_dir_all(parent).await?;
+                }
+
+                if !is_symlink {
+                    self.extract_zip_entry(&mut zip, index, &entry_path).await?;
+                    file_count += 1;
+                    continue;
+                }
+
+                // The content of a symlink entry is the link target
+                let mut link_target = String::new();
+                zip.reader_without_entry(index)
+                    .await?
+                    .compat()
+                    .read_to_string(&mut link_target)
+                    .await?;
+                let mut visited = HashSet::new();
+                let resolved = resolve_archive_link(entry_name, &link_target, &links, &mut visited);
+
+                let disposition = match on_symlink {
+                    SymlinkPolicy::Skip => "skipped".to_string(),
+                    SymlinkPolicy::Materialize => {
+                        let target_index = resolved.as_ref().and_then(|resolved| {
+                            entries
+                                .iter()
+                                .position(|(name, _)| Path::new(name) == resolved.as_path())
+                        });
+                        match (resolved.is_some(), target_index) {
+                            (false, _) => "skipped (target is outside the archive)".to_string(),
+                            (true, None) => "skipped (target not found in archive)".to_string(),
+                            (true, Some(target_index))
+                                if entries[target_index].1
+                                    || entries[target_index].0.ends_with('/') =>
+                            {
+                                "skipped (target is not a regular file)".to_string()
+                            }
+                            (true, Some(target_index)) => {
+                                self.extract_zip_entry(&mut zip, target_index, &entry_path)
+                                    .await?;
+                                file_count += 1;
+                                "materialized".to_string()
+                            }
+                        }
+                    }
+                    SymlinkPolicy::Create => match resolved {
+                        None => "skipped (target escapes the extraction root)".to_string(),
+                        // An earlier link resolving through this path would be redirected
+                        Some(_) if traversed.contains(&relative_path) => {
+                            "skipped (an earlier symlink resolves through it)".to_string()
+                        }
+                        #[cfg(unix)]
+                        Some(resolved) => {
+                            tokio::fs::symlink(&link_target, &entry_path).await?;
+                            links.insert(relative_path, resolved);
+                            traversed.extend(visited);
+                            file_count += 1;
+                            "created".to_string()
+                        }
+                        #[cfg(not(unix))]
+                        Some(_) => {
+                            "skipped (symlinks are not supported on this platform)".to_string()
+                        }
+                    },
+                };
+                symlink_dispositions
+                    .push(format!("  - {entry_name} -> {link_target}: {disposition}"));
+            }
+            Ok((file_count, symlink_dispositions))
+        }
+        .await;
+        let (file_count, symlink_dispositions) = match extracted {
+            Err(err) if is_storage_full(&err) => {
+                return Err(self.discard_partial_extraction(&target_dir_path).await);
+            }
+            extracted => extracted?,
+        };
+
+        let mut result_message = format!(
+            "Successfully extracted {} {} into '{}'.",
+            file_count,
+            if file_count == 1 { "file" } else { "files" },
+            target_dir_path.display()
+        );
+        if !symlink_dispositions.is_empty() {
+            result_message.push_str("\nSymlinks:\n");
+            result_message.push_str(&symlink_dispositions.join("\n"));
+        }
+
+        Ok(result_message)
+    }
+
+    /// Verifies the zip archive `archive_path` without extracting it. The central directory and
+    /// the local header of every entry are always read; with `deep`, each entry is also
+    /// decompressed to a sink and its size and CRC checked against the central directory.
+    /// Fails if the central directory cannot be read; failures of single entries are reported
+    /// in the result.
+    pub async fn verify_archive(
+        &self,
+        archive_path: &Path,
+        deep: bool,
+    ) -> ServiceResult<ArchiveVerification> {
+        let allowed_directories = self.allowed_directories().await;
+        let valid_path = self.validate_existing_file(archive_path, allowed_directories)?;
+        let file = BufReader::new(File::open(valid_path.as_path()).await?);
+        let mut zip = ZipFileReader::with_tokio(file).await.map_err(|err| {
+            ServiceError::FromString(format!(
+                "The central directory of '{}' is unreadable: {err}",
+                valid_path.display()
+            ))
+        })?;
+
+        let entries: Vec<(String, u64, u32)> = zip
+            .file()
+            .entries()
+            .iter()
+            .map(|entry| {
+                let name = entry.filename();
+                (
+                    name.as_str()
+                        .map(str::to_string)
+                        .unwrap_or_else(|_| String::from_utf8_lossy(name.as_bytes()).into_owned()),
+                    entry.uncompressed_size(),
+                    entry.crc32(),
+                )
+            })
+            .collect();
+
+        let mut verification = ArchiveVerification {
+            deep,
+            ..Default::default()
+        };
+        for (index, (name, size, crc)) in entries.into_iter().enumerate() {
+            verification.entries += 1;
+            verification.uncompressed_bytes += size;
+            if let Err(reason) = Self::verify_zip_entry(&mut zip, index, size, crc, deep).await {
+                verification.failures.push(EntryFailure {
+                    index,
+                    name,
+                    reason,
+                });
+            }
+        }
+        Ok(verification)
+    }
+
+    async fn verify_zip_entry<R>(
+        zip: &mut ZipFileReader<R>,
+        index: usize,
+        size: u64,
+        crc: u32,
+        deep: bool,
+    ) -> Result<(), String>
+    where
+        R: tokio::io::AsyncBufRead + tokio::io::AsyncSeek + Unpin,
+    {
+        let mut reader = zip
+            .reader_without_entry(index)
+            .await
+            .map_err(|err| format!("local header unreadable: {err}"))?;
+        if !deep {
+            return Ok(());
+        }
+        let read = tokio::io::copy(&mut (&mut reader).compat(), &mut tokio::io::sink())
+            .await
+            .map_err(|err| format!("decompression failed: {err}"))?;
+        record_bytes_read(read);
+        if read != size {
+            return Err(format!("decompressed to {read} bytes, {size} expected"));
+        }
+        if reader.compute_hash() != crc {
+            return Err("failed CRC check".to_string());
+        }
+        Ok(())
+    }
+
+    async fn extract_zip_entry<R>(
+        &self,
+        zip: &mut ZipFileReader<R>,
+        index: usize,
+        entry_path: &Path,
+    ) -> ServiceResult<()>
+    where
+        R: tokio::io::AsyncBufRead + tokio::io::AsyncSeek + Unpin,
+    {
+        // Stop once the entries extracted so far used up the budget, whatever the headers say
+        let declared_size = zip
+            .file()
+            .entries()
+            .get(index)
+            .map_or(0, |entry| entry.uncompressed_size());
+        self.write_budget.check(declared_size, 0)?;
+        let reader = zip.reader_without_entry(index).await?;
+        let mut compat_reader = reader.compat();
+        let mut output_file = self.open_for_write(entry_path).await?;
+
+        let bytes_written = tokio::io::copy(&mut compat_reader, &mut output_file).await?;
+        output_file.flush().await?;
+        record_bytes_written(bytes_written);
+        self.write_budget.record(bytes_written, 0);
+        Ok(())
+    }
+
+    pub fn mime_from_path(&self, path: &Path) -> ServiceResult<infer::Type> {
+        let is_svg = path
+            .extension()
+            .is_some_and(|e| e.to_str().is_some_and(|s| s == "svg"));
+        // consider it is a svg file as we cannot detect svg from bytes pattern
+        if is_svg {
+            return Ok(infer::Type::new(
+                infer::MatcherType::Image,
+                "image/svg+xml",
+                "svg",
+                |_: &[u8]| true,
+            ));
+
+            // infer::Type::new(infer::MatcherType::Image, "", "svg",);
+        }
+        let kind = infer::get_from_path(path)?.ok_or(ServiceError::FromString(
+            "File tyle is unknown!".to_string(),
+        ))?;
+        Ok(kind)
+    }
 
-        if target_path.exists() {
-            return Err(std::io::Error::new(
-                std::io::ErrorKind::AlreadyExists,
-                format!("'{target_zip_file}' already exists!"),
-            )
-            .into());
+    pub fn filesize_in_range(
+        &self,
+        file_size: u64,
+        min_bytes: Option<u64>,
+        max_bytes: Option<u64>,
+    ) -> bool {
+        if min_bytes.is_none() && max_bytes.is_none() {
+            return true;
+        }
+        match (min_bytes, max_bytes) {
+            (_, Some(max)) if file_size > max => false,
+            (Some(min), _) if file_size < min => false,
+            _ => true,
         }
+    }
 
-        let updated_pattern = if pattern.contains('*') {
-            pattern.to_lowercase()
+    pub async fn validate_file_size<P: AsRef<Path>>(
+        &self,
+        path: P,
+        min_bytes: Option<usize>,
+        max_bytes: Option<usize>,
+    ) -> ServiceResult<()> {
+        if min_bytes.is_none() && max_bytes.is_none() {
+            return Ok(());
+        }
+
+        let file_size = metadata(&path).await?.len() as usize;
+        check_file_size(file_size, min_bytes, max_bytes)
+    }
+
+    pub async fn read_media_files(
+        &self,
+        paths: Vec<String>,
+        max_bytes: Option<usize>,
+    ) -> ServiceResult<Vec<(infer::Type, String)>> {
+        self.take_fast_path(FAST_PATH_CONCURRENT);
+        let results = stream::iter(paths)
+            .map(|path| async {
+                self.read_media_file(Path::new(&path), max_bytes)
+                    .await
+                    .map_err(|e| (path, e))
+            })
+            .buffer_unordered(MAX_CONCURRENT_FILE_READ) // Process up to MAX_CONCURRENT_FILE_READ files concurrently
+            .filter_map(|result| async move { result.ok() })
+            .collect::<Vec<_>>()
+            .await;
+        Ok(results)
+    }
+
+    pub async fn read_media_file(
+        &self,
+        file_path: &Path,
+        max_bytes: Option<usize>,
+    ) -> ServiceResult<(infer::Type, String)> {
+        let allowed_directories = self.allowed_directories().await;
+        let valid_path = self.validate_existing_file(file_path, allowed_directories)?;
+        if let Some(metadata) = valid_path.metadata() {
+            check_file_size(metadata.len() as usize, None, max_bytes)?;
+        }
+        let kind = self.mime_from_path(&valid_path)?;
+        let content = self.read_file_as_base64(&valid_path).await?;
+        Ok((kind, content))
+    }
+
+    /// Reads a PNG, JPEG or WebP image and downscales it so that neither side exceeds
+    /// `max_dimension`, preserving the aspect ratio. Images with more pixels than
+    /// `PerformanceConfig::max_image_pixels` are rejected before being decoded.
+    pub async fn read_image_scaled(
+        &self,
+        file_path: &Path,
+        max_bytes: Option<usize>,
+        max_dimension: u32,
+    ) -> ServiceResult<ScaledImage> {
+        let allowed_directories = self.allowed_directories().await;
+        let valid_path = self.validate_existing_file(file_path, allowed_directories)?;
+        if let Some(metadata) = valid_path.metadata() {
+            check_file_size(metadata.len() as usize, None, max_bytes)?;
+        }
+        let kind = self.mime_from_path(&valid_path)?;
+        let format = scalable_format(kind.mime_type()).ok_or_else(|| {
+            ServiceError::FromString(format!(
+                "Only PNG, JPEG and WebP images can be resized (mime:{}).",
+                kind.mime_type()
+            ))
+        })?;
+
+        let mut bytes = Vec::new();
+        self.open_for_read(&valid_path)
+            .await?
+            .read_to_end(&mut bytes)
+            .await?;
+        record_bytes_read(bytes.len() as u64);
+
+        let max_pixels = self.performance_config.max_image_pixels;
+        tokio::task::spawn_blocking(move || {
+            downscale_image(bytes, format, max_dimension.max(1), max_pixels)
+        })
+        .await
+        .map_err(|err| ServiceError::FromString(err.to_string()))?
+    }
+
+    // reads file as base64 efficiently in a streaming manner
+    async fn read_file_as_base64(&self, file_path: &Path) -> ServiceResult<String> {
+        let file = self.open_for_read(file_path).await?;
+        let mut reader = self.stream_reader(file);
+
+        let mut output = Vec::new();
+        {
+            // Wrap output Vec<u8> in a Base64 encoder writer
+            let mut encoder = EncoderWriter::new(&mut output, &general_purpose::STANDARD);
+
+            let mut buffer = vec![0u8; self.performance_config.stream_buffer_size];
+            loop {
+                let n = reader.read(&mut buffer).await?;
+                if n == 0 {
+                    break;
+                }
+                record_bytes_read(n as u64);
+                // Write raw bytes to the Base64 encoder
+                encoder.write_all(&buffer[..n])?;
+            }
+            // Make sure to flush any remaining bytes
+            encoder.flush()?;
+        } // drop encoder before consuming output
+
+        // Convert the Base64 bytes to String (safe UTF-8)
+        let base64_string =
+            String::from_utf8(output).map_err(|err| ServiceError::FromString(format!("{err}")))?;
+        Ok(base64_string)
+    }
+
+    /// Reads a text file, decoding it to UTF-8 when it is in another encoding. Fails for files
+    /// classified as binary or too large.
+    pub async fn read_text_file(&self, file_path: &Path) -> ServiceResult<String> {
+        let allowed_directories = self.allowed_directories().await;
+        let valid_path = self.validate_path(file_path, allowed_directories)?;
+        let class = self.classify_file(&valid_path).await?;
+        self.read_classified_text(&valid_path, &class).await
+    }
+
+    /// Classifies a validated file as text, binary, too large or unreadable with
+    /// [`text_probe::classify`], reading at most [`text_probe::TEXT_PROBE_SIZE`] bytes.
+    pub async fn classify_file(&self, path: &Path) -> ServiceResult<FileClass> {
+        let path = path.to_path_buf();
+        let config = self.performance_config.clone();
+        tokio::task::spawn_blocking(move || classify(&path, &config))
+            .await
+            .map_err(|err| ServiceError::FromString(err.to_string()))
+    }
+
+    /// Reads a validated file already classified by [`classify_file`](Self::classify_file) as
+    /// text. Unreadable files are read anyway, so the error of the read is returned.
+    async fn read_classified_text(&self, path: &Path, class: &FileClass) -> ServiceResult<String> {
+        let not_text = || ServiceError::NotText {
+            path: path.to_path_buf(),
+            class: class.clone(),
+        };
+        match class {
+            FileClass::Binary | FileClass::TooLarge(_) => return Err(not_text()),
+            FileClass::TextUtf8 | FileClass::Unreadable(_) => {
+                let content = self.read_to_string_with_flags(path).await?;
+                record_bytes_read(content.len() as u64);
+                return Ok(content);
+            }
+            FileClass::TextOtherEncoding(_) => {}
+        }
+        let mut bytes = vec![];
+        self.open_for_read(path)
+            .await?
+            .read_to_end(&mut bytes)
+            .await?;
+        record_bytes_read(bytes.len() as u64);
+        self.telemetry.increment(Counter::TextDecoded);
+        decode_text(bytes, class).ok_or_else(not_text)
+    }
+
+    /// Reads every regular text file in `root_path` (and its subdirectories when `recursive`),
+    /// in alphabetical order of their relative paths. Binary files and files above the
+    /// per-file limit are skipped; once the total limit is reached, the remaining files are
+    /// skipped. Fails if the directory holds more than `limits.max_files` files.
+    pub async fn read_directory(
+        &self,
+        root_path: &Path,
+        recursive: bool,
+        limits: ReadDirectoryLimits,
+    ) -> ServiceResult<DirectoryContents> {
+        let allowed_directories = self.allowed_directories().await;
+        let valid_path = self.validate_dir(root_path, allowed_directories.clone())?;
+
+        let mut files: Vec<(PathBuf, PathBuf, u64)> = WalkDir::new(&valid_path)
+            .min_depth(1)
+            .max_depth(if recursive { usize::MAX } else { 1 })
+            .into_iter()
+            .filter_map(|entry| entry.ok())
+            .filter(|entry| entry.file_type().is_file())
+            .filter(|entry| {
+                self.validate_path(entry.path(), allowed_directories.clone())
+                    .is_ok()
+            })
+            .filter_map(|entry| {
+                let size = entry.metadata().ok()?.len();
+                let relative = entry.path().strip_prefix(&valid_path).ok()?.to_path_buf();
+                Some((relative, entry.into_path(), size))
+            })
+            // One file past the limit is enough to refuse, so the walk stops there
+            .take(usize::try_from(limits.max_files.saturating_add(1)).unwrap_or(usize::MAX))
+            .collect();
+        record_entries_visited(files.len() as u64);
+
+        if files.len() as u64 > limits.max_files {
+            return Err(ServiceError::FromString(format!(
+                "'{}' contains more than {} files, the limit. Narrow the path or raise max_files.",
+                root_path.display(),
+                limits.max_files
+            )));
+        }
+        files.sort_by(|a, b| a.0.cmp(&b.0));
+
+        let mut contents = DirectoryContents::default();
+        let mut total_bytes = 0u64;
+        let mut budget_exhausted = false;
+        for (path, full_path, size) in files {
+            if budget_exhausted {
+                contents.files.push(DirectoryFile::Skipped {
+                    path,
+                    reason: format!(
+                        "total limit of {} reached",
+                        self.byte_format.format(limits.max_total_bytes)
+                    ),
+                });
+                continue;
+            }
+            let max_file_bytes = match self.performance_config.max_text_file_bytes {
+                0 => limits.max_file_bytes,
+                max_text_file_bytes => limits.max_file_bytes.min(max_text_file_bytes),
+            };
+            if size > max_file_bytes {
+                contents.files.push(DirectoryFile::Skipped {
+                    path,
+                    reason: format!(
+                        "{} exceeds the per-file limit of {}",
+                        self.byte_format.format(size),
+                        self.byte_format.format(max_file_bytes)
+                    ),
+                });
+                continue;
+            }
+
+            let bytes = tokio::fs::read(&full_path).await?;
+            record_bytes_read(bytes.len() as u64);
+            let class = classify_bytes(&bytes);
+            let content = match decode_text(bytes, &class) {
+                Some(content) => content,
+                None => {
+                    contents.files.push(DirectoryFile::Skipped {
+                        path,
+                        reason: "binary file".to_string(),
+                    });
+                    continue;
+                }
+            };
+
+            if total_bytes + content.len() as u64 > limits.max_total_bytes {
+                budget_exhausted = true;
+                contents.files.push(DirectoryFile::Skipped {
+                    path,
+                    reason: format!(
+                        "total limit of {} reached",
+                        self.byte_format.format(limits.max_total_bytes)
+                    ),
+                });
+                continue;
+            }
+            total_bytes += content.len() as u64;
+            contents
+                .files
+                .push(DirectoryFile::Content { path, content });
+        }
+
+        Ok(contents)
+    }
+
+    /// Reads a text file like [`read_text_file`](Self::read_text_file), but if the file was
+    /// already returned by a deduplicated read during this session and is unchanged since,
+    /// returns a short stub with the time of that read and the content hash instead.
+    /// `force` always returns the full content.
+    pub async fn read_text_file_deduped(
+        &self,
+        file_path: &Path,
+        force: bool,
+    ) -> ServiceResult<DedupedRead> {
+        let allowed_directories = self.allowed_directories().await;
+        let valid_path = self.validate_path(file_path, allowed_directories)?;
+        let class = self.classify_file(&valid_path).await?;
+        let content = self.read_classified_text(&valid_path, &class).await?;
+
+        let metadata = tokio::fs::metadata(&valid_path).await?;
+        let modified = metadata.modified().ok();
+        let sha256 = format!("{:x}", Sha256::digest(content.as_bytes()));
+        if !force {
+            if let Some(stub) =
+                self.read_cache
+                    .unchanged_stub(&valid_path, modified, metadata.len(), &sha256)
+            {
+                self.telemetry.increment(Counter::ReadCacheHit);
+                return Ok(DedupedRead::Unchanged(stub));
+            }
+        }
+        self.telemetry.increment(Counter::ReadCacheMiss);
+        self.read_cache
+            .record(valid_path.into_path_buf(), modified, metadata.len(), sha256);
+        Ok(DedupedRead::Content(content))
+    }
+
+    /// Lists every definition in a source file, public or private, with its line span.
+    pub async fn list_definitions(&self, file_path: &Path) -> ServiceResult<Vec<DefinitionSpan>> {
+        self.list_definitions_with_options(file_path, false).await
+    }
+
+    /// Same as [`list_definitions`](Self::list_definitions), optionally listing the import
+    /// declarations (`use`, `extern crate`, `mod name;`, `import`) too, with the kind `import`.
+    pub async fn list_definitions_with_options(
+        &self,
+        file_path: &Path,
+        include_imports: bool,
+    ) -> ServiceResult<Vec<DefinitionSpan>> {
+        let content = self.read_text_file(file_path).await?;
+        let style = BlockStyle::from_path(file_path);
+        let syntax = CommentSyntax::from_path(file_path);
+        Ok(if include_imports {
+            list_definitions_with_imports(&content, style, syntax.as_ref())
+        } else {
+            list_definitions(&content, style, syntax.as_ref())
+        })
+    }
+
+    /// Summarizes a CSV/TSV file: its columns with inferred types, its row count and its first
+    /// and last rows. The file is streamed, so quoted fields spanning several lines are handled
+    /// and only `options.scan_budget` bytes are read to count the rows.
+    pub async fn preview_tabular_file(
+        &self,
+        file_path: &Path,
+        options: TabularOptions,
+    ) -> ServiceResult<TabularPreview> {
+        let allowed_directories = self.allowed_directories().await;
+        let valid_path = self.validate_existing_path(file_path, allowed_directories)?;
+        let path = valid_path.to_path_buf();
+        tokio::task::spawn_blocking(move || {
+            let file_size = fs::metadata(&path)?.len();
+            let delimiter = match options.delimiter {
+                Some(delimiter) => delimiter,
+                None => {
+                    let mut sample = Vec::with_capacity(DELIMITER_SAMPLE_SIZE);
+                    let mut head =
+                        std::io::Read::take(fs::File::open(&path)?, DELIMITER_SAMPLE_SIZE as u64);
+                    std::io::Read::read_to_end(&mut head, &mut sample)?;
+                    detect_delimiter(&sample)
+                }
+            };
+            let file = std::io::BufReader::new(fs::File::open(&path)?);
+            preview_tabular(file, file_size, delimiter, &options)
+        })
+        .await
+        .map_err(|err| ServiceError::FromString(err.to_string()))?
+    }
+
+    /// Reports, best effort, whether `file_path` is locked or open in another process, or
+    /// buffered by an `open_file` handle of this server, so that an edit may conflict.
+    pub async fn check_file_lock(&self, file_path: &Path) -> ServiceResult<FileLockReport> {
+        let allowed_directories = self.allowed_directories().await;
+        let valid_path = self.validate_existing_path(file_path, allowed_directories)?;
+        let path = valid_path.to_path_buf();
+        let mut report = tokio::task::spawn_blocking(move || probe_file_lock(&path))
+            .await
+            .map_err(|err| ServiceError::FromString(err.to_string()))??;
+        report.open_handle = self
+            .open_files
+            .snapshot(&valid_path)?
+            .map(|snapshot| snapshot.handle);
+        Ok(report)
+    }
+
+    /// Locates every definition named `name` in a source file and returns its complete text
+    /// along with its 1-based start and end lines.
+    pub async fn extract_definition(
+        &self,
+        file_path: &Path,
+        name: &str,
+    ) -> ServiceResult<Vec<CodeDefinition>> {
+        let content = self.read_text_file(file_path).await?;
+        Ok(extract_definitions(
+            &content,
+            name,
+            BlockStyle::from_path(file_path),
+            CommentSyntax::from_path(file_path).as_ref(),
+        ))
+    }
+
+    /// Returns the innermost block enclosing the 1-based line `line` of a source file, from its
+    /// header to its closing brace (or the end of its indented body), see
+    /// [`find_enclosing_block`]. Fails for lines past the end of the file or outside any block.
+    pub async fn read_enclosing_block(
+        &self,
+        file_path: &Path,
+        line: usize,
+    ) -> ServiceResult<CodeDefinition> {
+        let content = self.read_text_file(file_path).await?;
+        let line_count = content.lines().count();
+        if line == 0 || line > line_count {
+            return Err(ServiceError::FromString(format!(
+                "Line {line} is out of range: '{}' has {line_count} lines.",
+                file_path.display()
+            )));
+        }
+        find_enclosing_block(
+            &content,
+            line - 1,
+            BlockStyle::from_path(file_path),
+            CommentSyntax::from_path(file_path).as_ref(),
+        )
+        .ok_or_else(|| {
+            ServiceError::FromString(format!(
+                "Line {line} of '{}' is not inside a block.",
+                file_path.display()
+            ))
+        })
+    }
+
+    pub async fn create_directory(&self, file_path: &Path) -> ServiceResult<()> {
+        let allowed_directories = self.allowed_directories().await;
+        let valid_path = self.validate_path(file_path, allowed_directories)?;
+        tokio::fs::create_dir_all(valid_path).await?;
+        Ok(())
+    }
+
+    /// Like [`create_directory`](Self::create_directory), but if creating any component fails,
+    /// the directories created by this call are removed again, leaving the file system as it
+    /// was found.
+    pub async fn create_directory_atomic(&self, file_path: &Path) -> ServiceResult<()> {
+        let allowed_directories = self.allowed_directories().await;
+        let valid_path = self.validate_path(file_path, allowed_directories)?;
+
+        let mut missing = vec![];
+        let mut current = Some(valid_path.as_path());
+        while let Some(path) = current.filter(|path| !path.exists()) {
+            missing.push(path.to_path_buf());
+            current = path.parent();
+        }
+
+        let mut created: Vec<PathBuf> = vec![];
+        for dir in missing.into_iter().rev() {
+            match tokio::fs::create_dir(&dir).await {
+                Ok(()) => created.push(dir),
+                // created concurrently by someone else, leave it alone
+                Err(err) if err.kind() == std::io::ErrorKind::AlreadyExists && dir.is_dir() => {}
+                Err(err) => {
+                    for dir in created.iter().rev() {
+                        let _ = tokio::fs::remove_dir(dir).await;
+                    }
+                    return Err(err.into());
+                }
+            }
+        }
+        Ok(())
+    }
+
+    pub async fn move_file(&self, src_path: &Path, dest_path: &Path) -> ServiceResult<()> {
+        let allowed_directories = self.allowed_directories().await;
+        let valid_src_path = self.validate_path(src_path, allowed_directories.clone())?;
+        let valid_dest_path = self.validate_path(dest_path, allowed_directories)?;
+        if !valid_src_path.is_dir() {
+            self.check_write_extension(&valid_dest_path)?;
+        }
+        // Replacing an existing file removes its content
+        self.write_budget
+            .charge(0, existing_file_size(&valid_dest_path).await)?;
+        self.read_cache.invalidate(&valid_src_path);
+        self.read_cache.invalidate(&valid_dest_path);
+        self.rename_with_retry(&valid_src_path, &valid_dest_path)
+            .await?;
+        Ok(())
+    }
+
+    /// Checks whether the name of `path` can be confused with another entry of its directory,
+    /// see [`find_name_collision`]. `source` is the path renamed to `path`, if any, so that a
+    /// rename changing only the case of a name is not reported. With `strict`, a collision is an
+    /// error; otherwise it is returned for the caller to warn about.
+    pub async fn check_name_collision(
+        &self,
+        path: &Path,
+        source: Option<&Path>,
+        strict: bool,
+    ) -> ServiceResult<Option<NameCollision>> {
+        let allowed_directories = self.allowed_directories().await;
+        let valid_path = self.validate_path(path, allowed_directories.clone())?;
+        let (Some(dir), Some(name)) = (valid_path.parent(), valid_path.file_name()) else {
+            return Ok(None);
+        };
+        let ignored = match source {
+            Some(source) => {
+                let source = self.validate_path(source, allowed_directories)?;
+                (source.parent() == Some(dir))
+                    .then(|| source.file_name().map(|name| name.to_os_string()))
+                    .flatten()
+            }
+            None => None,
+        };
+        let (dir, name) = (dir.to_path_buf(), name.to_os_string());
+        let collision = tokio::task::spawn_blocking(move || {
+            find_name_collision(&dir, &name, ignored.as_deref())
+        })
+        .await
+        .map_err(|err| ServiceError::FromString(err.to_string()))??;
+        match collision {
+            Some(collision) if strict => Err(ServiceError::FromString(format!(
+                "Refusing to write '{}': {collision}.",
+                valid_path.display()
+            ))),
+            collision => Ok(collision),
+        }
+    }
+
+    /// Renames `from` to `to`, retrying with exponential backoff while the rename fails because
+    /// another process holds one of the files open, as happens on Windows.
+    async fn rename_with_retry(&self, from: &Path, to: &Path) -> std::io::Result<()> {
+        let mut delay = self.performance_config.rename_retry_delay;
+        let mut attempt = 0;
+        loop {
+            match tokio::fs::rename(from, to).await {
+                Err(err)
+                    if is_sharing_violation(&err)
+                        && attempt < self.performance_config.rename_retries =>
+                {
+                    tokio::time::sleep(delay).await;
+                    delay *= 2;
+                    attempt += 1;
+                }
+                result => return result,
+            }
+        }
+    }
+
+    /// Describes what [`move_file`](Self::move_file) would do, without touching the file system.
+    pub async fn plan_move(&self, src_path: &Path, dest_path: &Path) -> ServiceResult<MovePlan> {
+        let allowed_directories = self.allowed_directories().await;
+        let valid_src_path = self.validate_existing_path(src_path, allowed_directories.clone())?;
+        let valid_dest_path = self.validate_path(dest_path, allowed_directories)?;
+
+        let is_dir = valid_src_path.metadata().is_some_and(|m| m.is_dir());
+        let bytes = if is_dir {
+            WalkDir::new(valid_src_path.as_path())
+                .into_iter()
+                .filter_map(|entry| entry.ok())
+                .filter(|entry| entry.file_type().is_file())
+                .filter_map(|entry| entry.metadata().ok())
+                .map(|metadata| metadata.len())
+                .sum()
         } else {
-            format!("*{}*", &pattern.to_lowercase())
+            valid_src_path.metadata().map_or(0, |m| m.len())
         };
 
-        let glob_pattern = &updated_pattern;
+        let mut conflicts = Vec::new();
+        if valid_dest_path.exists() {
+            conflicts.push(format!(
+                "destination '{}' already exists",
+                valid_dest_path.display()
+            ));
+        }
+        if let Some(parent) = valid_dest_path.parent() {
+            if !parent.is_dir() {
+                conflicts.push(format!(
+                    "destination directory '{}' does not exist",
+                    parent.display()
+                ));
+            }
+        }
+        if is_dir && valid_dest_path.starts_with(valid_src_path.as_path()) {
+            conflicts.push("destination is inside the source directory".to_string());
+        }
 
-        let entries: Vec<_> = WalkDir::new(&valid_dir_path)
-            .follow_links(true)
+        Ok(MovePlan {
+            source: valid_src_path.into_path_buf(),
+            destination: valid_dest_path.into_path_buf(),
+            is_dir,
+            bytes,
+            conflicts,
+        })
+    }
+
+    /// Plans the edits that keep a Rust crate compiling when the module file `src_path` is moved
+    /// to `dest_path`: its `mod` declaration moves to the new parent module, and `crate::` paths
+    /// naming it are rewritten in every source file of the crate. Best effort: relative
+    /// (`self::`/`super::`) paths and `#[path]` attributes are not handled.
+    pub async fn plan_reference_updates(
+        &self,
+        src_path: &Path,
+        dest_path: &Path,
+    ) -> ServiceResult<ReferenceUpdatePlan> {
+        let allowed_directories = self.allowed_directories().await;
+        let valid_src_path = self.validate_existing_file(src_path, allowed_directories.clone())?;
+        let valid_dest_path = self.validate_path(dest_path, allowed_directories.clone())?;
+
+        let mut plan = ReferenceUpdatePlan::default();
+        let modules = crate_src_dir(&valid_src_path).and_then(|src_dir| {
+            let old_module = module_path(&src_dir, &valid_src_path)?;
+            let new_module = module_path(&src_dir, &valid_dest_path)?;
+            Some((src_dir, old_module, new_module))
+        });
+        let Some((src_dir, old_module, new_module)) = modules else {
+            plan.notes.push(
+                "references not updated: source and destination must be module files in the same crate's src directory"
+                    .to_string(),
+            );
+            return Ok(plan);
+        };
+        plan.old_module = Some(format!("crate::{}", old_module.join("::")));
+        plan.new_module = Some(format!("crate::{}", new_module.join("::")));
+
+        let old_parent = parent_module_file(&src_dir, &old_module);
+        let new_parent = parent_module_file(&src_dir, &new_module);
+        if new_parent.is_none() {
+            plan.notes.push(format!(
+                "no parent module file found for {}; add its `mod` declaration manually",
+                plan.new_module.as_deref().unwrap_or_default()
+            ));
+        }
+
+        let files: Vec<PathBuf> = WalkDir::new(&src_dir)
             .into_iter()
             .filter_map(|entry| entry.ok())
-            .filter_map(|entry| {
-                let full_path = entry.path();
-
-                self.validate_path(full_path, allowed_directories.clone())
-                    .ok()
-                    .and_then(|path| {
-                        if path != valid_dir_path
-                            && glob_match(glob_pattern, path.display().to_string().as_ref())
-                        {
-                            Some(path)
-                        } else {
-                            None
-                        }
-                    })
+            .inspect(|_| record_entries_visited(1))
+            .filter(|entry| {
+                entry.file_type().is_file() && entry.path().extension().is_some_and(|e| e == "rs")
+            })
+            .map(|entry| entry.into_path())
+            .filter(|path| {
+                self.validate_path(path, allowed_directories.clone())
+                    .is_ok()
             })
             .collect();
 
-        let zip_file = File::create(&target_path).await?;
-        let mut zip_writer = ZipFileWriter::new(zip_file.compat());
-
-        for entry_path_buf in &entries {
-            if entry_path_buf.is_dir() {
+        let old_name = old_module.last().map(String::as_str).unwrap_or_default();
+        let new_name = new_module.last().map(String::as_str).unwrap_or_default();
+        let mut visibility = None;
+        for file in files {
+            let Ok(original) = self.read_to_string_with_flags(&file).await else {
                 continue;
+            };
+            record_bytes_read(original.len() as u64);
+            let mut updated = rewrite_crate_paths(&original, &old_module, &new_module);
+            if old_parent.as_deref() == Some(file.as_path()) {
+                match remove_mod_declaration(&updated, old_name) {
+                    Some((content, declared_visibility)) => {
+                        updated = content;
+                        visibility = Some(declared_visibility);
+                    }
+                    None => plan.notes.push(format!(
+                        "`mod {old_name};` not found in '{}'",
+                        file.display()
+                    )),
+                }
             }
-            let entry_path = entry_path_buf.as_path();
-            let entry_str = entry_path.as_os_str().to_str().ok_or(std::io::Error::new(
-                std::io::ErrorKind::InvalidInput,
-                "Invalid UTF-8 in file name",
-            ))?;
-
-            if !entry_str.starts_with(input_dir_str) {
-                return Err(std::io::Error::new(
-                    std::io::ErrorKind::InvalidInput,
-                    "Entry file path does not start with base input directory path.",
-                )
-                .into());
+            if updated != original {
+                plan.updates.push(ReferenceUpdate {
+                    path: file,
+                    original,
+                    updated,
+                });
             }
+        }
 
-            let entry_str = &entry_str[input_dir_str.len() + 1..];
-            write_zip_entry(entry_str, entry_path, &mut zip_writer).await?;
+        if let Some(new_parent) = new_parent {
+            let visibility = visibility.unwrap_or_default();
+            match plan.updates.iter_mut().find(|u| u.path == new_parent) {
+                Some(update) => {
+                    update.updated = add_mod_declaration(&update.updated, new_name, &visibility)
+                }
+                None => {
+                    let original = self.read_to_string_with_flags(&new_parent).await?;
+                    let updated = add_mod_declaration(&original, new_name, &visibility);
+                    plan.updates.push(ReferenceUpdate {
+                        path: new_parent,
+                        original,
+                        updated,
+                    });
+                }
+            }
         }
 
-        let z_file = zip_writer.close().await?;
-        let zip_file_size = if let Ok(meta_data) = z_file.into_inner().metadata().await {
-            format_bytes(meta_data.len())
-        } else {
-            "unknown".to_string()
-        };
-        let result_message = format!(
-            "Successfully compressed '{}' directory into '{}' ({}).",
-            input_dir,
-            target_path.display(),
-            zip_file_size
-        );
-        Ok(result_message)
+        Ok(plan)
     }
 
-    pub async fn zip_files(
+    /// Writes the edits of a plan made by [`plan_reference_updates`](Self::plan_reference_updates)
+    /// after the move, redirecting edits of the moved file itself to `dest_path`. Returns the
+    /// paths of the updated files.
+    pub async fn apply_reference_updates(
         &self,
-        input_files: Vec<String>,
-        target_zip_file: String,
-    ) -> ServiceResult<String> {
-        let file_count = input_files.len();
-
-        if file_count == 0 {
-            return Err(std::io::Error::new(
-                std::io::ErrorKind::InvalidInput,
-                "No file(s) to zip. The input files array is empty.",
-            )
-            .into());
-        }
+        plan: &ReferenceUpdatePlan,
+        src_path: &Path,
+        dest_path: &Path,
+    ) -> ServiceResult<Vec<PathBuf>> {
         let allowed_directories = self.allowed_directories().await;
-        let target_path =
-            self.validate_path(Path::new(&target_zip_file), allowed_directories.clone())?;
-
-        if target_path.exists() {
-            return Err(std::io::Error::new(
-                std::io::ErrorKind::AlreadyExists,
-                format!("'{target_zip_file}' already exists!"),
-            )
-            .into());
+        let valid_src_path = self.validate_path(src_path, allowed_directories.clone())?;
+        let mut written = Vec::with_capacity(plan.updates.len());
+        for update in &plan.updates {
+            let target = if update.path == *valid_src_path {
+                dest_path
+            } else {
+                update.path.as_path()
+            };
+            let valid_target = self.validate_path(target, allowed_directories.clone())?;
+            self.check_write_extension(&valid_target)?;
+            self.read_cache.invalidate(&valid_target);
+            self.write_with_flags(&valid_target, update.updated.as_bytes())
+                .await?;
+            record_bytes_written(update.updated.len() as u64);
+            written.push(valid_target.into_path_buf());
         }
+        Ok(written)
+    }
 
-        let source_paths = input_files
-            .iter()
-            .map(|p| self.validate_path(Path::new(p), allowed_directories.clone()))
-            .collect::<Result<Vec<_>, _>>()?;
+    pub async fn list_directory(&self, dir_path: &Path) -> ServiceResult<Vec<tokio::fs::DirEntry>> {
+        let allowed_directories = self.allowed_directories().await;
 
-        let zip_file = File::create(&target_path).await?;
-        let mut zip_writer = ZipFileWriter::new(zip_file.compat());
-        for path in source_paths {
-            let filename = path.file_name().ok_or(std::io::Error::new(
-                std::io::ErrorKind::InvalidInput,
-                "Invalid path!",
-            ))?;
+        let valid_path = self.validate_path(dir_path, allowed_directories)?;
 
-            let filename = filename.to_str().ok_or(std::io::Error::new(
-                std::io::ErrorKind::InvalidInput,
-                "Invalid UTF-8 in file name",
-            ))?;
+        let mut dir = tokio::fs::read_dir(valid_path).await?;
+
+        let mut entries = Vec::new();
 
-            write_zip_entry(filename, &path, &mut zip_writer).await?;
+        // Use a loop to collect the directory entries
+        while let Some(entry) = dir.next_entry().await? {
+            entries.push(entry);
         }
-        let z_file = zip_writer.close().await?;
+        record_entries_visited(entries.len() as u64);
 
-        let zip_file_size = if let Ok(meta_data) = z_file.into_inner().metadata().await {
-            format_bytes(meta_data.len())
-        } else {
-            "unknown".to_string()
-        };
+        Ok(entries)
+    }
 
-        let result_message = format!(
-            "Successfully compressed {} {} into '{}' ({}).",
-            file_count,
-            if file_count == 1 { "file" } else { "files" },
-            target_path.display(),
-            zip_file_size
-        );
-        Ok(result_message)
+    /// Lists the entries of a directory with their type and, with `sizes`, the size of files,
+    /// in directory order. The type comes from the directory entry itself; metadata is only
+    /// looked up for symbolic links and, with `sizes`, for files, several lookups at a time so
+    /// that large or remote directories are not listed one round trip per entry.
+    pub async fn list_directory_entries(
+        &self,
+        dir_path: &Path,
+        sizes: bool,
+    ) -> ServiceResult<Vec<ListedEntry>> {
+        let entries = self.list_directory(dir_path).await?;
+        let entries = stream::iter(entries)
+            .map(|entry| async move {
+                let file_type = entry.file_type().await?;
+                let path = entry.path();
+                let metadata = if file_type.is_symlink() {
+                    // Describe the target, or neither a file nor a directory if it is broken
+                    metadata(&path).await.ok()
+                } else if sizes && file_type.is_file() {
+                    Some(entry.metadata().await?)
+                } else {
+                    None
+                };
+                let (is_dir, is_file) = match &metadata {
+                    Some(metadata) => (metadata.is_dir(), metadata.is_file()),
+                    None if file_type.is_symlink() => (false, false),
+                    None => (file_type.is_dir(), file_type.is_file()),
+                };
+                let metadata = metadata.filter(|metadata| sizes && metadata.is_file());
+                Ok::<_, std::io::Error>(ListedEntry {
+                    name: entry.file_name().to_string_lossy().into_owned(),
+                    path,
+                    is_dir,
+                    is_file,
+                    size: metadata.as_ref().map(|metadata| metadata.len()),
+                    allocated: metadata.as_ref().map(allocated_size),
+                    modified: metadata
+                        .as_ref()
+                        .and_then(|metadata| metadata.modified().ok()),
+                })
+            })
+            .buffered(MAX_CONCURRENT_METADATA)
+            .collect::<Vec<_>>()
+            .await;
+        Ok(entries.into_iter().collect::<Result<Vec<_>, _>>()?)
     }
 
-    pub async fn unzip_file(&self, zip_file: &str, target_dir: &str) -> ServiceResult<String> {
+    /// Returns the absolute canonical path of the directory at `dir_path`, which listings join
+    /// the names of entries to when absolute paths are requested.
+    pub async fn canonical_directory(&self, dir_path: &Path) -> ServiceResult<PathBuf> {
         let allowed_directories = self.allowed_directories().await;
+        let valid_path = self.validate_dir(dir_path, allowed_directories)?;
+        Ok(tokio::fs::canonicalize(valid_path.as_path()).await?)
+    }
 
-        let zip_file = self.validate_path(Path::new(&zip_file), allowed_directories.clone())?;
-        let target_dir_path = self.validate_path(Path::new(target_dir), allowed_directories)?;
-        if !zip_file.exists() {
-            return Err(std::io::Error::new(
-                std::io::ErrorKind::NotFound,
-                "Zip file does not exists.",
-            )
-            .into());
-        }
+    pub async fn write_file(&self, file_path: &Path, content: &String) -> ServiceResult<()> {
+        let allowed_directories = self.allowed_directories().await;
+        let valid_path = self.validate_path(file_path, allowed_directories)?;
+        self.check_write_extension(&valid_path)?;
+        self.read_cache.invalidate(&valid_path);
+        self.write_with_flags(&valid_path, content.as_bytes())
+            .await?;
+        record_bytes_written(content.len() as u64);
+        Ok(())
+    }
 
-        if target_dir_path.exists() {
-            return Err(std::io::Error::new(
-                std::io::ErrorKind::AlreadyExists,
-                format!("'{target_dir}' directory already exists!"),
-            )
-            .into());
+    /// Joins `sources` into `destination` in the given order, with `separator` between
+    /// consecutive sources. Every path is validated, and every source must exist, before the
+    /// destination is opened; the sources are then streamed into it. Returns the bytes written.
+    pub async fn concat_files(
+        &self,
+        sources: &[String],
+        destination: &Path,
+        separator: Option<&str>,
+    ) -> ServiceResult<u64> {
+        if sources.is_empty() {
+            return Err(ServiceError::FromString(
+                "At least one source file is required.".to_string(),
+            ));
+        }
+        let allowed_directories = self.allowed_directories().await;
+        let valid_sources = sources
+            .iter()
+            .map(|source| {
+                self.validate_existing_file(Path::new(source), allowed_directories.clone())
+            })
+            .collect::<ServiceResult<Vec<_>>>()?;
+        let valid_destination = self.validate_path(destination, allowed_directories)?;
+        self.check_write_extension(&valid_destination)?;
+        if valid_sources
+            .iter()
+            .any(|source| source.as_path() == valid_destination.as_path())
+        {
+            return Err(ServiceError::FromString(format!(
+                "The destination '{}' cannot also be a source.",
+                valid_destination.display()
+            )));
         }
 
-        let file = BufReader::new(File::open(zip_file).await?);
-        let mut zip = ZipFileReader::with_tokio(file).await?;
-
-        let file_count = zip.file().entries().len();
-
-        for index in 0..file_count {
-            let entry = zip.file().entries().get(index).unwrap();
-            let entry_path = target_dir_path.join(entry.filename().as_str()?);
-            // Ensure the parent directory exists
-            if let Some(parent) = entry_path.parent() {
-                tokio::fs::create_dir_all(parent).await?;
+        let separator = separator.unwrap_or_default().as_bytes();
+        let total = valid_sources
+            .iter()
+            .map(|source| source.metadata().map_or(0, |metadata| metadata.len()))
+            .sum::<u64>()
+            + (separator.len() * (valid_sources.len() - 1)) as u64;
+        let replaced = existing_file_size(&valid_destination).await;
+        self.check_disk_space(&valid_destination, total.saturating_sub(replaced))?;
+        self.write_budget.charge(total, replaced)?;
+        self.read_cache.invalidate(&valid_destination);
+
+        let joined: ServiceResult<u64> = async {
+            let mut output = self.open_for_write(&valid_destination).await?;
+            let mut written = 0;
+            for (index, source) in valid_sources.iter().enumerate() {
+                if index > 0 && !separator.is_empty() {
+                    output.write_all(separator).await?;
+                    written += separator.len() as u64;
+                }
+                let mut input = self.stream_reader(self.open_for_read(source).await?);
+                let copied = tokio::io::copy_buf(&mut input, &mut output).await?;
+                record_bytes_read(copied);
+                written += copied;
+            }
+            output.flush().await?;
+            Ok(written)
+        }
+        .await;
+        let written = match joined {
+            Err(err) if is_storage_full(&err) => {
+                return Err(self.discard_partial_file(&valid_destination).await);
             }
+            joined => joined?,
+        };
+        record_bytes_written(written);
+        Ok(written)
+    }
 
-            // Extract the file
-            let reader = zip.reader_without_entry(index).await?;
-            let mut compat_reader = reader.compat();
-            let mut output_file = File::create(&entry_path).await?;
+    /// The files below the validated `root` selected by `pattern`, see [`matches_file_glob`], and
+    /// not excluded by `exclude_patterns`, sorted by path. The selection of the batch tools that
+    /// take a glob, such as [`bulk_insert`](Self::bulk_insert).
+    async fn select_files(
+        &self,
+        root: &Path,
+        pattern: &str,
+        exclude_patterns: Vec<String>,
+    ) -> ServiceResult<Vec<walkdir::DirEntry>> {
+        let mut files: Vec<_> = self
+            .search_files_iter(root, "**/*".to_string(), exclude_patterns, None, None)
+            .await?
+            .filter(|entry| entry.file_type().is_file())
+            .filter(|entry| {
+                matches_file_glob(
+                    pattern,
+                    entry.path().strip_prefix(root).unwrap_or(entry.path()),
+                )
+            })
+            .collect();
+        files.sort_by(|a, b| a.path().cmp(b.path()));
+        Ok(files)
+    }
 
-            tokio::io::copy(&mut compat_reader, &mut output_file).await?;
-            output_file.flush().await?;
+    /// Lists the files below `root_path` that a batch tool given `pattern` and
+    /// `exclude_patterns` would act on, without touching them. At most `limit` are rendered.
+    pub async fn preview_glob(
+        &self,
+        root_path: &Path,
+        pattern: &str,
+        exclude_patterns: Vec<String>,
+        limit: usize,
+    ) -> ServiceResult<GlobPreview> {
+        let allowed_directories = self.allowed_directories().await;
+        let valid_path = self.validate_dir(root_path, allowed_directories)?;
+        if let Some(error) = glob_syntax_error(pattern) {
+            return Err(ServiceError::FromString(format!(
+                "Invalid glob '{pattern}': {error}"
+            )));
         }
+        let files = self
+            .select_files(&valid_path, pattern, exclude_patterns)
+            .await?
+            .into_iter()
+            .map(|entry| entry.into_path())
+            .collect();
+        Ok(GlobPreview {
+            root: valid_path.to_path_buf(),
+            pattern: pattern.to_string(),
+            files,
+            limit,
+        })
+    }
 
-        let result_message = format!(
-            "Successfully extracted {} {} into '{}'.",
-            file_count,
-            if file_count == 1 { "file" } else { "files" },
-            target_dir_path.display()
-        );
+    /// Inserts `content` at `anchor` in every file below `root_path` matched by `pattern`, see
+    /// [`matches_file_glob`]. Files already containing `guard`, a regex, or the content itself when
+    /// no guard is given, are skipped. Files are read and rewritten in parallel, each replaced
+    /// atomically; a file that cannot be changed is reported without stopping the others.
+    #[allow(clippy::too_many_arguments)]
+    pub async fn bulk_insert(
+        &self,
+        root_path: &Path,
+        pattern: &str,
+        exclude_patterns: Vec<String>,
+        anchor: &str,
+        content: &str,
+        guard: Option<&str>,
+        dry_run: bool,
+    ) -> ServiceResult<BulkInsertReport> {
+        let allowed_directories = self.allowed_directories().await;
+        let valid_path = self.validate_dir(root_path, allowed_directories)?;
+        let regex_options = ContentSearchOptions {
+            is_regex: true,
+            ignore_case: false,
+            ..Default::default()
+        };
+        let compile = |pattern: &str| {
+            let pattern = self.content_pattern(pattern, regex_options)?;
+            self.content_regex_set(&[pattern], regex_options)?
+                .ok_or_else(|| ServiceError::FromString("The pattern is empty.".to_string()))
+        };
+        let anchor = InsertAnchor::parse(anchor, compile)?;
+        let guard = guard.map(compile).transpose()?;
 
-        Ok(result_message)
-    }
+        let mut files = vec![];
+        for entry in self
+            .select_files(&valid_path, pattern, exclude_patterns)
+            .await?
+        {
+            // Renaming over a link would replace it, and open files are edited in their buffer
+            let refused = if entry.path_is_symlink() {
+                Some("symbolic links are not rewritten".to_string())
+            } else if self.open_files.snapshot(entry.path())?.is_some() {
+                Some("the file is open, edit its buffer instead".to_string())
+            } else {
+                self.check_write_extension(entry.path())
+                    .err()
+                    .map(|err| err.to_string())
+            };
+            files.push((entry.into_path(), refused));
+        }
 
-    pub fn mime_from_path(&self, path: &Path) -> ServiceResult<infer::Type> {
-        let is_svg = path
-            .extension()
-            .is_some_and(|e| e.to_str().is_some_and(|s| s == "svg"));
-        // consider it is a svg file as we cannot detect svg from bytes pattern
-        if is_svg {
-            return Ok(infer::Type::new(
-                infer::MatcherType::Image,
-                "image/svg+xml",
-                "svg",
-                |_: &[u8]| true,
-            ));
+        // Files are read on rayon worker threads, which do not see the task-local stats
+        let stats = OperationStats::current();
+        let descriptors = self.descriptor_limit.clone();
+        self.take_fast_path(FAST_PATH_PARALLEL);
+        let text = content.to_string();
+        let planned = tokio::task::spawn_blocking(move || {
+            files
+                .into_par_iter()
+                .map(|(path, refused)| {
+                    let mut insertion = FileInsertion {
+                        path,
+                        status: InsertStatus::NoAnchor,
+                        lines_added: 0,
+                    };
+                    if let Some(reason) = refused {
+                        insertion.status = InsertStatus::Failed(reason);
+                        return (insertion, None);
+                    }
+                    let current =
+                        match descriptors.with_permit(|| fs::read_to_string(&insertion.path)) {
+                            Ok(current) => current,
+                            Err(err) => {
+                                insertion.status = InsertStatus::Failed(err.to_string());
+                                return (insertion, None);
+                            }
+                        };
+                    if let Some(stats) = &stats {
+                        stats.add_bytes_read(current.len() as u64);
+                    }
+                    let guarded = match &guard {
+                        Some(guard) => guard.is_match(&current),
+                        None => normalize_line_endings(&current)
+                            .contains(normalize_line_endings(&text).trim_end()),
+                    };
+                    if guarded {
+                        insertion.status = InsertStatus::Guarded;
+                        return (insertion, None);
+                    }
+                    match insert_at_anchor(&current, &anchor, &text) {
+                        Some((modified, lines_added)) => {
+                            insertion.status = InsertStatus::WouldInsert;
+                            insertion.lines_added = lines_added;
+                            (insertion, Some((current.len() as u64, modified)))
+                        }
+                        None => (insertion, None),
+                    }
+                })
+                .collect::<Vec<_>>()
+        })
+        .await
+        .map_err(|err| ServiceError::FromString(err.to_string()))?;
 
-            // infer::Type::new(infer::MatcherType::Image, "", "svg",);
+        let mut report = BulkInsertReport {
+            root: valid_path.to_path_buf(),
+            dry_run,
+            ..Default::default()
+        };
+        if dry_run {
+            report.files = planned.into_iter().map(|(file, _)| file).collect();
+            report.files.sort_by(|a, b| a.path.cmp(&b.path));
+            return Ok(report);
         }
-        let kind = infer::get_from_path(path)?.ok_or(ServiceError::FromString(
-            "File tyle is unknown!".to_string(),
-        ))?;
-        Ok(kind)
+
+        let (written, replaced) = planned
+            .iter()
+            .filter_map(|(_, change)| change.as_ref())
+            .fold((0, 0), |(written, replaced), (current, modified)| {
+                (written + modified.len() as u64, replaced + current)
+            });
+        self.check_disk_space(&report.root, written)?;
+        self.write_budget.charge(written, replaced)?;
+        for (file, change) in &planned {
+            if change.is_some() {
+                self.read_cache.invalidate(&file.path);
+            }
+        }
+
+        let stats = OperationStats::current();
+        let descriptors = self.descriptor_limit.clone();
+        let mut files = tokio::task::spawn_blocking(move || {
+            planned
+                .into_par_iter()
+                .map(|(mut file, change)| {
+                    let Some((_, modified)) = change else {
+                        return (file, 0);
+                    };
+                    match descriptors.with_permit(|| write_atomically(&file.path, &modified)) {
+                        Ok(()) => {
+                            if let Some(stats) = &stats {
+                                stats.add_bytes_written(modified.len() as u64);
+                            }
+                            file.status = InsertStatus::Inserted;
+                            (file, modified.len() as u64)
+                        }
+                        Err(err) => {
+                            file.status = InsertStatus::Failed(err.to_string());
+                            (file, 0)
+                        }
+                    }
+                })
+                .collect::<Vec<_>>()
+        })
+        .await
+        .map_err(|err| ServiceError::FromString(err.to_string()))?;
+        files.sort_by(|a, b| a.0.path.cmp(&b.0.path));
+        for (file, bytes) in files {
+            report.bytes_written += bytes;
+            report.files.push(file);
+        }
+        Ok(report)
     }
 
-    pub fn filesize_in_range(
+    /// Converts the line endings, the encoding and the indentation of a text file and strips its
+    /// trailing whitespace, as requested, and returns the diff followed by which transformations
+    /// changed anything. Lines starting inside multi-line string literals keep their indentation
+    /// when the language is recognized. The file is replaced atomically.
+    #[allow(clippy::too_many_arguments)]
+    pub async fn convert_file(
         &self,
-        file_size: u64,
-        min_bytes: Option<u64>,
-        max_bytes: Option<u64>,
-    ) -> bool {
-        if min_bytes.is_none() && max_bytes.is_none() {
-            return true;
+        file_path: &Path,
+        line_ending: Option<LineEnding>,
+        encoding: Option<TargetEncoding>,
+        indentation: Option<Indentation>,
+        indent_width: usize,
+        strip_trailing: bool,
+        dry_run: bool,
+    ) -> ServiceResult<String> {
+        let allowed_directories = self.allowed_directories().await;
+        let valid_path = self.validate_path(file_path, allowed_directories)?;
+        if line_ending.is_none() && encoding.is_none() && indentation.is_none() && !strip_trailing {
+            return Err(ServiceError::FromString(
+                "Nothing to convert: set 'line_ending', 'encoding', 'indentation' or 'strip_trailing_whitespace'.".to_string(),
+            ));
         }
-        match (min_bytes, max_bytes) {
-            (_, Some(max)) if file_size > max => false,
-            (Some(min), _) if file_size < min => false,
-            _ => true,
+        if indentation.is_some() && !(1..=MAX_INDENT_WIDTH).contains(&indent_width) {
+            return Err(ServiceError::FromString(format!(
+                "'indent_width' must be between 1 and {MAX_INDENT_WIDTH}."
+            )));
+        }
+        // The converted file replaces the path, which would turn a link into a regular file
+        if tokio::fs::symlink_metadata(&valid_path)
+            .await
+            .is_ok_and(|metadata| metadata.is_symlink())
+        {
+            return Err(ServiceError::FromString(format!(
+                "'{}' is a symbolic link; symbolic links are not rewritten, convert its target instead.",
+                valid_path.display()
+            )));
+        }
+        if self.open_files.snapshot(&valid_path)?.is_some() {
+            return Err(ServiceError::FromString(format!(
+                "'{}' is open; close it before converting it.",
+                valid_path.display()
+            )));
         }
-    }
 
-    pub async fn validate_file_size<P: AsRef<Path>>(
-        &self,
-        path: P,
-        min_bytes: Option<usize>,
-        max_bytes: Option<usize>,
-    ) -> ServiceResult<()> {
-        if min_bytes.is_none() && max_bytes.is_none() {
-            return Ok(());
+        let class = self.classify_file(&valid_path).await?;
+        if let FileClass::TextOtherEncoding(source) = class
+            && encoding.is_none()
+        {
+            return Err(ServiceError::FromString(format!(
+                "'{}' is {source} text; set 'encoding' to convert it to UTF-8.",
+                valid_path.display()
+            )));
         }
+        let original = self.read_classified_text(&valid_path, &class).await?;
+        let had_bom = original.starts_with('\u{FEFF}');
+        let text = original.strip_prefix('\u{FEFF}').unwrap_or(&original);
+
+        let mut report = ConversionReport::default();
+        if let Some(encoding) = encoding {
+            let bom = encoding == TargetEncoding::Utf8Bom;
+            let description = if bom {
+                "encoding to UTF-8 with BOM"
+            } else {
+                "encoding to UTF-8"
+            };
+            let change = match class {
+                FileClass::TextOtherEncoding(source) => Some(format!("from {source}")),
+                _ if bom && !had_bom => Some("byte order mark added".to_string()),
+                _ if !bom && had_bom => Some("byte order mark removed".to_string()),
+                _ => None,
+            };
+            report.steps.push(ConversionStep::new(description, change));
+        }
+        let bom = match encoding {
+            Some(encoding) => encoding == TargetEncoding::Utf8Bom,
+            None => had_bom,
+        };
 
-        let file_size = metadata(&path).await?.len() as usize;
+        // Leading and trailing whitespace inside string literals belongs to the strings
+        let syntax = CommentSyntax::from_path(&valid_path);
+        let inside_strings = syntax
+            .map(|syntax| lines_inside_strings(text, &syntax))
+            .unwrap_or_default();
+        let mut converted = text.to_string();
+        if let Some(indentation) = indentation {
+            let to_tabs = indentation == Indentation::Tabs;
+            let (content, lines) =
+                convert_indentation(&converted, to_tabs, indent_width, &inside_strings);
+            converted = content;
+            let scope = if syntax.is_some() {
+                "leading whitespace outside string literals"
+            } else {
+                "leading whitespace only"
+            };
+            let description = if to_tabs {
+                format!("indentation to tabs of width {indent_width} ({scope})")
+            } else {
+                format!("indentation to {indent_width} spaces per tab ({scope})")
+            };
+            report.steps.push(ConversionStep::lines(description, lines));
+        }
+        if strip_trailing {
+            // The line break of a line is inside a string when the next line starts inside it
+            let ending_in_strings = inside_strings.iter().map(|line| line - 1).collect();
+            let (content, lines) = strip_trailing_whitespace(&converted, &ending_in_strings);
+            converted = content;
+            report
+                .steps
+                .push(ConversionStep::lines("trailing whitespace stripped", lines));
+        }
+        if let Some(line_ending) = line_ending {
+            let (content, lines) = convert_line_endings(&converted, line_ending.as_str());
+            converted = content;
+            report.steps.push(ConversionStep::lines(
+                format!("line endings to {}", line_ending.name()),
+                lines,
+            ));
+        }
+        if bom {
+            converted.insert(0, '\u{FEFF}');
+        }
 
-        match (min_bytes, max_bytes) {
-            (_, Some(max)) if file_size > max => Err(ServiceError::FileTooLarge(max)),
-            (Some(min), _) if file_size < min => Err(ServiceError::FileTooSmall(min)),
-            _ => Ok(()),
+        let diff = self.create_unified_diff(
+            &original,
+            &converted,
+            Some(valid_path.display().to_string()),
+        );
+        let mut output = if converted != original {
+            fenced_diff(&diff)
+        } else {
+            String::new()
+        };
+        output.push_str("Transformations:\n");
+        output.push_str(&report.render_text());
+        if !report.is_changed() {
+            output.push_str("The file already matches; nothing was written.\n");
+            return Ok(output);
+        }
+        if dry_run {
+            output.push_str("Dry run: the file was not changed.\n");
+            return Ok(output);
         }
-    }
 
-    pub async fn read_media_files(
-        &self,
-        paths: Vec<String>,
-        max_bytes: Option<usize>,
-    ) -> ServiceResult<Vec<(infer::Type, String)>> {
-        let results = stream::iter(paths)
-            .map(|path| async {
-                self.read_media_file(Path::new(&path), max_bytes)
-                    .await
-                    .map_err(|e| (path, e))
-            })
-            .buffer_unordered(MAX_CONCURRENT_FILE_READ) // Process up to MAX_CONCURRENT_FILE_READ files concurrently
-            .filter_map(|result| async move { result.ok() })
-            .collect::<Vec<_>>()
-            .await;
-        Ok(results)
+        self.check_write_extension(&valid_path)?;
+        let replaced = existing_file_size(&valid_path).await;
+        self.check_disk_space(&valid_path, converted.len() as u64)?;
+        self.write_budget.charge(converted.len() as u64, replaced)?;
+        self.read_cache.invalidate(&valid_path);
+        let target = valid_path.to_path_buf();
+        let written = converted.len() as u64;
+        tokio::task::spawn_blocking(move || write_atomically(&target, &converted))
+            .await
+            .map_err(|err| ServiceError::FromString(err.to_string()))??;
+        record_bytes_written(written);
+        output.push_str(&format!("Converted '{}'.\n", valid_path.display()));
+        Ok(output)
     }
 
-    pub async fn read_media_file(
+    /// Sorts the lines of a text file, see [`sort_lines`], and replaces it atomically. Returns the
+    /// diff and what changed; a dry run only previews them.
+    pub async fn sort_file(
         &self,
         file_path: &Path,
-        max_bytes: Option<usize>,
-    ) -> ServiceResult<(infer::Type, String)> {
+        options: SortOptions,
+        dry_run: bool,
+    ) -> ServiceResult<String> {
         let allowed_directories = self.allowed_directories().await;
         let valid_path = self.validate_path(file_path, allowed_directories)?;
-        self.validate_file_size(&valid_path, None, max_bytes)
-            .await?;
-        let kind = self.mime_from_path(&valid_path)?;
-        let content = self.read_file_as_base64(&valid_path).await?;
-        Ok((kind, content))
-    }
-
-    // reads file as base64 efficiently in a streaming manner
-    async fn read_file_as_base64(&self, file_path: &Path) -> ServiceResult<String> {
-        let file = File::open(file_path).await?;
-        let mut reader = BufReader::new(file);
-
-        let mut output = Vec::new();
+        if self.open_files.snapshot(&valid_path)?.is_some() {
+            return Err(ServiceError::FromString(format!(
+                "'{}' is open; close it before sorting it.",
+                valid_path.display()
+            )));
+        }
+        // The sorted file replaces the path, which would turn a link into a regular file
+        if tokio::fs::symlink_metadata(&valid_path)
+            .await
+            .is_ok_and(|metadata| metadata.is_symlink())
         {
-            // Wrap output Vec<u8> in a Base64 encoder writer
-            let mut encoder = EncoderWriter::new(&mut output, &general_purpose::STANDARD);
-
-            let mut buffer = [0u8; 8192];
-            loop {
-                let n = reader.read(&mut buffer).await?;
-                if n == 0 {
-                    break;
+            return Err(ServiceError::FromString(format!(
+                "'{}' is a symbolic link; symbolic links are not rewritten, sort its target instead.",
+                valid_path.display()
+            )));
+        }
+        let class = self.classify_file(&valid_path).await?;
+        if let FileClass::TextOtherEncoding(source) = class {
+            return Err(ServiceError::FromString(format!(
+                "'{}' is {source} text; convert it to UTF-8 with convert_file before sorting it.",
+                valid_path.display()
+            )));
+        }
+        let original = self.read_classified_text(&valid_path, &class).await?;
+        // A byte order mark stays at the start rather than being sorted with the first line
+        let (bom, text) = match original.strip_prefix('\u{FEFF}') {
+            Some(text) => ("\u{FEFF}", text),
+            None => ("", original.as_str()),
+        };
+        let sorted = sort_lines(text, &options);
+        let content = format!("{bom}{}", sorted.content);
+
+        let mut output = if content != original {
+            fenced_diff(&self.create_unified_diff(
+                &original,
+                &content,
+                Some(valid_path.display().to_string()),
+            ))
+        } else {
+            String::new()
+        };
+        output.push_str(&format!(
+            "{} {}{}{}",
+            sorted.lines,
+            if sorted.lines == 1 { "line" } else { "lines" },
+            if options.numeric { ", numerically" } else { "" },
+            if options.reverse { ", in reverse" } else { "" },
+        ));
+        if options.unique {
+            output.push_str(&format!(
+                ", {} {} removed",
+                sorted.duplicates_removed,
+                if sorted.duplicates_removed == 1 {
+                    "duplicate"
+                } else {
+                    "duplicates"
                 }
-                // Write raw bytes to the Base64 encoder
-                encoder.write_all(&buffer[..n])?;
-            }
-            // Make sure to flush any remaining bytes
-            encoder.flush()?;
-        } // drop encoder before consuming output
+            ));
+        }
+        output.push_str(".\n");
+        if content == original {
+            output.push_str("The file is already sorted; nothing was written.\n");
+            return Ok(output);
+        }
+        if dry_run {
+            output.push_str("Dry run: the file was not changed.\n");
+            return Ok(output);
+        }
 
-        // Convert the Base64 bytes to String (safe UTF-8)
-        let base64_string =
-            String::from_utf8(output).map_err(|err| ServiceError::FromString(format!("{err}")))?;
-        Ok(base64_string)
+        self.check_write_extension(&valid_path)?;
+        let replaced = existing_file_size(&valid_path).await;
+        self.check_disk_space(&valid_path, content.len() as u64)?;
+        self.write_budget.charge(content.len() as u64, replaced)?;
+        self.read_cache.invalidate(&valid_path);
+        let target = valid_path.to_path_buf();
+        let written = content.len() as u64;
+        tokio::task::spawn_blocking(move || write_atomically(&target, &content))
+            .await
+            .map_err(|err| ServiceError::FromString(err.to_string()))??;
+        record_bytes_written(written);
+        output.push_str(&format!("Sorted '{}'.\n", valid_path.display()));
+        Ok(output)
     }
 
-    pub async fn read_text_file(&self, file_path: &Path) -> ServiceResult<String> {
+    /// Writes `content` to `file_path` only if `precondition` holds for the current file, see
+    /// [`PreconditionKind`]. Regular expressions are subject to the configured pattern limits.
+    pub async fn write_file_if(
+        &self,
+        file_path: &Path,
+        content: &String,
+        precondition: &WritePrecondition,
+    ) -> ServiceResult<ConditionalWriteOutcome> {
         let allowed_directories = self.allowed_directories().await;
         let valid_path = self.validate_path(file_path, allowed_directories)?;
-        let content = tokio::fs::read_to_string(valid_path).await?;
-        Ok(content)
-    }
+        self.check_write_extension(&valid_path)?;
 
-    pub async fn create_directory(&self, file_path: &Path) -> ServiceResult<()> {
-        let allowed_directories = self.allowed_directories().await;
-        let valid_path = self.validate_path(file_path, allowed_directories)?;
-        tokio::fs::create_dir_all(valid_path).await?;
-        Ok(())
-    }
+        let value = || {
+            precondition.value.as_deref().ok_or_else(|| {
+                ServiceError::FromString(
+                    "The 'regex' and 'exact' preconditions require a value.".to_string(),
+                )
+            })
+        };
+        if precondition.kind == PreconditionKind::NotExists {
+            // Checking for the file and then writing it would race with another writer
+            self.read_cache.invalidate(&valid_path);
+            if !self
+                .create_with_flags(&valid_path, content.as_bytes())
+                .await?
+            {
+                return Ok(ConditionalWriteOutcome::Skipped(
+                    "the file already exists".to_string(),
+                ));
+            }
+            record_bytes_written(content.len() as u64);
+            return Ok(ConditionalWriteOutcome::Written);
+        }
 
-    pub async fn move_file(&self, src_path: &Path, dest_path: &Path) -> ServiceResult<()> {
-        let allowed_directories = self.allowed_directories().await;
-        let valid_src_path = self.validate_path(src_path, allowed_directories.clone())?;
-        let valid_dest_path = self.validate_path(dest_path, allowed_directories)?;
-        tokio::fs::rename(valid_src_path, valid_dest_path).await?;
-        Ok(())
+        let exists = tokio::fs::symlink_metadata(&valid_path).await.is_ok();
+        let skipped = match precondition.kind {
+            PreconditionKind::NotExists => None,
+            _ if !exists => {
+                // Still reject a missing value, so the mistake is not hidden by the skip
+                value()?;
+                Some("the file does not exist".to_string())
+            }
+            PreconditionKind::Exact => {
+                let expected = value()?;
+                let current = tokio::fs::read(&valid_path).await?;
+                record_bytes_read(current.len() as u64);
+                (current != expected.as_bytes())
+                    .then(|| "the content differs from the expected content".to_string())
+            }
+            PreconditionKind::Regex => {
+                let options = ContentSearchOptions {
+                    is_regex: true,
+                    ignore_case: false,
+                    ..Default::default()
+                };
+                let pattern = self.content_pattern(value()?, options)?;
+                let regex = self.content_regex_set(&[pattern], options)?;
+                let current = tokio::fs::read_to_string(&valid_path).await?;
+                record_bytes_read(current.len() as u64);
+                (!regex.is_some_and(|regex| regex.is_match(&current)))
+                    .then(|| "the content does not match the pattern".to_string())
+            }
+        };
+        if let Some(reason) = skipped {
+            return Ok(ConditionalWriteOutcome::Skipped(reason));
+        }
+
+        self.read_cache.invalidate(&valid_path);
+        self.write_with_flags(&valid_path, content.as_bytes())
+            .await?;
+        record_bytes_written(content.len() as u64);
+        Ok(ConditionalWriteOutcome::Written)
     }
 
-    pub async fn list_directory(&self, dir_path: &Path) -> ServiceResult<Vec<tokio::fs::DirEntry>> {
+    /// Returns the git status of the entries below the directory at `dir_path`, or `None` when it
+    /// is not inside a git working tree within its allowed directory or git support is not
+    /// compiled in.
+    pub async fn git_status(&self, dir_path: &Path) -> ServiceResult<Option<GitStatusMap>> {
         let allowed_directories = self.allowed_directories().await;
-
-        let valid_path = self.validate_path(dir_path, allowed_directories)?;
-
-        let mut dir = tokio::fs::read_dir(valid_path).await?;
-
-        let mut entries = Vec::new();
-
-        // Use a loop to collect the directory entries
-        while let Some(entry) = dir.next_entry().await? {
-            entries.push(entry);
-        }
-
-        Ok(entries)
+        let valid_path = self.validate_existing_path(dir_path, allowed_directories.clone())?;
+        let dir = valid_path.to_path_buf();
+        let root = allowed_directories
+            .iter()
+            .filter(|allowed| dir.starts_with(allowed))
+            .min_by_key(|allowed| allowed.components().count())
+            .unwrap_or(&dir)
+            .clone();
+        tokio::task::spawn_blocking(move || load_git_status(&dir, &root))
+            .await
+            .map_err(|err| ServiceError::FromString(err.to_string()))
     }
 
-    pub async fn write_file(&self, file_path: &Path, content: &String) -> ServiceResult<()> {
+    /// Opens a text file for a sequence of edits. Until the returned handle is closed with
+    /// [`close_file`](Self::close_file), `apply_file_changes` edits the in-memory buffer of the
+    /// file instead of reading and writing it on every call.
+    pub async fn open_file(&self, file_path: &Path) -> ServiceResult<OpenedFile> {
         let allowed_directories = self.allowed_directories().await;
         let valid_path = self.validate_path(file_path, allowed_directories)?;
-        tokio::fs::write(valid_path, content).await?;
-        Ok(())
+        self.check_write_extension(&valid_path)?;
+        let content = self.read_to_string_with_flags(&valid_path).await?;
+        record_bytes_read(content.len() as u64);
+        let size = content.len() as u64;
+        let handle = self.open_files.open(valid_path.to_path_buf(), content)?;
+        Ok(OpenedFile {
+            handle,
+            path: valid_path.to_path_buf(),
+            size,
+        })
+    }
+
+    /// Closes a handle returned by [`open_file`](Self::open_file), writing the buffer back to
+    /// disk in a single write if it was edited. With `discard`, the buffered edits are dropped.
+    ///
+    /// Closing an edited buffer fails, and keeps the handle open, if the file on disk changed
+    /// since it was opened; `discard` is then the only way to close it.
+    pub async fn close_file(&self, handle: &str, discard: bool) -> ServiceResult<ClosedFile> {
+        let buffer = self.open_files.buffer(handle)?;
+        let bytes_written = if discard || buffer.edits == 0 {
+            None
+        } else {
+            self.check_write_extension(&buffer.path)?;
+            let on_disk = self
+                .read_to_string_with_flags(&buffer.path)
+                .await
+                .map(|content| open_files::content_hash(&content));
+            if on_disk.ok().as_ref() != Some(&buffer.opened_hash) {
+                return Err(ServiceError::FromString(format!(
+                    "'{}' changed on disk since it was opened as '{handle}'; its {} buffered edit(s) were not written. Close it with 'discard' to drop them.",
+                    buffer.path.display(),
+                    buffer.edits
+                )));
+            }
+            self.read_cache.invalidate(&buffer.path);
+            self.write_with_flags(&buffer.path, buffer.content.as_bytes())
+                .await?;
+            record_bytes_written(buffer.content.len() as u64);
+            Some(buffer.content.len() as u64)
+        };
+        self.open_files.remove(handle);
+        Ok(ClosedFile {
+            path: buffer.path,
+            edits: buffer.edits,
+            bytes_written,
+        })
     }
 
     /// Searches for files in the directory tree starting at `root_path` that match the given `pattern`,
@@ -653,6 +3782,7 @@ impl FileSystemService {
     /// # Arguments
     /// * `root_path` - The root directory to start the search from.
     /// * `pattern` - A glob pattern to match file names. If no wildcards are provided, the pattern is wrapped in `**/*{pattern}*` for partial matching.
+    ///   A trailing `/` restricts the matches to directories.
     /// * `exclude_patterns` - A list of glob patterns to exclude paths (case-sensitive).
     ///
     /// # Returns
@@ -670,17 +3800,18 @@ impl FileSystemService {
         let allowed_directories = self.allowed_directories().await;
         let valid_path = self.validate_path(root_path, allowed_directories.clone())?;
 
-        let updated_pattern = if pattern.contains('*') {
-            pattern.to_lowercase()
-        } else {
-            format!("**/*{}*", &pattern.to_lowercase())
-        };
-        let glob_pattern = updated_pattern;
+        let (glob_pattern, dirs_only) = name_glob(&pattern);
+
+        // The iterator may be driven from rayon worker threads, so capture the stats up front
+        let stats = OperationStats::current();
 
         let result = WalkDir::new(valid_path)
             .follow_links(true)
             .into_iter()
             .filter_entry(move |dir_entry| {
+                if let Some(stats) = &stats {
+                    stats.add_entries_visited(1);
+                }
                 let full_path = dir_entry.path();
 
                 // Validate each path before processing
@@ -697,13 +3828,7 @@ impl FileSystemService {
                 let relative_path = full_path.strip_prefix(root_path).unwrap_or(full_path);
 
                 let mut should_exclude = exclude_patterns.iter().any(|pattern| {
-                    let glob_pattern = if pattern.contains('*') {
-                        pattern.strip_prefix("/").unwrap_or(pattern).to_owned()
-                    } else {
-                        format!("*{pattern}*")
-                    };
-
-                    glob_match(&glob_pattern, relative_path.to_str().unwrap_or(""))
+                    glob_match(&exclude_glob(pattern), relative_path.to_str().unwrap_or(""))
                 });
 
                 // enforce min/max bytes
@@ -724,7 +3849,7 @@ impl FileSystemService {
             })
             .filter_map(|v| v.ok())
             .filter(move |entry| {
-                if root_path == entry.path() {
+                if root_path == entry.path() || (dirs_only && !entry.file_type().is_dir()) {
                     return false;
                 }
 
@@ -737,6 +3862,126 @@ impl FileSystemService {
         Ok(result)
     }
 
+    /// Tests `pattern` against `samples` with the compilation and matching of the search tools:
+    /// a glob as the name pattern of [`search_files_iter`](Self::search_files_iter), matched
+    /// against the last component of each sample path, a regex as the query of a content search,
+    /// matched against each sample line.
+    pub fn test_pattern(
+        &self,
+        pattern: &str,
+        mode: PatternMode,
+        samples: Vec<String>,
+        ignore_case: bool,
+    ) -> PatternTest {
+        match mode {
+            PatternMode::Glob => {
+                let (glob, dirs_only) = name_glob(pattern);
+                let interpretation = format!(
+                    "search_files matches the glob '{glob}' against the lowercased name of each {}; \
+                     as an exclude pattern, '{}' is matched against paths relative to the search root",
+                    if dirs_only { "directory" } else { "entry" },
+                    exclude_glob(pattern)
+                );
+                if let Some(error) = glob_syntax_error(pattern) {
+                    return PatternTest {
+                        interpretation,
+                        error: Some(error),
+                        samples: samples.into_iter().map(|sample| (sample, false)).collect(),
+                    };
+                }
+                let samples = samples
+                    .into_iter()
+                    .map(|sample| {
+                        let is_dir = sample.ends_with('/') || sample.ends_with('\\');
+                        let name = Path::new(&sample)
+                            .file_name()
+                            .map(|name| name.to_string_lossy().to_lowercase())
+                            .unwrap_or_default();
+                        let matched = (!dirs_only || is_dir) && glob_match(&glob, &name);
+                        (sample, matched)
+                    })
+                    .collect();
+                PatternTest {
+                    interpretation,
+                    error: None,
+                    samples,
+                }
+            }
+            PatternMode::Regex => {
+                let options = ContentSearchOptions {
+                    is_regex: true,
+                    ignore_case,
+                    ..Default::default()
+                };
+                let interpretation = format!(
+                    "search_files_content matches the regular expression '{pattern}' against each line{}",
+                    if ignore_case { ", ignoring case" } else { "" }
+                );
+                match self.content_matcher(&ContentQuery::from(pattern), options) {
+                    Ok(content_matcher) => PatternTest {
+                        interpretation,
+                        error: None,
+                        samples: samples
+                            .into_iter()
+                            .map(|sample| {
+                                let matched = content_matcher
+                                    .matcher
+                                    .is_match(sample.as_bytes())
+                                    .unwrap_or_default();
+                                (sample, matched)
+                            })
+                            .collect(),
+                    },
+                    Err(err) => PatternTest {
+                        interpretation,
+                        error: Some(err.to_string()),
+                        samples: samples.into_iter().map(|sample| (sample, false)).collect(),
+                    },
+                }
+            }
+        }
+    }
+
+    /// Takes up to `limit` samples for [`test_pattern`](Self::test_pattern) from `path`: the
+    /// paths of the first entries below a directory, relative to it and in name order, with a
+    /// trailing `/` for directories, or the first lines of a file.
+    pub async fn pattern_samples(&self, path: &Path, limit: usize) -> ServiceResult<Vec<String>> {
+        let allowed_directories = self.allowed_directories().await;
+        let valid_path = self.validate_existing_path(path, allowed_directories)?;
+        if !valid_path.is_dir() {
+            let file = self.open_for_read(&valid_path).await?;
+            let mut lines = self.stream_reader(file).lines();
+            let mut samples = vec![];
+            while samples.len() < limit {
+                let Some(line) = lines.next_line().await? else {
+                    break;
+                };
+                record_bytes_read(line.len() as u64 + 1);
+                samples.push(line);
+            }
+            return Ok(samples);
+        }
+
+        let root = valid_path.into_path_buf();
+        let samples = WalkDir::new(&root)
+            .min_depth(1)
+            .sort_by_file_name()
+            .into_iter()
+            .filter_map(|entry| entry.ok())
+            .take(limit)
+            .map(|entry| {
+                let relative = entry.path().strip_prefix(&root).unwrap_or(entry.path());
+                let mut sample = relative.to_string_lossy().replace('\\', "/");
+                if entry.file_type().is_dir() {
+                    sample.push('/');
+                }
+                sample
+            })
+            .collect::<Vec<_>>();
+        record_entries_visited(samples.len() as u64);
+        Ok(samples)
+    }
+
     /// Generates a JSON representation of a directory tree starting at the given path.
     ///
     /// This function recursively builds a JSON array object representing the directory structure,
@@ -751,6 +3996,9 @@ impl FileSystemService {
     /// # IMPORTANT NOTE
     ///
     /// use max_depth or max_files could lead to partial or skewed representations of actual directory tree
+    ///
+    /// File and directory counts, total size and depth of the included entries are accumulated
+    /// into `summary` during the same traversal.
     pub fn directory_tree<P: AsRef<Path>>(
         &self,
         root_path: P,
@@ -758,11 +4006,37 @@ impl FileSystemService {
         max_files: Option<usize>,
         current_count: &mut usize,
         allowed_directories: Arc<Vec<PathBuf>>,
+        summary: &mut DirectoryTreeSummary,
+    ) -> ServiceResult<(Value, bool)> {
+        self.directory_tree_level(
+            root_path,
+            max_depth,
+            max_files,
+            current_count,
+            allowed_directories,
+            summary,
+            1,
+        )
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    fn directory_tree_level<P: AsRef<Path>>(
+        &self,
+        root_path: P,
+        max_depth: Option<usize>,
+        max_files: Option<usize>,
+        current_count: &mut usize,
+        allowed_directories: Arc<Vec<PathBuf>>,
+        summary: &mut DirectoryTreeSummary,
+        level: usize,
     ) -> ServiceResult<(Value, bool)> {
-        let valid_path = self.validate_path(root_path.as_ref(), allowed_directories.clone())?;
+        let valid_path =
+            self.validate_existing_path(root_path.as_ref(), allowed_directories.clone())?;
 
-        let metadata = fs::metadata(&valid_path)?;
-        if !metadata.is_dir() {
+        if !valid_path
+            .metadata()
+            .is_some_and(|metadata| metadata.is_dir())
+        {
             return Err(ServiceError::FromString(
                 "Root path must be a directory".into(),
             ));
@@ -777,10 +4051,23 @@ impl FileSystemService {
                 .max_depth(1)
                 .follow_links(true)
                 .into_iter()
-                .filter_map(|e| e.ok())
             {
+                let entry = match entry {
+                    Ok(entry) => entry,
+                    Err(err) => {
+                        summary.walk_errors.record(err);
+                        continue;
+                    }
+                };
                 let child_path = entry.path();
-                let metadata = fs::metadata(child_path)?;
+                // An entry removed since it was listed is reported like an unreadable one
+                let metadata = match fs::metadata(child_path) {
+                    Ok(metadata) => metadata,
+                    Err(err) => {
+                        summary.walk_errors.record_io(child_path, &err);
+                        continue;
+                    }
+                };
 
                 let entry_name = child_path
                     .file_name()
@@ -790,6 +4077,7 @@ impl FileSystemService {
 
                 // Increment the count for this entry
                 *current_count += 1;
+                record_entries_visited(1);
 
                 // Check if we've exceeded max_files (if set)
                 if let Some(max) = max_files {
@@ -803,14 +4091,24 @@ impl FileSystemService {
                     "type": if metadata.is_dir() { "directory" } else { "file" }
                 });
 
+                summary.max_depth = summary.max_depth.max(level);
+                if metadata.is_dir() {
+                    summary.dir_count += 1;
+                } else {
+                    summary.file_count += 1;
+                    summary.total_size_bytes += metadata.len();
+                }
+
                 if metadata.is_dir() {
                     let next_depth = max_depth.map(|d| d - 1);
-                    let (child_children, child_reached_max_depth) = self.directory_tree(
+                    let (child_children, child_reached_max_depth) = self.directory_tree_level(
                         child_path,
                         next_depth,
                         max_files,
                         current_count,
                         allowed_directories.clone(),
+                        summary,
+                        level + 1,
                     )?;
                     json_entry
                         .as_object_mut()
@@ -854,27 +4152,124 @@ impl FileSystemService {
         format!("Index: {}\n{}\n{}", file_name, "=".repeat(68), patch)
     }
 
-    pub async fn apply_file_edits(
+    pub async fn apply_file_edits(
+        &self,
+        file_path: &Path,
+        edits: Vec<EditOperation>,
+        dry_run: Option<bool>,
+        save_to: Option<&Path>,
+    ) -> ServiceResult<String> {
+        self.apply_file_changes(file_path, edits, vec![], dry_run, save_to)
+            .await
+    }
+
+    /// Returns the replacement text of an edit, reading it from `newTextFromFile` when set.
+    async fn resolve_edit_new_text(&self, edit: &EditOperation) -> ServiceResult<String> {
+        let Some(source) = edit.new_text_from_file.as_ref() else {
+            return Ok(edit.new_text.clone());
+        };
+        if !edit.new_text.is_empty() {
+            return Err(ServiceError::FromString(
+                "Specify either 'newText' or 'newTextFromFile', not both.".to_string(),
+            ));
+        }
+        let allowed_directories = self.allowed_directories().await;
+        let valid_source = self.validate_path(Path::new(source), allowed_directories)?;
+        let content = self
+            .read_to_string_with_flags(&valid_source)
+            .await
+            .map_err(|err| {
+                ServiceError::FromString(format!("Cannot read newTextFromFile '{source}': {err}"))
+            })?;
+        record_bytes_read(content.len() as u64);
+        Ok(content)
+    }
+
+    /// Applies text edits followed by line-based comment edits, and returns the resulting diff.
+    /// Lines skipped by a line edit are reported in a note after the diff.
+    pub async fn apply_file_changes(
+        &self,
+        file_path: &Path,
+        edits: Vec<EditOperation>,
+        line_edits: Vec<LineEditOperation>,
+        dry_run: Option<bool>,
+        save_to: Option<&Path>,
+    ) -> ServiceResult<String> {
+        self.apply_file_changes_guarded(file_path, edits, line_edits, dry_run, save_to, None)
+            .await
+    }
+
+    /// Same as [`apply_file_changes`](Self::apply_file_changes), but when `expected_hash` is
+    /// set, the edits are only applied if the SHA-256 of the current content, as reported by
+    /// the dry run that previewed them, still matches. A dry run ends with that hash.
+    pub async fn apply_file_changes_guarded(
         &self,
         file_path: &Path,
         edits: Vec<EditOperation>,
+        line_edits: Vec<LineEditOperation>,
         dry_run: Option<bool>,
         save_to: Option<&Path>,
+        expected_hash: Option<&str>,
     ) -> ServiceResult<String> {
         let allowed_directories = self.allowed_directories().await;
         let valid_path = self.validate_path(file_path, allowed_directories)?;
 
+        // Edits to an open file go to its buffer, unless the result is saved elsewhere
+        let open_buffer = match save_to {
+            Some(_) => None,
+            None => self.open_files.snapshot(&valid_path)?,
+        };
+
         // Read file content and normalize line endings
-        let content_str = tokio::fs::read_to_string(&valid_path).await?;
+        let content_str = match &open_buffer {
+            Some(buffer) => buffer.content.clone(),
+            None => {
+                let content = self.read_to_string_with_flags(&valid_path).await?;
+                record_bytes_read(content.len() as u64);
+                content
+            }
+        };
+        let is_dry_run = dry_run.unwrap_or(false);
+        let content_hash = format!("{:x}", Sha256::digest(content_str.as_bytes()));
+        if let Some(expected) = expected_hash {
+            let expected = expected.trim();
+            let expected = expected.strip_prefix("sha256:").unwrap_or(expected);
+            if !is_dry_run && !expected.eq_ignore_ascii_case(&content_hash) {
+                return Err(ServiceError::FromString(format!(
+                    "'{}' changed since the edits were previewed: expected content hash {expected}, found {content_hash}. Preview the edits again.",
+                    valid_path.display()
+                )));
+            }
+        }
         let original_line_ending = self.detect_line_ending(&content_str);
         let content_str = normalize_line_endings(&content_str);
 
         // Apply edits sequentially
         let mut modified_content = content_str.clone();
-
-        for edit in edits {
-            let normalized_old = normalize_line_endings(&edit.old_text);
-            let normalized_new = normalize_line_endings(&edit.new_text);
+        let mut notes = Vec::new();
+
+        for (index, edit) in edits.into_iter().enumerate() {
+            let new_text = self.resolve_edit_new_text(&edit).await?;
+            let old_text = if edit.trim_search.unwrap_or_default() {
+                let trimmed = edit.old_text.trim();
+                if trimmed.is_empty() {
+                    return Err(ServiceError::FromString(format!(
+                        "Edit {}: 'oldText' is empty once trimmed.",
+                        index + 1
+                    )));
+                }
+                if trimmed.len() < edit.old_text.len() {
+                    notes.push(format!(
+                        "edit {}: leading and trailing whitespace was trimmed from oldText before matching.",
+                        index + 1
+                    ));
+                }
+                trimmed
+            } else {
+                edit.old_text.as_str()
+            };
+            let normalized_old = normalize_line_endings(old_text);
+            let normalized_new = normalize_line_endings(&new_text);
             // If exact match exists, use it
             if modified_content.contains(&normalized_old) {
                 modified_content = modified_content.replacen(&normalized_old, &normalized_new, 1);
@@ -979,43 +4374,112 @@ impl FileSystemService {
             }
             if !match_found {
                 return Err(RpcError::internal_error()
-                    .with_message(format!(
-                        "Could not find exact match for edit:\n{}",
-                        edit.old_text
-                    ))
+                    .with_message(format!("Could not find exact match for edit:\n{old_text}"))
                     .into());
             }
         }
 
+        for line_edit in line_edits {
+            let style = match line_edit.comment_style {
+                Some(style) => style,
+                None => CommentStyle::from_path(&valid_path).ok_or_else(|| {
+                    ServiceError::FromString(format!(
+                        "Cannot detect the comment style of '{}'; please provide commentStyle.",
+                        valid_path.display()
+                    ))
+                })?,
+            };
+            modified_content = apply_line_edit(&modified_content, &line_edit, style, &mut notes)?;
+        }
+
         let diff = self.create_unified_diff(
             &content_str,
             &modified_content,
             Some(valid_path.display().to_string()),
         );
 
-        // Format diff with appropriate number of backticks
-        let mut num_backticks = 3;
-        while diff.contains(&"`".repeat(num_backticks)) {
-            num_backticks += 1;
+        let mut formatted_diff = fenced_diff(&diff);
+        for note in notes {
+            formatted_diff.push_str(&format!("Note: {note}\n"));
+        }
+        if is_dry_run {
+            formatted_diff.push_str(&format!("Content hash: sha256:{content_hash}\n"));
         }
-        let formatted_diff = format!(
-            "{}diff\n{}{}\n\n",
-            "`".repeat(num_backticks),
-            diff,
-            "`".repeat(num_backticks)
-        );
-
-        let is_dry_run = dry_run.unwrap_or(false);
 
-        if !is_dry_run {
+        if !is_dry_run && let Some(buffer) = &open_buffer {
+            let modified_content = modified_content.replace("\n", original_line_ending);
+            self.open_files.update(buffer, modified_content)?;
+            formatted_diff.push_str(&format!(
+                "Applied to open file '{}'; close it to write the changes to disk.\n",
+                buffer.handle
+            ));
+        } else if !is_dry_run {
             let target = save_to.unwrap_or(valid_path.as_path());
+            self.check_write_extension(target)?;
             let modified_content = modified_content.replace("\n", original_line_ending);
-            tokio::fs::write(target, modified_content).await?;
+            self.read_cache.invalidate(target);
+            self.write_with_flags(target, modified_content.as_bytes())
+                .await?;
+            record_bytes_written(modified_content.len() as u64);
         }
 
         Ok(formatted_diff)
     }
 
+    /// Applies pointer-addressed edits to a JSON, YAML or TOML file and returns the diff. The
+    /// format is detected from the extension when `format` is `None`.
+    pub async fn apply_structured_edits(
+        &self,
+        file_path: &Path,
+        format: Option<StructuredFormat>,
+        edits: &[StructuredEditOperation],
+        dry_run: bool,
+    ) -> ServiceResult<String> {
+        let allowed_directories = self.allowed_directories().await;
+        let valid_path = self.validate_path(file_path, allowed_directories)?;
+        let format = format
+            .or_else(|| StructuredFormat::from_path(&valid_path))
+            .ok_or_else(|| {
+                ServiceError::FromString(format!(
+                    "Cannot detect the format of '{}'; please provide format.",
+                    valid_path.display()
+                ))
+            })?;
+
+        // Edits to an open file go to its buffer, as with apply_file_changes
+        let open_buffer = self.open_files.snapshot(&valid_path)?;
+        let content = match &open_buffer {
+            Some(buffer) => buffer.content.clone(),
+            None => {
+                let content = self.read_to_string_with_flags(&valid_path).await?;
+                record_bytes_read(content.len() as u64);
+                content
+            }
+        };
+        let modified_content = apply_structured_edits(&content, format, edits)?;
+
+        let diff = self.create_unified_diff(
+            &content,
+            &modified_content,
+            Some(valid_path.display().to_string()),
+        );
+        let mut formatted_diff = fenced_diff(&diff);
+        if !dry_run && let Some(buffer) = &open_buffer {
+            self.open_files.update(buffer, modified_content)?;
+            formatted_diff.push_str(&format!(
+                "Applied to open file '{}'; close it to write the changes to disk.\n",
+                buffer.handle
+            ));
+        } else if !dry_run {
+            self.check_write_extension(&valid_path)?;
+            self.read_cache.invalidate(&valid_path);
+            self.write_with_flags(&valid_path, modified_content.as_bytes())
+                .await?;
+            record_bytes_written(modified_content.len() as u64);
+        }
+        Ok(formatted_diff)
+    }
+
     pub fn escape_regex(&self, text: &str) -> String {
         // Covers special characters in regex engines (RE2, PCRE, JS, Python)
         const SPECIAL_CHARS: &[char] = &[
@@ -1048,44 +4512,249 @@ impl FileSystemService {
         file_path: impl AsRef<Path>,
         is_regex: Option<bool>,
     ) -> ServiceResult<Option<FileSearchResult>> {
-        let query = if is_regex.unwrap_or_default() {
+        self.content_search_with_options(
+            query,
+            file_path,
+            ContentSearchOptions {
+                is_regex: is_regex.unwrap_or_default(),
+                ..Default::default()
+            },
+        )
+    }
+
+    /// Same as [`content_search`](Self::content_search), with control over case folding and
+    /// Unicode normalization.
+    ///
+    /// When `normalize_unicode` is set, both the query and the file content are converted to NFC
+    /// before matching, so composed and decomposed forms of the same text match each other.
+    /// Reported positions then refer to the normalized line.
+    pub fn content_search_with_options(
+        &self,
+        query: &str,
+        file_path: impl AsRef<Path>,
+        options: ContentSearchOptions,
+    ) -> ServiceResult<Option<FileSearchResult>> {
+        let matcher = self.content_matcher(&ContentQuery::from(query), options)?;
+        let deadline = self.search_deadline();
+        let (result, _) =
+            self.search_file_content(&matcher, file_path.as_ref(), options, deadline, &mut [0])?;
+        Ok(result)
+    }
+
+    /// Returns the instant at which a content search starting now runs out of time.
+    fn search_deadline(&self) -> Option<Instant> {
+        self.performance_config
+            .search_timeout
+            .map(|timeout| Instant::now() + timeout)
+    }
+
+    /// Prepares one pattern of a content search query: normalizes it if requested, escapes
+    /// literal text and enforces the configured pattern length.
+    fn content_pattern(&self, query: &str, options: ContentSearchOptions) -> ServiceResult<String> {
+        let query = if options.normalize_unicode {
+            query.nfc().collect::<String>()
+        } else {
             query.to_string()
+        };
+        let query = if options.is_regex {
+            query
         } else {
-            self.escape_regex(query)
+            self.escape_regex(&query)
         };
 
+        let max_length = self.performance_config.max_pattern_length;
+        let length = query.chars().count();
+        if length > max_length {
+            return Err(ServiceError::FromString(format!(
+                "Search pattern is too long: {length} characters exceeds the max_pattern_length limit of {max_length}."
+            )));
+        }
+        Ok(query)
+    }
+
+    /// Maps a regex build error, reporting patterns over the compiled size limit as too complex.
+    fn regex_build_error(&self, err: impl std::fmt::Display + Into<ServiceError>) -> ServiceError {
+        if err.to_string().contains("size limit") {
+            ServiceError::FromString(format!(
+                "Search pattern is too complex: it exceeds the regex_size_limit of {} bytes once compiled.",
+                self.performance_config.regex_size_limit
+            ))
+        } else {
+            err.into()
+        }
+    }
+
+    /// Compiles prepared `patterns` into a single set, or `None` when there are none.
+    fn content_regex_set(
+        &self,
+        patterns: &[String],
+        options: ContentSearchOptions,
+    ) -> ServiceResult<Option<RegexSet>> {
+        if patterns.is_empty() {
+            return Ok(None);
+        }
+        let size_limit = self.performance_config.regex_size_limit;
+        RegexSetBuilder::new(patterns)
+            .case_insensitive(options.ignore_case)
+            .unicode(true)
+            .size_limit(size_limit)
+            .dfa_size_limit(size_limit)
+            .build()
+            .map(Some)
+            .map_err(|err| self.regex_build_error(err))
+    }
+
+    /// Builds the matcher for a content search query, enforcing the configured pattern length
+    /// and compiled size limits.
+    fn content_matcher(
+        &self,
+        query: &ContentQuery,
+        options: ContentSearchOptions,
+    ) -> ServiceResult<ContentMatcher> {
+        if query.patterns.is_empty() {
+            return Err(ServiceError::FromString(
+                "The search query has no pattern.".to_string(),
+            ));
+        }
+        let patterns = query
+            .patterns
+            .iter()
+            .map(|pattern| self.content_pattern(pattern, options))
+            .collect::<ServiceResult<Vec<_>>>()?;
+        let exclusions = query
+            .exclude_in_line
+            .iter()
+            .map(|pattern| self.content_pattern(pattern, options))
+            .collect::<ServiceResult<Vec<_>>>()?;
+
+        let combined = match patterns.as_slice() {
+            [pattern] => pattern.clone(),
+            patterns => patterns
+                .iter()
+                .map(|pattern| format!("(?:{pattern})"))
+                .collect::<Vec<_>>()
+                .join("|"),
+        };
+        // With `unicode` enabled, case-insensitive matching uses Unicode simple case folding
+        let size_limit = self.performance_config.regex_size_limit;
         let matcher = RegexMatcherBuilder::new()
-            .case_insensitive(true)
-            .build(query.as_str())?;
+            .case_insensitive(options.ignore_case)
+            .unicode(true)
+            .size_limit(size_limit)
+            .dfa_size_limit(size_limit)
+            .build(combined.as_str())
+            .map_err(|err| self.regex_build_error(err))?;
+
+        Ok(ContentMatcher {
+            matcher,
+            patterns: if patterns.len() > 1 {
+                self.content_regex_set(&patterns, options)?
+            } else {
+                None
+            },
+            exclusions: self.content_regex_set(&exclusions, options)?,
+        })
+    }
 
+    /// Searches one file with a prepared matcher. The second value is `true` when `deadline`
+    /// passed before the whole file was scanned, in which case the matches are partial. The
+    /// deadline is checked between reads, after the start of the file has been scanned.
+    ///
+    /// `pattern_matches` holds a counter per pattern of the query, incremented for each line the
+    /// pattern matches.
+    fn search_file_content(
+        &self,
+        content_matcher: &ContentMatcher,
+        file_path: &Path,
+        options: ContentSearchOptions,
+        deadline: Option<Instant>,
+        pattern_matches: &mut [u64],
+    ) -> ServiceResult<(Option<FileSearchResult>, bool)> {
         let mut searcher = Searcher::new();
         let mut result = FileSearchResult {
-            file_path: file_path.as_ref().to_path_buf(),
+            file_path: file_path.to_path_buf(),
             matches: vec![],
         };
+        let matcher = &content_matcher.matcher;
 
-        searcher.set_binary_detection(BinaryDetection::quit(b'\x00'));
-
-        searcher.search_path(
-            &matcher,
-            file_path,
-            UTF8(|line_number, line| {
-                let actual_match = matcher.find(line.as_bytes())?.unwrap();
-
-                result.matches.push(ContentMatchResult {
-                    line_number,
-                    start_pos: actual_match.start(),
-                    line_text: self.extract_snippet(line, actual_match, None, None),
-                });
-                Ok(true)
-            }),
-        )?;
+        // Files that are not text are skipped by the probe, which every text tool shares
+        let class = classify(file_path, &self.performance_config);
+        if matches!(class, FileClass::Binary | FileClass::TooLarge(_)) {
+            return Ok((None, false));
+        }
+        searcher.set_binary_detection(BinaryDetection::none());
+
+        let sink = UTF8(|line_number, line| {
+            let text = line.trim_end_matches(['\r', '\n']);
+            if content_matcher
+                .exclusions
+                .as_ref()
+                .is_some_and(|exclusions| exclusions.is_match(text))
+            {
+                return Ok(true);
+            }
+            match &content_matcher.patterns {
+                Some(patterns) => {
+                    for index in patterns.matches(text).iter() {
+                        pattern_matches[index] += 1;
+                    }
+                }
+                None => pattern_matches[0] += 1,
+            }
+            let actual_match = matcher.find(line.as_bytes())?.unwrap();
+
+            result.matches.push(ContentMatchResult {
+                line_number,
+                start_pos: actual_match.start(),
+                line_text: self.extract_snippet(line, actual_match, None, None),
+                end_line_number: line_number,
+                duplicate_lines: vec![],
+            });
+            Ok(true)
+        });
+
+        let searched =
+            if options.normalize_unicode || matches!(class, FileClass::TextOtherEncoding(_)) {
+                self.telemetry.increment(Counter::SearchBuffered);
+                let content = fs::read(file_path)?;
+                let text = match class {
+                    FileClass::TextOtherEncoding(_) => {
+                        self.telemetry.increment(Counter::TextDecoded);
+                        decode_text(content, &class).unwrap_or_default()
+                    }
+                    _ => String::from_utf8(content)
+                        .unwrap_or_else(|err| String::from_utf8_lossy(err.as_bytes()).into_owned()),
+                };
+                let text = if options.normalize_unicode {
+                    text.nfc().collect::<String>()
+                } else {
+                    text
+                };
+                searcher.search_reader(
+                    matcher,
+                    DeadlineReader::new(text.as_bytes(), deadline),
+                    sink,
+                )
+            } else {
+                self.telemetry.increment(Counter::SearchStreamed);
+                let file = fs::File::open(file_path)?;
+                searcher.search_reader(matcher, DeadlineReader::new(file, deadline), sink)
+            };
+        let timed_out = match searched {
+            Ok(()) => false,
+            Err(err) if err.kind() == std::io::ErrorKind::TimedOut => true,
+            Err(err) => return Err(err.into()),
+        };
 
         if result.matches.is_empty() {
-            return Ok(None);
+            return Ok((None, timed_out));
+        }
+
+        if options.dedupe {
+            result.matches = ContentMatchResult::dedupe(result.matches);
         }
 
-        Ok(Some(result))
+        Ok((Some(result), timed_out))
     }
 
     /// Extracts a snippet from a given line of text around a match.
@@ -1165,17 +4834,51 @@ impl FileSystemService {
         result
     }
 
+    /// Searches the content of the files below `root_path` matching `pattern`.
+    ///
+    /// Invalid, over-long or over-complex queries fail the whole search. Files that cannot be
+    /// read are skipped. When the configured search timeout runs out, the search stops and
+    /// returns the matches found so far with `timed_out` set.
     #[allow(clippy::too_many_arguments)]
     pub async fn search_files_content(
         &self,
         root_path: impl AsRef<Path>,
         pattern: &str,
         query: &str,
-        is_regex: bool,
+        options: ContentSearchOptions,
+        exclude_patterns: Option<Vec<String>>,
+        min_bytes: Option<u64>,
+        max_bytes: Option<u64>,
+    ) -> ServiceResult<ContentSearchResults> {
+        self.search_files_content_query(
+            root_path,
+            pattern,
+            &ContentQuery::from(query),
+            options,
+            exclude_patterns,
+            min_bytes,
+            max_bytes,
+        )
+        .await
+    }
+
+    /// Same as [`search_files_content`](Self::search_files_content), for lines matching any of
+    /// several patterns and none of the exclusions of `query`. All patterns are compiled into one
+    /// matcher, so each file is scanned once; the number of lines matched by each pattern is
+    /// returned in `pattern_matches`.
+    #[allow(clippy::too_many_arguments)]
+    pub async fn search_files_content_query(
+        &self,
+        root_path: impl AsRef<Path>,
+        pattern: &str,
+        query: &ContentQuery,
+        options: ContentSearchOptions,
         exclude_patterns: Option<Vec<String>>,
         min_bytes: Option<u64>,
         max_bytes: Option<u64>,
-    ) -> ServiceResult<Vec<FileSearchResult>> {
+    ) -> ServiceResult<ContentSearchResults> {
+        let matcher = self.content_matcher(query, options)?;
+        let deadline = self.search_deadline();
         let files_iter = self
             .search_files_iter(
                 root_path.as_ref(),
@@ -1186,13 +4889,31 @@ impl FileSystemService {
             )
             .await?;
 
-        let results: Vec<FileSearchResult> = files_iter
-            .filter_map(|entry| {
-                self.content_search(query, entry.path(), Some(is_regex))
-                    .ok()
-                    .and_then(|v| v)
-            })
-            .collect();
+        let mut results = ContentSearchResults {
+            pattern_matches: vec![0; query.patterns.len()],
+            ..Default::default()
+        };
+        for (index, entry) in files_iter.enumerate() {
+            // Like within a file, the first one is always scanned
+            if index > 0 && deadline.is_some_and(|deadline| Instant::now() >= deadline) {
+                results.timed_out = true;
+                break;
+            }
+            let Ok((result, timed_out)) = self.search_file_content(
+                &matcher,
+                entry.path(),
+                options,
+                deadline,
+                &mut results.pattern_matches,
+            ) else {
+                continue;
+            };
+            results.files.extend(result);
+            if timed_out {
+                results.timed_out = true;
+                break;
+            }
+        }
         Ok(results)
     }
 
@@ -1207,8 +4928,8 @@ impl FileSystemService {
         let valid_path = self.validate_path(file_path, allowed_directories)?;
 
         // Open file asynchronously and create a BufReader
-        let file = File::open(&valid_path).await?;
-        let mut reader = BufReader::new(file);
+        let file = self.open_for_read(&valid_path).await?;
+        let mut reader = self.stream_reader(file);
         let mut result = String::with_capacity(n * 100); // Estimate capacity (avg 100 bytes/line)
         let mut count = 0;
 
@@ -1220,6 +4941,7 @@ impl FileSystemService {
             if bytes_read == 0 {
                 break; // Reached EOF
             }
+            record_bytes_read(bytes_read as u64);
             result.push_str(&String::from_utf8_lossy(&line));
             count += 1;
         }
@@ -1238,7 +4960,7 @@ impl FileSystemService {
         let valid_path = self.validate_path(file_path, allowed_directories)?;
 
         // Open file asynchronously
-        let file = File::open(&valid_path).await?;
+        let file = self.open_for_read(&valid_path).await?;
         let file_size = file.metadata().await?.len();
 
         // If file is empty or n is 0, return empty string
@@ -1247,10 +4969,10 @@ impl FileSystemService {
         }
 
         // Create a BufReader
-        let mut reader = BufReader::new(file);
+        let mut reader = self.stream_reader(file);
         let mut line_count = 0;
         let mut pos = file_size;
-        let chunk_size = 8192; // 8KB chunks
+        let chunk_size = self.performance_config.stream_buffer_size;
         let mut buffer = vec![0u8; chunk_size];
         let mut newline_positions = Vec::new();
 
@@ -1260,6 +4982,7 @@ impl FileSystemService {
             pos -= read_size as u64;
             reader.seek(SeekFrom::Start(pos)).await?;
             let read_bytes = reader.read_exact(&mut buffer[..read_size]).await?;
+            record_bytes_read(read_bytes as u64);
 
             // Process chunk in reverse to find newlines
             for (i, byte) in buffer[..read_bytes].iter().enumerate().rev() {
@@ -1272,7 +4995,7 @@ impl FileSystemService {
 
         // Check if file ends with a non-newline character (partial last line)
         if file_size > 0 {
-            let mut temp_reader = BufReader::new(File::open(&valid_path).await?);
+            let mut temp_reader = BufReader::new(self.open_for_read(&valid_path).await?);
             temp_reader.seek(SeekFrom::End(-1)).await?;
             let mut last_byte = [0u8; 1];
             temp_reader.read_exact(&mut last_byte).await?;
@@ -1281,11 +5004,13 @@ impl FileSystemService {
             }
         }
 
-        // Determine start position for reading the last n lines
+        // Determine start position for reading the last n lines: just after the line break
+        // ending the line before them
+        newline_positions.reverse();
         let start_pos = if line_count <= n {
             0 // Read from start if fewer than n lines
         } else {
-            *newline_positions.get(line_count - n).unwrap_or(&0) + 1
+            newline_positions[line_count - n - 1] + 1
         };
 
         // Read forward from start_pos
@@ -1304,6 +5029,7 @@ impl FileSystemService {
                 }
                 break;
             }
+            record_bytes_read(bytes_read as u64);
             result.push_str(&String::from_utf8_lossy(&line));
             lines_read += 1;
         }
@@ -1311,6 +5037,108 @@ impl FileSystemService {
         Ok(result)
     }
 
+    /// Returns the last `n` lines of a file, then follows it for `follow` (at most
+    /// [`MAX_FOLLOW_DURATION`]) and adds the lines appended meanwhile, like `tail -f | grep`.
+    /// When `pattern` is set, only the lines matching that regex are returned. A file that
+    /// shrinks, as when it is truncated or rotated, is read again from its start. The output ends
+    /// with a summary of what was followed.
+    pub async fn tail_and_follow(
+        &self,
+        file_path: &Path,
+        n: usize,
+        pattern: Option<&str>,
+        follow: Option<Duration>,
+    ) -> ServiceResult<String> {
+        let options = ContentSearchOptions {
+            is_regex: true,
+            ignore_case: false,
+            ..Default::default()
+        };
+        let filter = match pattern {
+            Some(pattern) => {
+                self.content_regex_set(&[self.content_pattern(pattern, options)?], options)?
+            }
+            None => None,
+        };
+        let keep = |line: &str| {
+            filter
+                .as_ref()
+                .is_none_or(|filter| filter.is_match(line.trim_end_matches(['\r', '\n'])))
+        };
+
+        let allowed_directories = self.allowed_directories().await;
+        let valid_path = self.validate_path(file_path, allowed_directories)?;
+        let mut offset = metadata(&valid_path).await?.len();
+        let mut output: String = self
+            .tail_file(&valid_path, n)
+            .await?
+            .split_inclusive('\n')
+            .filter(|line| keep(line))
+            .collect();
+        let Some(follow) = follow else {
+            return Ok(output);
+        };
+        if !output.is_empty() && !output.ends_with('\n') {
+            output.push('\n');
+        }
+
+        let follow = follow.min(MAX_FOLLOW_DURATION);
+        let deadline = Instant::now() + follow;
+        let mut pending = Vec::new();
+        let (mut appended, mut matched, mut restarted) = (0, 0, false);
+        let mut take_line = |line: &[u8], output: &mut String| {
+            let line = String::from_utf8_lossy(line);
+            appended += 1;
+            if keep(&line) {
+                matched += 1;
+                output.push_str(&line);
+            }
+        };
+        loop {
+            let size = metadata(&valid_path).await?.len();
+            if size < offset {
+                offset = 0;
+                pending.clear();
+                restarted = true;
+            }
+            if size > offset {
+                let mut file = self.open_for_read(&valid_path).await?;
+                file.seek(SeekFrom::Start(offset)).await?;
+                let mut chunk = Vec::new();
+                file.take(size - offset).read_to_end(&mut chunk).await?;
+                record_bytes_read(chunk.len() as u64);
+                offset += chunk.len() as u64;
+                pending.extend_from_slice(&chunk);
+                while let Some(end) = pending.iter().position(|&b| b == b'\n') {
+                    let line: Vec<u8> = pending.drain(..=end).collect();
+                    take_line(&line, &mut output);
+                }
+            }
+            let now = Instant::now();
+            if now >= deadline {
+                break;
+            }
+            tokio::time::sleep(FOLLOW_POLL_INTERVAL.min(deadline - now)).await;
+        }
+        // A last line still missing its line break
+        if !pending.is_empty() {
+            take_line(&pending, &mut output);
+            output.push('\n');
+        }
+
+        output.push_str(&format!(
+            "[followed for {:.1}s: {appended} {} appended, {matched} returned{}]",
+            follow.as_secs_f64(),
+            if appended == 1 { "line" } else { "lines" },
+            if restarted {
+                "; the file shrank and was read again from its start"
+            } else {
+                ""
+            }
+        ));
+        Ok(output)
+    }
+
     /// Reads lines from a text file starting at the specified offset (0-based), preserving line endings.
     /// Args:
     ///     path: Path to the file
@@ -1328,9 +5156,9 @@ impl FileSystemService {
         let valid_path = self.validate_path(path, allowed_directories)?;
 
         // Open file and get metadata before moving into BufReader
-        let file = File::open(&valid_path).await?;
+        let file = self.open_for_read(&valid_path).await?;
         let file_size = file.metadata().await?.len();
-        let mut reader = BufReader::new(file);
+        let mut reader = self.stream_reader(file);
 
         // If file is empty or limit is 0, return empty string
         if file_size == 0 || limit == Some(0) {
@@ -1356,6 +5184,7 @@ impl FileSystemService {
                     if bytes_read == 0 {
                         break; // Reached EOF
                     }
+                    record_bytes_read(bytes_read as u64);
                     result.push_str(&String::from_utf8_lossy(&buffer));
                 }
             }
@@ -1366,6 +5195,7 @@ impl FileSystemService {
                     if bytes_read == 0 {
                         break; // Reached EOF
                     }
+                    record_bytes_read(bytes_read as u64);
                     result.push_str(&String::from_utf8_lossy(&buffer));
                 }
             }
@@ -1391,18 +5221,47 @@ impl FileSystemService {
     /// - The search pattern is `"**/*"` (all files) and no exclusions are applied.
     /// - Parallel iteration is used to speed up the metadata fetching and summation.
     pub async fn calculate_directory_size(&self, root_path: &Path) -> ServiceResult<u64> {
+        Ok(self.calculate_directory_usage(root_path).await?.bytes)
+    }
+
+    /// Sums the sizes of the files below `root_path`, and the disk space allocated to them.
+    pub async fn calculate_directory_usage(&self, root_path: &Path) -> ServiceResult<DiskUsage> {
         let entries = self
             .search_files_iter(root_path, "**/*".to_string(), vec![], None, None)
             .await?
             .filter(|e| e.file_type().is_file()); // Only process files
 
         // Use rayon to parallelize size summation
-        let total_size: u64 = entries
+        self.take_fast_path(FAST_PATH_PARALLEL);
+        let usage = entries
             .par_bridge() // Convert to parallel iterator
-            .filter_map(|entry| entry.metadata().ok().map(|meta| meta.len()))
-            .sum();
+            .filter_map(|entry| {
+                let metadata = self.descriptor_limit.with_permit(|| entry.metadata());
+                metadata.ok().map(|meta| DiskUsage {
+                    bytes: meta.len(),
+                    allocated_bytes: allocated_size(&meta),
+                })
+            })
+            .reduce(DiskUsage::default, |a, b| DiskUsage {
+                bytes: a.bytes + b.bytes,
+                allocated_bytes: a.allocated_bytes + b.allocated_bytes,
+            });
+
+        Ok(usage)
+    }
 
-        Ok(total_size)
+    /// Computes a single digest of the contents and structure of the tree below `root_path`,
+    /// see [`hash_directory`]. Identical trees hash equal wherever they are located.
+    pub async fn hash_directory(&self, root_path: &Path) -> ServiceResult<DirectoryHash> {
+        let allowed_directories = self.allowed_directories().await;
+        let valid_path = self.validate_dir(root_path, allowed_directories)?;
+        let path = valid_path.to_path_buf();
+        let buffer_size = self.performance_config.stream_buffer_size;
+        let hash = tokio::task::spawn_blocking(move || hash_directory(&path, buffer_size))
+            .await
+            .map_err(|err| ServiceError::FromString(err.to_string()))??;
+        record_bytes_read(hash.bytes);
+        Ok(hash)
     }
 
     /// Recursively finds all empty directories within the given root path.
@@ -1473,6 +5332,299 @@ impl FileSystemService {
         Ok(empty_dirs)
     }
 
+    /// Removes the empty directories below `root_path`, see [`plan_prune`]. Neither the root nor
+    /// any allowed directory is removed. With `dry_run`, only reports what would be removed.
+    pub async fn prune_empty_directories(
+        &self,
+        root_path: &Path,
+        keep_patterns: Vec<String>,
+        gitkeep_as_empty: bool,
+        dry_run: bool,
+    ) -> ServiceResult<PrunePlan> {
+        let allowed_directories = self.allowed_directories().await;
+        let valid_path = self.validate_dir(root_path, allowed_directories.clone())?;
+        let root = valid_path.to_path_buf();
+        let options = PruneOptions {
+            keep_patterns,
+            gitkeep_as_empty,
+            protected: allowed_directories.to_vec(),
+        };
+        let plan = tokio::task::spawn_blocking(move || plan_prune(&root, &options))
+            .await
+            .map_err(|err| ServiceError::FromString(err.to_string()))??;
+        if dry_run {
+            return Ok(plan);
+        }
+
+        self.write_budget.charge(0, plan.bytes)?;
+        for file in &plan.placeholders {
+            self.read_cache.invalidate(file);
+        }
+        let plan = tokio::task::spawn_blocking(move || execute_prune(&plan).map(|_| plan))
+            .await
+            .map_err(|err| ServiceError::FromString(err.to_string()))??;
+        Ok(plan)
+    }
+
+    /// Moves every file below `root_path` into `destination`, or into the root itself, see
+    /// [`plan_flatten`] for how names are given. Directories left empty are removed unless
+    /// `keep_directories` is set. Files that fail to move are reported without stopping the
+    /// others.
+    pub async fn flatten_directory(
+        &self,
+        root_path: &Path,
+        destination: Option<&Path>,
+        prefix_paths: bool,
+        keep_directories: bool,
+        dry_run: bool,
+    ) -> ServiceResult<FlattenReport> {
+        let allowed_directories = self.allowed_directories().await;
+        let valid_path = self.validate_dir(root_path, allowed_directories.clone())?;
+        let root = valid_path.to_path_buf();
+        let destination = match destination {
+            Some(destination) => self
+                .validate_path(destination, allowed_directories.clone())?
+                .into_path_buf(),
+            None => root.clone(),
+        };
+        let options = FlattenOptions {
+            prefix_paths,
+            keep_directories,
+            protected: allowed_directories.to_vec(),
+        };
+        let plan = tokio::task::spawn_blocking(move || plan_flatten(&root, &destination, &options))
+            .await
+            .map_err(|err| ServiceError::FromString(err.to_string()))??;
+        record_entries_visited((plan.moves.len() + plan.directories.len()) as u64);
+        for planned in &plan.moves {
+            self.check_write_extension(&planned.to)?;
+            if self.open_files.snapshot(&planned.from)?.is_some() {
+                return Err(ServiceError::FromString(format!(
+                    "'{}' is open; close it before flattening '{}'.",
+                    planned.from.display(),
+                    plan.root.display()
+                )));
+            }
+        }
+        if dry_run {
+            return Ok(FlattenReport {
+                dry_run: true,
+                moved: plan.moves.len(),
+                plan,
+                ..Default::default()
+            });
+        }
+
+        for planned in &plan.moves {
+            self.read_cache.invalidate(&planned.from);
+        }
+        tokio::task::spawn_blocking(move || execute_flatten(plan))
+            .await
+            .map_err(|err| ServiceError::FromString(err.to_string()))
+    }
+
+    /// Reads the files listed by the context pack manifest at `manifest_path`, see
+    /// [`parse_manifest`]. Relative paths and globs are resolved against the directory of the
+    /// manifest, and every file must be within the allowed directories. A file matched by a glob
+    /// is skipped when an earlier entry already included it. Entries that cannot be read are
+    /// reported in their section rather than failing the pack.
+    pub async fn read_context_pack(
+        &self,
+        manifest_path: &Path,
+        max_bytes: Option<usize>,
+        redact_secrets: Option<bool>,
+    ) -> ServiceResult<ContextPack> {
+        let allowed_directories = self.allowed_directories().await;
+        let valid_path = self.validate_path(manifest_path, allowed_directories)?;
+        let manifest = self.read_text_file(&valid_path).await?;
+        let base = valid_path.parent().unwrap_or(&valid_path).to_path_buf();
+
+        let mut pack = ContextPack {
+            manifest: valid_path.to_path_buf(),
+            max_bytes,
+            ..Default::default()
+        };
+        let mut included: HashSet<PathBuf> = HashSet::new();
+        for line in parse_manifest(&manifest) {
+            let failed = |reason: String| PackSection {
+                label: format!("{} (manifest line {})", line.text, line.number),
+                content: Err(reason),
+            };
+            let files = match &line.entry {
+                ManifestEntry::Invalid(reason) => {
+                    pack.sections.push(failed(reason.clone()));
+                    continue;
+                }
+                ManifestEntry::File { path, lines } => {
+                    let label = match lines {
+                        Some((start, Some(end))) if start == end => {
+                            format!("{path} (line {start})")
+                        }
+                        Some((start, Some(end))) => format!("{path} (lines {start}-{end})"),
+                        Some((start, None)) => format!("{path} (lines {start}-)"),
+                        None => path.clone(),
+                    };
+                    vec![(label, base.join(expand_home(PathBuf::from(path))), *lines)]
+                }
+                ManifestEntry::Glob(pattern) => {
+                    let mut matches: Vec<PathBuf> = self
+                        .search_files_iter(&base, "**/*".to_string(), vec![], None, None)
+                        .await?
+                        .filter(|entry| entry.file_type().is_file())
+                        .map(|entry| entry.into_path())
+                        .filter(|path| {
+                            matches_file_glob(pattern, path.strip_prefix(&base).unwrap_or(path))
+                        })
+                        .collect();
+                    matches.sort();
+                    record_entries_visited(matches.len() as u64);
+                    if matches.is_empty() {
+                        pack.sections
+                            .push(failed(format!("no files match the glob '{pattern}'")));
+                    }
+                    matches
+                        .into_iter()
+                        .filter(|path| !included.contains(&normalize_path(path)))
+                        .map(|path| {
+                            let label = path
+                                .strip_prefix(&base)
+                                .unwrap_or(&path)
+                                .display()
+                                .to_string();
+                            (label, path, None)
+                        })
+                        .collect()
+                }
+            };
+            for (label, path, lines) in files {
+                let content = self
+                    .read_text_file(&path)
+                    .await
+                    .map_err(|err| err.to_string());
+                let content = match (content, lines) {
+                    (Ok(content), Some((start, end))) => slice_lines(&content, start, end),
+                    (content, _) => content,
+                };
+                let content = content.map(|content| {
+                    let (content, redactions) = self.redact_output(&path, redact_secrets, content);
+                    if let Some(count) = redactions {
+                        *pack.redactions.get_or_insert(0) += count;
+                    }
+                    content
+                });
+                included.insert(normalize_path(&path));
+                pack.sections.push(PackSection { label, content });
+            }
+        }
+        Ok(pack)
+    }
+
+    /// Deletes the file, symbolic link or directory at `path`, see [`plan_delete`] for the
+    /// targets that are refused. Every entry is counted before anything is removed; entries that
+    /// fail to be removed are reported rather than stopping the delete.
+    pub async fn delete_path(
+        &self,
+        path: &Path,
+        recursive: bool,
+        allow_vcs: bool,
+        force: bool,
+        dry_run: bool,
+    ) -> ServiceResult<DeleteReport> {
+        let allowed_directories = self.allowed_directories().await;
+        let valid_path = self.validate_path(path, allowed_directories.clone())?;
+        if !valid_path.is_dir() {
+            self.check_write_extension(&valid_path)?;
+        }
+        let target = valid_path.into_path_buf();
+        let options = DeleteOptions {
+            recursive,
+            allow_vcs,
+            force,
+            max_entries: self.performance_config.max_delete_entries,
+            protected: allowed_directories.to_vec(),
+        };
+        let plan = tokio::task::spawn_blocking(move || plan_delete(&target, &options))
+            .await
+            .map_err(|err| ServiceError::FromString(err.to_string()))??;
+        record_entries_visited(plan.entries.len() as u64);
+        if dry_run {
+            return Ok(DeleteReport {
+                target: plan.target,
+                dry_run: true,
+                planned: plan.counts,
+                ..Default::default()
+            });
+        }
+
+        self.write_budget.charge(0, plan.counts.bytes)?;
+        for entry in &plan.entries {
+            self.read_cache.invalidate(&entry.path);
+        }
+        tokio::task::spawn_blocking(move || execute_delete(&plan))
+            .await
+            .map_err(|err| ServiceError::FromString(err.to_string()))
+    }
+
+    /// Flags the files below `root_path` that look minified or generated, see
+    /// [`classify_sample`]. Only the first [`GENERATED_SAMPLE_SIZE`] bytes of each file are read.
+    pub async fn detect_generated_files(
+        &self,
+        root_path: &Path,
+        pattern: Option<String>,
+        exclude_patterns: Option<Vec<String>>,
+    ) -> ServiceResult<Vec<GeneratedFile>> {
+        let allowed_directories = self.allowed_directories().await;
+        let valid_path = self.validate_dir(root_path, allowed_directories)?;
+        let files: Vec<PathBuf> = self
+            .search_files_iter(
+                &valid_path,
+                pattern.unwrap_or("**/*".to_string()),
+                exclude_patterns.unwrap_or_default(),
+                None,
+                None,
+            )
+            .await?
+            .filter(|entry| entry.file_type().is_file())
+            .map(|entry| entry.into_path())
+            .collect();
+
+        // Sampling runs on rayon worker threads, which do not see the task-local stats
+        let stats = OperationStats::current();
+        let descriptors = self.descriptor_limit.clone();
+        self.take_fast_path(FAST_PATH_PARALLEL);
+        let mut flagged = tokio::task::spawn_blocking(move || {
+            use std::io::Read;
+
+            files
+                .into_par_iter()
+                .filter_map(|path| {
+                    let mut sample = Vec::with_capacity(GENERATED_SAMPLE_SIZE);
+                    descriptors
+                        .with_permit(|| {
+                            fs::File::open(&path)?
+                                .take(GENERATED_SAMPLE_SIZE as u64)
+                                .read_to_end(&mut sample)
+                        })
+                        .ok()?;
+                    if let Some(stats) = &stats {
+                        stats.add_bytes_read(sample.len() as u64);
+                    }
+                    let (reasons, longest_line, average_line_length) = classify_sample(&sample)?;
+                    Some(GeneratedFile {
+                        path,
+                        reasons,
+                        longest_line,
+                        average_line_length,
+                    })
+                })
+                .collect::<Vec<_>>()
+        })
+        .await
+        .map_err(|err| ServiceError::FromString(err.to_string()))?;
+        flagged.sort_by(|a, b| a.path.cmp(&b.path));
+        Ok(flagged)
+    }
+
     /// Finds groups of duplicate files within the given root path.
     /// Returns a vector of vectors, where each inner vector contains paths to files with identical content.
     /// Files are considered duplicates if they have the same size and SHA-256 hash.
@@ -1490,6 +5642,8 @@ impl FileSystemService {
 
         // Get Tokio runtime handle
         let rt = tokio::runtime::Handle::current();
+        // Hashing runs on rayon worker threads, which do not see the task-local stats
+        let stats = OperationStats::current();
 
         // Step 1: Collect files and group by size
         let mut size_map: HashMap<u64, Vec<String>> = HashMap::new();
@@ -1525,17 +5679,22 @@ impl FileSystemService {
             .collect();
 
         // Step 2: Group by quick hash (first 4KB)
+        self.take_fast_path(FAST_PATH_PARALLEL);
         let mut quick_hash_map: HashMap<Vec<u8>, Vec<String>> = HashMap::new();
         for paths in size_groups.into_iter() {
             let quick_hashes: Vec<(String, Vec<u8>)> = paths
                 .into_par_iter()
                 .filter_map(|path| {
                     let rt = rt.clone(); // Clone the runtime handle for this task
+                    let _permit = self.descriptor_limit.acquire();
                     rt.block_on(async {
                         let file = File::open(&path).await.ok()?;
                         let mut reader = tokio::io::BufReader::new(file);
                         let mut buffer = vec![0u8; 4096]; // Read first 4KB
                         let bytes_read = reader.read(&mut buffer).await.ok()?;
+                        if let Some(stats) = &stats {
+                            stats.add_bytes_read(bytes_read as u64);
+                        }
                         let mut hasher = Sha256::new();
                         hasher.update(&buffer[..bytes_read]);
                         Some((path, hasher.finalize().to_vec()))
@@ -1562,16 +5721,20 @@ impl FileSystemService {
                 .into_par_iter()
                 .filter_map(|path| {
                     let rt = rt.clone(); // Clone the runtime handle for this task
+                    let _permit = self.descriptor_limit.acquire();
                     rt.block_on(async {
                         let file = File::open(&path).await.ok()?;
-                        let mut reader = tokio::io::BufReader::new(file);
+                        let mut reader = self.stream_reader(file);
                         let mut hasher = Sha256::new();
-                        let mut buffer = vec![0u8; 8192]; // 8KB chunks
+                        let mut buffer = vec![0u8; self.performance_config.stream_buffer_size];
                         loop {
                             let bytes_read = reader.read(&mut buffer).await.ok()?;
                             if bytes_read == 0 {
                                 break;
                             }
+                            if let Some(stats) = &stats {
+                                stats.add_bytes_read(bytes_read as u64);
+                            }
                             hasher.update(&buffer[..bytes_read]);
                         }
                         Some((path, hasher.finalize().to_vec()))
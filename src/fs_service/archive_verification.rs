@@ -0,0 +1,58 @@
+use std::fmt::Write;
+
+use super::utils::ByteFormat;
+
+/// An archive entry that failed verification.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct EntryFailure {
+    /// Position of the entry in the central directory, from 0.
+    pub index: usize,
+    pub name: String,
+    pub reason: String,
+}
+
+/// The outcome of verifying an archive without extracting it.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ArchiveVerification {
+    /// Whether the entries were decompressed and their CRC checked, rather than only their
+    /// headers read.
+    pub deep: bool,
+    /// Number of entries in the central directory.
+    pub entries: u64,
+    /// Combined uncompressed size of the entries, as recorded in the central directory.
+    pub uncompressed_bytes: u64,
+    pub failures: Vec<EntryFailure>,
+}
+
+impl ArchiveVerification {
+    pub fn is_valid(&self) -> bool {
+        self.failures.is_empty()
+    }
+
+    pub fn render_text(&self, byte_format: &ByteFormat) -> String {
+        let mut output = format!(
+            "{}: {} {}, {} uncompressed ({})\n",
+            if self.is_valid() { "OK" } else { "FAILED" },
+            self.entries,
+            if self.entries == 1 {
+                "entry"
+            } else {
+                "entries"
+            },
+            byte_format.format(self.uncompressed_bytes),
+            if self.deep {
+                "contents decompressed and CRC checked"
+            } else {
+                "headers only"
+            }
+        );
+        for failure in &self.failures {
+            let _ = writeln!(
+                output,
+                "  entry {} '{}': {}",
+                failure.index, failure.name, failure.reason
+            );
+        }
+        output
+    }
+}
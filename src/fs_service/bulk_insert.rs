@@ -0,0 +1,295 @@
+use std::{
+    fmt::Write,
+    fs,
+    path::{Path, PathBuf},
+};
+
+use glob_match::glob_match;
+use regex::RegexSet;
+
+use super::utils::{ByteFormat, name_glob, normalize_line_endings};
+
+/// Where a bulk insertion puts its content.
+#[derive(Debug, Clone)]
+pub enum InsertAnchor {
+    /// Before the first line.
+    Start,
+    /// After the last line.
+    End,
+    /// After the given 1-based line, `0` being the start of the file.
+    AfterLine(usize),
+    /// After the first line matching the pattern.
+    AfterMatch(RegexSet),
+}
+
+impl InsertAnchor {
+    /// Parses `start`, `end` or a line number. Any other anchor is a pattern, compiled with
+    /// `compile`.
+    pub fn parse<E>(
+        anchor: &str,
+        compile: impl FnOnce(&str) -> Result<RegexSet, E>,
+    ) -> Result<Self, E> {
+        Ok(match anchor.trim() {
+            "start" => Self::Start,
+            "end" => Self::End,
+            line if !line.is_empty() && line.bytes().all(|b| b.is_ascii_digit()) => {
+                Self::AfterLine(line.parse().unwrap_or(usize::MAX))
+            }
+            _ => Self::AfterMatch(compile(anchor)?),
+        })
+    }
+}
+
+/// What a bulk insertion did, or would do, to a file.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum InsertStatus {
+    Inserted,
+    /// The content would be inserted, in a dry run.
+    WouldInsert,
+    /// The file already contains the guard pattern.
+    Guarded,
+    /// The anchor was not found: no line matches the pattern, or the file is too short.
+    NoAnchor,
+    Failed(String),
+}
+
+impl InsertStatus {
+    fn label(&self) -> &'static str {
+        match self {
+            Self::Inserted => "inserted",
+            Self::WouldInsert => "would insert",
+            Self::Guarded => "skipped (guard)",
+            Self::NoAnchor => "skipped (no anchor)",
+            Self::Failed(_) => "failed",
+        }
+    }
+}
+
+/// A file matched by a bulk insertion.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FileInsertion {
+    pub path: PathBuf,
+    pub status: InsertStatus,
+    /// Lines added, or that would be added in a dry run.
+    pub lines_added: usize,
+}
+
+/// The outcome of a bulk insertion, one entry per matched file, sorted by path.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct BulkInsertReport {
+    pub root: PathBuf,
+    pub dry_run: bool,
+    pub files: Vec<FileInsertion>,
+    /// Bytes written across the changed files.
+    pub bytes_written: u64,
+}
+
+impl BulkInsertReport {
+    /// Files with the status of `status`, whatever the reason of failures.
+    pub fn count(&self, status: &InsertStatus) -> usize {
+        self.files
+            .iter()
+            .filter(|file| std::mem::discriminant(&file.status) == std::mem::discriminant(status))
+            .count()
+    }
+
+    /// A status table of the matched files, followed by a diffstat of the insertion.
+    pub fn render_text(&self, byte_format: &ByteFormat) -> String {
+        if self.files.is_empty() {
+            return format!("No files matched in '{}'.\n", self.root.display());
+        }
+        let mut output = String::new();
+        let width = self
+            .files
+            .iter()
+            .map(|file| file.status.label().len())
+            .max()
+            .unwrap_or_default();
+        for file in &self.files {
+            let lines = if file.lines_added > 0 {
+                format!("+{}", file.lines_added)
+            } else {
+                "-".to_string()
+            };
+            let path = file.path.strip_prefix(&self.root).unwrap_or(&file.path);
+            let _ = write!(
+                output,
+                "{:<width$}  {lines:>6}  {}",
+                file.status.label(),
+                path.display()
+            );
+            if let InsertStatus::Failed(reason) = &file.status {
+                let _ = write!(output, ": {reason}");
+            }
+            output.push('\n');
+        }
+
+        let changed = if self.dry_run {
+            InsertStatus::WouldInsert
+        } else {
+            InsertStatus::Inserted
+        };
+        let files = self.count(&changed);
+        let lines: usize = self
+            .files
+            .iter()
+            .filter(|file| file.status == changed)
+            .map(|file| file.lines_added)
+            .sum();
+        let _ = write!(
+            output,
+            "\n{} {} changed, {} {}(+)",
+            files,
+            if files == 1 { "file" } else { "files" },
+            lines,
+            if lines == 1 {
+                "insertion"
+            } else {
+                "insertions"
+            }
+        );
+        if !self.dry_run {
+            let _ = write!(
+                output,
+                ", {} written",
+                byte_format.format(self.bytes_written)
+            );
+        }
+        let _ = writeln!(
+            output,
+            "; {} skipped by the guard, {} without the anchor, {} failed.",
+            self.count(&InsertStatus::Guarded),
+            self.count(&InsertStatus::NoAnchor),
+            self.count(&InsertStatus::Failed(String::new()))
+        );
+        if self.dry_run {
+            output.push_str("Dry run: no file was changed.\n");
+        }
+        output
+    }
+}
+
+/// The files a glob selects below a directory, as listed by
+/// [`FileSystemService::preview_glob`](crate::fs_service::FileSystemService::preview_glob).
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct GlobPreview {
+    pub root: PathBuf,
+    pub pattern: String,
+    /// The selected files, sorted by path.
+    pub files: Vec<PathBuf>,
+    /// Most files listed by [`render_text`](Self::render_text).
+    pub limit: usize,
+}
+
+impl GlobPreview {
+    pub fn render_text(&self) -> String {
+        let scope = if self.pattern.contains('/') {
+            "paths relative to"
+        } else {
+            "file names below"
+        };
+        if self.files.is_empty() {
+            return format!(
+                "'{}' matches no files, matched against {scope} '{}'.\n",
+                self.pattern,
+                self.root.display()
+            );
+        }
+        let mut output = format!(
+            "'{}' matches {} {}, matched against {scope} '{}':\n",
+            self.pattern,
+            self.files.len(),
+            if self.files.len() == 1 {
+                "file"
+            } else {
+                "files"
+            },
+            self.root.display()
+        );
+        for path in self.files.iter().take(self.limit) {
+            let relative = path.strip_prefix(&self.root).unwrap_or(path);
+            let _ = writeln!(output, "  {}", relative.display());
+        }
+        if self.files.len() > self.limit {
+            let _ = writeln!(output, "  ... and {} more", self.files.len() - self.limit);
+        }
+        output
+    }
+}
+
+/// Whether the file at `relative`, relative to the search root, is selected by `pattern`. A
+/// pattern holding a `/` is matched against the relative path, e.g. `src/**/*.rs`; others are
+/// matched against file names, as `search_files` matches them.
+pub fn matches_file_glob(pattern: &str, relative: &Path) -> bool {
+    if pattern.contains('/') {
+        let relative = relative.to_string_lossy().replace('\\', "/");
+        return glob_match(pattern.strip_prefix("./").unwrap_or(pattern), &relative);
+    }
+    let (glob, _) = name_glob(pattern);
+    relative
+        .file_name()
+        .is_some_and(|name| glob_match(&glob, &name.to_string_lossy().to_lowercase()))
+}
+
+/// Inserts `text` into `content` at `anchor`, returning the new content and the number of lines
+/// inserted, or `None` when the anchor is not found. The inserted lines use the line ending of
+/// `content`, and end with one.
+pub fn insert_at_anchor(
+    content: &str,
+    anchor: &InsertAnchor,
+    text: &str,
+) -> Option<(String, usize)> {
+    let line_ending = if content.contains("\r\n") {
+        "\r\n"
+    } else {
+        "\n"
+    };
+    let mut insertion = normalize_line_endings(text);
+    if !insertion.ends_with('\n') {
+        insertion.push('\n');
+    }
+    let inserted_lines = insertion.matches('\n').count();
+    let insertion = insertion.replace('\n', line_ending);
+
+    let lines: Vec<&str> = content.split_inclusive('\n').collect();
+    let position = match anchor {
+        InsertAnchor::Start => 0,
+        InsertAnchor::End => lines.len(),
+        InsertAnchor::AfterLine(line) if *line <= lines.len() => *line,
+        InsertAnchor::AfterLine(_) => return None,
+        InsertAnchor::AfterMatch(pattern) => {
+            lines
+                .iter()
+                .position(|line| pattern.is_match(line.trim_end_matches(['\r', '\n'])))?
+                + 1
+        }
+    };
+
+    let mut output = String::with_capacity(content.len() + insertion.len() + 2);
+    for line in &lines[..position] {
+        output.push_str(line);
+    }
+    if !output.is_empty() && !output.ends_with('\n') {
+        output.push_str(line_ending);
+    }
+    output.push_str(&insertion);
+    for line in &lines[position..] {
+        output.push_str(line);
+    }
+    Some((output, inserted_lines))
+}
+
+/// Replaces the file at `path` with `content` by writing a temporary file next to it and renaming
+/// it over the original, so readers see either the old or the new content. The permissions of
+/// the original are kept. Blocking.
+pub fn write_atomically(path: &Path, content: &str) -> std::io::Result<()> {
+    let file_name = path.file_name().unwrap_or_default().to_string_lossy();
+    let temporary = path.with_file_name(format!(".{file_name}.{}.tmp", std::process::id()));
+    let result = fs::write(&temporary, content)
+        .and_then(|()| fs::set_permissions(&temporary, fs::metadata(path)?.permissions()))
+        .and_then(|()| fs::rename(&temporary, path));
+    if result.is_err() {
+        let _ = fs::remove_file(&temporary);
+    }
+    result
+}
@@ -0,0 +1,575 @@
+use std::{path::Path, sync::LazyLock};
+
+use regex::Regex;
+
+use super::comment_stripping::{CommentSyntax, mask_comments_and_strings};
+use super::rust_signatures::{FunctionSignature, function_header, parse_function_signature};
+
+/// Keywords that introduce a named definition when directly followed by the name.
+const DEFINITION_KEYWORDS: &[&str] = &[
+    "fn",
+    "struct",
+    "enum",
+    "trait",
+    "impl",
+    "type",
+    "mod",
+    "union",
+    "const",
+    "static",
+    "macro_rules",
+    "class",
+    "interface",
+    "function",
+    "def",
+    "func",
+];
+
+/// Words that, following `const`, make it a qualifier of a function rather than a constant.
+const FUNCTION_QUALIFIERS: &[&str] = &["fn", "unsafe", "async", "extern"];
+
+/// How the body of a definition is delimited in a source file.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BlockStyle {
+    /// Bodies are enclosed in `{ ... }` (Rust, C-family, JavaScript, Go, ...).
+    Braces,
+    /// Bodies are the indented lines following a header ending with `:` (Python).
+    Indentation,
+}
+
+impl BlockStyle {
+    pub fn from_path(path: &Path) -> Self {
+        match path.extension().and_then(|ext| ext.to_str()) {
+            Some("py") | Some("pyi") => BlockStyle::Indentation,
+            _ => BlockStyle::Braces,
+        }
+    }
+}
+
+/// A definition located in a source file.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CodeDefinition {
+    /// First line of the definition, including leading comments and attributes (1-based).
+    pub start_line: usize,
+    /// Last line of the definition (1-based, inclusive).
+    pub end_line: usize,
+    /// Complete source text of the definition.
+    pub source: String,
+}
+
+/// A definition with its span, as listed by [`list_definitions`].
+#[derive(Debug, Clone, PartialEq, Eq, ::serde::Serialize)]
+pub struct DefinitionSpan {
+    /// Name of the item. For trait implementations this is `Trait for Type`, with paths kept.
+    pub name: String,
+    /// The keyword introducing the item, e.g. `fn`, `struct`, `impl`, `class` or `def`.
+    pub kind: String,
+    /// First line of the definition, including leading comments and attributes (1-based).
+    pub start_line: usize,
+    /// Last line of the definition (1-based, inclusive).
+    pub end_line: usize,
+    /// The visibility modifier (`pub`, `pub(crate)`, `public`, `export`, ...), or `private`
+    /// when there is none. For indentation delimited code, names starting with `_` are
+    /// `private` and others `public`.
+    pub visibility: String,
+    /// The parsed signature of Rust functions (`fn` items).
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub signature: Option<FunctionSignature>,
+}
+
+/// Matches an optional visibility modifier followed by a definition keyword and a name.
+static DEFINITION_HEADER: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(&format!(
+        r"(?:^|[^\w])(?:(pub(?:\s*\([^)]*\))?|public|private|protected|export)\s+)?(?:[a-z]+\s+)*?({})\s+([A-Za-z_]\w*(?:::\w+)*(?:\s+for\s+[A-Za-z_]\w*(?:::\w+)*)?)",
+        DEFINITION_KEYWORDS.join("|")
+    ))
+    .expect("valid definition header pattern")
+});
+
+/// Matches an import declaration: `use`, `extern crate` and body-less `mod` items in Rust,
+/// `import` and `from ... import` statements elsewhere.
+static IMPORT_HEADER: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(
+        r"^(?:(pub(?:\s*\([^)]*\))?|export)\s+)?(?:use\s+|extern\s+crate\s+|import\s+|from\s+(\S+)\s+import\s+|mod\s+(\w+)\s*;)",
+    )
+    .expect("valid import header pattern")
+});
+
+/// Kind reported for import declarations by [`list_definitions_with_imports`].
+pub const IMPORT_KIND: &str = "import";
+
+/// Lists every definition in `content`, public or private, with its line span. Nested items
+/// (methods inside an `impl` or `class`) are listed too, so spans either nest or are disjoint.
+/// Items are returned in file order. With the comment `syntax` of the language, definitions
+/// that only appear inside comments or string literals are not listed.
+pub fn list_definitions(
+    content: &str,
+    style: BlockStyle,
+    syntax: Option<&CommentSyntax>,
+) -> Vec<DefinitionSpan> {
+    collect_definitions(content, style, syntax, false)
+}
+
+/// Same as [`list_definitions`], also listing the `use`, `extern crate` and `mod name;`
+/// declarations (or `import` statements) of the file with the kind [`IMPORT_KIND`]. Their name
+/// is the imported path, e.g. `std::collections::{HashMap, HashSet}`.
+pub fn list_definitions_with_imports(
+    content: &str,
+    style: BlockStyle,
+    syntax: Option<&CommentSyntax>,
+) -> Vec<DefinitionSpan> {
+    collect_definitions(content, style, syntax, true)
+}
+
+/// The lines of `content`, with comments and literals blanked out when `syntax` is known.
+fn code_lines(content: &str, syntax: Option<&CommentSyntax>) -> Vec<String> {
+    match syntax {
+        Some(syntax) => mask_comments_and_strings(content, syntax)
+            .lines()
+            .map(ToString::to_string)
+            .collect(),
+        None => content.lines().map(ToString::to_string).collect(),
+    }
+}
+
+fn collect_definitions(
+    content: &str,
+    style: BlockStyle,
+    syntax: Option<&CommentSyntax>,
+    imports: bool,
+) -> Vec<DefinitionSpan> {
+    let lines: Vec<&str> = content.lines().collect();
+    let code_lines = code_lines(content, syntax);
+    let mut definitions = Vec::new();
+
+    for (index, line) in code_lines.iter().enumerate() {
+        let trimmed = line.trim_start();
+        if trimmed.is_empty()
+            || ["//", "/*", "*", "#"]
+                .iter()
+                .any(|prefix| trimmed.starts_with(prefix))
+        {
+            continue;
+        }
+        if imports && let Some(import) = import_span(&lines, index, style) {
+            definitions.push(import);
+            continue;
+        }
+        let code = strip_generics(strip_line_comment(trimmed, "//"));
+        let Some(mut captures) = DEFINITION_HEADER.captures(&code) else {
+            continue;
+        };
+        let modifier = captures
+            .get(1)
+            .map(|modifier| modifier.as_str().to_string());
+        // In `const fn` or `const unsafe fn`, `const` qualifies the function
+        while FUNCTION_QUALIFIERS.contains(&&captures[3]) {
+            let Some(function) =
+                DEFINITION_HEADER.captures(&code[captures.get(3).unwrap().start()..])
+            else {
+                break;
+            };
+            captures = function;
+        }
+        let name = captures[3].split_whitespace().collect::<Vec<_>>().join(" ");
+        let visibility = match (modifier, style) {
+            (Some(modifier), _) => modifier.split_whitespace().collect(),
+            (None, BlockStyle::Indentation) if name.starts_with('_') => "private".to_string(),
+            (None, BlockStyle::Indentation) => "public".to_string(),
+            (None, BlockStyle::Braces) => "private".to_string(),
+        };
+        let kind = captures[2].to_string();
+        let signature =
+            (kind == "fn").then(|| parse_function_signature(&function_header(&lines, index)));
+        definitions.push(DefinitionSpan {
+            name,
+            kind,
+            start_line: leading_comments_start(&lines, index) + 1,
+            end_line: find_function_end(&lines, index, style) + 1,
+            visibility,
+            signature,
+        });
+    }
+
+    definitions
+}
+
+/// Returns the import declaration starting at line `index`, if any. A declaration spans the
+/// lines until its braces and parentheses are balanced, e.g. `use a::{\n b,\n};`.
+fn import_span(lines: &[&str], index: usize, style: BlockStyle) -> Option<DefinitionSpan> {
+    let trimmed = lines[index].trim_start();
+    let captures = IMPORT_HEADER.captures(trimmed)?;
+    let header = captures.get(0)?;
+    // A variable named like a keyword, as in `use = 1`
+    if trimmed[header.end()..].starts_with('=') {
+        return None;
+    }
+
+    let comment_marker = match style {
+        BlockStyle::Braces => "//",
+        BlockStyle::Indentation => "#",
+    };
+    let mut depth = 0isize;
+    let mut code = vec![];
+    for line in &lines[index..] {
+        let line = strip_line_comment(line, comment_marker).trim();
+        for c in line.chars() {
+            match c {
+                '{' | '(' => depth += 1,
+                '}' | ')' => depth -= 1,
+                _ => {}
+            }
+        }
+        code.push(line);
+        if depth <= 0 {
+            break;
+        }
+    }
+    let end = index + code.len() - 1;
+
+    let imported = code.join(" ")[header.end()..]
+        .split_whitespace()
+        .collect::<Vec<_>>()
+        .join(" ")
+        .trim_end_matches(';')
+        .replace("{ ", "{")
+        .replace("( ", "(")
+        .replace(", }", "}")
+        .replace(",}", "}")
+        .replace(" }", "}");
+    let name = match (captures.get(2), captures.get(3)) {
+        // `from module import a, b` is listed as `module.{a, b}`
+        (Some(module), _) => {
+            let names = imported.trim_matches(['(', ')', ' ']).trim_end_matches(',');
+            if names.contains(',') {
+                format!("{}.{{{names}}}", module.as_str())
+            } else {
+                format!("{}.{names}", module.as_str())
+            }
+        }
+        (_, Some(module)) => module.as_str().to_string(),
+        _ => imported,
+    };
+    let visibility = match (captures.get(1), style) {
+        (Some(modifier), _) => modifier.as_str().split_whitespace().collect(),
+        (None, BlockStyle::Indentation) => "public".to_string(),
+        (None, BlockStyle::Braces) => "private".to_string(),
+    };
+
+    Some(DefinitionSpan {
+        name,
+        kind: IMPORT_KIND.to_string(),
+        start_line: leading_comments_start(lines, index) + 1,
+        end_line: end + 1,
+        visibility,
+        signature: None,
+    })
+}
+
+/// Finds every definition of `name` in `content` and returns its full source text.
+/// Duplicate or overloaded definitions are all returned, in file order. With the comment
+/// `syntax` of the language, definitions inside comments or string literals are ignored.
+pub fn extract_definitions(
+    content: &str,
+    name: &str,
+    style: BlockStyle,
+    syntax: Option<&CommentSyntax>,
+) -> Vec<CodeDefinition> {
+    let lines: Vec<&str> = content.lines().collect();
+    let code_lines = code_lines(content, syntax);
+    let mut definitions = Vec::new();
+    let mut index = 0;
+
+    while index < lines.len() {
+        if !code_lines
+            .get(index)
+            .is_some_and(|line| is_definition_of(line, name))
+        {
+            index += 1;
+            continue;
+        }
+        let start = leading_comments_start(&lines, index);
+        let end = find_function_end(&lines, index, style);
+        definitions.push(CodeDefinition {
+            start_line: start + 1,
+            end_line: end + 1,
+            source: lines[start..=end].join("\n"),
+        });
+        index = end + 1;
+    }
+
+    definitions
+}
+
+/// Returns the innermost block enclosing the 0-based line `line`: the block it opens, if it
+/// opens one, or else the nearest enclosing one, found by scanning backward for the unmatched
+/// opening brace (or the less indented header ending with `:` for indentation delimited code).
+/// The block starts at its header, with its leading comments and attributes, and ends where
+/// [`find_function_end`] ends it. Returns `None` for lines outside any block.
+pub fn find_enclosing_block(
+    content: &str,
+    line: usize,
+    style: BlockStyle,
+    syntax: Option<&CommentSyntax>,
+) -> Option<CodeDefinition> {
+    let lines: Vec<&str> = content.lines().collect();
+    let code_lines = code_lines(content, syntax);
+    let code_lines: Vec<&str> = code_lines.iter().map(String::as_str).collect();
+    if line >= lines.len() {
+        return None;
+    }
+
+    let opening = match style {
+        BlockStyle::Braces => find_opening_brace(&code_lines, line)?,
+        BlockStyle::Indentation => find_indented_header(&code_lines, line)?,
+    };
+    let header = match style {
+        BlockStyle::Braces => brace_header_start(&code_lines, opening),
+        BlockStyle::Indentation => opening,
+    };
+    let end = find_function_end(&code_lines, header, style);
+    if end < line {
+        return None;
+    }
+    let start = leading_comments_start(&lines, header);
+    Some(CodeDefinition {
+        start_line: start + 1,
+        end_line: end + 1,
+        source: lines[start..=end].join("\n"),
+    })
+}
+
+/// Scans backward from the end of line `line` for the first `{` that is not matched by a `}`
+/// in between, and returns the index of its line.
+fn find_opening_brace(lines: &[&str], line: usize) -> Option<usize> {
+    let mut depth = 0usize;
+    for index in (0..=line).rev() {
+        for c in lines[index].chars().rev() {
+            match c {
+                '}' => depth += 1,
+                '{' if depth == 0 => return Some(index),
+                '{' => depth -= 1,
+                _ => {}
+            }
+        }
+    }
+    None
+}
+
+/// Walks back from the line of an opening brace to the first line of its header, e.g. the
+/// `fn` line of a signature spanning several lines: up to the line after a blank line or after
+/// a line ending a statement or a block.
+fn brace_header_start(lines: &[&str], opening: usize) -> usize {
+    let mut start = opening;
+    while start > 0 {
+        let previous = lines[start - 1].trim();
+        if previous.is_empty() || previous.ends_with([';', '{', '}']) {
+            break;
+        }
+        start -= 1;
+    }
+    start
+}
+
+/// Returns the line of the header of the indented block holding `line`: `line` itself when it
+/// ends with `:`, or else the nearest less indented line above it ending with `:`.
+fn find_indented_header(lines: &[&str], line: usize) -> Option<usize> {
+    let is_header = |index: usize| {
+        strip_line_comment(lines[index], "#")
+            .trim_end()
+            .ends_with(':')
+    };
+    if is_header(line) {
+        return Some(line);
+    }
+    let mut indent = indentation(lines[line]);
+    if lines[line].trim().is_empty() {
+        // Blank lines belong to the block of the next non-blank line
+        indent = lines[line..]
+            .iter()
+            .find(|line| !line.trim().is_empty())
+            .map_or(0, |line| indentation(line));
+    }
+    for index in (0..line).rev() {
+        if lines[index].trim().is_empty() || indentation(lines[index]) >= indent {
+            continue;
+        }
+        if is_header(index) {
+            return Some(index);
+        }
+        indent = indentation(lines[index]);
+    }
+    None
+}
+
+/// Returns the index of the last line of the definition whose header is at `start`.
+///
+/// For brace delimited code this is the line holding the brace that closes the first opened
+/// block, or the line of a terminating `;` for definitions without a body (e.g. `struct Unit;`).
+/// For indentation delimited code it is the last line indented deeper than the header.
+pub fn find_function_end(lines: &[&str], start: usize, style: BlockStyle) -> usize {
+    match style {
+        BlockStyle::Braces => find_brace_block_end(lines, start),
+        BlockStyle::Indentation => find_indented_block_end(lines, start),
+    }
+}
+
+fn find_brace_block_end(lines: &[&str], start: usize) -> usize {
+    let mut depth = 0usize;
+    let mut opened = false;
+    let mut in_block_comment = false;
+    let mut in_string: Option<char> = None;
+
+    for (index, line) in lines.iter().enumerate().skip(start) {
+        let mut chars = line.chars().peekable();
+        while let Some(c) = chars.next() {
+            if in_block_comment {
+                if c == '*' && chars.peek() == Some(&'/') {
+                    chars.next();
+                    in_block_comment = false;
+                }
+                continue;
+            }
+            if let Some(quote) = in_string {
+                if c == '\\' {
+                    chars.next();
+                } else if c == quote {
+                    in_string = None;
+                }
+                continue;
+            }
+            match c {
+                '/' if chars.peek() == Some(&'/') => break,
+                '/' if chars.peek() == Some(&'*') => {
+                    chars.next();
+                    in_block_comment = true;
+                }
+                '"' | '`' => in_string = Some(c),
+                '\'' => {
+                    // Skip character literals such as '{' or '\'', but leave lifetimes alone
+                    let mut lookahead = chars.clone();
+                    match (lookahead.next(), lookahead.next()) {
+                        (Some('\\'), _) => {
+                            chars.next();
+                            chars.next();
+                            for c in chars.by_ref() {
+                                if c == '\'' {
+                                    break;
+                                }
+                            }
+                        }
+                        (Some(_), Some('\'')) => {
+                            chars.next();
+                            chars.next();
+                        }
+                        _ => {}
+                    }
+                }
+                '{' => {
+                    depth += 1;
+                    opened = true;
+                }
+                '}' => {
+                    depth = depth.saturating_sub(1);
+                    if opened && depth == 0 {
+                        return index;
+                    }
+                }
+                ';' if !opened && depth == 0 => return index,
+                _ => {}
+            }
+        }
+    }
+
+    lines.len().saturating_sub(1)
+}
+
+fn find_indented_block_end(lines: &[&str], start: usize) -> usize {
+    let base_indent = indentation(lines[start]);
+
+    // The header may span several lines; the body starts after the line ending with ':'
+    let header_end = (start..lines.len())
+        .find(|&index| {
+            strip_line_comment(lines[index], "#")
+                .trim_end()
+                .ends_with(':')
+        })
+        .unwrap_or(start);
+
+    let mut end = header_end;
+    for (index, line) in lines.iter().enumerate().skip(header_end + 1) {
+        if line.trim().is_empty() {
+            continue;
+        }
+        if indentation(line) <= base_indent {
+            break;
+        }
+        end = index;
+    }
+    end
+}
+
+/// Walks back from `index` over the comments, doc comments, attributes and decorators
+/// directly attached to the definition.
+fn leading_comments_start(lines: &[&str], index: usize) -> usize {
+    let mut start = index;
+    while start > 0 {
+        let previous = lines[start - 1].trim_start();
+        let attached = ["//", "/*", "*", "#", "@"]
+            .iter()
+            .any(|prefix| previous.starts_with(prefix));
+        if !attached {
+            break;
+        }
+        start -= 1;
+    }
+    start
+}
+
+fn is_definition_of(line: &str, name: &str) -> bool {
+    let trimmed = line.trim_start();
+    if ["//", "/*", "*", "#"]
+        .iter()
+        .any(|prefix| trimmed.starts_with(prefix))
+    {
+        return false;
+    }
+
+    let code = strip_generics(strip_line_comment(trimmed, "//"));
+    let tokens: Vec<&str> = code
+        .split(|c: char| !(c.is_alphanumeric() || c == '_'))
+        .filter(|token| !token.is_empty())
+        .collect();
+    let is_impl = tokens.contains(&"impl");
+
+    tokens.windows(2).any(|pair| {
+        pair[1] == name && (DEFINITION_KEYWORDS.contains(&pair[0]) || (is_impl && pair[0] == "for"))
+    })
+}
+
+/// Removes generic parameter lists so that `impl<T> Display for Wrapper<T>` tokenizes
+/// as `impl Display for Wrapper`.
+fn strip_generics(line: &str) -> String {
+    let mut depth = 0usize;
+    let mut result = String::with_capacity(line.len());
+    let mut previous = ' ';
+    for c in line.chars() {
+        match c {
+            '<' => depth += 1,
+            // `->` and `=>` are arrows rather than the end of a generic list
+            '>' if previous != '-' && previous != '=' => depth = depth.saturating_sub(1),
+            _ if depth == 0 => result.push(c),
+            _ => {}
+        }
+        previous = c;
+    }
+    result
+}
+
+fn strip_line_comment<'a>(line: &'a str, marker: &str) -> &'a str {
+    line.find(marker).map_or(line, |position| &line[..position])
+}
+
+fn indentation(line: &str) -> usize {
+    line.len() - line.trim_start().len()
+}
@@ -0,0 +1,373 @@
+use std::{collections::HashSet, path::Path};
+
+/// How comments and string literals are written in a language.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CommentSyntax {
+    /// Tokens starting a comment that runs to the end of the line.
+    pub line: &'static [&'static str],
+    /// Opening and closing tokens of block comments.
+    pub block: Option<(&'static str, &'static str)>,
+    /// Whether block comments nest, as in Rust and Haskell.
+    pub nested_blocks: bool,
+    /// Whether a line comment token only counts at the start of a line or after whitespace,
+    /// as `#` in shell scripts and YAML.
+    pub line_needs_boundary: bool,
+    /// String delimiters, longest first. A string closes with the token that opened it.
+    pub quotes: &'static [&'static str],
+    /// Whether `'` introduces a character literal rather than a string (and may otherwise be
+    /// a Rust lifetime).
+    pub char_literals: bool,
+    /// Whether Rust raw strings (`r"..."`, `r#"..."#`) are recognized.
+    pub raw_strings: bool,
+}
+
+const C_STYLE: CommentSyntax = CommentSyntax {
+    line: &["//"],
+    block: Some(("/*", "*/")),
+    nested_blocks: false,
+    line_needs_boundary: false,
+    quotes: &["\""],
+    char_literals: true,
+    raw_strings: false,
+};
+
+const HASH_STYLE: CommentSyntax = CommentSyntax {
+    line: &["#"],
+    block: None,
+    nested_blocks: false,
+    line_needs_boundary: false,
+    quotes: &["\"", "'"],
+    char_literals: false,
+    raw_strings: false,
+};
+
+impl CommentSyntax {
+    /// Detects the comment syntax from the file extension. Returns `None` for unrecognized
+    /// languages.
+    pub fn from_path(path: &Path) -> Option<Self> {
+        let extension = path.extension()?.to_str()?.to_lowercase();
+        let syntax = match extension.as_str() {
+            "rs" => CommentSyntax {
+                nested_blocks: true,
+                raw_strings: true,
+                ..C_STYLE
+            },
+            "c" | "h" | "cc" | "cpp" | "hpp" | "cs" | "java" | "proto" | "jsonc" => C_STYLE,
+            "go" => CommentSyntax {
+                quotes: &["\"", "`"],
+                ..C_STYLE
+            },
+            "kt" | "kts" | "scala" | "swift" => CommentSyntax {
+                quotes: &["\"\"\"", "\""],
+                ..C_STYLE
+            },
+            "js" | "jsx" | "mjs" | "cjs" | "ts" | "tsx" | "dart" => CommentSyntax {
+                quotes: &["\"", "'", "`"],
+                char_literals: false,
+                ..C_STYLE
+            },
+            "php" => CommentSyntax {
+                line: &["//", "#"],
+                quotes: &["\"", "'"],
+                char_literals: false,
+                ..C_STYLE
+            },
+            "css" => CommentSyntax {
+                line: &[],
+                quotes: &["\"", "'"],
+                char_literals: false,
+                ..C_STYLE
+            },
+            "scss" | "less" => CommentSyntax {
+                quotes: &["\"", "'"],
+                char_literals: false,
+                ..C_STYLE
+            },
+            "py" | "pyi" => CommentSyntax {
+                quotes: &["\"\"\"", "'''", "\"", "'"],
+                ..HASH_STYLE
+            },
+            "rb" | "pl" | "r" | "toml" | "conf" | "cfg" | "mk" | "dockerfile" | "nix" | "ps1" => {
+                HASH_STYLE
+            }
+            "sh" | "bash" | "zsh" | "fish" | "yaml" | "yml" => CommentSyntax {
+                line_needs_boundary: true,
+                ..HASH_STYLE
+            },
+            "sql" => CommentSyntax {
+                line: &["--"],
+                quotes: &["'", "\""],
+                char_literals: false,
+                ..C_STYLE
+            },
+            "lua" => CommentSyntax {
+                line: &["--"],
+                block: Some(("--[[", "]]")),
+                ..HASH_STYLE
+            },
+            "hs" | "elm" => CommentSyntax {
+                line: &["--"],
+                block: Some(("{-", "-}")),
+                nested_blocks: true,
+                quotes: &["\""],
+                ..HASH_STYLE
+            },
+            "html" | "htm" | "xml" | "xhtml" | "svg" | "vue" | "md" | "markdown" => CommentSyntax {
+                line: &[],
+                block: Some(("<!--", "-->")),
+                quotes: &[],
+                ..HASH_STYLE
+            },
+            _ => return None,
+        };
+        Some(syntax)
+    }
+}
+
+/// Removes the line and block comments from `content`, leaving string and character literals
+/// untouched. Lines left blank by removing a comment are dropped and trailing whitespace before
+/// a removed comment is trimmed; other lines are kept as is. A leading `#!` line is kept.
+pub fn strip_comments(content: &str, syntax: &CommentSyntax) -> String {
+    let mut output = String::with_capacity(content.len());
+    // Lines (0-based, in `output`) that lost a comment
+    let mut stripped_lines = vec![];
+    let mut line = 0;
+    let mut index = 0;
+
+    if content.starts_with("#!") && syntax.line.contains(&"#") {
+        index = content.find('\n').unwrap_or(content.len());
+        output.push_str(&content[..index]);
+    }
+
+    while index < content.len() {
+        let rest = &content[index..];
+
+        if let Some(end) = literal_end(content, index, syntax) {
+            let literal = &content[index..end];
+            line += literal.matches('\n').count();
+            output.push_str(literal);
+            index = end;
+            continue;
+        }
+
+        if let Some((open, close)) = syntax.block
+            && rest.starts_with(open)
+        {
+            let end = block_comment_end(content, index, open, close, syntax.nested_blocks);
+            // Keep the line breaks, so that code around the comment stays on its lines
+            let comment = &content[index..end];
+            let newlines = comment.matches('\n').count();
+            stripped_lines.extend(line..=line + newlines);
+            line += newlines;
+            for (position, _) in comment.match_indices('\n') {
+                output.push_str(if comment[..position].ends_with('\r') {
+                    "\r\n"
+                } else {
+                    "\n"
+                });
+            }
+            index = end;
+            continue;
+        }
+
+        if syntax.line.iter().any(|token| rest.starts_with(token))
+            && (!syntax.line_needs_boundary || at_boundary(content, index))
+        {
+            stripped_lines.push(line);
+            let comment = &rest[..rest.find('\n').unwrap_or(rest.len())];
+            index += comment.strip_suffix('\r').unwrap_or(comment).len();
+            continue;
+        }
+
+        let c = rest.chars().next().unwrap_or_default();
+        if c == '\n' {
+            line += 1;
+        }
+        output.push(c);
+        index += c.len_utf8();
+    }
+
+    if stripped_lines.is_empty() {
+        return output;
+    }
+    let mut stripped_lines = stripped_lines.into_iter().peekable();
+    let mut result = String::with_capacity(output.len());
+    for (number, text) in output.split_inclusive('\n').enumerate() {
+        while stripped_lines.next_if(|line| *line < number).is_some() {}
+        if stripped_lines.peek() != Some(&number) {
+            result.push_str(text);
+            continue;
+        }
+        let terminator_start = text.trim_end_matches(['\r', '\n']).len();
+        let terminator = &text[terminator_start..];
+        let trimmed = text[..terminator_start].trim_end();
+        if trimmed.is_empty() {
+            continue;
+        }
+        result.push_str(trimmed);
+        result.push_str(terminator);
+    }
+    result
+}
+
+/// Replaces the comments and the string and character literals of `content` with spaces,
+/// keeping line breaks, so that the remaining text on each line is code at its original line.
+pub fn mask_comments_and_strings(content: &str, syntax: &CommentSyntax) -> String {
+    let mut output = String::with_capacity(content.len());
+    let mut index = 0;
+    while index < content.len() {
+        let rest = &content[index..];
+        let end =
+            literal_end(content, index, syntax).or_else(|| comment_end(content, index, syntax));
+        match end {
+            Some(end) => {
+                output.extend(content[index..end].chars().map(|c| match c {
+                    '\n' | '\r' => c,
+                    _ => ' ',
+                }));
+                index = end;
+            }
+            None => {
+                let c = rest.chars().next().unwrap_or_default();
+                output.push(c);
+                index += c.len_utf8();
+            }
+        }
+    }
+    output
+}
+
+/// Returns the 0-based numbers of the lines of `content` that start inside a string literal:
+/// the continuation lines of multi-line strings, whose leading whitespace belongs to the string.
+pub fn lines_inside_strings(content: &str, syntax: &CommentSyntax) -> HashSet<usize> {
+    let mut lines = HashSet::new();
+    let mut line = 0;
+    let mut index = 0;
+    while index < content.len() {
+        if let Some(end) = literal_end(content, index, syntax) {
+            for _ in content[index..end].matches('\n') {
+                line += 1;
+                lines.insert(line);
+            }
+            index = end;
+        } else if let Some(end) = comment_end(content, index, syntax) {
+            line += content[index..end].matches('\n').count();
+            index = end;
+        } else {
+            let c = content[index..].chars().next().unwrap_or_default();
+            if c == '\n' {
+                line += 1;
+            }
+            index += c.len_utf8();
+        }
+    }
+    lines
+}
+
+/// Strips comments from `content` if the language of `path` is recognized.
+pub fn strip_comments_for_path(path: &Path, content: &str) -> Option<String> {
+    CommentSyntax::from_path(path).map(|syntax| strip_comments(content, &syntax))
+}
+
+/// Whether the character before `index` is whitespace, or `index` starts the content.
+fn at_boundary(content: &str, index: usize) -> bool {
+    content[..index]
+        .chars()
+        .next_back()
+        .is_none_or(char::is_whitespace)
+}
+
+/// If a comment starts at `index`, returns the index just past its end, which is before the line
+/// break for line comments.
+fn comment_end(content: &str, index: usize, syntax: &CommentSyntax) -> Option<usize> {
+    let rest = &content[index..];
+    if let Some((open, close)) = syntax.block
+        && rest.starts_with(open)
+    {
+        return Some(block_comment_end(
+            content,
+            index,
+            open,
+            close,
+            syntax.nested_blocks,
+        ));
+    }
+    (syntax.line.iter().any(|token| rest.starts_with(token))
+        && (!syntax.line_needs_boundary || at_boundary(content, index)))
+    .then(|| index + rest.find('\n').unwrap_or(rest.len()))
+}
+
+/// If a string or character literal starts at `index`, returns the index just past its end.
+fn literal_end(content: &str, index: usize, syntax: &CommentSyntax) -> Option<usize> {
+    let rest = &content[index..];
+
+    if syntax.raw_strings && (rest.starts_with("r\"") || rest.starts_with("r#")) {
+        let previous = content[..index].chars().next_back();
+        if !previous.is_some_and(|c| c.is_alphanumeric() || c == '_') {
+            let hashes = rest[1..].len() - rest[1..].trim_start_matches('#').len();
+            if rest[1 + hashes..].starts_with('"') {
+                let closing = format!("\"{}", "#".repeat(hashes));
+                let body = index + 2 + hashes;
+                return Some(
+                    content[body..]
+                        .find(&closing)
+                        .map_or(content.len(), |end| body + end + closing.len()),
+                );
+            }
+        }
+    }
+
+    if let Some(quote) = syntax.quotes.iter().find(|quote| rest.starts_with(**quote)) {
+        let mut position = index + quote.len();
+        while position < content.len() {
+            let rest = &content[position..];
+            if rest.starts_with(quote) {
+                return Some(position + quote.len());
+            }
+            let mut chars = rest.chars();
+            let c = chars.next().unwrap_or_default();
+            position += c.len_utf8();
+            if c == '\\' {
+                position += chars.next().map_or(0, char::len_utf8);
+            }
+        }
+        return Some(content.len());
+    }
+
+    if syntax.char_literals && rest.starts_with('\'') {
+        let mut chars = rest.char_indices().skip(1);
+        return match (chars.next(), chars.next()) {
+            // An escaped character, such as '\'' or '\u{1F600}'
+            (Some((_, '\\')), Some((offset, escaped))) => rest[offset + escaped.len_utf8()..]
+                .find('\'')
+                .map(|end| index + offset + escaped.len_utf8() + end + 1),
+            (Some(_), Some((offset, '\''))) => Some(index + offset + 1),
+            // A lifetime or label
+            _ => None,
+        };
+    }
+
+    None
+}
+
+/// Returns the index just past the block comment starting at `index`.
+fn block_comment_end(content: &str, index: usize, open: &str, close: &str, nested: bool) -> usize {
+    let mut depth = 0;
+    let mut position = index;
+    while position < content.len() {
+        let rest = &content[position..];
+        if rest.starts_with(open) && (nested || depth == 0) {
+            depth += 1;
+            position += open.len();
+        } else if rest.starts_with(close) {
+            depth -= 1;
+            position += close.len();
+            if depth == 0 {
+                return position;
+            }
+        } else {
+            position += rest.chars().next().map_or(1, char::len_utf8);
+        }
+    }
+    content.len()
+}
@@ -0,0 +1,137 @@
+use std::{path::Path, time::Duration};
+
+use crate::error::{ServiceError, ServiceResult};
+
+/// Default buffer size (in bytes) used by streaming reads.
+pub const DEFAULT_STREAM_BUFFER_SIZE: usize = 8 * 1024;
+/// Smallest accepted buffer size (in bytes) for streaming reads.
+pub const MIN_STREAM_BUFFER_SIZE: usize = 512;
+/// Default limit on the length (in characters) of search patterns.
+pub const DEFAULT_MAX_PATTERN_LENGTH: usize = 4096;
+/// Default limit on the compiled size (in bytes) of search regexes and their lazy DFA cache.
+pub const DEFAULT_REGEX_SIZE_LIMIT: usize = 10 * 1024 * 1024;
+/// Default time budget of a content search.
+pub const DEFAULT_SEARCH_TIMEOUT: Duration = Duration::from_secs(60);
+/// Default number of times a rename failing with a sharing violation is retried (Windows only).
+pub const DEFAULT_RENAME_RETRIES: u32 = 5;
+/// Default delay before the first rename retry; it doubles after every attempt.
+pub const DEFAULT_RENAME_RETRY_DELAY: Duration = Duration::from_millis(50);
+/// Default limit on the number of entries a delete may remove without being forced.
+pub const DEFAULT_MAX_DELETE_ENTRIES: u64 = 10_000;
+/// Default size (in bytes) from which writes check the free space of the destination first.
+pub const DEFAULT_DISK_SPACE_CHECK_THRESHOLD: u64 = 1024 * 1024;
+/// Default limit on the files opened or queried at once by the parallel parts of recursive walks.
+pub const DEFAULT_MAX_OPEN_FILES: usize = 256;
+/// Default limit on the pixel count of images decoded for scaling (8192 x 8192).
+pub const DEFAULT_MAX_IMAGE_PIXELS: u64 = 8192 * 8192;
+
+/// Tunable settings that affect the performance characteristics of `FileSystemService`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PerformanceConfig {
+    /// Buffer size (in bytes) used by streaming read paths such as head, tail and base64 reads.
+    /// Larger buffers reduce the number of reads on high-latency storage.
+    pub stream_buffer_size: usize,
+    /// Open files for writing with `O_NOFOLLOW`, so writes to a path whose final component is a
+    /// symlink fail at the OS level. No-op on non-Unix platforms.
+    pub no_follow_writes: bool,
+    /// Open files for reading with `O_NOATIME`, so reads do not update the access time. Linux only;
+    /// falls back to a regular open when the flag is not permitted (the file is owned by another
+    /// user). No-op on other platforms.
+    pub no_atime_reads: bool,
+    /// File extensions (without the leading dot, case-insensitive) that write operations may
+    /// create or modify. Empty allows every extension; otherwise files without an extension are
+    /// rejected too.
+    pub writable_extensions: Vec<String>,
+    /// File extensions that write operations may never create or modify. Takes precedence over
+    /// `writable_extensions`.
+    pub blocked_extensions: Vec<String>,
+    /// Images with more pixels than this are rejected before being decoded for scaling, which
+    /// protects against decompression bombs.
+    pub max_image_pixels: u64,
+    /// How many times a rename is retried when it fails because another process holds the file
+    /// open (a sharing violation). Windows only; other errors fail immediately.
+    pub rename_retries: u32,
+    /// Delay before the first rename retry, doubled after every further attempt.
+    pub rename_retry_delay: Duration,
+    /// Search patterns longer than this many characters are rejected.
+    pub max_pattern_length: usize,
+    /// Limit on the compiled size of a search regex and on its lazy DFA cache, in bytes.
+    /// Patterns exceeding it (e.g. huge alternations or repetitions) are rejected.
+    pub regex_size_limit: usize,
+    /// Time budget of a content search. When it runs out, the search stops and returns the
+    /// matches found so far. `None` disables the limit.
+    pub search_timeout: Option<Duration>,
+    /// Ceiling on the bytes written by all write operations of the session. `0` is unlimited.
+    pub max_session_write_bytes: u64,
+    /// Ceiling on the bytes removed by overwriting or replacing existing files during the
+    /// session. `0` is unlimited.
+    pub max_session_delete_bytes: u64,
+    /// Deleting a directory holding more entries than this is refused unless forced. `0` is
+    /// unlimited.
+    pub max_delete_entries: u64,
+    /// Writes of at least this many bytes are refused when the destination's filesystem has
+    /// less space available. `0` checks every write.
+    pub disk_space_check_threshold: u64,
+    /// Files larger than this are not read, searched or analyzed as text. `0` is unlimited.
+    pub max_text_file_bytes: u64,
+    /// Files opened or queried at once by the parallel parts of recursive walks (sizes,
+    /// duplicate and generated file detection, bulk insertion), which keeps large trees from
+    /// exhausting file descriptors. `0` is unlimited.
+    pub max_open_files: usize,
+}
+
+impl Default for PerformanceConfig {
+    fn default() -> Self {
+        Self {
+            stream_buffer_size: DEFAULT_STREAM_BUFFER_SIZE,
+            no_follow_writes: false,
+            no_atime_reads: false,
+            writable_extensions: vec![],
+            blocked_extensions: vec![],
+            max_image_pixels: DEFAULT_MAX_IMAGE_PIXELS,
+            rename_retries: DEFAULT_RENAME_RETRIES,
+            rename_retry_delay: DEFAULT_RENAME_RETRY_DELAY,
+            max_pattern_length: DEFAULT_MAX_PATTERN_LENGTH,
+            regex_size_limit: DEFAULT_REGEX_SIZE_LIMIT,
+            search_timeout: Some(DEFAULT_SEARCH_TIMEOUT),
+            max_session_write_bytes: 0,
+            max_session_delete_bytes: 0,
+            max_delete_entries: DEFAULT_MAX_DELETE_ENTRIES,
+            disk_space_check_threshold: DEFAULT_DISK_SPACE_CHECK_THRESHOLD,
+            max_text_file_bytes: 0,
+            max_open_files: DEFAULT_MAX_OPEN_FILES,
+        }
+    }
+}
+
+impl PerformanceConfig {
+    /// Checks that all settings are within sane bounds.
+    pub fn validate(&self) -> ServiceResult<()> {
+        if self.stream_buffer_size < MIN_STREAM_BUFFER_SIZE {
+            return Err(ServiceError::FromString(format!(
+                "Invalid stream buffer size {}: must be at least {} bytes.",
+                self.stream_buffer_size, MIN_STREAM_BUFFER_SIZE
+            )));
+        }
+        Ok(())
+    }
+
+    /// Whether write operations may create or modify the file at `path`, according to
+    /// `writable_extensions` and `blocked_extensions`.
+    pub fn is_writable_extension(&self, path: &Path) -> bool {
+        let extension = path
+            .extension()
+            .map(|extension| extension.to_string_lossy().to_lowercase());
+        let matches = |configured: &String| {
+            extension.as_deref().is_some_and(|extension| {
+                configured
+                    .trim_start_matches('.')
+                    .eq_ignore_ascii_case(extension)
+            })
+        };
+        if self.blocked_extensions.iter().any(matches) {
+            return false;
+        }
+        self.writable_extensions.is_empty() || self.writable_extensions.iter().any(matches)
+    }
+}
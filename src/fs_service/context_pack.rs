@@ -0,0 +1,210 @@
+use std::{fmt::Write, path::PathBuf};
+
+/// A line of a context pack manifest, see [`parse_manifest`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ManifestEntry {
+    /// A file, with an optional range of 1-based lines, inclusive. A range without an end runs
+    /// to the end of the file.
+    File {
+        path: String,
+        lines: Option<(usize, Option<usize>)>,
+    },
+    /// A glob matched against the paths relative to the manifest directory.
+    Glob(String),
+    /// A line that could not be parsed, and why.
+    Invalid(String),
+}
+
+/// An entry of a manifest and the line it was read from.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ManifestLine {
+    /// 1-based line number in the manifest.
+    pub number: usize,
+    /// The line as written, trimmed.
+    pub text: String,
+    pub entry: ManifestEntry,
+}
+
+/// Parses a context pack manifest: one entry per line, either `path`, `path:start-end`,
+/// `path:start-` or `path:line`, or a glob such as `src/**/*.rs`. Blank lines and lines starting
+/// with `#` are ignored. Entries are returned in the order listed, which is their priority.
+pub fn parse_manifest(content: &str) -> Vec<ManifestLine> {
+    content
+        .lines()
+        .enumerate()
+        .filter_map(|(index, line)| {
+            let text = line.trim();
+            if text.is_empty() || text.starts_with('#') {
+                return None;
+            }
+            Some(ManifestLine {
+                number: index + 1,
+                text: text.to_string(),
+                entry: parse_entry(text),
+            })
+        })
+        .collect()
+}
+
+fn parse_entry(text: &str) -> ManifestEntry {
+    // A suffix of digits and dashes is a line range; other colons belong to the path, as in `C:\`
+    let (path, range) = match text.rsplit_once(':') {
+        Some((path, range))
+            if !range.is_empty() && range.chars().all(|c| c.is_ascii_digit() || c == '-') =>
+        {
+            (path.trim_end(), Some(range))
+        }
+        _ => (text, None),
+    };
+    if path.is_empty() {
+        return ManifestEntry::Invalid("the entry has no path".to_string());
+    }
+    let is_glob = path.contains(['*', '?', '[', '{']);
+    let lines = match range.map(parse_range).transpose() {
+        Ok(lines) => lines,
+        Err(reason) => return ManifestEntry::Invalid(reason),
+    };
+    match (is_glob, lines) {
+        (true, Some(_)) => {
+            ManifestEntry::Invalid("a line range cannot be given for a glob".to_string())
+        }
+        (true, None) => ManifestEntry::Glob(path.to_string()),
+        (false, lines) => ManifestEntry::File {
+            path: path.to_string(),
+            lines,
+        },
+    }
+}
+
+fn parse_range(range: &str) -> Result<(usize, Option<usize>), String> {
+    let invalid = || format!("invalid line range '{range}', expected 'start-end' from line 1");
+    let (start, end) = match range.split_once('-') {
+        Some((start, "")) => (start.parse().map_err(|_| invalid())?, None),
+        Some((start, end)) => (
+            start.parse().map_err(|_| invalid())?,
+            Some(end.parse().map_err(|_| invalid())?),
+        ),
+        None => {
+            let line = range.parse().map_err(|_| invalid())?;
+            (line, Some(line))
+        }
+    };
+    if start == 0 || end.is_some_and(|end| end < start) {
+        return Err(invalid());
+    }
+    Ok((start, end))
+}
+
+/// Returns the lines `start..=end` (1-based) of `content`, with their line endings.
+pub fn slice_lines(content: &str, start: usize, end: Option<usize>) -> Result<String, String> {
+    let total = content.lines().count();
+    if start > total {
+        return Err(format!(
+            "line {start} is past the end of the file, which has {total} {}",
+            if total == 1 { "line" } else { "lines" }
+        ));
+    }
+    let count = end.map_or(usize::MAX, |end| end - start + 1);
+    Ok(content
+        .split_inclusive('\n')
+        .skip(start - 1)
+        .take(count)
+        .collect())
+}
+
+/// A file of a context pack, or the manifest entry that failed to resolve.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PackSection {
+    /// What the section shows, e.g. `src/lib.rs (lines 10-20)`.
+    pub label: String,
+    /// The content, or why the entry could not be read.
+    pub content: Result<String, String>,
+}
+
+/// The files listed by a manifest, in priority order.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ContextPack {
+    pub manifest: PathBuf,
+    pub sections: Vec<PackSection>,
+    /// Most content bytes returned, `None` when unlimited.
+    pub max_bytes: Option<usize>,
+    /// Secrets redacted, `None` when redaction does not apply.
+    pub redactions: Option<usize>,
+}
+
+impl ContextPack {
+    /// Renders the sections under headers. Content is kept in priority order until
+    /// [`max_bytes`](Self::max_bytes) is reached: the section crossing the budget is cut at a line
+    /// break, and the sections after it are omitted. Errors are reported in place.
+    pub fn render_text(&self) -> String {
+        let mut output = String::new();
+        let mut remaining = self.max_bytes.unwrap_or(usize::MAX);
+        let (mut files, mut bytes, mut truncated, mut omitted, mut errors) = (0, 0, 0, 0, 0);
+        for section in &self.sections {
+            let _ = writeln!(output, "==> {} <==", section.label);
+            let content = match &section.content {
+                Ok(content) => content,
+                Err(reason) => {
+                    errors += 1;
+                    let _ = writeln!(output, "Error: {reason}\n");
+                    continue;
+                }
+            };
+            if remaining == 0 && !content.is_empty() {
+                omitted += 1;
+                output.push_str("[omitted: the byte budget is exhausted]\n\n");
+                continue;
+            }
+            files += 1;
+            let kept = cut_at_line(content, remaining);
+            remaining -= kept.len();
+            bytes += kept.len();
+            output.push_str(kept);
+            if !kept.is_empty() && !kept.ends_with('\n') {
+                output.push('\n');
+            }
+            if kept.len() < content.len() {
+                truncated += 1;
+                remaining = 0;
+                let _ = writeln!(
+                    output,
+                    "[truncated: {} of {} bytes shown, the byte budget is exhausted]",
+                    kept.len(),
+                    content.len()
+                );
+            }
+            output.push('\n');
+        }
+        let _ = write!(
+            output,
+            "Context pack '{}': {files} {} ({bytes} bytes)",
+            self.manifest.display(),
+            if files == 1 { "file" } else { "files" },
+        );
+        if let Some(max_bytes) = self.max_bytes {
+            let _ = write!(output, " of a {max_bytes} byte budget");
+        }
+        let _ = write!(
+            output,
+            "; {truncated} truncated, {omitted} omitted, {errors} {}.",
+            if errors == 1 { "error" } else { "errors" }
+        );
+        output
+    }
+}
+
+/// The longest prefix of `content` within `budget` bytes, ending at a line break when there is
+/// one, or else at a character boundary.
+fn cut_at_line(content: &str, budget: usize) -> &str {
+    if content.len() <= budget {
+        return content;
+    }
+    let mut end = budget;
+    while !content.is_char_boundary(end) {
+        end -= 1;
+    }
+    match content[..end].rfind('\n') {
+        Some(line_end) => &content[..=line_end],
+        None => &content[..end],
+    }
+}
@@ -0,0 +1,276 @@
+use std::{
+    fmt::Write,
+    fs, io,
+    path::{Path, PathBuf},
+};
+
+use walkdir::WalkDir;
+
+use super::utils::{ByteFormat, normalize_path};
+use crate::error::{ServiceError, ServiceResult};
+
+/// Name of the directory, or of the file in worktrees and submodules, that marks a git
+/// repository.
+pub const VCS_MARKER: &str = ".git";
+
+/// Options of [`plan_delete`].
+#[derive(Debug, Clone, Default)]
+pub struct DeleteOptions {
+    /// Whether a directory is deleted with its contents, rather than only when empty.
+    pub recursive: bool,
+    /// Whether directories holding a `.git` folder may be deleted.
+    pub allow_vcs: bool,
+    /// Whether targets holding more than `max_entries` entries may be deleted.
+    pub force: bool,
+    /// Entries a target may hold without `force`. `0` is unlimited.
+    pub max_entries: u64,
+    /// Directories never deleted, such as the allowed directories.
+    pub protected: Vec<PathBuf>,
+}
+
+/// Number and combined size of files and directories.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct EntryCounts {
+    /// Files and symbolic links.
+    pub files: u64,
+    pub directories: u64,
+    /// Combined size of the files.
+    pub bytes: u64,
+}
+
+impl EntryCounts {
+    pub fn entries(&self) -> u64 {
+        self.files + self.directories
+    }
+
+    fn add(&mut self, entry: &PlannedEntry) {
+        if entry.is_dir {
+            self.directories += 1;
+        } else {
+            self.files += 1;
+            self.bytes += entry.bytes;
+        }
+    }
+
+    /// Describes the counts, e.g. `3 files, 1 directory (12 KiB)`.
+    pub fn describe(&self, byte_format: &ByteFormat) -> String {
+        format!(
+            "{} {}, {} {} ({})",
+            byte_format.format_count(self.files),
+            if self.files == 1 { "file" } else { "files" },
+            byte_format.format_count(self.directories),
+            if self.directories == 1 {
+                "directory"
+            } else {
+                "directories"
+            },
+            byte_format.format(self.bytes)
+        )
+    }
+}
+
+/// An entry a delete removes.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PlannedEntry {
+    pub path: PathBuf,
+    pub is_dir: bool,
+    pub bytes: u64,
+}
+
+/// The entries a delete removes, found by a counting pass before anything is removed.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct DeletePlan {
+    pub target: PathBuf,
+    /// Entries in removal order: the contents of a directory before the directory itself,
+    /// the target last.
+    pub entries: Vec<PlannedEntry>,
+    pub counts: EntryCounts,
+}
+
+/// An entry that could not be removed.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DeleteFailure {
+    pub path: PathBuf,
+    pub reason: String,
+}
+
+/// The outcome of a delete.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct DeleteReport {
+    pub target: PathBuf,
+    pub dry_run: bool,
+    /// What the counting pass found.
+    pub planned: EntryCounts,
+    /// What was removed.
+    pub deleted: EntryCounts,
+    /// Entries that could not be removed. Their parent directories are left in place.
+    pub failures: Vec<DeleteFailure>,
+}
+
+impl DeleteReport {
+    pub fn is_complete(&self) -> bool {
+        self.failures.is_empty()
+    }
+
+    pub fn render_text(&self, byte_format: &ByteFormat) -> String {
+        let mut output = format!(
+            "Found {} in '{}'.\n",
+            self.planned.describe(byte_format),
+            self.target.display()
+        );
+        if self.dry_run {
+            output.push_str("Dry run: nothing was deleted.\n");
+            return output;
+        }
+        let _ = writeln!(output, "Deleted {}.", self.deleted.describe(byte_format));
+        if !self.failures.is_empty() {
+            let _ = writeln!(
+                output,
+                "Failed to delete {} {}:",
+                self.failures.len(),
+                if self.failures.len() == 1 {
+                    "entry"
+                } else {
+                    "entries"
+                }
+            );
+            for failure in &self.failures {
+                let _ = writeln!(output, "  {}: {}", failure.path.display(), failure.reason);
+            }
+        }
+        output
+    }
+}
+
+/// The real path of `target` without following a symbolic link it names: its parent is
+/// canonicalized and its name kept. A target ending in `..` is canonicalized as a whole.
+fn resolve_target(target: &Path) -> io::Result<PathBuf> {
+    match (target.parent(), target.file_name()) {
+        (Some(parent), Some(name)) if !parent.as_os_str().is_empty() => {
+            Ok(fs::canonicalize(parent)?.join(name))
+        }
+        _ => fs::canonicalize(target),
+    }
+}
+
+/// Counts the entries a delete of `target` removes, refusing targets that are or hold protected
+/// directories, that hold a git repository without `allow_vcs`, or that hold more than
+/// `max_entries` entries without `force`. Symbolic links are removed, never followed. The target
+/// is resolved first, so that `..` components cannot reach a protected directory. Blocking.
+pub fn plan_delete(target: &Path, options: &DeleteOptions) -> ServiceResult<DeletePlan> {
+    let target = &resolve_target(target)?;
+    let refuse = |reason: String| {
+        ServiceError::FromString(format!(
+            "Refusing to delete '{}': {reason}",
+            target.display()
+        ))
+    };
+    if options.protected.iter().any(|protected| {
+        protected.starts_with(target) || normalize_path(protected).starts_with(target)
+    }) {
+        return Err(refuse("it is or holds an allowed directory.".to_string()));
+    }
+
+    let metadata = fs::symlink_metadata(target)?;
+    let mut plan = DeletePlan {
+        target: target.to_path_buf(),
+        ..Default::default()
+    };
+    if !metadata.is_dir() {
+        let entry = PlannedEntry {
+            path: target.to_path_buf(),
+            is_dir: false,
+            bytes: metadata.len(),
+        };
+        plan.counts.add(&entry);
+        plan.entries.push(entry);
+        return Ok(plan);
+    }
+    if !options.recursive && fs::read_dir(target)?.next().is_some() {
+        return Err(ServiceError::FromString(format!(
+            "'{}' is a directory that is not empty. Set 'recursive' to delete it with its contents.",
+            target.display()
+        )));
+    }
+
+    let mut held = 0;
+    for entry in WalkDir::new(target).contents_first(true) {
+        let entry = entry.map_err(|err| ServiceError::FromString(err.to_string()))?;
+        if !options.allow_vcs && entry.file_name() == VCS_MARKER {
+            return Err(refuse(format!(
+                "'{}' is a git repository. Set 'allow_vcs' to delete it.",
+                entry.path().parent().unwrap_or(target).display()
+            )));
+        }
+        if entry.depth() > 0 {
+            held += 1;
+            if options.max_entries > 0 && !options.force && held > options.max_entries {
+                return Err(refuse(format!(
+                    "it holds more than {} entries. Set 'force' to delete it.",
+                    options.max_entries
+                )));
+            }
+        }
+        let is_dir = entry.file_type().is_dir();
+        let bytes = if is_dir {
+            0
+        } else {
+            entry
+                .metadata()
+                .map_err(|err| ServiceError::FromString(err.to_string()))?
+                .len()
+        };
+        let planned = PlannedEntry {
+            path: entry.into_path(),
+            is_dir,
+            bytes,
+        };
+        plan.counts.add(&planned);
+        plan.entries.push(planned);
+    }
+    Ok(plan)
+}
+
+/// Removes the entries of `plan` depth first. A failed entry does not stop the delete: it is
+/// reported, and the directories above it are left in place. Blocking.
+pub fn execute_delete(plan: &DeletePlan) -> DeleteReport {
+    let mut report = DeleteReport {
+        target: plan.target.clone(),
+        planned: plan.counts,
+        ..Default::default()
+    };
+    for entry in &plan.entries {
+        if entry.is_dir
+            && report
+                .failures
+                .iter()
+                .any(|failure| failure.path.starts_with(&entry.path))
+        {
+            continue;
+        }
+        let result = if entry.is_dir {
+            fs::remove_dir(&entry.path)
+        } else {
+            remove_file_or_link(&entry.path)
+        };
+        match result {
+            Ok(()) => report.deleted.add(entry),
+            Err(err) => report.failures.push(DeleteFailure {
+                path: entry.path.clone(),
+                reason: err.to_string(),
+            }),
+        }
+    }
+    report
+}
+
+/// Removes a file or a symbolic link. Links to directories are directories on Windows.
+fn remove_file_or_link(path: &Path) -> std::io::Result<()> {
+    fs::remove_file(path).or_else(|err| {
+        if cfg!(windows) && fs::symlink_metadata(path).is_ok_and(|m| m.file_type().is_symlink()) {
+            fs::remove_dir(path)
+        } else {
+            Err(err)
+        }
+    })
+}
@@ -0,0 +1,86 @@
+use std::sync::{Arc, Condvar, Mutex, MutexGuard};
+
+#[derive(Debug, Default)]
+struct Usage {
+    /// Files opened or queried right now.
+    in_use: usize,
+    /// Most files opened or queried at once so far.
+    peak: usize,
+    /// Ceiling on `in_use`, `0` when unlimited.
+    limit: usize,
+}
+
+#[derive(Debug, Default)]
+struct Shared {
+    usage: Mutex<Usage>,
+    released: Condvar,
+}
+
+/// Bounds the files opened or queried at once by the parallel parts of recursive walks, so that
+/// large trees do not run out of file descriptors. A counting semaphore that blocks the calling
+/// thread, meant for rayon workers; clones share the same permits.
+#[derive(Debug, Clone, Default)]
+pub struct DescriptorLimit {
+    shared: Arc<Shared>,
+}
+
+/// A file operation holding a descriptor; the permit is returned when dropped.
+#[derive(Debug)]
+pub struct DescriptorPermit<'a> {
+    shared: &'a Shared,
+}
+
+impl DescriptorLimit {
+    /// Sets how many permits may be held at once; `0` is unlimited.
+    pub(crate) fn set_limit(&self, limit: usize) {
+        self.lock().limit = limit;
+        self.shared.released.notify_all();
+    }
+
+    /// Waits for a permit, blocking the thread while the limit is reached.
+    pub fn acquire(&self) -> DescriptorPermit<'_> {
+        let mut usage = self.lock();
+        while usage.limit > 0 && usage.in_use >= usage.limit {
+            usage = self
+                .shared
+                .released
+                .wait(usage)
+                .unwrap_or_else(|poisoned| poisoned.into_inner());
+        }
+        usage.in_use += 1;
+        usage.peak = usage.peak.max(usage.in_use);
+        DescriptorPermit {
+            shared: &self.shared,
+        }
+    }
+
+    /// Runs `operation` holding a permit, which is returned as soon as it completes.
+    pub fn with_permit<T>(&self, operation: impl FnOnce() -> T) -> T {
+        let _permit = self.acquire();
+        operation()
+    }
+
+    /// Most permits held at once since the service started.
+    pub fn peak(&self) -> usize {
+        self.lock().peak
+    }
+
+    fn lock(&self) -> MutexGuard<'_, Usage> {
+        self.shared
+            .usage
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+}
+
+impl Drop for DescriptorPermit<'_> {
+    fn drop(&mut self) {
+        let mut usage = self
+            .shared
+            .usage
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner());
+        usage.in_use -= 1;
+        self.shared.released.notify_one();
+    }
+}
@@ -0,0 +1,80 @@
+use std::{
+    fs,
+    io::{self, Read},
+    path::Path,
+};
+
+use sha2::{Digest, Sha256};
+
+/// A digest of the contents and structure of a directory tree.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct DirectoryHash {
+    /// Hex-encoded SHA-256 root digest.
+    pub digest: String,
+    /// Number of regular files hashed.
+    pub files: u64,
+    /// Number of directories below the root.
+    pub directories: u64,
+    /// Bytes of file content hashed.
+    pub bytes: u64,
+}
+
+/// Hashes the tree below `root`, Merkle style: each directory digests its entries in byte
+/// order of their names, each entry contributing its kind, its name and the digest of its
+/// content, of its target for a symbolic link, or of its own entries for a directory. The
+/// root digest therefore only depends on the relative paths and contents, not on where the
+/// tree is located. Files are streamed through a buffer of `buffer_size` bytes. Symbolic
+/// links are not followed and special files are skipped. Blocking.
+pub fn hash_directory(root: &Path, buffer_size: usize) -> io::Result<DirectoryHash> {
+    let mut totals = DirectoryHash::default();
+    let mut buffer = vec![0u8; buffer_size.max(1)];
+    let digest = hash_tree(root, &mut buffer, &mut totals)?;
+    totals.digest = format!("{digest:x}");
+    Ok(totals)
+}
+
+type Output = sha2::digest::Output<Sha256>;
+
+fn hash_tree(dir: &Path, buffer: &mut [u8], totals: &mut DirectoryHash) -> io::Result<Output> {
+    let mut entries = fs::read_dir(dir)?.collect::<io::Result<Vec<_>>>()?;
+    entries.sort_by_key(|entry| entry.file_name());
+
+    let mut hasher = Sha256::new();
+    for entry in entries {
+        let path = entry.path();
+        let file_type = entry.file_type()?;
+        let (kind, digest) = if file_type.is_dir() {
+            totals.directories += 1;
+            (b'd', hash_tree(&path, buffer, totals)?)
+        } else if file_type.is_symlink() {
+            let target = fs::read_link(&path)?;
+            (b'l', Sha256::digest(target.as_os_str().as_encoded_bytes()))
+        } else if file_type.is_file() {
+            totals.files += 1;
+            (b'f', hash_file(&path, buffer, totals)?)
+        } else {
+            continue;
+        };
+        let name = entry.file_name();
+        let name = name.as_encoded_bytes();
+        hasher.update([kind]);
+        hasher.update((name.len() as u64).to_le_bytes());
+        hasher.update(name);
+        hasher.update(digest);
+    }
+    Ok(hasher.finalize())
+}
+
+fn hash_file(path: &Path, buffer: &mut [u8], totals: &mut DirectoryHash) -> io::Result<Output> {
+    let mut file = fs::File::open(path)?;
+    let mut hasher = Sha256::new();
+    loop {
+        let bytes_read = file.read(buffer)?;
+        if bytes_read == 0 {
+            break;
+        }
+        totals.bytes += bytes_read as u64;
+        hasher.update(&buffer[..bytes_read]);
+    }
+    Ok(hasher.finalize())
+}
@@ -0,0 +1,62 @@
+use std::{fmt, path::Path};
+
+use async_zip::error::ZipError;
+
+use crate::error::ServiceError;
+
+/// Reports the free space of the filesystem a path is written to, so that large writes can be
+/// refused before they fill the disk.
+pub trait SpaceProvider: fmt::Debug + Send + Sync {
+    /// Bytes available to unprivileged writes on the filesystem holding `path`, or `None` when
+    /// unknown. `path` need not exist yet: its closest existing ancestor is queried.
+    fn available_space(&self, path: &Path) -> Option<u64>;
+}
+
+/// Queries the operating system: `statvfs` on Unix. The space is unknown on other platforms.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct SystemSpace;
+
+impl SpaceProvider for SystemSpace {
+    fn available_space(&self, path: &Path) -> Option<u64> {
+        let existing = path.ancestors().find(|ancestor| ancestor.exists())?;
+        filesystem_available_space(existing)
+    }
+}
+
+#[cfg(unix)]
+fn filesystem_available_space(path: &Path) -> Option<u64> {
+    use std::os::unix::ffi::OsStrExt;
+
+    let path = std::ffi::CString::new(path.as_os_str().as_bytes()).ok()?;
+    // SAFETY: `statvfs` is plain data filled in by the call, and `path` is a valid C string.
+    let mut stats: libc::statvfs = unsafe { std::mem::zeroed() };
+    if unsafe { libc::statvfs(path.as_ptr(), &mut stats) } != 0 {
+        return None;
+    }
+    #[allow(clippy::unnecessary_cast)]
+    Some(stats.f_bavail as u64 * stats.f_frsize as u64)
+}
+
+#[cfg(not(unix))]
+fn filesystem_available_space(_path: &Path) -> Option<u64> {
+    None
+}
+
+/// Whether an I/O error means the device is full.
+pub fn is_storage_full_io(err: &std::io::Error) -> bool {
+    #[cfg(unix)]
+    if err.raw_os_error() == Some(libc::ENOSPC) {
+        return true;
+    }
+    err.kind() == std::io::ErrorKind::StorageFull
+}
+
+/// Whether `err` was caused by the device running out of space.
+pub fn is_storage_full(err: &ServiceError) -> bool {
+    match err {
+        ServiceError::IoError(err) | ServiceError::ZipError(ZipError::UpstreamReadError(err)) => {
+            is_storage_full_io(err)
+        }
+        _ => false,
+    }
+}
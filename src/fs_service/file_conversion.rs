@@ -0,0 +1,134 @@
+use std::{collections::HashSet, fmt::Write};
+
+/// Widest tab stop accepted when converting indentation.
+pub const MAX_INDENT_WIDTH: usize = 16;
+
+/// A transformation applied by a file conversion, and what it changed.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ConversionStep {
+    /// What the transformation does, e.g. `line endings to LF`.
+    pub description: String,
+    /// What changed, e.g. `3 lines` or `from ISO-8859-1`. `None` when the file already matched.
+    pub change: Option<String>,
+}
+
+impl ConversionStep {
+    pub fn new(description: impl Into<String>, change: Option<String>) -> Self {
+        Self {
+            description: description.into(),
+            change,
+        }
+    }
+
+    /// A step that changed `lines` lines, or nothing when `0`.
+    pub fn lines(description: impl Into<String>, lines: usize) -> Self {
+        Self::new(
+            description,
+            (lines > 0).then(|| format!("{lines} {}", if lines == 1 { "line" } else { "lines" })),
+        )
+    }
+}
+
+/// The transformations of a file conversion, in the order they were applied.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ConversionReport {
+    pub steps: Vec<ConversionStep>,
+}
+
+impl ConversionReport {
+    pub fn is_changed(&self) -> bool {
+        self.steps.iter().any(|step| step.change.is_some())
+    }
+
+    pub fn render_text(&self) -> String {
+        let mut output = String::new();
+        for step in &self.steps {
+            let _ = match &step.change {
+                Some(change) => writeln!(output, "  changed    {}: {change}", step.description),
+                None => writeln!(output, "  unchanged  {}", step.description),
+            };
+        }
+        output
+    }
+}
+
+/// Converts every line ending of `content` to `line_ending`, `"\n"` or `"\r\n"`. A lone `\r`
+/// is not a line ending. Returns the new content and the number of lines changed.
+pub fn convert_line_endings(content: &str, line_ending: &str) -> (String, usize) {
+    let mut output = String::with_capacity(content.len());
+    let mut changed = 0;
+    for line in content.split_inclusive('\n') {
+        let Some(text) = line.strip_suffix('\n') else {
+            output.push_str(line);
+            continue;
+        };
+        let text = text.strip_suffix('\r').unwrap_or(text);
+        if text.len() + line_ending.len() != line.len() {
+            changed += 1;
+        }
+        output.push_str(text);
+        output.push_str(line_ending);
+    }
+    (output, changed)
+}
+
+/// Rewrites the leading whitespace of each line with tabs (`to_tabs`) or spaces, tab stops being
+/// `width` columns apart, `width` being at most [`MAX_INDENT_WIDTH`]. With tabs, the columns short of a full tab stay spaces. Only leading
+/// whitespace is touched, and the lines in `skipped` (0-based) are left alone. Returns the new
+/// content and the number of lines changed.
+pub fn convert_indentation(
+    content: &str,
+    to_tabs: bool,
+    width: usize,
+    skipped: &HashSet<usize>,
+) -> (String, usize) {
+    let width = width.clamp(1, MAX_INDENT_WIDTH);
+    let mut output = String::with_capacity(content.len());
+    let mut changed = 0;
+    for (number, line) in content.split_inclusive('\n').enumerate() {
+        let indent_length = line.len() - line.trim_start_matches([' ', '\t']).len();
+        let indent = &line[..indent_length];
+        if skipped.contains(&number) || indent.is_empty() {
+            output.push_str(line);
+            continue;
+        }
+        let columns = indent.chars().fold(0, |column, c| match c {
+            '\t' => (column / width + 1) * width,
+            _ => column + 1,
+        });
+        let converted = if to_tabs {
+            format!(
+                "{}{}",
+                "\t".repeat(columns / width),
+                " ".repeat(columns % width)
+            )
+        } else {
+            " ".repeat(columns)
+        };
+        if converted != indent {
+            changed += 1;
+        }
+        output.push_str(&converted);
+        output.push_str(&line[indent_length..]);
+    }
+    (output, changed)
+}
+
+/// Removes the spaces and tabs ending each line, except on the lines in `skipped` (0-based).
+/// Returns the new content and the number of lines changed.
+pub fn strip_trailing_whitespace(content: &str, skipped: &HashSet<usize>) -> (String, usize) {
+    let mut output = String::with_capacity(content.len());
+    let mut changed = 0;
+    for (number, line) in content.split_inclusive('\n').enumerate() {
+        let text = line.trim_end_matches(['\r', '\n']);
+        let trimmed = text.trim_end_matches([' ', '\t']);
+        if skipped.contains(&number) || trimmed.len() == text.len() {
+            output.push_str(line);
+            continue;
+        }
+        changed += 1;
+        output.push_str(trimmed);
+        output.push_str(&line[text.len()..]);
+    }
+    (output, changed)
+}
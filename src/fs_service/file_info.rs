@@ -11,6 +11,7 @@ pub struct FileInfo {
     pub accessed: Option<SystemTime>,
     pub is_directory: bool,
     pub is_file: bool,
+    pub is_symlink: bool,
     pub metadata: fs::Metadata,
 }
 
@@ -24,6 +25,7 @@ modified: {}
 accessed: {}
 isDirectory: {}
 isFile: {}
+isSymlink: {}
 permissions: {}
 "#,
             self.size,
@@ -32,6 +34,7 @@ permissions: {}
             self.accessed.map_or("".to_string(), format_system_time),
             self.is_directory,
             self.is_file,
+            self.is_symlink,
             format_permissions(&self.metadata)
         )
     }
@@ -0,0 +1,177 @@
+use std::{fmt::Write, path::Path};
+
+/// A process that has the file open.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FileHolder {
+    pub pid: u32,
+    /// The command name of the process, when readable.
+    pub command: Option<String>,
+}
+
+/// What a best-effort probe found about other users of a file.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct FileLockReport {
+    /// Whether a lock is held on the file: an advisory (`flock`) or POSIX record lock on Unix,
+    /// a sharing violation on an exclusive open on Windows. `None` when it could not be probed.
+    pub locked: Option<bool>,
+    /// The process holding the POSIX record lock, when known.
+    pub lock_owner: Option<u32>,
+    /// Processes with the file open. Only collected on Linux, from `/proc`.
+    pub holders: Vec<FileHolder>,
+    /// Whether `holders` was collected.
+    pub holders_scanned: bool,
+    /// The `open_file` handle of this server buffering the file, if any.
+    pub open_handle: Option<String>,
+}
+
+impl FileLockReport {
+    /// Whether the file looks locked or open elsewhere, so that an edit may conflict.
+    pub fn appears_in_use(&self) -> bool {
+        self.locked == Some(true) || !self.holders.is_empty() || self.open_handle.is_some()
+    }
+
+    pub fn render_text(&self, path: &Path) -> String {
+        let mut output = format!(
+            "{}: {}\n",
+            path.display(),
+            if self.appears_in_use() {
+                "appears to be in use"
+            } else {
+                "does not appear to be locked or open"
+            }
+        );
+        match (self.locked, self.lock_owner) {
+            (Some(true), Some(pid)) => {
+                let _ = writeln!(output, "  locked by process {pid}");
+            }
+            (Some(true), None) => output.push_str("  locked\n"),
+            (Some(false), _) => output.push_str("  no lock held\n"),
+            (None, _) => output.push_str("  locks could not be probed\n"),
+        }
+        let current = std::process::id();
+        for holder in &self.holders {
+            let _ = writeln!(
+                output,
+                "  open by process {}{}{}",
+                holder.pid,
+                holder
+                    .command
+                    .as_ref()
+                    .map(|command| format!(" ({command})"))
+                    .unwrap_or_default(),
+                if holder.pid == current {
+                    ", this server"
+                } else {
+                    ""
+                }
+            );
+        }
+        if !self.holders_scanned {
+            output.push_str("  open handles of other processes are not listed on this platform\n");
+        }
+        if let Some(handle) = &self.open_handle {
+            let _ = writeln!(output, "  buffered by open_file handle {handle}");
+        }
+        output
+    }
+}
+
+/// Probes `path` for locks and, on Linux, for processes holding it open. Blocking.
+pub fn probe_file_lock(path: &Path) -> std::io::Result<FileLockReport> {
+    let mut report = FileLockReport::default();
+    #[cfg(target_os = "linux")]
+    {
+        report.holders = open_file_holders(path)?;
+        report.holders_scanned = true;
+    }
+    (report.locked, report.lock_owner) = probe_lock(path)?;
+    Ok(report)
+}
+
+/// Probes the advisory and POSIX record locks of `path` without taking them.
+#[cfg(unix)]
+fn probe_lock(path: &Path) -> std::io::Result<(Option<bool>, Option<u32>)> {
+    use std::os::fd::AsRawFd;
+
+    let file = std::fs::File::open(path)?;
+    let fd = file.as_raw_fd();
+
+    // SAFETY: `flock` is zeroed plain data, and `fd` stays open for the duration of the calls.
+    let mut lock: libc::flock = unsafe { std::mem::zeroed() };
+    lock.l_type = libc::F_WRLCK as libc::c_short;
+    lock.l_whence = libc::SEEK_SET as libc::c_short;
+    if unsafe { libc::fcntl(fd, libc::F_GETLK, &mut lock) } == 0
+        && lock.l_type != libc::F_UNLCK as libc::c_short
+    {
+        return Ok((Some(true), Some(lock.l_pid as u32)));
+    }
+
+    // SAFETY: as above; a lock taken by the probe is released right away
+    if unsafe { libc::flock(fd, libc::LOCK_EX | libc::LOCK_NB) } == 0 {
+        unsafe { libc::flock(fd, libc::LOCK_UN) };
+        return Ok((Some(false), None));
+    }
+    let err = std::io::Error::last_os_error();
+    Ok(match err.raw_os_error() {
+        Some(libc::EWOULDBLOCK) => (Some(true), None),
+        _ => (None, None),
+    })
+}
+
+/// Attempts an exclusive open of `path`; a sharing violation means another process has it open.
+#[cfg(windows)]
+fn probe_lock(path: &Path) -> std::io::Result<(Option<bool>, Option<u32>)> {
+    use std::os::windows::fs::OpenOptionsExt;
+
+    match std::fs::OpenOptions::new()
+        .read(true)
+        .share_mode(0)
+        .open(path)
+    {
+        Ok(_) => Ok((Some(false), None)),
+        Err(err) if super::is_sharing_violation(&err) => Ok((Some(true), None)),
+        Err(err) if err.kind() == std::io::ErrorKind::PermissionDenied => Ok((None, None)),
+        Err(err) => Err(err),
+    }
+}
+
+#[cfg(not(any(unix, windows)))]
+fn probe_lock(_path: &Path) -> std::io::Result<(Option<bool>, Option<u32>)> {
+    Ok((None, None))
+}
+
+/// Lists the processes with a descriptor open on `path`, comparing device and inode numbers.
+/// Processes whose descriptors cannot be read, usually those of other users, are skipped.
+#[cfg(target_os = "linux")]
+fn open_file_holders(path: &Path) -> std::io::Result<Vec<FileHolder>> {
+    use std::os::unix::fs::MetadataExt;
+
+    let target = std::fs::metadata(path)?;
+    let mut holders = vec![];
+    for entry in std::fs::read_dir("/proc")?.flatten() {
+        let Some(pid) = entry
+            .file_name()
+            .to_str()
+            .and_then(|name| name.parse::<u32>().ok())
+        else {
+            continue;
+        };
+        let Ok(descriptors) = std::fs::read_dir(entry.path().join("fd")) else {
+            continue;
+        };
+        let holds = descriptors.flatten().any(|descriptor| {
+            std::fs::metadata(descriptor.path()).is_ok_and(|metadata| {
+                metadata.dev() == target.dev() && metadata.ino() == target.ino()
+            })
+        });
+        if holds {
+            holders.push(FileHolder {
+                pid,
+                command: std::fs::read_to_string(entry.path().join("comm"))
+                    .ok()
+                    .map(|command| command.trim_end().to_string()),
+            });
+        }
+    }
+    Ok(holders)
+}
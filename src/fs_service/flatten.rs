@@ -0,0 +1,278 @@
+use std::{
+    collections::HashSet,
+    fmt::Write,
+    fs, io,
+    path::{Path, PathBuf},
+};
+
+use walkdir::WalkDir;
+
+use super::deletion::VCS_MARKER;
+use crate::error::{ServiceError, ServiceResult};
+
+/// Options of [`plan_flatten`].
+#[derive(Debug, Clone, Default)]
+pub struct FlattenOptions {
+    /// Whether a colliding name is prefixed with the directories of the file, e.g.
+    /// `assets_icons_logo.png`, rather than suffixed with a counter, e.g. `logo_1.png`.
+    pub prefix_paths: bool,
+    /// Whether the directories emptied by the moves are kept.
+    pub keep_directories: bool,
+    /// Directories never removed, such as the allowed directories.
+    pub protected: Vec<PathBuf>,
+}
+
+/// A file a flatten moves.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PlannedMove {
+    pub from: PathBuf,
+    pub to: PathBuf,
+    /// Whether the file gets another name, to resolve a collision.
+    pub renamed: bool,
+}
+
+/// What a flatten moves and removes, planned before anything is touched.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct FlattenPlan {
+    pub root: PathBuf,
+    pub destination: PathBuf,
+    /// Moves in the order names were given: shallower files first, then by path.
+    pub moves: Vec<PlannedMove>,
+    /// Directories below the root removed once empty, deepest first. Empty when they are kept.
+    pub directories: Vec<PathBuf>,
+}
+
+/// A file that could not be moved.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MoveFailure {
+    pub path: PathBuf,
+    pub reason: String,
+}
+
+/// The outcome of a flatten.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct FlattenReport {
+    pub dry_run: bool,
+    pub plan: FlattenPlan,
+    /// Files moved. All the planned moves in a dry run.
+    pub moved: usize,
+    /// Directories removed once empty.
+    pub removed_directories: usize,
+    pub failures: Vec<MoveFailure>,
+}
+
+impl FlattenReport {
+    pub fn render_text(&self) -> String {
+        let plan = &self.plan;
+        if plan.moves.is_empty() {
+            return format!("No files to move into '{}'.\n", plan.destination.display());
+        }
+        let renamed = plan.moves.iter().filter(|planned| planned.renamed).count();
+        let mut output = format!(
+            "{} {} {} from '{}' into '{}' ({renamed} renamed to resolve name collisions):\n",
+            if self.dry_run { "Would move" } else { "Moved" },
+            self.moved,
+            if self.moved == 1 { "file" } else { "files" },
+            plan.root.display(),
+            plan.destination.display()
+        );
+        for planned in &plan.moves {
+            let from = planned
+                .from
+                .strip_prefix(&plan.root)
+                .unwrap_or(&planned.from);
+            let to = planned.to.file_name().unwrap_or_default();
+            let _ = writeln!(
+                output,
+                "  {} -> {}{}",
+                from.display(),
+                to.to_string_lossy(),
+                if planned.renamed { " (renamed)" } else { "" }
+            );
+        }
+        if self.dry_run {
+            let _ = writeln!(
+                output,
+                "Would remove up to {} emptied {}.",
+                plan.directories.len(),
+                if plan.directories.len() == 1 {
+                    "directory"
+                } else {
+                    "directories"
+                }
+            );
+            output.push_str("Dry run: nothing was moved.\n");
+            return output;
+        }
+        let _ = writeln!(
+            output,
+            "Removed {} emptied {}.",
+            self.removed_directories,
+            if self.removed_directories == 1 {
+                "directory"
+            } else {
+                "directories"
+            }
+        );
+        if !self.failures.is_empty() {
+            let _ = writeln!(
+                output,
+                "Failed to move {} {}:",
+                self.failures.len(),
+                if self.failures.len() == 1 {
+                    "file"
+                } else {
+                    "files"
+                }
+            );
+            for failure in &self.failures {
+                let _ = writeln!(output, "  {}: {}", failure.path.display(), failure.reason);
+            }
+        }
+        output
+    }
+}
+
+/// Plans moving every file below `root` into `destination`, which defaults to the root itself.
+/// Files already in the destination keep their place and their names; the others keep their
+/// names unless taken, shallower files first. Names are compared ignoring case, so that no file
+/// is overwritten on case-insensitive file systems. Git repositories and the destination are not
+/// descended into, and symbolic links are moved, never followed. Blocking.
+pub fn plan_flatten(
+    root: &Path,
+    destination: &Path,
+    options: &FlattenOptions,
+) -> ServiceResult<FlattenPlan> {
+    let mut taken: HashSet<String> = HashSet::new();
+    if destination.is_dir() {
+        for entry in fs::read_dir(destination)? {
+            taken.insert(entry?.file_name().to_string_lossy().to_lowercase());
+        }
+    }
+
+    let mut files = vec![];
+    let mut directories = vec![];
+    let mut walker = WalkDir::new(root).min_depth(1).into_iter();
+    while let Some(entry) = walker.next() {
+        let entry = entry.map_err(|err| ServiceError::FromString(err.to_string()))?;
+        if entry.file_type().is_dir() {
+            if entry.file_name() == VCS_MARKER || entry.path() == destination {
+                walker.skip_current_dir();
+            } else {
+                directories.push((entry.depth(), entry.into_path()));
+            }
+        } else if entry.depth() > 1 || root != destination {
+            files.push((entry.depth(), entry.into_path()));
+        }
+    }
+    files.sort();
+
+    let mut moves = Vec::with_capacity(files.len());
+    for (_, from) in files {
+        let name = from.file_name().unwrap_or_default().to_string_lossy();
+        let mut candidate = name.to_string();
+        if options.prefix_paths && taken.contains(&candidate.to_lowercase()) {
+            let relative = from.strip_prefix(root).unwrap_or(&from);
+            candidate = relative
+                .iter()
+                .map(|part| part.to_string_lossy())
+                .collect::<Vec<_>>()
+                .join("_");
+        }
+        let base = candidate.clone();
+        let mut counter = 0;
+        while taken.contains(&candidate.to_lowercase()) {
+            counter += 1;
+            candidate = with_counter(&base, counter);
+        }
+        taken.insert(candidate.to_lowercase());
+        moves.push(PlannedMove {
+            to: destination.join(&candidate),
+            renamed: candidate != name,
+            from,
+        });
+    }
+
+    let directories = if options.keep_directories {
+        vec![]
+    } else {
+        directories.sort_by(|a, b| b.cmp(a));
+        directories
+            .into_iter()
+            .map(|(_, dir)| dir)
+            .filter(|dir| {
+                !options
+                    .protected
+                    .iter()
+                    .any(|protected| protected.starts_with(dir))
+            })
+            .collect()
+    };
+    Ok(FlattenPlan {
+        root: root.to_path_buf(),
+        destination: destination.to_path_buf(),
+        moves,
+        directories,
+    })
+}
+
+/// Moves the files of `plan`, then removes the directories left empty. A failed move does not
+/// stop the flatten: it is reported, and its directory is kept. Blocking.
+pub fn execute_flatten(plan: FlattenPlan) -> FlattenReport {
+    let mut report = FlattenReport::default();
+    if !plan.moves.is_empty()
+        && let Err(err) = fs::create_dir_all(&plan.destination)
+    {
+        report.failures.push(MoveFailure {
+            path: plan.destination.clone(),
+            reason: err.to_string(),
+        });
+        report.plan = plan;
+        return report;
+    }
+    for planned in &plan.moves {
+        match move_entry(&planned.from, &planned.to) {
+            Ok(()) => report.moved += 1,
+            Err(err) => report.failures.push(MoveFailure {
+                path: planned.from.clone(),
+                reason: err.to_string(),
+            }),
+        }
+    }
+    // Directories still holding files fail to be removed, and are kept
+    report.removed_directories = plan
+        .directories
+        .iter()
+        .filter(|dir| fs::remove_dir(dir).is_ok())
+        .count();
+    report.plan = plan;
+    report
+}
+
+/// Renames `from` to `to`, copying then removing it when they are on different file systems.
+fn move_entry(from: &Path, to: &Path) -> io::Result<()> {
+    if to.symlink_metadata().is_ok() {
+        return Err(io::Error::new(
+            io::ErrorKind::AlreadyExists,
+            format!("'{}' already exists", to.display()),
+        ));
+    }
+    match fs::rename(from, to) {
+        Err(err) if err.kind() == io::ErrorKind::CrossesDevices => {
+            fs::copy(from, to)?;
+            fs::remove_file(from)
+        }
+        result => result,
+    }
+}
+
+/// Adds `_<counter>` to `name` before its extensions, e.g. `archive_2.tar.gz`. A leading dot
+/// belongs to the name, as in `.env_2`.
+fn with_counter(name: &str, counter: usize) -> String {
+    let split = name
+        .char_indices()
+        .skip(1)
+        .find(|(_, c)| *c == '.')
+        .map_or(name.len(), |(index, _)| index);
+    format!("{}_{counter}{}", &name[..split], &name[split..])
+}
@@ -0,0 +1,68 @@
+use std::path::PathBuf;
+
+use super::text_probe::{FileClass, classify_bytes, decode_text};
+
+/// Bytes read from the start of each file to classify it.
+pub const GENERATED_SAMPLE_SIZE: usize = 64 * 1024;
+/// Average line length, in characters, above which a file looks minified.
+pub const MAX_AVERAGE_LINE_LENGTH: usize = 250;
+/// Share of non-whitespace characters above which a file looks minified.
+pub const MAX_NON_WHITESPACE_DENSITY: f64 = 0.95;
+/// Samples shorter than this are too small for the density to mean anything.
+const MIN_DENSITY_SAMPLE: usize = 1024;
+/// Lines at the top of a file searched for a "DO NOT EDIT" banner.
+const BANNER_LINES: usize = 10;
+
+/// A file that looks minified or generated.
+#[derive(Debug, Clone, PartialEq)]
+pub struct GeneratedFile {
+    pub path: PathBuf,
+    /// Why the file was flagged, in plain words.
+    pub reasons: Vec<String>,
+    /// Length in characters of the longest line of the sample.
+    pub longest_line: usize,
+    /// Average line length in characters of the sample.
+    pub average_line_length: usize,
+}
+
+/// Classifies the start of a file. Returns the reasons it looks generated, with the longest and
+/// average line lengths, or `None` when it does not or is binary.
+pub fn classify_sample(sample: &[u8]) -> Option<(Vec<String>, usize, usize)> {
+    let text = match classify_bytes(sample) {
+        FileClass::TextUtf8 => String::from_utf8_lossy(sample).into_owned(),
+        class => decode_text(sample.to_vec(), &class)?,
+    };
+    let lines: Vec<&str> = text.lines().collect();
+    let lengths: Vec<usize> = lines.iter().map(|line| line.chars().count()).collect();
+    let longest_line = lengths.iter().copied().max().unwrap_or_default();
+    let average_line_length = lengths.iter().sum::<usize>() / lengths.len().max(1);
+
+    let mut reasons = vec![];
+    if text.contains("@generated") {
+        reasons.push("contains an @generated marker".to_string());
+    } else if lines
+        .iter()
+        .take(BANNER_LINES)
+        .any(|line| line.contains("DO NOT EDIT"))
+    {
+        reasons.push("has a DO NOT EDIT banner".to_string());
+    }
+    if average_line_length > MAX_AVERAGE_LINE_LENGTH {
+        reasons.push(format!(
+            "average line length is {average_line_length} characters"
+        ));
+    }
+    let characters = text.chars().count();
+    if characters >= MIN_DENSITY_SAMPLE {
+        let dense = text.chars().filter(|c| !c.is_whitespace()).count();
+        let density = dense as f64 / characters as f64;
+        if density > MAX_NON_WHITESPACE_DENSITY {
+            reasons.push(format!(
+                "{:.0}% of characters are not whitespace",
+                density * 100.0
+            ));
+        }
+    }
+
+    (!reasons.is_empty()).then_some((reasons, longest_line, average_line_length))
+}
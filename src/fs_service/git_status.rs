@@ -0,0 +1,163 @@
+use std::{
+    collections::HashMap,
+    fmt,
+    path::{Path, PathBuf},
+};
+
+use serde::Serialize;
+use serde_json::{Value, json};
+
+/// Maximum number of changed paths collected from a repository for annotations.
+pub const MAX_GIT_STATUS_ENTRIES: usize = 5000;
+
+/// The git status shown next to a listed entry.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum GitFileStatus {
+    /// Changed in the working tree (including deletions, renames and conflicts).
+    Modified,
+    /// Changed in the index only.
+    Staged,
+    Untracked,
+    Ignored,
+}
+
+impl GitFileStatus {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            GitFileStatus::Modified => "modified",
+            GitFileStatus::Staged => "staged",
+            GitFileStatus::Untracked => "untracked",
+            GitFileStatus::Ignored => "ignored",
+        }
+    }
+}
+
+impl fmt::Display for GitFileStatus {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// Git status of the changed paths below a directory. Paths without an entry are unmodified.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct GitStatusMap {
+    /// The directory whose entries are annotated.
+    pub root: PathBuf,
+    statuses: HashMap<PathBuf, GitFileStatus>,
+    /// Whether the repository had more than [`MAX_GIT_STATUS_ENTRIES`] changed paths, in which
+    /// case the remaining ones are not annotated.
+    pub truncated: bool,
+}
+
+impl GitStatusMap {
+    /// Returns the status of `path`. Entries inside an untracked or ignored directory inherit
+    /// its status.
+    pub fn status_of(&self, path: &Path) -> Option<GitFileStatus> {
+        if let Some(status) = self.statuses.get(path) {
+            return Some(*status);
+        }
+        path.ancestors().skip(1).find_map(|ancestor| {
+            self.statuses.get(ancestor).copied().filter(|status| {
+                matches!(status, GitFileStatus::Untracked | GitFileStatus::Ignored)
+            })
+        })
+    }
+
+    /// Adds a `git_status` field to the entries of a tree produced by `directory_tree` for
+    /// [`root`](Self::root).
+    pub fn annotate_tree(&self, entries: &mut Value) {
+        self.annotate_level(&self.root, entries);
+    }
+
+    fn annotate_level(&self, root: &Path, entries: &mut Value) {
+        let Some(entries) = entries.as_array_mut() else {
+            return;
+        };
+        for entry in entries {
+            let Some(name) = entry.get("name").and_then(Value::as_str) else {
+                continue;
+            };
+            let path = root.join(name);
+            if let Some(status) = self.status_of(&path) {
+                entry["git_status"] = json!(status);
+            }
+            if let Some(children) = entry.get_mut("children") {
+                self.annotate_level(&path, children);
+            }
+        }
+    }
+}
+
+/// Collects the git status of the paths below `dir`. Returns `None` when `dir` is not inside a
+/// git working tree rooted at or below `root`, the allowed directory holding `dir`: repositories
+/// above it are not searched.
+#[cfg(feature = "git-status")]
+pub fn load_git_status(dir: &Path, root: &Path) -> Option<GitStatusMap> {
+    use git2::{Repository, RepositoryOpenFlags, Status, StatusOptions};
+
+    // Discovery checks every directory below the ceiling, so the root itself is still searched
+    let ceiling = root.parent().unwrap_or(root);
+    let repository = Repository::open_ext(dir, RepositoryOpenFlags::empty(), [ceiling]).ok()?;
+    let workdir = repository.workdir()?.canonicalize().ok()?;
+    let relative = dir.strip_prefix(&workdir).ok()?;
+
+    let mut options = StatusOptions::new();
+    options
+        .include_untracked(true)
+        .include_ignored(true)
+        .recurse_untracked_dirs(false)
+        .recurse_ignored_dirs(false);
+    if !relative.as_os_str().is_empty() {
+        options.pathspec(relative);
+    }
+    let statuses = repository.statuses(Some(&mut options)).ok()?;
+
+    let mut map = GitStatusMap {
+        root: dir.to_path_buf(),
+        ..Default::default()
+    };
+    for entry in statuses.iter() {
+        if map.statuses.len() >= MAX_GIT_STATUS_ENTRIES {
+            map.truncated = true;
+            break;
+        }
+        let Some(path) = entry.path() else {
+            continue;
+        };
+        let status = entry.status();
+        let file_status = if status.is_ignored() {
+            GitFileStatus::Ignored
+        } else if status.is_wt_new() {
+            GitFileStatus::Untracked
+        } else if status.intersects(
+            Status::WT_MODIFIED
+                | Status::WT_DELETED
+                | Status::WT_RENAMED
+                | Status::WT_TYPECHANGE
+                | Status::CONFLICTED,
+        ) {
+            GitFileStatus::Modified
+        } else if status.intersects(
+            Status::INDEX_NEW
+                | Status::INDEX_MODIFIED
+                | Status::INDEX_DELETED
+                | Status::INDEX_RENAMED
+                | Status::INDEX_TYPECHANGE,
+        ) {
+            GitFileStatus::Staged
+        } else {
+            continue;
+        };
+        // Directories are reported with a trailing slash, which `join` drops.
+        map.statuses
+            .insert(workdir.join(path.trim_end_matches('/')), file_status);
+    }
+    Some(map)
+}
+
+/// Git status annotations are not compiled in; listings are never annotated.
+#[cfg(not(feature = "git-status"))]
+pub fn load_git_status(_dir: &Path, _root: &Path) -> Option<GitStatusMap> {
+    None
+}
@@ -0,0 +1,67 @@
+use std::io::Cursor;
+
+use image::{ImageFormat, ImageReader, imageops::FilterType};
+
+use crate::error::{ServiceError, ServiceResult};
+
+/// An image whose largest side has been limited to a maximum dimension.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ScaledImage {
+    pub mime_type: String,
+    /// The encoded image, in its original format.
+    pub bytes: Vec<u8>,
+    /// Width and height of the image on disk.
+    pub original_dimensions: (u32, u32),
+    /// Width and height of the returned image.
+    pub dimensions: (u32, u32),
+}
+
+/// Returns the image format for `mime_type` if it can be decoded and re-encoded for scaling.
+pub fn scalable_format(mime_type: &str) -> Option<ImageFormat> {
+    ImageFormat::from_mime_type(mime_type).filter(|format| {
+        matches!(
+            format,
+            ImageFormat::Png | ImageFormat::Jpeg | ImageFormat::WebP
+        )
+    })
+}
+
+/// Downscales `bytes` so that neither side exceeds `max_dimension`, preserving the aspect ratio.
+/// Images already within bounds are returned unchanged. Images with more than `max_pixels`
+/// pixels are rejected from their header, before anything is decoded.
+pub fn downscale_image(
+    bytes: Vec<u8>,
+    format: ImageFormat,
+    max_dimension: u32,
+    max_pixels: u64,
+) -> ServiceResult<ScaledImage> {
+    let (width, height) =
+        ImageReader::with_format(Cursor::new(&bytes), format).into_dimensions()?;
+    if u64::from(width) * u64::from(height) > max_pixels {
+        return Err(ServiceError::FromString(format!(
+            "Image is too large to decode: {width}x{height} exceeds the limit of {max_pixels} pixels."
+        )));
+    }
+
+    let mime_type = format.to_mime_type().to_string();
+    if width.max(height) <= max_dimension {
+        return Ok(ScaledImage {
+            mime_type,
+            bytes,
+            original_dimensions: (width, height),
+            dimensions: (width, height),
+        });
+    }
+
+    let image = ImageReader::with_format(Cursor::new(&bytes), format).decode()?;
+    let scaled = image.resize(max_dimension, max_dimension, FilterType::Lanczos3);
+    let mut output = Cursor::new(Vec::new());
+    scaled.write_to(&mut output, format)?;
+
+    Ok(ScaledImage {
+        mime_type,
+        bytes: output.into_inner(),
+        original_dimensions: (width, height),
+        dimensions: (scaled.width(), scaled.height()),
+    })
+}
@@ -0,0 +1,159 @@
+use std::{
+    collections::BTreeSet,
+    future::Future,
+    hash::{BuildHasher, Hasher, RandomState},
+    sync::{
+        Arc, Mutex,
+        atomic::{AtomicU64, Ordering},
+    },
+    time::{Duration, Instant, SystemTime, UNIX_EPOCH},
+};
+
+/// Prefix of the footer line appended to tool results when timings are requested.
+pub const TIMINGS_FOOTER_PREFIX: &str = "[timings]";
+/// Prefix of the footer line identifying the operation of a tool call.
+pub const OPERATION_FOOTER_PREFIX: &str = "[operation]";
+
+/// Crockford's base32 alphabet, used by ULIDs.
+const ULID_ALPHABET: &[u8; 32] = b"0123456789ABCDEFGHJKMNPQRSTVWXYZ";
+
+/// Fast path taken when work is spread across the rayon thread pool.
+pub const FAST_PATH_PARALLEL: &str = "parallel";
+/// Fast path taken when several files are read concurrently on the async runtime.
+pub const FAST_PATH_CONCURRENT: &str = "concurrent";
+
+tokio::task_local! {
+    static CURRENT_STATS: Arc<OperationStats>;
+}
+
+/// I/O counters collected while a single tool call is running.
+///
+/// Counters are only collected inside [`instrument`]; outside of it every `record_*`
+/// call is a no-op, so file system operations can report unconditionally.
+#[derive(Debug, Default)]
+pub struct OperationStats {
+    bytes_read: AtomicU64,
+    bytes_written: AtomicU64,
+    entries_visited: AtomicU64,
+    fast_paths: Mutex<BTreeSet<&'static str>>,
+}
+
+impl OperationStats {
+    /// Returns the stats of the operation running on the current task, if any.
+    ///
+    /// Work that is moved onto other threads (e.g. rayon) does not inherit the task-local
+    /// context, so it should capture the stats with this method before being spawned.
+    pub fn current() -> Option<Arc<OperationStats>> {
+        CURRENT_STATS.try_with(Arc::clone).ok()
+    }
+
+    pub fn add_bytes_read(&self, bytes: u64) {
+        self.bytes_read.fetch_add(bytes, Ordering::Relaxed);
+    }
+
+    pub fn add_bytes_written(&self, bytes: u64) {
+        self.bytes_written.fetch_add(bytes, Ordering::Relaxed);
+    }
+
+    pub fn add_entries_visited(&self, entries: u64) {
+        self.entries_visited.fetch_add(entries, Ordering::Relaxed);
+    }
+
+    pub fn add_fast_path(&self, name: &'static str) {
+        if let Ok(mut fast_paths) = self.fast_paths.lock() {
+            fast_paths.insert(name);
+        }
+    }
+
+    fn report(&self, elapsed: Duration) -> OperationReport {
+        OperationReport {
+            elapsed,
+            bytes_read: self.bytes_read.load(Ordering::Relaxed),
+            bytes_written: self.bytes_written.load(Ordering::Relaxed),
+            entries_visited: self.entries_visited.load(Ordering::Relaxed),
+            fast_paths: self
+                .fast_paths
+                .lock()
+                .map(|fast_paths| fast_paths.iter().copied().collect())
+                .unwrap_or_default(),
+        }
+    }
+}
+
+pub fn record_bytes_read(bytes: u64) {
+    let _ = CURRENT_STATS.try_with(|stats| stats.add_bytes_read(bytes));
+}
+
+pub fn record_bytes_written(bytes: u64) {
+    let _ = CURRENT_STATS.try_with(|stats| stats.add_bytes_written(bytes));
+}
+
+pub fn record_entries_visited(entries: u64) {
+    let _ = CURRENT_STATS.try_with(|stats| stats.add_entries_visited(entries));
+}
+
+pub fn record_fast_path(name: &'static str) {
+    let _ = CURRENT_STATS.try_with(|stats| stats.add_fast_path(name));
+}
+
+/// Final measurements of an instrumented operation.
+#[derive(Debug, Clone, PartialEq)]
+pub struct OperationReport {
+    pub elapsed: Duration,
+    pub bytes_read: u64,
+    pub bytes_written: u64,
+    pub entries_visited: u64,
+    pub fast_paths: Vec<&'static str>,
+}
+
+impl OperationReport {
+    /// Renders the report as a single line of space separated `key=value` pairs, e.g.
+    /// `[timings] elapsed_ms=1.042 bytes_read=2048 bytes_written=0 entries_visited=12 fast_paths=parallel`.
+    ///
+    /// The keys and their order are part of the output format and must stay stable.
+    pub fn footer(&self) -> String {
+        format!(
+            "{} elapsed_ms={:.3} bytes_read={} bytes_written={} entries_visited={} fast_paths={}",
+            TIMINGS_FOOTER_PREFIX,
+            self.elapsed.as_secs_f64() * 1000.0,
+            self.bytes_read,
+            self.bytes_written,
+            self.entries_visited,
+            if self.fast_paths.is_empty() {
+                "none".to_string()
+            } else {
+                self.fast_paths.join(",")
+            }
+        )
+    }
+}
+
+/// Runs `future` with a fresh [`OperationStats`] context and returns its output together
+/// with the collected report.
+pub async fn instrument<F: Future>(future: F) -> (F::Output, OperationReport) {
+    let stats = Arc::new(OperationStats::default());
+    let started = Instant::now();
+    let output = CURRENT_STATS.scope(stats.clone(), future).await;
+    let report = stats.report(started.elapsed());
+    (output, report)
+}
+
+/// Returns a new operation id: a ULID, 26 characters of which the first 10 encode the current
+/// time in milliseconds, so ids sort by creation time.
+pub fn new_operation_id() -> String {
+    static SEQUENCE: AtomicU64 = AtomicU64::new(0);
+    let millis = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |elapsed| elapsed.as_millis() as u64);
+    let sequence = SEQUENCE.fetch_add(1, Ordering::Relaxed);
+    // `RandomState` is seeded randomly, which is enough entropy for correlating calls
+    let mut hasher = RandomState::new().build_hasher();
+    hasher.write_u64(sequence);
+    let random = ((hasher.finish() as u128) << 16) | (sequence & 0xffff) as u128;
+
+    let value = ((millis as u128 & 0xffff_ffff_ffff) << 80) | random;
+    (0..26)
+        .rev()
+        .map(|index| ULID_ALPHABET[((value >> (index * 5)) & 0x1f) as usize] as char)
+        .collect()
+}
@@ -0,0 +1,87 @@
+/// Options of [`sort_lines`].
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct SortOptions {
+    /// Compare the number each line starts with rather than its text.
+    pub numeric: bool,
+    /// Sort in descending order.
+    pub reverse: bool,
+    /// Keep only the first of identical lines.
+    pub unique: bool,
+}
+
+/// The result of [`sort_lines`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SortedLines {
+    pub content: String,
+    /// Lines in the sorted content.
+    pub lines: usize,
+    /// Identical lines removed with [`SortOptions::unique`].
+    pub duplicates_removed: usize,
+}
+
+/// Sorts the lines of `content` in byte order, or by their leading number with
+/// [`SortOptions::numeric`], as `sort -n` does: lines without a number sort first, and lines with
+/// the same number by their text. Lines are compared without their line break. The sorted content
+/// uses the line ending of `content`, and ends with a line break when it did.
+pub fn sort_lines(content: &str, options: &SortOptions) -> SortedLines {
+    let line_ending = if content.contains("\r\n") {
+        "\r\n"
+    } else {
+        "\n"
+    };
+    let mut lines: Vec<&str> = content.lines().collect();
+    if options.numeric {
+        let mut keyed: Vec<(Option<f64>, &str)> = lines
+            .into_iter()
+            .map(|line| (leading_number(line), line))
+            .collect();
+        keyed.sort_by(|(a, line_a), (b, line_b)| {
+            let order = match (a, b) {
+                (Some(a), Some(b)) => a.total_cmp(b),
+                (a, b) => a.is_some().cmp(&b.is_some()),
+            };
+            order.then_with(|| line_a.cmp(line_b))
+        });
+        lines = keyed.into_iter().map(|(_, line)| line).collect();
+    } else {
+        lines.sort_unstable();
+    }
+    if options.reverse {
+        lines.reverse();
+    }
+    let total = lines.len();
+    if options.unique {
+        lines.dedup();
+    }
+
+    let mut sorted = lines.join(line_ending);
+    if !lines.is_empty() && content.ends_with('\n') {
+        sorted.push_str(line_ending);
+    }
+    SortedLines {
+        content: sorted,
+        lines: lines.len(),
+        duplicates_removed: total - lines.len(),
+    }
+}
+
+/// The number `line` starts with after leading whitespace, such as `-1.5` in `-1.5 kg`.
+fn leading_number(line: &str) -> Option<f64> {
+    let line = line.trim_start();
+    let mut end = 0;
+    let mut seen_digit = false;
+    let mut seen_point = false;
+    for (index, c) in line.char_indices() {
+        match c {
+            '-' | '+' if index == 0 => {}
+            '0'..='9' => seen_digit = true,
+            '.' if !seen_point => seen_point = true,
+            _ => break,
+        }
+        end = index + c.len_utf8();
+    }
+    if !seen_digit {
+        return None;
+    }
+    line[..end].trim_end_matches('.').parse().ok()
+}
@@ -0,0 +1,94 @@
+use std::{
+    ffi::OsStr,
+    fmt::{self, Display},
+    fs, io,
+    path::Path,
+};
+
+use unicode_normalization::UnicodeNormalization;
+
+/// How a new name can be confused with an existing one.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum NameCollisionKind {
+    /// The names differ only in case, as `Readme.md` and `README.md`.
+    Case,
+    /// The names differ only in Unicode normalization form, as a composed (NFC) and a decomposed
+    /// (NFD) `café.txt`.
+    UnicodeNormalization,
+}
+
+/// A name that another entry of the same directory already takes on some file systems.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct NameCollision {
+    pub name: String,
+    /// The name of the existing entry.
+    pub existing: String,
+    pub kind: NameCollisionKind,
+}
+
+impl Display for NameCollision {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.kind {
+            NameCollisionKind::Case => write!(
+                f,
+                "'{}' differs only in case from the existing '{}', which is the same file on \
+                 case-insensitive file systems such as those of macOS and Windows",
+                self.name, self.existing
+            ),
+            NameCollisionKind::UnicodeNormalization => write!(
+                f,
+                "'{}' differs only in Unicode normalization (NFC/NFD) from the existing '{}', \
+                 which macOS and many tools treat as the same name",
+                self.name, self.existing
+            ),
+        }
+    }
+}
+
+/// Returns the entry of `dir` whose name can be confused with `name` without being equal to it,
+/// see [`NameCollisionKind`]. The directory listing is compared rather than the file system
+/// probed, so collisions are found on case-sensitive file systems too. `ignored` is a name not
+/// to compare, such as the source of a rename changing only the case. When an entry has exactly
+/// `name`, it is the destination itself and nothing collides. Blocking.
+pub fn find_name_collision(
+    dir: &Path,
+    name: &OsStr,
+    ignored: Option<&OsStr>,
+) -> io::Result<Option<NameCollision>> {
+    let entries = match fs::read_dir(dir) {
+        Ok(entries) => entries,
+        Err(err) if err.kind() == io::ErrorKind::NotFound => return Ok(None),
+        Err(err) => return Err(err),
+    };
+    let name = name.to_string_lossy();
+    let normalized: String = name.nfc().collect();
+    let folded = normalized.to_lowercase();
+    let mut collision = None;
+    for entry in entries {
+        let existing = entry?.file_name();
+        if ignored == Some(existing.as_os_str()) {
+            continue;
+        }
+        let existing = existing.to_string_lossy();
+        if existing == name {
+            return Ok(None);
+        }
+        if collision.is_some() {
+            continue;
+        }
+        let existing_normalized: String = existing.nfc().collect();
+        let kind = if existing_normalized == normalized {
+            NameCollisionKind::UnicodeNormalization
+        } else if existing_normalized.to_lowercase() == folded {
+            NameCollisionKind::Case
+        } else {
+            continue;
+        };
+        collision = Some(NameCollision {
+            name: name.to_string(),
+            existing: existing.to_string(),
+            kind,
+        });
+    }
+    Ok(collision)
+}
@@ -0,0 +1,175 @@
+use std::{
+    collections::HashMap,
+    path::{Path, PathBuf},
+    sync::{
+        Mutex,
+        atomic::{AtomicU64, Ordering},
+    },
+};
+
+use sha2::{Digest, Sha256};
+
+use crate::error::{ServiceError, ServiceResult};
+
+/// Maximum number of files that can be open for buffered editing at the same time.
+pub const MAX_OPEN_FILES: usize = 16;
+
+#[derive(Debug, Clone)]
+struct OpenFileEntry {
+    path: PathBuf,
+    content: String,
+    edits: u64,
+    opened_hash: String,
+}
+
+/// The buffered content of an open file, as seen by an edit.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct BufferSnapshot {
+    pub handle: String,
+    pub content: String,
+    /// Number of edits applied to the buffer when the snapshot was taken.
+    pub edits: u64,
+}
+
+/// The path and buffered content of an open file.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct OpenBuffer {
+    pub path: PathBuf,
+    pub content: String,
+    pub edits: u64,
+    /// Content hash of the file on disk when it was opened.
+    pub opened_hash: String,
+}
+
+/// A file opened for buffered editing.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct OpenedFile {
+    pub handle: String,
+    pub path: PathBuf,
+    /// Size of the file when it was opened, in bytes.
+    pub size: u64,
+}
+
+/// The outcome of closing an open file.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ClosedFile {
+    pub path: PathBuf,
+    /// Number of edits applied to the buffer while the file was open.
+    pub edits: u64,
+    /// Bytes written back to disk, or `None` when the buffer was discarded or never edited.
+    pub bytes_written: Option<u64>,
+}
+
+/// Files opened for a sequence of edits. Edits to an open file are applied to its in-memory
+/// buffer, which is only written back to disk when the file is closed.
+#[derive(Debug)]
+pub(crate) struct OpenFiles {
+    files: Mutex<HashMap<String, OpenFileEntry>>,
+    next_handle: AtomicU64,
+    max_open: usize,
+}
+
+impl Default for OpenFiles {
+    fn default() -> Self {
+        Self {
+            files: Mutex::new(HashMap::new()),
+            next_handle: AtomicU64::new(1),
+            max_open: MAX_OPEN_FILES,
+        }
+    }
+}
+
+impl OpenFiles {
+    /// Registers `path` with its current `content` and returns the new handle.
+    pub(crate) fn open(&self, path: PathBuf, content: String) -> ServiceResult<String> {
+        let mut files = self.lock()?;
+        if let Some((handle, _)) = files.iter().find(|(_, entry)| entry.path == path) {
+            return Err(ServiceError::FromString(format!(
+                "'{}' is already open as handle '{handle}'.",
+                path.display()
+            )));
+        }
+        if files.len() >= self.max_open {
+            return Err(ServiceError::FromString(format!(
+                "Too many open files (limit {}); close one before opening another.",
+                self.max_open
+            )));
+        }
+        let handle = format!("fh-{}", self.next_handle.fetch_add(1, Ordering::Relaxed));
+        files.insert(
+            handle.clone(),
+            OpenFileEntry {
+                path,
+                opened_hash: content_hash(&content),
+                content,
+                edits: 0,
+            },
+        );
+        Ok(handle)
+    }
+
+    /// Returns the buffer of `path` if it is open.
+    pub(crate) fn snapshot(&self, path: &Path) -> ServiceResult<Option<BufferSnapshot>> {
+        let files = self.lock()?;
+        Ok(files
+            .iter()
+            .find(|(_, entry)| entry.path == path)
+            .map(|(handle, entry)| BufferSnapshot {
+                handle: handle.clone(),
+                content: entry.content.clone(),
+                edits: entry.edits,
+            }))
+    }
+
+    /// Replaces the buffer taken in `snapshot` with `content`. Fails if the buffer was edited
+    /// or closed in the meantime.
+    pub(crate) fn update(&self, snapshot: &BufferSnapshot, content: String) -> ServiceResult<()> {
+        let mut files = self.lock()?;
+        match files.get_mut(&snapshot.handle) {
+            Some(entry) if entry.edits == snapshot.edits => {
+                entry.content = content;
+                entry.edits += 1;
+                Ok(())
+            }
+            Some(_) => Err(ServiceError::FromString(format!(
+                "Open file '{}' was edited concurrently; retry the edit.",
+                snapshot.handle
+            ))),
+            None => Err(unknown_handle(&snapshot.handle)),
+        }
+    }
+
+    /// Returns the buffer of `handle`.
+    pub(crate) fn buffer(&self, handle: &str) -> ServiceResult<OpenBuffer> {
+        let files = self.lock()?;
+        let entry = files.get(handle).ok_or_else(|| unknown_handle(handle))?;
+        Ok(OpenBuffer {
+            path: entry.path.clone(),
+            content: entry.content.clone(),
+            edits: entry.edits,
+            opened_hash: entry.opened_hash.clone(),
+        })
+    }
+
+    /// Removes `handle` from the session.
+    pub(crate) fn remove(&self, handle: &str) {
+        if let Ok(mut files) = self.files.lock() {
+            files.remove(handle);
+        }
+    }
+
+    fn lock(&self) -> ServiceResult<std::sync::MutexGuard<'_, HashMap<String, OpenFileEntry>>> {
+        self.files
+            .lock()
+            .map_err(|_| ServiceError::FromString("Open file table is poisoned.".to_string()))
+    }
+}
+
+/// Returns the hex-encoded SHA-256 hash of `content`.
+pub(crate) fn content_hash(content: &str) -> String {
+    format!("{:x}", Sha256::digest(content.as_bytes()))
+}
+
+fn unknown_handle(handle: &str) -> ServiceError {
+    ServiceError::FromString(format!("No open file with handle '{handle}'."))
+}
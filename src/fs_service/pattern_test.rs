@@ -0,0 +1,76 @@
+use std::fmt::Write;
+
+/// Samples taken from a `sample_path` when no limit is given.
+pub const DEFAULT_PATTERN_SAMPLES: usize = 20;
+/// Largest number of samples a pattern is tested against.
+pub const MAX_PATTERN_SAMPLES: usize = 1000;
+
+/// The outcome of testing a pattern against samples.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct PatternTest {
+    /// How the tools taking the pattern apply it.
+    pub interpretation: String,
+    /// Why the pattern does not compile, if it does not. No sample matches then.
+    pub error: Option<String>,
+    /// Each sample, with whether the pattern matches it.
+    pub samples: Vec<(String, bool)>,
+}
+
+impl PatternTest {
+    pub fn render_text(&self) -> String {
+        let mut output = format!("Interpretation: {}\n", self.interpretation);
+        if let Some(error) = &self.error {
+            let _ = writeln!(output, "The pattern does not compile:\n{error}");
+            return output;
+        }
+        let matched = self.samples.iter().filter(|(_, matched)| *matched).count();
+        let _ = writeln!(
+            output,
+            "Matches {matched} of {} samples:",
+            self.samples.len()
+        );
+        for (sample, matched) in &self.samples {
+            let _ = writeln!(
+                output,
+                "  {} {sample}",
+                if *matched { "[match]" } else { "[  -  ]" }
+            );
+        }
+        output
+    }
+}
+
+/// Finds unbalanced brackets and braces, which make a glob silently match nothing, and
+/// describes the first one with its position, as regex syntax errors are.
+pub fn glob_syntax_error(pattern: &str) -> Option<String> {
+    let mut open: Vec<(usize, char)> = vec![];
+    let mut chars = pattern.chars().enumerate();
+    while let Some((position, c)) = chars.next() {
+        let in_class = open.last().is_some_and(|(_, c)| *c == '[');
+        match c {
+            '\\' => {
+                chars.next();
+            }
+            '[' if !in_class => open.push((position, c)),
+            ']' if in_class => {
+                open.pop();
+            }
+            '{' if !in_class => open.push((position, c)),
+            '}' if !in_class => {
+                if open.pop().is_none() {
+                    return Some(describe_position(pattern, position, "unmatched '}'"));
+                }
+            }
+            _ => {}
+        }
+    }
+    open.first()
+        .map(|(position, c)| describe_position(pattern, *position, &format!("unclosed '{c}'")))
+}
+
+fn describe_position(pattern: &str, position: usize, message: &str) -> String {
+    format!(
+        "glob parse error:\n    {pattern}\n    {}^\nerror: {message} at position {position}",
+        " ".repeat(position)
+    )
+}
@@ -0,0 +1,107 @@
+use std::{
+    fs, io,
+    path::{Path, PathBuf},
+};
+
+use glob_match::glob_match;
+
+use super::utils::{exclude_glob, is_system_metadata_file};
+
+/// Placeholder file kept in otherwise empty directories so that git tracks them.
+pub const GITKEEP: &str = ".gitkeep";
+
+/// Options of [`plan_prune`].
+#[derive(Debug, Clone, Default)]
+pub struct PruneOptions {
+    /// Glob patterns, matched against paths relative to the root, of directories and files to
+    /// keep. A kept file keeps its directory. Patterns without `*` match any path containing them.
+    pub keep_patterns: Vec<String>,
+    /// Whether a directory holding only a `.gitkeep` counts as empty.
+    pub gitkeep_as_empty: bool,
+    /// Directories never removed, such as the allowed directories.
+    pub protected: Vec<PathBuf>,
+}
+
+/// The directories an empty-directory prune removes.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct PrunePlan {
+    /// Directories to remove, children before their parents.
+    pub directories: Vec<PathBuf>,
+    /// Placeholder and OS metadata files removed along with their directories.
+    pub placeholders: Vec<PathBuf>,
+    /// Combined size of `placeholders`.
+    pub bytes: u64,
+}
+
+/// Finds the directories below `root` that are empty, or only contain empty directories, OS
+/// metadata files (`.DS_Store`, `Thumbs.db`) and, with `gitkeep_as_empty`, `.gitkeep` files.
+/// `root` itself and symbolic links are never removed. Blocking.
+pub fn plan_prune(root: &Path, options: &PruneOptions) -> io::Result<PrunePlan> {
+    let mut plan = PrunePlan::default();
+    plan_directory(root, root, options, &mut plan)?;
+    Ok(plan)
+}
+
+/// Removes the placeholders, then the directories of `plan`. Blocking.
+pub fn execute_prune(plan: &PrunePlan) -> io::Result<()> {
+    for file in &plan.placeholders {
+        fs::remove_file(file)?;
+    }
+    for dir in &plan.directories {
+        fs::remove_dir(dir)?;
+    }
+    Ok(())
+}
+
+/// Adds the removable directories below `dir` to `plan` and returns whether `dir` is removable.
+fn plan_directory(
+    root: &Path,
+    dir: &Path,
+    options: &PruneOptions,
+    plan: &mut PrunePlan,
+) -> io::Result<bool> {
+    let mut entries = fs::read_dir(dir)?.collect::<io::Result<Vec<_>>>()?;
+    entries.sort_by_key(|entry| entry.file_name());
+
+    let mut empty = true;
+    let mut placeholders = vec![];
+    for entry in entries {
+        let path = entry.path();
+        let file_type = entry.file_type()?;
+        if file_type.is_dir() {
+            if !plan_directory(root, &path, options, plan)? {
+                empty = false;
+            }
+        } else if file_type.is_file()
+            && !is_kept(root, &path, options)
+            && (is_system_metadata_file(&entry.file_name())
+                || (options.gitkeep_as_empty && entry.file_name() == GITKEEP))
+        {
+            placeholders.push((path, entry.metadata()?.len()));
+        } else {
+            empty = false;
+        }
+    }
+
+    let removable = empty
+        && dir != root
+        && !options.protected.iter().any(|protected| protected == dir)
+        && !is_kept(root, dir, options);
+    if removable {
+        for (path, len) in placeholders {
+            plan.bytes += len;
+            plan.placeholders.push(path);
+        }
+        plan.directories.push(dir.to_path_buf());
+    }
+    Ok(removable)
+}
+
+fn is_kept(root: &Path, path: &Path, options: &PruneOptions) -> bool {
+    let relative_path = path.strip_prefix(root).unwrap_or(path);
+    let relative_path = relative_path.to_str().unwrap_or("");
+    options
+        .keep_patterns
+        .iter()
+        .any(|pattern| glob_match(&exclude_glob(pattern), relative_path))
+}
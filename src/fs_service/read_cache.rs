@@ -0,0 +1,101 @@
+use std::{
+    collections::HashMap,
+    path::{Path, PathBuf},
+    sync::Mutex,
+    time::{Duration, Instant, SystemTime},
+};
+
+use super::utils::format_system_time;
+
+/// How long a returned file is remembered for deduplicated reads.
+pub const READ_DEDUP_TTL: Duration = Duration::from_secs(10 * 60);
+
+/// Result of a deduplicated read.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DedupedRead {
+    /// The full file content.
+    Content(String),
+    /// A stub stating the file is unchanged since it was last returned.
+    Unchanged(String),
+}
+
+#[derive(Debug, Clone)]
+struct ReadCacheEntry {
+    modified: Option<SystemTime>,
+    size: u64,
+    sha256: String,
+    returned_at: SystemTime,
+    returned_instant: Instant,
+}
+
+/// Remembers the files returned by deduplicated reads during the session, so unchanged files
+/// can be answered with a short stub instead of their full content.
+#[derive(Debug)]
+pub(crate) struct ReadCache {
+    entries: Mutex<HashMap<PathBuf, ReadCacheEntry>>,
+    ttl: Duration,
+}
+
+impl Default for ReadCache {
+    fn default() -> Self {
+        Self {
+            entries: Mutex::new(HashMap::new()),
+            ttl: READ_DEDUP_TTL,
+        }
+    }
+}
+
+impl ReadCache {
+    /// Returns the stub for `path` if it was returned before and is unchanged since then.
+    pub(crate) fn unchanged_stub(
+        &self,
+        path: &Path,
+        modified: Option<SystemTime>,
+        size: u64,
+        sha256: &str,
+    ) -> Option<String> {
+        let entries = self.entries.lock().ok()?;
+        let entry = entries.get(path)?;
+        let unchanged = entry.returned_instant.elapsed() < self.ttl
+            && entry.modified == modified
+            && entry.size == size
+            && entry.sha256 == sha256;
+        unchanged.then(|| {
+            format!(
+                "unchanged since last read at {}, sha256={}",
+                format_system_time(entry.returned_at),
+                entry.sha256
+            )
+        })
+    }
+
+    /// Records that the full content of `path` was returned.
+    pub(crate) fn record(
+        &self,
+        path: PathBuf,
+        modified: Option<SystemTime>,
+        size: u64,
+        sha256: String,
+    ) {
+        if let Ok(mut entries) = self.entries.lock() {
+            entries.retain(|_, entry| entry.returned_instant.elapsed() < self.ttl);
+            entries.insert(
+                path,
+                ReadCacheEntry {
+                    modified,
+                    size,
+                    sha256,
+                    returned_at: SystemTime::now(),
+                    returned_instant: Instant::now(),
+                },
+            );
+        }
+    }
+
+    /// Forgets `path` and every path below it.
+    pub(crate) fn invalidate(&self, path: &Path) {
+        if let Ok(mut entries) = self.entries.lock() {
+            entries.retain(|cached, _| !cached.starts_with(path));
+        }
+    }
+}
@@ -0,0 +1,132 @@
+use std::{collections::HashMap, path::Path, sync::LazyLock};
+
+use glob_match::glob_match;
+use regex::{Captures, Regex};
+
+/// Prefix of the footer line appended to tool results when secret redaction is active.
+pub const REDACTIONS_FOOTER_PREFIX: &str = "[redactions]";
+
+/// Minimum Shannon entropy (bits per character) for a value assigned to a `token=`,
+/// `password=`, ... key to be considered a secret.
+const MIN_SECRET_ENTROPY: f64 = 3.0;
+/// Minimum length of a value assigned to a `token=`, `password=`, ... key to be considered
+/// a secret.
+const MIN_SECRET_LENGTH: usize = 12;
+
+/// Controls redaction of well-known secrets in read and search output.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct RedactionConfig {
+    /// Redact output unless a call opts out.
+    pub enabled: bool,
+    /// Glob patterns, matched against the full path, of files whose content is never redacted.
+    pub exclude_globs: Vec<String>,
+}
+
+impl RedactionConfig {
+    /// Whether output read from `path` should be redacted, given the per-call override.
+    pub fn applies_to(&self, path: &Path, requested: Option<bool>) -> bool {
+        if !requested.unwrap_or(self.enabled) {
+            return false;
+        }
+        let path = path.to_string_lossy();
+        !self
+            .exclude_globs
+            .iter()
+            .any(|pattern| glob_match(pattern, &path))
+    }
+}
+
+struct SecretPattern {
+    kind: &'static str,
+    regex: Regex,
+    /// Only this capture group is redacted, and only if it looks random enough.
+    value_group: Option<usize>,
+}
+
+static SECRET_PATTERNS: LazyLock<Vec<SecretPattern>> = LazyLock::new(|| {
+    let pattern = |kind, regex: &str, value_group| SecretPattern {
+        kind,
+        regex: Regex::new(regex).expect("valid secret pattern"),
+        value_group,
+    };
+    vec![
+        // an unterminated block (e.g. a truncated read) is redacted to the end of the text
+        pattern(
+            "private_key",
+            r"-----BEGIN [A-Z0-9 ]*PRIVATE KEY-----(?s:.*?)(?:-----END [A-Z0-9 ]*PRIVATE KEY-----|\z)",
+            None,
+        ),
+        pattern("aws_key", r"\b(?:AKIA|ASIA)[0-9A-Z]{16}\b", None),
+        pattern(
+            "github_token",
+            r"\b(?:gh[pousr]_[A-Za-z0-9]{36,}|github_pat_[A-Za-z0-9_]{22,})\b",
+            None,
+        ),
+        pattern(
+            "secret",
+            r#"(?i)\b(?:[a-z0-9_-]*(?:token|password|passwd|secret|api[_-]?key))["']?\s*[:=]\s*["']?([^\s"',;]+)"#,
+            Some(1),
+        ),
+    ]
+});
+
+/// Replaces well-known secrets in `text` with `«redacted:<kind>»` markers and returns the
+/// redacted text together with the number of replacements.
+pub fn redact_secrets(text: &str) -> (String, usize) {
+    let mut redacted = text.to_string();
+    let mut count = 0;
+
+    for pattern in SECRET_PATTERNS.iter() {
+        let marker = format!("«redacted:{}»", pattern.kind);
+        let replaced = pattern.regex.replace_all(&redacted, |captures: &Captures| {
+            let whole = captures.get(0).expect("group 0 is always present");
+            let Some(group) = pattern.value_group else {
+                count += 1;
+                return marker.clone();
+            };
+            match captures.get(group) {
+                Some(value) if looks_random(value.as_str()) => {
+                    count += 1;
+                    let start = value.start() - whole.start();
+                    let end = value.end() - whole.start();
+                    format!(
+                        "{}{}{}",
+                        &whole.as_str()[..start],
+                        marker,
+                        &whole.as_str()[end..]
+                    )
+                }
+                _ => whole.as_str().to_string(),
+            }
+        });
+        redacted = replaced.into_owned();
+    }
+
+    (redacted, count)
+}
+
+/// Renders the footer line reporting the number of redactions.
+pub fn redactions_footer(count: usize) -> String {
+    format!("{REDACTIONS_FOOTER_PREFIX} count={count}")
+}
+
+fn looks_random(value: &str) -> bool {
+    value.chars().count() >= MIN_SECRET_LENGTH
+        && !value.starts_with('«')
+        && shannon_entropy(value) >= MIN_SECRET_ENTROPY
+}
+
+fn shannon_entropy(value: &str) -> f64 {
+    let mut frequencies: HashMap<char, usize> = HashMap::new();
+    for c in value.chars() {
+        *frequencies.entry(c).or_default() += 1;
+    }
+    let length = value.chars().count() as f64;
+    frequencies
+        .values()
+        .map(|&frequency| {
+            let probability = frequency as f64 / length;
+            -probability * probability.log2()
+        })
+        .sum()
+}
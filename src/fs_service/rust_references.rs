@@ -0,0 +1,128 @@
+use std::path::{Path, PathBuf};
+
+use regex::Regex;
+
+/// A file whose content changes when a Rust module is moved.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ReferenceUpdate {
+    /// Current location of the file. For the moved module itself this is the source path.
+    pub path: PathBuf,
+    pub original: String,
+    pub updated: String,
+}
+
+/// The `mod`/`use` edits needed to keep a crate compiling after moving one of its modules.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ReferenceUpdatePlan {
+    /// Module path before the move, e.g. `crate::net::client`.
+    pub old_module: Option<String>,
+    /// Module path after the move.
+    pub new_module: Option<String>,
+    pub updates: Vec<ReferenceUpdate>,
+    /// Parts of the update that could not be done automatically.
+    pub notes: Vec<String>,
+}
+
+/// Returns the `src` directory of the crate containing `path`, i.e. `<dir>/src` for the nearest
+/// ancestor `<dir>` holding a `Cargo.toml`.
+pub fn crate_src_dir(path: &Path) -> Option<PathBuf> {
+    path.ancestors()
+        .skip(1)
+        .find(|dir| dir.join("Cargo.toml").is_file())
+        .map(|dir| dir.join("src"))
+        .filter(|src_dir| path.starts_with(src_dir))
+}
+
+/// Returns the module path of a source file relative to the crate's `src` directory, e.g.
+/// `["net", "client"]` for `src/net/client.rs` or `src/net/client/mod.rs`. Crate roots
+/// (`lib.rs`, `main.rs`) and non-`.rs` files have no module path.
+pub fn module_path(src_dir: &Path, file: &Path) -> Option<Vec<String>> {
+    if file.extension()? != "rs" {
+        return None;
+    }
+    let relative = file.strip_prefix(src_dir).ok()?.with_extension("");
+    let mut segments: Vec<String> = relative
+        .components()
+        .map(|component| component.as_os_str().to_str().map(str::to_string))
+        .collect::<Option<_>>()?;
+    if segments.last().is_some_and(|last| last == "mod") {
+        segments.pop();
+    }
+    if segments.is_empty()
+        || (segments.len() == 1 && (segments[0] == "lib" || segments[0] == "main"))
+    {
+        return None;
+    }
+    let is_identifier = |segment: &String| {
+        segment
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '_')
+            && !segment.starts_with(|c: char| c.is_ascii_digit())
+    };
+    segments.iter().all(is_identifier).then_some(segments)
+}
+
+/// Returns the file declaring the module at `module`, i.e. the file of its parent module.
+pub fn parent_module_file(src_dir: &Path, module: &[String]) -> Option<PathBuf> {
+    let (_, parent) = module.split_last()?;
+    let candidates = if parent.is_empty() {
+        vec![src_dir.join("lib.rs"), src_dir.join("main.rs")]
+    } else {
+        let parent_path: PathBuf = parent.iter().collect();
+        vec![
+            src_dir.join(&parent_path).with_extension("rs"),
+            src_dir.join(&parent_path).join("mod.rs"),
+        ]
+    };
+    candidates.into_iter().find(|candidate| candidate.is_file())
+}
+
+/// Replaces `crate::<old>` paths with `crate::<new>`, leaving longer paths that merely share
+/// the prefix (`crate::net::client_pool` for `net::client`) untouched.
+pub fn rewrite_crate_paths(content: &str, old: &[String], new: &[String]) -> String {
+    let pattern = format!(r"\bcrate::{}\b", regex::escape(&old.join("::")));
+    let regex = Regex::new(&pattern).expect("valid module path pattern");
+    regex
+        .replace_all(content, format!("crate::{}", new.join("::")).as_str())
+        .into_owned()
+}
+
+/// Removes the `mod name;` declaration from `content` and returns the new content together
+/// with the declaration's visibility prefix (e.g. `pub ` or `pub(crate) `).
+pub fn remove_mod_declaration(content: &str, name: &str) -> Option<(String, String)> {
+    let pattern = format!(
+        r"(?m)^[ \t]*((?:pub(?:\([^)]*\))?[ \t]+)?)mod[ \t]+{}[ \t]*;[ \t]*(?:\r?\n)?",
+        regex::escape(name)
+    );
+    let regex = Regex::new(&pattern).expect("valid mod declaration pattern");
+    let captures = regex.captures(content)?;
+    let visibility = captures[1].split_whitespace().collect::<Vec<_>>().join(" ");
+    let visibility = if visibility.is_empty() {
+        visibility
+    } else {
+        format!("{visibility} ")
+    };
+    Some((regex.replace(content, "").into_owned(), visibility))
+}
+
+/// Adds a `mod name;` declaration after the last existing `mod` declaration, or at the top of
+/// the file when it has none.
+pub fn add_mod_declaration(content: &str, name: &str, visibility: &str) -> String {
+    let declaration = format!("{visibility}mod {name};\n");
+    let regex = Regex::new(r"(?m)^[ \t]*(?:pub(?:\([^)]*\))?[ \t]+)?mod[ \t]+\w+[ \t]*;[^\n]*\n?")
+        .expect("valid mod declaration pattern");
+    match regex.find_iter(content).last() {
+        Some(last) => {
+            let mut updated = content.to_string();
+            let at = last.end();
+            if !content[..at].ends_with('\n') {
+                updated.insert(at, '\n');
+                updated.insert_str(at + 1, &declaration);
+            } else {
+                updated.insert_str(at, &declaration);
+            }
+            updated
+        }
+        None => format!("{declaration}{content}"),
+    }
+}
@@ -0,0 +1,339 @@
+use std::{fmt, sync::LazyLock};
+
+use regex::Regex;
+
+/// Qualifiers that may precede `fn` in a Rust function signature.
+const FUNCTION_QUALIFIERS: &[&str] = &["default", "const", "async", "unsafe", "safe", "extern"];
+
+/// Lines joined at most when looking for the end of a signature.
+const MAX_SIGNATURE_LINES: usize = 50;
+
+/// Matches the `fn` keyword and the function name.
+static FUNCTION_NAME: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r"(?:^|\s)fn\s+((?:r#)?[A-Za-z_]\w*)").expect("valid function name pattern")
+});
+
+/// Matches the attributes and the visibility that may precede the qualifiers of a function.
+static ATTRIBUTES_AND_VISIBILITY: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r"^(?:#\[[^\]]*\]\s*)*(?:pub(?:\s*\([^)]*\))?(?:\s+|$))?")
+        .expect("valid visibility pattern")
+});
+
+/// Matches the shorthand receivers `self`, `mut self`, `&self`, `&'a mut self`, ...
+static SHORTHAND_RECEIVER: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r"^(?:(&(?:'\w+ )?(?:mut )?)|(mut ))?self$").expect("valid receiver pattern")
+});
+
+/// A parameter of a function signature.
+#[derive(Debug, Clone, PartialEq, Eq, ::serde::Serialize)]
+pub struct SignatureParameter {
+    /// The binding pattern, e.g. `name`, `mut buffer` or `(x, y)`. Receivers are `self` or
+    /// `mut self`.
+    pub name: String,
+    /// The type, e.g. `&str` or `impl Fn(u8) -> u8`. Shorthand receivers get their explicit
+    /// type: `&self` is `&Self`.
+    #[serde(rename = "type")]
+    pub type_name: String,
+}
+
+impl SignatureParameter {
+    fn is_receiver(&self) -> bool {
+        matches!(self.name.as_str(), "self" | "mut self")
+    }
+}
+
+impl fmt::Display for SignatureParameter {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        // Shorthand receivers are rendered as written: `&mut self` rather than `self: &mut Self`
+        if self.is_receiver()
+            && let Some(reference) = self.type_name.strip_suffix("Self")
+            && (reference.is_empty() || reference.starts_with('&'))
+        {
+            return write!(f, "{reference}{}", self.name);
+        }
+        write!(f, "{}: {}", self.name, self.type_name)
+    }
+}
+
+/// The signature of a Rust function, parsed from its header.
+#[derive(Debug, Clone, Default, PartialEq, Eq, ::serde::Serialize)]
+pub struct FunctionSignature {
+    /// Whether the header could be parsed. When it could not (e.g. in macro templates), only
+    /// `text` is set.
+    pub parsed: bool,
+    /// The signature on one line with normalized whitespace, e.g.
+    /// `async fn fetch(url: &str, retries: usize) -> Result<(), Error>`, without visibility.
+    /// The raw header, on one line, when it could not be parsed.
+    pub text: String,
+    /// `const`, `async`, `unsafe`, `extern "C"`, ... in order.
+    pub qualifiers: Vec<String>,
+    /// Lifetime, type and const parameters, e.g. `'a`, `T: Display` or `const N: usize`.
+    pub generics: Vec<String>,
+    pub parameters: Vec<SignatureParameter>,
+    pub return_type: Option<String>,
+    /// Predicates of the `where` clause, e.g. `T: Clone + Send`.
+    pub where_predicates: Vec<String>,
+}
+
+impl fmt::Display for FunctionSignature {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.text)
+    }
+}
+
+/// Returns the header of the function whose `fn` line is `lines[start]`: the text up to the
+/// opening brace of its body, or its terminating `;`, joined onto one line.
+pub fn function_header(lines: &[&str], start: usize) -> String {
+    let mut depth = 0usize;
+    let mut header = String::new();
+    for line in lines.iter().skip(start).take(MAX_SIGNATURE_LINES) {
+        let line = line.find("//").map_or(*line, |position| &line[..position]);
+        for c in line.chars() {
+            match c {
+                '(' | '[' => depth += 1,
+                ')' | ']' => depth = depth.saturating_sub(1),
+                '{' | ';' if depth == 0 => return normalize(&header),
+                _ => {}
+            }
+            header.push(c);
+        }
+        header.push(' ');
+    }
+    normalize(&header)
+}
+
+/// Parses a function header as returned by [`function_header`]. Headers that cannot be parsed
+/// are kept as text, with `parsed` unset.
+pub fn parse_function_signature(header: &str) -> FunctionSignature {
+    let header = normalize(header);
+    parse(&header).unwrap_or(FunctionSignature {
+        parsed: false,
+        text: header,
+        ..Default::default()
+    })
+}
+
+fn parse(header: &str) -> Option<FunctionSignature> {
+    let captures = FUNCTION_NAME.captures(header)?;
+    let keyword = captures.get(0)?;
+    let name = captures.get(1)?.as_str();
+    let qualifiers = parse_qualifiers(&header[..keyword.start()])?;
+
+    let mut rest = header[keyword.end()..].trim_start();
+    let mut generics = vec![];
+    if rest.starts_with('<') {
+        let close = matching_close(rest)?;
+        generics = split_top_level(&rest[1..close]);
+        rest = rest[close + 1..].trim_start();
+    }
+    if !rest.starts_with('(') {
+        return None;
+    }
+    let close = matching_close(rest)?;
+    let parameters = split_top_level(&rest[1..close])
+        .iter()
+        .map(|parameter| parse_parameter(parameter))
+        .collect::<Option<Vec<_>>>()?;
+    rest = rest[close + 1..].trim_start();
+
+    let (return_part, where_part) = match find_top_level_word(rest, "where") {
+        Some(position) => (rest[..position].trim(), Some(&rest[position + 5..])),
+        None => (rest.trim(), None),
+    };
+    let return_type = match return_part.strip_prefix("->") {
+        Some(return_type) if !return_type.trim().is_empty() => Some(return_type.trim().to_string()),
+        None if return_part.is_empty() => None,
+        _ => return None,
+    };
+    let where_predicates = where_part.map(split_top_level).unwrap_or_default();
+
+    let mut signature = FunctionSignature {
+        parsed: true,
+        text: String::new(),
+        qualifiers,
+        generics,
+        parameters,
+        return_type,
+        where_predicates,
+    };
+    signature.text = render(&signature, name);
+    Some(signature)
+}
+
+/// Parses what precedes `fn`: optional attributes and visibility, then qualifiers.
+fn parse_qualifiers(prefix: &str) -> Option<Vec<String>> {
+    let prefix = prefix.trim();
+    let prefix = &prefix[ATTRIBUTES_AND_VISIBILITY
+        .find(prefix)
+        .map_or(0, |m| m.end())..];
+
+    let mut qualifiers: Vec<String> = vec![];
+    for word in prefix.split_whitespace() {
+        match qualifiers.last_mut() {
+            // The ABI of `extern "C"`
+            Some(last) if last == "extern" && word.starts_with('"') => {
+                last.push(' ');
+                last.push_str(word);
+            }
+            _ if FUNCTION_QUALIFIERS.contains(&word) => qualifiers.push(word.to_string()),
+            _ => return None,
+        }
+    }
+    Some(qualifiers)
+}
+
+fn parse_parameter(parameter: &str) -> Option<SignatureParameter> {
+    if let Some(captures) = SHORTHAND_RECEIVER.captures(parameter) {
+        return Some(match (captures.get(1), captures.get(2)) {
+            (Some(reference), _) => SignatureParameter {
+                name: "self".to_string(),
+                type_name: format!("{}Self", reference.as_str()),
+            },
+            (_, Some(_)) => SignatureParameter {
+                name: "mut self".to_string(),
+                type_name: "Self".to_string(),
+            },
+            _ => SignatureParameter {
+                name: "self".to_string(),
+                type_name: "Self".to_string(),
+            },
+        });
+    }
+    let colon = find_top_level_colon(parameter)?;
+    let name = parameter[..colon].trim();
+    let type_name = parameter[colon + 1..].trim();
+    if name.is_empty() || type_name.is_empty() || name.contains('$') || type_name.contains('$') {
+        return None;
+    }
+    Some(SignatureParameter {
+        name: name.to_string(),
+        type_name: type_name.to_string(),
+    })
+}
+
+fn render(signature: &FunctionSignature, name: &str) -> String {
+    let mut text = String::new();
+    for qualifier in &signature.qualifiers {
+        text.push_str(qualifier);
+        text.push(' ');
+    }
+    text.push_str("fn ");
+    text.push_str(name);
+    if !signature.generics.is_empty() {
+        text.push_str(&format!("<{}>", signature.generics.join(", ")));
+    }
+    let parameters = signature
+        .parameters
+        .iter()
+        .map(ToString::to_string)
+        .collect::<Vec<_>>();
+    text.push_str(&format!("({})", parameters.join(", ")));
+    if let Some(return_type) = &signature.return_type {
+        text.push_str(&format!(" -> {return_type}"));
+    }
+    if !signature.where_predicates.is_empty() {
+        text.push_str(&format!(" where {}", signature.where_predicates.join(", ")));
+    }
+    text
+}
+
+/// Tracks the nesting of brackets, treating the `>` of `->` and `=>` as an arrow.
+#[derive(Default)]
+struct Depth {
+    depth: usize,
+    previous: char,
+}
+
+impl Depth {
+    /// Updates the depth with `c` and returns the depth before it.
+    fn update(&mut self, c: char) -> usize {
+        let before = self.depth;
+        match c {
+            '(' | '[' | '{' | '<' => self.depth += 1,
+            '>' if self.previous == '-' || self.previous == '=' => {}
+            ')' | ']' | '}' | '>' => self.depth = self.depth.saturating_sub(1),
+            _ => {}
+        }
+        self.previous = c;
+        before
+    }
+}
+
+/// Returns the position of the bracket closing the one `text` starts with.
+fn matching_close(text: &str) -> Option<usize> {
+    let mut depth = Depth::default();
+    for (position, c) in text.char_indices() {
+        depth.update(c);
+        if depth.depth == 0 {
+            return Some(position);
+        }
+    }
+    None
+}
+
+/// Splits `text` at the commas outside brackets, dropping empty parts such as the one after a
+/// trailing comma.
+fn split_top_level(text: &str) -> Vec<String> {
+    let mut parts = vec![];
+    let mut depth = Depth::default();
+    let mut start = 0;
+    for (position, c) in text.char_indices() {
+        if depth.update(c) == 0 && c == ',' {
+            parts.push(&text[start..position]);
+            start = position + 1;
+        }
+    }
+    parts.push(&text[start..]);
+    parts
+        .into_iter()
+        .map(str::trim)
+        .filter(|part| !part.is_empty())
+        .map(ToString::to_string)
+        .collect()
+}
+
+/// Returns the position of the first `:` outside brackets that is not part of a `::`.
+fn find_top_level_colon(text: &str) -> Option<usize> {
+    let bytes = text.as_bytes();
+    let mut depth = Depth::default();
+    for (position, c) in text.char_indices() {
+        if depth.update(c) == 0
+            && c == ':'
+            && bytes.get(position + 1) != Some(&b':')
+            && (position == 0 || bytes[position - 1] != b':')
+        {
+            return Some(position);
+        }
+    }
+    None
+}
+
+/// Returns the position of `word` as a whole word outside brackets.
+fn find_top_level_word(text: &str, word: &str) -> Option<usize> {
+    let is_ident = |c: Option<char>| c.is_some_and(|c| c.is_alphanumeric() || c == '_');
+    let mut depth = Depth::default();
+    for (position, c) in text.char_indices() {
+        if depth.update(c) == 0
+            && text[position..].starts_with(word)
+            && !is_ident(text[..position].chars().next_back())
+            && !is_ident(text[position + word.len()..].chars().next())
+        {
+            return Some(position);
+        }
+    }
+    None
+}
+
+/// Collapses whitespace and drops the spaces just inside brackets and before commas.
+fn normalize(text: &str) -> String {
+    text.split_whitespace()
+        .collect::<Vec<_>>()
+        .join(" ")
+        .replace("( ", "(")
+        .replace(" )", ")")
+        .replace("[ ", "[")
+        .replace(" ]", "]")
+        .replace("< ", "<")
+        .replace(" >", ">")
+        .replace(" ,", ",")
+}
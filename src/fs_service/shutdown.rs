@@ -0,0 +1,107 @@
+use std::{
+    sync::atomic::{AtomicU64, Ordering},
+    time::Duration,
+};
+
+use tokio::sync::{Notify, watch};
+use tokio_util::sync::CancellationToken;
+
+use crate::error::{ServiceError, ServiceResult};
+
+/// Outcome of [`FileSystemService::shutdown`](super::FileSystemService::shutdown).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ShutdownResult {
+    /// Operations that were in flight when shutdown started and completed before the timeout.
+    pub drained_operations: u64,
+    /// Operations still running when the timeout elapsed; they are cancelled.
+    pub timed_out_operations: u64,
+}
+
+/// Keeps track of in-flight operations so the service can reject new ones and drain the
+/// running ones on shutdown.
+#[derive(Debug)]
+pub(crate) struct OperationTracker {
+    shutdown_tx: watch::Sender<bool>,
+    in_flight: AtomicU64,
+    idle: Notify,
+    cancellation: CancellationToken,
+}
+
+impl Default for OperationTracker {
+    fn default() -> Self {
+        Self {
+            shutdown_tx: watch::Sender::new(false),
+            in_flight: AtomicU64::new(0),
+            idle: Notify::new(),
+            cancellation: CancellationToken::new(),
+        }
+    }
+}
+
+/// Marks an operation as in flight until dropped.
+#[derive(Debug)]
+pub struct OperationGuard<'a> {
+    tracker: &'a OperationTracker,
+}
+
+impl Drop for OperationGuard<'_> {
+    fn drop(&mut self) {
+        if self.tracker.in_flight.fetch_sub(1, Ordering::SeqCst) == 1 {
+            self.tracker.idle.notify_waiters();
+        }
+    }
+}
+
+impl OperationTracker {
+    pub(crate) fn begin(&self) -> ServiceResult<OperationGuard<'_>> {
+        // Register first, so a concurrent shutdown either sees this operation or rejects it
+        self.in_flight.fetch_add(1, Ordering::SeqCst);
+        let guard = OperationGuard { tracker: self };
+        if self.is_shutting_down() {
+            return Err(ServiceError::ShuttingDown);
+        }
+        Ok(guard)
+    }
+
+    pub(crate) fn is_shutting_down(&self) -> bool {
+        *self.shutdown_tx.borrow()
+    }
+
+    pub(crate) fn subscribe(&self) -> watch::Receiver<bool> {
+        self.shutdown_tx.subscribe()
+    }
+
+    pub(crate) fn cancellation_token(&self) -> CancellationToken {
+        self.cancellation.clone()
+    }
+
+    pub(crate) async fn shutdown(&self, timeout: Duration) -> ShutdownResult {
+        self.shutdown_tx.send_replace(true);
+        let pending = self.in_flight.load(Ordering::SeqCst);
+
+        let remaining = match tokio::time::timeout(timeout, self.wait_idle()).await {
+            Ok(()) => 0,
+            Err(_) => {
+                self.cancellation.cancel();
+                self.in_flight.load(Ordering::SeqCst)
+            }
+        };
+
+        ShutdownResult {
+            drained_operations: pending.saturating_sub(remaining),
+            timed_out_operations: remaining,
+        }
+    }
+
+    async fn wait_idle(&self) {
+        loop {
+            let notified = self.idle.notified();
+            tokio::pin!(notified);
+            notified.as_mut().enable();
+            if self.in_flight.load(Ordering::SeqCst) == 0 {
+                return;
+            }
+            notified.await;
+        }
+    }
+}
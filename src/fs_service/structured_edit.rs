@@ -0,0 +1,363 @@
+use serde::Serialize;
+use serde_json::{Map, Value, ser::PrettyFormatter};
+use toml_edit::{DocumentMut, InlineTable, Item, Table};
+
+use crate::{
+    error::{ServiceError, ServiceResult},
+    tools::{StructuredEditKind, StructuredEditOperation, StructuredFormat},
+};
+
+/// Splits a JSON Pointer (`/a/b/0`) or a dotted path (`a.b.0`) into its segments. The empty
+/// pointer addresses the whole document.
+pub fn parse_pointer(pointer: &str) -> ServiceResult<Vec<String>> {
+    if pointer.is_empty() {
+        return Ok(vec![]);
+    }
+    if let Some(pointer) = pointer.strip_prefix('/') {
+        return Ok(pointer
+            .split('/')
+            .map(|segment| segment.replace("~1", "/").replace("~0", "~"))
+            .collect());
+    }
+    let segments: Vec<String> = pointer.split('.').map(String::from).collect();
+    if segments.iter().any(String::is_empty) {
+        return Err(invalid_pointer(pointer, "it contains an empty segment"));
+    }
+    Ok(segments)
+}
+
+/// Applies `edits` to `content`, a document in `format`, and serializes it back. Fails without
+/// changing anything if the document does not parse or an edit cannot be applied.
+pub fn apply_structured_edits(
+    content: &str,
+    format: StructuredFormat,
+    edits: &[StructuredEditOperation],
+) -> ServiceResult<String> {
+    match format {
+        StructuredFormat::Json => {
+            let mut document: Value = serde_json::from_str(content).map_err(|err| {
+                ServiceError::FromString(format!("Failed to parse the file as JSON: {err}"))
+            })?;
+            for edit in edits {
+                apply_value_edit(&mut document, edit)?;
+            }
+            serialize_json(&document, content)
+        }
+        StructuredFormat::Yaml => {
+            let mut document: Value = serde_yaml::from_str(content).map_err(|err| {
+                ServiceError::FromString(format!("Failed to parse the file as YAML: {err}"))
+            })?;
+            for edit in edits {
+                apply_value_edit(&mut document, edit)?;
+            }
+            serde_yaml::to_string(&document)
+                .map_err(|err| ServiceError::FromString(err.to_string()))
+        }
+        StructuredFormat::Toml => {
+            let mut document: DocumentMut = content.parse().map_err(|err| {
+                ServiceError::FromString(format!("Failed to parse the file as TOML: {err}"))
+            })?;
+            for edit in edits {
+                apply_toml_edit(&mut document, edit)?;
+            }
+            Ok(document.to_string())
+        }
+    }
+}
+
+fn invalid_pointer(pointer: &str, reason: &str) -> ServiceError {
+    ServiceError::FromString(format!("Invalid pointer '{pointer}': {reason}."))
+}
+
+fn edit_value(edit: &StructuredEditOperation) -> ServiceResult<&Value> {
+    edit.value.as_ref().map(|value| &value.0).ok_or_else(|| {
+        ServiceError::FromString(format!(
+            "The '{}' operation at '{}' requires a value.",
+            match edit.op {
+                StructuredEditKind::Set => "set",
+                StructuredEditKind::Remove => "remove",
+                StructuredEditKind::Append => "append",
+            },
+            edit.pointer
+        ))
+    })
+}
+
+/// Parses an array index. With `allow_end`, `-` and the length address the slot past the end.
+fn parse_index(segment: &str, len: usize, allow_end: bool) -> Result<usize, String> {
+    if allow_end && segment == "-" {
+        return Ok(len);
+    }
+    let index: usize = segment
+        .parse()
+        .map_err(|_| format!("'{segment}' is not an array index"))?;
+    if index < len || (allow_end && index == len) {
+        Ok(index)
+    } else {
+        Err(format!(
+            "index {index} is out of bounds for an array of length {len}"
+        ))
+    }
+}
+
+fn json_child<'a>(
+    value: &'a mut Value,
+    segment: &str,
+    create: bool,
+) -> Result<&'a mut Value, String> {
+    match value {
+        Value::Object(map) => {
+            if create && !map.contains_key(segment) {
+                map.insert(segment.to_string(), Value::Object(Map::new()));
+            }
+            map.get_mut(segment)
+                .ok_or_else(|| format!("'{segment}' does not exist"))
+        }
+        Value::Array(items) => {
+            let index = parse_index(segment, items.len(), false)?;
+            Ok(&mut items[index])
+        }
+        _ => Err(format!("cannot look up '{segment}' in a scalar value")),
+    }
+}
+
+/// Applies an edit to a JSON or YAML document.
+fn apply_value_edit(document: &mut Value, edit: &StructuredEditOperation) -> ServiceResult<()> {
+    let segments = parse_pointer(&edit.pointer)?;
+    let fail = |reason: String| invalid_pointer(&edit.pointer, &reason);
+    let create = edit.op == StructuredEditKind::Set;
+
+    if edit.op == StructuredEditKind::Append {
+        let mut target = &mut *document;
+        for segment in &segments {
+            target = json_child(target, segment, false).map_err(fail)?;
+        }
+        let Value::Array(items) = target else {
+            return Err(fail("the target is not an array".to_string()));
+        };
+        items.push(edit_value(edit)?.clone());
+        return Ok(());
+    }
+
+    let Some((last, parents)) = segments.split_last() else {
+        return match edit.op {
+            StructuredEditKind::Set => {
+                *document = edit_value(edit)?.clone();
+                Ok(())
+            }
+            _ => Err(fail("the whole document cannot be removed".to_string())),
+        };
+    };
+    let mut parent = &mut *document;
+    for segment in parents {
+        parent = json_child(parent, segment, create).map_err(fail)?;
+    }
+
+    match (parent, edit.op) {
+        (Value::Object(map), StructuredEditKind::Set) => {
+            map.insert(last.clone(), edit_value(edit)?.clone());
+        }
+        (Value::Object(map), _) => {
+            map.shift_remove(last)
+                .ok_or_else(|| fail(format!("'{last}' does not exist")))?;
+        }
+        (Value::Array(items), StructuredEditKind::Set) => {
+            let index = parse_index(last, items.len(), true).map_err(fail)?;
+            let value = edit_value(edit)?.clone();
+            if index == items.len() {
+                items.push(value);
+            } else {
+                items[index] = value;
+            }
+        }
+        (Value::Array(items), _) => {
+            let index = parse_index(last, items.len(), false).map_err(fail)?;
+            items.remove(index);
+        }
+        _ => return Err(fail(format!("cannot look up '{last}' in a scalar value"))),
+    }
+    Ok(())
+}
+
+/// Serializes a JSON document with the indentation and final newline of `original`.
+/// Documents without an indented line (one-line or minified) stay compact.
+fn serialize_json(document: &Value, original: &str) -> ServiceResult<String> {
+    let indent = original.lines().find_map(|line| {
+        let content = line.trim_start();
+        (!content.is_empty() && content.len() < line.len())
+            .then(|| &line[..line.len() - content.len()])
+    });
+    let mut output = match indent {
+        Some(indent) => {
+            let mut output = Vec::new();
+            let mut serializer = serde_json::Serializer::with_formatter(
+                &mut output,
+                PrettyFormatter::with_indent(indent.as_bytes()),
+            );
+            document.serialize(&mut serializer)?;
+            String::from_utf8(output).map_err(|err| ServiceError::FromString(err.to_string()))?
+        }
+        None => serde_json::to_string(document)?,
+    };
+    if original.ends_with('\n') {
+        output.push('\n');
+    }
+    Ok(output)
+}
+
+/// Converts a JSON value to a TOML value. Objects become inline tables.
+fn toml_value(value: &Value) -> Result<toml_edit::Value, String> {
+    Ok(match value {
+        Value::Null => return Err("TOML has no null value".to_string()),
+        Value::Bool(value) => (*value).into(),
+        Value::Number(number) => match number.as_i64() {
+            Some(integer) => integer.into(),
+            None => number.as_f64().unwrap_or_default().into(),
+        },
+        Value::String(value) => value.as_str().into(),
+        Value::Array(items) => items
+            .iter()
+            .map(toml_value)
+            .collect::<Result<toml_edit::Array, _>>()?
+            .into(),
+        Value::Object(map) => {
+            let mut table = InlineTable::new();
+            for (key, value) in map {
+                table.insert(key, toml_value(value)?);
+            }
+            table.into()
+        }
+    })
+}
+
+fn toml_child<'a>(item: &'a mut Item, segment: &str, create: bool) -> Result<&'a mut Item, String> {
+    if item.is_array() || item.is_array_of_tables() {
+        let len = item
+            .as_array()
+            .map(|array| array.len())
+            .or_else(|| item.as_array_of_tables().map(|tables| tables.len()))
+            .unwrap_or_default();
+        let index = parse_index(segment, len, false)?;
+        return item
+            .get_mut(index)
+            .ok_or_else(|| format!("'{segment}' does not exist"));
+    }
+    let is_table = item.is_table();
+    let Some(table) = item.as_table_like_mut() else {
+        return Err(format!("cannot look up '{segment}' in a scalar value"));
+    };
+    if create && !table.contains_key(segment) {
+        let child = if is_table {
+            let mut child = Table::new();
+            child.set_implicit(true);
+            Item::Table(child)
+        } else {
+            Item::Value(InlineTable::new().into())
+        };
+        table.insert(segment, child);
+    }
+    table
+        .get_mut(segment)
+        .ok_or_else(|| format!("'{segment}' does not exist"))
+}
+
+/// Applies an edit to a TOML document, keeping the comments and formatting of untouched items.
+fn apply_toml_edit(
+    document: &mut DocumentMut,
+    edit: &StructuredEditOperation,
+) -> ServiceResult<()> {
+    let segments = parse_pointer(&edit.pointer)?;
+    let fail = |reason: String| invalid_pointer(&edit.pointer, &reason);
+    let converted = |edit: &StructuredEditOperation| -> ServiceResult<toml_edit::Value> {
+        toml_value(edit_value(edit)?).map_err(fail)
+    };
+    let create = edit.op == StructuredEditKind::Set;
+
+    if edit.op == StructuredEditKind::Append {
+        let mut target = document.as_item_mut();
+        for segment in &segments {
+            target = toml_child(target, segment, false).map_err(fail)?;
+        }
+        let value = converted(edit)?;
+        if let Some(array) = target.as_array_mut() {
+            array.push(value);
+        } else if let Some(tables) = target.as_array_of_tables_mut() {
+            let toml_edit::Value::InlineTable(table) = value else {
+                return Err(fail(
+                    "only tables can be appended to an array of tables".to_string(),
+                ));
+            };
+            tables.push(table.into_table());
+        } else {
+            return Err(fail("the target is not an array".to_string()));
+        }
+        return Ok(());
+    }
+
+    let Some((last, parents)) = segments.split_last() else {
+        return Err(fail(
+            "the whole document cannot be replaced or removed".to_string(),
+        ));
+    };
+    let mut parent = document.as_item_mut();
+    for segment in parents {
+        parent = toml_child(parent, segment, create).map_err(fail)?;
+    }
+
+    if let Some(array) = parent.as_array_mut() {
+        match edit.op {
+            StructuredEditKind::Set => {
+                let index = parse_index(last, array.len(), true).map_err(fail)?;
+                let value = converted(edit)?;
+                if index == array.len() {
+                    array.push(value);
+                } else {
+                    array.replace(index, value);
+                }
+            }
+            _ => {
+                let index = parse_index(last, array.len(), false).map_err(fail)?;
+                array.remove(index);
+            }
+        }
+        return Ok(());
+    }
+    if let Some(tables) = parent.as_array_of_tables_mut() {
+        let index = parse_index(last, tables.len(), false).map_err(fail)?;
+        match edit.op {
+            StructuredEditKind::Set => {
+                return Err(fail(
+                    "elements of an array of tables can only be removed or edited by key"
+                        .to_string(),
+                ));
+            }
+            _ => tables.remove(index),
+        }
+        return Ok(());
+    }
+
+    let Some(table) = parent.as_table_like_mut() else {
+        return Err(fail(format!("cannot look up '{last}' in a scalar value")));
+    };
+    match edit.op {
+        StructuredEditKind::Set => {
+            let mut value = converted(edit)?;
+            match table.get_mut(last) {
+                // Replace in place, keeping the key and the comments around the value
+                Some(Item::Value(existing)) => {
+                    *value.decor_mut() = existing.decor().clone();
+                    *existing = value;
+                }
+                _ => {
+                    table.insert(last, Item::Value(value));
+                }
+            }
+        }
+        _ => {
+            table
+                .remove(last)
+                .ok_or_else(|| fail(format!("'{last}' does not exist")))?;
+        }
+    }
+    Ok(())
+}
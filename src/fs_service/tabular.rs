@@ -0,0 +1,381 @@
+use std::{collections::VecDeque, fmt::Write, io::Read};
+
+use csv::{ReaderBuilder, StringRecord};
+use serde::Serialize;
+
+use crate::error::{ServiceError, ServiceResult};
+
+/// Number of bytes sampled from the start of a file to detect its delimiter.
+pub const DELIMITER_SAMPLE_SIZE: usize = 16 * 1024;
+/// Default number of bytes scanned to count rows before the count is estimated.
+pub const DEFAULT_TABULAR_SCAN_BUDGET: u64 = 64 * 1024 * 1024;
+/// Most rows returned from either end of a file; larger `head_rows` and `tail_rows` are clamped.
+pub const MAX_PREVIEW_ROWS: usize = 1000;
+
+/// Delimiters tried when none is given, in order of preference.
+const CANDIDATE_DELIMITERS: [u8; 4] = [b',', b'\t', b';', b'|'];
+/// Number of records parsed from the sample to compare candidate delimiters.
+const DELIMITER_SAMPLE_RECORDS: usize = 20;
+
+/// Settings of a tabular preview.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TabularOptions {
+    /// Field delimiter; detected from the start of the file when `None`.
+    pub delimiter: Option<u8>,
+    /// Whether the first record holds the column names.
+    pub has_header: bool,
+    /// Columns to return, by name or 1-based index. Empty returns every column.
+    pub columns: Vec<String>,
+    /// Number of rows returned from the start of the file, at most [`MAX_PREVIEW_ROWS`].
+    pub head_rows: usize,
+    /// Number of rows returned from the end of the file, at most [`MAX_PREVIEW_ROWS`].
+    pub tail_rows: usize,
+    /// Number of rows used to infer the column types.
+    pub sample_rows: usize,
+    /// Bytes scanned before the row count is estimated instead of counted.
+    pub scan_budget: u64,
+}
+
+impl Default for TabularOptions {
+    fn default() -> Self {
+        Self {
+            delimiter: None,
+            has_header: true,
+            columns: vec![],
+            head_rows: 5,
+            tail_rows: 5,
+            sample_rows: 100,
+            scan_budget: DEFAULT_TABULAR_SCAN_BUDGET,
+        }
+    }
+}
+
+/// The type inferred for a column from its sampled values.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ColumnType {
+    /// Every sampled value is empty.
+    Empty,
+    Boolean,
+    Integer,
+    Float,
+    String,
+}
+
+impl ColumnType {
+    fn of(value: &str) -> Self {
+        let value = value.trim();
+        if value.is_empty() {
+            ColumnType::Empty
+        } else if value.eq_ignore_ascii_case("true") || value.eq_ignore_ascii_case("false") {
+            ColumnType::Boolean
+        } else if value.parse::<i64>().is_ok() {
+            ColumnType::Integer
+        } else if value.parse::<f64>().is_ok() {
+            ColumnType::Float
+        } else {
+            ColumnType::String
+        }
+    }
+
+    /// The narrowest type that holds values of both types.
+    fn merge(self, other: Self) -> Self {
+        use ColumnType::*;
+        match (self, other) {
+            (Empty, other) | (other, Empty) => other,
+            (left, right) if left == right => left,
+            (Integer, Float) | (Float, Integer) => Float,
+            _ => String,
+        }
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ColumnType::Empty => "empty",
+            ColumnType::Boolean => "boolean",
+            ColumnType::Integer => "integer",
+            ColumnType::Float => "float",
+            ColumnType::String => "string",
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct ColumnSummary {
+    pub name: String,
+    #[serde(rename = "type")]
+    pub column_type: ColumnType,
+}
+
+/// Summary and sample rows of a delimited file.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct TabularPreview {
+    pub delimiter: String,
+    pub columns: Vec<ColumnSummary>,
+    /// Number of data rows, excluding the header.
+    pub row_count: u64,
+    /// Whether `row_count` is extrapolated because the file is larger than the scan budget.
+    pub row_count_approximate: bool,
+    pub head: Vec<Vec<String>>,
+    /// The last rows not already in `head`, or `None` when the scan stopped before the end of
+    /// the file.
+    pub tail: Option<Vec<Vec<String>>>,
+}
+
+impl TabularPreview {
+    /// Renders the preview with the rows as aligned tables.
+    pub fn render_text(&self) -> String {
+        let names: Vec<String> = self
+            .columns
+            .iter()
+            .map(|column| column.name.clone())
+            .collect();
+        let mut output = format!(
+            "Delimiter: {}\nRows: {}{}\nColumns: {}\n",
+            self.delimiter,
+            self.row_count,
+            if self.row_count_approximate {
+                " (approximate)"
+            } else {
+                ""
+            },
+            self.columns
+                .iter()
+                .map(|column| format!("{} ({})", column.name, column.column_type.as_str()))
+                .collect::<Vec<_>>()
+                .join(", ")
+        );
+        if !self.head.is_empty() {
+            let _ = write!(
+                output,
+                "\nFirst {} rows:\n{}",
+                self.head.len(),
+                render_table(&names, &self.head)
+            );
+        }
+        match &self.tail {
+            Some(tail) if !tail.is_empty() => {
+                let _ = write!(
+                    output,
+                    "\nLast {} rows:\n{}",
+                    tail.len(),
+                    render_table(&names, tail)
+                );
+            }
+            Some(_) => {}
+            None => output
+                .push_str("\nLast rows are not shown: the file is larger than the scan budget.\n"),
+        }
+        output
+    }
+}
+
+/// Parses a delimiter given as a single ASCII character, or as `\t` or `tab`.
+pub fn parse_delimiter(delimiter: &str) -> ServiceResult<u8> {
+    match delimiter {
+        "\t" | "\\t" | "tab" => Ok(b'\t'),
+        _ if delimiter.len() == 1 && delimiter.is_ascii() => Ok(delimiter.as_bytes()[0]),
+        _ => Err(ServiceError::FromString(format!(
+            "Invalid delimiter '{delimiter}': expected a single ASCII character."
+        ))),
+    }
+}
+
+/// Picks the candidate delimiter that splits the sampled records into the most columns while
+/// giving every record the same number of fields. Quoted fields are parsed, so delimiters and
+/// newlines inside quotes are not counted.
+pub fn detect_delimiter(sample: &[u8]) -> u8 {
+    // Drop the last line, which is usually cut off by the sample size
+    let sample = match sample.iter().rposition(|byte| *byte == b'\n') {
+        Some(end) if sample.len() == DELIMITER_SAMPLE_SIZE => &sample[..end],
+        _ => sample,
+    };
+
+    let mut best = (CANDIDATE_DELIMITERS[0], (false, 0));
+    for delimiter in CANDIDATE_DELIMITERS {
+        let mut reader = ReaderBuilder::new()
+            .has_headers(false)
+            .flexible(true)
+            .delimiter(delimiter)
+            .from_reader(sample);
+        let Ok(field_counts) = reader
+            .records()
+            .take(DELIMITER_SAMPLE_RECORDS)
+            .map(|record| record.map(|record| record.len()))
+            .collect::<Result<Vec<_>, _>>()
+        else {
+            continue;
+        };
+        let Some(&first) = field_counts.first() else {
+            continue;
+        };
+        let consistent = field_counts.iter().all(|count| *count == first);
+        let score = (consistent && first > 1, first);
+        if score > best.1 {
+            best = (delimiter, score);
+        }
+    }
+    best.0
+}
+
+/// Streams the delimited records of `reader` and summarizes them. `file_size` is used to
+/// estimate the row count when the scan budget runs out.
+pub fn preview_tabular<R: Read>(
+    reader: R,
+    file_size: u64,
+    delimiter: u8,
+    options: &TabularOptions,
+) -> ServiceResult<TabularPreview> {
+    let mut reader = ReaderBuilder::new()
+        .has_headers(options.has_header)
+        .flexible(true)
+        .delimiter(delimiter)
+        .from_reader(reader);
+
+    let mut record = StringRecord::new();
+    let (names, mut pending): (Vec<String>, bool) = if options.has_header {
+        let names = reader.headers()?.iter().map(String::from).collect();
+        (names, false)
+    } else {
+        let pending = reader.read_record(&mut record)?;
+        let names = (1..=record.len())
+            .map(|index| format!("column_{index}"))
+            .collect();
+        (names, pending)
+    };
+    let projection = resolve_columns(&names, &options.columns)?;
+    let project = |record: &StringRecord| -> Vec<String> {
+        projection
+            .iter()
+            .map(|index| record.get(*index).unwrap_or_default().to_string())
+            .collect()
+    };
+
+    let mut column_types = vec![ColumnType::Empty; projection.len()];
+    let head_rows = options.head_rows.min(MAX_PREVIEW_ROWS);
+    let tail_rows = options.tail_rows.min(MAX_PREVIEW_ROWS);
+    let mut head = Vec::new();
+    let mut tail = VecDeque::new();
+    let mut row_count: u64 = 0;
+    let mut budget_exhausted = false;
+    while pending || reader.read_record(&mut record)? {
+        pending = false;
+        let row = project(&record);
+        if (row_count as usize) < options.sample_rows {
+            for (column_type, value) in column_types.iter_mut().zip(&row) {
+                *column_type = column_type.merge(ColumnType::of(value));
+            }
+        }
+        if head.len() < head_rows {
+            head.push(row);
+        } else if tail_rows > 0 {
+            if tail.len() == tail_rows {
+                tail.pop_front();
+            }
+            tail.push_back(row);
+        }
+        row_count += 1;
+        if reader.position().byte() >= options.scan_budget {
+            budget_exhausted = true;
+            break;
+        }
+    }
+
+    let scanned = reader.position().byte();
+    let row_count_approximate = budget_exhausted && scanned < file_size;
+    if row_count_approximate {
+        row_count = (row_count as f64 * file_size as f64 / scanned as f64).round() as u64;
+    }
+
+    Ok(TabularPreview {
+        delimiter: match delimiter {
+            b'\t' => "tab".to_string(),
+            other => format!("'{}'", other as char),
+        },
+        columns: projection
+            .iter()
+            .zip(column_types)
+            .map(|(index, column_type)| ColumnSummary {
+                name: names.get(*index).cloned().unwrap_or_default(),
+                column_type,
+            })
+            .collect(),
+        row_count,
+        row_count_approximate,
+        head,
+        tail: (!row_count_approximate).then(|| tail.into_iter().collect()),
+    })
+}
+
+/// Resolves the requested columns, given by name or 1-based index, to field indexes.
+fn resolve_columns(names: &[String], columns: &[String]) -> ServiceResult<Vec<usize>> {
+    if columns.is_empty() {
+        return Ok((0..names.len()).collect());
+    }
+    columns
+        .iter()
+        .map(|column| {
+            names
+                .iter()
+                .position(|name| name == column)
+                .or_else(|| {
+                    column
+                        .parse::<usize>()
+                        .ok()
+                        .filter(|index| (1..=names.len()).contains(index))
+                        .map(|index| index - 1)
+                })
+                .ok_or_else(|| {
+                    ServiceError::FromString(format!(
+                        "Unknown column '{column}'. Available columns: {}.",
+                        names.join(", ")
+                    ))
+                })
+        })
+        .collect()
+}
+
+/// Formats rows as a table with left-aligned columns. Line breaks inside fields are escaped.
+fn render_table(headers: &[String], rows: &[Vec<String>]) -> String {
+    let escape = |value: &str| value.replace('\r', "\\r").replace('\n', "\\n");
+    let rows: Vec<Vec<String>> = rows
+        .iter()
+        .map(|row| row.iter().map(|value| escape(value)).collect())
+        .collect();
+    let widths: Vec<usize> = (0..headers.len())
+        .map(|index| {
+            rows.iter()
+                .filter_map(|row| row.get(index))
+                .chain(std::iter::once(&headers[index]))
+                .map(|value| value.chars().count())
+                .max()
+                .unwrap_or_default()
+        })
+        .collect();
+
+    let format_row = |values: &[String]| {
+        values
+            .iter()
+            .zip(&widths)
+            .map(|(value, width)| format!("{value:<width$}"))
+            .collect::<Vec<_>>()
+            .join(" | ")
+            .trim_end()
+            .to_string()
+    };
+    let mut table = format_row(headers);
+    table.push('\n');
+    table.push_str(
+        &widths
+            .iter()
+            .map(|width| "-".repeat(*width))
+            .collect::<Vec<_>>()
+            .join("-+-"),
+    );
+    table.push('\n');
+    for row in &rows {
+        table.push_str(&format_row(row));
+        table.push('\n');
+    }
+    table
+}
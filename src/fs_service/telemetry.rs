@@ -0,0 +1,104 @@
+use std::sync::atomic::{AtomicU64, Ordering};
+
+use super::instrumentation::{FAST_PATH_CONCURRENT, FAST_PATH_PARALLEL};
+
+/// A decision point of the service whose outcomes are counted, to tell whether the fast paths
+/// and the thresholds of `PerformanceConfig` trigger in practice.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Counter {
+    /// Work spread across the rayon thread pool.
+    ParallelPath,
+    /// Several files read concurrently on the async runtime.
+    ConcurrentPath,
+    /// A deduplicated read answered with a stub, the file being unchanged.
+    ReadCacheHit,
+    /// A deduplicated read that returned the content.
+    ReadCacheMiss,
+    /// A file searched as a stream.
+    SearchStreamed,
+    /// A file loaded in memory to be searched, for Unicode normalization or decoding.
+    SearchBuffered,
+    /// A text file decoded from an encoding other than UTF-8.
+    TextDecoded,
+    /// A write large enough to check the free space of its destination.
+    DiskSpaceChecked,
+    /// A write below `disk_space_check_threshold`, which did not check.
+    DiskSpaceCheckSkipped,
+}
+
+impl Counter {
+    pub const ALL: [Counter; 9] = [
+        Counter::ParallelPath,
+        Counter::ConcurrentPath,
+        Counter::ReadCacheHit,
+        Counter::ReadCacheMiss,
+        Counter::SearchStreamed,
+        Counter::SearchBuffered,
+        Counter::TextDecoded,
+        Counter::DiskSpaceChecked,
+        Counter::DiskSpaceCheckSkipped,
+    ];
+
+    /// The stable name of the counter, as reported by `get_performance_counters`.
+    pub fn name(self) -> &'static str {
+        match self {
+            Counter::ParallelPath => "parallel_path",
+            Counter::ConcurrentPath => "concurrent_path",
+            Counter::ReadCacheHit => "read_cache_hit",
+            Counter::ReadCacheMiss => "read_cache_miss",
+            Counter::SearchStreamed => "search_streamed",
+            Counter::SearchBuffered => "search_buffered",
+            Counter::TextDecoded => "text_decoded",
+            Counter::DiskSpaceChecked => "disk_space_checked",
+            Counter::DiskSpaceCheckSkipped => "disk_space_check_skipped",
+        }
+    }
+
+    /// The counter of a fast path recorded in the timings footer.
+    pub fn for_fast_path(name: &str) -> Option<Self> {
+        match name {
+            FAST_PATH_PARALLEL => Some(Counter::ParallelPath),
+            FAST_PATH_CONCURRENT => Some(Counter::ConcurrentPath),
+            _ => None,
+        }
+    }
+}
+
+/// Counters of the decision points of a service, cumulative since it started or was last
+/// reset. Unlike the per-call stats of the timings footer, they add up across tool calls.
+#[derive(Debug, Default)]
+pub(crate) struct Telemetry {
+    counters: [AtomicU64; Counter::ALL.len()],
+}
+
+impl Telemetry {
+    pub(crate) fn increment(&self, counter: Counter) {
+        self.counters[counter as usize].fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// The value of every counter, in the order of [`Counter::ALL`].
+    pub(crate) fn snapshot(&self) -> Vec<(Counter, u64)> {
+        Counter::ALL
+            .iter()
+            .map(|&counter| {
+                (
+                    counter,
+                    self.counters[counter as usize].load(Ordering::Relaxed),
+                )
+            })
+            .collect()
+    }
+
+    /// Sets every counter back to zero, returning their values before.
+    pub(crate) fn reset(&self) -> Vec<(Counter, u64)> {
+        Counter::ALL
+            .iter()
+            .map(|&counter| {
+                (
+                    counter,
+                    self.counters[counter as usize].swap(0, Ordering::Relaxed),
+                )
+            })
+            .collect()
+    }
+}
@@ -0,0 +1,217 @@
+use std::{fmt, fs::File, io::Read, path::Path};
+
+use super::config::PerformanceConfig;
+
+/// Bytes read from the start of a file to classify it.
+pub const TEXT_PROBE_SIZE: usize = 8 * 1024;
+
+/// Share of C0 control characters, other than whitespace and escape, above which a sample that
+/// is not UTF-8 is binary rather than single-byte text.
+const MAX_CONTROL_SHARE: f64 = 0.1;
+
+/// A text encoding other than UTF-8, recognized from a byte order mark or from the layout of the
+/// sample.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TextEncoding {
+    Utf16Le,
+    Utf16Be,
+    Utf32Le,
+    Utf32Be,
+    /// Text that is not UTF-8 and holds no NUL byte, decoded one byte per character.
+    Latin1,
+}
+
+impl fmt::Display for TextEncoding {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Self::Utf16Le => "UTF-16LE",
+            Self::Utf16Be => "UTF-16BE",
+            Self::Utf32Le => "UTF-32LE",
+            Self::Utf32Be => "UTF-32BE",
+            Self::Latin1 => "ISO-8859-1",
+        })
+    }
+}
+
+/// What a file holds, as far as the tools reading it as text are concerned. Every tool decides
+/// from the same classification, so a file is never binary to one tool and text to another;
+/// tools doing several passes over a file classify it once and pass the result along.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum FileClass {
+    /// UTF-8 text, with or without a byte order mark.
+    TextUtf8,
+    /// Text in another encoding, decoded to UTF-8 when read.
+    TextOtherEncoding(TextEncoding),
+    Binary,
+    /// The file is larger than `PerformanceConfig::max_text_file_bytes`. Holds its size.
+    TooLarge(u64),
+    /// The file could not be opened or read. Holds the reason.
+    Unreadable(String),
+}
+
+impl FileClass {
+    pub fn is_text(&self) -> bool {
+        matches!(self, Self::TextUtf8 | Self::TextOtherEncoding(_))
+    }
+}
+
+impl fmt::Display for FileClass {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::TextUtf8 => f.write_str("UTF-8 text"),
+            Self::TextOtherEncoding(encoding) => write!(f, "{encoding} text"),
+            Self::Binary => f.write_str("binary"),
+            Self::TooLarge(size) => write!(f, "too large ({size} bytes)"),
+            Self::Unreadable(reason) => write!(f, "unreadable: {reason}"),
+        }
+    }
+}
+
+/// Classifies the file at `path` from its size and its first [`TEXT_PROBE_SIZE`] bytes.
+/// Blocking.
+pub fn classify(path: &Path, config: &PerformanceConfig) -> FileClass {
+    let size = match path.metadata() {
+        Ok(metadata) => metadata.len(),
+        Err(err) => return FileClass::Unreadable(err.to_string()),
+    };
+    if config.max_text_file_bytes > 0 && size > config.max_text_file_bytes {
+        return FileClass::TooLarge(size);
+    }
+    let mut sample = Vec::with_capacity(TEXT_PROBE_SIZE);
+    match File::open(path)
+        .and_then(|file| file.take(TEXT_PROBE_SIZE as u64).read_to_end(&mut sample))
+    {
+        Ok(_) => classify_bytes(&sample),
+        Err(err) => FileClass::Unreadable(err.to_string()),
+    }
+}
+
+/// Classifies content already in memory from its first [`TEXT_PROBE_SIZE`] bytes, as
+/// [`classify`] does for files. Never returns `TooLarge` or `Unreadable`.
+pub fn classify_bytes(content: &[u8]) -> FileClass {
+    let sample = &content[..content.len().min(TEXT_PROBE_SIZE)];
+    if sample.starts_with(&[0xEF, 0xBB, 0xBF]) {
+        return FileClass::TextUtf8;
+    }
+    if let Some(encoding) = bom_encoding(sample).or_else(|| bomless_utf16(sample)) {
+        return FileClass::TextOtherEncoding(encoding);
+    }
+    if sample.contains(&0) {
+        return FileClass::Binary;
+    }
+    match std::str::from_utf8(sample) {
+        Ok(_) => FileClass::TextUtf8,
+        // The sample ends in the middle of a character
+        Err(err) if err.error_len().is_none() && sample.len() == TEXT_PROBE_SIZE => {
+            FileClass::TextUtf8
+        }
+        Err(_) => {
+            let controls = sample
+                .iter()
+                .filter(|&&b| b < 0x20 && !matches!(b, b'\t' | b'\n' | b'\r' | 0x0C | 0x1B))
+                .count();
+            if controls as f64 > sample.len() as f64 * MAX_CONTROL_SHARE {
+                FileClass::Binary
+            } else {
+                FileClass::TextOtherEncoding(TextEncoding::Latin1)
+            }
+        }
+    }
+}
+
+/// Decodes `content` according to its class. The byte order marks of other encodings are
+/// dropped; the one of UTF-8 is kept, so edits see the file as it is. Returns `None` for classes
+/// that are not text, and for UTF-8 files that turn out to be invalid past the sample.
+pub fn decode_text(content: Vec<u8>, class: &FileClass) -> Option<String> {
+    let encoding = match class {
+        FileClass::TextUtf8 => return String::from_utf8(content).ok(),
+        FileClass::TextOtherEncoding(encoding) => *encoding,
+        _ => return None,
+    };
+    let bom_length = match bom_encoding(&content) {
+        Some(bom) if bom == encoding => match encoding {
+            TextEncoding::Utf32Le | TextEncoding::Utf32Be => 4,
+            _ => 2,
+        },
+        _ => 0,
+    };
+    let content = &content[bom_length..];
+    Some(match encoding {
+        TextEncoding::Utf16Le | TextEncoding::Utf16Be => {
+            let units = content.chunks(2).map(|pair| match (encoding, pair) {
+                (TextEncoding::Utf16Le, [low, high]) => u16::from_le_bytes([*low, *high]),
+                (_, [high, low]) => u16::from_be_bytes([*high, *low]),
+                _ => 0xFFFD,
+            });
+            char::decode_utf16(units)
+                .map(|c| c.unwrap_or(char::REPLACEMENT_CHARACTER))
+                .collect()
+        }
+        TextEncoding::Utf32Le | TextEncoding::Utf32Be => content
+            .chunks(4)
+            .map(|unit| {
+                let bytes: [u8; 4] = unit.try_into().unwrap_or([0xFF; 4]);
+                let value = match encoding {
+                    TextEncoding::Utf32Le => u32::from_le_bytes(bytes),
+                    _ => u32::from_be_bytes(bytes),
+                };
+                char::from_u32(value).unwrap_or(char::REPLACEMENT_CHARACTER)
+            })
+            .collect(),
+        TextEncoding::Latin1 => content.iter().map(|&b| char::from(b)).collect(),
+    })
+}
+
+/// The encoding announced by a UTF-16 or UTF-32 byte order mark.
+fn bom_encoding(sample: &[u8]) -> Option<TextEncoding> {
+    if sample.starts_with(&[0xFF, 0xFE, 0, 0]) {
+        Some(TextEncoding::Utf32Le)
+    } else if sample.starts_with(&[0, 0, 0xFE, 0xFF]) {
+        Some(TextEncoding::Utf32Be)
+    } else if sample.starts_with(&[0xFF, 0xFE]) {
+        Some(TextEncoding::Utf16Le)
+    } else if sample.starts_with(&[0xFE, 0xFF]) {
+        Some(TextEncoding::Utf16Be)
+    } else {
+        None
+    }
+}
+
+/// Recognizes UTF-16 without a byte order mark: NUL bytes only ever in the high byte of a code
+/// unit, in at least half of them (as in mostly-ASCII text), no unpaired surrogate and no
+/// control character other than whitespace.
+fn bomless_utf16(sample: &[u8]) -> Option<TextEncoding> {
+    let units = sample.len() / 2;
+    if units == 0 {
+        return None;
+    }
+    let zeros_at = |parity: usize| {
+        sample
+            .iter()
+            .skip(parity)
+            .step_by(2)
+            .take(units)
+            .filter(|&&b| b == 0)
+            .count()
+    };
+    let (even, odd) = (zeros_at(0), zeros_at(1));
+    let encoding = match (even, odd) {
+        (0, odd) if odd * 2 >= units => TextEncoding::Utf16Le,
+        (even, 0) if even * 2 >= units => TextEncoding::Utf16Be,
+        _ => return None,
+    };
+    let code_units = sample[..units * 2].chunks(2).map(|pair| match encoding {
+        TextEncoding::Utf16Le => u16::from_le_bytes([pair[0], pair[1]]),
+        _ => u16::from_be_bytes([pair[0], pair[1]]),
+    });
+    let mut decoded = char::decode_utf16(code_units).peekable();
+    while let Some(c) = decoded.next() {
+        match c {
+            Ok(c) if c.is_control() && !matches!(c, '\t' | '\n' | '\r' | '\x0C') => return None,
+            // A high surrogate cut off by the end of the sample is fine
+            Err(_) if decoded.peek().is_some() => return None,
+            _ => {}
+        }
+    }
+    Some(encoding)
+}
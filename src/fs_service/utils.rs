@@ -1,20 +1,28 @@
-use async_zip::{Compression, ZipEntryBuilder, error::ZipError, tokio::write::ZipFileWriter};
-use chrono::{DateTime, Local};
+use async_zip::{
+    Compression, ZipEntry, ZipEntryBuilder, error::ZipError, tokio::write::ZipFileWriter,
+};
+use chrono::{DateTime, Local, SecondsFormat};
 use dirs::home_dir;
 use rust_mcp_sdk::macros::JsonSchema;
+use serde_json::Value;
 #[cfg(unix)]
 use std::os::unix::fs::PermissionsExt;
 #[cfg(windows)]
 use std::os::windows::fs::MetadataExt;
 use std::{
+    cmp::Ordering,
+    collections::{HashMap, HashSet},
     ffi::OsStr,
     fs::{self},
+    io::Read,
     path::{Component, Path, PathBuf, Prefix},
-    time::SystemTime,
+    time::{Instant, SystemTime},
 };
 use tokio::fs::File;
 use tokio::io::AsyncReadExt;
 
+use crate::fs_service::ListedEntry;
+
 #[derive(::serde::Deserialize, ::serde::Serialize, Clone, Debug, JsonSchema)]
 pub enum OutputFormat {
     #[serde(rename = "text")]
@@ -23,12 +31,173 @@ pub enum OutputFormat {
     Json,
 }
 
+/// How listing tools order entries by name.
+#[derive(
+    ::serde::Deserialize, ::serde::Serialize, Clone, Copy, Debug, Default, PartialEq, Eq, JsonSchema,
+)]
+pub enum SortOrder {
+    /// Case-insensitive, with runs of digits compared by value ("file2" before "file10").
+    #[default]
+    #[serde(rename = "natural")]
+    Natural,
+    /// Plain byte order of the names ("B" before "a", "file10" before "file2").
+    #[serde(rename = "lexicographic")]
+    Lexicographic,
+}
+
+/// The ordering of entries in directory listings, shared by all listing tools.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct ListingOrder {
+    pub sort: SortOrder,
+    /// List directories before files.
+    pub dirs_first: bool,
+}
+
+impl ListingOrder {
+    pub fn new(sort: Option<SortOrder>, dirs_first: Option<bool>) -> Self {
+        Self {
+            sort: sort.unwrap_or_default(),
+            dirs_first: dirs_first.unwrap_or_default(),
+        }
+    }
+
+    /// Compares two entries given their names and whether they are directories.
+    pub fn compare(&self, a: (&str, bool), b: (&str, bool)) -> Ordering {
+        let dirs_first = if self.dirs_first {
+            b.1.cmp(&a.1)
+        } else {
+            Ordering::Equal
+        };
+        dirs_first.then_with(|| match self.sort {
+            SortOrder::Natural => natural_cmp(a.0, b.0),
+            SortOrder::Lexicographic => a.0.cmp(b.0),
+        })
+    }
+
+    /// Sorts entries returned by `list_directory_entries`.
+    pub fn sort_listed_entries(&self, entries: &mut [ListedEntry]) {
+        entries.sort_by(|a, b| self.compare((&a.name, a.is_dir), (&b.name, b.is_dir)));
+    }
+
+    /// Sorts the entries of a tree produced by `directory_tree`, recursively.
+    pub fn sort_tree(&self, entries: &mut Value) {
+        let Some(entries) = entries.as_array_mut() else {
+            return;
+        };
+        let key = |entry: &Value| {
+            (
+                entry["name"].as_str().unwrap_or_default().to_string(),
+                entry["type"] == "directory",
+            )
+        };
+        entries.sort_by(|a, b| {
+            let (a, b) = (key(a), key(b));
+            self.compare((&a.0, a.1), (&b.0, b.1))
+        });
+        for entry in entries {
+            if let Some(children) = entry.get_mut("children") {
+                self.sort_tree(children);
+            }
+        }
+    }
+}
+
+/// Compares names the way file managers do: case-insensitively, with runs of ASCII digits
+/// compared by numeric value. Names that only differ in case or leading zeros are ordered by
+/// their bytes, so the order is total and deterministic.
+pub fn natural_cmp(a: &str, b: &str) -> Ordering {
+    let mut a_chars = a.chars().peekable();
+    let mut b_chars = b.chars().peekable();
+    loop {
+        match (a_chars.peek().copied(), b_chars.peek().copied()) {
+            (None, None) => return a.cmp(b),
+            (None, Some(_)) => return Ordering::Less,
+            (Some(_), None) => return Ordering::Greater,
+            (Some(x), Some(y)) if x.is_ascii_digit() && y.is_ascii_digit() => {
+                let x_digits = take_digits(&mut a_chars);
+                let y_digits = take_digits(&mut b_chars);
+                let x_value = x_digits.trim_start_matches('0');
+                let y_value = y_digits.trim_start_matches('0');
+                let ordering = x_value
+                    .len()
+                    .cmp(&y_value.len())
+                    .then_with(|| x_value.cmp(y_value));
+                if ordering != Ordering::Equal {
+                    return ordering;
+                }
+            }
+            (Some(x), Some(y)) => {
+                let ordering = x.to_lowercase().cmp(y.to_lowercase());
+                if ordering != Ordering::Equal {
+                    return ordering;
+                }
+                a_chars.next();
+                b_chars.next();
+            }
+        }
+    }
+}
+
+fn take_digits(chars: &mut std::iter::Peekable<std::str::Chars<'_>>) -> String {
+    let mut digits = String::new();
+    while let Some(c) = chars.next_if(char::is_ascii_digit) {
+        digits.push(c);
+    }
+    digits
+}
+
+/// Bytes a [`DeadlineReader`] always reads before enforcing its deadline.
+pub const DEADLINE_MIN_PROGRESS_BYTES: u64 = 64 * 1024;
+
+/// A reader that fails with `ErrorKind::TimedOut` once `deadline` has passed. The deadline is
+/// only enforced after the first [`DEADLINE_MIN_PROGRESS_BYTES`], so some progress is always
+/// made.
+pub struct DeadlineReader<R> {
+    inner: R,
+    deadline: Option<Instant>,
+    bytes_read: u64,
+}
+
+impl<R> DeadlineReader<R> {
+    pub fn new(inner: R, deadline: Option<Instant>) -> Self {
+        Self {
+            inner,
+            deadline,
+            bytes_read: 0,
+        }
+    }
+}
+
+impl<R: Read> Read for DeadlineReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        if self.bytes_read >= DEADLINE_MIN_PROGRESS_BYTES
+            && self
+                .deadline
+                .is_some_and(|deadline| Instant::now() >= deadline)
+        {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::TimedOut,
+                "search timeout exceeded",
+            ));
+        }
+        let read = self.inner.read(buf)?;
+        self.bytes_read += read as u64;
+        Ok(read)
+    }
+}
+
 pub fn format_system_time(system_time: SystemTime) -> String {
     // Convert SystemTime to DateTime<Local>
     let datetime: DateTime<Local> = system_time.into();
     datetime.format("%a %b %d %Y %H:%M:%S %:z").to_string()
 }
 
+/// Formats `system_time` as an RFC 3339 timestamp in the local time zone, to the second.
+pub fn format_rfc3339(system_time: SystemTime) -> String {
+    let datetime: DateTime<Local> = system_time.into();
+    datetime.to_rfc3339_opts(SecondsFormat::Secs, false)
+}
+
 pub fn format_permissions(metadata: &fs::Metadata) -> String {
     #[cfg(unix)]
     {
@@ -61,8 +230,64 @@ pub fn format_permissions(metadata: &fs::Metadata) -> String {
     }
 }
 
+/// Disk space allocated to a file: `st_blocks * 512` on Unix, which is less than the size for
+/// sparse files and usually more for small ones. Other platforms report the size.
+pub fn allocated_size(metadata: &fs::Metadata) -> u64 {
+    #[cfg(unix)]
+    {
+        std::os::unix::fs::MetadataExt::blocks(metadata) * 512
+    }
+
+    #[cfg(not(unix))]
+    {
+        metadata.len()
+    }
+}
+
+/// Canonicalizes `path`. A path that does not exist yet is resolved from its nearest existing
+/// ancestor, with the remaining components applied lexically, so a new file below a directory
+/// reached through a symlink resolves like the directory itself.
 pub fn normalize_path(path: &Path) -> PathBuf {
-    path.canonicalize().unwrap_or_else(|_| path.to_path_buf())
+    if let Ok(canonical_path) = path.canonicalize() {
+        return canonical_path;
+    }
+    for ancestor in path.ancestors().skip(1) {
+        let Ok(mut resolved) = ancestor.canonicalize() else {
+            continue;
+        };
+        let Ok(rest) = path.strip_prefix(ancestor) else {
+            break;
+        };
+        for component in rest.components() {
+            match component {
+                Component::Normal(part) => resolved.push(part),
+                Component::ParentDir => {
+                    resolved.pop();
+                }
+                _ => {}
+            }
+        }
+        return resolved;
+    }
+    path.to_path_buf()
+}
+
+/// Adds a `path` field to the entries of a tree produced by `directory_tree` for `root`: the
+/// root joined with the names of the entry and its parents.
+pub fn add_tree_paths(root: &Path, entries: &mut Value) {
+    let Some(entries) = entries.as_array_mut() else {
+        return;
+    };
+    for entry in entries {
+        let Some(name) = entry.get("name").and_then(Value::as_str) else {
+            continue;
+        };
+        let path = root.join(name);
+        entry["path"] = Value::String(path.display().to_string());
+        if let Some(children) = entry.get_mut("children") {
+            add_tree_paths(&path, children);
+        }
+    }
 }
 
 pub fn expand_home(path: PathBuf) -> PathBuf {
@@ -75,20 +300,190 @@ pub fn expand_home(path: PathBuf) -> PathBuf {
     path
 }
 
-pub fn format_bytes(bytes: u64) -> String {
-    const KB: u64 = 1024;
-    const MB: u64 = KB * 1024;
-    const GB: u64 = MB * 1024;
-    const TB: u64 = GB * 1024;
+/// Converts a string to a `PathBuf`, supporting both raw paths and `file://` URIs.
+/// Percent-encoded characters in `file://` URIs (e.g. `%20`) are decoded.
+pub fn parse_file_uri(input: &str) -> PathBuf {
+    let input = input.trim();
+    match input.strip_prefix("file://") {
+        Some(uri_path) => {
+            // drop the optional authority, file://localhost/path is equivalent to file:///path
+            let uri_path = uri_path.strip_prefix("localhost").unwrap_or(uri_path);
+            PathBuf::from(percent_decode(uri_path))
+        }
+        None => PathBuf::from(input),
+    }
+}
+
+fn percent_decode(input: &str) -> String {
+    let bytes = input.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        if bytes[i] == b'%' && i + 2 < bytes.len() {
+            let hex = std::str::from_utf8(&bytes[i + 1..i + 3]).ok();
+            if let Some(byte) = hex.and_then(|hex| u8::from_str_radix(hex, 16).ok()) {
+                decoded.push(byte);
+                i += 3;
+                continue;
+            }
+        }
+        decoded.push(bytes[i]);
+        i += 1;
+    }
+    String::from_utf8_lossy(&decoded).into_owned()
+}
+
+/// How a `search_files` pattern is matched: returns the glob matched against the lowercased
+/// name of each entry, and whether only directories match (the pattern ends with `/`).
+/// Patterns without `*` match names containing them.
+pub fn name_glob(pattern: &str) -> (String, bool) {
+    let (pattern, dirs_only) = match pattern.strip_suffix('/') {
+        Some(pattern) => (pattern, true),
+        None => (pattern, false),
+    };
+    let glob = if pattern.contains('*') {
+        pattern.to_lowercase()
+    } else {
+        format!("**/*{}*", pattern.to_lowercase())
+    };
+    (glob, dirs_only)
+}
+
+/// The glob an exclude pattern is matched with, against paths relative to the search root.
+/// Patterns without `*` match paths containing them.
+pub fn exclude_glob(pattern: &str) -> String {
+    if pattern.contains('*') {
+        pattern.strip_prefix('/').unwrap_or(pattern).to_owned()
+    } else {
+        format!("*{pattern}*")
+    }
+}
+
+/// Levenshtein distance between `a` and `b`, counted in characters.
+pub fn edit_distance(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut previous: Vec<usize> = (0..=b.len()).collect();
+    for (i, a_char) in a.chars().enumerate() {
+        let mut current = vec![i + 1; b.len() + 1];
+        for (j, b_char) in b.iter().enumerate() {
+            let substitution = previous[j] + usize::from(a_char != *b_char);
+            current[j + 1] = substitution.min(previous[j + 1] + 1).min(current[j] + 1);
+        }
+        previous = current;
+    }
+    previous[b.len()]
+}
+
+/// Unit system of human-readable sizes.
+#[derive(
+    ::serde::Deserialize,
+    ::serde::Serialize,
+    Clone,
+    Copy,
+    Debug,
+    Default,
+    PartialEq,
+    Eq,
+    JsonSchema,
+    clap::ValueEnum,
+)]
+pub enum SizeUnits {
+    /// Powers of 1024 labelled KB, MB, GB and TB, as older versions printed them.
+    #[serde(rename = "compact")]
+    Compact,
+    /// Powers of 1024 labelled KiB, MiB, GiB and TiB.
+    #[default]
+    #[serde(rename = "binary")]
+    Binary,
+    /// Powers of 1000 labelled KB, MB, GB and TB.
+    #[serde(rename = "decimal")]
+    Decimal,
+}
+
+/// Largest number of decimals accepted for human-readable sizes.
+pub const MAX_SIZE_PRECISION: usize = 6;
 
-    let units = [(TB, "TB"), (GB, "GB"), (MB, "MB"), (KB, "KB")];
+/// How sizes and counts are rendered in human-readable output. The server holds the configured
+/// default, which tools refine with the options of each call.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct ByteFormat {
+    pub units: SizeUnits,
+    /// Number of decimals, at most [`MAX_SIZE_PRECISION`].
+    pub precision: usize,
+    /// Group the digits of counts and byte counts by thousands (`12,345`).
+    pub thousands_separators: bool,
+}
 
-    for (threshold, unit) in units {
-        if bytes >= threshold {
-            return format!("{:.2} {}", bytes as f64 / threshold as f64, unit);
+impl Default for ByteFormat {
+    fn default() -> Self {
+        Self {
+            units: SizeUnits::Binary,
+            precision: 2,
+            thousands_separators: false,
         }
     }
-    format!("{bytes} bytes")
+}
+
+impl ByteFormat {
+    pub fn new(units: Option<SizeUnits>, precision: Option<u64>) -> Self {
+        Self::default().with_overrides(units, precision)
+    }
+
+    /// This format, with the units and precision replaced by those given.
+    pub fn with_overrides(&self, units: Option<SizeUnits>, precision: Option<u64>) -> Self {
+        Self {
+            units: units.unwrap_or(self.units),
+            precision: precision.map_or(self.precision, |precision| {
+                precision.min(MAX_SIZE_PRECISION as u64) as usize
+            }),
+            ..*self
+        }
+    }
+
+    pub fn format(&self, bytes: u64) -> String {
+        let (base, labels): (u64, [&str; 4]) = match self.units {
+            SizeUnits::Compact => (1024, ["KB", "MB", "GB", "TB"]),
+            SizeUnits::Binary => (1024, ["KiB", "MiB", "GiB", "TiB"]),
+            SizeUnits::Decimal => (1000, ["KB", "MB", "GB", "TB"]),
+        };
+
+        for (exponent, unit) in labels.iter().enumerate().rev() {
+            let threshold = base.pow(exponent as u32 + 1);
+            if bytes >= threshold {
+                let value = format!(
+                    "{:.precision$}",
+                    bytes as f64 / threshold as f64,
+                    precision = self.precision
+                );
+                let (integer, fraction) = value.split_at(value.find('.').unwrap_or(value.len()));
+                return format!("{}{fraction} {unit}", self.group_digits(integer));
+            }
+        }
+        format!("{} bytes", self.format_count(bytes))
+    }
+
+    /// Formats a count, such as a number of files.
+    pub fn format_count(&self, count: u64) -> String {
+        self.group_digits(&count.to_string())
+    }
+
+    fn group_digits(&self, digits: &str) -> String {
+        if !self.thousands_separators {
+            return digits.to_string();
+        }
+        let mut grouped = String::with_capacity(digits.len() + digits.len() / 3);
+        for (index, digit) in digits.chars().enumerate() {
+            if index > 0 && (digits.len() - index) % 3 == 0 {
+                grouped.push(',');
+            }
+            grouped.push(digit);
+        }
+        grouped
+    }
+}
+
+pub fn format_bytes(bytes: u64) -> String {
+    ByteFormat::default().format(bytes)
 }
 
 pub async fn write_zip_entry(
@@ -108,6 +503,74 @@ pub async fn write_zip_entry(
     Ok(())
 }
 
+/// Returns true if the entry was stored with Unix attributes marking it as a symbolic link.
+pub fn is_zip_symlink(entry: &ZipEntry) -> bool {
+    const S_IFMT: u16 = 0o170000;
+    const S_IFLNK: u16 = 0o120000;
+    entry
+        .unix_permissions()
+        .is_some_and(|mode| mode & S_IFMT == S_IFLNK)
+}
+
+/// Resolves the archive entry name `entry_name` into a path relative to the extraction root,
+/// dropping `.` components and applying `..` ones. Returns `None` if the name is absolute or
+/// escapes the root.
+pub fn archive_entry_path(entry_name: &str) -> Option<PathBuf> {
+    let mut resolved = PathBuf::new();
+    for component in Path::new(entry_name).components() {
+        match component {
+            Component::Normal(part) => resolved.push(part),
+            Component::CurDir => {}
+            Component::ParentDir => {
+                if !resolved.pop() {
+                    return None;
+                }
+            }
+            Component::RootDir | Component::Prefix(_) => return None,
+        }
+    }
+    Some(resolved)
+}
+
+/// Resolves the target of the archive symlink `entry_name` relative to the directory containing
+/// the link. `links` maps the symlinks already extracted to their resolved targets, which are
+/// followed as the file system would; every path a resolution goes through is added to
+/// `visited`. Returns the resolved path relative to the archive root, or `None` if the link name
+/// or its target is absolute or escapes the root through `..` components.
+pub fn resolve_archive_link(
+    entry_name: &str,
+    target: &str,
+    links: &HashMap<PathBuf, PathBuf>,
+    visited: &mut HashSet<PathBuf>,
+) -> Option<PathBuf> {
+    let target = Path::new(target);
+    if target.has_root() {
+        return None;
+    }
+
+    let mut resolved = archive_entry_path(entry_name)?;
+    resolved.pop();
+    for component in target.components() {
+        match component {
+            Component::Normal(part) => {
+                resolved.push(part);
+                visited.insert(resolved.clone());
+                if let Some(link_target) = links.get(&resolved) {
+                    resolved = link_target.clone();
+                }
+            }
+            Component::CurDir => {}
+            Component::ParentDir => {
+                if !resolved.pop() {
+                    return None;
+                }
+            }
+            Component::RootDir | Component::Prefix(_) => return None,
+        }
+    }
+    Some(resolved)
+}
+
 pub fn normalize_line_endings(text: &str) -> String {
     text.replace("\r\n", "\n").replace('\r', "\n")
 }
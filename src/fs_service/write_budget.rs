@@ -0,0 +1,154 @@
+use std::{fmt, sync::Mutex};
+
+use crate::error::{ServiceError, ServiceResult};
+
+/// Which of the session counters an operation would overshoot.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BudgetCounter {
+    /// Bytes written to files, including archives and extracted entries.
+    Written,
+    /// Bytes removed from files that were overwritten or replaced.
+    Removed,
+}
+
+impl fmt::Display for BudgetCounter {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            BudgetCounter::Written => "write",
+            BudgetCounter::Removed => "delete",
+        })
+    }
+}
+
+/// Bytes written and removed by the write operations of the session so far.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct SessionWriteUsage {
+    pub bytes_written: u64,
+    pub bytes_removed: u64,
+    /// Ceiling on `bytes_written`, `None` when unlimited.
+    pub write_limit: Option<u64>,
+    /// Ceiling on `bytes_removed`, `None` when unlimited.
+    pub delete_limit: Option<u64>,
+}
+
+impl fmt::Display for SessionWriteUsage {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let usage = |used: u64, limit: Option<u64>| match limit {
+            Some(limit) => format!("{used} of {limit} bytes"),
+            None => format!("{used} bytes"),
+        };
+        write!(
+            f,
+            "Session writes: {} written, {} removed.",
+            usage(self.bytes_written, self.write_limit),
+            usage(self.bytes_removed, self.delete_limit)
+        )
+    }
+}
+
+/// Per-session accounting of the bytes written and removed by write operations, with optional
+/// ceilings. An operation that would overshoot a ceiling is rejected before it touches the disk.
+#[derive(Debug, Default)]
+pub(crate) struct WriteBudget {
+    usage: Mutex<SessionWriteUsage>,
+}
+
+impl WriteBudget {
+    /// Sets the ceilings; `0` leaves the counter unlimited.
+    pub(crate) fn set_limits(&self, write_limit: u64, delete_limit: u64) {
+        if let Ok(mut usage) = self.usage.lock() {
+            usage.write_limit = (write_limit > 0).then_some(write_limit);
+            usage.delete_limit = (delete_limit > 0).then_some(delete_limit);
+        }
+    }
+
+    pub(crate) fn usage(&self) -> SessionWriteUsage {
+        self.usage.lock().map(|usage| *usage).unwrap_or_default()
+    }
+
+    /// Whether any ceiling is configured.
+    pub(crate) fn is_limited(&self) -> bool {
+        let usage = self.usage();
+        usage.write_limit.is_some() || usage.delete_limit.is_some()
+    }
+
+    /// Fails if `written` and `removed` more bytes would exceed a ceiling, without recording them.
+    pub(crate) fn check(&self, written: u64, removed: u64) -> ServiceResult<()> {
+        let usage = self.lock()?;
+        check_counter(
+            BudgetCounter::Written,
+            usage.bytes_written,
+            written,
+            usage.write_limit,
+        )?;
+        check_counter(
+            BudgetCounter::Removed,
+            usage.bytes_removed,
+            removed,
+            usage.delete_limit,
+        )
+    }
+
+    /// Records `written` and `removed` bytes, failing without recording anything if that would
+    /// exceed a ceiling.
+    pub(crate) fn charge(&self, written: u64, removed: u64) -> ServiceResult<()> {
+        let mut usage = self.lock()?;
+        check_counter(
+            BudgetCounter::Written,
+            usage.bytes_written,
+            written,
+            usage.write_limit,
+        )?;
+        check_counter(
+            BudgetCounter::Removed,
+            usage.bytes_removed,
+            removed,
+            usage.delete_limit,
+        )?;
+        usage.bytes_written += written;
+        usage.bytes_removed += removed;
+        Ok(())
+    }
+
+    /// Records bytes of an operation whose exact size is only known once it has completed.
+    /// Ceilings are not enforced; callers [`check`](Self::check) an estimate beforehand.
+    pub(crate) fn record(&self, written: u64, removed: u64) {
+        if let Ok(mut usage) = self.usage.lock() {
+            usage.bytes_written += written;
+            usage.bytes_removed += removed;
+        }
+    }
+
+    /// Gives back bytes [`charge`](Self::charge)d for an operation that then failed.
+    pub(crate) fn refund(&self, written: u64, removed: u64) {
+        if let Ok(mut usage) = self.usage.lock() {
+            usage.bytes_written = usage.bytes_written.saturating_sub(written);
+            usage.bytes_removed = usage.bytes_removed.saturating_sub(removed);
+        }
+    }
+
+    fn lock(&self) -> ServiceResult<std::sync::MutexGuard<'_, SessionWriteUsage>> {
+        self.usage
+            .lock()
+            .map_err(|_| ServiceError::FromString("Write budget is poisoned.".to_string()))
+    }
+}
+
+fn check_counter(
+    counter: BudgetCounter,
+    used: u64,
+    requested: u64,
+    limit: Option<u64>,
+) -> ServiceResult<()> {
+    match limit {
+        Some(limit) if used.saturating_add(requested) > limit => {
+            Err(ServiceError::WriteBudgetExhausted {
+                counter,
+                used,
+                requested,
+                limit,
+            })
+        }
+        _ => Ok(()),
+    }
+}
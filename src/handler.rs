@@ -1,32 +1,405 @@
 use crate::cli::CommandArguments;
-use crate::error::ServiceError;
+use crate::error::{ServiceError, describe_unknown_arguments};
 use crate::invoke_tools;
-use crate::{error::ServiceResult, fs_service::FileSystemService, tools::*};
+use crate::{
+    error::ServiceResult,
+    fs_service::{
+        FileSystemService, InvalidDirectoryPolicy,
+        config::PerformanceConfig,
+        instrumentation::{OPERATION_FOOTER_PREFIX, instrument, new_operation_id},
+        redaction::RedactionConfig,
+        utils::ByteFormat,
+    },
+    tool_defaults::ToolDefaults,
+    tools::*,
+};
 use async_trait::async_trait;
 use rust_mcp_sdk::McpServer;
 use rust_mcp_sdk::mcp_server::ServerHandler;
 use rust_mcp_sdk::schema::RootsListChangedNotification;
 use rust_mcp_sdk::schema::{
-    CallToolRequest, CallToolResult, InitializeRequest, InitializeResult, ListToolsRequest,
-    ListToolsResult, RpcError, schema_utils::CallToolError,
+    CallToolRequest, CallToolRequestParams, CallToolResult, InitializeRequest, InitializeResult,
+    ListToolsRequest, ListToolsResult, RpcError, TextContent, Tool, schema_utils::CallToolError,
 };
+use serde_json::{Map, json};
 use std::cmp::Ordering;
+use std::path::{Path, PathBuf};
 use std::sync::Arc;
+use std::time::Duration;
+
+/// Per-call argument accepted by every tool to request the timings footer.
+pub const INCLUDE_TIMINGS_ARG: &str = "include_timings";
+/// Per-call argument accepted by every tool, echoed back next to the operation id.
+pub const CORRELATION_ID_ARG: &str = "correlation_id";
+/// Key of the result metadata set to `true` for dry runs, which only preview their changes.
+pub const READ_ONLY_META: &str = "read_only";
+
+/// Identifies a tool call in its result footers and error messages.
+struct OperationIds {
+    id: String,
+    /// Supplied by the client, with whitespace replaced so the footer stays parseable.
+    correlation_id: Option<String>,
+}
+
+impl OperationIds {
+    /// The ids as space separated `key=value` pairs.
+    fn fields(&self) -> String {
+        match &self.correlation_id {
+            Some(correlation_id) => {
+                format!("operation_id={} correlation_id={correlation_id}", self.id)
+            }
+            None => format!("operation_id={}", self.id),
+        }
+    }
+}
 
 pub struct FileSystemHandler {
     readonly: bool,
     mcp_roots_support: bool,
+    result_timings: bool,
+    /// Whether calls with arguments the tool does not know are rejected, rather than run with
+    /// a warning.
+    strict_params: bool,
+    tools: Vec<Tool>,
+    disabled_tools: Vec<String>,
+    tool_defaults: ToolDefaults,
+    /// Problems with the configuration that did not prevent startup.
+    config_warnings: Vec<String>,
     fs_service: Arc<FileSystemService>,
 }
 
 impl FileSystemHandler {
     pub fn new(args: &CommandArguments) -> ServiceResult<Self> {
-        let fs_service = FileSystemService::try_new(&args.allowed_directories)?;
-        Ok(Self {
-            fs_service: Arc::new(fs_service),
-            readonly: !args.allow_write,
-            mcp_roots_support: args.enable_roots,
+        let invalid_directory_policy = if args.skip_invalid_directories {
+            InvalidDirectoryPolicy::Skip
+        } else {
+            InvalidDirectoryPolicy::Fail
+        };
+        let performance_config = PerformanceConfig {
+            stream_buffer_size: args.stream_buffer_size,
+            no_follow_writes: args.no_follow_writes,
+            no_atime_reads: args.no_atime_reads,
+            writable_extensions: args.writable_extensions.clone(),
+            blocked_extensions: args.blocked_extensions.clone(),
+            max_image_pixels: args.max_image_pixels,
+            max_pattern_length: args.max_pattern_length,
+            regex_size_limit: args.regex_size_limit,
+            search_timeout: (args.search_timeout > 0)
+                .then(|| Duration::from_secs(args.search_timeout)),
+            max_session_write_bytes: args.max_session_write_bytes,
+            max_session_delete_bytes: args.max_session_delete_bytes,
+            max_delete_entries: args.max_delete_entries,
+            disk_space_check_threshold: args.disk_space_check_threshold,
+            max_text_file_bytes: args.max_text_file_bytes,
+            max_open_files: args.max_open_files,
+            ..Default::default()
+        };
+        let fs_service = FileSystemService::try_new_with_policy(
+            &args.allowed_directories,
+            invalid_directory_policy,
+        )?
+        .with_performance_config(performance_config)?
+        .with_redaction_config(RedactionConfig {
+            enabled: args.redact_secrets,
+            exclude_globs: args.redact_exclude.clone(),
         })
+        .with_byte_format(ByteFormat {
+            units: args.size_units,
+            thousands_separators: args.thousands_separators,
+            ..Default::default()
+        })
+        .with_path_suggestions(!args.no_path_suggestions)
+        .with_metrics_file(args.metrics_file.as_ref().map(PathBuf::from));
+        let tool_defaults = match &args.config {
+            Some(config) => ToolDefaults::load(Path::new(config))?,
+            None => ToolDefaults::default(),
+        };
+        Ok(Self::from_service(
+            fs_service,
+            !args.allow_write,
+            args.enable_roots,
+            args.result_timings,
+            args.strict_params,
+            vec![],
+            tool_defaults,
+        ))
+    }
+
+    /// Creates a handler around an already configured `FileSystemService`. Tools listed in
+    /// `disabled_tools` are neither advertised nor callable. Defaults in `tool_defaults` that do
+    /// not fit the tools are dropped and reported in the startup message.
+    pub(crate) fn from_service(
+        fs_service: FileSystemService,
+        readonly: bool,
+        mcp_roots_support: bool,
+        result_timings: bool,
+        strict_params: bool,
+        disabled_tools: Vec<String>,
+        mut tool_defaults: ToolDefaults,
+    ) -> Self {
+        let mut tools: Vec<Tool> = Self::build_tools()
+            .into_iter()
+            .filter(|tool| !disabled_tools.contains(&tool.name))
+            .collect();
+        let config_warnings = tool_defaults.retain_known(&tools);
+        for tool in tools.iter_mut() {
+            tool_defaults.annotate(tool);
+        }
+        Self {
+            fs_service: Arc::new(fs_service),
+            readonly,
+            mcp_roots_support,
+            result_timings,
+            strict_params,
+            tools,
+            disabled_tools,
+            tool_defaults,
+            config_warnings,
+        }
+    }
+
+    /// Problems with the configuration that did not prevent startup, such as defaults set for
+    /// unknown tools or arguments.
+    pub fn config_warnings(&self) -> &[String] {
+        &self.config_warnings
+    }
+
+    pub fn fs_service(&self) -> Arc<FileSystemService> {
+        self.fs_service.clone()
+    }
+
+    /// Returns the available tools, with enum-like arguments listing their accepted values and
+    /// each tool advertising the `include_timings` and `correlation_id` arguments.
+    pub fn tools(&self) -> Vec<Tool> {
+        self.tools.clone()
+    }
+
+    fn build_tools() -> Vec<Tool> {
+        let mut tools = FileSystemTools::tools();
+        for tool in tools.iter_mut() {
+            flatten_enum_schemas(tool);
+            let properties = tool
+                .input_schema
+                .properties
+                .get_or_insert_with(Default::default);
+            let mut property = Map::new();
+            property.insert("type".to_string(), json!("boolean"));
+            property.insert(
+                "description".to_string(),
+                json!("Append a footer with elapsed time and I/O counters to the result."),
+            );
+            properties.insert(INCLUDE_TIMINGS_ARG.to_string(), property);
+            let mut property = Map::new();
+            property.insert("type".to_string(), json!("string"));
+            property.insert(
+                "description".to_string(),
+                json!("An id of the caller's choosing, echoed in a footer next to the operation id of the call."),
+            );
+            properties.insert(CORRELATION_ID_ARG.to_string(), property);
+        }
+        tools
+    }
+
+    /// Executes a tool call, appending the timings footer when it is enabled by the server
+    /// configuration or requested through the `include_timings` argument.
+    ///
+    /// Every call gets an operation id. It is added to error messages, to the timings footer,
+    /// and to an `[operation]` footer on error results and when a `correlation_id` is supplied.
+    pub async fn call_tool(
+        &self,
+        mut params: CallToolRequestParams,
+    ) -> std::result::Result<CallToolResult, CallToolError> {
+        let include_timings = params
+            .arguments
+            .as_mut()
+            .and_then(|arguments| arguments.remove(INCLUDE_TIMINGS_ARG))
+            .and_then(|value| value.as_bool())
+            .unwrap_or(false);
+        let operation = OperationIds {
+            id: new_operation_id(),
+            correlation_id: params
+                .arguments
+                .as_mut()
+                .and_then(|arguments| arguments.remove(CORRELATION_ID_ARG))
+                .filter(|value| !value.is_null())
+                .map(|value| match value {
+                    serde_json::Value::String(value) => value,
+                    value => value.to_string(),
+                })
+                .map(|value| value.split_whitespace().collect::<Vec<_>>().join("_")),
+        };
+
+        // Tracked until the call completes, so shutdown can wait for it to drain
+        let _operation = self
+            .fs_service
+            .begin_operation()
+            .map_err(CallToolError::new)?;
+
+        let (result, report) = if include_timings || self.result_timings {
+            let (result, report) = instrument(self.dispatch_cancellable(params)).await;
+            (result, Some(report))
+        } else {
+            (self.dispatch_cancellable(params).await, None)
+        };
+        let mut result = result.map_err(|err| {
+            CallToolError::new(ServiceError::FromString(format!(
+                "{err} ({})",
+                operation.fields()
+            )))
+        })?;
+        let footer = match report {
+            Some(report) => Some(format!("{} {}", report.footer(), operation.fields())),
+            None if result.is_error == Some(true) || operation.correlation_id.is_some() => {
+                Some(format!("{OPERATION_FOOTER_PREFIX} {}", operation.fields()))
+            }
+            None => None,
+        };
+        if let Some(footer) = footer {
+            result.content.push(TextContent::from(footer).into());
+        }
+        Ok(result)
+    }
+
+    /// Runs the tool call, abandoning it when shutdown cancels the remaining operations.
+    async fn dispatch_cancellable(
+        &self,
+        params: CallToolRequestParams,
+    ) -> std::result::Result<CallToolResult, CallToolError> {
+        let cancellation = self.fs_service.cancellation_token();
+        tokio::select! {
+            result = self.dispatch_tool(params) => result,
+            _ = cancellation.cancelled() => Err(CallToolError::new(ServiceError::ShuttingDown)),
+        }
+    }
+
+    async fn dispatch_tool(
+        &self,
+        mut params: CallToolRequestParams,
+    ) -> std::result::Result<CallToolResult, CallToolError> {
+        if self.disabled_tools.contains(&params.name) {
+            return Err(CallToolError::new(ServiceError::FromString(format!(
+                "Tool '{}' is disabled.",
+                params.name
+            ))));
+        }
+
+        self.tool_defaults
+            .apply(&params.name, &mut params.arguments);
+
+        // Report values outside of an enum-like argument's accepted values with the field name
+        // and the expected values, rather than the bare deserialization error. Unknown arguments,
+        // often misspelled ones, would be silently dropped: they are rejected in strict mode, and
+        // reported in a warning otherwise.
+        let mut unknown_warning = None;
+        if let (Some(tool), Some(arguments)) = (
+            self.tools.iter().find(|tool| tool.name == params.name),
+            params.arguments.as_ref(),
+        ) {
+            check_enum_arguments(tool, arguments).map_err(CallToolError::new)?;
+            let (unknown, expected) = unknown_arguments(tool, arguments);
+            if !unknown.is_empty() {
+                if self.strict_params {
+                    return Err(CallToolError::new(ServiceError::UnknownArguments {
+                        tool: tool.name.clone(),
+                        unknown,
+                        expected,
+                    }));
+                }
+                unknown_warning = Some(format!(
+                    "Warning: ignored unknown {}: {}.",
+                    if unknown.len() == 1 {
+                        "argument"
+                    } else {
+                        "arguments"
+                    },
+                    describe_unknown_arguments(&unknown, &expected)
+                ));
+            }
+        }
+
+        let tool_params: FileSystemTools =
+            FileSystemTools::try_from(params).map_err(CallToolError::new)?;
+
+        // Verify write access for tools that modify the file system
+        let writes = tool_params.require_write_access();
+        if writes {
+            self.assert_write_access()?;
+        }
+        let dry_run = tool_params.is_dry_run();
+
+        let mut result = invoke_tools!(
+            tool_params,
+            &self.fs_service,
+            ReadMediaFile,
+            ReadMultipleMediaFiles,
+            ReadTextFile,
+            ReadMultipleTextFiles,
+            WriteFile,
+            EditFile,
+            CreateDirectory,
+            ListDirectory,
+            DirectoryTree,
+            MoveFile,
+            SearchFiles,
+            GetFileInfo,
+            ListAllowedDirectories,
+            ZipFiles,
+            UnzipFile,
+            ZipDirectory,
+            SearchFilesContent,
+            ListDirectoryWithSizes,
+            HeadFile,
+            TailFile,
+            ReadFileLines,
+            FindEmptyDirectories,
+            CalculateDirectorySize,
+            FindDuplicateFiles,
+            ExtractDefinition,
+            ReadDirectory,
+            PathProbe,
+            OpenFile,
+            CloseFile,
+            PreviewTabularFile,
+            EditStructuredFile,
+            CheckFileLock,
+            HashDirectory,
+            PruneEmptyDirectories,
+            DetectGeneratedFiles,
+            VerifyArchive,
+            ConditionalWrite,
+            TestPattern,
+            DeletePath,
+            ConcatFiles,
+            BulkInsert,
+            ReadEnclosingBlock,
+            GetPerformanceCounters,
+            ConvertFile,
+            FlattenDirectory,
+            ReadContextPack,
+            SortFile,
+            PreviewGlob
+        )?;
+
+        // Tell clients that a dry run left the file system untouched, whatever the tool's hints
+        if dry_run {
+            result
+                .meta
+                .get_or_insert_with(Map::new)
+                .insert(READ_ONLY_META.to_string(), json!(true));
+        }
+
+        if let Some(warning) = unknown_warning {
+            result.content.push(TextContent::from(warning).into());
+        }
+
+        // Report the session write budget after every write once a ceiling is configured
+        if writes && self.fs_service.has_session_write_limits() {
+            let usage = self.fs_service.session_write_usage();
+            result
+                .content
+                .push(TextContent::from(usage.to_string()).into());
+        }
+        Ok(result)
     }
 
     pub fn assert_write_access(&self) -> std::result::Result<(), CallToolError> {
@@ -66,7 +439,19 @@ impl FileSystemHandler {
             )
         };
 
-        format!("{common_message}\n{sub_message}")
+        let mut message = format!("{common_message}\n{sub_message}");
+
+        for warning in self.fs_service.directory_warnings() {
+            message.push_str(&format!("\nWarning: {warning}"));
+        }
+        for rejected in self.fs_service.rejected_directories() {
+            message.push_str(&format!("\nSkipped invalid directory {rejected}"));
+        }
+        for warning in &self.config_warnings {
+            message.push_str(&format!("\nWarning: {warning}"));
+        }
+
+        message
     }
 
     pub(crate) async fn update_allowed_directories(&self, runtime: Arc<dyn McpServer>) {
@@ -152,7 +537,7 @@ impl ServerHandler for FileSystemHandler {
         _: Arc<dyn McpServer>,
     ) -> std::result::Result<ListToolsResult, RpcError> {
         Ok(ListToolsResult {
-            tools: FileSystemTools::tools(),
+            tools: self.tools(),
             meta: None,
             next_cursor: None,
         })
@@ -185,41 +570,6 @@ impl ServerHandler for FileSystemHandler {
         request: CallToolRequest,
         _: Arc<dyn McpServer>,
     ) -> std::result::Result<CallToolResult, CallToolError> {
-        let tool_params: FileSystemTools =
-            FileSystemTools::try_from(request.params).map_err(CallToolError::new)?;
-
-        // Verify write access for tools that modify the file system
-        if tool_params.require_write_access() {
-            self.assert_write_access()?;
-        }
-
-        invoke_tools!(
-            tool_params,
-            &self.fs_service,
-            ReadMediaFile,
-            ReadMultipleMediaFiles,
-            ReadTextFile,
-            ReadMultipleTextFiles,
-            WriteFile,
-            EditFile,
-            CreateDirectory,
-            ListDirectory,
-            DirectoryTree,
-            MoveFile,
-            SearchFiles,
-            GetFileInfo,
-            ListAllowedDirectories,
-            ZipFiles,
-            UnzipFile,
-            ZipDirectory,
-            SearchFilesContent,
-            ListDirectoryWithSizes,
-            HeadFile,
-            TailFile,
-            ReadFileLines,
-            FindEmptyDirectories,
-            CalculateDirectorySize,
-            FindDuplicateFiles
-        )
+        self.call_tool(request.params).await
     }
 }
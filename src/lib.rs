@@ -4,4 +4,7 @@ pub mod fs_service;
 pub mod handler;
 pub mod macros;
 pub mod server;
+pub mod tool_defaults;
 pub mod tools;
+
+pub use server::{FileSystemServer, ServerBuilder};
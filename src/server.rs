@@ -3,8 +3,16 @@ use rust_mcp_sdk::schema::{
     ServerCapabilitiesTools,
 };
 use rust_mcp_sdk::{McpServer, StdioTransport, TransportOptions, mcp_server::server_runtime};
+use std::{path::PathBuf, time::Duration};
 
+use crate::error::ServiceError;
+use crate::fs_service::{
+    FileSystemService, InvalidDirectoryPolicy, config::PerformanceConfig,
+    redaction::RedactionConfig, utils::ByteFormat,
+};
 use crate::handler::FileSystemHandler;
+use crate::tool_defaults::ToolDefaults;
+use crate::tools::FileSystemTools;
 use crate::{cli::CommandArguments, error::ServiceResult};
 
 pub fn server_details() -> InitializeResult {
@@ -28,13 +36,245 @@ pub fn server_details() -> InitializeResult {
     }
 }
 
+/// Time given to in-flight tool calls to complete once a termination signal is received.
+const SHUTDOWN_TIMEOUT: Duration = Duration::from_secs(10);
+
 pub async fn start_server(args: CommandArguments) -> ServiceResult<()> {
-    let transport = StdioTransport::new(TransportOptions::default())?;
+    FileSystemServer::new(FileSystemHandler::new(&args)?)
+        .run_stdio()
+        .await
+}
+
+/// Builds a filesystem MCP server programmatically, for embedding it in another binary.
+///
+/// ```no_run
+/// # async fn run() -> rust_mcp_filesystem::error::ServiceResult<()> {
+/// use rust_mcp_filesystem::ServerBuilder;
+///
+/// ServerBuilder::new()
+///     .allowed_dirs(["/srv/data"])
+///     .read_only(true)
+///     .disable_tools(["zip_directory"])
+///     .build()?
+///     .run_stdio()
+///     .await
+/// # }
+/// ```
+#[derive(Debug, Clone)]
+pub struct ServerBuilder {
+    allowed_directories: Vec<String>,
+    read_only: bool,
+    enable_roots: bool,
+    invalid_directory_policy: InvalidDirectoryPolicy,
+    performance_config: PerformanceConfig,
+    redaction_config: RedactionConfig,
+    byte_format: ByteFormat,
+    path_suggestions: bool,
+    result_timings: bool,
+    strict_params: bool,
+    disabled_tools: Vec<String>,
+    tool_defaults: ToolDefaults,
+    metrics_file: Option<PathBuf>,
+}
+
+impl Default for ServerBuilder {
+    fn default() -> Self {
+        Self {
+            allowed_directories: vec![],
+            read_only: true,
+            enable_roots: false,
+            invalid_directory_policy: InvalidDirectoryPolicy::default(),
+            performance_config: PerformanceConfig::default(),
+            redaction_config: RedactionConfig::default(),
+            byte_format: ByteFormat::default(),
+            path_suggestions: true,
+            result_timings: false,
+            strict_params: true,
+            disabled_tools: vec![],
+            tool_defaults: ToolDefaults::default(),
+            metrics_file: None,
+        }
+    }
+}
+
+impl ServerBuilder {
+    /// Creates a builder with the same defaults as the command line: read-only, no MCP Roots
+    /// support and no allowed directories.
+    pub fn new() -> Self {
+        Self::default()
+    }
 
-    let handler = FileSystemHandler::new(&args)?;
-    let server = server_runtime::create_server(server_details(), transport, handler);
+    pub fn allowed_dirs<I, S>(mut self, directories: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        self.allowed_directories = directories.into_iter().map(Into::into).collect();
+        self
+    }
 
-    server.start().await?;
+    pub fn read_only(mut self, read_only: bool) -> Self {
+        self.read_only = read_only;
+        self
+    }
+
+    pub fn enable_roots(mut self, enable_roots: bool) -> Self {
+        self.enable_roots = enable_roots;
+        self
+    }
 
-    Ok(())
+    pub fn invalid_directory_policy(mut self, policy: InvalidDirectoryPolicy) -> Self {
+        self.invalid_directory_policy = policy;
+        self
+    }
+
+    pub fn performance_config(mut self, config: PerformanceConfig) -> Self {
+        self.performance_config = config;
+        self
+    }
+
+    pub fn redaction_config(mut self, config: RedactionConfig) -> Self {
+        self.redaction_config = config;
+        self
+    }
+
+    /// Sets the default format of human-readable sizes and counts.
+    pub fn byte_format(mut self, format: ByteFormat) -> Self {
+        self.byte_format = format;
+        self
+    }
+
+    pub fn path_suggestions(mut self, path_suggestions: bool) -> Self {
+        self.path_suggestions = path_suggestions;
+        self
+    }
+
+    pub fn result_timings(mut self, result_timings: bool) -> Self {
+        self.result_timings = result_timings;
+        self
+    }
+
+    /// Rejects tool calls with arguments the tool does not know, the default. When disabled,
+    /// such calls run and their result ends with a warning listing the ignored arguments.
+    pub fn strict_params(mut self, strict_params: bool) -> Self {
+        self.strict_params = strict_params;
+        self
+    }
+
+    /// Hides the given tools from `tools/list` and rejects calls to them.
+    pub fn disable_tools<I, S>(mut self, tools: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        self.disabled_tools
+            .extend(tools.into_iter().map(Into::into));
+        self
+    }
+
+    /// Sets server-wide defaults of optional tool arguments. Values passed by clients win.
+    pub fn tool_defaults(mut self, defaults: ToolDefaults) -> Self {
+        self.tool_defaults = defaults;
+        self
+    }
+
+    /// Writes the performance counters to `path`, as a JSON object, when the server shuts down.
+    pub fn metrics_file(mut self, path: impl Into<PathBuf>) -> Self {
+        self.metrics_file = Some(path.into());
+        self
+    }
+
+    pub fn build(self) -> ServiceResult<FileSystemServer> {
+        let known_tools = FileSystemTools::tools();
+        if let Some(unknown) = self
+            .disabled_tools
+            .iter()
+            .find(|name| !known_tools.iter().any(|tool| &tool.name == *name))
+        {
+            return Err(ServiceError::FromString(format!(
+                "Cannot disable unknown tool '{unknown}'."
+            )));
+        }
+
+        let fs_service = FileSystemService::try_new_with_policy(
+            &self.allowed_directories,
+            self.invalid_directory_policy,
+        )?
+        .with_performance_config(self.performance_config)?
+        .with_redaction_config(self.redaction_config)
+        .with_byte_format(self.byte_format)
+        .with_path_suggestions(self.path_suggestions)
+        .with_metrics_file(self.metrics_file);
+
+        Ok(FileSystemServer::new(FileSystemHandler::from_service(
+            fs_service,
+            self.read_only,
+            self.enable_roots,
+            self.result_timings,
+            self.strict_params,
+            self.disabled_tools,
+            self.tool_defaults,
+        )))
+    }
+}
+
+/// A configured filesystem MCP server, ready to be run over a transport.
+pub struct FileSystemServer {
+    handler: FileSystemHandler,
+}
+
+impl FileSystemServer {
+    pub fn new(handler: FileSystemHandler) -> Self {
+        Self { handler }
+    }
+
+    /// The handler serving tool calls, which can also be invoked in-process.
+    pub fn handler(&self) -> &FileSystemHandler {
+        &self.handler
+    }
+
+    /// Serves the MCP protocol over the process's stdin/stdout until the client disconnects
+    /// or a termination signal is received, in which case in-flight tool calls are drained.
+    pub async fn run_stdio(self) -> ServiceResult<()> {
+        let transport = StdioTransport::new(TransportOptions::default())?;
+
+        let fs_service = self.handler.fs_service();
+        let server = server_runtime::create_server(server_details(), transport, self.handler);
+
+        tokio::select! {
+            result = server.start() => result?,
+            _ = termination_signal() => {
+                let result = fs_service.shutdown(SHUTDOWN_TIMEOUT).await;
+                tracing::info!(
+                    "Shut down: {} operation(s) drained, {} timed out",
+                    result.drained_operations, result.timed_out_operations
+                );
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// Resolves on SIGTERM or Ctrl+C.
+async fn termination_signal() {
+    #[cfg(unix)]
+    {
+        use tokio::signal::unix::{SignalKind, signal};
+        match signal(SignalKind::terminate()) {
+            Ok(mut sigterm) => {
+                tokio::select! {
+                    _ = sigterm.recv() => {},
+                    _ = tokio::signal::ctrl_c() => {},
+                }
+            }
+            Err(_) => {
+                let _ = tokio::signal::ctrl_c().await;
+            }
+        }
+    }
+    #[cfg(not(unix))]
+    {
+        let _ = tokio::signal::ctrl_c().await;
+    }
 }
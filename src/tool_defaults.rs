@@ -0,0 +1,217 @@
+use std::{collections::BTreeMap, path::Path};
+
+use rust_mcp_sdk::schema::Tool;
+use serde_json::{Map, Value};
+use toml_edit::{DocumentMut, Item};
+
+use crate::error::{ServiceError, ServiceResult};
+
+/// Name of the configuration file table holding one `[tool_defaults.<tool>]` table per tool.
+pub const TOOL_DEFAULTS_TABLE: &str = "tool_defaults";
+
+/// Server-wide default values of optional tool arguments, read from the configuration file:
+///
+/// ```toml
+/// [tool_defaults.directory_tree]
+/// max_depth = 3
+/// ```
+///
+/// Defaults are merged into the arguments of every call to the tool before they are parsed, so
+/// every tool resolves them the same way, and a value passed by the client always wins. They are
+/// advertised as the `default` of the argument's schema in `tools/list`.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ToolDefaults {
+    tools: BTreeMap<String, Map<String, Value>>,
+}
+
+impl ToolDefaults {
+    /// Reads the defaults from a TOML configuration file.
+    pub fn load(path: &Path) -> ServiceResult<Self> {
+        let source = std::fs::read_to_string(path).map_err(|err| {
+            ServiceError::FromString(format!(
+                "Cannot read the configuration file '{}': {err}",
+                path.display()
+            ))
+        })?;
+        Self::from_toml(&source).map_err(|err| {
+            ServiceError::FromString(format!(
+                "Invalid configuration file '{}': {err}",
+                path.display()
+            ))
+        })
+    }
+
+    /// Parses the `tool_defaults` table of a TOML document. Other tables are ignored.
+    pub fn from_toml(source: &str) -> ServiceResult<Self> {
+        let document = source
+            .parse::<DocumentMut>()
+            .map_err(|err| ServiceError::FromString(err.to_string()))?;
+        let mut defaults = Self::default();
+        let Some(table) = document.get(TOOL_DEFAULTS_TABLE) else {
+            return Ok(defaults);
+        };
+        let table = table.as_table_like().ok_or_else(|| {
+            ServiceError::FromString(format!("'{TOOL_DEFAULTS_TABLE}' must be a table."))
+        })?;
+        for (tool, arguments) in table.iter() {
+            let arguments = arguments.as_table_like().ok_or_else(|| {
+                ServiceError::FromString(format!(
+                    "'{TOOL_DEFAULTS_TABLE}.{tool}' must be a table of argument names and values."
+                ))
+            })?;
+            for (key, value) in arguments.iter() {
+                defaults = defaults.with_default(tool, key, toml_to_json(value));
+            }
+        }
+        Ok(defaults)
+    }
+
+    /// Sets the default of the argument `key` of `tool`.
+    pub fn with_default(mut self, tool: &str, key: &str, value: Value) -> Self {
+        self.tools
+            .entry(tool.to_string())
+            .or_default()
+            .insert(key.to_string(), value);
+        self
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.tools.is_empty()
+    }
+
+    /// The defaults of `tool`'s arguments.
+    pub fn get(&self, tool: &str) -> Option<&Map<String, Value>> {
+        self.tools.get(tool)
+    }
+
+    /// Drops the defaults of unknown tools, of arguments `tools` do not take or require, and of
+    /// values of the wrong type, returning a warning for each.
+    pub fn retain_known(&mut self, tools: &[Tool]) -> Vec<String> {
+        let mut warnings = vec![];
+        self.tools.retain(|name, arguments| {
+            let Some(tool) = tools.iter().find(|tool| &tool.name == name) else {
+                warnings.push(format!("Ignoring defaults of unknown tool '{name}'."));
+                return false;
+            };
+            arguments.retain(|key, value| {
+                let property = tool
+                    .input_schema
+                    .properties
+                    .as_ref()
+                    .and_then(|properties| properties.get(key));
+                let Some(property) = property else {
+                    warnings.push(format!(
+                        "Ignoring default of '{name}.{key}': the tool has no such argument."
+                    ));
+                    return false;
+                };
+                if tool.input_schema.required.contains(key) {
+                    warnings.push(format!(
+                        "Ignoring default of '{name}.{key}': the argument is required."
+                    ));
+                    return false;
+                }
+                if let Some(expected) = property.get("type").and_then(Value::as_str)
+                    && !has_type(value, expected)
+                {
+                    warnings.push(format!(
+                        "Ignoring default of '{name}.{key}': expected a value of type '{expected}', found {value}."
+                    ));
+                    return false;
+                }
+                true
+            });
+            !arguments.is_empty()
+        });
+        warnings
+    }
+
+    /// Adds the defaults of `tool` to `arguments` that were omitted or passed as null.
+    pub fn apply(&self, tool: &str, arguments: &mut Option<Map<String, Value>>) {
+        let Some(defaults) = self.tools.get(tool) else {
+            return;
+        };
+        let arguments = arguments.get_or_insert_with(Map::new);
+        for (key, value) in defaults {
+            match arguments.get(key) {
+                Some(current) if !current.is_null() => {}
+                _ => {
+                    arguments.insert(key.clone(), value.clone());
+                }
+            }
+        }
+    }
+
+    /// Advertises the defaults as the `default` of the arguments' schemas.
+    pub fn annotate(&self, tool: &mut Tool) {
+        let (Some(defaults), Some(properties)) = (
+            self.tools.get(&tool.name),
+            tool.input_schema.properties.as_mut(),
+        ) else {
+            return;
+        };
+        for (key, value) in defaults {
+            if let Some(property) = properties.get_mut(key) {
+                property.insert("default".to_string(), value.clone());
+            }
+        }
+    }
+}
+
+/// Whether `value` is of the JSON schema type `expected`. Unknown types accept any value.
+fn has_type(value: &Value, expected: &str) -> bool {
+    match expected {
+        "integer" => value.is_u64(),
+        "number" => value.is_number(),
+        "boolean" => value.is_boolean(),
+        "string" => value.is_string(),
+        "array" => value.is_array(),
+        "object" => value.is_object(),
+        _ => true,
+    }
+}
+
+fn toml_to_json(item: &Item) -> Value {
+    match item {
+        Item::None => Value::Null,
+        Item::Value(value) => toml_value_to_json(value),
+        Item::Table(table) => Value::Object(
+            table
+                .iter()
+                .map(|(key, item)| (key.to_string(), toml_to_json(item)))
+                .collect(),
+        ),
+        Item::ArrayOfTables(tables) => Value::Array(
+            tables
+                .iter()
+                .map(|table| {
+                    Value::Object(
+                        table
+                            .iter()
+                            .map(|(key, item)| (key.to_string(), toml_to_json(item)))
+                            .collect(),
+                    )
+                })
+                .collect(),
+        ),
+    }
+}
+
+fn toml_value_to_json(value: &toml_edit::Value) -> Value {
+    match value {
+        toml_edit::Value::String(value) => Value::String(value.value().clone()),
+        toml_edit::Value::Integer(value) => Value::from(*value.value()),
+        toml_edit::Value::Float(value) => Value::from(*value.value()),
+        toml_edit::Value::Boolean(value) => Value::Bool(*value.value()),
+        toml_edit::Value::Datetime(value) => Value::String(value.value().to_string()),
+        toml_edit::Value::Array(values) => {
+            Value::Array(values.iter().map(toml_value_to_json).collect())
+        }
+        toml_edit::Value::InlineTable(table) => Value::Object(
+            table
+                .iter()
+                .map(|(key, value)| (key.to_string(), toml_value_to_json(value)))
+                .collect(),
+        ),
+    }
+}
@@ -1,15 +1,36 @@
+mod bulk_insert;
 mod calculate_directory_size;
+mod check_file_lock;
+mod close_file;
+mod concat_files;
+mod conditional_write;
+mod convert_file;
 mod create_directory;
+mod delete_path;
+mod detect_generated_files;
 mod directory_tree;
 mod edit_file;
+mod edit_structured_file;
+mod extract_definition;
 mod find_duplicate_files;
 mod find_empty_directories;
+mod flatten_directory;
 mod get_file_info;
+mod get_performance_counters;
+mod hash_directory;
 mod head_file;
 mod list_allowed_directories;
 mod list_directory;
 mod list_directory_with_sizes;
 mod move_file;
+mod open_file;
+mod path_probe;
+mod preview_glob;
+mod preview_tabular_file;
+mod prune_empty_directories;
+mod read_context_pack;
+mod read_directory;
+mod read_enclosing_block;
 mod read_file_lines;
 mod read_media_file;
 mod read_multiple_media_files;
@@ -17,33 +38,64 @@ mod read_multiple_text_files;
 mod read_text_file;
 mod search_file;
 mod search_files_content;
+mod sort_file;
 mod tail_file;
+mod test_pattern;
 mod write_file;
 mod zip_unzip;
 
+pub use bulk_insert::BulkInsert;
 pub use calculate_directory_size::{CalculateDirectorySize, FileSizeOutputFormat};
+pub use check_file_lock::CheckFileLock;
+pub use close_file::CloseFile;
+pub use concat_files::ConcatFiles;
+pub use conditional_write::{ConditionalWrite, PreconditionKind, WritePrecondition};
+pub use convert_file::{ConvertFile, Indentation, LineEnding, TargetEncoding};
 pub use create_directory::CreateDirectory;
+pub use delete_path::DeletePath;
+pub use detect_generated_files::DetectGeneratedFiles;
 pub use directory_tree::DirectoryTree;
-pub use edit_file::{EditFile, EditOperation};
+pub use edit_file::{CommentStyle, EditFile, EditOperation, LineEditKind, LineEditOperation};
+pub use edit_structured_file::{
+    EditStructuredFile, JsonValue, StructuredEditKind, StructuredEditOperation, StructuredFormat,
+};
+pub use extract_definition::ExtractDefinition;
 pub use find_duplicate_files::FindDuplicateFiles;
 pub use find_empty_directories::FindEmptyDirectories;
+pub use flatten_directory::{CollisionScheme, FlattenDirectory};
 pub use get_file_info::GetFileInfo;
+pub use get_performance_counters::GetPerformanceCounters;
+pub use hash_directory::HashDirectory;
 pub use head_file::HeadFile;
 pub use list_allowed_directories::ListAllowedDirectories;
 pub use list_directory::ListDirectory;
 pub use list_directory_with_sizes::ListDirectoryWithSizes;
 pub use move_file::MoveFile;
+pub use open_file::OpenFile;
+pub use path_probe::PathProbe;
+pub use preview_glob::PreviewGlob;
+pub use preview_tabular_file::PreviewTabularFile;
+pub use prune_empty_directories::PruneEmptyDirectories;
+pub use read_context_pack::ReadContextPack;
+pub use read_directory::ReadDirectory;
+pub use read_enclosing_block::ReadEnclosingBlock;
 pub use read_file_lines::ReadFileLines;
 pub use read_media_file::ReadMediaFile;
 pub use read_multiple_media_files::ReadMultipleMediaFiles;
 pub use read_multiple_text_files::ReadMultipleTextFiles;
-pub use read_text_file::ReadTextFile;
+pub use read_text_file::{ReadFormat, ReadTextFile};
+use rust_mcp_sdk::schema::Tool;
 pub use rust_mcp_sdk::tool_box;
 pub use search_file::SearchFiles;
 pub use search_files_content::SearchFilesContent;
+use serde_json::{Map, Value, json};
+pub use sort_file::SortFile;
 pub use tail_file::TailFile;
+pub use test_pattern::{PatternMode, TestPattern};
 pub use write_file::WriteFile;
-pub use zip_unzip::{UnzipFile, ZipDirectory, ZipFiles};
+pub use zip_unzip::{SymlinkPolicy, UnzipFile, VerifyArchive, ZipDirectory, ZipFiles};
+
+use crate::error::ServiceError;
 //Generate FileSystemTools enum , tools() function, and TryFrom<CallToolRequestParams> trait implementation
 tool_box!(
     FileSystemTools,
@@ -71,19 +123,58 @@ tool_box!(
         ReadFileLines,
         FindEmptyDirectories,
         CalculateDirectorySize,
-        FindDuplicateFiles
+        FindDuplicateFiles,
+        ExtractDefinition,
+        ReadDirectory,
+        PathProbe,
+        OpenFile,
+        CloseFile,
+        PreviewTabularFile,
+        EditStructuredFile,
+        CheckFileLock,
+        HashDirectory,
+        PruneEmptyDirectories,
+        DetectGeneratedFiles,
+        VerifyArchive,
+        ConditionalWrite,
+        TestPattern,
+        DeletePath,
+        ConcatFiles,
+        BulkInsert,
+        ReadEnclosingBlock,
+        GetPerformanceCounters,
+        ConvertFile,
+        FlattenDirectory,
+        ReadContextPack,
+        SortFile,
+        PreviewGlob
     ]
 );
 
 impl FileSystemTools {
     // Determines whether the filesystem tool requires write access to the filesystem.
     // Returns `true` for tools that modify files or directories, and `false` otherwise.
+    // A dry run only reports what would happen, so it is allowed in read-only mode.
     pub fn require_write_access(&self) -> bool {
+        if self.is_dry_run() {
+            return false;
+        }
         match self {
-            FileSystemTools::CreateDirectory(_)
-            | FileSystemTools::MoveFile(_)
+            FileSystemTools::MoveFile(_)
+            | FileSystemTools::PruneEmptyDirectories(_)
+            | FileSystemTools::DeletePath(_)
+            | FileSystemTools::BulkInsert(_)
+            | FileSystemTools::ConvertFile(_)
+            | FileSystemTools::FlattenDirectory(_)
+            | FileSystemTools::SortFile(_)
+            | FileSystemTools::CreateDirectory(_)
             | FileSystemTools::WriteFile(_)
+            | FileSystemTools::ConditionalWrite(_)
+            | FileSystemTools::ConcatFiles(_)
             | FileSystemTools::EditFile(_)
+            | FileSystemTools::EditStructuredFile(_)
+            | FileSystemTools::OpenFile(_)
+            | FileSystemTools::CloseFile(_)
             | FileSystemTools::ZipFiles(_)
             | FileSystemTools::UnzipFile(_)
             | FileSystemTools::ZipDirectory(_) => true,
@@ -103,7 +194,162 @@ impl FileSystemTools {
             | FileSystemTools::FindEmptyDirectories(_)
             | FileSystemTools::CalculateDirectorySize(_)
             | FileSystemTools::FindDuplicateFiles(_)
+            | FileSystemTools::ExtractDefinition(_)
+            | FileSystemTools::ReadDirectory(_)
+            | FileSystemTools::PathProbe(_)
+            | FileSystemTools::PreviewTabularFile(_)
+            | FileSystemTools::CheckFileLock(_)
+            | FileSystemTools::HashDirectory(_)
+            | FileSystemTools::DetectGeneratedFiles(_)
+            | FileSystemTools::VerifyArchive(_)
+            | FileSystemTools::TestPattern(_)
+            | FileSystemTools::ReadEnclosingBlock(_)
+            | FileSystemTools::GetPerformanceCounters(_)
+            | FileSystemTools::ReadContextPack(_)
+            | FileSystemTools::PreviewGlob(_)
             | FileSystemTools::SearchFiles(_) => false,
         }
     }
+
+    // Whether the call is a dry run of a write tool: it previews the changes and leaves the
+    // file system untouched.
+    pub fn is_dry_run(&self) -> bool {
+        let dry_run = match self {
+            FileSystemTools::MoveFile(params) => params.dry_run,
+            FileSystemTools::PruneEmptyDirectories(params) => params.dry_run,
+            FileSystemTools::DeletePath(params) => params.dry_run,
+            FileSystemTools::ConvertFile(params) => params.dry_run,
+            FileSystemTools::FlattenDirectory(params) => params.dry_run,
+            FileSystemTools::SortFile(params) => params.dry_run,
+            FileSystemTools::BulkInsert(params) => params.dry_run,
+            FileSystemTools::EditFile(params) => params.dry_run,
+            FileSystemTools::EditStructuredFile(params) => params.dry_run,
+            _ => None,
+        };
+        dry_run.unwrap_or_default()
+    }
+}
+
+/// Rewrites the `oneOf: [{enum: [a]}, {enum: [b]}]` schemas generated for unit enums into
+/// `type: string, enum: [a, b]`, so clients can discover the accepted values up front. Nested
+/// schemas, such as the fields of the objects of an array, are rewritten too.
+pub fn flatten_enum_schemas(tool: &mut Tool) {
+    let Some(properties) = tool.input_schema.properties.as_mut() else {
+        return;
+    };
+    for property in properties.values_mut() {
+        flatten_enum_schema(property);
+    }
+}
+
+fn flatten_enum_schema(schema: &mut Map<String, Value>) {
+    if let Some(values) = one_of_enum_values(schema) {
+        schema.remove("oneOf");
+        schema.insert("type".to_string(), json!("string"));
+        schema.insert("enum".to_string(), Value::Array(values));
+    }
+    if let Some(items) = schema.get_mut("items").and_then(Value::as_object_mut) {
+        flatten_enum_schema(items);
+    }
+    if let Some(properties) = schema.get_mut("properties").and_then(Value::as_object_mut) {
+        for property in properties.values_mut().filter_map(Value::as_object_mut) {
+            flatten_enum_schema(property);
+        }
+    }
+}
+
+fn one_of_enum_values(property: &Map<String, Value>) -> Option<Vec<Value>> {
+    property
+        .get("oneOf")?
+        .as_array()?
+        .iter()
+        .map(
+            |variant| match variant.get("enum")?.as_array()?.as_slice() {
+                [value] => Some(value.clone()),
+                _ => None,
+            },
+        )
+        .collect()
+}
+
+/// Checks tool call arguments against the `enum` lists of the tool schema and reports the
+/// first argument whose value is not accepted. Array elements and object fields are checked
+/// against their nested schemas, and reported by their path, e.g. `edits[0].op`.
+pub fn check_enum_arguments(
+    tool: &Tool,
+    arguments: &Map<String, Value>,
+) -> std::result::Result<(), ServiceError> {
+    let Some(properties) = &tool.input_schema.properties else {
+        return Ok(());
+    };
+    for (field, value) in arguments {
+        if let Some(property) = properties.get(field) {
+            check_enum_value(field, property, value)?;
+        }
+    }
+    Ok(())
+}
+
+fn check_enum_value(
+    field: &str,
+    schema: &Map<String, Value>,
+    value: &Value,
+) -> std::result::Result<(), ServiceError> {
+    match value {
+        Value::Null => return Ok(()),
+        Value::Array(elements) => {
+            if let Some(items) = schema.get("items").and_then(Value::as_object) {
+                for (index, element) in elements.iter().enumerate() {
+                    check_enum_value(&format!("{field}[{index}]"), items, element)?;
+                }
+            }
+        }
+        Value::Object(fields) => {
+            if let Some(properties) = schema.get("properties").and_then(Value::as_object) {
+                for (name, field_value) in fields {
+                    if let Some(property) = properties.get(name).and_then(Value::as_object) {
+                        check_enum_value(&format!("{field}.{name}"), property, field_value)?;
+                    }
+                }
+            }
+        }
+        _ => {}
+    }
+    let Some(allowed) = schema.get("enum").and_then(Value::as_array) else {
+        return Ok(());
+    };
+    if !allowed.contains(value) {
+        return Err(ServiceError::InvalidEnumValue {
+            field: field.to_string(),
+            value: value.to_string(),
+            expected: allowed
+                .iter()
+                .map(|v| v.as_str().map_or_else(|| v.to_string(), str::to_string))
+                .collect(),
+        });
+    }
+    Ok(())
+}
+
+/// Returns the arguments of a call that are not properties of the tool schema, sorted, along
+/// with the accepted ones. Both are empty when every argument is known.
+pub fn unknown_arguments(
+    tool: &Tool,
+    arguments: &Map<String, Value>,
+) -> (Vec<String>, Vec<String>) {
+    let properties = tool.input_schema.properties.as_ref();
+    let mut unknown: Vec<String> = arguments
+        .keys()
+        .filter(|field| !properties.is_some_and(|properties| properties.contains_key(*field)))
+        .cloned()
+        .collect();
+    if unknown.is_empty() {
+        return (vec![], vec![]);
+    }
+    unknown.sort();
+    let mut expected: Vec<String> = properties
+        .map(|properties| properties.keys().cloned().collect())
+        .unwrap_or_default();
+    expected.sort();
+    (unknown, expected)
 }
@@ -0,0 +1,66 @@
+use std::path::Path;
+
+use rust_mcp_sdk::{
+    macros::{JsonSchema, mcp_tool},
+    schema::{CallToolResult, TextContent, schema_utils::CallToolError},
+};
+
+use crate::fs_service::FileSystemService;
+
+#[mcp_tool(
+    name = "bulk_insert",
+    title = "Insert content into many files",
+    description = concat!("Inserts the same content into every file below a directory that matches a glob, such as a license header or a lint attribute. ",
+    "A 'pattern' holding a '/' is matched against paths relative to 'path' (e.g. `src/**/*.rs`); other patterns are matched against file names, as in search_files. ",
+    "The 'anchor' is where the content goes: 'start', 'end', a line number to insert after (0 is the start), ",
+    "or a regular expression to insert after the first matching line. ",
+    "Files already containing the 'guard' regex are skipped, which makes the insertion safe to repeat; without a guard, files already containing the content are skipped. ",
+    "Files are processed in parallel and each is replaced atomically. ",
+    "Returns a status table of the matched files and a diffstat instead of full diffs. ",
+    "Set 'dry_run' to list the intended changes without writing. Only works within allowed directories."),
+    destructive_hint = false,
+    idempotent_hint = true,
+    open_world_hint = false,
+    read_only_hint = false
+)]
+#[derive(::serde::Deserialize, ::serde::Serialize, Clone, Debug, JsonSchema)]
+pub struct BulkInsert {
+    /// The directory holding the files to change.
+    pub path: String,
+    /// Glob pattern of the files to change, e.g. `src/**/*.rs` or `*.py`.
+    pub pattern: String,
+    /// Optional list of glob patterns to exclude.
+    pub exclude_patterns: Option<Vec<String>>,
+    /// 'start', 'end', a line number to insert after, or a regex to insert after the first matching line.
+    pub anchor: String,
+    /// The content to insert. A line ending is added when missing.
+    pub content: String,
+    /// Regex skipping the files that match it, e.g. the first line of a header. (Default : the content itself)
+    pub guard: Option<String>,
+    /// List the intended changes without writing. (Default : false)
+    pub dry_run: Option<bool>,
+}
+
+impl BulkInsert {
+    pub async fn run_tool(
+        params: Self,
+        context: &FileSystemService,
+    ) -> std::result::Result<CallToolResult, CallToolError> {
+        let report = context
+            .bulk_insert(
+                Path::new(&params.path),
+                &params.pattern,
+                params.exclude_patterns.unwrap_or_default(),
+                &params.anchor,
+                &params.content,
+                params.guard.as_deref(),
+                params.dry_run.unwrap_or_default(),
+            )
+            .await
+            .map_err(CallToolError::new)?;
+
+        Ok(CallToolResult::text_content(vec![TextContent::from(
+            report.render_text(&context.byte_format()),
+        )]))
+    }
+}
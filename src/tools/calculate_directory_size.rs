@@ -1,4 +1,4 @@
-use crate::fs_service::{FileSystemService, utils::format_bytes};
+use crate::fs_service::{FileSystemService, utils::SizeUnits};
 use rust_mcp_sdk::{
     macros::{JsonSchema, mcp_tool},
     schema::{CallToolResult, TextContent, schema_utils::CallToolError},
@@ -18,7 +18,10 @@ pub enum FileSizeOutputFormat {
     title="Calculate directory size",
     description = concat!("Calculates the total size of a directory specified by `root_path`.",
     "It recursively searches for files and sums their sizes. ",
-    "The result can be returned in either a `human-readable` format or as `bytes`, depending on the specified `output_format` argument.",
+    "The result can be returned in either a `human-readable` format or as `bytes`, depending on the specified `output_format` argument. ",
+    "Human-readable sizes use 'size_units' (`binary`: KiB/MiB, `decimal`: 1000-based KB/MB, `compact`: 1024-based KB/MB as in older versions) and 'size_precision' decimals. ",
+    "Set 'allocated_sizes' to also report the disk space allocated to the files, which is much smaller than their size for sparse files; ",
+    "the result then has a 'size:' and an 'allocated:' line. ",
     "Only works within allowed directories."),
     destructive_hint = false,
     idempotent_hint = false,
@@ -32,6 +35,12 @@ pub struct CalculateDirectorySize {
     /// Defines the output format, which can be either `human-readable` or `bytes`.
     #[json_schema(default = "human-readable")]
     pub output_format: Option<FileSizeOutputFormat>,
+    /// Units of human-readable sizes: `binary`, `decimal` or `compact` (default: the server setting, `binary` unless configured).
+    pub size_units: Option<SizeUnits>,
+    /// Number of decimals of human-readable sizes, at most 6 (default: 2).
+    pub size_precision: Option<u64>,
+    /// Also report the disk space allocated to the files (Unix: blocks * 512) (default: false).
+    pub allocated_sizes: Option<bool>,
 }
 
 impl CalculateDirectorySize {
@@ -39,17 +48,30 @@ impl CalculateDirectorySize {
         params: Self,
         context: &FileSystemService,
     ) -> std::result::Result<CallToolResult, CallToolError> {
-        let total_bytes = context
-            .calculate_directory_size(Path::new(&params.root_path))
+        let usage = context
+            .calculate_directory_usage(Path::new(&params.root_path))
             .await
             .map_err(CallToolError::new)?;
 
-        let output_content = match params
+        let byte_format = context
+            .byte_format()
+            .with_overrides(params.size_units, params.size_precision);
+        let format = |bytes: u64| match params
             .output_format
+            .clone()
             .unwrap_or(FileSizeOutputFormat::HumanReadable)
         {
-            FileSizeOutputFormat::HumanReadable => format_bytes(total_bytes),
-            FileSizeOutputFormat::Bytes => format!("{total_bytes}"),
+            FileSizeOutputFormat::HumanReadable => byte_format.format(bytes),
+            FileSizeOutputFormat::Bytes => format!("{bytes}"),
+        };
+        let output_content = if params.allocated_sizes.unwrap_or_default() {
+            format!(
+                "size: {}\nallocated: {}",
+                format(usage.bytes),
+                format(usage.allocated_bytes)
+            )
+        } else {
+            format(usage.bytes)
         };
 
         Ok(CallToolResult::text_content(vec![TextContent::from(
@@ -0,0 +1,44 @@
+use std::path::Path;
+
+use rust_mcp_sdk::{
+    macros::{JsonSchema, mcp_tool},
+    schema::{CallToolResult, TextContent, schema_utils::CallToolError},
+};
+
+use crate::fs_service::FileSystemService;
+
+#[mcp_tool(
+    name = "check_file_lock",
+    title = "Check whether a file is locked or open",
+    description = concat!("Reports, best effort, whether a file is locked or held open by another process before editing it. ",
+    "On Unix it probes advisory (flock) and POSIX record locks, and on Linux lists the processes with the file open; ",
+    "on Windows it attempts an exclusive open. Files buffered by an 'open_file' handle of this server are reported too. ",
+    "A file reported as not in use may still be opened by a program that takes no lock. ",
+    "Only works within allowed directories."),
+    destructive_hint = false,
+    idempotent_hint = false,
+    open_world_hint = false,
+    read_only_hint = true
+)]
+#[derive(::serde::Deserialize, ::serde::Serialize, Clone, Debug, JsonSchema)]
+pub struct CheckFileLock {
+    /// The path of the file to check.
+    pub path: String,
+}
+
+impl CheckFileLock {
+    pub async fn run_tool(
+        params: Self,
+        context: &FileSystemService,
+    ) -> std::result::Result<CallToolResult, CallToolError> {
+        let path = Path::new(&params.path);
+        let report = context
+            .check_file_lock(path)
+            .await
+            .map_err(CallToolError::new)?;
+
+        Ok(CallToolResult::text_content(vec![TextContent::from(
+            report.render_text(path),
+        )]))
+    }
+}
@@ -0,0 +1,59 @@
+use rust_mcp_sdk::{
+    macros::{JsonSchema, mcp_tool},
+    schema::TextContent,
+};
+
+use rust_mcp_sdk::schema::{CallToolResult, schema_utils::CallToolError};
+
+use crate::fs_service::FileSystemService;
+
+#[mcp_tool(
+    name = "close_file",
+    title="Close file",
+    description = concat!("Closes a handle returned by 'open_file'. ",
+"If the file was edited while open, its buffer is written back to disk in a single write. ",
+"If the file changed on disk since it was opened, nothing is written and the handle stays open. ",
+"Set 'discard' to drop the buffered edits instead."),
+    destructive_hint = true,
+    idempotent_hint = false,
+    open_world_hint = false,
+    read_only_hint = false
+)]
+#[derive(Debug, Clone, ::serde::Deserialize, ::serde::Serialize, JsonSchema)]
+pub struct CloseFile {
+    /// The handle returned by 'open_file'.
+    pub handle: String,
+    /// Drop the buffered edits without writing them (default: false).
+    #[serde(default, skip_serializing_if = "std::option::Option::is_none")]
+    pub discard: Option<bool>,
+}
+
+impl CloseFile {
+    pub async fn run_tool(
+        params: Self,
+        context: &FileSystemService,
+    ) -> std::result::Result<CallToolResult, CallToolError> {
+        let closed = context
+            .close_file(&params.handle, params.discard.unwrap_or_default())
+            .await
+            .map_err(CallToolError::new)?;
+
+        let message = match closed.bytes_written {
+            Some(bytes) => format!(
+                "Closed {}: wrote {} edit(s) ({} bytes).",
+                closed.path.display(),
+                closed.edits,
+                bytes
+            ),
+            None if closed.edits > 0 => format!(
+                "Closed {}: discarded {} edit(s).",
+                closed.path.display(),
+                closed.edits
+            ),
+            None => format!("Closed {}: no changes.", closed.path.display()),
+        };
+        Ok(CallToolResult::text_content(vec![TextContent::from(
+            message,
+        )]))
+    }
+}
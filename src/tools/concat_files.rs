@@ -0,0 +1,62 @@
+use std::path::Path;
+
+use rust_mcp_sdk::{
+    macros::{JsonSchema, mcp_tool},
+    schema::{CallToolResult, TextContent, schema_utils::CallToolError},
+};
+
+use crate::fs_service::FileSystemService;
+
+#[mcp_tool(
+    name = "concat_files",
+    title = "Join files",
+    description = concat!("Joins multiple files into one destination file, in the given order. ",
+    "Each source is streamed into the destination, so large files are not loaded into memory. ",
+    "An optional 'separator' is inserted between consecutive sources (not after the last one). ",
+    "All paths are validated and every source must exist before anything is written. ",
+    "The destination is created or overwritten, and cannot be one of the sources. ",
+    "Only works within allowed directories."),
+    destructive_hint = true,
+    idempotent_hint = true,
+    open_world_hint = false,
+    read_only_hint = false
+)]
+#[derive(::serde::Deserialize, ::serde::Serialize, Clone, Debug, JsonSchema)]
+pub struct ConcatFiles {
+    /// The files to join, in order.
+    pub sources: Vec<String>,
+    /// The path of the file to write the joined content to.
+    pub destination: String,
+    /// Text inserted between consecutive sources, such as a newline.
+    pub separator: Option<String>,
+}
+
+impl ConcatFiles {
+    pub async fn run_tool(
+        params: Self,
+        context: &FileSystemService,
+    ) -> std::result::Result<CallToolResult, CallToolError> {
+        let written = context
+            .concat_files(
+                &params.sources,
+                Path::new(&params.destination),
+                params.separator.as_deref(),
+            )
+            .await
+            .map_err(CallToolError::new)?;
+
+        Ok(CallToolResult::text_content(vec![TextContent::from(
+            format!(
+                "Successfully joined {} {} into {} ({}).",
+                params.sources.len(),
+                if params.sources.len() == 1 {
+                    "file"
+                } else {
+                    "files"
+                },
+                params.destination,
+                context.byte_format().format(written)
+            ),
+        )]))
+    }
+}
@@ -0,0 +1,82 @@
+use std::path::Path;
+
+use rust_mcp_sdk::{
+    macros::{JsonSchema, mcp_tool},
+    schema::{CallToolResult, TextContent, schema_utils::CallToolError},
+};
+
+use crate::fs_service::{ConditionalWriteOutcome, FileSystemService};
+
+#[derive(
+    ::serde::Deserialize, ::serde::Serialize, Clone, Copy, Debug, PartialEq, Eq, JsonSchema,
+)]
+/// The condition on the current file that allows a conditional write.
+pub enum PreconditionKind {
+    /// The file exists and the regular expression in `value` matches somewhere in its content.
+    #[serde(rename = "regex")]
+    Regex,
+    /// The file exists and its content is exactly `value`.
+    #[serde(rename = "exact")]
+    Exact,
+    /// Nothing exists at the path.
+    #[serde(rename = "not_exists")]
+    NotExists,
+}
+
+#[derive(::serde::Deserialize, ::serde::Serialize, Clone, Debug, JsonSchema)]
+/// A condition checked against the current file before writing.
+pub struct WritePrecondition {
+    /// Either `regex`, `exact` or `not_exists`.
+    pub kind: PreconditionKind,
+    /// The regular expression for `regex`, the expected content for `exact`. Unused for `not_exists`.
+    pub value: Option<String>,
+}
+
+#[mcp_tool(
+    name = "conditional_write",
+    title = "Write file if a condition holds",
+    description = concat!("Writes a file only if a precondition on its current state holds, for idempotent provisioning. ",
+    "The precondition is one of: 'regex' (the file exists and the regular expression matches its content), ",
+    "'exact' (the file exists and its content is exactly the given value) or 'not_exists' (nothing exists at the path). ",
+    "When the precondition holds, the whole file is replaced with 'content'; otherwise nothing is written ",
+    "and the result starts with 'Skipped' followed by the reason. Only works within allowed directories."),
+    destructive_hint = true,
+    idempotent_hint = false,
+    open_world_hint = false,
+    read_only_hint = false
+)]
+#[derive(::serde::Deserialize, ::serde::Serialize, Clone, Debug, JsonSchema)]
+pub struct ConditionalWrite {
+    /// The path of the file to write to.
+    pub path: String,
+    /// The content to write to the file.
+    pub content: String,
+    /// The condition the current file must meet for the write to happen.
+    pub precondition: WritePrecondition,
+}
+
+impl ConditionalWrite {
+    pub async fn run_tool(
+        params: Self,
+        context: &FileSystemService,
+    ) -> std::result::Result<CallToolResult, CallToolError> {
+        let outcome = context
+            .write_file_if(
+                Path::new(&params.path),
+                &params.content,
+                &params.precondition,
+            )
+            .await
+            .map_err(CallToolError::new)?;
+
+        let message = match outcome {
+            ConditionalWriteOutcome::Written => format!("Successfully wrote to {}", params.path),
+            ConditionalWriteOutcome::Skipped(reason) => {
+                format!("Skipped writing to {}: {reason}", params.path)
+            }
+        };
+        Ok(CallToolResult::text_content(vec![TextContent::from(
+            message,
+        )]))
+    }
+}
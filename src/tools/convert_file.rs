@@ -0,0 +1,119 @@
+use std::path::Path;
+
+use rust_mcp_sdk::{
+    macros::{JsonSchema, mcp_tool},
+    schema::{CallToolResult, TextContent, schema_utils::CallToolError},
+};
+
+use crate::fs_service::FileSystemService;
+
+#[derive(
+    ::serde::Deserialize, ::serde::Serialize, Clone, Copy, Debug, PartialEq, Eq, JsonSchema,
+)]
+/// The line ending a file is converted to.
+pub enum LineEnding {
+    #[serde(rename = "lf")]
+    Lf,
+    #[serde(rename = "crlf")]
+    Crlf,
+}
+
+impl LineEnding {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Lf => "\n",
+            Self::Crlf => "\r\n",
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Lf => "LF",
+            Self::Crlf => "CRLF",
+        }
+    }
+}
+
+#[derive(
+    ::serde::Deserialize, ::serde::Serialize, Clone, Copy, Debug, PartialEq, Eq, JsonSchema,
+)]
+/// The encoding a file is converted to.
+pub enum TargetEncoding {
+    /// UTF-8 without a byte order mark.
+    #[serde(rename = "utf-8")]
+    Utf8,
+    /// UTF-8 starting with a byte order mark.
+    #[serde(rename = "utf-8-bom")]
+    Utf8Bom,
+}
+
+#[derive(
+    ::serde::Deserialize, ::serde::Serialize, Clone, Copy, Debug, PartialEq, Eq, JsonSchema,
+)]
+/// What leading whitespace is converted to.
+pub enum Indentation {
+    #[serde(rename = "spaces")]
+    Spaces,
+    #[serde(rename = "tabs")]
+    Tabs,
+}
+
+#[mcp_tool(
+    name = "convert_file",
+    title = "Convert line endings, encoding and indentation",
+    description = concat!("Normalizes a text file by applying any combination of: ",
+    "'line_ending' ('lf' or 'crlf') for every line; ",
+    "'encoding' ('utf-8' or 'utf-8-bom'), converting from the detected encoding, such as ISO-8859-1 or UTF-16, or adding or removing the byte order mark; ",
+    "'indentation' ('spaces' or 'tabs') for leading whitespace, tab stops being 'indent_width' columns apart; ",
+    "and 'strip_trailing_whitespace'. ",
+    "Only leading whitespace is re-indented. In source files of a recognized language, lines starting inside a multi-line string literal are left alone, ",
+    "as is the trailing whitespace of lines ending inside one; other files are re-indented on leading whitespace only. ",
+    "Files in another encoding than UTF-8 need 'encoding' to be set, since the result is always written as UTF-8. ",
+    "The file is replaced atomically. Returns a diff and which transformations changed anything. ",
+    "Set 'dry_run' to preview the changes without writing. Only works within allowed directories."),
+    destructive_hint = true,
+    idempotent_hint = true,
+    open_world_hint = false,
+    read_only_hint = false
+)]
+#[derive(::serde::Deserialize, ::serde::Serialize, Clone, Debug, JsonSchema)]
+pub struct ConvertFile {
+    /// The path of the file to convert.
+    pub path: String,
+    /// Convert every line ending to 'lf' or 'crlf'.
+    pub line_ending: Option<LineEnding>,
+    /// Convert the file to 'utf-8' or 'utf-8-bom'.
+    pub encoding: Option<TargetEncoding>,
+    /// Convert leading whitespace to 'spaces' or 'tabs'.
+    pub indentation: Option<Indentation>,
+    /// Columns between tab stops when converting indentation, from 1 to 16. (Default : 4)
+    pub indent_width: Option<u64>,
+    /// Remove the spaces and tabs ending each line. (Default : false)
+    pub strip_trailing_whitespace: Option<bool>,
+    /// Preview the changes without writing. (Default : false)
+    pub dry_run: Option<bool>,
+}
+
+impl ConvertFile {
+    pub async fn run_tool(
+        params: Self,
+        context: &FileSystemService,
+    ) -> std::result::Result<CallToolResult, CallToolError> {
+        let result = context
+            .convert_file(
+                Path::new(&params.path),
+                params.line_ending,
+                params.encoding,
+                params.indentation,
+                usize::try_from(params.indent_width.unwrap_or(4)).unwrap_or(usize::MAX),
+                params.strip_trailing_whitespace.unwrap_or_default(),
+                params.dry_run.unwrap_or_default(),
+            )
+            .await
+            .map_err(CallToolError::new)?;
+
+        Ok(CallToolResult::text_content(vec![TextContent::from(
+            result,
+        )]))
+    }
+}
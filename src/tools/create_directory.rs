@@ -13,9 +13,10 @@ use crate::fs_service::FileSystemService;
     "Can create multiple nested directories in one operation. ",
     "If the directory already exists, this operation will succeed silently. ",
     "Perfect for setting up directory structures for projects or ensuring required paths exist. ",
+    "Set 'atomic' to true to remove any directories created by the call if it fails partway. ",
     "Only works within allowed directories."),
     destructive_hint = false,
-    idempotent_hint = false,
+    idempotent_hint = true,
     open_world_hint = false,
     read_only_hint = false
 )]
@@ -23,6 +24,8 @@ use crate::fs_service::FileSystemService;
 pub struct CreateDirectory {
     /// The path where the directory will be created.
     pub path: String,
+    /// Remove the directories created by this call if creating a nested component fails. (Default : false)
+    pub atomic: Option<bool>,
 }
 
 impl CreateDirectory {
@@ -30,10 +33,13 @@ impl CreateDirectory {
         params: Self,
         context: &FileSystemService,
     ) -> std::result::Result<CallToolResult, CallToolError> {
-        context
-            .create_directory(Path::new(&params.path))
-            .await
-            .map_err(CallToolError::new)?;
+        let path = Path::new(&params.path);
+        if params.atomic.unwrap_or_default() {
+            context.create_directory_atomic(path).await
+        } else {
+            context.create_directory(path).await
+        }
+        .map_err(CallToolError::new)?;
 
         Ok(CallToolResult::text_content(vec![TextContent::from(
             format!("Successfully created directory {}", &params.path),
@@ -0,0 +1,66 @@
+use std::path::Path;
+
+use rust_mcp_sdk::{
+    macros::{JsonSchema, mcp_tool},
+    schema::{CallToolResult, TextContent, schema_utils::CallToolError},
+};
+
+use crate::error::ServiceError;
+use crate::fs_service::FileSystemService;
+
+#[mcp_tool(
+    name = "delete_path",
+    title = "Delete a file or directory",
+    description = concat!("Deletes a file, a symbolic link (never its target) or a directory. ",
+    "A directory that is not empty is only deleted with 'recursive' set. ",
+    "Before anything is removed, the files, directories and bytes to delete are counted, and the counts are included in the result. ",
+    "Some targets are refused: an allowed directory or a directory holding one, a directory holding a git repository ",
+    "(a `.git` folder) unless 'allow_vcs' is set, and a directory holding more entries than the server's limit unless 'force' is set. ",
+    "Entries are removed depth first; an entry that cannot be removed does not stop the delete, and the failures are listed at the end. ",
+    "Set 'dry_run' to only count what would be deleted. Only works within allowed directories."),
+    destructive_hint = true,
+    idempotent_hint = false,
+    open_world_hint = false,
+    read_only_hint = false
+)]
+#[derive(::serde::Deserialize, ::serde::Serialize, Clone, Debug, JsonSchema)]
+pub struct DeletePath {
+    /// The path of the file or directory to delete.
+    pub path: String,
+    /// Delete a directory with its contents. (Default : false)
+    pub recursive: Option<bool>,
+    /// Allow deleting directories that hold a git repository. (Default : false)
+    pub allow_vcs: Option<bool>,
+    /// Allow deleting directories holding more entries than the server's limit. (Default : false)
+    pub force: Option<bool>,
+    /// Count what would be deleted without deleting anything. (Default : false)
+    pub dry_run: Option<bool>,
+}
+
+impl DeletePath {
+    pub async fn run_tool(
+        params: Self,
+        context: &FileSystemService,
+    ) -> std::result::Result<CallToolResult, CallToolError> {
+        let report = context
+            .delete_path(
+                Path::new(&params.path),
+                params.recursive.unwrap_or_default(),
+                params.allow_vcs.unwrap_or_default(),
+                params.force.unwrap_or_default(),
+                params.dry_run.unwrap_or_default(),
+            )
+            .await
+            .map_err(CallToolError::new)?;
+
+        let output = report.render_text(&context.byte_format());
+        if !report.is_complete() {
+            return Ok(CallToolResult::with_error(CallToolError::new(
+                ServiceError::FromString(output),
+            )));
+        }
+        Ok(CallToolResult::text_content(vec![TextContent::from(
+            output,
+        )]))
+    }
+}
@@ -0,0 +1,73 @@
+use std::fmt::Write;
+use std::path::Path;
+
+use rust_mcp_sdk::{
+    macros::{JsonSchema, mcp_tool},
+    schema::{CallToolResult, TextContent, schema_utils::CallToolError},
+};
+
+use crate::fs_service::FileSystemService;
+
+#[mcp_tool(
+    name = "detect_generated_files",
+    title = "Detect generated or minified files",
+    description = concat!("Scans a directory for files that look minified or generated, and are better left alone than edited by hand. ",
+    "A file is flagged when it contains an `@generated` marker or a 'DO NOT EDIT' banner near the top, ",
+    "when its average line length is very long, or when nearly all of its characters are not whitespace. ",
+    "Returns the flagged paths with the reasons and the longest line length. Only the start of each file is read, and binary files are skipped. ",
+    "The optional 'pattern' and 'exclude_patterns' arguments accept glob patterns to select the files scanned. ",
+    "Only works within allowed directories."),
+    destructive_hint = false,
+    idempotent_hint = true,
+    open_world_hint = false,
+    read_only_hint = true
+)]
+#[derive(::serde::Deserialize, ::serde::Serialize, Clone, Debug, JsonSchema)]
+pub struct DetectGeneratedFiles {
+    /// The directory to scan.
+    pub path: String,
+    /// Optional glob pattern of the file names to scan (e.g. `*.js`). (Default : all files)
+    pub pattern: Option<String>,
+    /// Optional list of glob patterns to exclude from the scan.
+    pub exclude_patterns: Option<Vec<String>>,
+}
+
+impl DetectGeneratedFiles {
+    pub async fn run_tool(
+        params: Self,
+        context: &FileSystemService,
+    ) -> std::result::Result<CallToolResult, CallToolError> {
+        let flagged = context
+            .detect_generated_files(
+                Path::new(&params.path),
+                params.pattern,
+                params.exclude_patterns,
+            )
+            .await
+            .map_err(CallToolError::new)?;
+
+        let mut output = if flagged.is_empty() {
+            "No generated or minified files were found.".to_string()
+        } else {
+            format!(
+                "Found {} likely generated or minified {}:\n",
+                flagged.len(),
+                if flagged.len() == 1 { "file" } else { "files" }
+            )
+        };
+        for file in &flagged {
+            writeln!(
+                output,
+                "  {}: {} (longest line {} characters)",
+                file.path.display(),
+                file.reasons.join("; "),
+                file.longest_line
+            )
+            .map_err(CallToolError::new)?;
+        }
+
+        Ok(CallToolResult::text_content(vec![TextContent::from(
+            output,
+        )]))
+    }
+}
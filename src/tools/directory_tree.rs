@@ -1,10 +1,14 @@
+use std::path::Path;
+
 use rust_mcp_sdk::macros::{JsonSchema, mcp_tool};
 use rust_mcp_sdk::schema::TextContent;
 use rust_mcp_sdk::schema::{CallToolResult, schema_utils::CallToolError};
 use serde_json::{Map, Value, json};
 
 use crate::error::ServiceError;
-use crate::fs_service::FileSystemService;
+use crate::fs_service::git_status::MAX_GIT_STATUS_ENTRIES;
+use crate::fs_service::utils::{ListingOrder, SizeUnits, SortOrder, add_tree_paths};
+use crate::fs_service::{DirectoryTreeSummary, FileSystemService};
 
 #[mcp_tool(
     name = "directory_tree",
@@ -14,7 +18,18 @@ use crate::fs_service::FileSystemService;
     "Files have no children array, while directories always have a children array (which may be empty). ",
     "If the 'max_depth' parameter is provided, the traversal will be limited to the specified depth. ",
     "As a result, the returned directory structure may be incomplete or provide a skewed representation of the full directory tree, since deeper-level files and subdirectories beyond the specified depth will be excluded. ",
-    "The output is formatted with 2-space indentation for readability. Only works within allowed directories."),
+    "The output is formatted with 2-space indentation for readability. ",
+    "Unless 'show_summary' is false, a summary line with file and directory counts, total size and depth is returned in a second text block, ",
+    "and the same figures are returned in a 'summary' object of the result metadata. ",
+    "Entries that cannot be read (permission errors, broken symlinks, filesystem loops, files removed during the walk) are counted by kind in the 'error_counts' of the summary metadata and listed in an 'Errors:' section of the second text block, ",
+    "or fail the call when 'strict' is true. ",
+    "When 'git_status' is true and the directory is inside a git working tree, entries get a 'git_status' field ",
+    "(modified, staged, untracked or ignored); unchanged entries have none. ",
+    "Entries are sorted in natural order (case-insensitive, 'file2' before 'file10'); set 'sort' to `lexicographic` for plain byte order ",
+"and 'dirs_first' to list directories before files. ",
+    "Set 'absolute_paths' to add a 'path' field with the absolute canonical path of every entry. ",
+    "The total size in the summary uses 'size_units' (`binary`: KiB/MiB, `decimal`: 1000-based KB/MB, `compact`: 1024-based KB/MB as in older versions) and 'size_precision' decimals. ",
+    "Only works within allowed directories."),
     destructive_hint = false,
     idempotent_hint = false,
     open_world_hint = false,
@@ -26,6 +41,23 @@ pub struct DirectoryTree {
     pub path: String,
     /// Limits the depth of directory traversal
     pub max_depth: Option<u64>,
+    /// Add a summary line with file and directory counts, total size and depth (default: true).
+    #[json_schema(default = true)]
+    pub show_summary: Option<bool>,
+    /// Fail instead of returning a partial tree when any entry cannot be read (default: false).
+    pub strict: Option<bool>,
+    /// Annotate entries with their git status (default: false).
+    pub git_status: Option<bool>,
+    /// Name ordering, either `natural` or `lexicographic` (default: natural).
+    pub sort: Option<SortOrder>,
+    /// List directories before files (default: false).
+    pub dirs_first: Option<bool>,
+    /// Units of the total size: `binary`, `decimal` or `compact` (default: the server setting, `binary` unless configured).
+    pub size_units: Option<SizeUnits>,
+    /// Number of decimals of the total size, at most 6 (default: 2).
+    pub size_precision: Option<u64>,
+    /// Add the absolute canonical path of every entry in a 'path' field (default: false).
+    pub absolute_paths: Option<bool>,
 }
 impl DirectoryTree {
     pub async fn run_tool(
@@ -33,30 +65,104 @@ impl DirectoryTree {
         context: &FileSystemService,
     ) -> std::result::Result<CallToolResult, CallToolError> {
         let mut entry_counter: usize = 0;
+        let mut summary = DirectoryTreeSummary::default();
 
         let allowed_directories = context.allowed_directories().await;
 
-        let (entries, reached_max_depth) = context
+        let (mut entries, reached_max_depth) = context
             .directory_tree(
-                params.path,
+                &params.path,
                 params.max_depth.map(|v| v as usize),
                 None,
                 &mut entry_counter,
                 allowed_directories,
+                &mut summary,
             )
             .map_err(CallToolError::new)?;
 
+        if params.strict.unwrap_or_default() {
+            summary
+                .walk_errors
+                .check_strict()
+                .map_err(CallToolError::new)?;
+        }
+
         if entry_counter == 0 {
             return Err(CallToolError::new(ServiceError::FromString(
                 "Could not find any entries".to_string(),
             )));
         }
 
+        ListingOrder::new(params.sort, params.dirs_first).sort_tree(&mut entries);
+
+        context.ensure_running().map_err(CallToolError::new)?;
+        let git_status = if params.git_status.unwrap_or_default() {
+            context
+                .git_status(Path::new(&params.path))
+                .await
+                .map_err(CallToolError::new)?
+        } else {
+            None
+        };
+        if let Some(git_status) = &git_status {
+            git_status.annotate_tree(&mut entries);
+        }
+        if params.absolute_paths.unwrap_or_default() {
+            let root = context
+                .canonical_directory(Path::new(&params.path))
+                .await
+                .map_err(CallToolError::new)?;
+            add_tree_paths(&root, &mut entries);
+        }
+
         let json_str = serde_json::to_string_pretty(&json!(entries)).map_err(CallToolError::new)?;
 
+        let mut meta = Map::new();
+        // Notes go in a second text block, keeping the first one valid JSON
+        let mut notes = vec![];
+
+        if params.show_summary.unwrap_or(true) {
+            let byte_format = context
+                .byte_format()
+                .with_overrides(params.size_units, params.size_precision);
+            notes.push(format!(
+                "Total: {} files, {} directories, {} across {} levels",
+                byte_format.format_count(summary.file_count),
+                byte_format.format_count(summary.dir_count),
+                byte_format.format(summary.total_size_bytes),
+                summary.max_depth
+            ));
+            let mut summary_meta = json!({
+                "file_count": summary.file_count,
+                "dir_count": summary.dir_count,
+                "total_size_bytes": summary.total_size_bytes,
+                "max_depth": summary.max_depth,
+            });
+            if !summary.walk_errors.is_empty() {
+                summary_meta["error_count"] = json!(summary.walk_errors.len());
+                summary_meta["error_counts"] = json!(Map::from_iter(
+                    summary
+                        .walk_errors
+                        .counts_by_kind()
+                        .into_iter()
+                        .map(|(kind, count)| (kind, json!(count)))
+                ));
+            }
+            meta.insert("summary".to_string(), summary_meta);
+        }
+
+        if !summary.walk_errors.is_empty() {
+            notes.push(summary.walk_errors.render());
+        }
+
+        if git_status.is_some_and(|git_status| git_status.truncated) {
+            notes.push(format!(
+                "Git status: only the first {MAX_GIT_STATUS_ENTRIES} changed paths are annotated."
+            ));
+        }
+
         // Include meta flag to denote that max depth was hit; some files and directories might be omitted
-        let meta = if reached_max_depth {
-            let mut meta = Map::new();
+        if reached_max_depth {
             meta.insert(
                 "warning".to_string(),
                 Value::String(
@@ -64,11 +170,15 @@ impl DirectoryTree {
                         .to_string(),
                 ),
             );
-            Some(meta)
-        } else {
-            None
-        };
+        }
+
+        let meta = (!meta.is_empty()).then_some(meta);
+
+        let mut content = vec![TextContent::from(json_str)];
+        if !notes.is_empty() {
+            content.push(TextContent::from(notes.join("\n")));
+        }
 
-        Ok(CallToolResult::text_content(vec![TextContent::from(json_str)]).with_meta(meta))
+        Ok(CallToolResult::text_content(content).with_meta(meta))
     }
 }
@@ -12,9 +12,97 @@ pub struct EditOperation {
     /// Text to search for - must match exactly.
     #[serde(rename = "oldText")]
     pub old_text: String,
-    #[serde(rename = "newText")]
-    /// Text to replace the matched text with.
+    #[serde(rename = "newText", default)]
+    /// Text to replace the matched text with. Leave empty when 'newTextFromFile' is set.
     pub new_text: String,
+    /// Path of a file, within the allowed directories, whose content is used as the replacement
+    /// text. Lets large replacements be uploaded once and referenced from several edits.
+    #[serde(
+        rename = "newTextFromFile",
+        default,
+        skip_serializing_if = "std::option::Option::is_none"
+    )]
+    pub new_text_from_file: Option<String>,
+    /// Trim leading and trailing whitespace from 'oldText' before matching, for search text
+    /// padded by accident. A note is added to the result when this changes it. (Default: false)
+    #[serde(
+        rename = "trimSearch",
+        default,
+        skip_serializing_if = "std::option::Option::is_none"
+    )]
+    pub trim_search: Option<bool>,
+}
+
+#[derive(
+    ::serde::Deserialize, ::serde::Serialize, Clone, Copy, Debug, PartialEq, Eq, JsonSchema,
+)]
+/// The kind of a line-based edit.
+pub enum LineEditKind {
+    #[serde(rename = "comment_lines")]
+    CommentLines,
+    #[serde(rename = "uncomment_lines")]
+    UncommentLines,
+}
+
+#[derive(
+    ::serde::Deserialize, ::serde::Serialize, Clone, Copy, Debug, PartialEq, Eq, JsonSchema,
+)]
+/// Comment syntax used by a line-based edit.
+pub enum CommentStyle {
+    #[serde(rename = "//")]
+    DoubleSlash,
+    #[serde(rename = "#")]
+    Hash,
+    #[serde(rename = "--")]
+    DoubleDash,
+    #[serde(rename = "<!-- -->")]
+    Html,
+}
+
+impl CommentStyle {
+    /// Detects the comment style from the file extension.
+    pub fn from_path(path: &Path) -> Option<Self> {
+        let extension = path.extension()?.to_str()?.to_lowercase();
+        let style = match extension.as_str() {
+            "rs" | "c" | "h" | "cc" | "cpp" | "hpp" | "cs" | "java" | "kt" | "kts" | "scala"
+            | "go" | "swift" | "js" | "jsx" | "mjs" | "cjs" | "ts" | "tsx" | "dart" | "php"
+            | "scss" | "less" | "jsonc" | "proto" => CommentStyle::DoubleSlash,
+            "py" | "pyi" | "sh" | "bash" | "zsh" | "fish" | "rb" | "pl" | "r" | "toml" | "yaml"
+            | "yml" | "ps1" | "conf" | "cfg" | "mk" | "dockerfile" | "nix" => CommentStyle::Hash,
+            "sql" | "lua" | "hs" | "elm" | "ada" => CommentStyle::DoubleDash,
+            "html" | "htm" | "xml" | "xhtml" | "svg" | "md" | "markdown" | "vue" => {
+                CommentStyle::Html
+            }
+            _ => return None,
+        };
+        Some(style)
+    }
+
+    /// Returns the opening token and, for block comment styles, the closing token.
+    pub fn tokens(&self) -> (&'static str, Option<&'static str>) {
+        match self {
+            CommentStyle::DoubleSlash => ("//", None),
+            CommentStyle::Hash => ("#", None),
+            CommentStyle::DoubleDash => ("--", None),
+            CommentStyle::Html => ("<!--", Some("-->")),
+        }
+    }
+}
+
+#[derive(::serde::Deserialize, ::serde::Serialize, Clone, Debug, JsonSchema)]
+/// Comments out or uncomments a range of lines.
+pub struct LineEditOperation {
+    /// Either `comment_lines` or `uncomment_lines`.
+    pub operation: LineEditKind,
+    /// First line of the range (1-based, inclusive).
+    #[serde(rename = "startLine")]
+    pub start_line: u64,
+    /// Last line of the range (1-based, inclusive).
+    #[serde(rename = "endLine")]
+    pub end_line: u64,
+    /// Comment syntax to use. Detected from the file extension when omitted.
+    #[serde(rename = "commentStyle")]
+    pub comment_style: Option<CommentStyle>,
 }
 
 #[mcp_tool(
@@ -23,8 +111,14 @@ pub struct EditOperation {
     description = concat!("Make line-based edits to a text file. ",
     "Each edit replaces exact line sequences with new content. ",
     "Returns a git-style diff showing the changes made. ",
+    "An edit can take its replacement from a file via 'newTextFromFile' instead of 'newText'. ",
+    "'lineEdits' can comment out or uncomment ranges of lines ('comment_lines' / 'uncomment_lines'); ",
+    "they are applied after 'edits', and their line numbers refer to the content at that point. ",
+    "If the file is open (see 'open_file'), the edits are applied to its buffer and written when it is closed. ",
+    "A dry run ends with the hash of the content it previewed ('Content hash: sha256:...'); pass it as 'expectedHash' ",
+    "to apply the edits only if the file has not changed since, otherwise the call fails without writing. ",
     "Only works within allowed directories."),
-    destructive_hint = false,
+    destructive_hint = true,
     idempotent_hint = false,
     open_world_hint = false,
     read_only_hint = false
@@ -36,6 +130,13 @@ pub struct EditFile {
 
     /// The list of edit operations to apply.
     pub edits: Vec<EditOperation>,
+    /// Line-based operations to comment out or uncomment line ranges.
+    #[serde(
+        rename = "lineEdits",
+        default,
+        skip_serializing_if = "std::option::Option::is_none"
+    )]
+    pub line_edits: Option<Vec<LineEditOperation>>,
     /// Preview changes using git-style diff format without applying them.
     #[serde(
         rename = "dryRun",
@@ -43,6 +144,13 @@ pub struct EditFile {
         skip_serializing_if = "std::option::Option::is_none"
     )]
     pub dry_run: Option<bool>,
+    /// The content hash reported by the dry run of these edits. The edits are refused if the file changed since.
+    #[serde(
+        rename = "expectedHash",
+        default,
+        skip_serializing_if = "std::option::Option::is_none"
+    )]
+    pub expected_hash: Option<String>,
 }
 
 impl EditFile {
@@ -50,8 +158,19 @@ impl EditFile {
         params: Self,
         context: &FileSystemService,
     ) -> std::result::Result<CallToolResult, CallToolError> {
+        let path = Path::new(&params.path);
         let diff = context
-            .apply_file_edits(Path::new(&params.path), params.edits, params.dry_run, None)
+            .suggest_paths_on_not_found(
+                path,
+                context.apply_file_changes_guarded(
+                    path,
+                    params.edits,
+                    params.line_edits.unwrap_or_default(),
+                    params.dry_run,
+                    None,
+                    params.expected_hash.as_deref(),
+                ),
+            )
             .await
             .map_err(CallToolError::new)?;
 
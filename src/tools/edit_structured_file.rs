@@ -0,0 +1,125 @@
+use std::path::Path;
+
+use rust_mcp_sdk::macros::{JsonSchema, mcp_tool};
+use rust_mcp_sdk::schema::TextContent;
+use rust_mcp_sdk::schema::{CallToolResult, schema_utils::CallToolError};
+use serde_json::{Map, Value};
+
+use crate::fs_service::FileSystemService;
+
+#[derive(
+    ::serde::Deserialize, ::serde::Serialize, Clone, Copy, Debug, PartialEq, Eq, JsonSchema,
+)]
+/// Format of a structured file.
+pub enum StructuredFormat {
+    #[serde(rename = "json")]
+    Json,
+    #[serde(rename = "yaml")]
+    Yaml,
+    #[serde(rename = "toml")]
+    Toml,
+}
+
+impl StructuredFormat {
+    /// Detects the format from the file extension.
+    pub fn from_path(path: &Path) -> Option<Self> {
+        let extension = path.extension()?.to_str()?.to_lowercase();
+        match extension.as_str() {
+            "json" => Some(StructuredFormat::Json),
+            "yaml" | "yml" => Some(StructuredFormat::Yaml),
+            "toml" => Some(StructuredFormat::Toml),
+            _ => None,
+        }
+    }
+}
+
+#[derive(
+    ::serde::Deserialize, ::serde::Serialize, Clone, Copy, Debug, PartialEq, Eq, JsonSchema,
+)]
+/// The kind of a structured edit.
+pub enum StructuredEditKind {
+    /// Sets the value at the pointer, creating missing parent objects.
+    #[serde(rename = "set")]
+    Set,
+    /// Removes the key or array element at the pointer.
+    #[serde(rename = "remove")]
+    Remove,
+    /// Appends the value to the array at the pointer.
+    #[serde(rename = "append")]
+    Append,
+}
+
+/// An arbitrary JSON value.
+#[derive(::serde::Deserialize, ::serde::Serialize, Clone, Debug, PartialEq)]
+#[serde(transparent)]
+pub struct JsonValue(pub Value);
+
+impl JsonValue {
+    /// Accepts any JSON value.
+    pub fn json_schema() -> Map<String, Value> {
+        Map::new()
+    }
+}
+
+#[derive(::serde::Deserialize, ::serde::Serialize, Clone, Debug, JsonSchema)]
+/// A point edit addressed by pointer.
+pub struct StructuredEditOperation {
+    /// Either `set`, `remove` or `append`.
+    pub op: StructuredEditKind,
+    /// Location of the edited value: a JSON Pointer such as `/dependencies/serde/version`, or a
+    /// dotted path such as `dependencies.serde.version`. Array elements are addressed by index;
+    /// `-` sets the element past the end of an array.
+    pub pointer: String,
+    /// The value to set or append, as JSON. Objects become inline tables in TOML.
+    pub value: Option<JsonValue>,
+}
+
+#[mcp_tool(
+    name = "edit_structured_file",
+    title = "Edit a JSON, YAML or TOML file",
+    description = concat!("Applies point edits to a JSON, YAML or TOML file, addressing values by pointer rather than by text. ",
+    "Each operation sets, removes or appends to (an array) the value at a JSON Pointer ('/dependencies/serde/version') ",
+    "or dotted path ('dependencies.serde.version'). The format is detected from the file extension unless given. ",
+    "TOML comments and formatting are preserved; JSON keeps its key order and indentation; YAML is re-serialized, so its comments are lost. ",
+    "Files that fail to parse are refused with the location of the error. Returns a git-style diff. ",
+    "Only works within allowed directories."),
+    destructive_hint = true,
+    idempotent_hint = false,
+    open_world_hint = false,
+    read_only_hint = false
+)]
+#[derive(::serde::Deserialize, ::serde::Serialize, Clone, Debug, JsonSchema)]
+pub struct EditStructuredFile {
+    /// The path of the file to edit.
+    pub path: String,
+    /// The format of the file: `json`, `yaml` or `toml`. Detected from the extension when omitted.
+    pub format: Option<StructuredFormat>,
+    /// The operations to apply, in order.
+    pub edits: Vec<StructuredEditOperation>,
+    /// Preview changes using git-style diff format without applying them.
+    #[serde(
+        rename = "dryRun",
+        default,
+        skip_serializing_if = "std::option::Option::is_none"
+    )]
+    pub dry_run: Option<bool>,
+}
+
+impl EditStructuredFile {
+    pub async fn run_tool(
+        params: Self,
+        context: &FileSystemService,
+    ) -> std::result::Result<CallToolResult, CallToolError> {
+        let diff = context
+            .apply_structured_edits(
+                Path::new(&params.path),
+                params.format,
+                &params.edits,
+                params.dry_run.unwrap_or_default(),
+            )
+            .await
+            .map_err(CallToolError::new)?;
+
+        Ok(CallToolResult::text_content(vec![TextContent::from(diff)]))
+    }
+}
@@ -0,0 +1,84 @@
+use std::path::Path;
+
+use rust_mcp_sdk::{
+    macros::{JsonSchema, mcp_tool},
+    schema::{CallToolResult, TextContent, schema_utils::CallToolError},
+};
+
+use crate::{error::ServiceError, fs_service::FileSystemService};
+
+#[mcp_tool(
+    name = "extract_definition",
+    title = "Extract code definition",
+    description = concat!("Returns the complete source text of a named definition (function, method, struct, enum, trait, impl, class, etc.) in a source file, ",
+    "together with its start and end line numbers. Leading doc comments and attributes are included. ",
+    "If the name is defined more than once (e.g. overloads or multiple impl blocks), all definitions are returned in file order. ",
+    "When 'name' is omitted, every definition in the file (public and private, including nested ones such as methods) is listed instead ",
+    "as a JSON array of {name, kind, start_line, end_line, visibility}; spans of nested items lie within their parent's span. ",
+    "Rust functions also get a 'signature' with the one-line 'text' of the signature, its 'qualifiers' (async, const, unsafe, extern), 'generics', ",
+    "'parameters' ({name, type}), 'return_type' and 'where_predicates'; signatures that cannot be parsed (e.g. in macros) only have 'text', with 'parsed' false. ",
+    "Definitions that only appear inside comments or string literals are ignored, for languages whose comment syntax is recognized. ",
+    "Set 'include_imports' to also list the `use`, `extern crate` and `mod name;` declarations (or `import` statements) with the kind 'import', for an overview of the file's dependencies. ",
+    "Only works within allowed directories."),
+    destructive_hint = false,
+    idempotent_hint = false,
+    open_world_hint = false,
+    read_only_hint = true
+)]
+#[derive(::serde::Deserialize, ::serde::Serialize, Clone, Debug, JsonSchema)]
+pub struct ExtractDefinition {
+    /// The path of the source file to search.
+    pub path: String,
+    /// The name of the definition to extract. Omit to list all definitions with their spans.
+    pub name: Option<String>,
+    /// When listing, also list import declarations with the kind `import`. (Default : false)
+    pub include_imports: Option<bool>,
+}
+
+impl ExtractDefinition {
+    pub async fn run_tool(
+        params: Self,
+        context: &FileSystemService,
+    ) -> std::result::Result<CallToolResult, CallToolError> {
+        let Some(name) = params.name else {
+            let definitions = context
+                .list_definitions_with_options(
+                    Path::new(&params.path),
+                    params.include_imports.unwrap_or_default(),
+                )
+                .await
+                .map_err(CallToolError::new)?;
+            let result = serde_json::to_string_pretty(&definitions).map_err(CallToolError::new)?;
+            return Ok(CallToolResult::text_content(vec![TextContent::from(
+                result,
+            )]));
+        };
+
+        let definitions = context
+            .extract_definition(Path::new(&params.path), &name)
+            .await
+            .map_err(CallToolError::new)?;
+
+        if definitions.is_empty() {
+            return Err(CallToolError::new(ServiceError::FromString(format!(
+                "No definition named '{}' found in {}",
+                name, params.path
+            ))));
+        }
+
+        let result = definitions
+            .iter()
+            .map(|definition| {
+                format!(
+                    "{}:{}-{}\n{}",
+                    params.path, definition.start_line, definition.end_line, definition.source
+                )
+            })
+            .collect::<Vec<_>>()
+            .join("\n\n");
+
+        Ok(CallToolResult::text_content(vec![TextContent::from(
+            result,
+        )]))
+    }
+}
@@ -0,0 +1,71 @@
+use std::path::Path;
+
+use rust_mcp_sdk::{
+    macros::{JsonSchema, mcp_tool},
+    schema::{CallToolResult, TextContent, schema_utils::CallToolError},
+};
+
+use crate::fs_service::FileSystemService;
+
+#[derive(
+    ::serde::Deserialize, ::serde::Serialize, Clone, Copy, Debug, Default, PartialEq, Eq, JsonSchema,
+)]
+/// How a file is renamed when its name is already taken in the destination.
+pub enum CollisionScheme {
+    /// Append a counter to the name, e.g. `logo_1.png`.
+    #[default]
+    #[serde(rename = "counter")]
+    Counter,
+    /// Prefix the name with the directories of the file, e.g. `icons_small_logo.png`.
+    #[serde(rename = "path_prefix")]
+    PathPrefix,
+}
+
+#[mcp_tool(
+    name = "flatten_directory",
+    title = "Flatten a directory tree",
+    description = concat!("Moves every file below a directory into a single directory, by default the directory itself, such as to collect scattered assets. ",
+    "Files keep their names unless taken: shallower files get their name first, and a later file with a taken name is renamed with the 'collision' scheme, ",
+    "'counter' (default, e.g. `logo_1.png`) or 'path_prefix' (e.g. `icons_small_logo.png`). Names are compared ignoring case, and no file is ever overwritten. ",
+    "Directories left empty are removed unless 'keep_directories' is set. Git repositories are not descended into, and symbolic links are moved, not followed. ",
+    "Set 'dry_run' to list the planned moves and renames without moving anything. Only works within allowed directories."),
+    destructive_hint = false,
+    idempotent_hint = true,
+    open_world_hint = false,
+    read_only_hint = false
+)]
+#[derive(::serde::Deserialize, ::serde::Serialize, Clone, Debug, JsonSchema)]
+pub struct FlattenDirectory {
+    /// The directory to flatten.
+    pub path: String,
+    /// The directory receiving the files, created if missing. (Default : the directory to flatten)
+    pub destination: Option<String>,
+    /// How to rename a file whose name is taken: 'counter' or 'path_prefix'. (Default : counter)
+    pub collision: Option<CollisionScheme>,
+    /// Keep the directories left empty by the moves. (Default : false)
+    pub keep_directories: Option<bool>,
+    /// List the planned moves without moving anything. (Default : false)
+    pub dry_run: Option<bool>,
+}
+
+impl FlattenDirectory {
+    pub async fn run_tool(
+        params: Self,
+        context: &FileSystemService,
+    ) -> std::result::Result<CallToolResult, CallToolError> {
+        let report = context
+            .flatten_directory(
+                Path::new(&params.path),
+                params.destination.as_deref().map(Path::new),
+                params.collision.unwrap_or_default() == CollisionScheme::PathPrefix,
+                params.keep_directories.unwrap_or_default(),
+                params.dry_run.unwrap_or_default(),
+            )
+            .await
+            .map_err(CallToolError::new)?;
+
+        Ok(CallToolResult::text_content(vec![TextContent::from(
+            report.render_text(),
+        )]))
+    }
+}
@@ -13,7 +13,10 @@ use crate::fs_service::FileSystemService;
     "Returns comprehensive information including size, creation time, ",
     "last modified time, permissions, and type. ",
     "This tool is perfect for understanding file characteristics without ",
-    "reading the actual content. Only works within allowed directories."),
+    "reading the actual content. Symlinks are followed by default; set 'follow_symlinks' to false ",
+    "to inspect the link itself. When 'pattern' is provided, 'path' is treated as a directory and ",
+    "information is returned for every entry matching the glob pattern within it. ",
+    "Only works within allowed directories."),
     destructive_hint = false,
     idempotent_hint = false,
     open_world_hint = false,
@@ -21,8 +24,12 @@ use crate::fs_service::FileSystemService;
 )]
 #[derive(::serde::Deserialize, ::serde::Serialize, Clone, Debug, JsonSchema)]
 pub struct GetFileInfo {
-    /// The path of the file to get information for.
+    /// The path of the file to get information for, or the directory to search when `pattern` is provided.
     pub path: String,
+    /// Glob pattern (e.g. `*.rs`) matched against entry names under `path`; returns information for each match.
+    pub pattern: Option<String>,
+    /// Report the symlink target's metadata (true) or the symlink's own metadata (false). (Default : true)
+    pub follow_symlinks: Option<bool>,
 }
 
 impl GetFileInfo {
@@ -30,12 +37,38 @@ impl GetFileInfo {
         params: Self,
         context: &FileSystemService,
     ) -> std::result::Result<CallToolResult, CallToolError> {
-        let stats = context
-            .get_file_stats(Path::new(&params.path))
+        let follow_symlinks = params.follow_symlinks.unwrap_or(true);
+
+        let Some(pattern) = params.pattern else {
+            let path = Path::new(&params.path);
+            let stats = context
+                .suggest_paths_on_not_found(
+                    path,
+                    context.get_file_stats_with(path, follow_symlinks),
+                )
+                .await
+                .map_err(CallToolError::new)?;
+            return Ok(CallToolResult::text_content(vec![TextContent::from(
+                stats.to_string(),
+            )]));
+        };
+
+        let matches = context
+            .get_file_stats_matching(Path::new(&params.path), pattern, follow_symlinks)
             .await
             .map_err(CallToolError::new)?;
+
+        let result = if !matches.is_empty() {
+            matches
+                .iter()
+                .map(|(path, stats)| format!("path: {}\n{}", path.display(), stats))
+                .collect::<Vec<_>>()
+                .join("\n")
+        } else {
+            "No matches found".to_string()
+        };
         Ok(CallToolResult::text_content(vec![TextContent::from(
-            stats.to_string(),
+            result,
         )]))
     }
 }
@@ -0,0 +1,42 @@
+use rust_mcp_sdk::macros::{JsonSchema, mcp_tool};
+use rust_mcp_sdk::schema::TextContent;
+use rust_mcp_sdk::schema::{CallToolResult, schema_utils::CallToolError};
+use serde_json::{Map, Value};
+
+use crate::fs_service::FileSystemService;
+
+#[mcp_tool(
+    name = "get_performance_counters",
+    title = "Get performance counters",
+    description = concat!("Returns how often the server took each of its fast paths and threshold decisions since it started or the counters were last reset, ",
+    "to help tune the performance options: 'parallel_path' and 'concurrent_path' (work spread across threads or concurrent reads), ",
+    "'read_cache_hit' and 'read_cache_miss' (deduplicated reads), 'search_streamed' and 'search_buffered' (files searched as a stream or loaded in memory), ",
+    "'text_decoded' (text files decoded from an encoding other than UTF-8), and 'disk_space_checked' and 'disk_space_check_skipped' (writes above and below the disk space check threshold). ",
+    "The counters are returned as a JSON object. Set 'reset' to set them back to zero; the values before the reset are returned."),
+    destructive_hint = false,
+    idempotent_hint = false,
+    open_world_hint = false,
+    read_only_hint = true
+)]
+#[derive(::serde::Deserialize, ::serde::Serialize, Clone, Debug, JsonSchema)]
+pub struct GetPerformanceCounters {
+    /// Set the counters back to zero after reading them. (Default : false)
+    pub reset: Option<bool>,
+}
+
+impl GetPerformanceCounters {
+    pub async fn run_tool(
+        params: Self,
+        context: &FileSystemService,
+    ) -> std::result::Result<CallToolResult, CallToolError> {
+        let counters: Map<String, Value> = context
+            .performance_counters(params.reset.unwrap_or_default())
+            .into_iter()
+            .map(|(counter, value)| (counter.name().to_string(), Value::from(value)))
+            .collect();
+        let result = serde_json::to_string_pretty(&counters).map_err(CallToolError::new)?;
+        Ok(CallToolResult::text_content(vec![TextContent::from(
+            result,
+        )]))
+    }
+}
@@ -0,0 +1,46 @@
+use std::path::Path;
+
+use rust_mcp_sdk::{
+    macros::{JsonSchema, mcp_tool},
+    schema::{CallToolResult, TextContent, schema_utils::CallToolError},
+};
+
+use crate::fs_service::FileSystemService;
+
+#[mcp_tool(
+    name = "hash_directory",
+    title = "Hash a directory tree",
+    description = concat!("Computes a single SHA-256 digest of the contents and structure of a directory tree, for detecting changes anywhere in it. ",
+    "Every file, directory and symbolic link contributes its relative path and content (the target for a link, which is not followed), ",
+    "so identical trees produce the same digest wherever they are located, and any change to a name or a byte changes it. ",
+    "Files are streamed, so memory use does not depend on their size. Also returns the number of files and directories and the bytes hashed. ",
+    "Only works within allowed directories."),
+    destructive_hint = false,
+    idempotent_hint = true,
+    open_world_hint = false,
+    read_only_hint = true
+)]
+#[derive(::serde::Deserialize, ::serde::Serialize, Clone, Debug, JsonSchema)]
+pub struct HashDirectory {
+    /// The root directory of the tree to hash.
+    pub path: String,
+}
+
+impl HashDirectory {
+    pub async fn run_tool(
+        params: Self,
+        context: &FileSystemService,
+    ) -> std::result::Result<CallToolResult, CallToolError> {
+        let hash = context
+            .hash_directory(Path::new(&params.path))
+            .await
+            .map_err(CallToolError::new)?;
+
+        Ok(CallToolResult::text_content(vec![TextContent::from(
+            format!(
+                "sha256:{}\n{} files, {} directories, {} bytes hashed",
+                hash.digest, hash.files, hash.directories, hash.bytes
+            ),
+        )]))
+    }
+}
@@ -33,8 +33,9 @@ impl HeadFile {
         params: Self,
         context: &FileSystemService,
     ) -> std::result::Result<CallToolResult, CallToolError> {
+        let path = Path::new(&params.path);
         let result = context
-            .head_file(Path::new(&params.path), params.lines as usize)
+            .suggest_paths_on_not_found(path, context.head_file(path, params.lines as usize))
             .await
             .map_err(CallToolError::new)?;
 
@@ -1,6 +1,7 @@
 use rust_mcp_sdk::macros::{JsonSchema, mcp_tool};
 use rust_mcp_sdk::schema::TextContent;
 use rust_mcp_sdk::schema::{CallToolResult, schema_utils::CallToolError};
+use serde_json::{Map, json};
 
 use crate::fs_service::FileSystemService;
 
@@ -10,7 +11,9 @@ use crate::fs_service::FileSystemService;
     description = concat!("Returns a list of directories that the server has permission ",
     "to access Subdirectories within these allowed directories are also accessible. ",
     "Use this to identify which directories and their nested paths are available ",
-    "before attempting to access files."),
+    "before attempting to access files. ",
+    "Each directory is followed by its current state: whether it still exists, is readable and writable, ",
+    "and whether it is a symlink; the same flags are returned in a 'directories' array of the result metadata."),
     destructive_hint = false,
     idempotent_hint = false,
     open_world_hint = false,
@@ -24,22 +27,48 @@ impl ListAllowedDirectories {
         _: Self,
         context: &FileSystemService,
     ) -> std::result::Result<CallToolResult, CallToolError> {
-        let allowed_directories = context.allowed_directories().await;
+        let statuses = context.allowed_directory_statuses().await;
 
-        let result = if allowed_directories.is_empty() {
+        let mut result = if statuses.is_empty() {
             "Allowed directories list is empty!".to_string()
         } else {
             format!(
                 "Allowed directories:\n{}",
-                allowed_directories
+                statuses
                     .iter()
-                    .map(|entry| entry.display().to_string())
+                    .map(|status| status.to_string())
                     .collect::<Vec<_>>()
                     .join("\n")
             )
         };
-        Ok(CallToolResult::text_content(vec![TextContent::from(
-            result,
-        )]))
+
+        let rejected_directories = context.rejected_directories();
+        if !rejected_directories.is_empty() {
+            result.push_str(&format!(
+                "\n\nRejected directories:\n{}",
+                rejected_directories
+                    .iter()
+                    .map(|issue| issue.to_string())
+                    .collect::<Vec<_>>()
+                    .join("\n")
+            ));
+        }
+
+        let directory_warnings = context.directory_warnings();
+        if !directory_warnings.is_empty() {
+            result.push_str(&format!(
+                "\n\nWarnings:\n{}",
+                directory_warnings
+                    .iter()
+                    .map(|issue| issue.to_string())
+                    .collect::<Vec<_>>()
+                    .join("\n")
+            ));
+        }
+
+        let mut meta = Map::new();
+        meta.insert("directories".to_string(), json!(statuses));
+
+        Ok(CallToolResult::text_content(vec![TextContent::from(result)]).with_meta(Some(meta)))
     }
 }
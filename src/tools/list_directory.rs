@@ -4,7 +4,11 @@ use rust_mcp_sdk::macros::{JsonSchema, mcp_tool};
 use rust_mcp_sdk::schema::TextContent;
 use rust_mcp_sdk::schema::{CallToolResult, schema_utils::CallToolError};
 
-use crate::fs_service::FileSystemService;
+use crate::fs_service::{
+    FileSystemService,
+    git_status::MAX_GIT_STATUS_ENTRIES,
+    utils::{ListingOrder, SortOrder},
+};
 
 #[mcp_tool(
     name = "list_directory",
@@ -12,7 +16,12 @@ use crate::fs_service::FileSystemService;
     description = concat!("Get a detailed listing of all files and directories in a specified path. ",
 "Results clearly distinguish between files and directories with [FILE] and [DIR] ",
 "prefixes. This tool is essential for understanding directory structure and ",
-"finding specific files within a directory. Only works within allowed directories."),
+"finding specific files within a directory. ",
+"Entries are sorted in natural order (case-insensitive, 'file2' before 'file10'); set 'sort' to `lexicographic` for plain byte order ",
+"and 'dirs_first' to list directories before files. ",
+"When 'git_status' is true and the directory is inside a git working tree, changed entries are followed by ",
+"their git status in parentheses: (modified), (staged), (untracked) or (ignored). ",
+"Entries are listed by name unless 'absolute_paths' is true, which lists their absolute canonical paths. Only works within allowed directories."),
     destructive_hint = false,
     idempotent_hint = false,
     open_world_hint = false,
@@ -22,6 +31,14 @@ use crate::fs_service::FileSystemService;
 pub struct ListDirectory {
     /// The path of the directory to list.
     pub path: String,
+    /// Annotate entries with their git status (default: false).
+    pub git_status: Option<bool>,
+    /// Name ordering, either `natural` or `lexicographic` (default: natural).
+    pub sort: Option<SortOrder>,
+    /// List directories before files (default: false).
+    pub dirs_first: Option<bool>,
+    /// List absolute canonical paths instead of names relative to the directory (default: false).
+    pub absolute_paths: Option<bool>,
 }
 
 impl ListDirectory {
@@ -29,25 +46,58 @@ impl ListDirectory {
         params: Self,
         context: &FileSystemService,
     ) -> std::result::Result<CallToolResult, CallToolError> {
-        let entries = context
-            .list_directory(Path::new(&params.path))
+        let mut entries = context
+            .list_directory_entries(Path::new(&params.path), false)
             .await
             .map_err(CallToolError::new)?;
 
-        let formatted: Vec<_> = entries
+        ListingOrder::new(params.sort, params.dirs_first).sort_listed_entries(&mut entries);
+        let root = if params.absolute_paths.unwrap_or_default() {
+            Some(
+                context
+                    .canonical_directory(Path::new(&params.path))
+                    .await
+                    .map_err(CallToolError::new)?,
+            )
+        } else {
+            None
+        };
+
+        context.ensure_running().map_err(CallToolError::new)?;
+        let git_status = if params.git_status.unwrap_or_default() {
+            context
+                .git_status(Path::new(&params.path))
+                .await
+                .map_err(CallToolError::new)?
+        } else {
+            None
+        };
+
+        let mut formatted: Vec<_> = entries
             .iter()
             .map(|entry| {
+                let status = git_status
+                    .as_ref()
+                    .and_then(|git_status| git_status.status_of(&git_status.root.join(&entry.name)))
+                    .map(|status| format!(" ({status})"))
+                    .unwrap_or_default();
+                let name = match &root {
+                    Some(root) => root.join(&entry.name).display().to_string(),
+                    None => entry.name.clone(),
+                };
                 format!(
-                    "{} {}",
-                    if entry.path().is_dir() {
-                        "[DIR]"
-                    } else {
-                        "[FILE]"
-                    },
-                    entry.file_name().to_str().unwrap_or_default()
+                    "{} {}{}",
+                    if entry.is_dir { "[DIR]" } else { "[FILE]" },
+                    name,
+                    status
                 )
             })
             .collect();
+        if git_status.is_some_and(|git_status| git_status.truncated) {
+            formatted.push(format!(
+                "Git status: only the first {MAX_GIT_STATUS_ENTRIES} changed paths are annotated."
+            ));
+        }
 
         Ok(CallToolResult::text_content(vec![TextContent::from(
             formatted.join("\n"),
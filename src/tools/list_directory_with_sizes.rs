@@ -4,8 +4,8 @@ use rust_mcp_sdk::schema::{CallToolResult, schema_utils::CallToolError};
 use std::fmt::Write;
 use std::path::Path;
 
-use crate::fs_service::FileSystemService;
-use crate::fs_service::utils::format_bytes;
+use crate::fs_service::utils::{ByteFormat, ListingOrder, SizeUnits, SortOrder, format_rfc3339};
+use crate::fs_service::{FileSystemService, ListedEntry};
 
 #[mcp_tool(
     name = "list_directory_with_sizes",
@@ -13,7 +13,14 @@ use crate::fs_service::utils::format_bytes;
     description = concat!("Get a detailed listing of all files and directories in a specified path, including sizes. " ,
         "Results clearly distinguish between files and directories with [FILE] and [DIR] prefixes. " ,
         "This tool is useful for understanding directory structure and " ,
-        "finding specific files within a directory. Only works within allowed directories."),
+        "finding specific files within a directory. ",
+        "Entries are sorted in natural order (case-insensitive, 'file2' before 'file10'); set 'sort' to `lexicographic` for plain byte order ",
+"and 'dirs_first' to list directories before files. ",
+        "Sizes use 'size_units' (`binary`: KiB/MiB, `decimal`: 1000-based KB/MB, `compact`: 1024-based KB/MB as in older versions) and 'size_precision' decimals. ",
+        "Set 'allocated_sizes' to also report the disk space allocated to each file, which is much smaller than the size of sparse files. ",
+        "Set 'show_mtime' to also report the last modification time of each file, in RFC 3339 format. ",
+        "Entries are listed by name unless 'absolute_paths' is true, which lists their absolute canonical paths. ",
+        "Only works within allowed directories."),
     destructive_hint = false,
     idempotent_hint = false,
     open_world_hint = false,
@@ -23,42 +30,70 @@ use crate::fs_service::utils::format_bytes;
 pub struct ListDirectoryWithSizes {
     /// The path of the directory to list.
     pub path: String,
+    /// Name ordering, either `natural` or `lexicographic` (default: natural).
+    pub sort: Option<SortOrder>,
+    /// List directories before files (default: false).
+    pub dirs_first: Option<bool>,
+    /// Units of sizes: `binary`, `decimal` or `compact` (default: the server setting, `binary` unless configured).
+    pub size_units: Option<SizeUnits>,
+    /// Number of decimals of sizes, at most 6 (default: 2).
+    pub size_precision: Option<u64>,
+    /// Also report the disk space allocated to files (Unix: blocks * 512), next to their size (default: false).
+    pub allocated_sizes: Option<bool>,
+    /// List absolute canonical paths instead of names relative to the directory (default: false).
+    pub absolute_paths: Option<bool>,
+    /// Also report the last modification time of files, in RFC 3339 format (default: false).
+    pub show_mtime: Option<bool>,
 }
 
 impl ListDirectoryWithSizes {
-    async fn format_directory_entries(
+    fn format_directory_entries(
         &self,
-        mut entries: Vec<tokio::fs::DirEntry>,
+        mut entries: Vec<ListedEntry>,
+        byte_format: ByteFormat,
+        root: Option<&Path>,
     ) -> std::result::Result<String, CallToolError> {
         let mut file_count = 0;
         let mut dir_count = 0;
         let mut total_size: u64 = 0;
+        let mut total_allocated: u64 = 0;
+        let allocated_sizes = self.allocated_sizes.unwrap_or_default();
+        let show_mtime = self.show_mtime.unwrap_or_default();
 
         // Estimate initial capacity: assume ~50 bytes per entry + summary
         let mut output = String::with_capacity(entries.len() * 50 + 120);
 
-        // Sort entries by file name
-        entries.sort_by_key(|a| a.file_name());
+        ListingOrder::new(self.sort, self.dirs_first).sort_listed_entries(&mut entries);
 
         // build the output string
         for entry in &entries {
-            let file_name = entry.file_name();
-            let file_name = file_name.to_string_lossy();
+            let file_name = match root {
+                Some(root) => root.join(&entry.name).display().to_string(),
+                None => entry.name.clone(),
+            };
 
-            if entry.path().is_dir() {
+            if entry.is_dir {
                 writeln!(output, "[DIR]  {file_name:<30}").map_err(CallToolError::new)?;
                 dir_count += 1;
-            } else if entry.path().is_file() {
-                let metadata = entry.metadata().await.map_err(CallToolError::new)?;
-
-                let file_size = metadata.len();
-                writeln!(
+            } else if entry.is_file {
+                let file_size = entry.size.unwrap_or_default();
+                write!(
                     output,
                     "[FILE] {:<30} {:>10}",
                     file_name,
-                    format_bytes(file_size)
+                    byte_format.format(file_size)
                 )
                 .map_err(CallToolError::new)?;
+                if allocated_sizes {
+                    let allocated = entry.allocated.unwrap_or_default();
+                    write!(output, "  ({} allocated)", byte_format.format(allocated))
+                        .map_err(CallToolError::new)?;
+                    total_allocated += allocated;
+                }
+                if show_mtime && let Some(modified) = entry.modified {
+                    write!(output, "  {}", format_rfc3339(modified)).map_err(CallToolError::new)?;
+                }
+                output.push('\n');
                 file_count += 1;
                 total_size += file_size;
             }
@@ -67,10 +102,21 @@ impl ListDirectoryWithSizes {
         // Append summary
         writeln!(
             output,
-            "\nTotal: {file_count} files, {dir_count} directories"
+            "\nTotal: {} files, {} directories",
+            byte_format.format_count(file_count),
+            byte_format.format_count(dir_count)
         )
         .map_err(CallToolError::new)?;
-        writeln!(output, "Total size: {}", format_bytes(total_size)).map_err(CallToolError::new)?;
+        writeln!(output, "Total size: {}", byte_format.format(total_size))
+            .map_err(CallToolError::new)?;
+        if allocated_sizes {
+            writeln!(
+                output,
+                "Total allocated: {}",
+                byte_format.format(total_allocated)
+            )
+            .map_err(CallToolError::new)?;
+        }
 
         Ok(output)
     }
@@ -80,14 +126,24 @@ impl ListDirectoryWithSizes {
         context: &FileSystemService,
     ) -> std::result::Result<CallToolResult, CallToolError> {
         let entries = context
-            .list_directory(Path::new(&params.path))
+            .list_directory_entries(Path::new(&params.path), true)
             .await
             .map_err(CallToolError::new)?;
 
-        let output = params
-            .format_directory_entries(entries)
-            .await
-            .map_err(CallToolError::new)?;
+        let byte_format = context
+            .byte_format()
+            .with_overrides(params.size_units, params.size_precision);
+        let root = if params.absolute_paths.unwrap_or_default() {
+            Some(
+                context
+                    .canonical_directory(Path::new(&params.path))
+                    .await
+                    .map_err(CallToolError::new)?,
+            )
+        } else {
+            None
+        };
+        let output = params.format_directory_entries(entries, byte_format, root.as_deref())?;
         Ok(CallToolResult::text_content(vec![TextContent::from(
             output,
         )]))
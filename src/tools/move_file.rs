@@ -5,6 +5,7 @@ use rust_mcp_sdk::schema::TextContent;
 use rust_mcp_sdk::schema::{CallToolResult, schema_utils::CallToolError};
 
 use crate::fs_service::FileSystemService;
+use crate::fs_service::rust_references::ReferenceUpdatePlan;
 
 #[mcp_tool(
     name = "move_file",
@@ -13,6 +14,11 @@ use crate::fs_service::FileSystemService;
 "and rename them in a single operation. If the destination exists, the ",
 "operation will fail. Works across different directories and can be used ",
 "for simple renaming within the same directory. ",
+"Warns when the destination name differs only in case or Unicode normalization from another name in its directory, ",
+"which is the same file on some file systems; set 'strict_case' to refuse the move instead. ",
+"Set 'dry_run' to preview the move (source, destination, size and conflicts) without changing anything. ",
+"For Rust module files, 'update_references' also moves the module's `mod` declaration and rewrites `crate::` paths ",
+"that refer to it across the crate (best effort; previewed as diffs in a dry run). ",
 "Both source and destination must be within allowed directories."),
     destructive_hint = false,
     idempotent_hint = false,
@@ -25,6 +31,12 @@ pub struct MoveFile {
     pub source: String,
     /// The destination path to move the file to.
     pub destination: String,
+    /// Report what would be moved, and any conflicts, without moving anything. (Default : false)
+    pub dry_run: Option<bool>,
+    /// Update `mod` declarations and `crate::` paths referring to a moved Rust module. (Default : false)
+    pub update_references: Option<bool>,
+    /// Refuse to move when the destination name differs only in case or Unicode normalization from an existing name. (Default : false)
+    pub strict_case: Option<bool>,
 }
 
 impl MoveFile {
@@ -32,16 +44,97 @@ impl MoveFile {
         params: Self,
         context: &FileSystemService,
     ) -> std::result::Result<CallToolResult, CallToolError> {
+        let source = Path::new(&params.source);
+        let destination = Path::new(&params.destination);
+        let collision = context
+            .check_name_collision(
+                destination,
+                Some(source),
+                params.strict_case.unwrap_or_default(),
+            )
+            .await
+            .map_err(CallToolError::new)?;
+        let warning = collision
+            .map(|collision| format!("\nWarning: {collision}."))
+            .unwrap_or_default();
+        let reference_plan = if params.update_references.unwrap_or_default() {
+            Some(
+                context
+                    .plan_reference_updates(source, destination)
+                    .await
+                    .map_err(CallToolError::new)?,
+            )
+        } else {
+            None
+        };
+
+        if params.dry_run.unwrap_or_default() {
+            let plan = context
+                .plan_move(source, destination)
+                .await
+                .map_err(CallToolError::new)?;
+            let mut output = plan.to_string();
+            if let Some(reference_plan) = &reference_plan {
+                output.push_str(&Self::format_reference_preview(reference_plan, context));
+            }
+            output.push_str(&warning);
+            return Ok(CallToolResult::text_content(vec![TextContent::from(
+                output,
+            )]));
+        }
+
+        context.ensure_running().map_err(CallToolError::new)?;
         context
-            .move_file(Path::new(&params.source), Path::new(&params.destination))
+            .move_file(source, destination)
             .await
             .map_err(CallToolError::new)?;
 
+        let mut output = format!(
+            "Successfully moved {} to {}",
+            &params.source, &params.destination
+        );
+        if let Some(reference_plan) = &reference_plan {
+            let updated = context
+                .apply_reference_updates(reference_plan, source, destination)
+                .await
+                .map_err(CallToolError::new)?;
+            output.push_str(&format!(
+                "\nUpdated references in {} file(s):",
+                updated.len()
+            ));
+            for path in updated {
+                output.push_str(&format!("\n  - {}", path.display()));
+            }
+            for note in &reference_plan.notes {
+                output.push_str(&format!("\nNote: {note}"));
+            }
+        }
+        output.push_str(&warning);
+
         Ok(CallToolResult::text_content(vec![TextContent::from(
-            format!(
-                "Successfully moved {} to {}",
-                &params.source, &params.destination
-            ),
+            output,
         )]))
     }
+
+    fn format_reference_preview(plan: &ReferenceUpdatePlan, context: &FileSystemService) -> String {
+        let mut output = String::new();
+        if let (Some(old_module), Some(new_module)) = (&plan.old_module, &plan.new_module) {
+            output.push_str(&format!(
+                "\nReferences: {old_module} -> {new_module} in {} file(s)",
+                plan.updates.len()
+            ));
+        }
+        for update in &plan.updates {
+            output.push_str("\n\n");
+            output.push_str(&context.create_unified_diff(
+                &update.original,
+                &update.updated,
+                Some(update.path.display().to_string()),
+            ));
+        }
+        for note in &plan.notes {
+            output.push_str(&format!("\nNote: {note}"));
+        }
+        output
+    }
 }
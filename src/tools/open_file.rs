@@ -0,0 +1,52 @@
+use rust_mcp_sdk::{
+    macros::{JsonSchema, mcp_tool},
+    schema::TextContent,
+};
+use std::path::Path;
+
+use rust_mcp_sdk::schema::{CallToolResult, schema_utils::CallToolError};
+
+use crate::fs_service::FileSystemService;
+
+#[mcp_tool(
+    name = "open_file",
+    title="Open file",
+    description = concat!("Opens a text file for a sequence of edits and returns a handle. ",
+"While the file is open, 'edit_file' calls on its path are applied to an in-memory buffer instead of re-reading and rewriting the file, ",
+"which is much cheaper for repeated edits to a large file. ",
+"Nothing is written until the handle is closed with 'close_file'. Other tools still see the file as it is on disk. ",
+"At most 16 files can be open at the same time. Only works within allowed directories."),
+    destructive_hint = false,
+    idempotent_hint = false,
+    open_world_hint = false,
+    read_only_hint = false
+)]
+#[derive(Debug, Clone, ::serde::Deserialize, ::serde::Serialize, JsonSchema)]
+pub struct OpenFile {
+    /// The path of the file to open.
+    pub path: String,
+}
+
+impl OpenFile {
+    pub async fn run_tool(
+        params: Self,
+        context: &FileSystemService,
+    ) -> std::result::Result<CallToolResult, CallToolError> {
+        let opened = context
+            .suggest_paths_on_not_found(
+                Path::new(&params.path),
+                context.open_file(Path::new(&params.path)),
+            )
+            .await
+            .map_err(CallToolError::new)?;
+
+        Ok(CallToolResult::text_content(vec![TextContent::from(
+            format!(
+                "Opened {} ({} bytes) as handle '{}'. Close it with 'close_file' to write the edits to disk.",
+                opened.path.display(),
+                opened.size,
+                opened.handle
+            ),
+        )]))
+    }
+}
@@ -0,0 +1,49 @@
+use rust_mcp_sdk::{
+    macros::{JsonSchema, mcp_tool},
+    schema::{CallToolResult, TextContent, schema_utils::CallToolError},
+};
+
+use crate::fs_service::{FileSystemService, utils::OutputFormat};
+
+#[mcp_tool(
+    name = "path_probe",
+    title = "Probe paths",
+    description = concat!("Cheaply checks whether one or more paths exist. ",
+    "For each path, reports whether it exists, its type (file, dir, symlink, other or missing), its size if it is a file, ",
+    "and whether it is inside the allowed directories. Symlinks are not followed. ",
+    "Missing paths are not errors, and paths outside the allowed directories are reported without being inspected. ",
+    "The output is one line per path, or a JSON array when 'output_format' is `json` (default: text)."),
+    destructive_hint = false,
+    idempotent_hint = true,
+    open_world_hint = false,
+    read_only_hint = true
+)]
+#[derive(::serde::Deserialize, ::serde::Serialize, Clone, Debug, JsonSchema)]
+pub struct PathProbe {
+    /// The paths to check.
+    pub paths: Vec<String>,
+    /// Specify the output format, accepts either `text` or `json` (default: text).
+    pub output_format: Option<OutputFormat>,
+}
+
+impl PathProbe {
+    pub async fn run_tool(
+        params: Self,
+        context: &FileSystemService,
+    ) -> std::result::Result<CallToolResult, CallToolError> {
+        let probes = context.probe_paths(&params.paths).await;
+
+        let content = match params.output_format.unwrap_or(OutputFormat::Text) {
+            OutputFormat::Text => probes
+                .iter()
+                .map(|probe| probe.to_string())
+                .collect::<Vec<_>>()
+                .join("\n"),
+            OutputFormat::Json => serde_json::to_string(&probes).map_err(CallToolError::new)?,
+        };
+
+        Ok(CallToolResult::text_content(vec![TextContent::from(
+            content,
+        )]))
+    }
+}
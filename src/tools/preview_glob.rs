@@ -0,0 +1,59 @@
+use std::path::Path;
+
+use rust_mcp_sdk::{
+    macros::{JsonSchema, mcp_tool},
+    schema::{CallToolResult, TextContent, schema_utils::CallToolError},
+};
+
+use crate::fs_service::FileSystemService;
+
+/// Files listed when 'max_results' is not given.
+const DEFAULT_PREVIEW_RESULTS: usize = 200;
+
+#[mcp_tool(
+    name = "preview_glob",
+    title = "Preview the files a glob selects",
+    description = concat!("Lists the files below a directory that a glob selects, without changing anything, ",
+    "to check the selection before running a batch tool such as bulk_insert with the same 'path', 'pattern' and 'exclude_patterns'. ",
+    "The files are selected exactly as the batch tools select them: a 'pattern' holding a '/' is matched against paths relative to 'path' (e.g. `src/**/*.rs`); ",
+    "other patterns are matched against file names, as in search_files. Directories are never selected. ",
+    "Returns the number of matches and the matched paths sorted, up to 'max_results'. Only works within allowed directories."),
+    destructive_hint = false,
+    idempotent_hint = true,
+    open_world_hint = false,
+    read_only_hint = true
+)]
+#[derive(::serde::Deserialize, ::serde::Serialize, Clone, Debug, JsonSchema)]
+pub struct PreviewGlob {
+    /// The directory the glob is matched below.
+    pub path: String,
+    /// Glob pattern of the files to select, e.g. `src/**/*.rs` or `*.py`.
+    pub pattern: String,
+    /// Optional list of glob patterns to exclude.
+    pub exclude_patterns: Option<Vec<String>>,
+    /// Most matched paths to list; all matches are counted. (Default : 200)
+    pub max_results: Option<u64>,
+}
+
+impl PreviewGlob {
+    pub async fn run_tool(
+        params: Self,
+        context: &FileSystemService,
+    ) -> std::result::Result<CallToolResult, CallToolError> {
+        let preview = context
+            .preview_glob(
+                Path::new(&params.path),
+                &params.pattern,
+                params.exclude_patterns.unwrap_or_default(),
+                params
+                    .max_results
+                    .map_or(DEFAULT_PREVIEW_RESULTS, |limit| limit as usize),
+            )
+            .await
+            .map_err(CallToolError::new)?;
+
+        Ok(CallToolResult::text_content(vec![TextContent::from(
+            preview.render_text(),
+        )]))
+    }
+}
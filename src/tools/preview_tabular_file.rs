@@ -0,0 +1,92 @@
+use std::path::Path;
+
+use rust_mcp_sdk::{
+    macros::{JsonSchema, mcp_tool},
+    schema::{CallToolResult, TextContent, schema_utils::CallToolError},
+};
+
+use crate::fs_service::{
+    FileSystemService,
+    tabular::{TabularOptions, parse_delimiter},
+    utils::OutputFormat,
+};
+
+#[mcp_tool(
+    name = "preview_tabular_file",
+    title = "Preview a CSV/TSV file",
+    description = concat!("Summarizes a delimited text file (CSV, TSV, ...) without reading all of it: the delimiter (detected unless given), ",
+    "the columns with their types inferred from the first 'sample_rows' rows, the row count, and the first and last rows. ",
+    "Quoted fields, including ones spanning several lines, are parsed correctly. ",
+    "Rows are counted up to 'scan_budget' bytes; past it the count is estimated from the file size and flagged as approximate, and the last rows are omitted. ",
+    "Use 'columns' to return only some columns, by name or 1-based index, and 'has_header': false for files without a header row. ",
+    "Only works within allowed directories."),
+    destructive_hint = false,
+    idempotent_hint = false,
+    open_world_hint = false,
+    read_only_hint = true
+)]
+#[derive(::serde::Deserialize, ::serde::Serialize, Clone, Debug, JsonSchema)]
+pub struct PreviewTabularFile {
+    /// The path of the file to preview.
+    pub path: String,
+    /// Field delimiter, a single character or "tab". Detected from the start of the file when omitted.
+    pub delimiter: Option<String>,
+    /// Whether the first row holds the column names. (Default : true)
+    pub has_header: Option<bool>,
+    /// Columns to return, by name or 1-based index. (Default : all columns)
+    pub columns: Option<Vec<String>>,
+    /// Number of rows returned from the start of the file, at most 1000. (Default : 5)
+    pub head_rows: Option<u64>,
+    /// Number of rows returned from the end of the file, at most 1000. (Default : 5)
+    pub tail_rows: Option<u64>,
+    /// Number of rows used to infer the column types. (Default : 100)
+    pub sample_rows: Option<u64>,
+    /// Bytes scanned to count rows before the count is estimated. (Default : 64 MiB)
+    pub scan_budget: Option<u64>,
+    /// Specify the output format, accepts either `text` or `json` (default: text).
+    pub output_format: Option<OutputFormat>,
+}
+
+impl PreviewTabularFile {
+    pub async fn run_tool(
+        params: Self,
+        context: &FileSystemService,
+    ) -> std::result::Result<CallToolResult, CallToolError> {
+        let defaults = TabularOptions::default();
+        let options = TabularOptions {
+            delimiter: params
+                .delimiter
+                .as_deref()
+                .map(parse_delimiter)
+                .transpose()
+                .map_err(CallToolError::new)?,
+            has_header: params.has_header.unwrap_or(defaults.has_header),
+            columns: params.columns.unwrap_or_default(),
+            head_rows: params
+                .head_rows
+                .map_or(defaults.head_rows, |rows| rows as usize),
+            tail_rows: params
+                .tail_rows
+                .map_or(defaults.tail_rows, |rows| rows as usize),
+            sample_rows: params
+                .sample_rows
+                .map_or(defaults.sample_rows, |rows| rows as usize),
+            scan_budget: params.scan_budget.unwrap_or(defaults.scan_budget),
+        };
+
+        let preview = context
+            .preview_tabular_file(Path::new(&params.path), options)
+            .await
+            .map_err(CallToolError::new)?;
+
+        let content = match params.output_format.unwrap_or(OutputFormat::Text) {
+            OutputFormat::Text => preview.render_text(),
+            OutputFormat::Json => {
+                serde_json::to_string_pretty(&preview).map_err(CallToolError::new)?
+            }
+        };
+        Ok(CallToolResult::text_content(vec![TextContent::from(
+            content,
+        )]))
+    }
+}
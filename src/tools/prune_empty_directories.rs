@@ -0,0 +1,76 @@
+use std::fmt::Write;
+use std::path::Path;
+
+use rust_mcp_sdk::{
+    macros::{JsonSchema, mcp_tool},
+    schema::{CallToolResult, TextContent, schema_utils::CallToolError},
+};
+
+use crate::fs_service::FileSystemService;
+
+#[mcp_tool(
+    name = "prune_empty_directories",
+    title = "Remove empty directories",
+    description = concat!("Recursively removes the empty directories below the given root path. ",
+    "A directory that only contains empty directories is itself removed. ",
+    "Operating system metadata files `.DS_Store` (macOS) and `Thumbs.db` (Windows) do not count and are removed with their directory. ",
+    "Directories holding a `.gitkeep` are kept unless 'gitkeep_as_empty' is set. ",
+    "'keep_patterns' lists glob patterns of directories or files to keep, matched against paths relative to the root. ",
+    "The root and the allowed directories are never removed. Set 'dry_run' to list what would be removed without removing anything. ",
+    "Only works within allowed directories."),
+    destructive_hint = true,
+    idempotent_hint = true,
+    open_world_hint = false,
+    read_only_hint = false
+)]
+#[derive(::serde::Deserialize, ::serde::Serialize, Clone, Debug, JsonSchema)]
+pub struct PruneEmptyDirectories {
+    /// The root directory to prune.
+    pub path: String,
+    /// Glob patterns of directories or files to keep, relative to the root (e.g. `**/cache`). A kept file keeps its directory.
+    pub keep_patterns: Option<Vec<String>>,
+    /// Treat a directory holding only a `.gitkeep` as empty, removing the placeholder. (Default : false)
+    pub gitkeep_as_empty: Option<bool>,
+    /// List the directories that would be removed without removing them. (Default : false)
+    pub dry_run: Option<bool>,
+}
+
+impl PruneEmptyDirectories {
+    pub async fn run_tool(
+        params: Self,
+        context: &FileSystemService,
+    ) -> std::result::Result<CallToolResult, CallToolError> {
+        let dry_run = params.dry_run.unwrap_or_default();
+        let plan = context
+            .prune_empty_directories(
+                Path::new(&params.path),
+                params.keep_patterns.unwrap_or_default(),
+                params.gitkeep_as_empty.unwrap_or_default(),
+                dry_run,
+            )
+            .await
+            .map_err(CallToolError::new)?;
+
+        let count = plan.directories.len();
+        let mut output = if count == 0 {
+            "No empty directories were found.".to_string()
+        } else {
+            format!(
+                "{} {count} empty {}:\n",
+                if dry_run { "Would remove" } else { "Removed" },
+                if count == 1 {
+                    "directory"
+                } else {
+                    "directories"
+                }
+            )
+        };
+        for dir in &plan.directories {
+            writeln!(output, "  {}", dir.display()).map_err(CallToolError::new)?;
+        }
+
+        Ok(CallToolResult::text_content(vec![TextContent::from(
+            output,
+        )]))
+    }
+}
@@ -0,0 +1,59 @@
+use std::path::Path;
+
+use rust_mcp_sdk::{
+    macros::{JsonSchema, mcp_tool},
+    schema::{CallToolResult, TextContent, schema_utils::CallToolError},
+};
+
+use crate::fs_service::{FileSystemService, redaction::redactions_footer};
+
+#[mcp_tool(
+    name = "read_context_pack",
+    title = "Read a context pack",
+    description = concat!("Reads the files listed by a context pack manifest, such as a '.ctxinclude', and returns their contents under headers, in the listed order. ",
+    "Each manifest line is a path, a path with a line range ('src/lib.rs:10-40', 'notes.md:5-' or 'main.rs:12'), or a glob ('src/**/*.rs'); ",
+    "blank lines and lines starting with '#' are ignored. Paths and globs are relative to the directory of the manifest, ",
+    "and files already included by an earlier line are not repeated by a glob. ",
+    "'max_bytes' bounds the content returned: earlier lines have priority, so the last-listed files are truncated or omitted first. ",
+    "Lines that cannot be resolved or read are reported in place without failing the pack. ",
+    "Secrets are redacted when enabled by the server or requested with 'redact_secrets'. Only works within allowed directories."),
+    destructive_hint = false,
+    idempotent_hint = false,
+    open_world_hint = false,
+    read_only_hint = true
+)]
+#[derive(::serde::Deserialize, ::serde::Serialize, Clone, Debug, JsonSchema)]
+pub struct ReadContextPack {
+    /// The path of the manifest listing the files to read.
+    pub path: String,
+    /// Most bytes of file content to return; the last-listed files are cut first. (Default : unlimited)
+    pub max_bytes: Option<u64>,
+    /// Replace well-known secrets with markers, overriding the server setting for this call.
+    pub redact_secrets: Option<bool>,
+}
+
+impl ReadContextPack {
+    pub async fn run_tool(
+        params: Self,
+        context: &FileSystemService,
+    ) -> std::result::Result<CallToolResult, CallToolError> {
+        let path = Path::new(&params.path);
+        let pack = context
+            .suggest_paths_on_not_found(
+                path,
+                context.read_context_pack(
+                    path,
+                    params.max_bytes.map(|max_bytes| max_bytes as usize),
+                    params.redact_secrets,
+                ),
+            )
+            .await
+            .map_err(CallToolError::new)?;
+
+        let mut result = vec![TextContent::from(pack.render_text())];
+        if let Some(count) = pack.redactions {
+            result.push(TextContent::from(redactions_footer(count)));
+        }
+        Ok(CallToolResult::text_content(result))
+    }
+}
@@ -0,0 +1,63 @@
+use std::path::Path;
+
+use rust_mcp_sdk::macros::{JsonSchema, mcp_tool};
+use rust_mcp_sdk::schema::TextContent;
+use rust_mcp_sdk::schema::{CallToolResult, schema_utils::CallToolError};
+
+use crate::fs_service::{FileSystemService, ReadDirectoryLimits};
+
+#[mcp_tool(
+    name = "read_directory",
+    title="Read directory",
+    description = concat!("Read every text file in a small directory in one call, such as a folder of ",
+    "configuration files or manifests. Files are returned in alphabetical order of their relative paths, ",
+    "each preceded by a '==> relative/path <==' header. Only the top level is read unless 'recursive' is true. ",
+    "Binary files and files larger than 'max_file_bytes' are skipped; once 'max_total_bytes' is reached ",
+    "the remaining files are skipped. Skipped files are listed at the end with the reason. ",
+    "The call is refused if the directory contains more than 'max_files' files. ",
+    "Only works within allowed directories."),
+    destructive_hint = false,
+    idempotent_hint = false,
+    open_world_hint = false,
+    read_only_hint = true
+)]
+#[derive(::serde::Deserialize, ::serde::Serialize, Clone, Debug, JsonSchema)]
+pub struct ReadDirectory {
+    /// The path of the directory to read.
+    pub path: String,
+    /// Include files in subdirectories. (Default : false)
+    pub recursive: Option<bool>,
+    /// Refuse the read if the directory contains more files than this. (Default : 50)
+    pub max_files: Option<u64>,
+    /// Skip files larger than this many bytes. (Default : 65536)
+    pub max_file_bytes: Option<u64>,
+    /// Maximum combined size of the returned file contents, in bytes. (Default : 262144)
+    pub max_total_bytes: Option<u64>,
+}
+
+impl ReadDirectory {
+    pub async fn run_tool(
+        params: Self,
+        context: &FileSystemService,
+    ) -> std::result::Result<CallToolResult, CallToolError> {
+        let defaults = ReadDirectoryLimits::default();
+        let limits = ReadDirectoryLimits {
+            max_files: params.max_files.unwrap_or(defaults.max_files),
+            max_file_bytes: params.max_file_bytes.unwrap_or(defaults.max_file_bytes),
+            max_total_bytes: params.max_total_bytes.unwrap_or(defaults.max_total_bytes),
+        };
+
+        let contents = context
+            .read_directory(
+                Path::new(&params.path),
+                params.recursive.unwrap_or(false),
+                limits,
+            )
+            .await
+            .map_err(CallToolError::new)?;
+
+        Ok(CallToolResult::text_content(vec![TextContent::from(
+            contents.to_string(),
+        )]))
+    }
+}
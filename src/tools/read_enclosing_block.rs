@@ -0,0 +1,52 @@
+use std::path::Path;
+
+use rust_mcp_sdk::{
+    macros::{JsonSchema, mcp_tool},
+    schema::{CallToolResult, TextContent, schema_utils::CallToolError},
+};
+
+use crate::fs_service::FileSystemService;
+
+#[mcp_tool(
+    name = "read_enclosing_block",
+    title = "Read enclosing block",
+    description = concat!("Returns the innermost block enclosing a line of a source file, such as the function, method, impl or loop holding a match ",
+    "reported by 'search_files_content', together with its start and end line numbers. ",
+    "For brace delimited languages the block runs from its header (with leading doc comments and attributes) to its closing brace; ",
+    "a line opening a block, such as a function signature, returns that block. For Python the block is the indented body of the enclosing header. ",
+    "Braces inside comments and string literals are ignored, for languages whose comment syntax is recognized. ",
+    "Only works within allowed directories."),
+    destructive_hint = false,
+    idempotent_hint = false,
+    open_world_hint = false,
+    read_only_hint = true
+)]
+#[derive(::serde::Deserialize, ::serde::Serialize, Clone, Debug, JsonSchema)]
+pub struct ReadEnclosingBlock {
+    /// The path of the source file.
+    pub path: String,
+    /// The 1-based line whose enclosing block is returned, e.g. the line number of a search match.
+    pub line: u64,
+}
+
+impl ReadEnclosingBlock {
+    pub async fn run_tool(
+        params: Self,
+        context: &FileSystemService,
+    ) -> std::result::Result<CallToolResult, CallToolError> {
+        let path = Path::new(&params.path);
+        let block = context
+            .suggest_paths_on_not_found(
+                path,
+                context.read_enclosing_block(path, params.line as usize),
+            )
+            .await
+            .map_err(CallToolError::new)?;
+        Ok(CallToolResult::text_content(vec![TextContent::from(
+            format!(
+                "{}:{}-{}\n{}",
+                params.path, block.start_line, block.end_line, block.source
+            ),
+        )]))
+    }
+}
@@ -35,11 +35,15 @@ impl ReadFileLines {
         params: Self,
         context: &FileSystemService,
     ) -> std::result::Result<CallToolResult, CallToolError> {
+        let path = Path::new(&params.path);
         let result = context
-            .read_file_lines(
-                Path::new(&params.path),
-                params.offset as usize,
-                params.limit.map(|v| v as usize),
+            .suggest_paths_on_not_found(
+                path,
+                context.read_file_lines(
+                    path,
+                    params.offset as usize,
+                    params.limit.map(|v| v as usize),
+                ),
             )
             .await
             .map_err(CallToolError::new)?;
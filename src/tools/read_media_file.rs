@@ -1,7 +1,8 @@
 use std::path::Path;
 
+use base64::{Engine, engine::general_purpose};
 use rust_mcp_sdk::macros::{JsonSchema, mcp_tool};
-use rust_mcp_sdk::schema::{AudioContent, ImageContent};
+use rust_mcp_sdk::schema::{AudioContent, ContentBlock, ImageContent};
 use rust_mcp_sdk::schema::{CallToolResult, schema_utils::CallToolError};
 
 use crate::error::ServiceError;
@@ -13,6 +14,8 @@ use crate::fs_service::FileSystemService;
     description = concat!("Reads an image or audio file and returns its Base64-encoded content along with the corresponding MIME type. ",
         "The max_bytes argument could be used to enforce an upper limit on the size of a file to read ",
         "if the media file exceeds this limit, the operation will return an error instead of reading the media file. ",
+        "For PNG, JPEG and WebP images, 'max_dimension' downscales the image so that neither side exceeds it, preserving the aspect ratio; ",
+        "the original and returned dimensions are reported in a text block after the image. ",
     "Access is restricted to files within allowed directories only."),
     destructive_hint = false,
     idempotent_hint = false,
@@ -25,6 +28,8 @@ pub struct ReadMediaFile {
     pub path: String,
     /// Maximum allowed file size (in bytes) to be read.
     pub max_bytes: Option<u64>,
+    /// Downscale PNG, JPEG and WebP images so that neither side exceeds this many pixels.
+    pub max_dimension: Option<u64>,
 }
 
 impl ReadMediaFile {
@@ -32,10 +37,37 @@ impl ReadMediaFile {
         params: Self,
         context: &FileSystemService,
     ) -> std::result::Result<CallToolResult, CallToolError> {
+        let path = Path::new(&params.path);
+        if let Some(max_dimension) = params.max_dimension {
+            let image = context
+                .suggest_paths_on_not_found(
+                    path,
+                    context.read_image_scaled(
+                        path,
+                        params.max_bytes.map(|v| v as usize),
+                        max_dimension.min(u32::MAX as u64) as u32,
+                    ),
+                )
+                .await
+                .map_err(CallToolError::new)?;
+            let (original_width, original_height) = image.original_dimensions;
+            let (width, height) = image.dimensions;
+            let mut result = CallToolResult::image_content(vec![ImageContent::new(
+                general_purpose::STANDARD.encode(&image.bytes),
+                image.mime_type,
+                None,
+                None,
+            )]);
+            result.content.push(ContentBlock::text_content(format!(
+                "Image dimensions: original {original_width}x{original_height}, returned {width}x{height}"
+            )));
+            return Ok(result);
+        }
+
         let (kind, content) = context
-            .read_media_file(
-                Path::new(&params.path),
-                params.max_bytes.map(|v| v as usize),
+            .suggest_paths_on_not_found(
+                path,
+                context.read_media_file(path, params.max_bytes.map(|v| v as usize)),
             )
             .await
             .map_err(CallToolError::new)?;
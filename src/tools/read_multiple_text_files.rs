@@ -1,4 +1,4 @@
-use crate::fs_service::FileSystemService;
+use crate::fs_service::{FileSystemService, read_cache::DedupedRead, redaction::redactions_footer};
 use futures::future::join_all;
 use rust_mcp_sdk::macros::{JsonSchema, mcp_tool};
 use rust_mcp_sdk::schema::TextContent;
@@ -12,7 +12,10 @@ use std::path::Path;
     "This is more efficient than reading files one by one when you need to analyze ",
     "or compare multiple files. Each file's content is returned with its ",
     "path as a reference. Failed reads for individual files won't stop ",
-    "the entire operation. Only works within allowed directories."),
+    "the entire operation. Set 'dedupe' to true to receive a short stub instead of the content of ",
+    "files unchanged since they were last returned with 'dedupe' in this session; 'force' returns the ",
+    "full content regardless. Secrets are redacted when enabled by the server or requested with ",
+    "'redact_secrets'. Only works within allowed directories."),
     destructive_hint = false,
     idempotent_hint = false,
     open_world_hint = false,
@@ -22,6 +25,12 @@ use std::path::Path;
 pub struct ReadMultipleTextFiles {
     /// The list of file paths to read.
     pub paths: Vec<String>,
+    /// Return a short stub for files unchanged since they were last returned in this session. (Default : false)
+    pub dedupe: Option<bool>,
+    /// Return the full content even if `dedupe` would return a stub. (Default : false)
+    pub force: Option<bool>,
+    /// Replace well-known secrets with markers, overriding the server setting for this call.
+    pub redact_secrets: Option<bool>,
 }
 
 impl ReadMultipleTextFiles {
@@ -29,28 +38,51 @@ impl ReadMultipleTextFiles {
         params: Self,
         context: &FileSystemService,
     ) -> std::result::Result<CallToolResult, CallToolError> {
+        let dedupe = params.dedupe.unwrap_or_default();
+        let force = params.force.unwrap_or_default();
+        let redact_secrets = params.redact_secrets;
         let content_futures: Vec<_> = params
             .paths
             .iter()
             .map(|path| async move {
                 {
-                    let content = context
-                        .read_text_file(Path::new(&path))
-                        .await
-                        .map_err(CallToolError::new);
+                    let content = if let Err(err) = context.ensure_running() {
+                        Err(err)
+                    } else if dedupe {
+                        context
+                            .read_text_file_deduped(Path::new(&path), force)
+                            .await
+                            .map(|read| match read {
+                                DedupedRead::Content(content) | DedupedRead::Unchanged(content) => {
+                                    content
+                                }
+                            })
+                    } else {
+                        context.read_text_file(Path::new(&path)).await
+                    }
+                    .map_err(CallToolError::new);
 
-                    content.map_or_else(
-                        |err| format!("{path}: Error - {err}"),
-                        |value| format!("{path}:\n{value}\n"),
-                    )
+                    match content {
+                        Ok(value) => {
+                            let (value, redactions) =
+                                context.redact_output(Path::new(&path), redact_secrets, value);
+                            (format!("{path}:\n{value}\n"), redactions)
+                        }
+                        Err(err) => (format!("{path}: Error - {err}"), None),
+                    }
                 }
             })
             .collect();
 
-        let contents = join_all(content_futures).await;
+        let (contents, redactions): (Vec<String>, Vec<Option<usize>>) =
+            join_all(content_futures).await.into_iter().unzip();
 
-        Ok(CallToolResult::text_content(vec![TextContent::from(
-            contents.join("\n---\n"),
-        )]))
+        let mut result = vec![TextContent::from(contents.join("\n---\n"))];
+        // Report the footer whenever redaction applied to at least one file
+        if redactions.iter().any(Option::is_some) {
+            let count = redactions.into_iter().flatten().sum();
+            result.push(TextContent::from(redactions_footer(count)));
+        }
+        Ok(CallToolResult::text_content(result))
     }
 }
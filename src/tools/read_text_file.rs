@@ -3,8 +3,26 @@ use std::path::Path;
 use rust_mcp_sdk::macros::{JsonSchema, mcp_tool};
 use rust_mcp_sdk::schema::TextContent;
 use rust_mcp_sdk::schema::{CallToolResult, schema_utils::CallToolError};
+use serde_json::json;
 
-use crate::fs_service::FileSystemService;
+use crate::fs_service::{
+    FileSystemService, comment_stripping::strip_comments_for_path, read_cache::DedupedRead,
+    redaction::redactions_footer,
+};
+
+#[derive(
+    ::serde::Deserialize, ::serde::Serialize, Clone, Copy, Debug, Default, PartialEq, Eq, JsonSchema,
+)]
+/// How the content of a text file is returned.
+pub enum ReadFormat {
+    /// The file content as is.
+    #[default]
+    #[serde(rename = "text")]
+    Text,
+    /// A JSON object with the lines of the file, without line terminators.
+    #[serde(rename = "lines")]
+    Lines,
+}
 
 #[mcp_tool(
     name = "read_text_file",
@@ -12,7 +30,16 @@ use crate::fs_service::FileSystemService;
     description = concat!("Read the complete contents of a text file from the file system as text. ",
     "Handles various text encodings and provides detailed error messages if the ",
     "file cannot be read. Use this tool when you need to examine the contents of ",
-    "a single file. Only works within allowed directories."),
+    "a single file. Set 'format' to 'lines' to receive a JSON object ",
+    "{\"lines\": [...], \"final_newline\": bool} with the lines stripped of their '\\n' or '\\r\\n' terminators. ",
+    "Set 'dedupe' to true to receive a short stub instead of the content when the file is unchanged ",
+    "since it was last returned with 'dedupe' in this session; 'force' returns the full content regardless. ",
+    "Secrets are redacted when enabled by the server or requested with 'redact_secrets'. ",
+    "Set 'strip_comments' to true to remove line and block comments (string literals are preserved) from source files in recognized languages; ",
+    "lines left blank by a removed comment are dropped, so line numbers may shift. ",
+    "A leading byte order mark is removed by default, where earlier versions returned it as the character U+FEFF; set 'strip_bom' to false to keep it. ",
+    "Files in UTF-16 or UTF-32 are decoded to UTF-8, without their byte order mark. ",
+    "Only works within allowed directories."),
     destructive_hint = false,
     idempotent_hint = false,
     open_world_hint = false,
@@ -22,6 +49,18 @@ use crate::fs_service::FileSystemService;
 pub struct ReadTextFile {
     /// The path of the file to read.
     pub path: String,
+    /// Output format: "text" returns the content as is, "lines" returns a JSON array of lines. (Default : "text")
+    pub format: Option<ReadFormat>,
+    /// Return a short stub if the file is unchanged since it was last returned in this session. (Default : false)
+    pub dedupe: Option<bool>,
+    /// Return the full content even if `dedupe` would return a stub. (Default : false)
+    pub force: Option<bool>,
+    /// Replace well-known secrets with markers, overriding the server setting for this call.
+    pub redact_secrets: Option<bool>,
+    /// Remove comments from source files in recognized languages, keeping string literals. (Default : false)
+    pub strip_comments: Option<bool>,
+    /// Remove a leading UTF-8 byte order mark from the content. Set to false to keep it as the character U+FEFF. (Default : true)
+    pub strip_bom: Option<bool>,
 }
 
 impl ReadTextFile {
@@ -29,13 +68,67 @@ impl ReadTextFile {
         params: Self,
         context: &FileSystemService,
     ) -> std::result::Result<CallToolResult, CallToolError> {
-        let content = context
-            .read_text_file(Path::new(&params.path))
-            .await
-            .map_err(CallToolError::new)?;
+        let path = Path::new(&params.path);
+        let content = if params.dedupe.unwrap_or_default() {
+            match context
+                .suggest_paths_on_not_found(
+                    path,
+                    context.read_text_file_deduped(path, params.force.unwrap_or_default()),
+                )
+                .await
+                .map_err(CallToolError::new)?
+            {
+                DedupedRead::Content(content) => content,
+                DedupedRead::Unchanged(stub) => {
+                    return Ok(CallToolResult::text_content(vec![TextContent::from(stub)]));
+                }
+            }
+        } else {
+            context
+                .suggest_paths_on_not_found(path, context.read_text_file(path))
+                .await
+                .map_err(CallToolError::new)?
+        };
+        let content = match content.strip_prefix('\u{feff}') {
+            Some(stripped) if params.strip_bom.unwrap_or(true) => stripped.to_string(),
+            _ => content,
+        };
+        let mut notes = vec![];
+        let content = if params.strip_comments.unwrap_or_default() {
+            strip_comments_for_path(path, &content).unwrap_or_else(|| {
+                notes.push(format!(
+                    "Comments were not stripped: the language of '{}' is not recognized.",
+                    params.path
+                ));
+                content
+            })
+        } else {
+            content
+        };
+        let (content, redactions) = context.redact_output(path, params.redact_secrets, content);
+
+        let content = match params.format.unwrap_or_default() {
+            ReadFormat::Text => content,
+            ReadFormat::Lines => {
+                let final_newline = content.ends_with('\n');
+                let body = content.strip_suffix('\n').unwrap_or(&content);
+                let lines: Vec<&str> = if content.is_empty() {
+                    vec![]
+                } else {
+                    body.split('\n')
+                        .map(|line| line.strip_suffix('\r').unwrap_or(line))
+                        .collect()
+                };
+                serde_json::to_string(&json!({ "lines": lines, "final_newline": final_newline }))
+                    .map_err(CallToolError::new)?
+            }
+        };
 
-        Ok(CallToolResult::text_content(vec![TextContent::from(
-            content,
-        )]))
+        let mut result = vec![TextContent::from(content)];
+        result.extend(notes.into_iter().map(TextContent::from));
+        if let Some(count) = redactions {
+            result.push(TextContent::from(redactions_footer(count)));
+        }
+        Ok(CallToolResult::text_content(result))
     }
 }
@@ -13,6 +13,7 @@ use crate::fs_service::FileSystemService;
   "and matches partial names. Returns full paths to all matching items.",
   "Optional 'min_bytes' and 'max_bytes' arguments can be used to filter files by size, ",
   "ensuring that only files within the specified byte range are included in the search. ",
+  "Directories are matched too unless 'include_dirs' is false or 'files_only' is true; a pattern ending in '/' (e.g. 'migrations/') only matches directories. ",
   "This tool is great for finding files when you don't know their exact location or find files by their size.",
   "Only searches within allowed directories."),
     destructive_hint = false,
@@ -35,6 +36,10 @@ pub struct SearchFiles {
     pub min_bytes: Option<u64>,
     /// Maximum file size (in bytes) to include in the search (optional).
     pub max_bytes: Option<u64>,
+    /// Include matching directories in the results (default: true).
+    pub include_dirs: Option<bool>,
+    /// Only return regular files, leaving out directories and other entries (default: false).
+    pub files_only: Option<bool>,
 }
 impl SearchFiles {
     pub async fn run_tool(
@@ -51,6 +56,19 @@ impl SearchFiles {
             )
             .await
             .map_err(CallToolError::new)?;
+        let include_dirs = params.include_dirs.unwrap_or(true);
+        let files_only = params.files_only.unwrap_or_default();
+        let list: Vec<_> = list
+            .into_iter()
+            .filter(|entry| {
+                let file_type = entry.file_type();
+                if files_only {
+                    file_type.is_file()
+                } else {
+                    include_dirs || !file_type.is_dir()
+                }
+            })
+            .collect();
 
         let result = if !list.is_empty() {
             list.iter()
@@ -1,5 +1,8 @@
 use crate::error::ServiceError;
-use crate::fs_service::{FileSearchResult, FileSystemService};
+use crate::fs_service::redaction::redactions_footer;
+use crate::fs_service::{
+    ContentQuery, ContentSearchOptions, ContentSearchResults, FileSearchResult, FileSystemService,
+};
 use rust_mcp_sdk::macros::{JsonSchema, mcp_tool};
 use rust_mcp_sdk::schema::TextContent;
 use rust_mcp_sdk::schema::{CallToolResult, schema_utils::CallToolError};
@@ -10,8 +13,13 @@ use std::fmt::Write;
     description = concat!("Searches for text or regex patterns in the content of files matching matching a GLOB pattern.",
                           "Returns detailed matches with file path, line number, column number and a preview of matched text.",
                           "By default, it performs a literal text search; if the 'is_regex' parameter is set to true, it performs a regular expression (regex) search instead.",
+                          "Several patterns can be searched at once with 'queries', matching lines that contain any of them and reporting the number of matching lines per pattern; ",
+                          "lines containing one of the 'exclude_patterns_in_line' are left out. ",
                           "Optional 'min_bytes' and 'max_bytes' arguments can be used to filter files by size, ",
                           "ensuring that only files within the specified byte range are included in the search. ",
+                          "Matches on adjacent lines are merged and repeated snippets are listed once, unless 'dedupe' is false. ",
+                          "Patterns that are too long or compile to too large a regex are rejected, and a search running past the server's time budget returns the matches found so far. ",
+                          "Secrets in the previews are redacted when enabled by the server or requested with 'redact_secrets'. ",
                           "Ideal for finding specific code, comments, or text when you don’t know their exact location."),
     destructive_hint = false,
     idempotent_hint = false,
@@ -27,9 +35,18 @@ pub struct SearchFilesContent {
     /// The file glob pattern to match (e.g., "*.rs").
     pub pattern: String,
     /// Text or regex pattern to find in file contents (e.g., 'TODO' or '^function\\s+').
-    pub query: String,
+    pub query: Option<String>,
+    /// Additional text or regex patterns; lines matching any of them, or `query`, are returned.
+    pub queries: Option<Vec<String>>,
+    /// Text or regex patterns of lines to leave out of the results, even when they match.
+    pub exclude_patterns_in_line: Option<Vec<String>>,
     /// Whether the query is a regular expression. If false, the query as plain text. (Default : false)
     pub is_regex: Option<bool>,
+    /// Whether matching ignores case, using Unicode-aware case folding. (Default : true)
+    pub ignore_case: Option<bool>,
+    /// Normalize the query and file content to Unicode NFC before matching, so composed and
+    /// decomposed forms of accented text match each other. (Default : false)
+    pub normalize_unicode: Option<bool>,
     #[serde(rename = "excludePatterns")]
     /// Optional list of patterns to exclude from the search.
     pub exclude_patterns: Option<Vec<String>>,
@@ -37,6 +54,11 @@ pub struct SearchFilesContent {
     pub min_bytes: Option<u64>,
     /// Maximum file size (in bytes) to include in the search (optional).
     pub max_bytes: Option<u64>,
+    /// Replace well-known secrets in the previews with markers, overriding the server setting for this call.
+    pub redact_secrets: Option<bool>,
+    /// Merge matches on adjacent lines into one group and omit snippets identical to an earlier
+    /// one in the same file, listing their lines instead. (Default : true)
+    pub dedupe: Option<bool>,
 }
 
 impl SearchFilesContent {
@@ -52,12 +74,28 @@ impl SearchFilesContent {
 
             // Push each match line
             for m in &file_result.matches {
-                // Format: "  line:col: text snippet"
-                let _ = writeln!(
+                // Format: "  line:col: text snippet", or "  first-last:col: ..." for merged lines
+                let lines = if m.end_line_number > m.line_number {
+                    format!("{}-{}", m.line_number, m.end_line_number)
+                } else {
+                    m.line_number.to_string()
+                };
+                let _ = write!(
                     output,
                     "  {}:{}: {}",
-                    m.line_number, m.start_pos, m.line_text
+                    lines,
+                    m.start_pos,
+                    m.line_text.replace('\n', "\n    ")
                 );
+                if !m.duplicate_lines.is_empty() {
+                    let duplicates: Vec<String> = m
+                        .duplicate_lines
+                        .iter()
+                        .map(|line| line.to_string())
+                        .collect();
+                    let _ = write!(output, " (also at lines {})", duplicates.join(", "));
+                }
+                output.push('\n');
             }
 
             // double spacing
@@ -66,32 +104,105 @@ impl SearchFilesContent {
 
         output
     }
+    /// Combines `query` and `queries`, in that order.
+    fn content_query(&self) -> ContentQuery {
+        ContentQuery {
+            patterns: self
+                .query
+                .iter()
+                .chain(self.queries.iter().flatten())
+                .cloned()
+                .collect(),
+            exclude_in_line: self.exclude_patterns_in_line.clone().unwrap_or_default(),
+        }
+    }
+
+    /// Lists the number of matching lines per pattern.
+    fn format_pattern_matches(query: &ContentQuery, pattern_matches: &[u64]) -> String {
+        let counts: Vec<String> = query
+            .patterns
+            .iter()
+            .zip(pattern_matches)
+            .map(|(pattern, count)| format!("'{pattern}': {count}"))
+            .collect();
+        format!("Matching lines per pattern: {}.", counts.join(", "))
+    }
+
     pub async fn run_tool(
         params: Self,
         context: &FileSystemService,
     ) -> std::result::Result<CallToolResult, CallToolError> {
-        let is_regex = params.is_regex.unwrap_or_default();
+        let query = params.content_query();
+        if query.patterns.is_empty() {
+            return Ok(CallToolResult::with_error(CallToolError::new(
+                ServiceError::FromString("Either 'query' or 'queries' is required.".into()),
+            )));
+        }
+        let options = ContentSearchOptions {
+            is_regex: params.is_regex.unwrap_or_default(),
+            ignore_case: params.ignore_case.unwrap_or(true),
+            normalize_unicode: params.normalize_unicode.unwrap_or_default(),
+            dedupe: params.dedupe.unwrap_or(true),
+        };
         match context
-            .search_files_content(
+            .search_files_content_query(
                 &params.path,
                 &params.pattern,
-                &params.query,
-                is_regex,
+                &query,
+                options,
                 params.exclude_patterns.to_owned(),
                 params.min_bytes,
                 params.max_bytes,
             )
             .await
         {
-            Ok(results) => {
+            Ok(ContentSearchResults {
+                files: mut results,
+                timed_out,
+                pattern_matches,
+            }) => {
                 if results.is_empty() {
+                    let message = if timed_out {
+                        "No matches found before the search_timeout limit ran out."
+                    } else {
+                        "No matches found in the files content."
+                    };
                     return Ok(CallToolResult::with_error(CallToolError::new(
-                        ServiceError::FromString("No matches found in the files content.".into()),
+                        ServiceError::FromString(message.into()),
                     )));
                 }
-                Ok(CallToolResult::text_content(vec![TextContent::from(
-                    params.format_result(results),
-                )]))
+                let mut redactions = None;
+                for file_result in results.iter_mut() {
+                    for m in file_result.matches.iter_mut() {
+                        let (line_text, count) = context.redact_output(
+                            &file_result.file_path,
+                            params.redact_secrets,
+                            std::mem::take(&mut m.line_text),
+                        );
+                        m.line_text = line_text;
+                        if let Some(count) = count {
+                            *redactions.get_or_insert(0) += count;
+                        }
+                    }
+                }
+
+                let mut content = vec![TextContent::from(params.format_result(results))];
+                if query.patterns.len() > 1 {
+                    content.push(TextContent::from(Self::format_pattern_matches(
+                        &query,
+                        &pattern_matches,
+                    )));
+                }
+                if let Some(count) = redactions {
+                    content.push(TextContent::from(redactions_footer(count)));
+                }
+                if timed_out {
+                    content.push(TextContent::from(
+                        "Search stopped by the search_timeout limit; the matches are partial."
+                            .to_string(),
+                    ));
+                }
+                Ok(CallToolResult::text_content(content))
             }
             Err(err) => Ok(CallToolResult::with_error(CallToolError::new(err))),
         }
@@ -0,0 +1,60 @@
+use std::path::Path;
+
+use rust_mcp_sdk::{
+    macros::{JsonSchema, mcp_tool},
+    schema::{CallToolResult, TextContent, schema_utils::CallToolError},
+};
+
+use crate::fs_service::{FileSystemService, line_sorting::SortOptions};
+
+#[mcp_tool(
+    name = "sort_file",
+    title = "Sort the lines of a file",
+    description = concat!("Sorts the lines of a text file in place, such as a '.gitignore' or a word list. ",
+    "Lines are sorted in byte order, or by the number they start with when 'numeric' is set (lines without a number first, as `sort -n` does). ",
+    "Set 'reverse' for descending order and 'unique' to remove duplicate lines. ",
+    "Line endings and a final line break are preserved. The file is replaced atomically. ",
+    "Returns a diff of the change; set 'dry_run' to preview it without writing. Only works within allowed directories."),
+    destructive_hint = true,
+    idempotent_hint = true,
+    open_world_hint = false,
+    read_only_hint = false
+)]
+#[derive(::serde::Deserialize, ::serde::Serialize, Clone, Debug, JsonSchema)]
+pub struct SortFile {
+    /// The path of the file to sort.
+    pub path: String,
+    /// Sort by the number each line starts with. (Default : false)
+    pub numeric: Option<bool>,
+    /// Sort in descending order. (Default : false)
+    pub reverse: Option<bool>,
+    /// Remove duplicate lines. (Default : false)
+    pub unique: Option<bool>,
+    /// Preview the changes without writing. (Default : false)
+    pub dry_run: Option<bool>,
+}
+
+impl SortFile {
+    pub async fn run_tool(
+        params: Self,
+        context: &FileSystemService,
+    ) -> std::result::Result<CallToolResult, CallToolError> {
+        let options = SortOptions {
+            numeric: params.numeric.unwrap_or_default(),
+            reverse: params.reverse.unwrap_or_default(),
+            unique: params.unique.unwrap_or_default(),
+        };
+        let result = context
+            .sort_file(
+                Path::new(&params.path),
+                options,
+                params.dry_run.unwrap_or_default(),
+            )
+            .await
+            .map_err(CallToolError::new)?;
+
+        Ok(CallToolResult::text_content(vec![TextContent::from(
+            result,
+        )]))
+    }
+}
@@ -1,4 +1,4 @@
-use std::path::Path;
+use std::{path::Path, time::Duration};
 
 use rust_mcp_sdk::{
     macros::{JsonSchema, mcp_tool},
@@ -11,9 +11,11 @@ use crate::fs_service::FileSystemService;
 #[mcp_tool(
     name = "tail_file",
     title="Tail file",
-    description = concat!("Reads and returns the last N lines of a text file.",
-    "This is useful for quickly previewing file contents without loading the entire file into memory.",
-    "If the file has fewer than N lines, the entire file will be returned.",
+    description = concat!("Reads and returns the last N lines of a text file. ",
+    "This is useful for quickly previewing file contents without loading the entire file into memory. ",
+    "If the file has fewer than N lines, the entire file will be returned. ",
+    "Set 'follow_seconds' (at most 60) to then keep watching a growing file, such as a log, and also return the lines appended during that window. ",
+    "Set 'pattern' to a regular expression to only return the matching lines, like `tail -f | grep`. ",
     "Only works within allowed directories."),
     destructive_hint = false,
     idempotent_hint = false,
@@ -22,10 +24,14 @@ use crate::fs_service::FileSystemService;
 )]
 #[derive(::serde::Deserialize, ::serde::Serialize, Clone, Debug, JsonSchema)]
 pub struct TailFile {
-    /// The path of the file to get information for.
+    /// The path of the file to read.
     pub path: String,
-    /// The number of lines to read from the beginning of the file.
+    /// The number of lines to read from the end of the file.
     pub lines: u64,
+    /// A regular expression; only the matching lines are returned, from the tail and the followed window.
+    pub pattern: Option<String>,
+    /// Seconds to keep following the file for appended lines, at most 60. (Default : 0, no following)
+    pub follow_seconds: Option<u64>,
 }
 
 impl TailFile {
@@ -33,10 +39,25 @@ impl TailFile {
         params: Self,
         context: &FileSystemService,
     ) -> std::result::Result<CallToolResult, CallToolError> {
-        let result = context
-            .tail_file(Path::new(&params.path), params.lines as usize)
-            .await
-            .map_err(CallToolError::new)?;
+        let path = Path::new(&params.path);
+        let lines = params.lines as usize;
+        let follow = params
+            .follow_seconds
+            .filter(|seconds| *seconds > 0)
+            .map(Duration::from_secs);
+        let result = if params.pattern.is_none() && follow.is_none() {
+            context
+                .suggest_paths_on_not_found(path, context.tail_file(path, lines))
+                .await
+        } else {
+            context
+                .suggest_paths_on_not_found(
+                    path,
+                    context.tail_and_follow(path, lines, params.pattern.as_deref(), follow),
+                )
+                .await
+        }
+        .map_err(CallToolError::new)?;
 
         Ok(CallToolResult::text_content(vec![TextContent::from(
             result,
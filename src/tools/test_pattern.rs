@@ -0,0 +1,93 @@
+use std::path::Path;
+
+use rust_mcp_sdk::{
+    macros::{JsonSchema, mcp_tool},
+    schema::{CallToolResult, TextContent, schema_utils::CallToolError},
+};
+
+use crate::error::ServiceError;
+use crate::fs_service::FileSystemService;
+use crate::fs_service::pattern_test::{DEFAULT_PATTERN_SAMPLES, MAX_PATTERN_SAMPLES};
+
+#[derive(
+    ::serde::Deserialize, ::serde::Serialize, Clone, Copy, Debug, PartialEq, Eq, JsonSchema,
+)]
+/// The syntax of a tested pattern.
+pub enum PatternMode {
+    /// A glob, as taken by `search_files` and the exclude patterns of the search tools.
+    #[serde(rename = "glob")]
+    Glob,
+    /// A regular expression, as taken by `search_files_content` with `is_regex`.
+    #[serde(rename = "regex")]
+    Regex,
+}
+
+#[mcp_tool(
+    name = "test_pattern",
+    title = "Test a glob or regex pattern",
+    description = concat!("Tests a glob or regular expression against a few samples before running an expensive search with it. ",
+    "The pattern is compiled exactly as the search tools compile it: a glob as the 'pattern' of search_files (matched against entry names, ",
+    "case-insensitively; a pattern without '*' matches names containing it; a trailing '/' only matches directories), ",
+    "a regex as the query of search_files_content (matched against each line). ",
+    "Returns how the pattern is interpreted, which samples match, and compile errors with the position of the problem. ",
+    "Samples are given inline in 'samples' (paths for a glob, lines for a regex; end a path with '/' to mark a directory), ",
+    "or taken from 'sample_path': the first entries below a directory, or the first lines of a file, up to 'max_samples'. ",
+    "Nothing large is walked or read. Only works within allowed directories."),
+    destructive_hint = false,
+    idempotent_hint = true,
+    open_world_hint = false,
+    read_only_hint = true
+)]
+#[derive(::serde::Deserialize, ::serde::Serialize, Clone, Debug, JsonSchema)]
+pub struct TestPattern {
+    /// The glob or regular expression to test.
+    pub pattern: String,
+    /// Either `glob` or `regex`.
+    pub mode: PatternMode,
+    /// Paths (for a glob) or lines (for a regex) to test the pattern against.
+    pub samples: Option<Vec<String>>,
+    /// A directory whose first entries, or a file whose first lines, are added to the samples.
+    pub sample_path: Option<String>,
+    /// Number of samples taken from 'sample_path', at most 1000 (default: 20).
+    pub max_samples: Option<u64>,
+    /// Match a regex case-insensitively, as search_files_content does by default (default: true).
+    pub ignore_case: Option<bool>,
+}
+
+impl TestPattern {
+    pub async fn run_tool(
+        params: Self,
+        context: &FileSystemService,
+    ) -> std::result::Result<CallToolResult, CallToolError> {
+        let mut samples = params.samples.unwrap_or_default();
+        if let Some(sample_path) = &params.sample_path {
+            let limit = params
+                .max_samples
+                .map_or(DEFAULT_PATTERN_SAMPLES, |limit| limit as usize)
+                .min(MAX_PATTERN_SAMPLES);
+            samples.extend(
+                context
+                    .pattern_samples(Path::new(sample_path), limit)
+                    .await
+                    .map_err(CallToolError::new)?,
+            );
+        }
+        if samples.is_empty() {
+            return Ok(CallToolResult::with_error(CallToolError::new(
+                ServiceError::FromString(
+                    "Either 'samples' or 'sample_path' is required.".to_string(),
+                ),
+            )));
+        }
+
+        let test = context.test_pattern(
+            &params.pattern,
+            params.mode,
+            samples,
+            params.ignore_case.unwrap_or(true),
+        );
+        Ok(CallToolResult::text_content(vec![TextContent::from(
+            test.render_text(),
+        )]))
+    }
+}
@@ -12,9 +12,11 @@ use crate::fs_service::FileSystemService;
     title="Write file",
     description = concat!("Create a new file or completely overwrite an existing file with new content. ",
 "Use with caution as it will overwrite existing files without warning. ",
+"Warns when the file name differs only in case or Unicode normalization from an existing name in the same directory, ",
+"which is the same file on some file systems; set 'strict_case' to refuse the write instead. ",
 "Handles text content with proper encoding. Only works within allowed directories."),
-    destructive_hint = false,
-    idempotent_hint = false,
+    destructive_hint = true,
+    idempotent_hint = true,
     open_world_hint = false,
     read_only_hint = false
 )]
@@ -24,6 +26,8 @@ pub struct WriteFile {
     pub path: String,
     /// The content to write to the file.
     pub content: String,
+    /// Refuse to write when the name differs only in case or Unicode normalization from an existing name. (Default : false)
+    pub strict_case: Option<bool>,
 }
 
 impl WriteFile {
@@ -31,13 +35,23 @@ impl WriteFile {
         params: Self,
         context: &FileSystemService,
     ) -> std::result::Result<CallToolResult, CallToolError> {
+        let path = Path::new(&params.path);
+        let collision = context
+            .check_name_collision(path, None, params.strict_case.unwrap_or_default())
+            .await
+            .map_err(CallToolError::new)?;
+        context.ensure_running().map_err(CallToolError::new)?;
         context
-            .write_file(Path::new(&params.path), &params.content)
+            .write_file(path, &params.content)
             .await
             .map_err(CallToolError::new)?;
 
+        let mut output = format!("Successfully wrote to {}", &params.path);
+        if let Some(collision) = collision {
+            output.push_str(&format!("\nWarning: {collision}."));
+        }
         Ok(CallToolResult::text_content(vec![TextContent::from(
-            format!("Successfully wrote to {}", &params.path),
+            output,
         )]))
     }
 }
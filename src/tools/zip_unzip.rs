@@ -1,17 +1,37 @@
+use std::path::Path;
+
 use rust_mcp_sdk::macros::{JsonSchema, mcp_tool};
 use rust_mcp_sdk::schema::TextContent;
 use rust_mcp_sdk::schema::{CallToolResult, schema_utils::CallToolError};
 
+use crate::error::ServiceError;
 use crate::fs_service::FileSystemService;
 
+#[derive(
+    ::serde::Deserialize, ::serde::Serialize, Clone, Copy, Debug, Default, PartialEq, Eq, JsonSchema,
+)]
+/// How symbolic links stored in an archive are extracted.
+pub enum SymlinkPolicy {
+    /// Do not extract symbolic links.
+    #[default]
+    #[serde(rename = "skip")]
+    Skip,
+    /// Write a copy of the target's content, if the target is a file inside the archive.
+    #[serde(rename = "materialize")]
+    Materialize,
+    /// Recreate the link, if its target stays inside the extraction root.
+    #[serde(rename = "create")]
+    Create,
+}
+
 #[mcp_tool(
     name = "zip_files",
     title="Zip files",
     description = concat!("Creates a ZIP archive by compressing files. ",
 "It takes a list of files to compress and a target path for the resulting ZIP file. ",
 "Both the source files and the target ZIP file should reside within allowed directories."),
-    destructive_hint = false,
-    idempotent_hint = false,
+    destructive_hint = true,
+    idempotent_hint = true,
     open_world_hint = false,
     read_only_hint = false
 )]
@@ -45,7 +65,13 @@ impl ZipFiles {
     description = "Extracts the contents of a ZIP archive to a specified target directory.
 It takes a source ZIP file path and a target extraction directory.
 The tool decompresses all files and directories stored in the ZIP, recreating their structure in the target location.
-Both the source ZIP file and the target directory should reside within allowed directories."
+Symbolic links stored in the archive are handled according to 'on_symlink': 'skip' (default) ignores them, 'materialize' copies the content of targets that are files inside the archive, and 'create' recreates links whose target stays inside the extraction root. The result lists what happened to each link.
+Archives holding an entry whose path is absolute or escapes the target directory are refused before anything is extracted.
+Both the source ZIP file and the target directory should reside within allowed directories.",
+    destructive_hint = false,
+    idempotent_hint = false,
+    open_world_hint = false,
+    read_only_hint = false
 )]
 #[derive(::serde::Deserialize, ::serde::Serialize, Clone, Debug, JsonSchema)]
 pub struct UnzipFile {
@@ -53,6 +79,8 @@ pub struct UnzipFile {
     pub zip_file: String,
     /// Path to the target directory where the contents of the ZIP file will be extracted.
     pub target_path: String,
+    /// How to extract symbolic links stored in the archive: "skip", "materialize" or "create". (Default : "skip")
+    pub on_symlink: Option<SymlinkPolicy>,
 }
 
 impl UnzipFile {
@@ -61,7 +89,11 @@ impl UnzipFile {
         context: &FileSystemService,
     ) -> std::result::Result<CallToolResult, CallToolError> {
         let result_content = context
-            .unzip_file(&params.zip_file, &params.target_path)
+            .unzip_file_with(
+                &params.zip_file,
+                &params.target_path,
+                params.on_symlink.unwrap_or_default(),
+            )
             .await
             .map_err(CallToolError::new)?;
         //TODO: return resource?
@@ -76,7 +108,12 @@ impl UnzipFile {
     title = "Zip Directory",
     description = "Creates a ZIP archive by compressing a directory , including files and subdirectories matching a specified glob pattern.
 It takes a path to the folder and a glob pattern to identify files to compress and a target path for the resulting ZIP file.
-Both the source directory and the target ZIP file should reside within allowed directories."
+Both the source directory and the target ZIP file should reside within allowed directories.
+Entries that cannot be read are listed in an 'Errors:' section of the result, or fail the call when 'strict' is true.",
+    destructive_hint = true,
+    idempotent_hint = true,
+    open_world_hint = false,
+    read_only_hint = false
 )]
 #[derive(::serde::Deserialize, ::serde::Serialize, Clone, Debug, JsonSchema)]
 pub struct ZipDirectory {
@@ -86,6 +123,8 @@ pub struct ZipDirectory {
     pub pattern: Option<String>,
     /// Path to save the resulting ZIP file, including filename and .zip extension
     pub target_zip_file: String,
+    /// Fail without creating the archive when any entry cannot be read (default: false).
+    pub strict: Option<bool>,
 }
 
 impl ZipDirectory {
@@ -95,7 +134,12 @@ impl ZipDirectory {
     ) -> std::result::Result<CallToolResult, CallToolError> {
         let pattern = params.pattern.unwrap_or("**/*".to_string());
         let result_content = context
-            .zip_directory(params.input_directory, pattern, params.target_zip_file)
+            .zip_directory_with(
+                params.input_directory,
+                pattern,
+                params.target_zip_file,
+                params.strict.unwrap_or_default(),
+            )
             .await
             .map_err(CallToolError::new)?;
         //TODO: return resource?
@@ -104,3 +148,45 @@ impl ZipDirectory {
         )]))
     }
 }
+
+#[mcp_tool(
+    name = "verify_archive",
+    title = "Verify archive",
+    description = "Verifies a ZIP archive without extracting it.
+The central directory and the local header of every entry are read. With 'deep' (the default), every entry is also decompressed and its size and CRC-32 checked, which detects corrupted content.
+Reports the number of entries, their total uncompressed size and every entry that failed with the reason. The call fails with 'central directory is unreadable' when the archive cannot be opened at all, and with the list of failed entries when any entry fails.
+The archive should reside within allowed directories.",
+    destructive_hint = false,
+    idempotent_hint = true,
+    open_world_hint = false,
+    read_only_hint = true
+)]
+#[derive(::serde::Deserialize, ::serde::Serialize, Clone, Debug, JsonSchema)]
+pub struct VerifyArchive {
+    /// A filesystem path to an existing ZIP file to verify.
+    pub zip_file: String,
+    /// Decompress every entry and check its CRC, rather than only reading the headers. (Default : true)
+    pub deep: Option<bool>,
+}
+
+impl VerifyArchive {
+    pub async fn run_tool(
+        params: Self,
+        context: &FileSystemService,
+    ) -> std::result::Result<CallToolResult, CallToolError> {
+        let verification = context
+            .verify_archive(Path::new(&params.zip_file), params.deep.unwrap_or(true))
+            .await
+            .map_err(CallToolError::new)?;
+
+        let report = verification.render_text(&context.byte_format());
+        if !verification.is_valid() {
+            return Ok(CallToolResult::with_error(CallToolError::new(
+                ServiceError::FromString(report),
+            )));
+        }
+        Ok(CallToolResult::text_content(vec![TextContent::from(
+            report,
+        )]))
+    }
+}
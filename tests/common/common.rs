@@ -72,6 +72,7 @@ pub fn create_temp_file_info(content: &[u8]) -> (PathBuf, FileInfo) {
         accessed: metadata.accessed().ok(),
         is_directory: metadata.is_dir(),
         is_file: metadata.is_file(),
+        is_symlink: metadata.is_symlink(),
         metadata,
     };
     (dir, file_info)
@@ -88,6 +89,7 @@ pub fn create_temp_dir() -> (TempDir, FileInfo) {
         accessed: metadata.accessed().ok(),
         is_directory: metadata.is_dir(),
         is_file: metadata.is_file(),
+        is_symlink: metadata.is_symlink(),
         metadata,
     };
     (dir, file_info)
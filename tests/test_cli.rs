@@ -35,6 +35,37 @@ fn test_parse_with_write_flag_long() {
     assert!(result.allow_write);
 }
 
+#[test]
+fn test_parse_with_redaction_flags() {
+    let args = [
+        "mcp-server",
+        "--redact-secrets",
+        "--redact-exclude",
+        "**/fixtures/**,**/*.pem",
+        "/path/to/dir",
+    ];
+    let result = parse_args(&args).unwrap();
+    assert!(result.redact_secrets);
+    assert_eq!(result.redact_exclude, vec!["**/fixtures/**", "**/*.pem"]);
+    assert_eq!(result.allowed_directories, vec!["/path/to/dir"]);
+}
+
+#[test]
+fn test_parse_with_extension_filters() {
+    let args = [
+        "mcp-server",
+        "--writable-extensions",
+        "md,txt",
+        "--blocked-extensions",
+        "sh",
+        "/path/to/dir",
+    ];
+    let result = parse_args(&args).unwrap();
+    assert_eq!(result.writable_extensions, vec!["md", "txt"]);
+    assert_eq!(result.blocked_extensions, vec!["sh"]);
+    assert_eq!(result.allowed_directories, vec!["/path/to/dir"]);
+}
+
 #[test]
 fn test_missing_required_directories() {
     let args = ["mcp-server"];
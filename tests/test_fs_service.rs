@@ -10,15 +10,43 @@ use common::setup_service;
 use dirs::home_dir;
 use grep::matcher::Match;
 use rust_mcp_filesystem::error::ServiceError;
-use rust_mcp_filesystem::fs_service::FileSystemService;
+use rust_mcp_filesystem::fs_service::bulk_insert::InsertStatus;
+use rust_mcp_filesystem::fs_service::comment_stripping::strip_comments_for_path;
+use rust_mcp_filesystem::fs_service::config::{MIN_STREAM_BUFFER_SIZE, PerformanceConfig};
+use rust_mcp_filesystem::fs_service::deletion::{DeleteOptions, execute_delete, plan_delete};
+use rust_mcp_filesystem::fs_service::disk_space::SpaceProvider;
 use rust_mcp_filesystem::fs_service::file_info::FileInfo;
+use rust_mcp_filesystem::fs_service::flatten::FlattenReport;
+use rust_mcp_filesystem::fs_service::line_sorting::SortOptions;
+use rust_mcp_filesystem::fs_service::name_collisions::NameCollisionKind;
+use rust_mcp_filesystem::fs_service::read_cache::DedupedRead;
+use rust_mcp_filesystem::fs_service::tabular::{ColumnType, MAX_PREVIEW_ROWS, TabularOptions};
+use rust_mcp_filesystem::fs_service::telemetry::Counter;
+use rust_mcp_filesystem::fs_service::text_probe::{
+    FileClass, TEXT_PROBE_SIZE, TextEncoding, classify, classify_bytes,
+};
 use rust_mcp_filesystem::fs_service::utils::*;
-use rust_mcp_filesystem::tools::EditOperation;
+use rust_mcp_filesystem::fs_service::write_budget::BudgetCounter;
+use rust_mcp_filesystem::fs_service::{
+    ConditionalWriteOutcome, ContentQuery, ContentSearchOptions, DirectoryFile,
+    DirectoryTreeSummary, FileSearchResult, FileSystemService, InvalidDirectoryPolicy, ListedEntry,
+    MAX_SUGGESTION_SCAN_ENTRIES, PathKind, ReadDirectoryLimits,
+};
+use rust_mcp_filesystem::fs_service::{instrumentation, open_files};
+use rust_mcp_filesystem::tools::{
+    CommentStyle, EditOperation, Indentation, JsonValue, LineEditKind, LineEditOperation,
+    LineEnding, PatternMode, PreconditionKind, StructuredEditKind, StructuredEditOperation,
+    SymlinkPolicy, TargetEncoding, WritePrecondition,
+};
+use serde_json::{Value, json};
 use std::fs::{self, File};
 use std::io::Write;
 use std::path::{Path, PathBuf};
-use std::time::SystemTime;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::{Duration, SystemTime};
 use tokio::fs as tokio_fs;
+use tokio::io::AsyncBufReadExt;
 use tokio_util::compat::TokioAsyncReadCompatExt;
 
 use crate::common::create_sub_dir;
@@ -40,9 +68,82 @@ async fn test_try_new_success() {
 }
 
 #[test]
-#[should_panic(expected = "Error: /does/not/exist is not a directory")]
 fn test_try_new_invalid_directory() {
-    let _ = FileSystemService::try_new(&["/does/not/exist".to_string()]);
+    let result = FileSystemService::try_new(&["/does/not/exist".to_string()]);
+    assert!(matches!(
+        result,
+        Err(ServiceError::InvalidAllowedDirectories(message)) if message.contains("/does/not/exist: does not exist")
+    ));
+}
+
+#[test]
+fn test_try_new_mixed_roots_fail_policy() {
+    let temp_dir = get_temp_dir();
+    let file_path = create_temp_file(&temp_dir, "file.txt", "content");
+    let dirs = [
+        temp_dir.to_str().unwrap().to_string(),
+        "/does/not/exist".to_string(),
+        file_path.to_str().unwrap().to_string(),
+    ];
+
+    let result = FileSystemService::try_new_with_policy(&dirs, InvalidDirectoryPolicy::Fail);
+    let Err(ServiceError::InvalidAllowedDirectories(message)) = result else {
+        panic!("Expected InvalidAllowedDirectories error");
+    };
+    // every bad entry is reported, not just the first one
+    assert!(message.contains("/does/not/exist: does not exist"));
+    assert!(message.contains(&format!("{}: is not a directory", file_path.display())));
+}
+
+#[tokio::test]
+async fn test_try_new_mixed_roots_skip_policy() {
+    let temp_dir = get_temp_dir();
+    let file_path = create_temp_file(&temp_dir, "file.txt", "content");
+    let dirs = [
+        temp_dir.to_str().unwrap().to_string(),
+        "/does/not/exist".to_string(),
+        file_path.to_str().unwrap().to_string(),
+    ];
+
+    let service =
+        FileSystemService::try_new_with_policy(&dirs, InvalidDirectoryPolicy::Skip).unwrap();
+    assert_eq!(*service.allowed_directories().await, vec![temp_dir]);
+
+    let rejected = service.rejected_directories();
+    assert_eq!(rejected.len(), 2);
+    assert_eq!(rejected[0].entry, "/does/not/exist");
+    assert_eq!(rejected[0].reason, "does not exist");
+    assert_eq!(rejected[1].entry, file_path.to_str().unwrap());
+    assert_eq!(rejected[1].reason, "is not a directory");
+}
+
+#[tokio::test]
+async fn test_try_new_file_uri_root() {
+    let temp_dir = get_temp_dir();
+    let sub_dir = temp_dir.join("my dir");
+    fs::create_dir_all(&sub_dir).unwrap();
+    let uri = format!("file://{}", sub_dir.to_str().unwrap().replace(' ', "%20"));
+
+    let service = FileSystemService::try_new(&[uri]).unwrap();
+    assert_eq!(*service.allowed_directories().await, vec![sub_dir]);
+    assert!(service.rejected_directories().is_empty());
+}
+
+#[tokio::test]
+async fn test_try_new_relative_root_resolved_with_warning() {
+    // tests run from the crate root, where the `src` directory exists
+    let service = FileSystemService::try_new(&["src".to_string()]).unwrap();
+    let expected = std::env::current_dir()
+        .unwrap()
+        .join("src")
+        .canonicalize()
+        .unwrap();
+    assert_eq!(*service.allowed_directories().await, vec![expected]);
+
+    let warnings = service.directory_warnings();
+    assert_eq!(warnings.len(), 1);
+    assert_eq!(warnings[0].entry, "src");
+    assert!(warnings[0].reason.contains("relative path"));
 }
 
 #[tokio::test]
@@ -152,6 +253,97 @@ async fn test_zip_directory_already_exists() {
     ));
 }
 
+#[cfg(unix)]
+#[tokio::test]
+async fn test_zip_directory_reports_walk_errors() {
+    let (temp_dir, service, _allowed_dirs) = setup_service(vec!["dir1".to_string()]);
+    let dir_path = temp_dir.join("dir1");
+    create_temp_file(&dir_path, "file1.txt", "content1");
+    std::os::unix::fs::symlink(dir_path.join("missing.txt"), dir_path.join("broken.txt")).unwrap();
+
+    let zip_path = dir_path.join("output.zip");
+    let result = service
+        .zip_directory(
+            dir_path.to_str().unwrap().to_string(),
+            "*.txt".to_string(),
+            zip_path.to_str().unwrap().to_string(),
+        )
+        .await
+        .unwrap();
+    assert!(result.contains("Successfully compressed"));
+    assert!(result.contains("1 entries could not be read"));
+    assert!(result.contains(&format!(
+        "Errors:\n  - {}: entity not found",
+        dir_path.join("broken.txt").display()
+    )));
+
+    let strict_zip_path = dir_path.join("strict.zip");
+    let result = service
+        .zip_directory_with(
+            dir_path.to_str().unwrap().to_string(),
+            "*.txt".to_string(),
+            strict_zip_path.to_str().unwrap().to_string(),
+            true,
+        )
+        .await;
+    assert!(
+        matches!(result, Err(ServiceError::FromString(message)) if message.contains("broken.txt"))
+    );
+    assert!(!strict_zip_path.exists());
+}
+
+#[cfg(unix)]
+#[tokio::test]
+async fn test_directory_tree_collects_walk_errors() {
+    let (temp_dir, service, allowed_dirs) = setup_service(vec!["dir1".to_string()]);
+    let root = temp_dir.join("dir1");
+    create_temp_file(&root.join("readable"), "a.txt", "a");
+    create_temp_file(&root.join("readable"), "b.txt", "b");
+    create_temp_file(&root, "top.txt", "top");
+    std::os::unix::fs::symlink(root.join("missing"), root.join("dangling")).unwrap();
+    let locked = root.join("locked");
+    create_temp_file(&locked, "hidden.txt", "hidden");
+    fs::set_permissions(&locked, fs::Permissions::from_mode(0o000)).unwrap();
+    let locked_unreadable = fs::read_dir(&locked).is_err();
+
+    let mut count = 0;
+    let mut summary = DirectoryTreeSummary::default();
+    let result = service.directory_tree(
+        &root,
+        None,
+        None,
+        &mut count,
+        allowed_dirs.clone(),
+        &mut summary,
+    );
+    fs::set_permissions(&locked, fs::Permissions::from_mode(0o755)).unwrap();
+    let (tree, _) = result.unwrap();
+
+    // siblings of the failing entries are fully listed
+    let readable = tree
+        .as_array()
+        .unwrap()
+        .iter()
+        .find(|entry| entry["name"] == "readable")
+        .unwrap();
+    assert_eq!(readable["children"].as_array().unwrap().len(), 2);
+    assert!(tree.to_string().contains("top.txt"));
+
+    let failed: Vec<_> = summary
+        .walk_errors
+        .errors
+        .iter()
+        .map(|error| (error.path.clone(), error.kind.clone()))
+        .collect();
+    assert!(failed.contains(&(root.join("dangling"), "entity not found".to_string())));
+    // privileged test runs can read the locked directory anyway
+    if locked_unreadable {
+        assert!(failed.contains(&(locked.clone(), "permission denied".to_string())));
+    }
+    assert!(summary.walk_errors.render().starts_with("Errors:\n  - "));
+    assert!(summary.walk_errors.check_strict().is_err());
+}
+
 #[tokio::test]
 async fn test_zip_files() {
     let (temp_dir, service, _allowed_dirs) = setup_service(vec!["dir1".to_string()]);
@@ -244,6 +436,55 @@ async fn test_create_directory() {
     assert!(new_dir.is_dir());
 }
 
+#[tokio::test]
+async fn test_create_directory_atomic() {
+    let (temp_dir, service, _allowed_dirs) = setup_service(vec!["dir1".to_string()]);
+    let new_dir = temp_dir.join("dir1").join("a").join("b").join("c");
+    service.create_directory_atomic(&new_dir).await.unwrap();
+    assert!(new_dir.is_dir());
+
+    // existing directories are fine
+    service.create_directory_atomic(&new_dir).await.unwrap();
+}
+
+#[cfg(unix)]
+#[tokio::test]
+async fn test_create_directory_atomic_removes_partial_directories() {
+    let (temp_dir, service, _allowed_dirs) = setup_service(vec!["dir1".to_string()]);
+    let base = temp_dir.join("dir1");
+
+    // "a" and "b" are created before the over-long component fails
+    let path = base.join("a").join("b").join("x".repeat(300)).join("c");
+    let result = service.create_directory_atomic(&path).await;
+    assert!(result.is_err());
+    assert!(!base.join("a").exists());
+}
+
+#[cfg(unix)]
+#[tokio::test]
+async fn test_create_directory_atomic_unwritable_component() {
+    let (temp_dir, service, _allowed_dirs) = setup_service(vec!["dir1".to_string()]);
+    let base = temp_dir.join("dir1");
+    let locked = base.join("locked");
+    fs::create_dir(&locked).unwrap();
+    fs::set_permissions(&locked, fs::Permissions::from_mode(0o555)).unwrap();
+    if fs::create_dir(locked.join("probe")).is_ok() {
+        // running with privileges that ignore directory permissions
+        return;
+    }
+
+    let result = service
+        .create_directory_atomic(&locked.join("a").join("b"))
+        .await;
+    fs::set_permissions(&locked, fs::Permissions::from_mode(0o755)).unwrap();
+
+    assert!(matches!(
+        result,
+        Err(ServiceError::IoError(ref e)) if e.kind() == std::io::ErrorKind::PermissionDenied
+    ));
+    assert_eq!(fs::read_dir(&locked).unwrap().count(), 0);
+}
+
 #[tokio::test]
 async fn test_move_file() {
     let (temp_dir, service, _allowed_dirs) = setup_service(vec!["dir1".to_string()]);
@@ -281,6 +522,170 @@ async fn test_write_file() {
     assert_eq!(tokio_fs::read_to_string(&file_path).await.unwrap(), content);
 }
 
+#[cfg(unix)]
+#[tokio::test]
+async fn test_new_paths_under_a_symlinked_root_are_allowed() {
+    let temp_dir = get_temp_dir();
+    let real = temp_dir.join("real");
+    fs::create_dir_all(&real).unwrap();
+    let link = temp_dir.join("link");
+    std::os::unix::fs::symlink(&real, &link).unwrap();
+    let service = FileSystemService::try_new(&[link.to_str().unwrap().to_string()]).unwrap();
+
+    service
+        .write_file(&link.join("new.txt"), &"content".to_string())
+        .await
+        .unwrap();
+    assert_eq!(fs::read_to_string(real.join("new.txt")).unwrap(), "content");
+    service
+        .create_directory(&link.join("a").join("b"))
+        .await
+        .unwrap();
+    assert!(real.join("a").join("b").is_dir());
+
+    let escaping = link
+        .join("missing")
+        .join("..")
+        .join("..")
+        .join("escape.txt");
+    assert!(
+        service
+            .write_file(&escaping, &"content".to_string())
+            .await
+            .is_err()
+    );
+    assert!(!temp_dir.join("escape.txt").exists());
+}
+
+#[tokio::test]
+async fn test_write_file_if_preconditions() {
+    let (temp_dir, service, _allowed_dirs) = setup_service(vec!["dir1".to_string()]);
+    let file_path = temp_dir.join("dir1").join("app.conf");
+    let precondition = |kind, value: Option<&str>| WritePrecondition {
+        kind,
+        value: value.map(String::from),
+    };
+    let write = |content: &str, precondition| {
+        let service = &service;
+        let file_path = &file_path;
+        let content = content.to_string();
+        async move {
+            service
+                .write_file_if(file_path, &content, &precondition)
+                .await
+                .unwrap()
+        }
+    };
+
+    // not_exists writes a missing file, then skips once it exists
+    assert_eq!(
+        write(
+            "port = 80\n",
+            precondition(PreconditionKind::NotExists, None)
+        )
+        .await,
+        ConditionalWriteOutcome::Written
+    );
+    assert_eq!(
+        write(
+            "port = 81\n",
+            precondition(PreconditionKind::NotExists, None)
+        )
+        .await,
+        ConditionalWriteOutcome::Skipped("the file already exists".to_string())
+    );
+
+    assert_eq!(
+        write(
+            "port = 8080\n",
+            precondition(PreconditionKind::Regex, Some(r"^port = \d{2}$"))
+        )
+        .await,
+        ConditionalWriteOutcome::Skipped("the content does not match the pattern".to_string())
+    );
+    assert_eq!(
+        write(
+            "port = 8080\n",
+            precondition(PreconditionKind::Regex, Some(r"(?m)^port = \d{2}$"))
+        )
+        .await,
+        ConditionalWriteOutcome::Written
+    );
+
+    assert_eq!(
+        write(
+            "port = 9090\n",
+            precondition(PreconditionKind::Exact, Some("port = 8080"))
+        )
+        .await,
+        ConditionalWriteOutcome::Skipped(
+            "the content differs from the expected content".to_string()
+        )
+    );
+    assert_eq!(
+        write(
+            "port = 9090\n",
+            precondition(PreconditionKind::Exact, Some("port = 8080\n"))
+        )
+        .await,
+        ConditionalWriteOutcome::Written
+    );
+    assert_eq!(fs::read_to_string(&file_path).unwrap(), "port = 9090\n");
+
+    let missing = temp_dir.join("dir1").join("missing.conf");
+    let outcome = service
+        .write_file_if(
+            &missing,
+            &"x".to_string(),
+            &precondition(PreconditionKind::Exact, Some("x")),
+        )
+        .await
+        .unwrap();
+    assert_eq!(
+        outcome,
+        ConditionalWriteOutcome::Skipped("the file does not exist".to_string())
+    );
+    assert!(!missing.exists());
+    assert!(
+        service
+            .write_file_if(
+                &file_path,
+                &"x".to_string(),
+                &precondition(PreconditionKind::Regex, None)
+            )
+            .await
+            .is_err()
+    );
+}
+
+#[cfg(unix)]
+#[tokio::test]
+async fn test_write_file_if_not_exists_never_follows_links() {
+    let (temp_dir, service, _allowed_dirs) = setup_service(vec!["dir1".to_string()]);
+    let dir = temp_dir.join("dir1");
+    let target = dir.join("target.conf");
+    let link = dir.join("link.conf");
+    std::os::unix::fs::symlink(&target, &link).unwrap();
+
+    let outcome = service
+        .write_file_if(
+            &link,
+            &"port = 80\n".to_string(),
+            &WritePrecondition {
+                kind: PreconditionKind::NotExists,
+                value: None,
+            },
+        )
+        .await
+        .unwrap();
+    assert_eq!(
+        outcome,
+        ConditionalWriteOutcome::Skipped("the file already exists".to_string())
+    );
+    assert!(!target.exists());
+    assert_eq!(service.session_write_usage().bytes_written, 0);
+}
+
 #[tokio::test]
 async fn test_search_files() {
     let (temp_dir, service, _allowed_dirs) = setup_service(vec!["dir1".to_string()]);
@@ -345,6 +750,8 @@ async fn test_apply_file_edits() {
     let edits = vec![EditOperation {
         old_text: "line2".to_string(),
         new_text: "line4".to_string(),
+        new_text_from_file: None,
+        trim_search: None,
     }];
     let result = service
         .apply_file_edits(&file_path, edits, Some(false), None)
@@ -368,6 +775,8 @@ async fn test_apply_file_edits_dry_run() {
     let edits = vec![EditOperation {
         old_text: "line2".to_string(),
         new_text: "line4".to_string(),
+        new_text_from_file: None,
+        trim_search: None,
     }];
     let result = service
         .apply_file_edits(&file_path, edits, Some(true), None)
@@ -391,6 +800,8 @@ async fn test_apply_file_edits_no_match() {
     let edits = vec![EditOperation {
         old_text: "non_existent".to_string(),
         new_text: "line4".to_string(),
+        new_text_from_file: None,
+        trim_search: None,
     }];
     let result = service
         .apply_file_edits(&file_path, edits, Some(false), None)
@@ -488,12 +899,12 @@ fn test_expand_home() {
 #[test]
 fn test_format_bytes() {
     assert_eq!(format_bytes(500), "500 bytes");
-    assert_eq!(format_bytes(1024), "1.00 KB");
-    assert_eq!(format_bytes(1500), "1.46 KB");
-    assert_eq!(format_bytes(1024 * 1024), "1.00 MB");
-    assert_eq!(format_bytes(1024 * 1024 * 1024), "1.00 GB");
-    assert_eq!(format_bytes(1024 * 1024 * 1024 * 1024), "1.00 TB");
-    assert_eq!(format_bytes(1500 * 1024 * 1024), "1.46 GB");
+    assert_eq!(format_bytes(1024), "1.00 KiB");
+    assert_eq!(format_bytes(1500), "1.46 KiB");
+    assert_eq!(format_bytes(1024 * 1024), "1.00 MiB");
+    assert_eq!(format_bytes(1024 * 1024 * 1024), "1.00 GiB");
+    assert_eq!(format_bytes(1024 * 1024 * 1024 * 1024), "1.00 TiB");
+    assert_eq!(format_bytes(1500 * 1024 * 1024), "1.46 GiB");
 }
 
 #[tokio::test]
@@ -584,6 +995,7 @@ fn test_display_format_for_empty_timestamps() {
         accessed: None,
         is_directory: false,
         is_file: true,
+        is_symlink: false,
         metadata: metadata.clone(),
     };
 
@@ -635,6 +1047,8 @@ async fn test_apply_file_edits_mixed_indentation() {
 				},
 			];"#
         .to_string(),
+        new_text_from_file: None,
+        trim_search: None,
     }];
 
     let out_file = temp_dir.join("dir1").join("out_indent.txt");
@@ -683,6 +1097,8 @@ async fn test_apply_file_edits_mixed_indentation_2() {
 				},
 			];"#
         .to_string(),
+        new_text_from_file: None,
+        trim_search: None,
     }];
 
     let out_file = temp_dir.join("dir1").join("out_indent.txt");
@@ -706,6 +1122,8 @@ async fn test_exact_match() {
     let edit = EditOperation {
         old_text: "hello world".to_string(),
         new_text: "hello universe".to_string(),
+        new_text_from_file: None,
+        trim_search: None,
     };
 
     let result = service
@@ -718,6 +1136,96 @@ async fn test_exact_match() {
     assert!(result.contains("-hello world\n+hello universe"));
 }
 
+#[tokio::test]
+async fn test_edit_new_text_from_file() {
+    let (temp_dir, service, _allowed_dirs) = setup_service(vec!["dir1".to_string()]);
+    let dir = temp_dir.join("dir1");
+    let file = create_temp_file(&dir, "target.txt", "a\nPLACEHOLDER\nb\nPLACEHOLDER\n");
+    let payload = create_temp_file(&dir, "payload.txt", "generated 1\ngenerated 2");
+
+    // the uploaded payload is referenced by several edits
+    let edit = EditOperation {
+        old_text: "PLACEHOLDER".to_string(),
+        new_text: String::new(),
+        new_text_from_file: Some(payload.to_string_lossy().to_string()),
+        trim_search: None,
+    };
+    let result = service
+        .apply_file_edits(&file, vec![edit.clone(), edit], Some(false), None)
+        .await
+        .unwrap();
+
+    assert_eq!(
+        fs::read_to_string(&file).unwrap(),
+        "a\ngenerated 1\ngenerated 2\nb\ngenerated 1\ngenerated 2\n"
+    );
+    assert!(result.contains("-PLACEHOLDER\n+generated 1\n+generated 2"));
+}
+
+#[tokio::test]
+async fn test_edit_new_text_from_missing_file() {
+    let (temp_dir, service, _allowed_dirs) = setup_service(vec!["dir1".to_string()]);
+    let dir = temp_dir.join("dir1");
+    let file = create_temp_file(&dir, "target.txt", "hello world\n");
+
+    let edit = EditOperation {
+        old_text: "world".to_string(),
+        new_text: String::new(),
+        new_text_from_file: Some(dir.join("missing.txt").to_string_lossy().to_string()),
+        trim_search: None,
+    };
+    let result = service
+        .apply_file_edits(&file, vec![edit], Some(false), None)
+        .await;
+
+    assert!(
+        matches!(result, Err(ServiceError::FromString(message)) if message.contains("Cannot read newTextFromFile"))
+    );
+    assert_eq!(fs::read_to_string(&file).unwrap(), "hello world\n");
+}
+
+#[tokio::test]
+async fn test_edit_new_text_from_file_outside_allowed_dirs() {
+    let (temp_dir, service, _allowed_dirs) = setup_service(vec!["dir1".to_string()]);
+    let file = create_temp_file(&temp_dir.join("dir1"), "target.txt", "hello world\n");
+    let outside = create_temp_file(&temp_dir, "secret.txt", "leaked");
+
+    let edit = EditOperation {
+        old_text: "world".to_string(),
+        new_text: String::new(),
+        new_text_from_file: Some(outside.to_string_lossy().to_string()),
+        trim_search: None,
+    };
+    let result = service
+        .apply_file_edits(&file, vec![edit], Some(false), None)
+        .await;
+
+    assert!(result.is_err());
+    assert_eq!(fs::read_to_string(&file).unwrap(), "hello world\n");
+}
+
+#[tokio::test]
+async fn test_edit_rejects_new_text_and_file_together() {
+    let (temp_dir, service, _allowed_dirs) = setup_service(vec!["dir1".to_string()]);
+    let dir = temp_dir.join("dir1");
+    let file = create_temp_file(&dir, "target.txt", "hello world\n");
+    let payload = create_temp_file(&dir, "payload.txt", "universe");
+
+    let edit = EditOperation {
+        old_text: "world".to_string(),
+        new_text: "there".to_string(),
+        new_text_from_file: Some(payload.to_string_lossy().to_string()),
+        trim_search: None,
+    };
+    let result = service
+        .apply_file_edits(&file, vec![edit], Some(false), None)
+        .await;
+
+    assert!(
+        matches!(result, Err(ServiceError::FromString(message)) if message.contains("not both"))
+    );
+}
+
 #[tokio::test]
 async fn test_exact_match_edit2() {
     let (temp_dir, service, _allowed_dirs) = setup_service(vec!["dir1".to_string()]);
@@ -730,6 +1238,8 @@ async fn test_exact_match_edit2() {
     let edits = vec![EditOperation {
         old_text: "hello world\n".into(),
         new_text: "hello Rust\n".into(),
+        new_text_from_file: None,
+        trim_search: None,
     }];
 
     let result = service
@@ -753,6 +1263,8 @@ async fn test_line_by_line_match_with_indent() {
     let edits = vec![EditOperation {
         old_text: "let x = 42;\nprintln!(\"{}\");\n".into(),
         new_text: "let x = 43;\nprintln!(\"x = {}\", x)".into(),
+        new_text_from_file: None,
+        trim_search: None,
     }];
 
     let result = service
@@ -778,6 +1290,8 @@ async fn test_dry_run_mode() {
     let edits = vec![EditOperation {
         old_text: "echo hello\n".into(),
         new_text: "echo world\n".into(),
+        new_text_from_file: None,
+        trim_search: None,
     }];
 
     let result = service
@@ -803,6 +1317,8 @@ async fn test_save_to_different_path() {
     let edits = vec![EditOperation {
         old_text: "foo = 1\n".into(),
         new_text: "foo = 2\n".into(),
+        new_text_from_file: None,
+        trim_search: None,
     }];
 
     let result = service
@@ -829,6 +1345,8 @@ async fn test_diff_backtick_formatting() {
     let edits = vec![EditOperation {
         old_text: "```\nhello\n```".into(),
         new_text: "```\nworld\n```".into(),
+        new_text_from_file: None,
+        trim_search: None,
     }];
 
     let result = service
@@ -871,6 +1389,8 @@ async fn test_preserve_windows_line_endings() {
     let edits = vec![EditOperation {
         old_text: "line1\nline2".into(), // normalized format
         new_text: "updated1\nupdated2".into(),
+        new_text_from_file: None,
+        trim_search: None,
     }];
 
     let result = service
@@ -894,6 +1414,8 @@ async fn test_preserve_unix_line_endings() {
     let edits = vec![EditOperation {
         old_text: "line1\nline2".into(),
         new_text: "updated1\nupdated2".into(),
+        new_text_from_file: None,
+        trim_search: None,
     }];
 
     let result = service
@@ -906,6 +1428,38 @@ async fn test_preserve_unix_line_endings() {
     assert_eq!(updated, "updated1\nupdated2\n"); // Still uses \n endings
 }
 
+#[tokio::test]
+async fn test_apply_file_edits_trim_search() {
+    let (temp_dir, service, _allowed_dirs) = setup_service(vec!["dir1".to_string()]);
+    let file = create_temp_file(
+        &temp_dir.join("dir1"),
+        "trim.txt",
+        "call(alpha);\ncall(beta);\n",
+    );
+    let edit = |trim_search| EditOperation {
+        old_text: " alpha ".to_string(),
+        new_text: "omega".to_string(),
+        new_text_from_file: None,
+        trim_search,
+    };
+
+    let result = service
+        .apply_file_edits(&file, vec![edit(None)], Some(false), None)
+        .await;
+    assert!(result.is_err());
+
+    let result = service
+        .apply_file_edits(&file, vec![edit(Some(true))], Some(false), None)
+        .await
+        .unwrap();
+    assert!(
+        result.contains("Note: edit 1: leading and trailing whitespace was trimmed"),
+        "{result}"
+    );
+    let updated = std::fs::read_to_string(&file).unwrap();
+    assert_eq!(updated, "call(omega);\ncall(beta);\n");
+}
+
 #[tokio::test]
 // Issue #19: https://github.com/rust-mcp-stack/rust-mcp-filesystem/issues/19
 async fn test_panic_on_out_of_bounds_edit() {
@@ -915,6 +1469,8 @@ async fn test_panic_on_out_of_bounds_edit() {
     let edit = EditOperation {
         old_text: "line e\n".repeat(41).to_string(),
         new_text: "replaced content".to_string(),
+        new_text_from_file: None,
+        trim_search: None,
     };
 
     // Set up your file content with only 2 lines
@@ -1095,51 +1651,252 @@ async fn search_files_content() {
             temp_dir.as_path().join("dir_search"),
             "*.txt",
             query,
-            true,
+            ContentSearchOptions {
+                is_regex: true,
+                ..Default::default()
+            },
             None,
             None,
             None,
         )
         .await
-        .unwrap();
+        .unwrap()
+        .files;
     assert_eq!(results.len(), 2);
     assert_eq!(results[0].matches.len(), 2);
     assert_eq!(results[1].matches.len(), 2);
 }
 
-#[tokio::test]
-async fn test_head_file_normal() {
-    let (temp_dir, service, _allowed_dirs) = setup_service(vec!["dir1".to_string()]);
-    let file_path = create_test_file_with_line_ending(
-        &temp_dir,
-        "dir1/test.txt",
-        vec!["line1", "line2", "line3", "line4", "line5"],
-        "\n",
-    )
-    .await;
-
-    let result = service.head_file(&file_path, 3).await.unwrap();
-    assert_eq!(result, "line1\nline2\nline3\n");
+fn matched_lines(results: &[FileSearchResult]) -> Vec<(String, u64)> {
+    let mut lines: Vec<(String, u64)> = results
+        .iter()
+        .flat_map(|file| {
+            file.matches.iter().map(|m| {
+                (
+                    file.file_path.file_name().unwrap().to_string_lossy().into(),
+                    m.line_number,
+                )
+            })
+        })
+        .collect();
+    lines.sort();
+    lines.dedup();
+    lines
 }
 
 #[tokio::test]
-async fn test_head_file_empty_file() {
+async fn test_search_files_content_multiple_patterns() {
     let (temp_dir, service, _allowed_dirs) = setup_service(vec!["dir1".to_string()]);
-    let file_path =
-        create_test_file_with_line_ending(&temp_dir, "dir1/empty.txt", vec![], "\n").await;
+    let dir = temp_dir.join("dir1");
+    create_temp_file(
+        &dir,
+        "a.rs",
+        "// TODO: parse
+fn main() {}
+// FIXME: leak
+// TODO(test): skip
+",
+    );
+    create_temp_file(
+        &dir,
+        "b.rs",
+        "// FIXME: typo
+let x = 1;
+",
+    );
+    let query = ContentQuery {
+        patterns: vec!["TODO".to_string(), "FIXME".to_string()],
+        exclude_in_line: vec![],
+    };
 
-    let result = service.head_file(&file_path, 5).await.unwrap();
-    assert_eq!(result, "");
+    let results = service
+        .search_files_content_query(&dir, "*.rs", &query, Default::default(), None, None, None)
+        .await
+        .unwrap();
+
+    assert_eq!(
+        matched_lines(&results.files),
+        vec![
+            ("a.rs".to_string(), 1),
+            ("a.rs".to_string(), 3),
+            ("a.rs".to_string(), 4),
+            ("b.rs".to_string(), 1),
+        ]
+    );
+    assert_eq!(results.pattern_matches, vec![2, 2]);
+    let total: u64 = results.pattern_matches.iter().sum();
+    assert_eq!(total as usize, matched_lines(&results.files).len());
+
+    // the lines matching an exclusion are left out, and not counted
+    let query = ContentQuery {
+        exclude_in_line: vec!["(test)".to_string()],
+        ..query
+    };
+    let results = service
+        .search_files_content_query(&dir, "*.rs", &query, Default::default(), None, None, None)
+        .await
+        .unwrap();
+    assert_eq!(
+        matched_lines(&results.files),
+        vec![
+            ("a.rs".to_string(), 1),
+            ("a.rs".to_string(), 3),
+            ("b.rs".to_string(), 1),
+        ]
+    );
+    assert_eq!(results.pattern_matches, vec![1, 2]);
 }
 
 #[tokio::test]
-async fn test_head_file_n_zero() {
+async fn test_search_files_content_multiple_patterns_match_separate_searches() {
     let (temp_dir, service, _allowed_dirs) = setup_service(vec!["dir1".to_string()]);
-    let file_path = create_test_file_with_line_ending(
-        &temp_dir,
-        "dir1/test.txt",
-        vec!["line1", "line2", "line3"],
-        "\n",
+    let dir = temp_dir.join("dir1");
+    create_temp_file(
+        &dir,
+        "notes.txt",
+        "alpha 1
+beta 22
+Gamma 333
+alpha beta
+delta
+beta-4444
+",
+    );
+    create_temp_file(
+        &dir,
+        "more.txt",
+        "ALPHA
+nothing
+55555 gamma
+",
+    );
+    let patterns = vec![
+        r"alpha\s\w+".to_string(),
+        r"\d{3,}".to_string(),
+        "gamma".to_string(),
+    ];
+    let options = ContentSearchOptions {
+        is_regex: true,
+        ..Default::default()
+    };
+
+    let query = ContentQuery {
+        patterns: patterns.clone(),
+        exclude_in_line: vec![],
+    };
+    let combined = service
+        .search_files_content_query(&dir, "*.txt", &query, options, None, None, None)
+        .await
+        .unwrap();
+
+    let mut separate = vec![];
+    for pattern in &patterns {
+        let results = service
+            .search_files_content(&dir, "*.txt", pattern, options, None, None, None)
+            .await
+            .unwrap();
+        assert_eq!(results.pattern_matches.len(), 1);
+        separate.push(matched_lines(&results.files));
+    }
+
+    let per_pattern: Vec<u64> = separate.iter().map(|lines| lines.len() as u64).collect();
+    assert_eq!(combined.pattern_matches, per_pattern);
+    let mut union: Vec<(String, u64)> = separate.into_iter().flatten().collect();
+    union.sort();
+    union.dedup();
+    assert_eq!(matched_lines(&combined.files), union);
+}
+
+#[tokio::test]
+async fn test_content_search_dedupe_merges_adjacent_matches() {
+    let (temp_dir, service, _allowed_dirs) = setup_service(vec!["dir1".to_string()]);
+    let file = create_temp_file(
+        &temp_dir.join("dir1"),
+        "config.toml",
+        "[server]\nport = 1\nport = 2\nport = 3\nname = \"x\"\n",
+    );
+    let options = ContentSearchOptions {
+        dedupe: true,
+        ..Default::default()
+    };
+
+    let result = service
+        .content_search_with_options("port", &file, options)
+        .unwrap()
+        .unwrap();
+
+    assert_eq!(result.matches.len(), 1);
+    let group = &result.matches[0];
+    assert_eq!(group.line_number, 2);
+    assert_eq!(group.end_line_number, 4);
+    assert_eq!(group.line_text, "port = 1\nport = 2\nport = 3");
+
+    // without dedupe, every line is reported separately
+    let result = service
+        .content_search_with_options("port", &file, ContentSearchOptions::default())
+        .unwrap()
+        .unwrap();
+    assert_eq!(result.matches.len(), 3);
+}
+
+#[tokio::test]
+async fn test_content_search_dedupe_removes_identical_snippets() {
+    let (temp_dir, service, _allowed_dirs) = setup_service(vec!["dir1".to_string()]);
+    let file = create_temp_file(
+        &temp_dir.join("dir1"),
+        "main.rs",
+        "use std::fs;\nfn a() {}\nuse std::fs;\nfn b() {}\nuse std::io;\n",
+    );
+    let options = ContentSearchOptions {
+        dedupe: true,
+        ..Default::default()
+    };
+
+    let result = service
+        .content_search_with_options("use std", &file, options)
+        .unwrap()
+        .unwrap();
+
+    assert_eq!(result.matches.len(), 2);
+    assert_eq!(result.matches[0].line_text, "use std::fs;");
+    assert_eq!(result.matches[0].duplicate_lines, vec![3]);
+    assert_eq!(result.matches[1].line_text, "use std::io;");
+    assert!(result.matches[1].duplicate_lines.is_empty());
+}
+
+#[tokio::test]
+async fn test_head_file_normal() {
+    let (temp_dir, service, _allowed_dirs) = setup_service(vec!["dir1".to_string()]);
+    let file_path = create_test_file_with_line_ending(
+        &temp_dir,
+        "dir1/test.txt",
+        vec!["line1", "line2", "line3", "line4", "line5"],
+        "\n",
+    )
+    .await;
+
+    let result = service.head_file(&file_path, 3).await.unwrap();
+    assert_eq!(result, "line1\nline2\nline3\n");
+}
+
+#[tokio::test]
+async fn test_head_file_empty_file() {
+    let (temp_dir, service, _allowed_dirs) = setup_service(vec!["dir1".to_string()]);
+    let file_path =
+        create_test_file_with_line_ending(&temp_dir, "dir1/empty.txt", vec![], "\n").await;
+
+    let result = service.head_file(&file_path, 5).await.unwrap();
+    assert_eq!(result, "");
+}
+
+#[tokio::test]
+async fn test_head_file_n_zero() {
+    let (temp_dir, service, _allowed_dirs) = setup_service(vec!["dir1".to_string()]);
+    let file_path = create_test_file_with_line_ending(
+        &temp_dir,
+        "dir1/test.txt",
+        vec!["line1", "line2", "line3"],
+        "\n",
     )
     .await;
 
@@ -1316,6 +2073,68 @@ async fn test_tail_file_invalid_path() {
     assert!(result.is_err(), "Expected error for invalid path");
 }
 
+#[tokio::test]
+async fn test_tail_file_trailing_newline() {
+    let (temp_dir, service, _allowed_dirs) = setup_service(vec!["dir1".to_string()]);
+    let file_path = create_temp_file(&temp_dir.join("dir1"), "test.txt", "a\nb\nc\n");
+
+    assert_eq!(service.tail_file(&file_path, 1).await.unwrap(), "c\n");
+    assert_eq!(service.tail_file(&file_path, 2).await.unwrap(), "b\nc\n");
+}
+
+#[tokio::test]
+async fn test_tail_and_follow_returns_only_matching_appended_lines() {
+    let (temp_dir, service, _allowed_dirs) = setup_service(vec!["dir1".to_string()]);
+    let file_path = create_temp_file(
+        &temp_dir.join("dir1"),
+        "app.log",
+        "INFO started\nERROR disk full\n",
+    );
+
+    let writer_path = file_path.clone();
+    let writer = tokio::spawn(async move {
+        tokio::time::sleep(std::time::Duration::from_millis(200)).await;
+        let mut file = std::fs::OpenOptions::new()
+            .append(true)
+            .open(&writer_path)
+            .unwrap();
+        use std::io::Write;
+        file.write_all(b"INFO request served\nERROR timeout\n")
+            .unwrap();
+        file.flush().unwrap();
+        tokio::time::sleep(std::time::Duration::from_millis(200)).await;
+        file.write_all(b"DEBUG cache hit\nERROR connection reset")
+            .unwrap();
+    });
+
+    let result = service
+        .tail_and_follow(
+            &file_path,
+            1,
+            Some("^ERROR"),
+            Some(std::time::Duration::from_secs(1)),
+        )
+        .await
+        .unwrap();
+    writer.await.unwrap();
+
+    // The tail line is "ERROR disk full"; "INFO started" is outside the last line
+    assert_eq!(
+        result,
+        "ERROR disk full\nERROR timeout\nERROR connection reset\n\
+         [followed for 1.0s: 4 lines appended, 2 returned]"
+    );
+    assert!(!result.contains("INFO"));
+    assert!(!result.contains("DEBUG"));
+
+    // Without a window, only the matching tail lines are returned
+    let result = service
+        .tail_and_follow(&file_path, 10, Some("timeout|reset"), None)
+        .await
+        .unwrap();
+    assert_eq!(result, "ERROR timeout\nERROR connection reset");
+}
+
 #[tokio::test]
 async fn test_read_file_lines_normal() {
     let (temp_dir, service, _allowed_dirs) = setup_service(vec!["dir1".to_string()]);
@@ -1777,6 +2596,112 @@ async fn test_find_empty_directories_exclude_patterns() {
     assert_eq!(result, expected);
 }
 
+#[tokio::test]
+async fn test_prune_empty_directories_nested_and_dry_run() {
+    let (temp_dir, service, _allowed_dirs) = setup_service(vec!["dir1".to_string()]);
+    let dir1 = temp_dir.join("dir1");
+    fs::create_dir_all(dir1.join("a/b/c")).unwrap();
+    fs::create_dir_all(dir1.join("a/d")).unwrap();
+    fs::create_dir_all(dir1.join("kept/cache")).unwrap();
+    create_temp_file(&dir1.join("kept"), "file.txt", "content");
+    create_temp_file(&dir1.join("a/d"), ".DS_Store", "metadata");
+
+    let plan = service
+        .prune_empty_directories(&dir1, vec![], false, true)
+        .await
+        .unwrap();
+    // Children come before their parents
+    assert_eq!(
+        plan.directories,
+        vec![
+            dir1.join("a/b/c"),
+            dir1.join("a/b"),
+            dir1.join("a/d"),
+            dir1.join("a"),
+            dir1.join("kept/cache"),
+        ]
+    );
+    assert_eq!(plan.placeholders, vec![dir1.join("a/d/.DS_Store")]);
+    assert!(dir1.join("a/b/c").is_dir());
+    assert!(dir1.join("a/d/.DS_Store").is_file());
+
+    let plan = service
+        .prune_empty_directories(&dir1, vec!["**/cache".to_string()], false, false)
+        .await
+        .unwrap();
+    assert_eq!(plan.directories.len(), 4);
+    assert!(!dir1.join("a").exists());
+    assert!(dir1.join("kept/cache").is_dir());
+    assert!(dir1.is_dir());
+
+    // Pruning a tree that is entirely empty keeps its root
+    fs::remove_dir_all(dir1.join("kept")).unwrap();
+    let plan = service
+        .prune_empty_directories(&dir1, vec![], false, false)
+        .await
+        .unwrap();
+    assert!(plan.directories.is_empty());
+    assert!(dir1.is_dir());
+}
+
+#[tokio::test]
+async fn test_prune_empty_directories_gitkeep() {
+    let (temp_dir, service, _allowed_dirs) = setup_service(vec!["dir1".to_string()]);
+    let dir1 = temp_dir.join("dir1");
+    fs::create_dir_all(dir1.join("logs/archive")).unwrap();
+    create_temp_file(&dir1.join("logs"), ".gitkeep", "");
+
+    let plan = service
+        .prune_empty_directories(&dir1, vec![], false, false)
+        .await
+        .unwrap();
+    assert_eq!(plan.directories, vec![dir1.join("logs/archive")]);
+    assert!(dir1.join("logs/.gitkeep").is_file());
+
+    let plan = service
+        .prune_empty_directories(&dir1, vec![], true, false)
+        .await
+        .unwrap();
+    assert_eq!(plan.directories, vec![dir1.join("logs")]);
+    assert_eq!(plan.placeholders, vec![dir1.join("logs/.gitkeep")]);
+    assert!(!dir1.join("logs").exists());
+}
+
+#[tokio::test]
+async fn test_detect_generated_files() {
+    let (temp_dir, service, _allowed_dirs) = setup_service(vec!["dir1".to_string()]);
+    let dir1 = temp_dir.join("dir1");
+    let minified = "function a(b){return b*2}".repeat(200);
+    create_temp_file(&dir1, "app.min.js", &minified);
+    create_temp_file(
+        &dir1,
+        "app.js",
+        &"function double(value) {\n    return value * 2;\n}\n\n".repeat(50),
+    );
+    create_temp_file(
+        &dir1,
+        "schema.rs",
+        "// @generated by the schema compiler\npub struct Schema;\n",
+    );
+
+    let flagged = service
+        .detect_generated_files(&dir1, None, None)
+        .await
+        .unwrap();
+    let paths: Vec<_> = flagged.iter().map(|file| file.path.clone()).collect();
+    assert_eq!(paths, vec![dir1.join("app.min.js"), dir1.join("schema.rs")]);
+    assert_eq!(flagged[0].longest_line, minified.len());
+    assert!(
+        flagged[0]
+            .reasons
+            .iter()
+            .any(|reason| reason.starts_with("average line length")),
+        "{:?}",
+        flagged[0].reasons
+    );
+    assert_eq!(flagged[1].reasons, vec!["contains an @generated marker"]);
+}
+
 #[tokio::test]
 async fn test_find_empty_directories_exclude_patterns_2() {
     let (temp_dir, service, _allowed_dirs) = setup_service(vec!["dir1".to_string()]);
@@ -1913,5 +2838,4080 @@ async fn test_search_files_brace_expanded_github_issue_50() {
     assert_eq!(names.len(), 5);
 }
 
+// Reader wrapper recording the largest read buffer requested by its consumer
+struct CapacityRecorder {
+    inner: tokio::fs::File,
+    max_requested: Arc<AtomicUsize>,
+}
+
+impl tokio::io::AsyncRead for CapacityRecorder {
+    fn poll_read(
+        mut self: std::pin::Pin<&mut Self>,
+        cx: &mut std::task::Context<'_>,
+        buf: &mut tokio::io::ReadBuf<'_>,
+    ) -> std::task::Poll<std::io::Result<()>> {
+        self.max_requested
+            .fetch_max(buf.remaining(), Ordering::SeqCst);
+        std::pin::Pin::new(&mut self.inner).poll_read(cx, buf)
+    }
+}
+
+#[tokio::test]
+async fn test_stream_reader_uses_configured_buffer_size() {
+    let (temp_dir, service, _allowed_dirs) = setup_service(vec!["dir1".to_string()]);
+    let service = service
+        .with_performance_config(PerformanceConfig {
+            stream_buffer_size: 64 * 1024,
+            ..Default::default()
+        })
+        .unwrap();
+    let file_path = create_temp_file(&temp_dir.join("dir1"), "big.txt", &"a".repeat(200_000));
+
+    let max_requested = Arc::new(AtomicUsize::new(0));
+    let recorder = CapacityRecorder {
+        inner: tokio::fs::File::open(&file_path).await.unwrap(),
+        max_requested: max_requested.clone(),
+    };
+
+    let mut reader = service.stream_reader(recorder);
+    let filled = reader.fill_buf().await.unwrap().len();
+
+    assert_eq!(filled, 64 * 1024);
+    assert_eq!(max_requested.load(Ordering::SeqCst), 64 * 1024);
+}
+
+#[tokio::test]
+async fn test_stream_buffer_size_below_minimum_rejected() {
+    let (_temp_dir, service, _allowed_dirs) = setup_service(vec!["dir1".to_string()]);
+    let result = service.with_performance_config(PerformanceConfig {
+        stream_buffer_size: MIN_STREAM_BUFFER_SIZE - 1,
+        ..Default::default()
+    });
+    assert!(
+        matches!(result, Err(ServiceError::FromString(message)) if message.contains("at least 512 bytes"))
+    );
+}
+
+#[tokio::test]
+async fn test_head_file_with_small_stream_buffer() {
+    let (temp_dir, service, _allowed_dirs) = setup_service(vec!["dir1".to_string()]);
+    let service = service
+        .with_performance_config(PerformanceConfig {
+            stream_buffer_size: MIN_STREAM_BUFFER_SIZE,
+            ..Default::default()
+        })
+        .unwrap();
+    let lines: Vec<String> = (0..200).map(|i| format!("line {i}")).collect();
+    let file_path = create_temp_file(&temp_dir.join("dir1"), "lines.txt", &lines.join("\n"));
+
+    let head = service.head_file(&file_path, 3).await.unwrap();
+    assert_eq!(head, "line 0\nline 1\nline 2\n");
+    // spans several buffer refills
+    let lines = service
+        .read_file_lines(&file_path, 150, Some(2))
+        .await
+        .unwrap();
+    assert_eq!(lines, "line 150\nline 151\n");
+}
+
+#[cfg(unix)]
+#[tokio::test]
+async fn test_write_file_no_follow_rejects_symlink() {
+    let (temp_dir, service, _allowed_dirs) = setup_service(vec!["dir1".to_string()]);
+    let target = create_temp_file(&temp_dir.join("dir1"), "target.txt", "original");
+    let link = temp_dir.join("dir1").join("link.txt");
+    std::os::unix::fs::symlink(&target, &link).unwrap();
+    let service = service
+        .with_performance_config(PerformanceConfig {
+            no_follow_writes: true,
+            ..Default::default()
+        })
+        .unwrap();
+
+    let result = service.write_file(&link, &"replaced".to_string()).await;
+    assert!(result.is_err());
+    assert_eq!(std::fs::read_to_string(&target).unwrap(), "original");
+
+    // regular files are still written
+    service
+        .write_file(&target, &"updated".to_string())
+        .await
+        .unwrap();
+    assert_eq!(std::fs::read_to_string(&target).unwrap(), "updated");
+}
+
+#[cfg(unix)]
+#[tokio::test]
+async fn test_write_file_follows_symlink_by_default() {
+    let (temp_dir, service, _allowed_dirs) = setup_service(vec!["dir1".to_string()]);
+    let target = create_temp_file(&temp_dir.join("dir1"), "target.txt", "original");
+    let link = temp_dir.join("dir1").join("link.txt");
+    std::os::unix::fs::symlink(&target, &link).unwrap();
+
+    service
+        .write_file(&link, &"replaced".to_string())
+        .await
+        .unwrap();
+    assert_eq!(std::fs::read_to_string(&target).unwrap(), "replaced");
+}
+
+#[tokio::test]
+async fn test_read_text_file_with_no_atime_reads() {
+    let (temp_dir, service, _allowed_dirs) = setup_service(vec!["dir1".to_string()]);
+    let file_path = create_temp_file(&temp_dir.join("dir1"), "notes.txt", "line 1\nline 2\n");
+    let service = service
+        .with_performance_config(PerformanceConfig {
+            no_atime_reads: true,
+            ..Default::default()
+        })
+        .unwrap();
+
+    let content = service.read_text_file(&file_path).await.unwrap();
+    assert_eq!(content, "line 1\nline 2\n");
+    let head = service.head_file(&file_path, 1).await.unwrap();
+    assert_eq!(head, "line 1\n");
+}
+
+#[tokio::test]
+async fn test_extract_definition_function_body() {
+    let (temp_dir, service, _allowed_dirs) = setup_service(vec!["dir1".to_string()]);
+    let source = r#"use std::fmt;
+
+/// Adds two numbers.
+#[inline]
+pub fn add(a: i32, b: i32) -> i32 {
+    let brace = '}';
+    let text = "{ not a block";
+    if a > b {
+        return a + b;
+    }
+    a + b
+}
+
+fn other() {}
+"#;
+    let file_path = create_temp_file(&temp_dir.join("dir1"), "lib.rs", source);
+
+    let definitions = service.extract_definition(&file_path, "add").await.unwrap();
+    assert_eq!(definitions.len(), 1);
+    let definition = &definitions[0];
+    assert_eq!(definition.start_line, 3);
+    assert_eq!(definition.end_line, 12);
+    assert!(definition.source.starts_with("/// Adds two numbers."));
+    assert!(definition.source.ends_with("    a + b\n}"));
+}
+
+#[tokio::test]
+async fn test_extract_definition_returns_all_duplicates() {
+    let (temp_dir, service, _allowed_dirs) = setup_service(vec!["dir1".to_string()]);
+    let source = r#"struct Point<T> {
+    x: T,
+}
+
+impl<T> Point<T> {
+    fn x(&self) -> &T {
+        &self.x
+    }
+}
+
+impl<T: fmt::Display> fmt::Display for Point<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.x)
+    }
+}
+"#;
+    let file_path = create_temp_file(&temp_dir.join("dir1"), "point.rs", source);
+
+    let definitions = service
+        .extract_definition(&file_path, "Point")
+        .await
+        .unwrap();
+    let ranges: Vec<(usize, usize)> = definitions
+        .iter()
+        .map(|d| (d.start_line, d.end_line))
+        .collect();
+    assert_eq!(ranges, vec![(1, 3), (5, 9), (11, 15)]);
+
+    let missing = service
+        .extract_definition(&file_path, "Missing")
+        .await
+        .unwrap();
+    assert!(missing.is_empty());
+}
+
+#[tokio::test]
+async fn test_list_definitions_spans() {
+    let (temp_dir, service, _allowed_dirs) = setup_service(vec!["dir1".to_string()]);
+    let source = r#"use std::fmt;
+
+/// A point.
+#[derive(Debug)]
+pub struct Point {
+    x: i32,
+}
+
+impl fmt::Display for Point {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.x)
+    }
+}
+
+pub(crate) const ORIGIN: i32 = 0;
+
+async fn helper(callback: fn(i32) -> i32) -> i32 {
+    callback(ORIGIN)
+}
+"#;
+    let file_path = create_temp_file(&temp_dir.join("dir1"), "lib.rs", source);
+
+    let definitions = service.list_definitions(&file_path).await.unwrap();
+    let summary: Vec<_> = definitions
+        .iter()
+        .map(|d| {
+            (
+                d.name.as_str(),
+                d.kind.as_str(),
+                d.start_line,
+                d.end_line,
+                d.visibility.as_str(),
+            )
+        })
+        .collect();
+    assert_eq!(
+        summary,
+        vec![
+            ("Point", "struct", 3, 7, "pub"),
+            ("fmt::Display for Point", "impl", 9, 13, "private"),
+            ("fmt", "fn", 10, 12, "private"),
+            ("ORIGIN", "const", 15, 15, "pub(crate)"),
+            ("helper", "fn", 17, 19, "private"),
+        ]
+    );
+    // Spans are either disjoint or nested, never partially overlapping
+    for a in &definitions {
+        for b in &definitions {
+            let disjoint = a.end_line < b.start_line || b.end_line < a.start_line;
+            let nested = (a.start_line >= b.start_line && a.end_line <= b.end_line)
+                || (b.start_line >= a.start_line && b.end_line <= a.end_line);
+            assert!(disjoint || nested, "{a:?} overlaps {b:?}");
+        }
+    }
+}
+
+#[tokio::test]
+async fn test_list_definitions_with_imports() {
+    let (temp_dir, service, _allowed_dirs) = setup_service(vec!["dir1".to_string()]);
+    let source = r#"extern crate serde;
+
+use std::fmt;
+use std::collections::{
+    HashMap,
+    HashSet,
+};
+pub use crate::error::ServiceError; // re-exported
+mod tests;
+
+mod inline {
+    use super::fmt;
+}
+
+fn main() {}
+"#;
+    let file_path = create_temp_file(&temp_dir.join("dir1"), "main.rs", source);
+
+    let definitions = service
+        .list_definitions_with_options(&file_path, true)
+        .await
+        .unwrap();
+    let summary: Vec<_> = definitions
+        .iter()
+        .map(|d| {
+            (
+                d.name.as_str(),
+                d.kind.as_str(),
+                d.start_line,
+                d.end_line,
+                d.visibility.as_str(),
+            )
+        })
+        .collect();
+    assert_eq!(
+        summary,
+        vec![
+            ("serde", "import", 1, 1, "private"),
+            ("std::fmt", "import", 3, 3, "private"),
+            (
+                "std::collections::{HashMap, HashSet}",
+                "import",
+                4,
+                7,
+                "private"
+            ),
+            ("crate::error::ServiceError", "import", 8, 8, "pub"),
+            ("tests", "import", 9, 9, "private"),
+            ("inline", "mod", 11, 13, "private"),
+            ("super::fmt", "import", 12, 12, "private"),
+            ("main", "fn", 15, 15, "private"),
+        ]
+    );
+
+    // Imports are only listed on request
+    let definitions = service.list_definitions(&file_path).await.unwrap();
+    assert!(definitions.iter().all(|d| d.kind != "import"));
+    assert!(
+        definitions
+            .iter()
+            .any(|d| d.name == "tests" && d.kind == "mod")
+    );
+}
+
 #[tokio::test]
-async fn adhock() {}
+async fn test_extract_definition_python_indentation() {
+    let (temp_dir, service, _allowed_dirs) = setup_service(vec!["dir1".to_string()]);
+    let source = "@decorator\ndef greet(\n    name,\n):\n    message = f\"hi {name}\"\n\n    return message\n\ndef other():\n    pass\n";
+    let file_path = create_temp_file(&temp_dir.join("dir1"), "greet.py", source);
+
+    let definitions = service
+        .extract_definition(&file_path, "greet")
+        .await
+        .unwrap();
+    assert_eq!(definitions.len(), 1);
+    assert_eq!(definitions[0].start_line, 1);
+    assert_eq!(definitions[0].end_line, 7);
+    assert!(definitions[0].source.ends_with("    return message"));
+}
+
+#[tokio::test]
+async fn test_content_search_normalize_unicode() {
+    let (temp_dir, service, _allowed_dirs) = setup_service(vec!["dir1".to_string()]);
+    // "café" with a decomposed "e" + combining acute accent
+    let file = create_temp_file(
+        &temp_dir.join("dir1"),
+        "menu.txt",
+        "tea\nCafe\u{301} au lait\n",
+    );
+    // composed "é"
+    let query = "caf\u{e9}";
+
+    let result = service.content_search(query, &file, Some(false)).unwrap();
+    assert!(result.is_none());
+
+    let options = ContentSearchOptions {
+        normalize_unicode: true,
+        ..Default::default()
+    };
+    let result = service
+        .content_search_with_options(query, &file, options)
+        .unwrap()
+        .unwrap();
+    assert_eq!(result.matches.len(), 1);
+    assert_eq!(result.matches[0].line_number, 2);
+    assert_eq!(result.matches[0].line_text, "Caf\u{e9} au lait");
+
+    let case_sensitive = ContentSearchOptions {
+        ignore_case: false,
+        ..options
+    };
+    let result = service
+        .content_search_with_options(query, &file, case_sensitive)
+        .unwrap();
+    assert!(result.is_none());
+
+    let uppercase_query = ContentSearchOptions {
+        normalize_unicode: true,
+        ..Default::default()
+    };
+    let result = service
+        .content_search_with_options("CAF\u{c9}", &file, uppercase_query)
+        .unwrap();
+    assert!(result.is_some());
+}
+
+#[tokio::test]
+async fn test_validate_existing_file_and_dir() {
+    let (temp_dir, service, allowed_dirs) = setup_service(vec!["dir1".to_string()]);
+    let dir = temp_dir.join("dir1");
+    let file_path = create_temp_file(&dir, "test.txt", "content");
+
+    let valid_file = service
+        .validate_existing_file(&file_path, allowed_dirs.clone())
+        .unwrap();
+    assert_eq!(valid_file, file_path);
+    assert_eq!(valid_file.metadata().unwrap().len(), 7);
+
+    let valid_dir = service.validate_dir(&dir, allowed_dirs.clone()).unwrap();
+    assert_eq!(valid_dir, dir);
+    assert!(valid_dir.metadata().unwrap().is_dir());
+
+    // plain validation does not stat the path
+    assert!(
+        service
+            .validate_path(&file_path, allowed_dirs.clone())
+            .unwrap()
+            .metadata()
+            .is_none()
+    );
+
+    assert!(matches!(
+        service.validate_existing_file(&dir, allowed_dirs.clone()),
+        Err(ServiceError::NotAFile(_))
+    ));
+    assert!(matches!(
+        service.validate_dir(&file_path, allowed_dirs.clone()),
+        Err(ServiceError::NotADirectory(_))
+    ));
+    assert!(matches!(
+        service.validate_existing_file(&dir.join("missing.txt"), allowed_dirs.clone()),
+        Err(ServiceError::IoError(err)) if err.kind() == std::io::ErrorKind::NotFound
+    ));
+}
+
+#[tokio::test]
+async fn test_validate_variants_match_validate_path_on_denied() {
+    let (temp_dir, service, allowed_dirs) = setup_service(vec!["dir1".to_string()]);
+    let outside_dir = temp_dir.join("dir2");
+    let outside_file = create_temp_file(&outside_dir, "test.txt", "content");
+
+    let expected = service
+        .validate_path(&outside_file, allowed_dirs.clone())
+        .unwrap_err()
+        .to_string();
+    let file_err = service
+        .validate_existing_file(&outside_file, allowed_dirs.clone())
+        .unwrap_err();
+    assert!(matches!(&file_err, ServiceError::FromString(_)));
+    assert_eq!(file_err.to_string(), expected);
+
+    let dir_err = service
+        .validate_dir(&outside_dir, allowed_dirs.clone())
+        .unwrap_err();
+    assert!(matches!(dir_err, ServiceError::FromString(_)));
+}
+
+fn line_edit(
+    operation: LineEditKind,
+    start_line: u64,
+    end_line: u64,
+    comment_style: Option<CommentStyle>,
+) -> LineEditOperation {
+    LineEditOperation {
+        operation,
+        start_line,
+        end_line,
+        comment_style,
+    }
+}
+
+#[tokio::test]
+async fn test_comment_lines_rust_style() {
+    let (temp_dir, service, _allowed_dirs) = setup_service(vec!["dir1".to_string()]);
+    let file = create_temp_file(
+        &temp_dir.join("dir1"),
+        "main.rs",
+        "fn main() {\n    let a = 1;\n    // already\n        nested();\n\n    done();\n}\n",
+    );
+
+    let result = service
+        .apply_file_changes(
+            &file,
+            vec![],
+            vec![line_edit(LineEditKind::CommentLines, 2, 6, None)],
+            Some(false),
+            None,
+        )
+        .await
+        .unwrap();
+    assert!(result.contains("+    // let a = 1;"));
+
+    let content = tokio_fs::read_to_string(&file).await.unwrap();
+    assert_eq!(
+        content,
+        "fn main() {\n    // let a = 1;\n    // already\n    //     nested();\n\n    // done();\n}\n"
+    );
+
+    // commenting again is a no-op
+    let result = service
+        .apply_file_changes(
+            &file,
+            vec![],
+            vec![line_edit(LineEditKind::CommentLines, 2, 6, None)],
+            Some(false),
+            None,
+        )
+        .await
+        .unwrap();
+    assert!(!result.contains("+    //"));
+    assert_eq!(tokio_fs::read_to_string(&file).await.unwrap(), content);
+}
+
+#[tokio::test]
+async fn test_comment_lines_with_non_ascii_whitespace() {
+    let (temp_dir, service, _allowed_dirs) = setup_service(vec!["dir1".to_string()]);
+    // An ideographic space is three bytes wide, deeper than the two-space indentation
+    let file = create_temp_file(
+        &temp_dir.join("dir1"),
+        "notes.py",
+        "  first\n\u{3000}second\n\u{a0}\u{a0}third\n",
+    );
+
+    service
+        .apply_file_changes(
+            &file,
+            vec![],
+            vec![line_edit(LineEditKind::CommentLines, 1, 3, None)],
+            Some(false),
+            None,
+        )
+        .await
+        .unwrap();
+    assert_eq!(
+        tokio_fs::read_to_string(&file).await.unwrap(),
+        "#   first\n# \u{3000}second\n# \u{a0}\u{a0}third\n"
+    );
+}
+
+#[tokio::test]
+async fn test_comment_and_uncomment_python_style() {
+    let (temp_dir, service, _allowed_dirs) = setup_service(vec!["dir1".to_string()]);
+    let original = "def f():\n    x = 1\n    if x:\n        return x\n";
+    let file = create_temp_file(&temp_dir.join("dir1"), "script.py", original);
+
+    service
+        .apply_file_changes(
+            &file,
+            vec![],
+            vec![line_edit(LineEditKind::CommentLines, 2, 4, None)],
+            Some(false),
+            None,
+        )
+        .await
+        .unwrap();
+    assert_eq!(
+        tokio_fs::read_to_string(&file).await.unwrap(),
+        "def f():\n    # x = 1\n    # if x:\n    #     return x\n"
+    );
+
+    service
+        .apply_file_changes(
+            &file,
+            vec![],
+            vec![line_edit(
+                LineEditKind::UncommentLines,
+                2,
+                4,
+                Some(CommentStyle::Hash),
+            )],
+            Some(false),
+            None,
+        )
+        .await
+        .unwrap();
+    assert_eq!(tokio_fs::read_to_string(&file).await.unwrap(), original);
+}
+
+#[tokio::test]
+async fn test_uncomment_mixed_range_skips_with_note() {
+    let (temp_dir, service, _allowed_dirs) = setup_service(vec!["dir1".to_string()]);
+    let file = create_temp_file(&temp_dir.join("dir1"), "lib.rs", "// one\ntwo\n  //three\n");
+
+    let result = service
+        .apply_file_changes(
+            &file,
+            vec![],
+            vec![line_edit(LineEditKind::UncommentLines, 1, 3, None)],
+            Some(false),
+            None,
+        )
+        .await
+        .unwrap();
+    assert!(result.contains("Note: line 2 is not commented with '//'; skipped."));
+    assert_eq!(
+        tokio_fs::read_to_string(&file).await.unwrap(),
+        "one\ntwo\n  three\n"
+    );
+}
+
+#[tokio::test]
+async fn test_comment_lines_auto_detects_toml_and_html() {
+    let (temp_dir, service, _allowed_dirs) = setup_service(vec!["dir1".to_string()]);
+    let toml = create_temp_file(
+        &temp_dir.join("dir1"),
+        "Cargo.toml",
+        "[dependencies]\nserde = \"1\"\n",
+    );
+    service
+        .apply_file_changes(
+            &toml,
+            vec![],
+            vec![line_edit(LineEditKind::CommentLines, 2, 2, None)],
+            Some(false),
+            None,
+        )
+        .await
+        .unwrap();
+    assert_eq!(
+        tokio_fs::read_to_string(&toml).await.unwrap(),
+        "[dependencies]\n# serde = \"1\"\n"
+    );
+
+    let html = create_temp_file(
+        &temp_dir.join("dir1"),
+        "index.html",
+        "<ul>\n  <li>a</li>\n  <li>b</li>\n</ul>\n",
+    );
+    let comment = line_edit(LineEditKind::CommentLines, 2, 3, None);
+    service
+        .apply_file_changes(&html, vec![], vec![comment], Some(false), None)
+        .await
+        .unwrap();
+    assert_eq!(
+        tokio_fs::read_to_string(&html).await.unwrap(),
+        "<ul>\n  <!-- <li>a</li>\n  <li>b</li> -->\n</ul>\n"
+    );
+
+    let uncomment = line_edit(LineEditKind::UncommentLines, 2, 3, None);
+    service
+        .apply_file_changes(&html, vec![], vec![uncomment], Some(false), None)
+        .await
+        .unwrap();
+    assert_eq!(
+        tokio_fs::read_to_string(&html).await.unwrap(),
+        "<ul>\n  <li>a</li>\n  <li>b</li>\n</ul>\n"
+    );
+
+    let unknown = create_temp_file(&temp_dir.join("dir1"), "notes.unknown", "text\n");
+    let result = service
+        .apply_file_changes(
+            &unknown,
+            vec![],
+            vec![line_edit(LineEditKind::CommentLines, 1, 1, None)],
+            Some(false),
+            None,
+        )
+        .await;
+    assert!(result.is_err());
+}
+
+#[cfg(unix)]
+#[tokio::test]
+async fn test_get_file_stats_follow_symlinks_policy() {
+    let (temp_dir, service, _allowed_dirs) = setup_service(vec!["dir1".to_string()]);
+    let target = create_temp_file(&temp_dir.join("dir1"), "target.txt", "content");
+    let link = temp_dir.join("dir1").join("link.txt");
+    std::os::unix::fs::symlink(&target, &link).unwrap();
+
+    let followed = service.get_file_stats_with(&link, true).await.unwrap();
+    assert!(followed.is_file);
+    assert!(!followed.is_symlink);
+    assert_eq!(followed.size, 7);
+
+    let link_info = service.get_file_stats_with(&link, false).await.unwrap();
+    assert!(!link_info.is_file);
+    assert!(link_info.is_symlink);
+    assert_eq!(link_info.size, target.as_os_str().len() as u64);
+    assert!(link_info.to_string().contains("isSymlink: true"));
+
+    // following is the default
+    let default_info = service.get_file_stats(&link).await.unwrap();
+    assert!(default_info.is_file);
+}
+
+#[tokio::test]
+async fn test_shutdown_drains_in_flight_operations() {
+    let (_temp_dir, service, _allowed_dirs) = setup_service(vec!["dir1".to_string()]);
+    let service = Arc::new(service);
+    let completed = Arc::new(AtomicUsize::new(0));
+    let (started_tx, started_rx) = tokio::sync::oneshot::channel();
+
+    let worker = {
+        let service = service.clone();
+        let completed = completed.clone();
+        tokio::spawn(async move {
+            let _operation = service.begin_operation().unwrap();
+            started_tx.send(()).unwrap();
+            tokio::time::sleep(Duration::from_millis(100)).await;
+            completed.fetch_add(1, Ordering::SeqCst);
+        })
+    };
+    started_rx.await.unwrap();
+
+    let result = service.shutdown(Duration::from_secs(5)).await;
+    assert_eq!(completed.load(Ordering::SeqCst), 1);
+    assert_eq!(result.drained_operations, 1);
+    assert_eq!(result.timed_out_operations, 0);
+    assert!(!service.cancellation_token().is_cancelled());
+    worker.await.unwrap();
+
+    assert!(service.is_shutting_down());
+    assert!(*service.shutdown_signal().borrow());
+    let err = service.begin_operation().unwrap_err();
+    assert!(matches!(err, ServiceError::ShuttingDown));
+    assert_eq!(err.to_string(), "Server shutting down");
+}
+
+#[tokio::test]
+async fn test_shutdown_cancels_operations_after_timeout() {
+    let (_temp_dir, service, _allowed_dirs) = setup_service(vec!["dir1".to_string()]);
+    let _operation = service.begin_operation().unwrap();
+
+    let result = service.shutdown(Duration::from_millis(20)).await;
+    assert_eq!(result.drained_operations, 0);
+    assert_eq!(result.timed_out_operations, 1);
+    assert!(service.cancellation_token().is_cancelled());
+}
+
+#[tokio::test]
+async fn test_shutdown_exports_metrics_file() {
+    let (temp_dir, service, _allowed_dirs) = setup_service(vec!["dir1".to_string()]);
+    let file_path = create_temp_file(&temp_dir.join("dir1"), "test.txt", "alpha\n");
+    let metrics_file = temp_dir.join("metrics.json");
+    let service = service.with_metrics_file(Some(metrics_file.clone()));
+    service
+        .read_text_file_deduped(&file_path, false)
+        .await
+        .unwrap();
+    service
+        .read_text_file_deduped(&file_path, false)
+        .await
+        .unwrap();
+
+    service.shutdown(Duration::from_secs(1)).await;
+    let metrics: serde_json::Value =
+        serde_json::from_str(&fs::read_to_string(&metrics_file).unwrap()).unwrap();
+    assert_eq!(metrics["drained_operations"], 0);
+    assert_eq!(metrics["timed_out_operations"], 0);
+    assert_eq!(metrics["counters"]["read_cache_miss"], 1);
+    assert_eq!(metrics["counters"]["read_cache_hit"], 1);
+}
+
+#[tokio::test]
+async fn test_verify_archive() {
+    use async_zip::{Compression, ZipEntryBuilder};
+
+    let (temp_dir, service, _allowed_dirs) = setup_service(vec!["dir1".to_string()]);
+    let dir_path = temp_dir.join("dir1");
+    let first = create_temp_file(&dir_path, "a.txt", &"alpha ".repeat(100));
+    let second = create_temp_file(&dir_path, "b.txt", "beta");
+    let clean = dir_path.join("clean.zip");
+    service
+        .zip_files(
+            vec![
+                first.to_str().unwrap().to_string(),
+                second.to_str().unwrap().to_string(),
+            ],
+            clean.to_str().unwrap().to_string(),
+        )
+        .await
+        .unwrap();
+    for deep in [false, true] {
+        let verification = service.verify_archive(&clean, deep).await.unwrap();
+        assert!(verification.is_valid(), "{verification:?}");
+        assert_eq!(verification.entries, 2);
+        assert_eq!(verification.uncompressed_bytes, 604);
+    }
+
+    // Flip a bit of the stored content: the headers still read, the CRC does not match
+    let content = "0123456789abcdef".repeat(4);
+    let corrupt = dir_path.join("corrupt.zip");
+    let mut zip_writer =
+        ZipFileWriter::new(tokio::fs::File::create(&corrupt).await.unwrap().compat());
+    for name in ["good.txt", "bad.txt"] {
+        let builder = ZipEntryBuilder::new(name.into(), Compression::Stored);
+        zip_writer
+            .write_entry_whole(builder, format!("{name}{content}").as_bytes())
+            .await
+            .unwrap();
+    }
+    zip_writer.close().await.unwrap();
+    let mut bytes = fs::read(&corrupt).unwrap();
+    let needle = format!("bad.txt{content}");
+    let offset = bytes
+        .windows(needle.len())
+        .position(|window| window == needle.as_bytes())
+        .unwrap();
+    bytes[offset + 20] ^= 0x01;
+    fs::write(&corrupt, &bytes).unwrap();
+
+    let shallow = service.verify_archive(&corrupt, false).await.unwrap();
+    assert!(shallow.is_valid(), "{shallow:?}");
+    let deep = service.verify_archive(&corrupt, true).await.unwrap();
+    assert_eq!(deep.entries, 2);
+    assert_eq!(deep.failures.len(), 1);
+    assert_eq!(deep.failures[0].index, 1);
+    assert_eq!(deep.failures[0].name, "bad.txt");
+    assert_eq!(deep.failures[0].reason, "failed CRC check");
+
+    let truncated = dir_path.join("truncated.zip");
+    fs::write(&truncated, &bytes[..bytes.len() / 2]).unwrap();
+    let err = service.verify_archive(&truncated, false).await.unwrap_err();
+    assert!(err.to_string().contains("central directory"), "{err}");
+}
+
+async fn create_symlink_archive(zip_path: &Path) {
+    write_archive(
+        zip_path,
+        &[
+            ("data/file.txt", "in archive", 0o100644),
+            ("data/in_tree", "file.txt", 0o120777),
+            ("data/escaping", "../../outside.txt", 0o120777),
+            ("data/absolute", "/etc/passwd", 0o120777),
+        ],
+    )
+    .await;
+}
+
+/// Writes an archive of `(name, content, mode)` entries, symlinks having the mode `0o120777`.
+async fn write_archive(zip_path: &Path, entries: &[(&str, &str, u16)]) {
+    use async_zip::{AttributeCompatibility, Compression, ZipEntryBuilder};
+
+    let zip_file = tokio::fs::File::create(zip_path).await.unwrap();
+    let mut zip_writer = ZipFileWriter::new(zip_file.compat());
+    for &(name, content, mode) in entries {
+        let builder = ZipEntryBuilder::new(name.into(), Compression::Stored)
+            .attribute_compatibility(AttributeCompatibility::Unix)
+            .unix_permissions(mode);
+        zip_writer
+            .write_entry_whole(builder, content.as_bytes())
+            .await
+            .unwrap();
+    }
+    zip_writer.close().await.unwrap();
+}
+
+#[tokio::test]
+async fn test_unzip_file_skips_symlinks_by_default() {
+    let (temp_dir, service, _allowed_dirs) = setup_service(vec!["dir1".to_string()]);
+    let dir_path = temp_dir.join("dir1");
+    let zip_path = dir_path.join("links.zip");
+    create_symlink_archive(&zip_path).await;
+
+    let extract_dir = dir_path.join("extracted");
+    let result = service
+        .unzip_file(zip_path.to_str().unwrap(), extract_dir.to_str().unwrap())
+        .await
+        .unwrap();
+
+    assert!(result.starts_with("Successfully extracted 1 file"));
+    assert!(result.contains("  - data/in_tree -> file.txt: skipped\n"));
+    assert!(result.contains("  - data/escaping -> ../../outside.txt: skipped\n"));
+    assert!(result.ends_with("  - data/absolute -> /etc/passwd: skipped"));
+    for name in ["in_tree", "escaping", "absolute"] {
+        assert!(fs::symlink_metadata(extract_dir.join("data").join(name)).is_err());
+    }
+}
+
+#[tokio::test]
+async fn test_unzip_file_materializes_symlinks_inside_archive() {
+    let (temp_dir, service, _allowed_dirs) = setup_service(vec!["dir1".to_string()]);
+    let dir_path = temp_dir.join("dir1");
+    let zip_path = dir_path.join("links.zip");
+    create_symlink_archive(&zip_path).await;
+
+    let extract_dir = dir_path.join("extracted");
+    let result = service
+        .unzip_file_with(
+            zip_path.to_str().unwrap(),
+            extract_dir.to_str().unwrap(),
+            SymlinkPolicy::Materialize,
+        )
+        .await
+        .unwrap();
+
+    assert!(result.starts_with("Successfully extracted 2 files"));
+    assert!(result.contains("data/in_tree -> file.txt: materialized"));
+    assert!(
+        result.contains(
+            "data/escaping -> ../../outside.txt: skipped (target is outside the archive)"
+        )
+    );
+    assert!(
+        result.contains("data/absolute -> /etc/passwd: skipped (target is outside the archive)")
+    );
+
+    let materialized = extract_dir.join("data").join("in_tree");
+    assert!(!fs::symlink_metadata(&materialized).unwrap().is_symlink());
+    assert_eq!(fs::read_to_string(materialized).unwrap(), "in archive");
+    assert!(fs::symlink_metadata(extract_dir.join("data").join("escaping")).is_err());
+    assert!(fs::symlink_metadata(extract_dir.join("data").join("absolute")).is_err());
+}
+
+#[cfg(unix)]
+#[tokio::test]
+async fn test_unzip_file_creates_symlinks_inside_extraction_root() {
+    let (temp_dir, service, _allowed_dirs) = setup_service(vec!["dir1".to_string()]);
+    let dir_path = temp_dir.join("dir1");
+    let zip_path = dir_path.join("links.zip");
+    create_symlink_archive(&zip_path).await;
+
+    let extract_dir = dir_path.join("extracted");
+    let result = service
+        .unzip_file_with(
+            zip_path.to_str().unwrap(),
+            extract_dir.to_str().unwrap(),
+            SymlinkPolicy::Create,
+        )
+        .await
+        .unwrap();
+
+    assert!(result.starts_with("Successfully extracted 2 files"));
+    assert!(result.contains("data/in_tree -> file.txt: created"));
+    assert!(result.contains(
+        "data/escaping -> ../../outside.txt: skipped (target escapes the extraction root)"
+    ));
+    assert!(
+        result
+            .contains("data/absolute -> /etc/passwd: skipped (target escapes the extraction root)")
+    );
+
+    let link = extract_dir.join("data").join("in_tree");
+    assert!(fs::symlink_metadata(&link).unwrap().is_symlink());
+    assert_eq!(fs::read_link(&link).unwrap(), PathBuf::from("file.txt"));
+    assert_eq!(fs::read_to_string(link).unwrap(), "in archive");
+    assert!(fs::symlink_metadata(extract_dir.join("data").join("escaping")).is_err());
+    assert!(fs::symlink_metadata(extract_dir.join("data").join("absolute")).is_err());
+}
+
+#[tokio::test]
+async fn test_unzip_file_refuses_link_entries_escaping_the_root() {
+    let (temp_dir, service, _allowed_dirs) = setup_service(vec!["dir1".to_string()]);
+    let dir_path = temp_dir.join("dir1");
+    for (index, name) in ["../evil", "d/../../evil"].into_iter().enumerate() {
+        let zip_path = dir_path.join(format!("escape{index}.zip"));
+        write_archive(
+            &zip_path,
+            &[
+                ("inside.txt", "x", 0o100644),
+                (name, "inside.txt", 0o120777),
+            ],
+        )
+        .await;
+        for policy in [
+            SymlinkPolicy::Skip,
+            SymlinkPolicy::Materialize,
+            SymlinkPolicy::Create,
+        ] {
+            let extract_dir = dir_path.join("out").join(format!("{index}-{policy:?}"));
+            let err = service
+                .unzip_file_with(
+                    zip_path.to_str().unwrap(),
+                    extract_dir.to_str().unwrap(),
+                    policy,
+                )
+                .await
+                .unwrap_err();
+            assert!(
+                err.to_string()
+                    .contains("is absolute or escapes the extraction root"),
+                "{err}"
+            );
+            assert!(!extract_dir.exists());
+            assert!(fs::symlink_metadata(dir_path.join("out").join("evil")).is_err());
+            assert!(fs::symlink_metadata(dir_path.join("evil")).is_err());
+        }
+    }
+}
+
+#[cfg(unix)]
+#[tokio::test]
+async fn test_unzip_file_follows_extracted_symlinks_when_checking_targets() {
+    let (temp_dir, service, _allowed_dirs) = setup_service(vec!["dir1".to_string()]);
+    let dir_path = temp_dir.join("dir1");
+    let unzip = |name: &str, entries: &'static [(&'static str, &'static str, u16)]| {
+        let zip_path = dir_path.join(format!("{name}.zip"));
+        let extract_dir = dir_path.join(name);
+        let service = &service;
+        async move {
+            write_archive(&zip_path, entries).await;
+            let result = service
+                .unzip_file_with(
+                    zip_path.to_str().unwrap(),
+                    extract_dir.to_str().unwrap(),
+                    SymlinkPolicy::Create,
+                )
+                .await;
+            (result, extract_dir)
+        }
+    };
+
+    // 'c/b' points at the root, so 'c/b/..' is above it
+    let (result, extract_dir) = unzip(
+        "through_link",
+        &[("c/b", "..", 0o120777), ("d", "c/b/..", 0o120777)],
+    )
+    .await;
+    let result = result.unwrap();
+    assert!(result.contains("c/b -> ..: created"), "{result}");
+    assert!(
+        result.contains("d -> c/b/..: skipped (target escapes the extraction root)"),
+        "{result}"
+    );
+    assert!(fs::symlink_metadata(extract_dir.join("d")).is_err());
+
+    // Entries below a created link would be written wherever it points
+    let (result, _) = unzip(
+        "below_link",
+        &[("c/b", "..", 0o120777), ("c/b/y", "../..", 0o120777)],
+    )
+    .await;
+    let err = result.unwrap_err();
+    assert!(
+        err.to_string()
+            .contains("'c/b' is a symbolic link extracted from the archive"),
+        "{err}"
+    );
+    let (result, extract_dir) = unzip(
+        "file_below_link",
+        &[("c/b", "..", 0o120777), ("c/b/escaped.txt", "x", 0o100644)],
+    )
+    .await;
+    assert!(result.is_err());
+    assert!(!extract_dir.join("escaped.txt").exists());
+    assert!(!dir_path.join("escaped.txt").exists());
+
+    // A later link cannot redirect the path an earlier link resolved through
+    let (result, extract_dir) = unzip(
+        "redirected",
+        &[("x", "y/..", 0o120777), ("y", ".", 0o120777)],
+    )
+    .await;
+    let result = result.unwrap();
+    assert!(
+        result.contains("y -> .: skipped (an earlier symlink resolves through it)"),
+        "{result}"
+    );
+    assert!(fs::symlink_metadata(extract_dir.join("y")).is_err());
+}
+
+#[tokio::test]
+async fn test_read_directory_mixed_text_and_binary() {
+    let (temp_dir, service, _allowed_dirs) = setup_service(vec!["dir1".to_string()]);
+    let dir = temp_dir.join("dir1").join("config");
+    create_temp_file(&dir, "b.yaml", "kind: Service\n");
+    create_temp_file(&dir, "a.yaml", "kind: Deployment");
+    fs::write(dir.join("logo.png"), [0x89, b'P', b'N', b'G', 0x00, 0xff]).unwrap();
+    create_temp_file(&dir.join("nested"), "c.yaml", "kind: ConfigMap\n");
+
+    let contents = service
+        .read_directory(&dir, false, ReadDirectoryLimits::default())
+        .await
+        .unwrap();
+    assert_eq!(
+        contents.to_string(),
+        "==> a.yaml <==\nkind: Deployment\n\n==> b.yaml <==\nkind: Service\n\nSkipped:\n  - logo.png: binary file"
+    );
+
+    let contents = service
+        .read_directory(&dir, true, ReadDirectoryLimits::default())
+        .await
+        .unwrap();
+    assert!(contents.to_string().contains(&format!(
+        "==> {} <==\nkind: ConfigMap\n",
+        Path::new("nested").join("c.yaml").display()
+    )));
+}
+
+#[tokio::test]
+async fn test_read_directory_refuses_too_many_files() {
+    let (temp_dir, service, _allowed_dirs) = setup_service(vec!["dir1".to_string()]);
+    let dir = temp_dir.join("dir1");
+    for index in 0..6 {
+        create_temp_file(&dir, &format!("file{index}.txt"), "content");
+    }
+
+    let limits = ReadDirectoryLimits {
+        max_files: 3,
+        ..Default::default()
+    };
+    let err = service
+        .read_directory(&dir, false, limits)
+        .await
+        .unwrap_err();
+    assert!(
+        err.to_string()
+            .contains("contains more than 3 files, the limit")
+    );
+}
+
+#[tokio::test]
+async fn test_read_directory_budgets_in_alphabetical_order() {
+    let (temp_dir, service, _allowed_dirs) = setup_service(vec!["dir1".to_string()]);
+    let dir = temp_dir.join("dir1");
+    create_temp_file(&dir, "c.txt", "cccccc");
+    create_temp_file(&dir, "a.txt", "aaaa");
+    create_temp_file(&dir, "b.txt", "bbbbbbbbbb");
+    create_temp_file(&dir, "d.txt", "dd");
+
+    let limits = ReadDirectoryLimits {
+        max_files: 10,
+        max_file_bytes: 8,
+        max_total_bytes: 9,
+    };
+    let contents = service.read_directory(&dir, false, limits).await.unwrap();
+    let names = |included: bool| -> Vec<String> {
+        contents
+            .files
+            .iter()
+            .filter_map(|file| match file {
+                DirectoryFile::Content { path, .. } if included => Some(path.display().to_string()),
+                DirectoryFile::Skipped { path, .. } if !included => {
+                    Some(path.display().to_string())
+                }
+                _ => None,
+            })
+            .collect()
+    };
+
+    // b.txt is over the per-file limit and c.txt exhausts the budget; d.txt would still fit,
+    // but files after the budget ran out are skipped
+    assert_eq!(names(true), vec!["a.txt"]);
+    assert_eq!(names(false), vec!["b.txt", "c.txt", "d.txt"]);
+    let output = contents.to_string();
+    assert!(output.contains("  - b.txt: 10 bytes exceeds the per-file limit of 8 bytes"));
+    assert!(output.contains("  - c.txt: total limit of 9 bytes reached"));
+    assert!(output.contains("  - d.txt: total limit of 9 bytes reached"));
+}
+
+#[tokio::test]
+async fn test_read_text_file_deduped() {
+    let (temp_dir, service, _allowed_dirs) = setup_service(vec!["dir1".to_string()]);
+    let file = create_temp_file(&temp_dir.join("dir1"), "notes.txt", "first version");
+
+    let read = service.read_text_file_deduped(&file, false).await.unwrap();
+    assert_eq!(read, DedupedRead::Content("first version".to_string()));
+
+    let DedupedRead::Unchanged(stub) = service.read_text_file_deduped(&file, false).await.unwrap()
+    else {
+        panic!("Expected an unchanged stub");
+    };
+    assert!(stub.starts_with("unchanged since last read at "));
+    assert!(
+        stub.ends_with(", sha256=80d8f975e768eecac59d22a788bf8e811e51ca85e309ee47f1e821e3e58280f2")
+    );
+
+    // force bypasses the stub
+    let read = service.read_text_file_deduped(&file, true).await.unwrap();
+    assert_eq!(read, DedupedRead::Content("first version".to_string()));
+
+    // writes invalidate the cached entry
+    service
+        .apply_file_edits(
+            &file,
+            vec![EditOperation {
+                old_text: "first".to_string(),
+                new_text: "second".to_string(),
+                new_text_from_file: None,
+                trim_search: None,
+            }],
+            None,
+            None,
+        )
+        .await
+        .unwrap();
+    let read = service.read_text_file_deduped(&file, false).await.unwrap();
+    assert_eq!(read, DedupedRead::Content("second version".to_string()));
+    assert!(matches!(
+        service.read_text_file_deduped(&file, false).await.unwrap(),
+        DedupedRead::Unchanged(_)
+    ));
+}
+
+#[cfg(unix)]
+#[tokio::test]
+async fn test_probe_paths_classifies_batch() {
+    let (temp_dir, service, _allowed_dirs) = setup_service(vec!["dir1".to_string()]);
+    let root = temp_dir.join("dir1");
+    let file = create_temp_file(&root, "file.txt", "12345");
+    let link = root.join("link.txt");
+    std::os::unix::fs::symlink(&file, &link).unwrap();
+    let outside = create_temp_file(&temp_dir, "outside.txt", "secret");
+
+    let paths: Vec<String> = [&file, &root, &link, &root.join("missing.txt"), &outside]
+        .iter()
+        .map(|path| path.to_string_lossy().to_string())
+        .collect();
+    let probes = service.probe_paths(&paths).await;
+
+    let summary: Vec<_> = probes
+        .iter()
+        .map(|probe| (probe.allowed, probe.exists, probe.kind, probe.size))
+        .collect();
+    assert_eq!(
+        summary,
+        vec![
+            (true, Some(true), Some(PathKind::File), Some(5)),
+            (true, Some(true), Some(PathKind::Dir), None),
+            (true, Some(true), Some(PathKind::Symlink), None),
+            (true, Some(false), Some(PathKind::Missing), None),
+            (false, None, None, None),
+        ]
+    );
+    assert_eq!(probes[0].to_string(), format!("{}: file 5 bytes", paths[0]));
+    assert_eq!(
+        probes[4].to_string(),
+        format!("{}: outside allowed directories", paths[4])
+    );
+}
+
+#[test]
+fn test_edit_distance() {
+    assert_eq!(edit_distance("fs_service.rs", "fs_service.rs"), 0);
+    assert_eq!(edit_distance("fs_servce.rs", "fs_service.rs"), 1);
+    assert_eq!(edit_distance("kitten", "sitting"), 3);
+    assert_eq!(edit_distance("", "abc"), 3);
+}
+
+#[tokio::test]
+async fn test_not_found_suggests_similar_names() {
+    let (temp_dir, service, _allowed_dirs) = setup_service(vec!["dir1".to_string()]);
+    let dir = temp_dir.join("dir1");
+    create_temp_file(&dir, "fs_service.rs", "");
+    create_temp_file(&dir, "handler.rs", "");
+    let requested = dir.join("fs_servce.rs");
+
+    let result = service
+        .suggest_paths_on_not_found(&requested, service.read_text_file(&requested))
+        .await;
+
+    let message = result.unwrap_err().to_string();
+    assert!(
+        message.ends_with("\nDid you mean 'fs_service.rs'?"),
+        "{message}"
+    );
+
+    create_temp_file(&dir, "fs_services.rs", "");
+    let message = service
+        .suggest_paths_on_not_found(&requested, service.read_text_file(&requested))
+        .await
+        .unwrap_err()
+        .to_string();
+    assert!(message.ends_with("\nDid you mean one of: 'fs_service.rs', 'fs_services.rs'?"));
+
+    let service = service.with_path_suggestions(false);
+    let result = service
+        .suggest_paths_on_not_found(&requested, service.read_text_file(&requested))
+        .await;
+    assert!(matches!(
+        result,
+        Err(ServiceError::IoError(ref e)) if e.kind() == std::io::ErrorKind::NotFound
+    ));
+}
+
+#[tokio::test]
+async fn test_not_found_skips_suggestions_for_large_parent() {
+    let (temp_dir, service, _allowed_dirs) = setup_service(vec!["dir1".to_string()]);
+    let dir = temp_dir.join("dir1");
+    for i in 0..=MAX_SUGGESTION_SCAN_ENTRIES {
+        File::create(dir.join(format!("file{i}.txt"))).unwrap();
+    }
+    let requested = dir.join("file1.tx");
+
+    let result = service
+        .suggest_paths_on_not_found(&requested, service.read_text_file(&requested))
+        .await;
+
+    assert!(matches!(
+        result,
+        Err(ServiceError::IoError(ref e)) if e.kind() == std::io::ErrorKind::NotFound
+    ));
+}
+
+#[tokio::test]
+async fn test_not_found_with_missing_parent_is_plain_error() {
+    let (temp_dir, service, _allowed_dirs) = setup_service(vec!["dir1".to_string()]);
+    let dir = temp_dir.join("dir1");
+    create_temp_file(&dir, "notes.txt", "");
+    let requested = dir.join("missing").join("notes.txt");
+
+    let result = service
+        .suggest_paths_on_not_found(&requested, service.read_text_file(&requested))
+        .await;
+
+    assert!(matches!(
+        result,
+        Err(ServiceError::IoError(ref e)) if e.kind() == std::io::ErrorKind::NotFound
+    ));
+}
+
+#[tokio::test]
+async fn test_write_rejected_when_extension_not_allowlisted() {
+    let (temp_dir, service, _allowed_dirs) = setup_service(vec!["dir1".to_string()]);
+    let dir = temp_dir.join("dir1");
+    let service = service
+        .with_performance_config(PerformanceConfig {
+            writable_extensions: vec!["md".to_string(), ".TXT".to_string()],
+            ..Default::default()
+        })
+        .unwrap();
+
+    let script = dir.join("install.sh");
+    let result = service.write_file(&script, &"rm -rf /".to_string()).await;
+    assert!(
+        matches!(result, Err(ServiceError::FromString(message)) if message.contains("Writing '.sh' files is not allowed"))
+    );
+    assert!(!script.exists());
+
+    let result = service
+        .write_file(&dir.join("Makefile"), &"all:".to_string())
+        .await;
+    assert!(
+        matches!(result, Err(ServiceError::FromString(message)) if message.contains("files without an extension"))
+    );
+
+    service
+        .write_file(&dir.join("notes.txt"), &"ok".to_string())
+        .await
+        .unwrap();
+    service
+        .write_file(&dir.join("README.md"), &"ok".to_string())
+        .await
+        .unwrap();
+}
+
+#[tokio::test]
+async fn test_blocked_extensions_apply_to_edits_and_moves() {
+    let (temp_dir, service, _allowed_dirs) = setup_service(vec!["dir1".to_string()]);
+    let dir = temp_dir.join("dir1");
+    let script = create_temp_file(&dir, "run.sh", "echo hello\n");
+    let notes = create_temp_file(&dir, "notes.txt", "hello\n");
+    let service = service
+        .with_performance_config(PerformanceConfig {
+            blocked_extensions: vec!["sh".to_string()],
+            ..Default::default()
+        })
+        .unwrap();
+
+    let edit = EditOperation {
+        old_text: "hello".to_string(),
+        new_text: "bye".to_string(),
+        new_text_from_file: None,
+        trim_search: None,
+    };
+    let result = service
+        .apply_file_edits(&script, vec![edit.clone()], Some(false), None)
+        .await;
+    assert!(result.is_err());
+    assert_eq!(fs::read_to_string(&script).unwrap(), "echo hello\n");
+    // previewing is still possible
+    service
+        .apply_file_edits(&script, vec![edit], Some(true), None)
+        .await
+        .unwrap();
+
+    let result = service.move_file(&notes, &dir.join("notes.sh")).await;
+    assert!(result.is_err());
+    assert!(notes.exists());
+}
+
+fn write_png(path: &Path, width: u32, height: u32) {
+    let image = image::RgbImage::from_fn(width, height, |x, y| {
+        image::Rgb([(x % 256) as u8, (y % 256) as u8, 128])
+    });
+    image
+        .save_with_format(path, image::ImageFormat::Png)
+        .unwrap();
+}
+
+#[tokio::test]
+async fn test_read_image_scaled_png_round_trip() {
+    let (temp_dir, service, _allowed_dirs) = setup_service(vec!["dir1".to_string()]);
+    let path = temp_dir.join("dir1").join("small.png");
+    write_png(&path, 64, 32);
+
+    let image = service.read_image_scaled(&path, None, 512).await.unwrap();
+
+    assert_eq!(image.mime_type, "image/png");
+    assert_eq!(image.original_dimensions, (64, 32));
+    assert_eq!(image.dimensions, (64, 32));
+    assert_eq!(image.bytes, fs::read(&path).unwrap());
+}
+
+#[tokio::test]
+async fn test_read_image_scaled_downscales_preserving_aspect_ratio() {
+    let (temp_dir, service, _allowed_dirs) = setup_service(vec!["dir1".to_string()]);
+    let path = temp_dir.join("dir1").join("wide.png");
+    write_png(&path, 1024, 512);
+
+    let image = service.read_image_scaled(&path, None, 512).await.unwrap();
+
+    assert_eq!(image.original_dimensions, (1024, 512));
+    assert_eq!(image.dimensions, (512, 256));
+    let decoded =
+        image::load_from_memory_with_format(&image.bytes, image::ImageFormat::Png).unwrap();
+    assert_eq!((decoded.width(), decoded.height()), (512, 256));
+}
+
+#[tokio::test]
+async fn test_read_image_scaled_rejects_oversized_pixel_count() {
+    let (temp_dir, service, _allowed_dirs) = setup_service(vec!["dir1".to_string()]);
+    let path = temp_dir.join("dir1").join("bomb.png");
+    write_png(&path, 200, 200);
+    let service = service
+        .with_performance_config(PerformanceConfig {
+            max_image_pixels: 10_000,
+            ..Default::default()
+        })
+        .unwrap();
+
+    let result = service.read_image_scaled(&path, None, 64).await;
+
+    assert!(
+        matches!(result, Err(ServiceError::FromString(message)) if message.contains("200x200 exceeds the limit of 10000 pixels"))
+    );
+}
+
+#[tokio::test]
+async fn test_open_file_buffers_edits_until_close() {
+    let (temp_dir, service, _allowed_dirs) = setup_service(vec!["dir1".to_string()]);
+    let file_path = create_temp_file(
+        temp_dir.join("dir1").as_path(),
+        "test.txt",
+        "alpha\nbeta\ngamma\n",
+    );
+
+    let (result, report) = instrumentation::instrument(async {
+        let opened = service.open_file(&file_path).await.unwrap();
+        for (old, new) in [("alpha", "one"), ("beta", "two"), ("gamma", "three")] {
+            let edits = vec![EditOperation {
+                old_text: old.to_string(),
+                new_text: new.to_string(),
+                new_text_from_file: None,
+                trim_search: None,
+            }];
+            let diff = service
+                .apply_file_edits(&file_path, edits, Some(false), None)
+                .await
+                .unwrap();
+            assert!(diff.contains(&format!("Applied to open file '{}'", opened.handle)));
+            assert_eq!(
+                fs::read_to_string(&file_path).unwrap(),
+                "alpha\nbeta\ngamma\n"
+            );
+        }
+        service.close_file(&opened.handle, false).await.unwrap()
+    })
+    .await;
+
+    assert_eq!(result.edits, 3);
+    assert_eq!(result.bytes_written, Some(14));
+    assert_eq!(fs::read_to_string(&file_path).unwrap(), "one\ntwo\nthree\n");
+    // One read when opening and one write when closing.
+    assert_eq!(report.bytes_read, 17);
+    assert_eq!(report.bytes_written, 14);
+}
+
+#[tokio::test]
+async fn test_close_file_discard_and_handle_limit() {
+    let (temp_dir, service, _allowed_dirs) = setup_service(vec!["dir1".to_string()]);
+    let dir = temp_dir.join("dir1");
+    let file_path = create_temp_file(&dir, "test.txt", "alpha\n");
+
+    let opened = service.open_file(&file_path).await.unwrap();
+    let result = service.open_file(&file_path).await;
+    assert!(
+        matches!(result, Err(ServiceError::FromString(message)) if message.contains("already open"))
+    );
+    let edits = vec![EditOperation {
+        old_text: "alpha".to_string(),
+        new_text: "omega".to_string(),
+        new_text_from_file: None,
+        trim_search: None,
+    }];
+    service
+        .apply_file_edits(&file_path, edits, Some(false), None)
+        .await
+        .unwrap();
+    let closed = service.close_file(&opened.handle, true).await.unwrap();
+    assert_eq!((closed.edits, closed.bytes_written), (1, None));
+    assert_eq!(fs::read_to_string(&file_path).unwrap(), "alpha\n");
+    assert!(service.close_file(&opened.handle, false).await.is_err());
+
+    for i in 0..open_files::MAX_OPEN_FILES {
+        let path = create_temp_file(&dir, &format!("file{i}.txt"), "content");
+        service.open_file(&path).await.unwrap();
+    }
+    let result = service.open_file(&file_path).await;
+    assert!(
+        matches!(result, Err(ServiceError::FromString(message)) if message.contains("Too many open files"))
+    );
+}
+
+#[tokio::test]
+async fn test_close_file_refuses_when_file_changed_on_disk() {
+    let (temp_dir, service, _allowed_dirs) = setup_service(vec!["dir1".to_string()]);
+    let file_path = create_temp_file(&temp_dir.join("dir1"), "test.txt", "alpha\n");
+
+    let opened = service.open_file(&file_path).await.unwrap();
+    let edits = vec![EditOperation {
+        old_text: "alpha".to_string(),
+        new_text: "omega".to_string(),
+        new_text_from_file: None,
+        trim_search: None,
+    }];
+    service
+        .apply_file_edits(&file_path, edits, Some(false), None)
+        .await
+        .unwrap();
+    fs::write(&file_path, "beta\n").unwrap();
+
+    let result = service.close_file(&opened.handle, false).await;
+    assert!(
+        matches!(result, Err(ServiceError::FromString(message)) if message.contains("changed on disk"))
+    );
+    assert_eq!(fs::read_to_string(&file_path).unwrap(), "beta\n");
+
+    let closed = service.close_file(&opened.handle, true).await.unwrap();
+    assert_eq!((closed.edits, closed.bytes_written), (1, None));
+    assert_eq!(fs::read_to_string(&file_path).unwrap(), "beta\n");
+}
+
+#[test]
+fn test_natural_sort_order() {
+    let order = ListingOrder::default();
+    let mut names = vec!["file10", "File1", "file2", "b", "a", "file02", "A"];
+    names.sort_by(|a, b| order.compare((a, false), (b, false)));
+    assert_eq!(
+        names,
+        vec!["A", "a", "b", "File1", "file02", "file2", "file10"]
+    );
+
+    let lexicographic = ListingOrder::new(Some(SortOrder::Lexicographic), None);
+    names.sort_by(|a, b| lexicographic.compare((a, false), (b, false)));
+    assert_eq!(
+        names,
+        vec!["A", "File1", "a", "b", "file02", "file10", "file2"]
+    );
+}
+
+#[test]
+fn test_listing_order_dirs_first() {
+    let entries = [
+        ("b.txt", false),
+        ("a_dir", true),
+        ("c_dir", true),
+        ("a.txt", false),
+    ];
+    let mut sorted = entries.to_vec();
+    let order = ListingOrder::new(None, Some(true));
+    sorted.sort_by(|a, b| order.compare(*a, *b));
+    assert_eq!(
+        sorted,
+        vec![
+            ("a_dir", true),
+            ("c_dir", true),
+            ("a.txt", false),
+            ("b.txt", false)
+        ]
+    );
+
+    let mut interleaved = entries.to_vec();
+    let order = ListingOrder::default();
+    interleaved.sort_by(|a, b| order.compare(*a, *b));
+    assert_eq!(
+        interleaved,
+        vec![
+            ("a.txt", false),
+            ("a_dir", true),
+            ("b.txt", false),
+            ("c_dir", true)
+        ]
+    );
+}
+
+#[test]
+fn test_listing_order_parallel_sort_matches_serial() {
+    use rayon::slice::ParallelSliceMut;
+
+    let names: Vec<String> = (0..5000)
+        .map(|i| match i % 4 {
+            0 => format!("file{}", i * 7919 % 1000),
+            1 => format!("File{:03}", i % 500),
+            2 => format!("dir_{}", i % 97),
+            _ => format!("Z{}a{}", i % 13, i % 11),
+        })
+        .collect();
+    let order = ListingOrder::new(None, Some(true));
+    let mut serial: Vec<(String, bool)> = names
+        .iter()
+        .enumerate()
+        .map(|(i, name)| (name.clone(), i % 3 == 0))
+        .collect();
+    let mut parallel = serial.clone();
+    serial.sort_by(|a, b| order.compare((&a.0, a.1), (&b.0, b.1)));
+    parallel.par_sort_by(|a, b| order.compare((&a.0, a.1), (&b.0, b.1)));
+    assert_eq!(serial, parallel);
+}
+
+#[cfg(windows)]
+#[tokio::test]
+async fn test_move_file_retries_while_destination_is_locked() {
+    use std::os::windows::fs::OpenOptionsExt;
+
+    let (temp_dir, service, _allowed_dirs) = setup_service(vec!["dir1".to_string()]);
+    let dir = temp_dir.join("dir1");
+    let src = create_temp_file(&dir, "src.txt", "new");
+    let dest = create_temp_file(&dir, "dest.txt", "old");
+    let service = service
+        .with_performance_config(PerformanceConfig {
+            rename_retries: 8,
+            rename_retry_delay: Duration::from_millis(20),
+            ..Default::default()
+        })
+        .unwrap();
+
+    // Hold the destination open without sharing for a moment, as an editor or scanner would.
+    let lock = std::fs::OpenOptions::new()
+        .read(true)
+        .share_mode(0)
+        .open(&dest)
+        .unwrap();
+    let release = std::thread::spawn(move || {
+        std::thread::sleep(Duration::from_millis(100));
+        drop(lock);
+    });
+
+    service.move_file(&src, &dest).await.unwrap();
+    release.join().unwrap();
+
+    assert_eq!(fs::read_to_string(&dest).unwrap(), "new");
+    assert!(!src.exists());
+}
+
+#[tokio::test]
+async fn test_search_files_content_rejects_long_and_complex_patterns() {
+    let (temp_dir, service, _allowed_dirs) = setup_service(vec!["dir1".to_string()]);
+    let dir = temp_dir.join("dir1");
+    create_temp_file(&dir, "a.txt", "some text");
+    let service = service
+        .with_performance_config(PerformanceConfig {
+            max_pattern_length: 16,
+            regex_size_limit: 10_000,
+            ..Default::default()
+        })
+        .unwrap();
+    let options = ContentSearchOptions {
+        is_regex: true,
+        ..Default::default()
+    };
+
+    let result = service
+        .search_files_content(&dir, "*.txt", &"a".repeat(17), options, None, None, None)
+        .await;
+    assert!(
+        matches!(result, Err(ServiceError::FromString(message)) if message.contains("17 characters exceeds the max_pattern_length limit of 16"))
+    );
+
+    let result = service
+        .search_files_content(&dir, "*.txt", r"\w{1000}", options, None, None, None)
+        .await;
+    assert!(
+        matches!(result, Err(ServiceError::FromString(message)) if message.contains("regex_size_limit of 10000 bytes"))
+    );
+}
+
+#[tokio::test]
+async fn test_search_files_content_timeout_returns_partial_results() {
+    let (temp_dir, service, _allowed_dirs) = setup_service(vec!["dir1".to_string()]);
+    let dir = temp_dir.join("dir1");
+    let filler = "lorem ipsum dolor sit amet, consectetur adipiscing elit\n".repeat(200_000);
+    create_temp_file(&dir, "large.txt", &format!("needle\n{filler}needle\n"));
+    let service = service
+        .with_performance_config(PerformanceConfig {
+            search_timeout: Some(Duration::ZERO),
+            ..Default::default()
+        })
+        .unwrap();
+
+    let results = service
+        .search_files_content(
+            &dir,
+            "*.txt",
+            "needle",
+            ContentSearchOptions::default(),
+            None,
+            None,
+            None,
+        )
+        .await
+        .unwrap();
+
+    assert!(results.timed_out);
+    assert_eq!(results.files.len(), 1);
+    let lines: Vec<u64> = results.files[0]
+        .matches
+        .iter()
+        .map(|m| m.line_number)
+        .collect();
+    assert_eq!(lines, vec![1]);
+}
+
+#[tokio::test]
+async fn test_session_write_budget_stops_before_overshooting_write() {
+    let (temp_dir, service, _allowed_dirs) = setup_service(vec!["dir1".to_string()]);
+    let dir = temp_dir.join("dir1");
+    let service = service
+        .with_performance_config(PerformanceConfig {
+            max_session_write_bytes: 10,
+            ..Default::default()
+        })
+        .unwrap();
+
+    let mut rejected = None;
+    for name in ["a.txt", "b.txt", "c.txt"] {
+        if let Err(err) = service
+            .write_file(&dir.join(name), &"1234".to_string())
+            .await
+        {
+            rejected = Some((name, err));
+            break;
+        }
+    }
+
+    let (name, err) = rejected.expect("the third write exceeds the budget");
+    assert_eq!(name, "c.txt");
+    assert!(matches!(
+        err,
+        ServiceError::WriteBudgetExhausted {
+            counter: BudgetCounter::Written,
+            used: 8,
+            requested: 4,
+            limit: 10,
+        }
+    ));
+    assert!(!dir.join("c.txt").exists());
+
+    let written: u64 = ["a.txt", "b.txt"]
+        .iter()
+        .map(|name| fs::metadata(dir.join(name)).unwrap().len())
+        .sum();
+    let usage = service.session_write_usage();
+    assert_eq!(usage.bytes_written, written);
+    assert_eq!(usage.bytes_removed, 0);
+    assert_eq!(usage.write_limit, Some(10));
+    assert_eq!(usage.delete_limit, None);
+}
+
+#[tokio::test]
+async fn test_session_write_budget_counts_removed_bytes() {
+    let (temp_dir, service, _allowed_dirs) = setup_service(vec!["dir1".to_string()]);
+    let dir = temp_dir.join("dir1");
+    let overwritten = create_temp_file(&dir, "overwritten.txt", "0123456789");
+    let source = create_temp_file(&dir, "source.txt", "new");
+    let replaced = create_temp_file(&dir, "replaced.txt", "12345");
+    let service = service
+        .with_performance_config(PerformanceConfig {
+            max_session_delete_bytes: 12,
+            ..Default::default()
+        })
+        .unwrap();
+
+    service
+        .write_file(&overwritten, &"short".to_string())
+        .await
+        .unwrap();
+    let usage = service.session_write_usage();
+    assert_eq!(usage.bytes_written, 5);
+    assert_eq!(usage.bytes_removed, 10);
+
+    // Replacing the 5-byte file would remove 15 bytes in total
+    let err = service.move_file(&source, &replaced).await.unwrap_err();
+    assert!(matches!(
+        err,
+        ServiceError::WriteBudgetExhausted {
+            counter: BudgetCounter::Removed,
+            ..
+        }
+    ));
+    assert_eq!(fs::read_to_string(&replaced).unwrap(), "12345");
+    assert!(source.exists());
+    assert_eq!(service.session_write_usage().bytes_removed, 10);
+}
+
+#[tokio::test]
+async fn test_session_write_budget_counts_written_bytes_only() {
+    let (temp_dir, service, _allowed_dirs) = setup_service(vec!["dir1".to_string()]);
+    let dir = temp_dir.join("dir1");
+    let zip_path = dir.join("data.zip");
+    write_archive(
+        &zip_path,
+        &[("a.txt", "12345", 0o644), ("sub/b.txt", "678", 0o644)],
+    )
+    .await;
+    let service = service
+        .with_performance_config(PerformanceConfig {
+            max_session_write_bytes: 100,
+            ..Default::default()
+        })
+        .unwrap();
+
+    let extract_dir = dir.join("extracted");
+    service
+        .unzip_file(zip_path.to_str().unwrap(), extract_dir.to_str().unwrap())
+        .await
+        .unwrap();
+    assert_eq!(service.session_write_usage().bytes_written, 8);
+
+    // A directory cannot be opened for writing, so nothing is written or counted
+    let err = service
+        .write_file(&extract_dir.join("sub"), &"1234".to_string())
+        .await;
+    assert!(err.is_err());
+    assert_eq!(service.session_write_usage().bytes_written, 8);
+}
+
+#[tokio::test]
+async fn test_preview_tabular_quoted_multiline_field() {
+    let (temp_dir, service, _allowed_dirs) = setup_service(vec!["dir1".to_string()]);
+    let file = create_temp_file(
+        &temp_dir.join("dir1"),
+        "notes.csv",
+        "id,note,score\n1,\"first line\nsecond, line\",3.5\n2,plain,4\n",
+    );
+
+    let preview = service
+        .preview_tabular_file(&file, TabularOptions::default())
+        .await
+        .unwrap();
+
+    assert_eq!(preview.delimiter, "','");
+    assert_eq!(preview.row_count, 2);
+    assert!(!preview.row_count_approximate);
+    assert_eq!(
+        preview.head,
+        vec![
+            vec!["1", "first line\nsecond, line", "3.5"],
+            vec!["2", "plain", "4"],
+        ]
+    );
+    let types: Vec<_> = preview
+        .columns
+        .iter()
+        .map(|column| (column.name.as_str(), column.column_type))
+        .collect();
+    assert_eq!(
+        types,
+        vec![
+            ("id", ColumnType::Integer),
+            ("note", ColumnType::String),
+            ("score", ColumnType::Float),
+        ]
+    );
+    assert_eq!(preview.tail, Some(vec![]));
+    assert!(preview.render_text().contains("first line\\nsecond, line"));
+}
+
+#[tokio::test]
+async fn test_preview_tabular_detects_tsv_and_projects_columns() {
+    let (temp_dir, service, _allowed_dirs) = setup_service(vec!["dir1".to_string()]);
+    let rows: String = (1..=20)
+        .map(|row| format!("{row}\tname, {row}\t{}\n", row % 2 == 0))
+        .collect();
+    let file = create_temp_file(
+        &temp_dir.join("dir1"),
+        "data.txt",
+        &format!("id\tname\tactive\n{rows}"),
+    );
+
+    let preview = service
+        .preview_tabular_file(
+            &file,
+            TabularOptions {
+                columns: vec!["active".to_string(), "1".to_string()],
+                head_rows: 2,
+                tail_rows: 2,
+                ..Default::default()
+            },
+        )
+        .await
+        .unwrap();
+
+    assert_eq!(preview.delimiter, "tab");
+    assert_eq!(preview.row_count, 20);
+    let names: Vec<_> = preview.columns.iter().map(|c| c.name.as_str()).collect();
+    assert_eq!(names, vec!["active", "id"]);
+    assert_eq!(preview.columns[0].column_type, ColumnType::Boolean);
+    assert_eq!(preview.head, vec![vec!["false", "1"], vec!["true", "2"]]);
+    assert_eq!(
+        preview.tail.unwrap(),
+        vec![vec!["false", "19"], vec!["true", "20"]]
+    );
+
+    let err = service
+        .preview_tabular_file(
+            &file,
+            TabularOptions {
+                columns: vec!["missing".to_string()],
+                ..Default::default()
+            },
+        )
+        .await
+        .unwrap_err();
+    assert!(err.to_string().contains("Unknown column 'missing'"));
+}
+
+#[tokio::test]
+async fn test_preview_tabular_clamps_row_counts() {
+    let (temp_dir, service, _allowed_dirs) = setup_service(vec!["dir1".to_string()]);
+    let rows: String = (0..MAX_PREVIEW_ROWS + 10)
+        .map(|row| format!("{row}\n"))
+        .collect();
+    let file = create_temp_file(&temp_dir.join("dir1"), "rows.csv", &rows);
+
+    let preview = service
+        .preview_tabular_file(
+            &file,
+            TabularOptions {
+                has_header: false,
+                head_rows: usize::MAX,
+                tail_rows: usize::MAX,
+                ..Default::default()
+            },
+        )
+        .await
+        .unwrap();
+
+    assert_eq!(preview.row_count, MAX_PREVIEW_ROWS as u64 + 10);
+    assert_eq!(preview.head.len(), MAX_PREVIEW_ROWS);
+    assert_eq!(preview.tail.unwrap().len(), 10);
+}
+
+#[tokio::test]
+async fn test_preview_tabular_estimates_row_count_past_scan_budget() {
+    let (temp_dir, service, _allowed_dirs) = setup_service(vec!["dir1".to_string()]);
+    let rows: String = (0..10_000).map(|row| format!("{row:05},value\n")).collect();
+    let file = create_temp_file(&temp_dir.join("dir1"), "big.csv", &rows);
+
+    let preview = service
+        .preview_tabular_file(
+            &file,
+            TabularOptions {
+                has_header: false,
+                scan_budget: 4096,
+                ..Default::default()
+            },
+        )
+        .await
+        .unwrap();
+
+    assert!(preview.row_count_approximate);
+    assert!(
+        (9_000..=11_000).contains(&preview.row_count),
+        "{}",
+        preview.row_count
+    );
+    assert_eq!(preview.columns[0].name, "column_1");
+    assert_eq!(preview.head[0], vec!["00000", "value"]);
+    assert_eq!(preview.tail, None);
+    assert!(preview.render_text().contains("(approximate)"));
+}
+
+#[test]
+fn test_strip_comments_hash_languages() {
+    let python = "#!/usr/bin/env python\n# module comment\ndoc = \"\"\"# not a comment\"\"\"\nx = '#'  # trailing\n";
+    assert_eq!(
+        strip_comments_for_path(Path::new("script.py"), python).unwrap(),
+        "#!/usr/bin/env python\ndoc = \"\"\"# not a comment\"\"\"\nx = '#'\n"
+    );
+
+    let shell = "echo ${#items[@]} # count\r\n";
+    assert_eq!(
+        strip_comments_for_path(Path::new("run.sh"), shell).unwrap(),
+        "echo ${#items[@]}\r\n"
+    );
+}
+
+fn structured_edit(
+    op: StructuredEditKind,
+    pointer: &str,
+    value: Option<Value>,
+) -> StructuredEditOperation {
+    StructuredEditOperation {
+        op,
+        pointer: pointer.to_string(),
+        value: value.map(JsonValue),
+    }
+}
+
+#[tokio::test]
+async fn test_structured_edit_sets_nested_json_key() {
+    let (temp_dir, service, _allowed_dirs) = setup_service(vec!["dir1".to_string()]);
+    let file = create_temp_file(
+        &temp_dir.join("dir1"),
+        "package.json",
+        "{\n    \"name\": \"app\",\n    \"dependencies\": {\n        \"zod\": \"^3.0.0\"\n    },\n    \"private\": true\n}\n",
+    );
+
+    let diff = service
+        .apply_structured_edits(
+            &file,
+            None,
+            &[
+                structured_edit(
+                    StructuredEditKind::Set,
+                    "/dependencies/zod",
+                    Some(json!("^3.23.0")),
+                ),
+                structured_edit(
+                    StructuredEditKind::Set,
+                    "dev.tools.lint",
+                    Some(json!({ "strict": true })),
+                ),
+                structured_edit(StructuredEditKind::Remove, "/private", None),
+            ],
+            false,
+        )
+        .await
+        .unwrap();
+
+    assert!(diff.contains("-        \"zod\": \"^3.0.0\""));
+    assert_eq!(
+        fs::read_to_string(&file).unwrap(),
+        "{\n    \"name\": \"app\",\n    \"dependencies\": {\n        \"zod\": \"^3.23.0\"\n    },\n    \"dev\": {\n        \"tools\": {\n            \"lint\": {\n                \"strict\": true\n            }\n        }\n    }\n}\n"
+    );
+}
+
+#[tokio::test]
+async fn test_structured_edit_keeps_minified_json_compact() {
+    let (temp_dir, service, _allowed_dirs) = setup_service(vec!["dir1".to_string()]);
+    let file = create_temp_file(
+        &temp_dir.join("dir1"),
+        "config.json",
+        r#"{"name":"app","tags":["a","b"]}"#,
+    );
+
+    service
+        .apply_structured_edits(
+            &file,
+            None,
+            &[structured_edit(
+                StructuredEditKind::Append,
+                "/tags",
+                Some(json!("c")),
+            )],
+            false,
+        )
+        .await
+        .unwrap();
+    assert_eq!(
+        fs::read_to_string(&file).unwrap(),
+        r#"{"name":"app","tags":["a","b","c"]}"#
+    );
+}
+
+#[tokio::test]
+async fn test_structured_edit_of_open_file_goes_to_its_buffer() {
+    let (temp_dir, service, _allowed_dirs) = setup_service(vec!["dir1".to_string()]);
+    let original = "{\n  \"name\": \"app\",\n  \"version\": \"1.0.0\"\n}\n";
+    let file = create_temp_file(&temp_dir.join("dir1"), "package.json", original);
+    let opened = service.open_file(&file).await.unwrap();
+
+    let diff = service
+        .apply_structured_edits(
+            &file,
+            None,
+            &[structured_edit(
+                StructuredEditKind::Set,
+                "/version",
+                Some(json!("1.1.0")),
+            )],
+            false,
+        )
+        .await
+        .unwrap();
+    assert!(diff.contains(&format!("Applied to open file '{}'", opened.handle)));
+    assert_eq!(fs::read_to_string(&file).unwrap(), original);
+
+    service.close_file(&opened.handle, false).await.unwrap();
+    assert_eq!(
+        fs::read_to_string(&file).unwrap(),
+        "{\n  \"name\": \"app\",\n  \"version\": \"1.1.0\"\n}\n"
+    );
+}
+
+#[tokio::test]
+async fn test_structured_edit_appends_to_yaml_array() {
+    let (temp_dir, service, _allowed_dirs) = setup_service(vec!["dir1".to_string()]);
+    let file = create_temp_file(
+        &temp_dir.join("dir1"),
+        "ci.yml",
+        "name: ci\non:\n  push:\n    branches:\n    - main\n",
+    );
+
+    service
+        .apply_structured_edits(
+            &file,
+            None,
+            &[structured_edit(
+                StructuredEditKind::Append,
+                "on.push.branches",
+                Some(json!("release")),
+            )],
+            false,
+        )
+        .await
+        .unwrap();
+
+    assert_eq!(
+        fs::read_to_string(&file).unwrap(),
+        "name: ci\non:\n  push:\n    branches:\n    - main\n    - release\n"
+    );
+}
+
+#[tokio::test]
+async fn test_structured_edit_preserves_toml_comments() {
+    let (temp_dir, service, _allowed_dirs) = setup_service(vec!["dir1".to_string()]);
+    let manifest = "# The crate manifest\n[package]\nname = \"demo\"\n# Bumped on release\nversion = \"0.1.0\" # keep in sync with the changelog\nedition = \"2024\"\n\n[dependencies]\nserde = { version = \"1.0\", features = [\"derive\"] } # serialization\n";
+    let file = create_temp_file(&temp_dir.join("dir1"), "Cargo.toml", manifest);
+
+    service
+        .apply_structured_edits(
+            &file,
+            None,
+            &[
+                structured_edit(
+                    StructuredEditKind::Set,
+                    "package.version",
+                    Some(json!("0.2.0")),
+                ),
+                structured_edit(
+                    StructuredEditKind::Set,
+                    "/dependencies/serde/version",
+                    Some(json!("1.0.200")),
+                ),
+                structured_edit(
+                    StructuredEditKind::Append,
+                    "dependencies.serde.features",
+                    Some(json!("rc")),
+                ),
+            ],
+            false,
+        )
+        .await
+        .unwrap();
+
+    assert_eq!(
+        fs::read_to_string(&file).unwrap(),
+        manifest
+            .replace("\"0.1.0\"", "\"0.2.0\"")
+            .replace("\"1.0\"", "\"1.0.200\"")
+            .replace("[\"derive\"]", "[\"derive\", \"rc\"]")
+    );
+}
+
+#[tokio::test]
+async fn test_structured_edit_rejects_invalid_pointer_and_documents() {
+    let (temp_dir, service, _allowed_dirs) = setup_service(vec!["dir1".to_string()]);
+    let dir = temp_dir.join("dir1");
+    let file = create_temp_file(&dir, "config.json", "{\"items\": [1, 2]}");
+
+    let err = service
+        .apply_structured_edits(
+            &file,
+            None,
+            &[structured_edit(
+                StructuredEditKind::Set,
+                "/items/5",
+                Some(json!(3)),
+            )],
+            false,
+        )
+        .await
+        .unwrap_err();
+    assert_eq!(
+        err.to_string(),
+        "Invalid pointer '/items/5': index 5 is out of bounds for an array of length 2."
+    );
+
+    let err = service
+        .apply_structured_edits(
+            &file,
+            None,
+            &[structured_edit(
+                StructuredEditKind::Remove,
+                "/missing/key",
+                None,
+            )],
+            false,
+        )
+        .await
+        .unwrap_err();
+    assert_eq!(
+        err.to_string(),
+        "Invalid pointer '/missing/key': 'missing' does not exist."
+    );
+    assert_eq!(fs::read_to_string(&file).unwrap(), "{\"items\": [1, 2]}");
+
+    let broken = create_temp_file(&dir, "broken.toml", "[package]\nname = \n");
+    let err = service
+        .apply_structured_edits(
+            &broken,
+            None,
+            &[structured_edit(
+                StructuredEditKind::Set,
+                "package.name",
+                Some(json!("x")),
+            )],
+            false,
+        )
+        .await
+        .unwrap_err();
+    assert!(err.to_string().contains("line 2"), "{err}");
+}
+
+#[test]
+fn test_byte_format_units_and_precision() {
+    assert_eq!(format_bytes(1_048_576), "1.00 MiB");
+    assert_eq!(
+        ByteFormat::new(Some(SizeUnits::Compact), None).format(1_048_576),
+        "1.00 MB"
+    );
+    assert_eq!(
+        ByteFormat::new(Some(SizeUnits::Binary), Some(1)).format(1_048_576),
+        "1.0 MiB"
+    );
+    assert_eq!(
+        ByteFormat::new(Some(SizeUnits::Decimal), Some(1)).format(1_048_576),
+        "1.0 MB"
+    );
+    assert_eq!(
+        ByteFormat::new(Some(SizeUnits::Decimal), Some(0)).format(1_500),
+        "2 KB"
+    );
+    assert_eq!(
+        ByteFormat::new(Some(SizeUnits::Binary), None).format(999),
+        "999 bytes"
+    );
+    assert_eq!(
+        ByteFormat::new(None, Some(99)).precision,
+        MAX_SIZE_PRECISION
+    );
+}
+
+#[test]
+fn test_byte_format_thousands_separators() {
+    let binary = ByteFormat {
+        thousands_separators: true,
+        ..ByteFormat::default()
+    };
+    let decimal = ByteFormat {
+        units: SizeUnits::Decimal,
+        ..binary
+    };
+    assert_eq!(binary.format(999), "999 bytes");
+    assert_eq!(binary.format(1_000), "1,000 bytes");
+    assert_eq!(decimal.format(1_000), "1.00 KB");
+    assert_eq!(binary.format(12_345_678), "11.77 MiB");
+    assert_eq!(decimal.format(12_345_678), "12.35 MB");
+    assert_eq!(binary.format(5_000 * (1 << 40)), "5,000.00 TiB");
+    assert_eq!(decimal.format(5_000_000_000_000_000), "5,000.00 TB");
+    assert_eq!(binary.format_count(1_234_567), "1,234,567");
+    assert_eq!(ByteFormat::default().format_count(1_234_567), "1234567");
+    assert_eq!(
+        decimal.with_overrides(Some(SizeUnits::Binary), Some(0)),
+        ByteFormat {
+            precision: 0,
+            ..binary
+        }
+    );
+}
+
+/// Human-readable sizes are only produced by `ByteFormat`: no other code divides by 1024 or
+/// prints size labels.
+#[test]
+fn test_sizes_only_formatted_by_byte_format() {
+    fn sources(dir: &Path, files: &mut Vec<PathBuf>) {
+        for entry in fs::read_dir(dir).unwrap() {
+            let path = entry.unwrap().path();
+            if path.is_dir() {
+                sources(&path, files);
+            } else if path.extension().is_some_and(|extension| extension == "rs") {
+                files.push(path);
+            }
+        }
+    }
+    let src = Path::new(env!("CARGO_MANIFEST_DIR")).join("src");
+    let mut files = vec![];
+    sources(&src, &mut files);
+    let formatter = src.join("fs_service").join("utils.rs");
+    assert!(
+        fs::read_to_string(&formatter)
+            .unwrap()
+            .contains("impl ByteFormat")
+    );
+    for file in files.iter().filter(|file| **file != formatter) {
+        let content = fs::read_to_string(file).unwrap();
+        for needle in ["/ 1024", "/ 1000", "1024.0", "\"KB\"", "\"KiB\"", "\"MB\""] {
+            assert!(
+                !content.contains(needle),
+                "{} formats sizes itself ({needle})",
+                file.display()
+            );
+        }
+    }
+}
+
+#[cfg(target_os = "linux")]
+#[tokio::test]
+async fn test_check_file_lock_detects_open_handle() {
+    let (temp_dir, service, _allowed_dirs) = setup_service(vec!["dir1".to_string()]);
+    let file = create_temp_file(&temp_dir.join("dir1"), "busy.log", "line\n");
+
+    let report = service.check_file_lock(&file).await.unwrap();
+    assert!(!report.appears_in_use());
+    assert_eq!(report.locked, Some(false));
+
+    let handle = File::open(&file).unwrap();
+    let report = service.check_file_lock(&file).await.unwrap();
+    assert!(report.appears_in_use());
+    assert!(
+        report
+            .holders
+            .iter()
+            .any(|holder| holder.pid == std::process::id())
+    );
+    assert!(report.render_text(&file).contains(", this server"));
+    drop(handle);
+}
+
+#[cfg(unix)]
+#[tokio::test]
+async fn test_check_file_lock_detects_advisory_lock() {
+    use std::os::fd::AsRawFd;
+
+    let (temp_dir, service, _allowed_dirs) = setup_service(vec!["dir1".to_string()]);
+    let file = create_temp_file(&temp_dir.join("dir1"), "db.lock", "");
+
+    let handle = File::open(&file).unwrap();
+    assert_eq!(
+        unsafe { libc::flock(handle.as_raw_fd(), libc::LOCK_EX | libc::LOCK_NB) },
+        0
+    );
+    let report = service.check_file_lock(&file).await.unwrap();
+    assert_eq!(report.locked, Some(true));
+
+    drop(handle);
+    let report = service.check_file_lock(&file).await.unwrap();
+    assert_eq!(report.locked, Some(false));
+}
+
+#[tokio::test]
+async fn test_list_directory_entries_matches_serial_metadata() {
+    let (temp_dir, service, _allowed_dirs) = setup_service(vec!["dir1".to_string()]);
+    let dir = temp_dir.join("dir1");
+    for index in 0..300 {
+        create_temp_file(&dir, &format!("file{index}.txt"), &"x".repeat(index * 7));
+    }
+    for index in 0..20 {
+        fs::create_dir(dir.join(format!("sub{index}"))).unwrap();
+    }
+    #[cfg(unix)]
+    {
+        std::os::unix::fs::symlink(dir.join("sub0"), dir.join("link_to_dir")).unwrap();
+        std::os::unix::fs::symlink(dir.join("file9.txt"), dir.join("link_to_file")).unwrap();
+        std::os::unix::fs::symlink(dir.join("missing"), dir.join("broken_link")).unwrap();
+    }
+
+    let mut expected: Vec<ListedEntry> = fs::read_dir(&dir)
+        .unwrap()
+        .map(|entry| {
+            let entry = entry.unwrap();
+            let metadata = fs::metadata(entry.path()).ok();
+            let file_metadata = metadata.as_ref().filter(|metadata| metadata.is_file());
+            ListedEntry {
+                name: entry.file_name().to_string_lossy().into_owned(),
+                path: entry.path(),
+                is_dir: metadata.as_ref().is_some_and(|metadata| metadata.is_dir()),
+                is_file: metadata.as_ref().is_some_and(|metadata| metadata.is_file()),
+                size: file_metadata.map(|metadata| metadata.len()),
+                allocated: file_metadata.map(allocated_size),
+                modified: file_metadata.and_then(|metadata| metadata.modified().ok()),
+            }
+        })
+        .collect();
+    expected.sort_by(|a, b| a.name.cmp(&b.name));
+
+    let mut entries = service.list_directory_entries(&dir, true).await.unwrap();
+    entries.sort_by(|a, b| a.name.cmp(&b.name));
+    assert_eq!(entries, expected);
+
+    // Without sizes, the types are the same and no size is reported
+    let mut entries = service.list_directory_entries(&dir, false).await.unwrap();
+    entries.sort_by(|a, b| a.name.cmp(&b.name));
+    for entry in &mut expected {
+        entry.size = None;
+        entry.allocated = None;
+        entry.modified = None;
+    }
+    assert_eq!(entries, expected);
+}
+
+#[tokio::test]
+async fn test_hash_directory_identical_trees_hash_equal() {
+    let (temp_dir, service, _allowed_dirs) =
+        setup_service(vec!["dir1".to_string(), "dir2".to_string()]);
+    for root in ["dir1", "dir2"] {
+        let root = temp_dir.join(root);
+        create_temp_file(&root, "README.md", "# Project\n");
+        create_temp_file(&root.join("src"), "main.rs", "fn main() {}\n");
+        create_temp_file(&root.join("src/util"), "mod.rs", "");
+        fs::create_dir_all(root.join("empty")).unwrap();
+    }
+
+    let first = service
+        .hash_directory(&temp_dir.join("dir1"))
+        .await
+        .unwrap();
+    let second = service
+        .hash_directory(&temp_dir.join("dir2"))
+        .await
+        .unwrap();
+    assert_eq!(first, second);
+    assert_eq!(first.files, 3);
+    assert_eq!(first.directories, 3);
+    assert_eq!(first.bytes, 23);
+    assert_eq!(first.digest.len(), 64);
+
+    // A single changed byte changes the digest
+    create_temp_file(&temp_dir.join("dir2/src"), "main.rs", "fn main() {}\r");
+    let changed = service
+        .hash_directory(&temp_dir.join("dir2"))
+        .await
+        .unwrap();
+    assert_ne!(changed.digest, first.digest);
+    assert_eq!(changed.bytes, first.bytes);
+
+    // So does moving a file, or adding an empty directory
+    create_temp_file(&temp_dir.join("dir2/src"), "main.rs", "fn main() {}\n");
+    assert_eq!(
+        service
+            .hash_directory(&temp_dir.join("dir2"))
+            .await
+            .unwrap(),
+        first
+    );
+    fs::rename(
+        temp_dir.join("dir2/README.md"),
+        temp_dir.join("dir2/src/README.md"),
+    )
+    .unwrap();
+    let moved = service
+        .hash_directory(&temp_dir.join("dir2"))
+        .await
+        .unwrap();
+    assert_ne!(moved.digest, first.digest);
+    fs::rename(
+        temp_dir.join("dir2/src/README.md"),
+        temp_dir.join("dir2/README.md"),
+    )
+    .unwrap();
+    fs::create_dir(temp_dir.join("dir2/another")).unwrap();
+    let added = service
+        .hash_directory(&temp_dir.join("dir2"))
+        .await
+        .unwrap();
+    assert_ne!(added.digest, first.digest);
+}
+
+#[tokio::test]
+async fn test_test_pattern() {
+    let (temp_dir, service, _allowed) = setup_service(vec!["dir1".to_string()]);
+    let samples = vec![
+        "src/main.rs".to_string(),
+        "Cargo.TOML".to_string(),
+        "README.md".to_string(),
+        "target/".to_string(),
+    ];
+
+    let test = service.test_pattern("*.{rs,toml}", PatternMode::Glob, samples.clone(), true);
+    assert_eq!(test.error, None);
+    assert_eq!(
+        test.samples
+            .iter()
+            .filter(|(_, matched)| *matched)
+            .map(|(sample, _)| sample.as_str())
+            .collect::<Vec<_>>(),
+        vec!["src/main.rs", "Cargo.TOML"]
+    );
+    assert!(test.render_text().contains("Matches 2 of 4 samples:"));
+
+    // A trailing '/' only matches directories
+    let test = service.test_pattern("target/", PatternMode::Glob, samples.clone(), true);
+    assert_eq!(test.samples[3], ("target/".to_string(), true));
+    assert_eq!(
+        test.samples.iter().filter(|(_, matched)| *matched).count(),
+        1
+    );
+
+    // Unbalanced braces are reported with their position
+    let test = service.test_pattern("*.{rs,toml", PatternMode::Glob, samples.clone(), true);
+    let error = test.error.unwrap();
+    assert!(error.contains("unclosed '{' at position 2"));
+    assert!(error.contains("\n      ^\n"));
+
+    let test = service.test_pattern(r"fn \w+\(", PatternMode::Regex, samples.clone(), true);
+    assert!(test.samples.iter().all(|(_, matched)| !matched));
+    let test = service.test_pattern("foo(bar", PatternMode::Regex, samples, true);
+    assert!(test.error.unwrap().contains('^'));
+
+    // Samples taken from a directory are relative, sorted and capped
+    let root = temp_dir.join("dir1");
+    create_temp_file(&root.join("b"), "two.rs", "");
+    create_temp_file(&root, "a.rs", "");
+    create_temp_file(&root, "c.rs", "");
+    let samples = service.pattern_samples(&root, 3).await.unwrap();
+    assert_eq!(samples, vec!["a.rs", "b/", "b/two.rs"]);
+
+    let file = create_temp_file(&root, "lines.txt", "one\ntwo\nthree\n");
+    let samples = service.pattern_samples(&file, 2).await.unwrap();
+    assert_eq!(samples, vec!["one", "two"]);
+}
+
+#[tokio::test]
+async fn test_delete_path_refuses_dangerous_targets() {
+    let (temp_dir, service, _allowed) = setup_service(vec!["dir1".to_string()]);
+    let service = service
+        .with_performance_config(PerformanceConfig {
+            max_delete_entries: 4,
+            ..Default::default()
+        })
+        .unwrap();
+    let root = temp_dir.join("dir1");
+
+    // The allowed directory itself
+    let result = service.delete_path(&root, true, true, true, false).await;
+    assert!(
+        matches!(result, Err(ServiceError::FromString(message)) if message.contains("it is or holds an allowed directory"))
+    );
+    // ... also when named through a `..` component
+    create_temp_file(&root.join("sub"), "keep.txt", "keep");
+    let result = service
+        .delete_path(&root.join("sub").join(".."), true, true, true, false)
+        .await;
+    assert!(
+        matches!(result, Err(ServiceError::FromString(message)) if message.contains("it is or holds an allowed directory"))
+    );
+    assert!(root.join("sub/keep.txt").exists());
+    fs::remove_dir_all(root.join("sub")).unwrap();
+
+    // A directory holding a git repository
+    create_temp_file(&root.join("project/.git"), "HEAD", "ref: refs/heads/main\n");
+    create_temp_file(&root.join("project/src"), "lib.rs", "");
+    let project = root.join("project");
+    let result = service
+        .delete_path(&project, true, false, false, false)
+        .await;
+    assert!(
+        matches!(result, Err(ServiceError::FromString(message)) if message.contains("is a git repository"))
+    );
+    assert!(project.join(".git/HEAD").exists());
+    let report = service
+        .delete_path(&project, true, true, false, false)
+        .await
+        .unwrap();
+    assert!(report.is_complete());
+    assert!(!project.exists());
+
+    // A directory holding more entries than the limit
+    for name in ["a.txt", "b.txt", "c.txt", "d.txt", "e.txt"] {
+        create_temp_file(&root.join("big"), name, "12345");
+    }
+    let big = root.join("big");
+    let result = service.delete_path(&big, true, false, false, false).await;
+    assert!(
+        matches!(result, Err(ServiceError::FromString(message)) if message.contains("more than 4 entries"))
+    );
+
+    // A dry run only counts, and the counts are reported when deleting too
+    let report = service
+        .delete_path(&big, true, false, true, true)
+        .await
+        .unwrap();
+    assert_eq!(report.planned.files, 5);
+    assert_eq!(report.planned.directories, 1);
+    assert_eq!(report.planned.bytes, 25);
+    assert!(big.exists());
+    let report = service
+        .delete_path(&big, true, false, true, false)
+        .await
+        .unwrap();
+    assert_eq!(report.deleted, report.planned);
+    assert!(
+        report
+            .render_text(&Default::default())
+            .contains("Deleted 5 files, 1 directory (25 bytes).")
+    );
+    assert!(!big.exists());
+
+    // A directory that is not empty needs 'recursive'
+    let file = create_temp_file(&root.join("kept"), "file.txt", "x");
+    let result = service
+        .delete_path(&root.join("kept"), false, false, false, false)
+        .await;
+    assert!(
+        matches!(result, Err(ServiceError::FromString(message)) if message.contains("Set 'recursive'"))
+    );
+    service
+        .delete_path(&file, false, false, false, false)
+        .await
+        .unwrap();
+    assert!(!file.exists());
+}
+
+#[test]
+fn test_delete_reports_partial_failures() {
+    let temp_dir = get_temp_dir();
+    let root = temp_dir.join("tree");
+    create_temp_file(&root.join("a"), "one.txt", "1");
+    create_temp_file(&root.join("a"), "two.txt", "2");
+    create_temp_file(&root.join("b"), "three.txt", "3");
+
+    let plan = plan_delete(
+        &root,
+        &DeleteOptions {
+            recursive: true,
+            ..Default::default()
+        },
+    )
+    .unwrap();
+    assert_eq!(plan.counts.files, 3);
+    assert_eq!(plan.counts.directories, 3);
+    assert_eq!(plan.entries.last().unwrap().path, root);
+
+    // A file replaced by a directory after counting cannot be removed as a file
+    let undeletable = root.join("a/one.txt");
+    fs::remove_file(&undeletable).unwrap();
+    create_temp_file(&undeletable, "inner.txt", "x");
+
+    let report = execute_delete(&plan);
+    assert!(!report.is_complete());
+    assert_eq!(report.failures.len(), 1);
+    assert_eq!(report.failures[0].path, undeletable);
+    // Everything else is deleted; the directories above the failure are kept
+    assert_eq!(report.deleted.files, 2);
+    assert_eq!(report.deleted.directories, 1);
+    assert!(!root.join("a/two.txt").exists());
+    assert!(!root.join("b").exists());
+    assert!(undeletable.join("inner.txt").exists());
+    assert!(
+        report
+            .render_text(&Default::default())
+            .contains("Failed to delete 1 entry:")
+    );
+}
+
+#[tokio::test]
+async fn test_concat_files() {
+    let (temp_dir, service, _allowed) = setup_service(vec!["dir1".to_string()]);
+    let root = temp_dir.join("dir1");
+    let sources: Vec<String> = [
+        ("one.txt", "first\n".as_bytes()),
+        ("two.bin", &[0u8, 159, 146, 150, 255]),
+        ("three.txt", "third, without a newline".as_bytes()),
+    ]
+    .into_iter()
+    .map(|(name, content)| {
+        let path = root.join(name);
+        fs::write(&path, content).unwrap();
+        path.to_str().unwrap().to_string()
+    })
+    .collect();
+
+    let destination = root.join("joined.txt");
+    let written = service
+        .concat_files(&sources, &destination, None)
+        .await
+        .unwrap();
+    let mut expected = b"first\n".to_vec();
+    expected.extend_from_slice(&[0, 159, 146, 150, 255]);
+    expected.extend_from_slice(b"third, without a newline");
+    assert_eq!(fs::read(&destination).unwrap(), expected);
+    assert_eq!(written, expected.len() as u64);
+
+    // The separator only goes between sources, and the destination is overwritten
+    service
+        .concat_files(&sources[..2], &destination, Some("\n---\n"))
+        .await
+        .unwrap();
+    let mut expected = b"first\n\n---\n".to_vec();
+    expected.extend_from_slice(&[0, 159, 146, 150, 255]);
+    assert_eq!(fs::read(&destination).unwrap(), expected);
+
+    // A missing source fails before the destination is touched
+    let other = root.join("other.txt");
+    let mut with_missing = sources.clone();
+    with_missing.insert(1, root.join("missing.txt").to_str().unwrap().to_string());
+    assert!(
+        service
+            .concat_files(&with_missing, &other, None)
+            .await
+            .is_err()
+    );
+    assert!(!other.exists());
+
+    let result = service
+        .concat_files(&sources, Path::new(&sources[0]), None)
+        .await;
+    assert!(
+        matches!(result, Err(ServiceError::FromString(message)) if message.contains("cannot also be a source"))
+    );
+    assert_eq!(fs::read(&sources[0]).unwrap(), b"first\n");
+}
+
+#[tokio::test]
+async fn test_list_definitions_rust_signatures() {
+    let (temp_dir, service, _allowed_dirs) = setup_service(vec!["dir1".to_string()]);
+    let source = r#"pub async fn fetch(
+    url: &str, // the target
+    retries: usize,
+) -> Result<(), Error> {
+    todo!()
+}
+
+pub(crate) fn longest<'a, T: AsRef<str> + ?Sized>(a: &'a T, b: &'a T) -> &'a str
+where
+    T: Debug,
+{
+    todo!()
+}
+
+impl Buffer {
+    pub const unsafe fn get_mut(&mut self, (start, end): (usize, usize)) -> &mut [u8; 4] {
+        todo!()
+    }
+}
+
+fn apply(callback: impl Fn(u8) -> u8, items: &mut Vec<impl Into<u8>>) {}
+
+extern "C" fn exported(mut self: Box<Self>);
+
+macro_rules! handlers {
+    ($name:ident) => {
+        pub fn handle(ctx: Ctx, $($arg: $ty),*) -> Result<()> {
+        }
+    };
+}
+"#;
+    let file_path = create_temp_file(&temp_dir.join("dir1"), "lib.rs", source);
+    let definitions = service.list_definitions(&file_path).await.unwrap();
+    let signature = |name: &str| {
+        definitions
+            .iter()
+            .find(|definition| definition.name == name)
+            .and_then(|definition| definition.signature.clone())
+            .unwrap()
+    };
+
+    // A multi-line signature with a trailing comma and a comment
+    let fetch = signature("fetch");
+    assert!(fetch.parsed);
+    assert_eq!(
+        fetch.text,
+        "async fn fetch(url: &str, retries: usize) -> Result<(), Error>"
+    );
+    assert_eq!(fetch.qualifiers, vec!["async"]);
+    assert_eq!(
+        fetch
+            .parameters
+            .iter()
+            .map(|parameter| (parameter.name.as_str(), parameter.type_name.as_str()))
+            .collect::<Vec<_>>(),
+        vec![("url", "&str"), ("retries", "usize")]
+    );
+    assert_eq!(fetch.return_type.as_deref(), Some("Result<(), Error>"));
+
+    // Lifetimes, bounded generics and a where clause
+    let longest = signature("longest");
+    assert_eq!(longest.generics, vec!["'a", "T: AsRef<str> + ?Sized"]);
+    assert_eq!(longest.parameters[1].type_name, "&'a T");
+    assert_eq!(longest.return_type.as_deref(), Some("&'a str"));
+    assert_eq!(longest.where_predicates, vec!["T: Debug"]);
+    assert_eq!(
+        longest.text,
+        "fn longest<'a, T: AsRef<str> + ?Sized>(a: &'a T, b: &'a T) -> &'a str where T: Debug"
+    );
+
+    // Receivers, tuple patterns and array types, in a `const` function listed as a function
+    assert!(
+        definitions
+            .iter()
+            .any(|definition| definition.name == "get_mut"
+                && definition.kind == "fn"
+                && definition.visibility == "pub")
+    );
+    assert!(
+        definitions
+            .iter()
+            .all(|definition| definition.kind != "const")
+    );
+    let get_mut = signature("get_mut");
+    assert_eq!(get_mut.qualifiers, vec!["const", "unsafe"]);
+    assert_eq!(get_mut.parameters[0].name, "self");
+    assert_eq!(get_mut.parameters[0].type_name, "&mut Self");
+    assert_eq!(get_mut.parameters[1].name, "(start, end)");
+    assert_eq!(
+        get_mut.text,
+        "const unsafe fn get_mut(&mut self, (start, end): (usize, usize)) -> &mut [u8; 4]"
+    );
+
+    // impl Trait parameters, whose arrows do not close the generic lists
+    let apply = signature("apply");
+    assert_eq!(apply.parameters[0].type_name, "impl Fn(u8) -> u8");
+    assert_eq!(apply.parameters[1].type_name, "&mut Vec<impl Into<u8>>");
+    assert_eq!(apply.return_type, None);
+
+    let exported = signature("exported");
+    assert_eq!(exported.qualifiers, vec!["extern \"C\""]);
+    assert_eq!(exported.parameters[0].name, "mut self");
+    assert_eq!(exported.parameters[0].type_name, "Box<Self>");
+
+    // Macro templates fall back to the raw text
+    let handle = signature("handle");
+    assert!(!handle.parsed);
+    assert_eq!(
+        handle.text,
+        "pub fn handle(ctx: Ctx, $($arg: $ty),*) -> Result<()>"
+    );
+    assert!(handle.parameters.is_empty());
+
+    // Only functions have a signature
+    let json = serde_json::to_value(&definitions).unwrap();
+    assert!(json[0]["signature"]["parsed"].as_bool().unwrap());
+    assert!(
+        json.as_array()
+            .unwrap()
+            .iter()
+            .filter(|definition| definition["kind"] != "fn")
+            .all(|definition| definition.get("signature").is_none())
+    );
+}
+
+#[tokio::test]
+async fn test_bulk_insert() {
+    let (temp_dir, service, _allowed) = setup_service(vec!["dir1".to_string()]);
+    let root = temp_dir.join("dir1");
+    let header = "// SPDX-License-Identifier: MIT\n";
+    let lib = create_temp_file(&root.join("src"), "lib.rs", "pub mod util;\n");
+    let util = create_temp_file(&root.join("src/util"), "mod.rs", "pub fn f() {}\r\n");
+    let licensed = create_temp_file(
+        &root.join("src"),
+        "main.rs",
+        "// SPDX-License-Identifier: MIT\nfn main() {}\n",
+    );
+    let outside = create_temp_file(&root, "build.rs", "fn main() {}\n");
+
+    // A dry run lists the intended changes
+    let report = service
+        .bulk_insert(
+            &root,
+            "src/**/*.rs",
+            vec![],
+            "start",
+            header,
+            Some("SPDX-License-Identifier"),
+            true,
+        )
+        .await
+        .unwrap();
+    let statuses: Vec<_> = report
+        .files
+        .iter()
+        .map(|file| (file.path.clone(), file.status.clone(), file.lines_added))
+        .collect();
+    assert_eq!(
+        statuses,
+        vec![
+            (lib.clone(), InsertStatus::WouldInsert, 1),
+            (licensed.clone(), InsertStatus::Guarded, 0),
+            (util.clone(), InsertStatus::WouldInsert, 1),
+        ]
+    );
+    assert_eq!(fs::read_to_string(&lib).unwrap(), "pub mod util;\n");
+    let text = report.render_text(&service.byte_format());
+    assert!(text.contains("would insert"), "{text}");
+    assert!(text.contains("2 files changed, 2 insertions(+)"), "{text}");
+    assert!(text.contains("Dry run: no file was changed."), "{text}");
+
+    let report = service
+        .bulk_insert(
+            &root,
+            "src/**/*.rs",
+            vec![],
+            "start",
+            header,
+            Some("SPDX-License-Identifier"),
+            false,
+        )
+        .await
+        .unwrap();
+    assert_eq!(report.count(&InsertStatus::Inserted), 2);
+    assert_eq!(report.count(&InsertStatus::Guarded), 1);
+    assert_eq!(
+        fs::read_to_string(&lib).unwrap(),
+        "// SPDX-License-Identifier: MIT\npub mod util;\n"
+    );
+    // The inserted lines take the line ending of the file
+    assert_eq!(
+        fs::read_to_string(&util).unwrap(),
+        "// SPDX-License-Identifier: MIT\r\npub fn f() {}\r\n"
+    );
+    assert_eq!(
+        fs::read_to_string(&licensed).unwrap(),
+        "// SPDX-License-Identifier: MIT\nfn main() {}\n"
+    );
+    assert_eq!(fs::read_to_string(&outside).unwrap(), "fn main() {}\n");
+    assert!(
+        report
+            .render_text(&service.byte_format())
+            .contains("1 skipped by the guard")
+    );
+
+    // Repeating the insertion changes nothing
+    let report = service
+        .bulk_insert(
+            &root,
+            "src/**/*.rs",
+            vec![],
+            "start",
+            header,
+            Some("SPDX-License-Identifier"),
+            false,
+        )
+        .await
+        .unwrap();
+    assert_eq!(report.count(&InsertStatus::Guarded), 3);
+
+    // After the first line matching a pattern, guarded by the content itself
+    let report = service
+        .bulk_insert(
+            &root,
+            "*.rs",
+            vec!["src".to_string()],
+            "^fn main",
+            "// end of main",
+            None,
+            false,
+        )
+        .await
+        .unwrap();
+    assert_eq!(report.count(&InsertStatus::Inserted), 1);
+    assert_eq!(
+        fs::read_to_string(&outside).unwrap(),
+        "fn main() {}\n// end of main\n"
+    );
+    let report = service
+        .bulk_insert(&root, "build.rs", vec![], "12", "// late", None, true)
+        .await
+        .unwrap();
+    assert_eq!(report.files[0].status, InsertStatus::NoAnchor);
+}
+
+#[tokio::test]
+async fn test_list_definitions_ignores_comments_and_strings() {
+    let (temp_dir, service, _allowed_dirs) = setup_service(vec!["dir1".to_string()]);
+    let source = r##"const TEMPLATE: &str = "fn in_string() {}";
+
+/*
+fn in_block_comment() {}
+*/
+fn real() -> &'static str {
+    r#"
+fn in_raw_string() {}
+"#
+}
+
+// fn in_line_comment() {}
+fn also_real(c: char) -> bool { c == '"' } // fn trailing() {}
+"##;
+    let file_path = create_temp_file(&temp_dir.join("dir1"), "lib.rs", source);
+
+    let definitions = service.list_definitions(&file_path).await.unwrap();
+    let names: Vec<_> = definitions
+        .iter()
+        .map(|d| (d.name.as_str(), d.start_line, d.end_line))
+        .collect();
+    assert_eq!(
+        names,
+        vec![("TEMPLATE", 1, 1), ("real", 5, 10), ("also_real", 12, 13)]
+    );
+
+    for name in ["in_string", "in_block_comment", "in_raw_string"] {
+        let found = service.extract_definition(&file_path, name).await.unwrap();
+        assert!(found.is_empty(), "{name}: {found:?}");
+    }
+    assert_eq!(
+        service
+            .extract_definition(&file_path, "real")
+            .await
+            .unwrap()
+            .len(),
+        1
+    );
+
+    // Python docstrings are strings too
+    let file_path = create_temp_file(
+        &temp_dir.join("dir1"),
+        "module.py",
+        "def documented():\n    \"\"\"\ndef in_docstring():\n    \"\"\"\n    return 1\n",
+    );
+    let definitions = service.list_definitions(&file_path).await.unwrap();
+    let names: Vec<_> = definitions.iter().map(|d| d.name.as_str()).collect();
+    assert_eq!(names, vec!["documented"]);
+}
+
+/// Reports a fixed amount of free space on every filesystem.
+#[derive(Debug)]
+struct FixedSpace(u64);
+
+impl SpaceProvider for FixedSpace {
+    fn available_space(&self, _path: &Path) -> Option<u64> {
+        Some(self.0)
+    }
+}
+
+#[tokio::test]
+async fn test_writes_refused_without_disk_space() {
+    let (temp_dir, service, _allowed) = setup_service(vec!["dir1".to_string()]);
+    let root = temp_dir.join("dir1");
+    let input = create_temp_file(&root, "input.txt", &"y".repeat(8192));
+    let archive = root.join("archive.zip");
+    service
+        .zip_files(
+            vec![input.to_str().unwrap().to_string()],
+            archive.to_str().unwrap().to_string(),
+        )
+        .await
+        .unwrap();
+
+    let service = service
+        .with_performance_config(PerformanceConfig {
+            disk_space_check_threshold: 1024,
+            ..Default::default()
+        })
+        .unwrap()
+        .with_space_provider(FixedSpace(4096));
+
+    let large = root.join("large.txt");
+    let result = service.write_file(&large, &"x".repeat(8192)).await;
+    assert!(matches!(
+        result,
+        Err(ServiceError::InsufficientDiskSpace {
+            needed: 8192,
+            available: 4096,
+            ..
+        })
+    ));
+    assert_eq!(
+        result.unwrap_err().to_string(),
+        format!(
+            "Insufficient disk space on the filesystem of '{}': need 8192 bytes, have 4096 bytes.",
+            large.display()
+        )
+    );
+    assert!(!large.exists());
+
+    // Writes below the threshold are not checked
+    let small = root.join("small.txt");
+    service.write_file(&small, &"x".repeat(512)).await.unwrap();
+
+    // Archives are refused by the size of their input, extractions by their uncompressed size
+    let second_archive = root.join("second.zip");
+    let result = service
+        .zip_files(
+            vec![input.to_str().unwrap().to_string()],
+            second_archive.to_str().unwrap().to_string(),
+        )
+        .await;
+    assert!(matches!(
+        result,
+        Err(ServiceError::InsufficientDiskSpace { needed: 8192, .. })
+    ));
+    assert!(!second_archive.exists());
+
+    let target = root.join("extracted");
+    let result = service
+        .unzip_file(archive.to_str().unwrap(), target.to_str().unwrap())
+        .await;
+    assert!(matches!(
+        result,
+        Err(ServiceError::InsufficientDiskSpace { needed: 8192, .. })
+    ));
+    assert!(!target.exists());
+}
+
+/// Mounts a small tmpfs for the lifetime of the guard.
+#[cfg(target_os = "linux")]
+struct SmallTmpfs(PathBuf);
+
+#[cfg(target_os = "linux")]
+impl SmallTmpfs {
+    fn mount(path: &Path, size: &str) -> Option<Self> {
+        fs::create_dir_all(path).unwrap();
+        let mounted = std::process::Command::new("mount")
+            .args(["-t", "tmpfs", "-o", &format!("size={size}"), "tmpfs"])
+            .arg(path)
+            .stderr(std::process::Stdio::null())
+            .status()
+            .is_ok_and(|status| status.success());
+        mounted.then(|| Self(path.to_path_buf()))
+    }
+}
+
+#[cfg(target_os = "linux")]
+impl Drop for SmallTmpfs {
+    fn drop(&mut self) {
+        let _ = std::process::Command::new("umount").arg(&self.0).status();
+    }
+}
+
+#[cfg(target_os = "linux")]
+#[tokio::test]
+async fn test_disk_full_cleans_up_partial_output() {
+    let (temp_dir, service, _allowed) = setup_service(vec!["dir1".to_string()]);
+    // The free space check passes, so the writes run out of space midway
+    let service = service.with_space_provider(FixedSpace(u64::MAX));
+    let root = temp_dir.join("dir1");
+    let Some(_tmpfs) = SmallTmpfs::mount(&root.join("small"), "64k") else {
+        eprintln!("Skipping: mounting a tmpfs requires privileges.");
+        return;
+    };
+    let small = root.join("small");
+
+    let result = service
+        .write_file(&small.join("large.txt"), &"x".repeat(256 * 1024))
+        .await;
+    assert!(
+        matches!(&result, Err(ServiceError::DiskFull { cleanup, .. }) if cleanup == "The partial file was removed."),
+        "{result:?}"
+    );
+    assert!(!small.join("large.txt").exists());
+
+    // Highly compressible entries make a small archive that does not fit once extracted
+    let sources: Vec<String> = (0..4)
+        .map(|index| {
+            create_temp_file(&root, &format!("part{index}.txt"), &"z".repeat(48 * 1024))
+                .to_str()
+                .unwrap()
+                .to_string()
+        })
+        .collect();
+    let archive = root.join("parts.zip");
+    service
+        .zip_files(sources, archive.to_str().unwrap().to_string())
+        .await
+        .unwrap();
+
+    let target = small.join("extracted");
+    let result = service
+        .unzip_file(archive.to_str().unwrap(), target.to_str().unwrap())
+        .await;
+    let Err(ServiceError::DiskFull { path, cleanup }) = result else {
+        panic!("expected a disk full error, got {result:?}");
+    };
+    assert_eq!(path, target);
+    assert!(
+        cleanup.starts_with("Removed the partial extraction:"),
+        "{cleanup}"
+    );
+    assert!(!target.exists());
+    assert_eq!(fs::read_dir(&small).unwrap().count(), 0);
+}
+
+#[tokio::test]
+async fn test_read_enclosing_block() {
+    let (temp_dir, service, _allowed_dirs) = setup_service(vec!["dir1".to_string()]);
+    let source = r#"use std::fmt;
+
+impl Widget {
+    /// Renders the widget.
+    pub fn render(
+        &self,
+        out: &mut String,
+    ) -> fmt::Result {
+        let open = "{";
+        for part in &self.parts {
+            out.push_str(part); // }
+        }
+        Ok(())
+    }
+}
+"#;
+    let file_path = create_temp_file(&temp_dir.join("dir1"), "widget.rs", source);
+
+    // Inside the loop, whose braces are unbalanced by a string and a comment before it
+    let block = service.read_enclosing_block(&file_path, 11).await.unwrap();
+    assert_eq!((block.start_line, block.end_line), (10, 12));
+    // In the function body: the multi-line signature and its doc comment are included
+    let block = service.read_enclosing_block(&file_path, 13).await.unwrap();
+    assert_eq!((block.start_line, block.end_line), (4, 14));
+    assert!(block.source.starts_with("    /// Renders the widget."));
+    assert!(block.source.ends_with("        Ok(())\n    }"));
+    // A line opening a block returns that block
+    let block = service.read_enclosing_block(&file_path, 3).await.unwrap();
+    assert_eq!((block.start_line, block.end_line), (3, 15));
+
+    assert!(service.read_enclosing_block(&file_path, 1).await.is_err());
+    assert!(service.read_enclosing_block(&file_path, 99).await.is_err());
+
+    let python = "class Shape:\n    def area(self):\n        if self.empty:\n            return 0\n\n        return self.w * self.h\n\nprint(1)\n";
+    let file_path = create_temp_file(&temp_dir.join("dir1"), "shape.py", python);
+    let block = service.read_enclosing_block(&file_path, 6).await.unwrap();
+    assert_eq!((block.start_line, block.end_line), (2, 6));
+    let block = service.read_enclosing_block(&file_path, 4).await.unwrap();
+    assert_eq!((block.start_line, block.end_line), (3, 4));
+}
+
+fn utf16le(text: &str) -> Vec<u8> {
+    text.encode_utf16().flat_map(u16::to_le_bytes).collect()
+}
+
+#[test]
+fn test_text_probe_classes() {
+    assert_eq!(classify_bytes(b"fn main() {}\n"), FileClass::TextUtf8);
+    assert_eq!(
+        classify_bytes("héllo wörld\n".as_bytes()),
+        FileClass::TextUtf8
+    );
+    assert_eq!(
+        classify_bytes(b"\xEF\xBB\xBFwith bom\n"),
+        FileClass::TextUtf8
+    );
+    // A multi-byte character cut off by the end of the sample
+    let mut cut = "a".repeat(TEXT_PROBE_SIZE - 1).into_bytes();
+    cut.extend("é".as_bytes());
+    assert_eq!(classify_bytes(&cut), FileClass::TextUtf8);
+
+    let mut with_bom = vec![0xFF, 0xFE];
+    with_bom.extend(utf16le("hello"));
+    assert_eq!(
+        classify_bytes(&with_bom),
+        FileClass::TextOtherEncoding(TextEncoding::Utf16Le)
+    );
+    assert_eq!(
+        classify_bytes(&utf16le("hello world\r\n")),
+        FileClass::TextOtherEncoding(TextEncoding::Utf16Le)
+    );
+    let big_endian: Vec<u8> = "hello".encode_utf16().flat_map(u16::to_be_bytes).collect();
+    assert_eq!(
+        classify_bytes(&big_endian),
+        FileClass::TextOtherEncoding(TextEncoding::Utf16Be)
+    );
+    assert_eq!(
+        classify_bytes(b"caf\xE9 cr\xE8me\n"),
+        FileClass::TextOtherEncoding(TextEncoding::Latin1)
+    );
+
+    assert_eq!(
+        classify_bytes(b"\x7FELF\x02\x01\x01\0\0\0"),
+        FileClass::Binary
+    );
+    assert_eq!(
+        classify_bytes(b"\x89PNG\r\n\x1A\n\0\0\0\rIHDR"),
+        FileClass::Binary
+    );
+    assert_eq!(
+        classify_bytes(b"\x01\x02\x03\x04\xFF\xFE\x05"),
+        FileClass::Binary
+    );
+
+    let (temp_dir, _service, _allowed_dirs) = setup_service(vec!["dir1".to_string()]);
+    let file_path = create_temp_file(&temp_dir.join("dir1"), "big.txt", &"a".repeat(100));
+    let config = PerformanceConfig {
+        max_text_file_bytes: 50,
+        ..Default::default()
+    };
+    assert_eq!(classify(&file_path, &config), FileClass::TooLarge(100));
+    assert_eq!(
+        classify(&file_path, &PerformanceConfig::default()),
+        FileClass::TextUtf8
+    );
+    assert!(matches!(
+        classify(&temp_dir.join("dir1/missing.txt"), &config),
+        FileClass::Unreadable(_)
+    ));
+}
+
+#[tokio::test]
+async fn test_text_tools_agree_on_utf16_without_bom() {
+    let (temp_dir, service, _allowed_dirs) = setup_service(vec!["dir1".to_string()]);
+    let dir = temp_dir.join("dir1");
+    // No byte order mark, and a character outside the Basic Multilingual Plane
+    let source = "fn greet() -> &'static str {\n    \"hi 👋\"\n}\n";
+    let file_path = dir.join("greet.rs");
+    fs::write(&file_path, utf16le(source)).unwrap();
+
+    assert_eq!(service.read_text_file(&file_path).await.unwrap(), source);
+
+    let results = service
+        .search_files_content(
+            &dir,
+            "*.rs",
+            "👋",
+            ContentSearchOptions::default(),
+            None,
+            None,
+            None,
+        )
+        .await
+        .unwrap();
+    assert_eq!(results.files.len(), 1);
+    assert_eq!(results.files[0].matches[0].line_number, 2);
+
+    let definitions = service.list_definitions(&file_path).await.unwrap();
+    let spans: Vec<_> = definitions
+        .iter()
+        .map(|d| (d.name.as_str(), d.start_line, d.end_line))
+        .collect();
+    assert_eq!(spans, vec![("greet", 1, 3)]);
+
+    // The same bytes with a stray NUL code unit are binary to all three
+    let mut binary = utf16le(source);
+    binary.extend([0, 0]);
+    fs::write(&file_path, binary).unwrap();
+    assert!(matches!(
+        service.read_text_file(&file_path).await,
+        Err(ServiceError::NotText {
+            class: FileClass::Binary,
+            ..
+        })
+    ));
+    let results = service
+        .search_files_content(
+            &dir,
+            "*.rs",
+            "greet",
+            ContentSearchOptions::default(),
+            None,
+            None,
+            None,
+        )
+        .await
+        .unwrap();
+    assert!(results.files.is_empty());
+    assert!(service.list_definitions(&file_path).await.is_err());
+}
+
+#[tokio::test]
+async fn test_performance_counters() {
+    let (temp_dir, service, _allowed_dirs) = setup_service(vec!["dir1".to_string()]);
+    let dir = temp_dir.join("dir1");
+    let file_path = create_temp_file(&dir, "notes.txt", "alpha\nbeta\n");
+    let counter = |counters: &[(Counter, u64)], wanted: Counter| {
+        counters
+            .iter()
+            .find(|(counter, _)| *counter == wanted)
+            .map(|(_, value)| *value)
+            .unwrap()
+    };
+    assert!(
+        service
+            .performance_counters(false)
+            .iter()
+            .all(|(_, value)| *value == 0)
+    );
+
+    service
+        .detect_generated_files(&dir, None, None)
+        .await
+        .unwrap();
+    service
+        .read_text_file_deduped(&file_path, false)
+        .await
+        .unwrap();
+    service
+        .read_text_file_deduped(&file_path, false)
+        .await
+        .unwrap();
+    service
+        .search_files_content(
+            &dir,
+            "*.txt",
+            "beta",
+            ContentSearchOptions::default(),
+            None,
+            None,
+            None,
+        )
+        .await
+        .unwrap();
+    service
+        .write_file(&dir.join("small.txt"), &"tiny".to_string())
+        .await
+        .unwrap();
+
+    let counters = service.performance_counters(true);
+    assert_eq!(counter(&counters, Counter::ParallelPath), 1);
+    assert_eq!(counter(&counters, Counter::ReadCacheMiss), 1);
+    assert_eq!(counter(&counters, Counter::ReadCacheHit), 1);
+    assert_eq!(counter(&counters, Counter::SearchStreamed), 1);
+    assert_eq!(counter(&counters, Counter::SearchBuffered), 0);
+    assert_eq!(counter(&counters, Counter::DiskSpaceCheckSkipped), 1);
+    assert_eq!(counter(&counters, Counter::DiskSpaceChecked), 0);
+
+    // The reset returned the values before it
+    assert_eq!(
+        counter(&service.performance_counters(false), Counter::ParallelPath),
+        0
+    );
+}
+
+#[tokio::test]
+async fn test_apply_file_changes_rejects_changed_file() {
+    let (temp_dir, service, _allowed_dirs) = setup_service(vec!["dir1".to_string()]);
+    let file = create_temp_file(
+        &temp_dir.join("dir1"),
+        "config.ini",
+        "mode=debug\nlevel=1\n",
+    );
+    let edits = || {
+        vec![EditOperation {
+            old_text: "mode=debug".to_string(),
+            new_text: "mode=release".to_string(),
+            new_text_from_file: None,
+            trim_search: None,
+        }]
+    };
+    let preview_hash = |preview: &str| {
+        preview
+            .lines()
+            .find_map(|line| line.strip_prefix("Content hash: "))
+            .unwrap()
+            .to_string()
+    };
+
+    let preview = service
+        .apply_file_changes_guarded(&file, edits(), vec![], Some(true), None, None)
+        .await
+        .unwrap();
+    let hash = preview_hash(&preview);
+    assert!(hash.starts_with("sha256:"));
+
+    // Changed between the preview and the apply
+    fs::write(&file, "mode=debug\nlevel=2\n").unwrap();
+    let err = service
+        .apply_file_changes_guarded(&file, edits(), vec![], Some(false), None, Some(&hash))
+        .await
+        .unwrap_err();
+    assert!(
+        err.to_string()
+            .contains("changed since the edits were previewed")
+    );
+    assert_eq!(fs::read_to_string(&file).unwrap(), "mode=debug\nlevel=2\n");
+
+    let preview = service
+        .apply_file_changes_guarded(&file, edits(), vec![], Some(true), None, None)
+        .await
+        .unwrap();
+    service
+        .apply_file_changes_guarded(
+            &file,
+            edits(),
+            vec![],
+            Some(false),
+            None,
+            Some(&preview_hash(&preview)),
+        )
+        .await
+        .unwrap();
+    assert_eq!(
+        fs::read_to_string(&file).unwrap(),
+        "mode=release\nlevel=2\n"
+    );
+}
+
+#[tokio::test]
+async fn test_convert_file_line_endings() {
+    let (temp_dir, service, _allowed_dirs) = setup_service(vec!["dir1".to_string()]);
+    let file = create_temp_file(
+        &temp_dir.join("dir1"),
+        "mixed.txt",
+        "one\r\ntwo\nthree\r\nfour",
+    );
+
+    let preview = service
+        .convert_file(&file, Some(LineEnding::Lf), None, None, 4, false, true)
+        .await
+        .unwrap();
+    assert!(preview.contains("changed    line endings to LF: 2 lines"));
+    assert!(preview.contains("Dry run"));
+    assert_eq!(
+        fs::read_to_string(&file).unwrap(),
+        "one\r\ntwo\nthree\r\nfour"
+    );
+
+    let result = service
+        .convert_file(&file, Some(LineEnding::Lf), None, None, 4, true, false)
+        .await
+        .unwrap();
+    assert!(result.contains("unchanged  trailing whitespace stripped"));
+    assert_eq!(fs::read_to_string(&file).unwrap(), "one\ntwo\nthree\nfour");
+
+    // Converting again changes nothing
+    let result = service
+        .convert_file(&file, Some(LineEnding::Lf), None, None, 4, false, false)
+        .await
+        .unwrap();
+    assert!(result.contains("nothing was written"));
+}
+
+#[tokio::test]
+async fn test_convert_file_latin1_to_utf8() {
+    let (temp_dir, service, _allowed_dirs) = setup_service(vec!["dir1".to_string()]);
+    let file = temp_dir.join("dir1").join("names.txt");
+    // "Café, naïve, Zoë" in ISO-8859-1
+    fs::write(&file, b"Caf\xe9, na\xefve, Zo\xeb\n").unwrap();
+
+    let err = service
+        .convert_file(&file, Some(LineEnding::Crlf), None, None, 4, false, false)
+        .await
+        .unwrap_err();
+    assert!(err.to_string().contains("set 'encoding'"));
+
+    let result = service
+        .convert_file(
+            &file,
+            None,
+            Some(TargetEncoding::Utf8),
+            None,
+            4,
+            false,
+            false,
+        )
+        .await
+        .unwrap();
+    assert!(result.contains("changed    encoding to UTF-8: from ISO-8859-1"));
+    assert_eq!(fs::read_to_string(&file).unwrap(), "Café, naïve, Zoë\n");
+
+    service
+        .convert_file(
+            &file,
+            None,
+            Some(TargetEncoding::Utf8Bom),
+            None,
+            4,
+            false,
+            false,
+        )
+        .await
+        .unwrap();
+    assert_eq!(
+        fs::read(&file).unwrap(),
+        "\u{FEFF}Café, naïve, Zoë\n".as_bytes()
+    );
+}
+
+#[tokio::test]
+async fn test_convert_file_indentation() {
+    let (temp_dir, service, _allowed_dirs) = setup_service(vec!["dir1".to_string()]);
+    let dir = temp_dir.join("dir1");
+
+    // Without a recognized language, only leading whitespace is converted
+    let notes = create_temp_file(&dir, "notes.txt", "key:\n\tvalue \"a\tb\"\n\t\tnested\t\n");
+    let result = service
+        .convert_file(
+            &notes,
+            None,
+            None,
+            Some(Indentation::Spaces),
+            4,
+            false,
+            false,
+        )
+        .await
+        .unwrap();
+    assert!(result.contains("leading whitespace only): 2 lines"));
+    assert_eq!(
+        fs::read_to_string(&notes).unwrap(),
+        "key:\n    value \"a\tb\"\n        nested\t\n"
+    );
+
+    // In code, lines starting inside a multi-line string keep their whitespace
+    let code = create_temp_file(
+        &dir,
+        "lib.rs",
+        "fn text() -> &'static str {\n\t\"first\n\tsecond \n\"\n}\n",
+    );
+    let result = service
+        .convert_file(&code, None, None, Some(Indentation::Spaces), 4, true, false)
+        .await
+        .unwrap();
+    assert!(result.contains("outside string literals): 1 line"));
+    assert!(result.contains("unchanged  trailing whitespace stripped"));
+    assert_eq!(
+        fs::read_to_string(&code).unwrap(),
+        "fn text() -> &'static str {\n    \"first\n\tsecond \n\"\n}\n"
+    );
+
+    service
+        .convert_file(&notes, None, None, Some(Indentation::Tabs), 4, false, false)
+        .await
+        .unwrap();
+    assert_eq!(
+        fs::read_to_string(&notes).unwrap(),
+        "key:\n\tvalue \"a\tb\"\n\t\tnested\t\n"
+    );
+
+    for width in [0, 17, usize::MAX] {
+        let err = service
+            .convert_file(
+                &notes,
+                None,
+                None,
+                Some(Indentation::Tabs),
+                width,
+                false,
+                false,
+            )
+            .await
+            .unwrap_err();
+        assert!(
+            err.to_string().contains("must be between 1 and 16"),
+            "{err}"
+        );
+    }
+}
+
+#[cfg(unix)]
+#[tokio::test]
+async fn test_convert_file_refuses_symlinks() {
+    let (temp_dir, service, _allowed_dirs) = setup_service(vec!["dir1".to_string()]);
+    let dir = temp_dir.join("dir1");
+    let target = create_temp_file(&dir, "target.txt", "a\r\nb\r\n");
+    let link = dir.join("link.txt");
+    std::os::unix::fs::symlink(&target, &link).unwrap();
+
+    let err = service
+        .convert_file(&link, Some(LineEnding::Lf), None, None, 4, false, false)
+        .await
+        .unwrap_err();
+    assert!(err.to_string().contains("symbolic links are not rewritten"));
+    assert!(fs::symlink_metadata(&link).unwrap().is_symlink());
+    assert_eq!(fs::read_to_string(&target).unwrap(), "a\r\nb\r\n");
+}
+
+#[tokio::test]
+async fn test_flatten_directory_resolves_collisions() {
+    let (temp_dir, service, _allowed_dirs) = setup_service(vec!["dir1".to_string()]);
+    let root = temp_dir.join("dir1").join("assets");
+    let tree = |root: &Path| {
+        create_temp_file(root, "logo.png", "top");
+        create_temp_file(&root.join("icons"), "logo.png", "icons");
+        create_temp_file(&root.join("icons/small"), "Logo.png", "small");
+        create_temp_file(&root.join("icons/small"), "archive.tar.gz", "archive");
+        create_temp_file(&root.join("zip"), "archive.tar.gz", "zip");
+    };
+    tree(&root);
+
+    let preview = service
+        .flatten_directory(&root, None, false, false, true)
+        .await
+        .unwrap();
+    let names = |report: &FlattenReport| {
+        report
+            .plan
+            .moves
+            .iter()
+            .map(|planned| {
+                (
+                    planned
+                        .from
+                        .strip_prefix(&root)
+                        .unwrap()
+                        .to_string_lossy()
+                        .replace('\\', "/"),
+                    planned
+                        .to
+                        .file_name()
+                        .unwrap()
+                        .to_string_lossy()
+                        .to_string(),
+                )
+            })
+            .collect::<Vec<_>>()
+    };
+    let pairs = |pairs: &[(&str, &str)]| {
+        pairs
+            .iter()
+            .map(|(from, to)| (from.to_string(), to.to_string()))
+            .collect::<Vec<_>>()
+    };
+    // Shallower files first; the file at the top level keeps its name
+    assert_eq!(
+        names(&preview),
+        pairs(&[
+            ("icons/logo.png", "logo_1.png"),
+            ("zip/archive.tar.gz", "archive.tar.gz"),
+            ("icons/small/Logo.png", "Logo_2.png"),
+            ("icons/small/archive.tar.gz", "archive_1.tar.gz"),
+        ])
+    );
+    assert!(preview.render_text().contains("Dry run"));
+    assert!(root.join("icons/logo.png").exists());
+
+    let report = service
+        .flatten_directory(&root, None, false, false, false)
+        .await
+        .unwrap();
+    assert_eq!(report.moved, 4);
+    assert!(report.failures.is_empty());
+    assert_eq!(report.removed_directories, 3);
+    assert_eq!(fs::read_to_string(root.join("logo.png")).unwrap(), "top");
+    assert_eq!(
+        fs::read_to_string(root.join("logo_1.png")).unwrap(),
+        "icons"
+    );
+    assert_eq!(
+        fs::read_to_string(root.join("Logo_2.png")).unwrap(),
+        "small"
+    );
+    assert_eq!(
+        fs::read_to_string(root.join("archive_1.tar.gz")).unwrap(),
+        "archive"
+    );
+    assert!(!root.join("icons").exists());
+
+    // Path prefixes, into another directory
+    let root = temp_dir.join("dir1").join("more");
+    tree(&root);
+    let destination = temp_dir.join("dir1").join("collected");
+    let report = service
+        .flatten_directory(&root, Some(&destination), true, true, false)
+        .await
+        .unwrap();
+    let mut moved: Vec<_> = fs::read_dir(&destination)
+        .unwrap()
+        .map(|entry| entry.unwrap().file_name().to_string_lossy().to_string())
+        .collect();
+    moved.sort();
+    assert_eq!(
+        moved,
+        [
+            "archive.tar.gz",
+            "icons_logo.png",
+            "icons_small_Logo.png",
+            "icons_small_archive.tar.gz",
+            "logo.png",
+        ]
+    );
+    assert_eq!(report.removed_directories, 0);
+    assert!(root.join("icons/small").is_dir());
+}
+
+#[tokio::test]
+async fn test_recursive_walks_respect_open_file_limit() {
+    let (temp_dir, service, _allowed_dirs) = setup_service(vec!["dir1".to_string()]);
+    let service = service
+        .with_performance_config(PerformanceConfig {
+            max_open_files: 2,
+            ..Default::default()
+        })
+        .unwrap();
+    let root = temp_dir.join("dir1");
+    for dir in 0..40 {
+        for file in 0..50 {
+            // Every tenth file of a directory duplicates the same file of the other directories
+            let content = if file % 10 == 0 {
+                format!("shared {file}")
+            } else {
+                format!("file {dir}/{file}")
+            };
+            create_temp_file(
+                &root.join(format!("d{dir}")),
+                &format!("f{file}.txt"),
+                &content,
+            );
+        }
+    }
+
+    let usage = service.calculate_directory_usage(&root).await.unwrap();
+    assert!(usage.bytes > 0);
+    let duplicates = service
+        .find_duplicate_files(&root, None, None, None, None)
+        .await
+        .unwrap();
+    assert_eq!(duplicates.len(), 5);
+    assert!(duplicates.iter().all(|group| group.len() == 40));
+    service
+        .detect_generated_files(&root, None, None)
+        .await
+        .unwrap();
+    let report = service
+        .bulk_insert(&root, "*.txt", vec![], "end", "tail", None, false)
+        .await
+        .unwrap();
+    assert_eq!(report.count(&InsertStatus::Inserted), 2000);
+
+    let peak = service.descriptor_limit().peak();
+    assert!((1..=2).contains(&peak), "peak of {peak} open files");
+}
+
+#[tokio::test]
+async fn test_read_context_pack() {
+    let (temp_dir, service, _allowed_dirs) = setup_service(vec!["dir1".to_string()]);
+    let root = temp_dir.join("dir1").join("pack");
+    create_temp_file(
+        &root,
+        "src/main.rs",
+        "fn a() {}\nfn b() {}\nfn c() {}\nfn d() {}\n",
+    );
+    create_temp_file(&root, "src/util.rs", "pub fn util() {}\n");
+    create_temp_file(&root, "docs/guide.md", "# Guide\n");
+    create_temp_file(&root, "docs/api.md", "# API\n");
+    let manifest = create_temp_file(
+        &root,
+        ".ctxinclude",
+        "# Entry point first\nsrc/main.rs:2-3\n\ndocs/*.md\nmissing.rs\nsrc/main.rs:9-3\nsrc/*.rs\n",
+    );
+
+    let pack = service
+        .read_context_pack(&manifest, None, None)
+        .await
+        .unwrap();
+    let labels: Vec<_> = pack
+        .sections
+        .iter()
+        .map(|section| section.label.as_str())
+        .collect();
+    // The glob on src/ does not repeat main.rs, already listed with a range
+    assert_eq!(
+        labels,
+        vec![
+            "src/main.rs (lines 2-3)",
+            "docs/api.md",
+            "docs/guide.md",
+            "missing.rs",
+            "src/main.rs:9-3 (manifest line 6)",
+            "src/util.rs",
+        ]
+    );
+    assert_eq!(
+        pack.sections[0].content,
+        Ok("fn b() {}\nfn c() {}\n".to_string())
+    );
+    assert!(pack.sections[3].content.is_err());
+    assert!(pack.sections[4].content.is_err());
+    let text = pack.render_text();
+    assert!(text.starts_with("==> src/main.rs (lines 2-3) <==\nfn b() {}\nfn c() {}\n\n"));
+    assert!(text.contains("==> src/util.rs <==\npub fn util() {}\n"));
+    assert!(!text.contains("Entry point"));
+    assert!(text.ends_with("4 files (51 bytes); 0 truncated, 0 omitted, 2 errors."));
+
+    // 30 bytes keep main.rs and api.md, cut guide.md and leave nothing for util.rs
+    let text = service
+        .read_context_pack(&manifest, Some(30), None)
+        .await
+        .unwrap()
+        .render_text();
+    assert!(text.contains("fn b() {}\nfn c() {}\n"));
+    assert!(text.contains("==> docs/api.md <==\n# API\n"));
+    assert!(text.contains(
+        "==> docs/guide.md <==\n# Gu\n[truncated: 4 of 8 bytes shown, the byte budget is exhausted]"
+    ));
+    assert!(text.contains("==> missing.rs <==\nError: "));
+    assert!(text.contains("==> src/util.rs <==\n[omitted: the byte budget is exhausted]"));
+    assert!(
+        text.ends_with("3 files (30 bytes) of a 30 byte budget; 1 truncated, 1 omitted, 2 errors.")
+    );
+}
+
+#[tokio::test]
+async fn test_check_name_collision_compares_directory_listing() {
+    let (temp_dir, service, _allowed_dirs) = setup_service(vec!["dir1".to_string()]);
+    let dir = temp_dir.join("dir1");
+    create_temp_file(&dir, "README.md", "# Readme");
+    create_temp_file(&dir, "caf\u{e9}.txt", "composed");
+
+    // The file system of the tests is case-sensitive, yet the listing shows the collision
+    let collision = service
+        .check_name_collision(&dir.join("Readme.md"), None, false)
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(collision.kind, NameCollisionKind::Case);
+    assert_eq!(collision.existing, "README.md");
+    let err = service
+        .check_name_collision(&dir.join("Readme.md"), None, true)
+        .await
+        .unwrap_err();
+    assert!(err.to_string().contains("differs only in case"), "{err}");
+
+    let collision = service
+        .check_name_collision(&dir.join("cafe\u{301}.txt"), None, false)
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(collision.kind, NameCollisionKind::UnicodeNormalization);
+
+    for name in ["notes.md", "README.md", "caf\u{e9}.txt"] {
+        let collision = service
+            .check_name_collision(&dir.join(name), None, true)
+            .await
+            .unwrap();
+        assert_eq!(collision, None, "{name}");
+    }
+    // Renaming a file to change the case of its name does not collide with itself
+    let collision = service
+        .check_name_collision(&dir.join("Readme.md"), Some(&dir.join("README.md")), true)
+        .await
+        .unwrap();
+    assert_eq!(collision, None);
+}
+
+#[tokio::test]
+async fn test_sort_file_alphabetical_and_unique() {
+    let (temp_dir, service, _allowed_dirs) = setup_service(vec!["dir1".to_string()]);
+    let file = create_temp_file(
+        &temp_dir.join("dir1"),
+        ".gitignore",
+        "target/\n*.log\nnode_modules/\n*.log\n.env\n",
+    );
+
+    let sorted = SortOptions::default();
+    let output = service.sort_file(&file, sorted, true).await.unwrap();
+    assert!(output.starts_with("```diff"), "{output}");
+    assert!(
+        output.ends_with("Dry run: the file was not changed.\n"),
+        "{output}"
+    );
+    assert_eq!(
+        fs::read_to_string(&file).unwrap(),
+        "target/\n*.log\nnode_modules/\n*.log\n.env\n"
+    );
+
+    service.sort_file(&file, sorted, false).await.unwrap();
+    assert_eq!(
+        fs::read_to_string(&file).unwrap(),
+        "*.log\n*.log\n.env\nnode_modules/\ntarget/\n"
+    );
+
+    let unique = SortOptions {
+        unique: true,
+        ..Default::default()
+    };
+    let output = service.sort_file(&file, unique, false).await.unwrap();
+    assert!(output.contains("4 lines, 1 duplicate removed."), "{output}");
+    assert_eq!(
+        fs::read_to_string(&file).unwrap(),
+        "*.log\n.env\nnode_modules/\ntarget/\n"
+    );
+    let output = service.sort_file(&file, unique, false).await.unwrap();
+    assert!(output.ends_with("The file is already sorted; nothing was written.\n"));
+}
+
+#[cfg(unix)]
+#[tokio::test]
+async fn test_sort_file_refuses_symlinks() {
+    let (temp_dir, service, _allowed_dirs) = setup_service(vec!["dir1".to_string()]);
+    let dir = temp_dir.join("dir1");
+    let target = create_temp_file(&dir, "names.txt", "b\na\n");
+    let link = dir.join("link.txt");
+    std::os::unix::fs::symlink(&target, &link).unwrap();
+
+    let err = service
+        .sort_file(&link, SortOptions::default(), false)
+        .await
+        .unwrap_err();
+    assert!(err.to_string().contains("symbolic links are not rewritten"));
+    assert!(fs::symlink_metadata(&link).unwrap().is_symlink());
+    assert_eq!(fs::read_to_string(&target).unwrap(), "b\na\n");
+}
+
+#[tokio::test]
+async fn test_sort_file_numeric_and_reverse() {
+    let (temp_dir, service, _allowed_dirs) = setup_service(vec!["dir1".to_string()]);
+    let file = create_temp_file(
+        &temp_dir.join("dir1"),
+        "sizes.txt",
+        "10 kb\r\n9 kb\r\n-2.5 kb\r\nunknown\r\n100 kb",
+    );
+
+    let numeric = SortOptions {
+        numeric: true,
+        ..Default::default()
+    };
+    service.sort_file(&file, numeric, false).await.unwrap();
+    // Byte order would put "100 kb" before "9 kb"; line endings are kept, with no final break
+    assert_eq!(
+        fs::read_to_string(&file).unwrap(),
+        "unknown\r\n-2.5 kb\r\n9 kb\r\n10 kb\r\n100 kb"
+    );
+
+    let reverse = SortOptions {
+        numeric: true,
+        reverse: true,
+        ..Default::default()
+    };
+    let output = service.sort_file(&file, reverse, false).await.unwrap();
+    assert!(
+        output.contains("5 lines, numerically, in reverse."),
+        "{output}"
+    );
+    assert_eq!(
+        fs::read_to_string(&file).unwrap(),
+        "100 kb\r\n10 kb\r\n9 kb\r\n-2.5 kb\r\nunknown"
+    );
+
+    let reverse = SortOptions {
+        reverse: true,
+        ..Default::default()
+    };
+    service.sort_file(&file, reverse, false).await.unwrap();
+    assert_eq!(
+        fs::read_to_string(&file).unwrap(),
+        "unknown\r\n9 kb\r\n100 kb\r\n10 kb\r\n-2.5 kb"
+    );
+}
+
+#[tokio::test]
+async fn test_preview_glob_matches_bulk_insert_selection() {
+    let (temp_dir, service, _allowed) = setup_service(vec!["dir1".to_string()]);
+    let root = temp_dir.join("dir1");
+    create_temp_file(&root.join("src"), "lib.rs", "pub mod net;\n");
+    create_temp_file(&root.join("src/net"), "client.rs", "pub fn get() {}\n");
+    create_temp_file(&root.join("src/net"), "notes.md", "# Notes\n");
+    create_temp_file(&root.join("src/generated"), "schema.rs", "// generated\n");
+    create_temp_file(&root, "build.rs", "fn main() {}\n");
+    fs::create_dir_all(root.join("src/empty.rs")).unwrap();
+
+    for (pattern, exclude) in [
+        ("src/**/*.rs", vec!["**/generated/**".to_string()]),
+        ("*.rs", vec![]),
+        ("client*", vec![]),
+    ] {
+        let preview = service
+            .preview_glob(&root, pattern, exclude.clone(), 10)
+            .await
+            .unwrap();
+        let report = service
+            .bulk_insert(&root, pattern, exclude, "start", "// header\n", None, true)
+            .await
+            .unwrap();
+        let acted_on: Vec<_> = report.files.into_iter().map(|file| file.path).collect();
+        assert_eq!(preview.files, acted_on, "{pattern}");
+    }
+
+    let preview = service
+        .preview_glob(&root, "src/**/*.rs", vec!["**/generated/**".to_string()], 1)
+        .await
+        .unwrap();
+    assert_eq!(
+        preview.files,
+        vec![root.join("src/lib.rs"), root.join("src/net/client.rs")]
+    );
+    let text = preview.render_text();
+    assert!(
+        text.starts_with("'src/**/*.rs' matches 2 files, matched against paths relative to '"),
+        "{text}"
+    );
+    assert!(
+        text.ends_with("\n  src/lib.rs\n  ... and 1 more\n"),
+        "{text}"
+    );
+
+    let err = service
+        .preview_glob(&root, "src/[a-z.rs", vec![], 10)
+        .await
+        .unwrap_err();
+    assert!(
+        err.to_string().starts_with("Invalid glob 'src/[a-z.rs'"),
+        "{err}"
+    );
+}
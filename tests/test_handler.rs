@@ -0,0 +1,712 @@
+#[path = "common/common.rs"]
+pub mod common;
+
+use common::{create_temp_file, get_temp_dir, parse_args};
+use rust_mcp_filesystem::ServerBuilder;
+use rust_mcp_filesystem::fs_service::instrumentation::{
+    OPERATION_FOOTER_PREFIX, TIMINGS_FOOTER_PREFIX,
+};
+use rust_mcp_filesystem::handler::{
+    CORRELATION_ID_ARG, FileSystemHandler, INCLUDE_TIMINGS_ARG, READ_ONLY_META,
+};
+use rust_mcp_filesystem::tool_defaults::ToolDefaults;
+use rust_mcp_filesystem::tools::FileSystemTools;
+use rust_mcp_sdk::schema::{
+    CallToolRequestParams, CallToolResult, ContentBlock, schema_utils::CallToolError,
+};
+use serde_json::{Value, json};
+use std::collections::HashMap;
+use std::path::Path;
+
+fn setup_handler(extra_args: &[&str]) -> (std::path::PathBuf, FileSystemHandler) {
+    let temp_dir = get_temp_dir();
+    let dir = temp_dir.to_str().unwrap().to_string();
+    let mut args = vec!["mcp-server"];
+    args.extend_from_slice(extra_args);
+    args.push(&dir);
+    let handler = FileSystemHandler::new(&parse_args(&args).unwrap()).unwrap();
+    (temp_dir, handler)
+}
+
+fn create_nested_tree(root: &Path) {
+    create_temp_file(root, "a.txt", "alpha");
+    create_temp_file(&root.join("sub"), "b.txt", "beta");
+    create_temp_file(&root.join("sub").join("deeper"), "c.txt", "gamma");
+}
+
+fn call_params(name: &str, arguments: Value) -> CallToolRequestParams {
+    CallToolRequestParams {
+        name: name.to_string(),
+        arguments: arguments.as_object().cloned(),
+    }
+}
+
+fn timings_footer(result: &CallToolResult) -> Option<HashMap<String, String>> {
+    let ContentBlock::TextContent(text) = result.content.last()? else {
+        return None;
+    };
+    let fields = text.text.strip_prefix(TIMINGS_FOOTER_PREFIX)?;
+    Some(
+        fields
+            .split_whitespace()
+            .filter_map(|pair| pair.split_once('='))
+            .map(|(key, value)| (key.to_string(), value.to_string()))
+            .collect(),
+    )
+}
+
+#[tokio::test]
+async fn test_timings_footer_absent_by_default() {
+    let (temp_dir, handler) = setup_handler(&[]);
+    create_nested_tree(&temp_dir);
+
+    let result = handler
+        .call_tool(call_params(
+            "directory_tree",
+            json!({ "path": temp_dir.to_str().unwrap() }),
+        ))
+        .await
+        .unwrap();
+
+    // the tree and its summary
+    assert_eq!(result.content.len(), 2);
+    assert!(timings_footer(&result).is_none());
+}
+
+#[tokio::test]
+async fn test_timings_footer_for_recursive_listing() {
+    let (temp_dir, handler) = setup_handler(&[]);
+    create_nested_tree(&temp_dir);
+
+    let result = handler
+        .call_tool(call_params(
+            "directory_tree",
+            json!({ "path": temp_dir.to_str().unwrap(), INCLUDE_TIMINGS_ARG: true }),
+        ))
+        .await
+        .unwrap();
+
+    assert_eq!(result.content.len(), 3);
+    let footer = timings_footer(&result).expect("timings footer");
+    assert!(footer["elapsed_ms"].parse::<f64>().is_ok());
+    assert_eq!(footer["entries_visited"], "5");
+    assert_eq!(footer["bytes_read"], "0");
+    assert_eq!(footer["bytes_written"], "0");
+    assert_eq!(footer["fast_paths"], "none");
+}
+
+#[tokio::test]
+async fn test_timings_footer_reports_bytes_and_fast_paths() {
+    let (temp_dir, handler) = setup_handler(&[]);
+    create_nested_tree(&temp_dir);
+
+    let result = handler
+        .call_tool(call_params(
+            "read_text_file",
+            json!({ "path": temp_dir.join("a.txt"), INCLUDE_TIMINGS_ARG: true }),
+        ))
+        .await
+        .unwrap();
+    let footer = timings_footer(&result).expect("timings footer");
+    assert_eq!(footer["bytes_read"], "5");
+
+    let result = handler
+        .call_tool(call_params(
+            "calculate_directory_size",
+            json!({ "root_path": temp_dir.to_str().unwrap(), INCLUDE_TIMINGS_ARG: true }),
+        ))
+        .await
+        .unwrap();
+    let footer = timings_footer(&result).expect("timings footer");
+    assert_ne!(footer["entries_visited"], "0");
+    assert_eq!(footer["fast_paths"], "parallel");
+}
+
+#[tokio::test]
+async fn test_timings_footer_enabled_by_config() {
+    let (temp_dir, handler) = setup_handler(&["--result-timings"]);
+    create_nested_tree(&temp_dir);
+
+    let result = handler
+        .call_tool(call_params(
+            "directory_tree",
+            json!({ "path": temp_dir.to_str().unwrap() }),
+        ))
+        .await
+        .unwrap();
+    assert!(timings_footer(&result).is_some());
+}
+
+#[tokio::test]
+async fn test_tools_advertise_include_timings() {
+    let (_temp_dir, handler) = setup_handler(&[]);
+    for tool in handler.tools() {
+        let properties = tool.input_schema.properties.unwrap();
+        assert!(
+            properties.contains_key(INCLUDE_TIMINGS_ARG),
+            "{}",
+            tool.name
+        );
+    }
+}
+
+/// The message of a tool call error, without the operation id appended to every error.
+fn error_message(err: &CallToolError) -> String {
+    let message = err.to_string();
+    let (message, ids) = message
+        .rsplit_once(" (operation_id=")
+        .expect("error message with an operation id");
+    assert_eq!(ids.len(), 27, "{ids}");
+    message.to_string()
+}
+
+fn operation_footer(result: &CallToolResult) -> Option<HashMap<String, String>> {
+    let ContentBlock::TextContent(text) = result.content.last()? else {
+        return None;
+    };
+    let fields = text.text.strip_prefix(OPERATION_FOOTER_PREFIX)?;
+    Some(
+        fields
+            .split_whitespace()
+            .filter_map(|pair| pair.split_once('='))
+            .map(|(key, value)| (key.to_string(), value.to_string()))
+            .collect(),
+    )
+}
+
+#[tokio::test]
+async fn test_operation_ids_in_footers_and_errors() {
+    let (temp_dir, handler) = setup_handler(&[]);
+    create_nested_tree(&temp_dir);
+    let path = temp_dir.join("a.txt");
+
+    // No footer unless asked for
+    let result = handler
+        .call_tool(call_params("read_text_file", json!({ "path": path })))
+        .await
+        .unwrap();
+    assert_eq!(result.content.len(), 1);
+
+    let result = handler
+        .call_tool(call_params(
+            "read_text_file",
+            json!({ "path": path, CORRELATION_ID_ARG: "step 7" }),
+        ))
+        .await
+        .unwrap();
+    let footer = operation_footer(&result).expect("operation footer");
+    assert_eq!(footer["correlation_id"], "step_7");
+    let first_id = footer["operation_id"].clone();
+    assert_eq!(first_id.len(), 26);
+
+    // The timings footer carries the ids of the same call, and every call gets a new id
+    let result = handler
+        .call_tool(call_params(
+            "read_text_file",
+            json!({ "path": path, INCLUDE_TIMINGS_ARG: true, CORRELATION_ID_ARG: "step-8" }),
+        ))
+        .await
+        .unwrap();
+    let footer = timings_footer(&result).expect("timings footer");
+    assert_eq!(footer["correlation_id"], "step-8");
+    assert_ne!(footer["operation_id"], first_id);
+
+    let err = handler
+        .call_tool(call_params(
+            "read_text_file",
+            json!({ "path": temp_dir.join("missing.txt"), CORRELATION_ID_ARG: "step-9" }),
+        ))
+        .await
+        .unwrap_err();
+    let message = err.to_string();
+    assert!(message.contains("operation_id="), "{message}");
+    assert!(message.ends_with("correlation_id=step-9)"), "{message}");
+}
+
+fn enum_values(handler: &FileSystemHandler, tool_name: &str, field: &str) -> Vec<Value> {
+    let tool = handler
+        .tools()
+        .into_iter()
+        .find(|tool| tool.name == tool_name)
+        .unwrap();
+    let properties = tool.input_schema.properties.unwrap();
+    properties[field]["enum"].as_array().unwrap().clone()
+}
+
+#[tokio::test]
+async fn test_output_format_enum_schema_and_error() {
+    let (temp_dir, handler) = setup_handler(&[]);
+    for tool_name in ["find_duplicate_files", "find_empty_directories"] {
+        assert_eq!(
+            enum_values(&handler, tool_name, "output_format"),
+            vec![json!("text"), json!("json")]
+        );
+    }
+
+    let err = handler
+        .call_tool(call_params(
+            "find_empty_directories",
+            json!({ "path": temp_dir.to_str().unwrap(), "output_format": "yaml" }),
+        ))
+        .await
+        .unwrap_err();
+    assert_eq!(
+        error_message(&err),
+        r#"Invalid value "yaml" for field "output_format", expected one of [text, json]"#
+    );
+}
+
+#[tokio::test]
+async fn test_file_size_output_format_enum_schema_and_error() {
+    let (temp_dir, handler) = setup_handler(&[]);
+    assert_eq!(
+        enum_values(&handler, "calculate_directory_size", "output_format"),
+        vec![json!("human-readable"), json!("bytes")]
+    );
+
+    let err = handler
+        .call_tool(call_params(
+            "calculate_directory_size",
+            json!({ "root_path": temp_dir.to_str().unwrap(), "output_format": 42 }),
+        ))
+        .await
+        .unwrap_err();
+    assert_eq!(
+        error_message(&err),
+        r#"Invalid value 42 for field "output_format", expected one of [human-readable, bytes]"#
+    );
+}
+
+#[tokio::test]
+async fn test_nested_enum_schema_and_error() {
+    let (temp_dir, handler) = setup_handler(&[]);
+    let tool = handler
+        .tools()
+        .into_iter()
+        .find(|tool| tool.name == "edit_structured_file")
+        .unwrap();
+    let properties = tool.input_schema.properties.unwrap();
+    assert_eq!(
+        properties["edits"]["items"]["properties"]["op"]["enum"],
+        json!(["set", "remove", "append"])
+    );
+
+    let file = create_temp_file(&temp_dir, "config.json", "{}");
+    let err = handler
+        .call_tool(call_params(
+            "edit_structured_file",
+            json!({
+                "path": file.to_str().unwrap(),
+                "edits": [
+                    { "op": "set", "pointer": "/a", "value": 1 },
+                    { "op": "rename", "pointer": "/a" },
+                ],
+            }),
+        ))
+        .await
+        .unwrap_err();
+    assert_eq!(
+        error_message(&err),
+        r#"Invalid value "rename" for field "edits[1].op", expected one of [set, remove, append]"#
+    );
+}
+
+#[tokio::test]
+async fn test_tool_calls_rejected_after_shutdown() {
+    let (temp_dir, handler) = setup_handler(&[]);
+    handler
+        .fs_service()
+        .shutdown(std::time::Duration::from_millis(10))
+        .await;
+
+    let err = handler
+        .call_tool(call_params(
+            "directory_tree",
+            json!({ "path": temp_dir.to_str().unwrap() }),
+        ))
+        .await
+        .unwrap_err();
+    assert_eq!(err.to_string(), "Server shutting down");
+}
+
+#[tokio::test]
+async fn test_server_builder_end_to_end() {
+    let temp_dir = get_temp_dir();
+    let server = ServerBuilder::new()
+        .allowed_dirs([temp_dir.to_str().unwrap()])
+        .read_only(false)
+        .disable_tools(["zip_directory"])
+        .build()
+        .unwrap();
+    let handler = server.handler();
+
+    let tool_names: Vec<String> = handler.tools().into_iter().map(|tool| tool.name).collect();
+    assert!(tool_names.contains(&"write_file".to_string()));
+    assert!(!tool_names.contains(&"zip_directory".to_string()));
+
+    let file = temp_dir.join("embedded.txt");
+    handler
+        .call_tool(call_params(
+            "write_file",
+            json!({ "path": file, "content": "hello from the builder" }),
+        ))
+        .await
+        .unwrap();
+    let result = handler
+        .call_tool(call_params("read_text_file", json!({ "path": file })))
+        .await
+        .unwrap();
+    let ContentBlock::TextContent(text) = result.content.first().unwrap() else {
+        panic!("Expected TextContent result");
+    };
+    assert_eq!(text.text, "hello from the builder");
+
+    let err = handler
+        .call_tool(call_params(
+            "zip_directory",
+            json!({ "input_directory": temp_dir, "target_zip_file": temp_dir.join("out.zip") }),
+        ))
+        .await
+        .unwrap_err();
+    assert_eq!(error_message(&err), "Tool 'zip_directory' is disabled.");
+
+    let result = handler
+        .fs_service()
+        .shutdown(std::time::Duration::from_secs(1))
+        .await;
+    assert_eq!(result.timed_out_operations, 0);
+}
+
+#[test]
+fn test_server_builder_rejects_unknown_disabled_tool() {
+    let err = ServerBuilder::new()
+        .disable_tools(["no_such_tool"])
+        .build()
+        .err()
+        .unwrap();
+    assert_eq!(
+        err.to_string(),
+        "Cannot disable unknown tool 'no_such_tool'."
+    );
+}
+
+#[tokio::test]
+async fn test_server_builder_read_only_by_default() {
+    let temp_dir = get_temp_dir();
+    let server = ServerBuilder::new()
+        .allowed_dirs([temp_dir.to_str().unwrap()])
+        .build()
+        .unwrap();
+    let result = server
+        .handler()
+        .call_tool(call_params(
+            "write_file",
+            json!({ "path": temp_dir.join("file.txt"), "content": "x" }),
+        ))
+        .await;
+    assert!(result.is_err());
+}
+
+#[tokio::test]
+async fn test_session_write_usage_footer() {
+    let (temp_dir, handler) = setup_handler(&["--allow-write", "--max-session-write-bytes", "100"]);
+    let path = temp_dir.join("out.txt");
+
+    let result = handler
+        .call_tool(call_params(
+            "write_file",
+            json!({ "path": path.to_str().unwrap(), "content": "hello" }),
+        ))
+        .await
+        .unwrap();
+    let Some(ContentBlock::TextContent(footer)) = result.content.last() else {
+        panic!("missing footer");
+    };
+    assert_eq!(
+        footer.text,
+        "Session writes: 5 of 100 bytes written, 0 bytes removed."
+    );
+
+    // Read tools do not report the budget
+    let result = handler
+        .call_tool(call_params(
+            "read_text_file",
+            json!({ "path": path.to_str().unwrap() }),
+        ))
+        .await
+        .unwrap();
+    assert_eq!(result.content.len(), 1);
+}
+
+fn result_text(result: &CallToolResult) -> &str {
+    match &result.content[0] {
+        ContentBlock::TextContent(text) => &text.text,
+        _ => panic!("expected text content"),
+    }
+}
+
+#[tokio::test]
+async fn test_tool_defaults_from_config_file() {
+    let config_dir = get_temp_dir();
+    let config = create_temp_file(
+        &config_dir,
+        "config.toml",
+        "[tool_defaults.directory_tree]\nmax_depth = 1\n",
+    );
+    let (temp_dir, handler) = setup_handler(&["--config", config.to_str().unwrap()]);
+    create_nested_tree(&temp_dir);
+    assert!(handler.config_warnings().is_empty());
+
+    // The default is advertised in the argument's schema
+    let tool = handler
+        .tools()
+        .into_iter()
+        .find(|tool| tool.name == "directory_tree")
+        .unwrap();
+    assert_eq!(
+        tool.input_schema.properties.unwrap()["max_depth"]["default"],
+        json!(1)
+    );
+
+    // It applies when the argument is omitted...
+    let result = handler
+        .call_tool(call_params(
+            "directory_tree",
+            json!({ "path": temp_dir.to_str().unwrap() }),
+        ))
+        .await
+        .unwrap();
+    assert!(!result_text(&result).contains("b.txt"));
+    assert!(result_text(&result).contains("sub"));
+
+    // ...but a value passed with the call wins
+    let result = handler
+        .call_tool(call_params(
+            "directory_tree",
+            json!({ "path": temp_dir.to_str().unwrap(), "max_depth": 3 }),
+        ))
+        .await
+        .unwrap();
+    assert!(result_text(&result).contains("c.txt"));
+}
+
+#[test]
+fn test_tool_defaults_unknown_entries_warn() {
+    let config_dir = get_temp_dir();
+    let config = create_temp_file(
+        &config_dir,
+        "config.toml",
+        concat!(
+            "[tool_defaults.no_such_tool]\nlimit = 1\n\n",
+            "[tool_defaults.directory_tree]\nno_such_argument = true\npath = \"/tmp\"\n",
+            "max_depth = \"deep\"\ndirs_first = true\n",
+        ),
+    );
+    let (_temp_dir, handler) = setup_handler(&["--config", config.to_str().unwrap()]);
+
+    let warnings = handler.config_warnings();
+    assert_eq!(warnings.len(), 4, "{warnings:?}");
+    assert!(
+        warnings
+            .iter()
+            .any(|warning| warning.contains("'no_such_tool'"))
+    );
+    assert!(
+        warnings
+            .iter()
+            .any(|warning| warning.contains("'directory_tree.path': the argument is required"))
+    );
+    assert!(warnings.iter().any(|warning| {
+        warning.contains("'directory_tree.max_depth': expected a value of type 'integer'")
+    }));
+
+    // Valid defaults are kept
+    let tool = handler
+        .tools()
+        .into_iter()
+        .find(|tool| tool.name == "directory_tree")
+        .unwrap();
+    assert_eq!(
+        tool.input_schema.properties.unwrap()["dirs_first"]["default"],
+        json!(true)
+    );
+
+    // A malformed file fails startup
+    let config = create_temp_file(&config_dir, "broken.toml", "[tool_defaults\n");
+    let args = vec![
+        "mcp-server",
+        "--config",
+        config.to_str().unwrap(),
+        config_dir.to_str().unwrap(),
+    ];
+    let err = FileSystemHandler::new(&parse_args(&args).unwrap())
+        .err()
+        .unwrap();
+    assert!(err.to_string().contains("Invalid configuration file"));
+}
+
+#[tokio::test]
+async fn test_server_builder_tool_defaults() {
+    let temp_dir = get_temp_dir();
+    create_nested_tree(&temp_dir);
+    let server = ServerBuilder::new()
+        .allowed_dirs([temp_dir.to_str().unwrap()])
+        .tool_defaults(ToolDefaults::default().with_default(
+            "directory_tree",
+            "max_depth",
+            json!(1),
+        ))
+        .build()
+        .unwrap();
+
+    let result = server
+        .handler()
+        .call_tool(call_params(
+            "directory_tree",
+            json!({ "path": temp_dir.to_str().unwrap(), "max_depth": null }),
+        ))
+        .await
+        .unwrap();
+    assert!(!result_text(&result).contains("b.txt"));
+}
+
+#[test]
+fn test_tool_hints() {
+    // (destructive, idempotent, read-only) of every tool that changes the file system
+    let expected = [
+        ("bulk_insert", false, true),
+        ("close_file", true, false),
+        ("concat_files", true, true),
+        ("conditional_write", true, false),
+        ("convert_file", true, true),
+        ("create_directory", false, true),
+        ("delete_path", true, false),
+        ("edit_file", true, false),
+        ("edit_structured_file", true, false),
+        ("flatten_directory", false, true),
+        ("move_file", false, false),
+        ("open_file", false, false),
+        ("prune_empty_directories", true, true),
+        ("sort_file", true, true),
+        ("unzip_file", false, false),
+        ("write_file", true, true),
+        ("zip_directory", true, true),
+        ("zip_files", true, true),
+    ];
+    let mut writers = vec![];
+    for tool in FileSystemTools::tools() {
+        let annotations = tool
+            .annotations
+            .unwrap_or_else(|| panic!("{} has no annotations", tool.name));
+        assert_eq!(annotations.open_world_hint, Some(false), "{}", tool.name);
+        match annotations.read_only_hint {
+            Some(true) => {}
+            Some(false) => writers.push((
+                tool.name.clone(),
+                annotations.destructive_hint.unwrap(),
+                annotations.idempotent_hint.unwrap(),
+            )),
+            None => panic!("{} has no read-only hint", tool.name),
+        }
+    }
+    writers.sort();
+    let expected: Vec<_> = expected
+        .iter()
+        .map(|(name, destructive, idempotent)| (name.to_string(), *destructive, *idempotent))
+        .collect();
+    assert_eq!(writers, expected);
+}
+
+#[tokio::test]
+async fn test_dry_run_allowed_in_read_only_mode() {
+    let (temp_dir, handler) = setup_handler(&[]);
+    let file = create_temp_file(&temp_dir, "notes.txt", "old line\n");
+    let edit = |dry_run: bool| {
+        call_params(
+            "edit_file",
+            json!({
+                "path": file.to_str().unwrap(),
+                "edits": [{ "oldText": "old line", "newText": "new line" }],
+                "dryRun": dry_run,
+            }),
+        )
+    };
+
+    let result = handler.call_tool(edit(true)).await.unwrap();
+    assert_ne!(result.is_error, Some(true));
+    assert_eq!(result.meta.unwrap()[READ_ONLY_META], json!(true));
+    assert_eq!(std::fs::read_to_string(&file).unwrap(), "old line\n");
+
+    let err = handler.call_tool(edit(false)).await.unwrap_err();
+    assert!(err.to_string().contains("read-only mode"), "{err}");
+    assert_eq!(std::fs::read_to_string(&file).unwrap(), "old line\n");
+
+    // Results of real runs carry no read-only annotation
+    let (temp_dir, handler) = setup_handler(&["--allow-write"]);
+    let file = create_temp_file(&temp_dir, "notes.txt", "old line\n");
+    let result = handler
+        .call_tool(call_params(
+            "edit_file",
+            json!({
+                "path": file.to_str().unwrap(),
+                "edits": [{ "oldText": "old line", "newText": "new line" }],
+            }),
+        ))
+        .await
+        .unwrap();
+    assert!(
+        result
+            .meta
+            .is_none_or(|meta| !meta.contains_key(READ_ONLY_META))
+    );
+}
+
+#[tokio::test]
+async fn test_unknown_arguments() {
+    let (temp_dir, handler) = setup_handler(&[]);
+    create_nested_tree(&temp_dir);
+    let read_directory = |dir: &Path| {
+        call_params(
+            "read_directory",
+            json!({ "path": dir.to_str().unwrap(), "recurse": true }),
+        )
+    };
+
+    let err = handler
+        .call_tool(read_directory(&temp_dir))
+        .await
+        .unwrap_err();
+    let message = err.to_string();
+    assert!(
+        message.contains(
+            "Unknown argument for tool 'read_directory': 'recurse' (did you mean 'recursive'?)."
+        ),
+        "{message}"
+    );
+    assert!(message.contains("Accepted arguments: "), "{message}");
+    assert!(message.contains("path, recursive"), "{message}");
+
+    // Lenient mode runs the call with its defaults and says what was ignored
+    let (temp_dir, handler) = setup_handler(&["--strict-params", "false"]);
+    create_nested_tree(&temp_dir);
+    let result = handler.call_tool(read_directory(&temp_dir)).await.unwrap();
+    assert_ne!(result.is_error, Some(true));
+    let ContentBlock::TextContent(warning) = result.content.last().unwrap() else {
+        panic!("expected text content");
+    };
+    assert_eq!(
+        warning.text,
+        "Warning: ignored unknown argument: 'recurse' (did you mean 'recursive'?)."
+    );
+
+    // Per-call arguments shared by every tool are known
+    let (temp_dir, handler) = setup_handler(&[]);
+    create_nested_tree(&temp_dir);
+    handler
+        .call_tool(call_params(
+            "read_directory",
+            json!({ "path": temp_dir.to_str().unwrap(), "correlation_id": "abc" }),
+        ))
+        .await
+        .unwrap();
+}